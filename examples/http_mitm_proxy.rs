@@ -3,13 +3,17 @@
 //! Note that this MITM proxy is not production ready, and is only meant
 //! to show you how one might start. You might want to address the following:
 //!
-//! - Load in your tls mitm cert/key pair from file or ACME
-//! - Make sure your clients trust the MITM cert
+//! - Load in your tls mitm CA cert/key pair from file
+//! - Make sure your clients trust the MITM CA cert
 //! - Do not enforce the Application protocol and instead convert requests when needed,
 //!   e.g. in this example we _always_ map the protocol between two ends,
 //!   even though it might be better to be able to map bidirectionaly between http versions
 //! - ... and much more
 //!
+//! The leaf certificates presented to the clients are issued on the fly
+//! for the requested server name (SNI), signed by the (generated) MITM CA.
+//! These mirror the subject alternative names of the certificate of the upstream server.
+//!
 //! That said for basic usage it does work and should at least give you an idea on how to get started.
 //!
 //! It combines concepts that can seen in action separately in the following examples:
@@ -31,11 +35,19 @@
 //! curl -v -x http://127.0.0.1:62017 --proxy-user 'john:secret' http://www.example.com/
 //! curl -k -v -x http://127.0.0.1:62017 --proxy-user 'john:secret' https://www.example.com/
 //! ```
+//!
+//! WebSocket upgrades are intercepted as well, after which the (ws) messages
+//! exchanged between the client and the server are logged as they are relayed.
+//!
+//! Set the `SSLKEYLOGFILE` environment variable to write the key log of both
+//! the intercepted and the upstream TLS connections to that file,
+//! such that the traffic can be decrypted using Wireshark.
 
 use rama::{
     error::{BoxError, ErrorContext, OpaqueError},
     http::{
         client::HttpClient,
+        header::{PROXY_AUTHORIZATION, SEC_WEBSOCKET_PROTOCOL},
         layer::{
             map_response_body::MapResponseBodyLayer,
             proxy_auth::ProxyAuthLayer,
@@ -47,34 +59,54 @@ use rama::{
         },
         matcher::MethodMatcher,
         server::HttpServer,
+        ws::{
+            relay, Message, WebSocket, WebSocketAcceptor, WebSocketConfig, WebSocketConnector,
+            WebSocketMatcher, WebSocketService,
+        },
         Body, IntoResponse, Request, Response, StatusCode,
     },
     layer::ConsumeErrLayer,
-    net::http::RequestContext,
     net::stream::layer::http::BodyLimitLayer,
     net::user::Basic,
+    net::{
+        address::Authority,
+        client::{ConnectorService, EstablishedClientConnection},
+        http::RequestContext,
+        tls::client::NegotiatedTlsParameters,
+    },
     rt::Executor,
     service::service_fn,
-    tcp::server::TcpListener,
+    tcp::{
+        client::{service::TcpConnector, Request as TcpRequest},
+        server::TcpListener,
+    },
     tls::{
         dep::rcgen::{self, KeyPair},
+        issuer::{CertIssuer, UpstreamCert},
+        keylog::KeyLogIntent,
         rustls::{
+            client::HttpsConnector,
             dep::{
                 pki_types::{CertificateDer, PrivatePkcs8KeyDer},
                 rustls::ServerConfig,
             },
-            server::TlsAcceptorLayer,
+            server::{CertIssuerProvider, TlsAcceptorLayer, TlsClientConfigHandler},
         },
     },
     Layer, Service,
 };
-use std::{convert::Infallible, sync::Arc, time::Duration};
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 #[derive(Debug, Clone)]
 struct State {
     mitm_tls_config: Arc<ServerConfig>,
+    mitm_cert_issuer_provider: CertIssuerProvider,
 }
 
 type Context = rama::Context<State>;
@@ -90,12 +122,17 @@ async fn main() -> Result<(), BoxError> {
         )
         .init();
 
-    let mitm_tls_config = Arc::new(
-        mitm_tls_server_credentials()
-            .map_err(OpaqueError::from_boxed)
-            .context("generate self-signed mitm tls cert")?,
-    );
-    let state = State { mitm_tls_config };
+    let (mitm_cert_issuer, mitm_tls_config) = mitm_tls_server_credentials()
+        .map_err(OpaqueError::from_boxed)
+        .context("generate self-signed mitm tls cert")?;
+    let mitm_tls_config = Arc::new(mitm_tls_config);
+    let state = State {
+        mitm_cert_issuer_provider: CertIssuerProvider::new(
+            mitm_cert_issuer,
+            mitm_tls_config.clone(),
+        ),
+        mitm_tls_config,
+    };
 
     let graceful = rama::graceful::Shutdown::default();
 
@@ -160,6 +197,17 @@ async fn http_connect_accept(
 }
 
 async fn http_connect_proxy(mut ctx: Context, upgraded: Upgraded) -> Result<(), Infallible> {
+    // fetch the certificate of the upstream server, such that the issued
+    // certificate is valid for the same names as the one it replaces,
+    // the tls acceptor finds it in the context of this connection
+    let upstream_cert = match ctx.get::<RequestContext>() {
+        Some(request_ctx) => fetch_upstream_cert(ctx.clone(), request_ctx.authority.clone()).await,
+        None => None,
+    };
+    if let Some(upstream_cert) = upstream_cert {
+        ctx.insert(upstream_cert);
+    }
+
     // delete request context as a new one should be made per seen request
     ctx.remove::<RequestContext>();
 
@@ -167,8 +215,13 @@ async fn http_connect_proxy(mut ctx: Context, upgraded: Upgraded) -> Result<(),
 
     let http_transport_service = HttpServer::auto(ctx.executor().clone()).service(http_service);
 
-    let https_service =
-        TlsAcceptorLayer::new(ctx.state().mitm_tls_config.clone()).layer(http_transport_service);
+    let https_service = TlsAcceptorLayer::with_client_config_handler(
+        ctx.state().mitm_tls_config.clone(),
+        TlsClientConfigHandler::new()
+            .server_config_provider(ctx.state().mitm_cert_issuer_provider.clone()),
+    )
+    .with_key_log_intent(KeyLogIntent::Environment)
+    .layer(http_transport_service);
 
     https_service
         .serve(ctx, upgraded)
@@ -178,12 +231,33 @@ async fn http_connect_proxy(mut ctx: Context, upgraded: Upgraded) -> Result<(),
     Ok(())
 }
 
+async fn fetch_upstream_cert(ctx: Context, authority: Authority) -> Option<UpstreamCert> {
+    let connector = HttpsConnector::secure_only(TcpConnector::new()).with_store_server_info(true);
+    match connector.connect(ctx, TcpRequest::new(authority)).await {
+        Ok(EstablishedClientConnection { ctx, .. }) => ctx
+            .get::<NegotiatedTlsParameters>()
+            .and_then(|params| params.server_certificate_chain.first().cloned())
+            .map(UpstreamCert),
+        Err(err) => {
+            tracing::debug!(error = %err, "failed to fetch upstream certificate");
+            None
+        }
+    }
+}
+
 fn new_http_mitm_proxy(
     exec: &Executor,
 ) -> impl Service<State, Request, Response = Response, Error = Infallible> {
     (
         MapResponseBodyLayer::new(Body::new),
         TraceLayer::new_for_http(),
+        // websocket upgrades are intercepted prior to removing the hop-by-hop headers,
+        // as these are required for the opening handshake
+        UpgradeLayer::new(
+            WebSocketMatcher::new(),
+            service_fn(ws_mitm_accept),
+            WebSocketService::new(service_fn(ws_mitm_relay)),
+        ),
         RemoveResponseHeaderLayer::hop_by_hop(),
         RemoveRequestHeaderLayer::hop_by_hop(),
         ConsumeErrLayer::default(),
@@ -201,7 +275,7 @@ async fn http_mitm_proxy(ctx: Context, req: Request) -> Result<Response, Infalli
 
     // NOTE: use a custom connector (layers) in case you wish to add custom features,
     // such as upstream proxies or other configurations
-    let client = HttpClient::default();
+    let client = HttpClient::default().with_key_log_intent(KeyLogIntent::Environment);
     match client.serve(ctx, req).await {
         Ok(resp) => Ok(resp),
         Err(err) => {
@@ -214,10 +288,84 @@ async fn http_mitm_proxy(ctx: Context, req: Request) -> Result<Response, Infalli
     }
 }
 
+/// The upstream [`WebSocket`], connected while accepting the upgrade of the client,
+/// such that it can be relayed to by [`ws_mitm_relay`].
+#[derive(Clone)]
+struct UpstreamWebSocket(Arc<Mutex<Option<WebSocket<Upgraded>>>>);
+
+fn ws_mitm_config() -> WebSocketConfig {
+    // pings are relayed, such that they are answered by the peers themselves
+    WebSocketConfig::new().with_auto_pong(false)
+}
+
+async fn ws_mitm_accept(
+    mut ctx: Context,
+    req: Request,
+) -> Result<(Response, Context, Request), Response> {
+    // connect to the server first, such that the client is accepted
+    // using the subprotocol selected by the server
+    let protocols: Vec<_> = req
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|protocol| !protocol.is_empty())
+        .map(ToOwned::to_owned)
+        .collect();
+
+    let mut upstream_req = Request::new(Body::empty());
+    *upstream_req.uri_mut() = req.uri().clone();
+    *upstream_req.headers_mut() = req.headers().clone();
+    upstream_req.headers_mut().remove(PROXY_AUTHORIZATION);
+
+    let connector = WebSocketConnector::new(
+        HttpClient::default().with_key_log_intent(KeyLogIntent::Environment),
+    )
+    .with_config(ws_mitm_config())
+    .with_protocols(protocols);
+    let upstream = match connector.serve(ctx.clone(), upstream_req).await {
+        Ok(upstream) => upstream,
+        Err(err) => {
+            tracing::error!(error = %err, "error connecting to upstream websocket");
+            return Err(StatusCode::BAD_GATEWAY.into_response());
+        }
+    };
+
+    let acceptor = WebSocketAcceptor::new()
+        .with_config(ws_mitm_config())
+        .with_protocols(upstream.protocol());
+    ctx.insert(UpstreamWebSocket(Arc::new(Mutex::new(Some(upstream)))));
+    acceptor.serve(ctx, req).await
+}
+
+async fn ws_mitm_relay(ctx: Context, mut client: WebSocket<Upgraded>) -> Result<(), OpaqueError> {
+    let mut server = ctx
+        .get::<UpstreamWebSocket>()
+        .and_then(|upstream| upstream.0.lock().unwrap().take())
+        .context("missing upstream websocket")?;
+
+    relay(&mut client, &mut server, |direction, message| {
+        // these logs are for example purposes only,
+        // best not to print messages like this in production...
+        let kind = match &message {
+            Message::Text(_) => "text",
+            Message::Binary(_) => "binary",
+            Message::Ping(_) => "ping",
+            Message::Pong(_) => "pong",
+            Message::Close(_) => "close",
+        };
+        tracing::info!(%direction, kind, len = message.len(), "relay websocket message");
+        Some(message)
+    })
+    .await
+}
+
 // NOTE: for a production service you ideally use
 // an issued TLS cert (if possible via ACME). Or at the very least
 // load it in from memory/file, so that your clients can install the certificate for trust.
-fn mitm_tls_server_credentials() -> Result<ServerConfig, BoxError> {
+fn mitm_tls_server_credentials() -> Result<(CertIssuer, ServerConfig), BoxError> {
     // Create an issuer CA cert.
     let alg = &rcgen::PKCS_ECDSA_P256_SHA256;
     let ca_key_pair = KeyPair::generate_for(alg).expect("generate ca key pair");
@@ -252,5 +400,9 @@ fn mitm_tls_server_credentials() -> Result<ServerConfig, BoxError> {
             PrivatePkcs8KeyDer::from(server_key_der.secret_pkcs8_der().to_owned()).into(),
         )?;
     tls_server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
-    Ok(tls_server_config)
+
+    // Issues the leaf certificates for the requested server names (SNI).
+    let cert_issuer = CertIssuer::new(ca_cert.der().to_vec(), ca_key_pair)?;
+
+    Ok((cert_issuer, tls_server_config))
 }
@@ -23,18 +23,19 @@
 //! curl -v http://127.0.0.1:62014/hello
 //! ```
 //!
-//! You should see a response with `HTTP/1.1 200 OK` and a body with `Hello, authorized client!`.
+//! You should see a response with `HTTP/1.1 200 OK` and a body with
+//! `Hello, authorized client CN=Example Client!`, the subject of the client certificate
+//! presented by the tunnel proxy, which is only authorized for that subject.
 
 // these dependencies are re-exported by rama for your convenience,
 // as to make it easy to use them and ensure that the versions remain compatible
 // (given most do not have a stable release yet)
 use rama::{
     tls::dep::rcgen::{self, KeyPair},
+    tls::keylog::KeyLogIntent,
     tls::rustls::dep::{
         pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName},
-        rustls::{
-            server::WebPkiClientVerifier, ClientConfig, KeyLogFile, RootCertStore, ServerConfig,
-        },
+        rustls::{server::WebPkiClientVerifier, ClientConfig, RootCertStore, ServerConfig},
         tokio_rustls::TlsConnector,
     },
 };
@@ -44,16 +45,16 @@ use rama::{
     error::BoxError,
     graceful::Shutdown,
     http::{
-        layer::trace::TraceLayer,
+        layer::{client_identity_auth::ClientIdentityAuthLayer, trace::TraceLayer},
         response::{Html, Redirect},
         server::HttpServer,
-        service::web::WebService,
+        service::web::{extract::ClientIdentity, WebService},
     },
     layer::TraceErrLayer,
     rt::Executor,
     service::service_fn,
     tcp::server::TcpListener,
-    tls::rustls::server::TlsAcceptorLayer,
+    tls::{rustls::server::TlsAcceptorLayer, types::server::ClientIdentityPattern},
     Context, Layer,
 };
 
@@ -66,6 +67,7 @@ use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 const SERVER_DOMAIN: &str = "127.0.0.1";
 const SERVER_ADDR: &str = "127.0.0.1:63014";
 const TUNNEL_ADDR: &str = "127.0.0.1:62014";
+const CLIENT_COMMON_NAME: &str = "Example Client";
 
 #[derive(Debug)]
 struct TunnelState {
@@ -115,11 +117,26 @@ async fn main() {
 
         let tcp_service = TlsAcceptorLayer::new(Arc::new(tls_server_config)).layer(
             HttpServer::auto(executor).service(
-                TraceLayer::new_for_http().layer(
-                    WebService::default()
-                        .get("/", Redirect::temporary("/hello"))
-                        .get("/hello", Html("<h1>Hello, authorized client!</h1>")),
-                ),
+                (
+                    TraceLayer::new_for_http(),
+                    // only allow clients with a trusted certificate for the expected subject
+                    ClientIdentityAuthLayer::new(ClientIdentityPattern::CommonName(
+                        CLIENT_COMMON_NAME.to_owned(),
+                    )),
+                )
+                    .layer(
+                        WebService::default()
+                            .get("/", Redirect::temporary("/hello"))
+                            .get(
+                                "/hello",
+                                |ClientIdentity(identity): ClientIdentity| async move {
+                                    Html(format!(
+                                        "<h1>Hello, authorized client {}!</h1>",
+                                        identity.certificate().subject()
+                                    ))
+                                },
+                            ),
+                    ),
             ),
         );
 
@@ -149,8 +166,11 @@ async fn main() {
             .expect("build mTLS client config");
 
         // support key logging
-        if std::env::var("SSLKEYLOGFILE").is_ok() {
-            client_config.key_log = Arc::new(KeyLogFile::new());
+        if let Some(writer) = KeyLogIntent::Environment
+            .writer()
+            .expect("open key log file")
+        {
+            client_config.key_log = Arc::new(writer);
         }
 
         let client_config = Arc::new(client_config);
@@ -179,6 +199,9 @@ fn generate_tls_cert_client() -> (CertificateDer<'static>, PrivatePkcs8KeyDer<'s
         .expect("create client EE Params");
     client_ee_params.is_ca = rcgen::IsCa::NoCa;
     client_ee_params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth];
+    client_ee_params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, CLIENT_COMMON_NAME);
     let client_cert = client_ee_params
         .self_signed(&client_key_pair)
         .expect("create client self-signed cert");
@@ -0,0 +1,74 @@
+//! An example to showcase how one can build an authenticated socks5 proxy server.
+//!
+//! The username labels are parsed using the [`UsernameOpaqueLabelParser`],
+//! in the same way as you would do for an http proxy using the `ProxyAuthLayer`.
+//!
+//! [`UsernameOpaqueLabelParser`]: rama::username::UsernameOpaqueLabelParser
+//!
+//! # Run the example
+//!
+//! ```sh
+//! cargo run --example socks5_connect_proxy --features=socks5
+//! ```
+//!
+//! # Expected output
+//!
+//! The server will start and listen on `:62021`. You can use `curl` to interact with the service:
+//!
+//! ```sh
+//! curl -v -x socks5://127.0.0.1:62021 --proxy-user 'john:secret' http://www.example.com/
+//! curl -v -x socks5h://127.0.0.1:62021 --proxy-user 'john-red-blue:secret' http://www.example.com/
+//! curl -v -x socks5h://127.0.0.1:62021 --proxy-user 'john:secret' https://www.example.com/
+//! ```
+//!
+//! You should see in all the above examples the responses from the server.
+
+use rama::{
+    layer::{TimeoutLayer, TraceErrLayer},
+    net::user::Basic,
+    proxy::socks5::server::Socks5Acceptor,
+    tcp::server::TcpListener,
+    username::UsernameOpaqueLabelParser,
+    Layer,
+};
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::DEBUG.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let graceful = rama::graceful::Shutdown::default();
+
+    graceful.spawn_task_fn(|guard| async {
+        TcpListener::bind("127.0.0.1:62021")
+            .await
+            .expect("bind TCP Listener")
+            .serve_graceful(
+                guard,
+                (
+                    TraceErrLayer::new(),
+                    TimeoutLayer::new(Duration::from_secs(300)),
+                )
+                    .layer(
+                        Socks5Acceptor::new()
+                            .with_auth(Basic::new("john", "secret"))
+                            .with_labels::<UsernameOpaqueLabelParser>(),
+                    ),
+            )
+            .await;
+    });
+
+    graceful
+        .shutdown_with_limit(Duration::from_secs(30))
+        .await
+        .expect("graceful shutdown");
+}
@@ -0,0 +1,162 @@
+//! This example demonstrates how to obtain (and keep renewing) a certificate
+//! for a rustls https server over ACME, e.g. from Let's Encrypt.
+//!
+//! The HTTP-01 challenges are responded to by a plain text http server,
+//! while the obtained certificates are swapped into the running https server
+//! using a live cert store, without any need to restart it.
+//!
+//! # Run the example
+//!
+//! Start a local [pebble](https://github.com/letsencrypt/pebble) ACME server,
+//! which validates the HTTP-01 challenges on port `5002`:
+//!
+//! ```sh
+//! docker run --rm --network host -e PEBBLE_VA_NOSLEEP=1 ghcr.io/letsencrypt/pebble
+//! ```
+//!
+//! And run the example:
+//!
+//! ```sh
+//! cargo run --example tls_acme --features=acme,http-full,rustls
+//! ```
+//!
+//! # Expected output
+//!
+//! The server will start and listen on `:63019`, presenting a self-signed certificate
+//! until the certificate for `localhost` is obtained from pebble.
+//! You can use `curl` to interact with the service:
+//!
+//! ```sh
+//! curl -k -v https://localhost:63019
+//! ```
+//!
+//! You should see a response with `HTTP/1.1 200 OK` and the body `Hello world!`,
+//! with the certificate of the server issued by the `Pebble Intermediate CA`.
+
+use rama::{
+    graceful::Shutdown,
+    http::{client::HttpClient, server::HttpServer, Request},
+    net::address::Domain,
+    rt::Executor,
+    service::service_fn,
+    tcp::server::TcpListener,
+    tls::{
+        acme::{AccountKey, AcmeClient, AcmeRenewer, Http01ChallengeResponder},
+        cert_store::cert_store_updater,
+        dep::rcgen::{self, KeyPair},
+        rustls::{
+            dep::{pki_types::PrivatePkcs8KeyDer, rustls::ServerConfig},
+            server::{CertStoreProvider, TlsAcceptorLayer, TlsClientConfigHandler},
+        },
+    },
+    Layer,
+};
+
+use std::{sync::Arc, time::Duration};
+use tracing::metadata::LevelFilter;
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+const PEBBLE_DIRECTORY: &str = "https://localhost:14000/dir";
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::DEBUG.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let shutdown = Shutdown::default();
+
+    let responder = Http01ChallengeResponder::new();
+    let (cert_store, cert_store_setter) = cert_store_updater();
+
+    // serve the HTTP-01 challenges on the port pebble validates them on
+    let http01_responder = responder.clone();
+    shutdown.spawn_task_fn(|guard| async move {
+        let exec = Executor::graceful(guard.clone());
+        HttpServer::auto(exec)
+            .listen_graceful(guard, "127.0.0.1:5002", http01_responder)
+            .await
+            .expect("serve http-01 challenges");
+    });
+
+    // obtain and renew the certificate for localhost
+    shutdown.spawn_task_fn(|guard| async move {
+        // the default http client does not verify the server certificate,
+        // which is fine for pebble but not recommended for a real ACME server
+        let client = AcmeClient::new(HttpClient::default(), PEBBLE_DIRECTORY)
+            .await
+            .expect("create acme client");
+        let account = client
+            .new_account(
+                AccountKey::generate().expect("generate account key"),
+                &["mailto:admin@example.com".to_owned()],
+            )
+            .await
+            .expect("create acme account");
+
+        let renewer = AcmeRenewer::new(
+            client,
+            account,
+            vec![Domain::from_static("localhost")],
+            responder,
+        )
+        .with_retry_interval(Duration::from_secs(10));
+
+        tokio::select! {
+            _ = renewer.run(cert_store_setter) => (),
+            _ = guard.cancelled() => (),
+        }
+    });
+
+    // serve https, using the obtained certificate once available
+    shutdown.spawn_task_fn(|guard| async move {
+        let tls_server_config = Arc::new(self_signed_server_config());
+        let tls_client_config_handler = TlsClientConfigHandler::default().server_config_provider(
+            CertStoreProvider::new(cert_store, tls_server_config.clone()),
+        );
+
+        let exec = Executor::graceful(guard.clone());
+        let http_service = HttpServer::auto(exec)
+            .service(service_fn(|_req: Request| async { Ok("Hello world!") }));
+
+        TcpListener::bind("127.0.0.1:63019")
+            .await
+            .expect("bind tcp listener")
+            .serve_graceful(
+                guard,
+                TlsAcceptorLayer::with_client_config_handler(
+                    tls_server_config,
+                    tls_client_config_handler,
+                )
+                .layer(http_service),
+            )
+            .await;
+    });
+
+    shutdown
+        .shutdown_with_limit(Duration::from_secs(30))
+        .await
+        .expect("graceful shutdown");
+}
+
+/// The server config used until a certificate is obtained over ACME.
+fn self_signed_server_config() -> ServerConfig {
+    let key_pair = KeyPair::generate().expect("generate server key pair");
+    let cert = rcgen::CertificateParams::new(vec!["localhost".to_owned()])
+        .expect("create server cert params")
+        .self_signed(&key_pair)
+        .expect("create server cert");
+
+    ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![cert.into()],
+            PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+        )
+        .expect("create tls server config")
+}
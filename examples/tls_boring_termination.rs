@@ -62,6 +62,7 @@ use rama::{
             },
             server::{ServerConfig, TlsAcceptorLayer},
         },
+        keylog::KeyLogIntent,
         types::{ApplicationProtocol, SecureTransport},
     },
     Context, Layer,
@@ -101,9 +102,7 @@ async fn main() {
         let mut tls_server_config = ServerConfig::new(key, vec![cert]);
         tls_server_config.alpn_protocols =
             vec![ApplicationProtocol::HTTP_2, ApplicationProtocol::HTTP_11];
-        if let Ok(keylog_file) = std::env::var("SSLKEYLOGFILE") {
-            tls_server_config.keylog_filename = Some(keylog_file);
-        }
+        tls_server_config.set_keylog_intent(KeyLogIntent::Environment);
 
         let tcp_service = (
             TlsAcceptorLayer::new(Arc::new(tls_server_config)).with_store_client_hello(true),
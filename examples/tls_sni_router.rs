@@ -0,0 +1,97 @@
+//! This example demonstrates how to create a TLS passthrough router, routing TLS connections
+//! to different upstream servers based on the server name (SNI) and ALPN protocols
+//! of the client hello, without terminating (decrypting) the TLS connection.
+//!
+//! The `ClientHelloPeekLayer` parses the client hello from the incoming TCP stream,
+//! without consuming it, such that the `SniMatcher` and `AlpnMatcher` can match on it.
+//! All bytes read are replayed to the `Forwarder` chosen for the matched route.
+//!
+//! # Run the example
+//!
+//! ```sh
+//! cargo run --example tls_sni_router --features=tcp,tls
+//! ```
+//!
+//! # Expected output
+//!
+//! The server will start and listen on `:62050`. You can use `curl` to interact with the service:
+//!
+//! ```sh
+//! curl -v --connect-to ::127.0.0.1:62050 https://example.com
+//! curl -v --connect-to ::127.0.0.1:62050 https://www.example.org
+//! ```
+//!
+//! Both requests are forwarded, as-is, to the server of the requested domain.
+//! Requests for other domains, or connections which are not TLS, are refused:
+//!
+//! ```sh
+//! curl -v --connect-to ::127.0.0.1:62050 https://ramaproxy.org
+//! ```
+
+use rama::{
+    error::{BoxError, OpaqueError},
+    layer::{HijackLayer, TraceErrLayer},
+    matcher::And,
+    net::{
+        address::Domain,
+        stream::{
+            layer::tls::ClientHelloPeekLayer,
+            matcher::{AlpnMatcher, SniMatcher},
+        },
+    },
+    service::service_fn,
+    tcp::{client::service::Forwarder, server::TcpListener},
+    tls::types::ApplicationProtocol,
+    Layer,
+};
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::DEBUG.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let graceful = rama::graceful::Shutdown::default();
+
+    graceful.spawn_task_fn(|guard| async {
+        let tcp_service = (
+            TraceErrLayer::new(),
+            ClientHelloPeekLayer::new(),
+            HijackLayer::new(
+                SniMatcher::exact(Domain::from_static("example.com")),
+                Forwarder::new((Domain::from_static("example.com"), 443)),
+            ),
+            HijackLayer::new(
+                And::new((
+                    SniMatcher::sub(Domain::from_static("example.org")),
+                    AlpnMatcher::new(ApplicationProtocol::HTTP_2),
+                )),
+                Forwarder::new((Domain::from_static("www.example.org"), 443)),
+            ),
+        )
+            .layer(service_fn(|| async {
+                Err::<(), _>(BoxError::from(OpaqueError::from_display(
+                    "no route found for incoming connection",
+                )))
+            }));
+
+        TcpListener::bind("127.0.0.1:62050")
+            .await
+            .expect("bind TCP Listener")
+            .serve_graceful(guard, tcp_service)
+            .await;
+    });
+
+    graceful
+        .shutdown_with_limit(Duration::from_secs(30))
+        .await
+        .expect("graceful shutdown");
+}
@@ -4,7 +4,7 @@ use rama::{
     http::{dep::http::request::Parts, headers::Forwarded, Request},
     net::{http::RequestContext, stream::SocketInfo},
     tls::types::{
-        client::{ClientHello, ClientHelloExtension},
+        client::{ClientHello, ClientHelloExtension, Ja3, Ja4},
         SecureTransport,
     },
     ua::UserAgent,
@@ -204,6 +204,10 @@ pub(super) fn get_http_info(req: &Request) -> HttpInfo {
 
 #[derive(Debug, Clone, Serialize)]
 pub(super) struct TlsDisplayInfo {
+    pub(super) ja3: String,
+    pub(super) ja3_full: String,
+    pub(super) ja4: String,
+    pub(super) ja4_r: String,
     pub(super) cipher_suites: Vec<String>,
     pub(super) compression_algorithms: Vec<String>,
     pub(super) extensions: Vec<TlsDisplayInfoExtension>,
@@ -226,7 +230,14 @@ pub(super) fn get_tls_display_info(ctx: &Context<State>) -> Option<TlsDisplayInf
         .get::<SecureTransport>()
         .and_then(|st| st.client_hello())?;
 
+    let ja3 = ctx.get::<Ja3>().cloned().unwrap_or_else(|| hello.ja3());
+    let ja4 = ctx.get::<Ja4>().cloned().unwrap_or_else(|| hello.ja4());
+
     Some(TlsDisplayInfo {
+        ja3: ja3.hash().to_owned(),
+        ja3_full: ja3.full().to_owned(),
+        ja4: ja4.to_string(),
+        ja4_r: ja4.to_raw_string(),
         cipher_suites: hello
             .cipher_suites()
             .iter()
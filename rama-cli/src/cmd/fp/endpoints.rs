@@ -470,7 +470,16 @@ fn render_page(title: &'static str, head: String, content: String) -> Html {
 
 impl From<TlsDisplayInfo> for Vec<Table> {
     fn from(info: TlsDisplayInfo) -> Self {
-        let mut vec = Vec::with_capacity(info.extensions.len() + 1);
+        let mut vec = Vec::with_capacity(info.extensions.len() + 2);
+        vec.push(Table {
+            title: "🔒 TLS Client Hello — Fingerprints".to_owned(),
+            rows: vec![
+                ("JA3".to_owned(), info.ja3),
+                ("JA3 (full)".to_owned(), info.ja3_full),
+                ("JA4".to_owned(), info.ja4),
+                ("JA4 (raw)".to_owned(), info.ja4_r),
+            ],
+        });
         vec.push(Table {
             title: "🔒 TLS Client Hello — Header".to_owned(),
             rows: vec![
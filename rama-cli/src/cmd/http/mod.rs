@@ -51,6 +51,13 @@ pub struct CliCommandHttp {
     /// The `Content-Type` is set to `application/x-www-form-urlencoded` (if not specified).
     form: bool,
 
+    #[arg(short = 'm', long)]
+    /// data items from the command line are serialized as multipart form fields.
+    ///
+    /// The `Content-Type` is set to `multipart/form-data`.
+    /// This is also the case when a file field is specified (`field@path`).
+    multipart: bool,
+
     #[arg(short = 'F', long)]
     /// follow 30 Location redirects
     follow: bool,
@@ -177,6 +184,10 @@ pub struct CliCommandHttp {
     ///
     ///     awesome:=true  amount:=42  colors:='["red", "green", "blue"]'
     ///
+    /// '@' Files to be uploaded as multipart form data:
+    ///
+    ///     cv@cv.pdf  photo@./photos/holiday.jpg
+    ///
     /// You can use a backslash to escape a colliding separator in the field name:
     ///
     ///     field-name-with\:colon=value
@@ -246,6 +257,8 @@ async fn run_inner(guard: ShutdownGuard, cfg: CliCommandHttp) -> Result<(), BoxE
         RequestArgsBuilder::new_json()
     } else if cfg.form {
         RequestArgsBuilder::new_form()
+    } else if cfg.multipart {
+        RequestArgsBuilder::new_multipart()
     } else {
         RequestArgsBuilder::new()
     };
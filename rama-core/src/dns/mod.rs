@@ -150,6 +150,16 @@ impl Dns {
         Ok(self)
     }
 
+    /// Returns the IP addresses which overwrite the DNS lookup
+    /// of the given domain, if any.
+    pub fn overwrite(&self, name: impl TryIntoName) -> Option<&[IpAddr]> {
+        let name = Name::fqdn_from_domain(name).ok()?;
+        self.overwrites
+            .as_ref()
+            .and_then(|overwrites| overwrites.get(&name))
+            .map(Vec::as_slice)
+    }
+
     /// Performs a 'A' DNS record lookup.
     pub async fn ipv4_lookup(
         &self,
@@ -6,6 +6,8 @@ use rama_core::{
     Context, Layer, Service,
 };
 use rama_http_types::{dep::http_body, Request, Version};
+#[cfg(feature = "tls")]
+use rama_net::tls::{client::NegotiatedApplicationProtocol, ApplicationProtocol};
 use rama_net::{
     client::{ConnectorService, EstablishedClientConnection},
     stream::Stream,
@@ -68,6 +70,9 @@ where
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        #[cfg(feature = "tls")]
+        let req = with_negotiated_version(&ctx, req);
+
         let io = TokioIo::new(Box::pin(conn));
 
         match req.version() {
@@ -94,7 +99,7 @@ where
                 let (sender, conn) = hyper::client::conn::http1::handshake(io).await?;
 
                 ctx.spawn(async move {
-                    if let Err(err) = conn.await {
+                    if let Err(err) = conn.with_upgrades().await {
                         tracing::debug!("connection failed: {:?}", err);
                     }
                 });
@@ -117,6 +122,26 @@ where
     }
 }
 
+#[cfg(feature = "tls")]
+/// Set the http version of the request to the application protocol
+/// negotiated using ALPN, as that takes precedence over the requested http version.
+fn with_negotiated_version<State, Body>(
+    ctx: &Context<State>,
+    mut req: Request<Body>,
+) -> Request<Body> {
+    match ctx
+        .get::<NegotiatedApplicationProtocol>()
+        .map(|NegotiatedApplicationProtocol(protocol)| protocol)
+    {
+        Some(ApplicationProtocol::HTTP_2) => *req.version_mut() = Version::HTTP_2,
+        Some(ApplicationProtocol::HTTP_11) if req.version() == Version::HTTP_2 => {
+            *req.version_mut() = Version::HTTP_11
+        }
+        _ => (),
+    }
+    req
+}
+
 /// A [`Layer`] that produces an [`HttpConnector`].
 #[derive(Debug, Clone)]
 #[non_exhaustive]
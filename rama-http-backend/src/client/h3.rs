@@ -0,0 +1,247 @@
+use crate::h3_body::{send_body, H3Body};
+use bytes::Bytes;
+use quinn::crypto::rustls::QuicClientConfig;
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Service,
+};
+use rama_http_types::{dep::http_body, Request, Response};
+use rama_net::{
+    address::{Authority, Domain, Host},
+    client::EstablishedClientConnection,
+    transport::TryRefIntoTransportContext,
+};
+use rama_tcp::client::IpPreference;
+use rama_tls::rustls::{dep::rustls::ClientConfig, verify::NoServerCertVerifier};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+/// The ALPN protocol identifier of HTTP/3.
+const ALPN_H3: &[u8] = b"h3";
+
+#[derive(Debug, Clone, Default)]
+/// A [`Service`] which establishes an HTTP/3 connection over QUIC.
+///
+/// The target is the authority of the request, which is resolved
+/// using the [`Dns`] of the [`Context`], respecting the [`IpPreference`]
+/// found in the [`Context`] (if any).
+///
+/// By default the server certificate is not verified, same as the
+/// default configuration of the `HttpsConnector`. Use [`H3Connector::with_tls_config`]
+/// to use your own [`ClientConfig`] instead.
+///
+/// [`Dns`]: rama_core::dns::Dns
+pub struct H3Connector {
+    tls_config: Option<Arc<ClientConfig>>,
+}
+
+impl H3Connector {
+    /// Create a new [`H3Connector`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the rustls [`ClientConfig`] used to secure the QUIC connections.
+    ///
+    /// The `h3` ALPN protocol is set in case the [`ClientConfig`] defines no ALPN protocols.
+    pub fn set_tls_config(&mut self, cfg: Arc<ClientConfig>) -> &mut Self {
+        self.tls_config = Some(cfg);
+        self
+    }
+
+    /// Replace this [`H3Connector`] with the rustls [`ClientConfig`] set.
+    ///
+    /// See [`H3Connector::set_tls_config`] for more information.
+    pub fn with_tls_config(mut self, cfg: Arc<ClientConfig>) -> Self {
+        self.tls_config = Some(cfg);
+        self
+    }
+
+    /// Replace this [`H3Connector`] with an option of the rustls [`ClientConfig`] set.
+    ///
+    /// See [`H3Connector::set_tls_config`] for more information.
+    pub fn maybe_with_tls_config(mut self, cfg: Option<Arc<ClientConfig>>) -> Self {
+        self.tls_config = cfg;
+        self
+    }
+
+    fn quic_client_config(&self) -> Result<quinn::ClientConfig, OpaqueError> {
+        let tls_config = match self.tls_config.clone() {
+            Some(tls_config) if !tls_config.alpn_protocols.is_empty() => tls_config,
+            Some(tls_config) => {
+                let mut tls_config = (*tls_config).clone();
+                tls_config.alpn_protocols = vec![ALPN_H3.to_vec()];
+                Arc::new(tls_config)
+            }
+            None => new_tls_client_config(),
+        };
+        let crypto = QuicClientConfig::try_from(tls_config)
+            .context("h3 connector: create quic client config")?;
+        Ok(quinn::ClientConfig::new(Arc::new(crypto)))
+    }
+}
+
+impl<State, Body> Service<State, Request<Body>> for H3Connector
+where
+    State: Send + Sync + 'static,
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    type Response = EstablishedClientConnection<H3ClientService, State, Request<Body>>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        let authority = ctx
+            .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
+            .map_err(|err| {
+                OpaqueError::from_boxed(err.into())
+                    .context("h3 connector: compute transport context")
+            })?
+            .authority
+            .clone();
+        let server_name = authority.host().to_string();
+
+        let addr = resolve(&ctx, authority).await?;
+        let bind_addr: SocketAddr = if addr.is_ipv6() {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        };
+        let endpoint =
+            quinn::Endpoint::client(bind_addr).context("h3 connector: bind quic endpoint")?;
+
+        let conn = endpoint
+            .connect_with(self.quic_client_config()?, addr, &server_name)
+            .context("h3 connector: connect quic endpoint")?
+            .await
+            .context("h3 connector: establish quic connection")?;
+
+        let (mut driver, sender) = h3::client::new(h3_quinn::Connection::new(conn))
+            .await
+            .context("h3 connector: establish h3 connection")?;
+
+        ctx.spawn(async move {
+            let err = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+            if !err.is_h3_no_error() {
+                tracing::debug!(%err, "h3 connection failed");
+            }
+        });
+
+        Ok(EstablishedClientConnection {
+            ctx,
+            req,
+            conn: H3ClientService { sender, endpoint },
+            addr,
+        })
+    }
+}
+
+/// Internal http sender used to send the actual requests over an HTTP/3 connection.
+pub struct H3ClientService {
+    sender: h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>,
+    // keep the endpoint around for as long as the connection is in use
+    endpoint: quinn::Endpoint,
+}
+
+impl std::fmt::Debug for H3ClientService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("H3ClientService")
+            .field("endpoint", &self.endpoint)
+            .finish()
+    }
+}
+
+impl<State, Body> Service<State, Request<Body>> for H3ClientService
+where
+    State: Send + Sync + 'static,
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    type Response = Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        let (parts, body) = req.into_parts();
+
+        let mut sender = self.sender.clone();
+        let stream = sender
+            .send_request(Request::from_parts(parts, ()))
+            .await
+            .context("h3 client: send request")?;
+        let (mut send, mut recv) = stream.split();
+
+        send_body(&mut send, body).await?;
+
+        let resp = recv
+            .recv_response()
+            .await
+            .context("h3 client: receive response")?;
+        Ok(resp.map(|()| rama_http_types::Body::new(H3Body::new(recv))))
+    }
+}
+
+/// Resolve the authority into the [`SocketAddr`] to connect to,
+/// respecting the [`IpPreference`] found in the [`Context`] (if any).
+async fn resolve<State>(
+    ctx: &Context<State>,
+    authority: Authority,
+) -> Result<SocketAddr, OpaqueError> {
+    let (host, port) = authority.into_parts();
+    let domain = match host {
+        Host::Name(domain) => domain,
+        Host::Address(ip) => return Ok((ip, port).into()),
+    };
+
+    let preference = ctx.get::<IpPreference>().copied().unwrap_or_default();
+    let ip = match preference {
+        IpPreference::PreferIpv6 => match lookup_ipv6(ctx, &domain).await {
+            Some(ip) => Some(ip),
+            None => lookup_ipv4(ctx, &domain).await,
+        },
+        IpPreference::PreferIpv4 => match lookup_ipv4(ctx, &domain).await {
+            Some(ip) => Some(ip),
+            None => lookup_ipv6(ctx, &domain).await,
+        },
+        IpPreference::Ipv4Only => lookup_ipv4(ctx, &domain).await,
+        IpPreference::Ipv6Only => lookup_ipv6(ctx, &domain).await,
+    };
+
+    ip.map(|ip| (ip, port).into()).ok_or_else(|| {
+        OpaqueError::from_display(format!("h3 connector: failed to resolve domain {domain}"))
+    })
+}
+
+async fn lookup_ipv4<State>(ctx: &Context<State>, domain: &Domain) -> Option<IpAddr> {
+    ctx.dns()
+        .ipv4_lookup(domain.clone())
+        .await
+        .ok()?
+        .next()
+        .map(IpAddr::V4)
+}
+
+async fn lookup_ipv6<State>(ctx: &Context<State>, domain: &Domain) -> Option<IpAddr> {
+    ctx.dns()
+        .ipv6_lookup(domain.clone())
+        .await
+        .ok()?
+        .next()
+        .map(IpAddr::V6)
+}
+
+fn new_tls_client_config() -> Arc<ClientConfig> {
+    let mut config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(NoServerCertVerifier::default()))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![ALPN_H3.to_vec()];
+    Arc::new(config)
+}
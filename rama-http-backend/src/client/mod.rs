@@ -6,18 +6,23 @@ use rama_core::{
     error::{BoxError, ErrorExt, OpaqueError},
     Context, Service,
 };
-use rama_http_types::{dep::http_body, Request, Response};
+use rama_http_types::{dep::http_body, Method, Request, Response, StatusCode, Version};
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
+use rama_socks5::client::{Socks4ProxyConnector, Socks5ProxyConnector};
 use rama_tcp::client::service::TcpConnector;
+use std::net::SocketAddr;
 
-// TODO: also support client config in boring...
+#[cfg(feature = "boring")]
+use rama_tls::boring::client::ClientConfig;
 #[cfg(all(feature = "rustls", not(feature = "boring")))]
 use rama_tls::rustls::dep::rustls::ClientConfig;
-#[cfg(all(feature = "rustls", not(feature = "boring")))]
+#[cfg(any(feature = "rustls", feature = "boring"))]
 use std::sync::Arc;
 
 #[cfg(any(feature = "rustls", feature = "boring"))]
-use rama_tls::std::client::HttpsConnector;
+use rama_net::tls::SecureTransport;
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_tls::{keylog::KeyLogIntent, std::client::HttpsConnector, verify::ServerVerifyPolicy};
 
 mod svc;
 #[doc(inline)]
@@ -27,8 +32,21 @@ mod conn;
 #[doc(inline)]
 pub use conn::{HttpConnector, HttpConnectorLayer};
 
+mod pool;
+#[doc(inline)]
+pub use pool::{DisableConnectionPool, PoolConfig};
+use pool::{Pool, PoolKey, PooledBody};
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use pool::{TlsConfigKey, TlsPoolKey};
+
 pub mod proxy;
 
+#[cfg(feature = "http3")]
+mod h3;
+#[cfg(feature = "http3")]
+#[doc(inline)]
+pub use h3::{H3ClientService, H3Connector};
+
 #[derive(Debug, Clone, Default)]
 #[non_exhaustive]
 /// An opiniated http client that can be used to serve HTTP requests.
@@ -38,9 +56,28 @@ pub mod proxy;
 /// passed through your "connector" setup. All this and more is possible by defining your own
 /// http client. Rama is here to empower you, the building blocks are there, go crazy
 /// with your own service fork and use the full power of Rust at your fingertips ;)
+///
+/// In case a [`ProxyAddress`] is found in the [`Context`], the connection
+/// is established via that proxy, using a [`Socks4ProxyConnector`]
+/// for `socks4` and `socks4a` proxies, a [`Socks5ProxyConnector`]
+/// for `socks5` and `socks5h` proxies, and a [`HttpProxyConnector`] otherwise.
+///
+/// Established connections are pooled, such that they can be reused by later requests,
+/// see [`PoolConfig`] for more information. Clones of a [`HttpClient`] share the same pool.
+/// Insert [`DisableConnectionPool`] in the [`Context`] to opt out of pooling for a request.
+///
+/// The [`Response`] contains a [`ConnectionInfo`] extension, regardless
+/// of whether the connection was established for it or reused.
+///
+/// [`ProxyAddress`]: rama_net::address::ProxyAddress
 pub struct HttpClient {
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     tls_config: Option<Arc<ClientConfig>>,
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    verify_policy: Option<ServerVerifyPolicy>,
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    key_log_intent: KeyLogIntent,
+    pool: Pool,
 }
 
 impl HttpClient {
@@ -49,26 +86,81 @@ impl HttpClient {
         Self::default()
     }
 
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    /// Set the [`PoolConfig`] of this [`HttpClient`],
+    /// replacing its connection pool with a new (empty) one.
+    pub fn set_pool_config(&mut self, cfg: PoolConfig) -> &mut Self {
+        self.pool = Pool::new(cfg);
+        self
+    }
+
+    /// Replace this [`HttpClient`] with the [`PoolConfig`] set,
+    /// replacing its connection pool with a new (empty) one.
+    pub fn with_pool_config(mut self, cfg: PoolConfig) -> Self {
+        self.pool = Pool::new(cfg);
+        self
+    }
+
+    /// Returns the [`PoolConfig`] of this [`HttpClient`].
+    pub fn pool_config(&self) -> &PoolConfig {
+        self.pool.config()
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     /// Set the [`ClientConfig`] of this [`HttpClient`].
     pub fn set_tls_config(&mut self, cfg: Arc<ClientConfig>) -> &mut Self {
         self.tls_config = Some(cfg);
         self
     }
 
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     /// Replace this [`HttpClient`] with the [`ClientConfig`] set.
     pub fn with_tls_config(mut self, cfg: Arc<ClientConfig>) -> Self {
         self.tls_config = Some(cfg);
         self
     }
 
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     /// Replace this [`HttpClient`] with an option of [`ClientConfig`] set.
     pub fn maybe_with_tls_config(mut self, cfg: Option<Arc<ClientConfig>>) -> Self {
         self.tls_config = cfg;
         self
     }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpClient`],
+    /// used to verify the certificates of the servers it connects to.
+    ///
+    /// A [`ServerVerifyPolicy`] found in the [`Context`] takes precedence.
+    pub fn set_verify_policy(&mut self, policy: ServerVerifyPolicy) -> &mut Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Replace this [`HttpClient`] with the [`ServerVerifyPolicy`] set,
+    /// used to verify the certificates of the servers it connects to.
+    ///
+    /// A [`ServerVerifyPolicy`] found in the [`Context`] takes precedence.
+    pub fn with_verify_policy(mut self, policy: ServerVerifyPolicy) -> Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Set the [`KeyLogIntent`] of this [`HttpClient`],
+    /// used to write the key log of its tls handshakes.
+    pub fn set_key_log_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    /// Replace this [`HttpClient`] with the [`KeyLogIntent`] set,
+    /// used to write the key log of its tls handshakes.
+    pub fn with_key_log_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.key_log_intent = intent;
+        self
+    }
 }
 
 impl<State, Body> Service<State, Request<Body>> for HttpClient
@@ -81,32 +173,325 @@ where
 
     async fn serve(
         &self,
-        ctx: Context<State>,
+        mut ctx: Context<State>,
         req: Request<Body>,
     ) -> Result<Self::Response, Self::Error> {
         let uri = req.uri().clone();
 
-        #[cfg(all(feature = "rustls", not(feature = "boring")))]
+        let pool_key = if ctx.contains::<DisableConnectionPool>() {
+            None
+        } else {
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            let tls = TlsPoolKey {
+                config: self.tls_config.clone().map(TlsConfigKey),
+                verify_policy: ctx
+                    .get::<ServerVerifyPolicy>()
+                    .or(self.verify_policy.as_ref())
+                    .cloned(),
+                key_log_intent: self.key_log_intent.clone(),
+                client_hello: ctx
+                    .get::<SecureTransport>()
+                    .and_then(SecureTransport::client_hello)
+                    .cloned(),
+            };
+
+            Some(
+                PoolKey::new(
+                    &mut ctx,
+                    &req,
+                    #[cfg(any(feature = "rustls", feature = "boring"))]
+                    tls,
+                )
+                .map_err(|err| err.with_context(|| uri.to_string()))?,
+            )
+        };
+
+        let mut req = req;
+        if let Some(key) = pool_key.as_ref() {
+            if let Some((sender, info)) = self.pool.checkout(key) {
+                tracing::trace!(%uri, "http client: reuse pooled connection");
+                match self
+                    .serve_pooled(ctx.clone(), req, key.clone(), sender, info, false)
+                    .await
+                {
+                    Ok(resp) => return Ok(resp),
+                    // the pooled connection was closed before the request could be sent,
+                    // in which case it is safe to retry (once) using a new connection
+                    Err((err, Some(unsent))) => {
+                        tracing::debug!(%uri, %err, "http client: pooled connection failed: retry on new connection");
+                        req = unsent;
+                    }
+                    Err((err, None)) => return Err(err),
+                }
+            }
+        }
+
+        // the proxy connectors are boxed to keep the (future) types of the
+        // connector stack manageable for the compiler
+        #[cfg(any(feature = "rustls", feature = "boring"))]
         let connector = HttpConnector::new(
-            HttpsConnector::auto(HttpProxyConnector::optional(HttpsConnector::tunnel(
-                TcpConnector::new(),
-            )))
-            .maybe_with_config(self.tls_config.clone()),
+            HttpsConnector::auto(
+                Socks4ProxyConnector::optional(Socks5ProxyConnector::optional(
+                    HttpProxyConnector::optional(
+                        HttpsConnector::tunnel(TcpConnector::new())
+                            .with_key_log_intent(self.key_log_intent.clone()),
+                    ),
+                ))
+                .boxed(),
+            )
+            .maybe_with_config(self.tls_config.clone())
+            .maybe_with_verify_policy(self.verify_policy.clone())
+            .with_key_log_intent(self.key_log_intent.clone()),
         );
-        #[cfg(feature = "boring")]
-        let connector = HttpConnector::new(HttpsConnector::auto(HttpProxyConnector::optional(
-            HttpsConnector::tunnel(TcpConnector::new()),
-        )));
         #[cfg(not(any(feature = "rustls", feature = "boring")))]
-        let connector = HttpConnector::new(HttpProxyConnector::optional(TcpConnector::new()));
+        let connector = HttpConnector::new(Socks4ProxyConnector::optional(
+            Socks5ProxyConnector::optional(HttpProxyConnector::optional(TcpConnector::new())),
+        ));
 
-        let EstablishedClientConnection { ctx, req, conn, .. } = connector
+        let EstablishedClientConnection {
+            ctx,
+            req,
+            conn,
+            addr,
+        } = connector
             .connect(ctx, req)
             .await
             .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))?;
 
-        conn.serve(ctx, req)
-            .await
-            .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))
+        let info = ConnectionInfo { addr };
+        match pool_key {
+            Some(key) => self
+                .serve_pooled(ctx, req, key, conn.0, info, true)
+                .await
+                .map_err(|(err, _)| err),
+            None => {
+                let mut resp = conn
+                    .serve(ctx, req)
+                    .await
+                    .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))?;
+                resp.extensions_mut().insert(info);
+                Ok(resp)
+            }
+        }
+    }
+}
+
+impl HttpClient {
+    /// Serve the request using the given (pooled) connection,
+    /// putting it (back) in the pool such that it can be reused.
+    ///
+    /// The request is returned as part of the error in case it was not sent.
+    async fn serve_pooled<State, Body>(
+        &self,
+        ctx: Context<State>,
+        mut req: Request<Body>,
+        key: PoolKey,
+        sender: svc::SendRequest<Body>,
+        info: ConnectionInfo,
+        new: bool,
+    ) -> Result<Response, (OpaqueError, Option<Request<Body>>)>
+    where
+        State: Send + Sync + 'static,
+        Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+    {
+        let uri = req.uri().clone();
+        let is_connect = req.method() == Method::CONNECT;
+
+        // the request is sent using the protocol negotiated for the connection,
+        // which can differ from the requested one
+        match &sender {
+            svc::SendRequest::Http2(_) => *req.version_mut() = Version::HTTP_2,
+            svc::SendRequest::Http1(_) if req.version() == Version::HTTP_2 => {
+                *req.version_mut() = Version::HTTP_11
+            }
+            svc::SendRequest::Http1(_) => (),
+        }
+
+        // http/2 connections are shared between requests,
+        // and can thus be made available prior to sending the request
+        if new {
+            if let Some(shared) = sender.try_clone_http2() {
+                self.pool.put(key.clone(), shared, info.clone());
+            }
+        }
+
+        let svc = HttpClientService(sender);
+        let resp = svc.try_send(ctx, req).await.map_err(|(err, req)| {
+            (
+                OpaqueError::from_boxed(err).with_context(|| uri.to_string()),
+                req,
+            )
+        })?;
+
+        // http/1 connections can only be reused once the response is fully received,
+        // and never in case these are taken over (upgraded) by the request
+        let upgraded = is_connect || resp.status() == StatusCode::SWITCHING_PROTOCOLS;
+        let mut resp = match svc.0 {
+            sender @ svc::SendRequest::Http1(_) if !upgraded => {
+                let pool = self.pool.clone();
+                let info = info.clone();
+                resp.map(|body| {
+                    rama_http_types::Body::new(PooledBody::new(body, pool, key, sender, info))
+                })
+            }
+            svc::SendRequest::Http1(_) | svc::SendRequest::Http2(_) => resp,
+        };
+
+        resp.extensions_mut().insert(info);
+        Ok(resp)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Information about the connection over which the [`HttpClient`]
+/// sent a request, inserted in the extensions of the [`Response`].
+pub struct ConnectionInfo {
+    addr: SocketAddr,
+}
+
+impl ConnectionInfo {
+    /// Returns the address of the server (or proxy) connected to.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::HttpServer;
+    use rama_core::{rt::Executor, service::service_fn};
+    use rama_http_types::{BodyExtractExt, Version};
+    use rama_tcp::server::TcpListener;
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+    use tokio::net::TcpStream;
+
+    async fn spawn_server() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        let http_service =
+            HttpServer::auto(Executor::default()).service(service_fn(|req: Request| async move {
+                let resp = if req.uri().path() == "/upgrade" {
+                    Response::builder()
+                        .status(StatusCode::SWITCHING_PROTOCOLS)
+                        .header("connection", "upgrade")
+                        .header("upgrade", "test")
+                        .body(rama_http_types::Body::empty())
+                        .unwrap()
+                } else {
+                    Response::new(rama_http_types::Body::from("ok"))
+                };
+                Ok::<_, Infallible>(resp)
+            }));
+        let http_service = Arc::new(http_service);
+
+        tokio::spawn(
+            listener.serve(service_fn(move |ctx: Context<()>, stream: TcpStream| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let http_service = http_service.clone();
+                async move { http_service.serve(ctx, stream).await }
+            })),
+        );
+
+        (addr, connections)
+    }
+
+    async fn get(client: &HttpClient, ctx: Context<()>, addr: SocketAddr, version: Version) {
+        let req = Request::builder()
+            .uri(format!("http://{addr}/"))
+            .version(version)
+            .body(rama_http_types::Body::empty())
+            .unwrap();
+        let resp = client.serve(ctx, req).await.unwrap();
+        assert_eq!(
+            resp.extensions().get::<ConnectionInfo>().unwrap().addr(),
+            addr
+        );
+        assert_eq!(resp.try_into_string().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_http1_connection_reuse() {
+        let (addr, connections) = spawn_server().await;
+        let client = HttpClient::default();
+
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        let mut ctx = Context::default();
+        ctx.insert(DisableConnectionPool::new());
+        get(&client, ctx, addr, Version::HTTP_11).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        let client = client.with_pool_config(PoolConfig::new().with_max_idle_per_host(0));
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_http1_connection_reused_after_body() {
+        let (addr, connections) = spawn_server().await;
+        let client = HttpClient::default();
+
+        let req = Request::builder()
+            .uri(format!("http://{addr}/"))
+            .body(rama_http_types::Body::empty())
+            .unwrap();
+        let resp = client.serve(Context::default(), req).await.unwrap();
+
+        // the connection is in use until the response body is received
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        assert_eq!(resp.try_into_string().await.unwrap(), "ok");
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_http1_upgraded_connection_not_reused() {
+        let (addr, connections) = spawn_server().await;
+        let client = HttpClient::default();
+
+        let req = Request::builder()
+            .uri(format!("http://{addr}/upgrade"))
+            .header("connection", "upgrade")
+            .header("upgrade", "test")
+            .body(rama_http_types::Body::empty())
+            .unwrap();
+        let resp = client.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(client.pool.size(), 0);
+
+        get(&client, Context::default(), addr, Version::HTTP_11).await;
+        assert_eq!(client.pool.size(), 1);
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_http2_connection_multiplexed() {
+        let (addr, connections) = spawn_server().await;
+        let client = HttpClient::default();
+
+        get(&client, Context::default(), addr, Version::HTTP_2).await;
+        tokio::join!(
+            get(&client, Context::default(), addr, Version::HTTP_2),
+            get(&client, Context::default(), addr, Version::HTTP_2),
+            get(&client, Context::default(), addr, Version::HTTP_2),
+        );
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
     }
 }
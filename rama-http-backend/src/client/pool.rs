@@ -0,0 +1,569 @@
+//! Connection pool used by the [`HttpClient`] to reuse established connections.
+//!
+//! [`HttpClient`]: super::HttpClient
+
+use super::{svc::SendRequest, ConnectionInfo};
+use pin_project_lite::pin_project;
+use rama_core::{error::OpaqueError, Context};
+use rama_http_types::{
+    dep::http_body::{self, Frame, SizeHint},
+    Request, Version,
+};
+use rama_net::{
+    address::{Authority, Host, ProxyAddress},
+    http::RequestContext,
+    Protocol,
+};
+use rama_tcp::client::IpPreference;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Poll},
+    time::{Duration, Instant},
+};
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_net::tls::client::ClientHello;
+#[cfg(feature = "boring")]
+use rama_tls::boring::client::ClientConfig;
+#[cfg(all(feature = "rustls", not(feature = "boring")))]
+use rama_tls::rustls::dep::rustls::ClientConfig;
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use rama_tls::{keylog::KeyLogIntent, verify::ServerVerifyPolicy};
+#[cfg(any(feature = "rustls", feature = "boring"))]
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone)]
+/// Configuration of the connection pool used by the [`HttpClient`].
+///
+/// Http/1 connections are checked out of the pool for a single request
+/// at a time, and only returned once its response body is fully received,
+/// while Http/2 connections are shared (multiplexed)
+/// between all requests that target the same pool key.
+/// Whether a connection is Http/1 or Http/2 is defined by the protocol
+/// negotiated for it (using ALPN), and not the http version requested.
+///
+/// Pooled connections are keyed on the authority, protocol and requested http version
+/// of the request, as well as all settings which influence how the connection is established,
+/// such as the [`ProxyAddress`], ip preference, dns overwrites and tls settings in use.
+///
+/// [`HttpClient`]: super::HttpClient
+pub struct PoolConfig {
+    idle_timeout: Duration,
+    max_idle_per_host: usize,
+    max_total: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(90),
+            max_idle_per_host: 32,
+            max_total: 512,
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Create a new [`PoolConfig`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the duration after which an unused pooled connection is dropped.
+    ///
+    /// Defaults to 90 seconds.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set the duration after which an unused pooled connection is dropped.
+    ///
+    /// Defaults to 90 seconds.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of connections pooled per pool key (~host).
+    ///
+    /// Defaults to 32.
+    pub fn with_max_idle_per_host(mut self, max: usize) -> Self {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /// Set the maximum number of connections pooled per pool key (~host).
+    ///
+    /// Defaults to 32.
+    pub fn set_max_idle_per_host(&mut self, max: usize) -> &mut Self {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /// Set the maximum number of connections pooled in total,
+    /// after which the least recently used connection is dropped
+    /// in favour of a new one.
+    ///
+    /// Defaults to 512.
+    pub fn with_max_total(mut self, max: usize) -> Self {
+        self.max_total = max;
+        self
+    }
+
+    /// Set the maximum number of connections pooled in total,
+    /// after which the least recently used connection is dropped
+    /// in favour of a new one.
+    ///
+    /// Defaults to 512.
+    pub fn set_max_total(&mut self, max: usize) -> &mut Self {
+        self.max_total = max;
+        self
+    }
+
+    /// Returns the duration after which an unused pooled connection is dropped.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Returns the maximum number of connections pooled per pool key (~host).
+    pub fn max_idle_per_host(&self) -> usize {
+        self.max_idle_per_host
+    }
+
+    /// Returns the maximum number of connections pooled in total.
+    pub fn max_total(&self) -> usize {
+        self.max_total
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// Insert this extension in the [`Context`] to opt out of the connection pool
+/// of the [`HttpClient`] for a request, meaning a new connection is established
+/// for it, which will not be reused by any other request.
+///
+/// [`HttpClient`]: super::HttpClient
+pub struct DisableConnectionPool;
+
+impl DisableConnectionPool {
+    /// Create a new [`DisableConnectionPool`] extension.
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Key on which connections are pooled.
+///
+/// Besides the target of the request, it contains all settings
+/// which influence how a connection is established, such that
+/// a connection is only reused for requests which would have
+/// resulted in an equivalent connection.
+pub(super) struct PoolKey {
+    authority: Authority,
+    protocol: Protocol,
+    /// The requested http version, which defines the protocols offered using ALPN.
+    version: Version,
+    proxy: Option<ProxyAddress>,
+    ip_preference: Option<IpPreference>,
+    dns_overwrite: Option<Vec<IpAddr>>,
+    #[cfg(any(feature = "rustls", feature = "boring"))]
+    tls: TlsPoolKey,
+    body: TypeId,
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The tls settings used to establish a connection, as part of a [`PoolKey`].
+pub(super) struct TlsPoolKey {
+    pub(super) config: Option<TlsConfigKey>,
+    pub(super) verify_policy: Option<ServerVerifyPolicy>,
+    pub(super) key_log_intent: KeyLogIntent,
+    /// The emulated [`ClientHello`], including the order of its
+    /// cipher suites and extensions, as that is part of its fingerprint.
+    pub(super) client_hello: Option<ClientHello>,
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl Hash for TlsPoolKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // the key log intent is only compared for equality,
+        // which is consistent as it is not expected to differ much between requests
+        self.config.hash(state);
+        self.verify_policy.hash(state);
+        self.client_hello.hash(state);
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+#[derive(Debug, Clone)]
+/// Tls [`ClientConfig`] compared by identity.
+///
+/// The [`Arc`] is held by the key, such that the identity
+/// cannot be reused by another config as long as the key exists.
+pub(super) struct TlsConfigKey(pub(super) Arc<ClientConfig>);
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl PartialEq for TlsConfigKey {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl Eq for TlsConfigKey {}
+
+#[cfg(any(feature = "rustls", feature = "boring"))]
+impl Hash for TlsConfigKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state)
+    }
+}
+
+impl PoolKey {
+    /// Compute the [`PoolKey`] for the given request,
+    /// using the (optional) tls settings used to establish connections.
+    pub(super) fn new<State, Body: 'static>(
+        ctx: &mut Context<State>,
+        req: &Request<Body>,
+        #[cfg(any(feature = "rustls", feature = "boring"))] tls: TlsPoolKey,
+    ) -> Result<Self, OpaqueError> {
+        let request_ctx = ctx
+            .get_or_try_insert_with_ctx::<RequestContext, _>(|ctx| (ctx, req).try_into())?
+            .clone();
+        let dns_overwrite = match request_ctx.authority.host() {
+            Host::Name(domain) => ctx.dns().overwrite(domain.as_str()).map(<[_]>::to_vec),
+            Host::Address(_) => None,
+        };
+        Ok(Self {
+            authority: request_ctx.authority,
+            protocol: request_ctx.protocol,
+            version: req.version(),
+            proxy: ctx.get::<ProxyAddress>().cloned(),
+            ip_preference: ctx.get::<IpPreference>().copied(),
+            dns_overwrite,
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            tls,
+            body: TypeId::of::<Body>(),
+        })
+    }
+}
+
+/// A pool of established http connections.
+///
+/// Cloning a [`Pool`] results in a handle to the same pool.
+pub(super) struct Pool {
+    config: PoolConfig,
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("config", &self.config)
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+impl Clone for Pool {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self::new(PoolConfig::default())
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: HashMap<PoolKey, Vec<Idle>>,
+    total: usize,
+}
+
+struct Idle {
+    conn: Box<dyn PooledConnection>,
+    info: ConnectionInfo,
+    last_used: Instant,
+}
+
+/// Object-safe view on a [`SendRequest`], such that connections
+/// for different body types can be stored in the same pool.
+trait PooledConnection: Send {
+    fn is_closed(&self) -> bool;
+    fn is_ready(&self) -> bool;
+    fn is_multiplexed(&self) -> bool;
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<Body: Send + 'static> PooledConnection for SendRequest<Body> {
+    fn is_closed(&self) -> bool {
+        match self {
+            SendRequest::Http1(sender) => sender.try_lock().map(|s| s.is_closed()).unwrap_or(false),
+            SendRequest::Http2(sender) => sender.try_lock().map(|s| s.is_closed()).unwrap_or(false),
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        match self {
+            SendRequest::Http1(sender) => sender.try_lock().map(|s| s.is_ready()).unwrap_or(false),
+            SendRequest::Http2(sender) => sender.try_lock().map(|s| s.is_ready()).unwrap_or(false),
+        }
+    }
+
+    fn is_multiplexed(&self) -> bool {
+        matches!(self, SendRequest::Http2(_))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl Pool {
+    pub(super) fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            state: Default::default(),
+        }
+    }
+
+    pub(super) fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Returns the number of idle connections in the pool.
+    pub(super) fn size(&self) -> usize {
+        self.state.lock().unwrap().total
+    }
+
+    /// Checkout a healthy connection for the given key, if any.
+    ///
+    /// Http/1 connections are removed from the pool until they are put back,
+    /// while a shared handle is returned for Http/2 connections.
+    pub(super) fn checkout<Body: Send + 'static>(
+        &self,
+        key: &PoolKey,
+    ) -> Option<(SendRequest<Body>, ConnectionInfo)> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let idle_timeout = self.config.idle_timeout;
+
+        let entries = state.idle.get_mut(key)?;
+        let before = entries.len();
+        entries.retain(|idle| {
+            !idle.conn.is_closed() && now.duration_since(idle.last_used) < idle_timeout
+        });
+        let mut removed = before - entries.len();
+
+        let conn = match entries.iter().position(|idle| idle.conn.is_ready()) {
+            Some(index) if entries[index].conn.is_multiplexed() => {
+                let idle = &mut entries[index];
+                idle.last_used = now;
+                idle.conn
+                    .as_any()
+                    .downcast_ref::<SendRequest<Body>>()
+                    .and_then(SendRequest::try_clone_http2)
+                    .map(|conn| (conn, idle.info.clone()))
+            }
+            Some(index) => {
+                removed += 1;
+                let idle = entries.swap_remove(index);
+                let info = idle.info;
+                idle.conn
+                    .into_any()
+                    .downcast::<SendRequest<Body>>()
+                    .ok()
+                    .map(|conn| (*conn, info))
+            }
+            None => None,
+        };
+
+        if entries.is_empty() {
+            state.idle.remove(key);
+        }
+        state.total -= removed;
+        conn
+    }
+
+    /// Put the given connection in the pool, such that it can be used
+    /// by future requests for the same key.
+    pub(super) fn put<Body: Send + 'static>(
+        &self,
+        key: PoolKey,
+        conn: SendRequest<Body>,
+        info: ConnectionInfo,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let idle_timeout = self.config.idle_timeout;
+
+        // prune connections that are no longer usable
+        let mut pruned = 0;
+        state.idle.retain(|_, entries| {
+            let before = entries.len();
+            entries.retain(|idle| {
+                !idle.conn.is_closed() && now.duration_since(idle.last_used) < idle_timeout
+            });
+            pruned += before - entries.len();
+            !entries.is_empty()
+        });
+        state.total -= pruned;
+
+        if self.config.max_idle_per_host == 0 || self.config.max_total == 0 {
+            return;
+        }
+
+        if let Some(entries) = state.idle.get_mut(&key) {
+            if entries.len() >= self.config.max_idle_per_host {
+                if let Some(index) = oldest(entries) {
+                    entries.swap_remove(index);
+                    state.total -= 1;
+                }
+            }
+        }
+
+        if state.total >= self.config.max_total {
+            let lru = state
+                .idle
+                .iter()
+                .filter_map(|(key, entries)| {
+                    oldest(entries).map(|index| (key.clone(), index, entries[index].last_used))
+                })
+                .min_by_key(|(_, _, last_used)| *last_used);
+            if let Some((lru_key, index, _)) = lru {
+                if let Some(entries) = state.idle.get_mut(&lru_key) {
+                    entries.swap_remove(index);
+                    if entries.is_empty() {
+                        state.idle.remove(&lru_key);
+                    }
+                }
+                state.total -= 1;
+            }
+        }
+
+        state.idle.entry(key).or_default().push(Idle {
+            conn: Box::new(conn),
+            info,
+            last_used: now,
+        });
+        state.total += 1;
+    }
+}
+
+pin_project! {
+    /// Body of a response received over a pooled Http/1 connection,
+    /// which puts that connection back in the [`Pool`] once the body is fully received.
+    ///
+    /// The connection is dropped (and thus closed) in case the body fails
+    /// or is dropped before it is fully received.
+    pub(super) struct PooledBody<B, ReqBody> {
+        #[pin]
+        inner: B,
+        release: Option<PoolRelease<ReqBody>>,
+    }
+}
+
+/// A connection to be put back in the [`Pool`].
+pub(super) struct PoolRelease<ReqBody> {
+    pool: Pool,
+    key: PoolKey,
+    conn: SendRequest<ReqBody>,
+    info: ConnectionInfo,
+}
+
+impl<ReqBody: Send + 'static> PoolRelease<ReqBody> {
+    fn put(self) {
+        self.pool.put(self.key, self.conn, self.info);
+    }
+}
+
+impl<B, ReqBody> PooledBody<B, ReqBody>
+where
+    B: http_body::Body,
+    ReqBody: Send + 'static,
+{
+    /// Wrap the given response body, putting the given connection
+    /// back in the pool once the body is fully received.
+    pub(super) fn new(
+        inner: B,
+        pool: Pool,
+        key: PoolKey,
+        conn: SendRequest<ReqBody>,
+        info: ConnectionInfo,
+    ) -> Self {
+        let release = PoolRelease {
+            pool,
+            key,
+            conn,
+            info,
+        };
+        let release = if inner.is_end_stream() {
+            release.put();
+            None
+        } else {
+            Some(release)
+        };
+        Self { inner, release }
+    }
+}
+
+impl<B, ReqBody> http_body::Body for PooledBody<B, ReqBody>
+where
+    B: http_body::Body,
+    ReqBody: Send + 'static,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.inner.as_mut().poll_frame(cx));
+        match &frame {
+            Some(Ok(_)) if !this.inner.is_end_stream() => (),
+            Some(Err(_)) => drop(this.release.take()),
+            Some(Ok(_)) | None => {
+                if let Some(release) = this.release.take() {
+                    release.put();
+                }
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+fn oldest(entries: &[Idle]) -> Option<usize> {
+    entries
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, idle)| idle.last_used)
+        .map(|(index, _)| index)
+}
@@ -1,3 +1,4 @@
+use crate::client::proxy::is_http_proxy;
 use rama_core::{Context, Layer, Service};
 use rama_http_types::{
     headers::{HeaderMapExt, ProxyAuthorization},
@@ -79,7 +80,7 @@ where
         {
             // only do this for non-secure requests!!!
 
-            if let Some(pa) = ctx.get::<ProxyAddress>() {
+            if let Some(pa) = ctx.get::<ProxyAddress>().filter(|pa| is_http_proxy(pa)) {
                 if let Some(credential) = pa.credential.clone() {
                     match credential {
                         ProxyCredential::Basic(basic) => {
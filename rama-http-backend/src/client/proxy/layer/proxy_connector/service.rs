@@ -1,4 +1,5 @@
 use super::InnerHttpProxyConnector;
+use crate::client::proxy::is_http_proxy;
 use rama_core::{
     error::{BoxError, ErrorExt, OpaqueError},
     Context, Service,
@@ -20,7 +21,8 @@ use rama_net::tls::HttpsTunnel;
 /// A connector which can be used to establish a connection over an HTTP Proxy.
 ///
 /// This behaviour is optional and only triggered in case there
-/// is a [`ProxyAddress`] found in the [`Context`].
+/// is a [`ProxyAddress`] found in the [`Context`]. Socks5(h) proxies
+/// are ignored by this connector, as these are to be handled by a socks5 connector.
 pub struct HttpProxyConnector<S> {
     inner: S,
     required: bool,
@@ -83,7 +85,10 @@ where
         mut ctx: Context<State>,
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
-        let address = ctx.get::<ProxyAddress>().cloned();
+        let address = ctx
+            .get::<ProxyAddress>()
+            .filter(|address| is_http_proxy(address))
+            .cloned();
 
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
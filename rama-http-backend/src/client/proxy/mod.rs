@@ -1,3 +1,15 @@
 //! Client Http Proxy Connector Support.
 
 pub mod layer;
+
+use rama_net::address::ProxyAddress;
+
+/// Returns `true` in case the [`ProxyAddress`] is to be used as an http proxy,
+/// which is the case for any proxy not defined with a socks4(a) or socks5(h) protocol.
+pub(crate) fn is_http_proxy(address: &ProxyAddress) -> bool {
+    !address
+        .protocol
+        .as_ref()
+        .map(|p| p.is_socks4() || p.is_socks4a() || p.is_socks5() || p.is_socks5h())
+        .unwrap_or_default()
+}
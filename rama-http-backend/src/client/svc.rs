@@ -1,3 +1,4 @@
+use super::proxy::is_http_proxy;
 use rama_core::{
     error::{BoxError, OpaqueError},
     Context, Service,
@@ -17,6 +18,20 @@ pub(super) enum SendRequest<Body> {
     Http2(Mutex<hyper::client::conn::http2::SendRequest<Body>>),
 }
 
+impl<Body> SendRequest<Body> {
+    /// Clone the sender in case it is an Http/2 sender,
+    /// which can be used to multiplex requests over the same connection.
+    pub(super) fn try_clone_http2(&self) -> Option<Self> {
+        match self {
+            SendRequest::Http1(_) => None,
+            SendRequest::Http2(sender) => sender
+                .try_lock()
+                .ok()
+                .map(|sender| SendRequest::Http2(Mutex::new(sender.clone()))),
+        }
+    }
+}
+
 #[derive(Debug)]
 /// Internal http sender used to send the actual requests.
 pub struct HttpClientService<Body>(pub(super) SendRequest<Body>);
@@ -31,9 +46,25 @@ where
 
     async fn serve(
         &self,
-        mut ctx: Context<State>,
+        ctx: Context<State>,
         req: Request<Body>,
     ) -> Result<Self::Response, Self::Error> {
+        self.try_send(ctx, req).await.map_err(|(err, _)| err)
+    }
+}
+
+impl<Body> HttpClientService<Body>
+where
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    /// Send the request over this connection, returning the request
+    /// together with the error in case it was not sent at all,
+    /// e.g. because a pooled connection was closed in the meantime.
+    pub(super) async fn try_send<State>(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<Response, (BoxError, Option<Request<Body>>)> {
         // sanitize subject line request uri
         // because Hyper (http) writes the URI as-is
         //
@@ -42,14 +73,20 @@ where
         //
         // TODO: fix this in hyper fork (embedded in rama http core)
         // directly instead of here...
-        let req = sanitize_client_req_header(&mut ctx, req)?;
+        let req = sanitize_client_req_header(&mut ctx, req).map_err(|err| (err, None))?;
 
-        let resp = match &self.0 {
-            SendRequest::Http1(sender) => sender.lock().await.send_request(req).await,
-            SendRequest::Http2(sender) => sender.lock().await.send_request(req).await,
-        }?;
+        let result = match &self.0 {
+            SendRequest::Http1(sender) => sender.lock().await.try_send_request(req).await,
+            SendRequest::Http2(sender) => sender.lock().await.try_send_request(req).await,
+        };
 
-        Ok(resp.map(rama_http_types::Body::new))
+        match result {
+            Ok(resp) => Ok(resp.map(rama_http_types::Body::new)),
+            Err(mut err) => {
+                let req = err.take_message();
+                Err((err.into_error().into(), req))
+            }
+        }
     }
 }
 
@@ -67,7 +104,10 @@ fn sanitize_client_req_header<S, B>(
         }
         _ => {
             // GET | HEAD | POST | PUT | DELETE | OPTIONS | TRACE | PATCH
-            if !ctx.contains::<ProxyAddress>()
+            if !ctx
+                .get::<ProxyAddress>()
+                .map(is_http_proxy)
+                .unwrap_or_default()
                 && req.uri().host().is_some()
                 && req.version() <= Version::HTTP_11
             {
@@ -0,0 +1,188 @@
+//! HTTP/3 utilities shared by the h3 server and client.
+
+use bytes::{Buf, Bytes};
+use h3::error::StreamError;
+use rama_core::error::BoxError;
+use rama_http_types::{
+    dep::{
+        http_body::{self, Frame},
+        http_body_util::BodyExt,
+    },
+    HeaderMap,
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use sync_wrapper::SyncWrapper;
+
+/// The receiving half of an h3 request stream,
+/// implemented for both the server and client request streams.
+pub(crate) trait H3RecvStream: Send + Unpin + 'static {
+    fn poll_recv_data(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Bytes>, StreamError>>;
+
+    fn poll_recv_trailers(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, StreamError>>;
+}
+
+/// The sending half of an h3 request stream,
+/// implemented for both the server and client request streams.
+pub(crate) trait H3SendStream: Send {
+    fn send_data(
+        &mut self,
+        data: Bytes,
+    ) -> impl Future<Output = Result<(), StreamError>> + Send + '_;
+
+    fn send_trailers(
+        &mut self,
+        trailers: HeaderMap,
+    ) -> impl Future<Output = Result<(), StreamError>> + Send + '_;
+
+    fn finish(&mut self) -> impl Future<Output = Result<(), StreamError>> + Send + '_;
+}
+
+macro_rules! impl_h3_streams {
+    ($($stream:ident),+ $(,)?) => {
+        $(
+            impl H3RecvStream for h3::$stream::RequestStream<h3_quinn::RecvStream, Bytes> {
+                fn poll_recv_data(
+                    &mut self,
+                    cx: &mut Context<'_>,
+                ) -> Poll<Result<Option<Bytes>, StreamError>> {
+                    self.poll_recv_data(cx)
+                        .map_ok(|data| data.map(|mut data| data.copy_to_bytes(data.remaining())))
+                }
+
+                fn poll_recv_trailers(
+                    &mut self,
+                    cx: &mut Context<'_>,
+                ) -> Poll<Result<Option<HeaderMap>, StreamError>> {
+                    self.poll_recv_trailers(cx)
+                }
+            }
+
+            impl H3SendStream for h3::$stream::RequestStream<h3_quinn::SendStream<Bytes>, Bytes> {
+                fn send_data(
+                    &mut self,
+                    data: Bytes,
+                ) -> impl Future<Output = Result<(), StreamError>> + Send + '_ {
+                    self.send_data(data)
+                }
+
+                fn send_trailers(
+                    &mut self,
+                    trailers: HeaderMap,
+                ) -> impl Future<Output = Result<(), StreamError>> + Send + '_ {
+                    self.send_trailers(trailers)
+                }
+
+                fn finish(&mut self) -> impl Future<Output = Result<(), StreamError>> + Send + '_ {
+                    self.finish()
+                }
+            }
+        )+
+    };
+}
+
+impl_h3_streams!(server, client);
+
+/// A [`http_body::Body`] which receives its data and trailers
+/// from the receiving half of an h3 request stream.
+pub(crate) struct H3Body<S> {
+    stream: SyncWrapper<S>,
+    data_done: bool,
+    done: bool,
+}
+
+impl<S> H3Body<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        Self {
+            stream: SyncWrapper::new(stream),
+            data_done: false,
+            done: false,
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for H3Body<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("H3Body")
+            .field("data_done", &self.data_done)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl<S: H3RecvStream> http_body::Body for H3Body<S> {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        if !this.data_done {
+            match ready!(this.stream.get_mut().poll_recv_data(cx)) {
+                Ok(Some(data)) => return Poll::Ready(Some(Ok(Frame::data(data)))),
+                Ok(None) => this.data_done = true,
+                Err(err) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+            }
+        }
+
+        let result = ready!(this.stream.get_mut().poll_recv_trailers(cx));
+        this.done = true;
+        Poll::Ready(match result {
+            Ok(Some(trailers)) => Some(Ok(Frame::trailers(trailers))),
+            Ok(None) => None,
+            Err(err) => Some(Err(err.into())),
+        })
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+/// Send the given [`Body`] over the sending half of an h3 request stream,
+/// finishing the stream once all its frames are sent.
+pub(crate) async fn send_body<S, B>(stream: &mut S, mut body: B) -> Result<(), BoxError>
+where
+    S: H3SendStream,
+    B: http_body::Body<Error: Into<BoxError>> + Unpin,
+{
+    loop {
+        // map the error right away, as the body error is not required to be `Send`
+        let frame = match body.frame().await {
+            Some(frame) => frame.map_err(Into::into)?,
+            None => break,
+        };
+        let frame = match frame.into_data() {
+            Ok(mut data) => {
+                if data.has_remaining() {
+                    stream
+                        .send_data(data.copy_to_bytes(data.remaining()))
+                        .await?;
+                }
+                continue;
+            }
+            Err(frame) => frame,
+        };
+        if let Ok(trailers) = frame.into_trailers() {
+            stream.send_trailers(trailers).await?;
+            break;
+        }
+    }
+    stream.finish().await?;
+    Ok(())
+}
@@ -54,5 +54,9 @@
 
 pub mod client;
 pub mod server;
+pub mod ws;
 
 mod executor;
+
+#[cfg(feature = "http3")]
+mod h3_body;
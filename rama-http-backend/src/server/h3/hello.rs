@@ -0,0 +1,153 @@
+use quinn::{
+    crypto::{
+        self, ExportKeyingMaterialError, HeaderKey, KeyPair, Keys, PacketKey, UnsupportedVersion,
+    },
+    ConnectionId, Side,
+};
+use quinn_proto::{transport_parameters::TransportParameters, TransportError};
+use rama_net::tls::client::{parse_client_hello, ClientHello};
+use std::{any::Any, sync::Arc};
+
+/// Maximum amount of handshake bytes buffered to capture the [`ClientHello`].
+const MAX_CLIENT_HELLO_LEN: usize = 1 << 16;
+
+const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 0x01;
+
+/// A QUIC crypto [`crypto::ServerConfig`] which captures the [`ClientHello`]
+/// of each session, making it available via the handshake data of the connection,
+/// as [`CapturedHandshakeData`].
+pub(super) struct ClientHelloCaptureConfig {
+    inner: Arc<dyn crypto::ServerConfig>,
+}
+
+impl ClientHelloCaptureConfig {
+    pub(super) fn new(inner: Arc<dyn crypto::ServerConfig>) -> Self {
+        Self { inner }
+    }
+}
+
+impl crypto::ServerConfig for ClientHelloCaptureConfig {
+    fn initial_keys(
+        &self,
+        version: u32,
+        dst_cid: &ConnectionId,
+    ) -> Result<Keys, UnsupportedVersion> {
+        self.inner.initial_keys(version, dst_cid)
+    }
+
+    fn retry_tag(&self, version: u32, orig_dst_cid: &ConnectionId, packet: &[u8]) -> [u8; 16] {
+        self.inner.retry_tag(version, orig_dst_cid, packet)
+    }
+
+    fn start_session(
+        self: Arc<Self>,
+        version: u32,
+        params: &TransportParameters,
+    ) -> Box<dyn crypto::Session> {
+        Box::new(ClientHelloCaptureSession {
+            inner: self.inner.clone().start_session(version, params),
+            pending: Some(Vec::new()),
+            client_hello: None,
+        })
+    }
+}
+
+/// The handshake data of a connection accepted using the [`ClientHelloCaptureConfig`].
+pub(super) struct CapturedHandshakeData {
+    pub(super) client_hello: Option<ClientHello>,
+}
+
+struct ClientHelloCaptureSession {
+    inner: Box<dyn crypto::Session>,
+    pending: Option<Vec<u8>>,
+    client_hello: Option<ClientHello>,
+}
+
+impl ClientHelloCaptureSession {
+    fn capture(&mut self, buf: &[u8]) {
+        let Some(pending) = self.pending.as_mut() else {
+            return;
+        };
+        pending.extend_from_slice(buf);
+
+        if pending[0] != HANDSHAKE_TYPE_CLIENT_HELLO || pending.len() > MAX_CLIENT_HELLO_LEN {
+            tracing::debug!("h3 server: failed to capture client hello: unexpected handshake");
+            self.pending = None;
+            return;
+        }
+        let Some(header) = pending.get(1..4) else {
+            return;
+        };
+        let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+        let Some(message) = pending.get(4..4 + len) else {
+            return;
+        };
+        match parse_client_hello(message) {
+            Ok(hello) => self.client_hello = Some(hello),
+            Err(err) => tracing::debug!(%err, "h3 server: failed to parse client hello"),
+        }
+        self.pending = None;
+    }
+}
+
+impl crypto::Session for ClientHelloCaptureSession {
+    fn initial_keys(&self, dst_cid: &ConnectionId, side: Side) -> Keys {
+        self.inner.initial_keys(dst_cid, side)
+    }
+
+    fn handshake_data(&self) -> Option<Box<dyn Any>> {
+        // only used to signal that the handshake data is ready
+        let _ = self.inner.handshake_data()?;
+        Some(Box::new(CapturedHandshakeData {
+            client_hello: self.client_hello.clone(),
+        }))
+    }
+
+    fn peer_identity(&self) -> Option<Box<dyn Any>> {
+        self.inner.peer_identity()
+    }
+
+    fn early_crypto(&self) -> Option<(Box<dyn HeaderKey>, Box<dyn PacketKey>)> {
+        self.inner.early_crypto()
+    }
+
+    fn early_data_accepted(&self) -> Option<bool> {
+        self.inner.early_data_accepted()
+    }
+
+    fn is_handshaking(&self) -> bool {
+        self.inner.is_handshaking()
+    }
+
+    fn read_handshake(&mut self, buf: &[u8]) -> Result<bool, TransportError> {
+        if !buf.is_empty() {
+            self.capture(buf);
+        }
+        self.inner.read_handshake(buf)
+    }
+
+    fn transport_parameters(&self) -> Result<Option<TransportParameters>, TransportError> {
+        self.inner.transport_parameters()
+    }
+
+    fn write_handshake(&mut self, buf: &mut Vec<u8>) -> Option<Keys> {
+        self.inner.write_handshake(buf)
+    }
+
+    fn next_1rtt_keys(&mut self) -> Option<KeyPair<Box<dyn PacketKey>>> {
+        self.inner.next_1rtt_keys()
+    }
+
+    fn is_valid_retry(&self, orig_dst_cid: &ConnectionId, header: &[u8], payload: &[u8]) -> bool {
+        self.inner.is_valid_retry(orig_dst_cid, header, payload)
+    }
+
+    fn export_keying_material(
+        &self,
+        output: &mut [u8],
+        label: &[u8],
+        context: &[u8],
+    ) -> Result<(), ExportKeyingMaterialError> {
+        self.inner.export_keying_material(output, label, context)
+    }
+}
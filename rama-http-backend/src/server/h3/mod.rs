@@ -0,0 +1,386 @@
+//! HTTP/3 server support, serving rama http [`Service`]s over QUIC.
+//!
+//! See [`HttpServer::h3`] to create an HTTP/3 server
+//! and [`AltSvcLayer`] to advertise it to clients of the HTTP/1.1 and h2 servers.
+//!
+//! [`HttpServer::h3`]: super::HttpServer::h3
+//! [`AltSvcLayer`]: super::layer::alt_svc::AltSvcLayer
+
+use super::HttpServeResult;
+use crate::h3_body::{send_body, H3Body};
+use bytes::Bytes;
+use quinn::crypto::rustls::QuicServerConfig;
+use rama_core::{
+    error::{ErrorContext, OpaqueError},
+    graceful::ShutdownGuard,
+    rt::Executor,
+    Context, Service,
+};
+use rama_http_types::{Body, BodyLimit, IntoResponse, Request};
+use rama_net::{
+    stream::SocketInfo,
+    tls::{server::ClientIdentity, SecureTransport},
+};
+use rama_tls::rustls::dep::{pki_types::CertificateDer, rustls::ServerConfig};
+use rama_utils::future::Fuse;
+use std::{convert::Infallible, net::SocketAddr, pin::pin, sync::Arc};
+
+mod hello;
+use hello::{CapturedHandshakeData, ClientHelloCaptureConfig};
+
+/// The ALPN protocol identifier of HTTP/3.
+const ALPN_H3: &[u8] = b"h3";
+
+#[derive(Debug, Clone)]
+/// The builder used by the [`HttpServer`] to serve HTTP/3 connections.
+///
+/// Created using [`HttpServer::h3`] and configured using [`HttpServer::h3_mut`].
+///
+/// [`HttpServer`]: super::HttpServer
+/// [`HttpServer::h3`]: super::HttpServer::h3
+/// [`HttpServer::h3_mut`]: super::HttpServer::h3_mut
+pub struct H3ConnBuilder {
+    exec: Executor,
+    max_field_section_size: Option<u64>,
+    send_grease: bool,
+    store_client_hello: bool,
+}
+
+impl H3ConnBuilder {
+    pub(crate) fn new(exec: Executor) -> Self {
+        Self {
+            exec,
+            max_field_section_size: None,
+            send_grease: true,
+            store_client_hello: false,
+        }
+    }
+
+    /// Create the QUIC [`quinn::ServerConfig`] used to accept HTTP/3 connections
+    /// for the given rustls [`ServerConfig`].
+    ///
+    /// The `h3` ALPN protocol is set in case the [`ServerConfig`] defines no ALPN protocols.
+    pub(crate) fn quic_server_config(
+        &self,
+        tls_config: Arc<ServerConfig>,
+    ) -> Result<quinn::ServerConfig, OpaqueError> {
+        let tls_config = if tls_config.alpn_protocols.is_empty() {
+            let mut tls_config = (*tls_config).clone();
+            tls_config.alpn_protocols = vec![ALPN_H3.to_vec()];
+            Arc::new(tls_config)
+        } else {
+            tls_config
+        };
+
+        let crypto: Arc<dyn quinn::crypto::ServerConfig> = Arc::new(
+            QuicServerConfig::try_from(tls_config)
+                .context("h3 server: create quic server config")?,
+        );
+        let crypto: Arc<dyn quinn::crypto::ServerConfig> = if self.store_client_hello {
+            Arc::new(ClientHelloCaptureConfig::new(crypto))
+        } else {
+            crypto
+        };
+        Ok(quinn::ServerConfig::with_crypto(crypto))
+    }
+
+    /// Serve the incoming QUIC connection as HTTP/3,
+    /// serving each of its requests using the given [`Service`].
+    pub(crate) async fn serve_connection<State, S, Response>(
+        &self,
+        mut ctx: Context<State>,
+        incoming: quinn::Incoming,
+        service: Arc<S>,
+    ) -> HttpServeResult
+    where
+        State: Send + Sync + 'static,
+        S: Service<State, Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+    {
+        let conn = incoming
+            .await
+            .context("h3 server: accept quic connection")?;
+        insert_connection_info(&mut ctx, &conn);
+
+        let mut builder = h3::server::builder();
+        builder.send_grease(self.send_grease);
+        if let Some(size) = self.max_field_section_size {
+            builder.max_field_section_size(size);
+        }
+        let mut conn: h3::server::Connection<_, Bytes> = builder
+            .build(h3_quinn::Connection::new(conn))
+            .await
+            .context("h3 server: establish h3 connection")?;
+
+        let mut cancelled_fut = pin!(Fuse::new(cancelled(ctx.guard().cloned())));
+        loop {
+            let result = tokio::select! {
+                _ = cancelled_fut.as_mut() => {
+                    tracing::trace!("signal received: initiate graceful shutdown");
+                    conn.shutdown(0).await.context("h3 server: shutdown connection")?;
+                    continue;
+                }
+                result = conn.accept() => result,
+            };
+
+            let resolver = match result {
+                Ok(Some(resolver)) => resolver,
+                Ok(None) => return Ok(()),
+                Err(err) if err.is_h3_no_error() => return Ok(()),
+                Err(err) => return Err(err.into()),
+            };
+
+            let ctx = ctx.clone();
+            let service = service.clone();
+            self.exec.spawn_task(async move {
+                let (req, stream) = match resolver.resolve_request().await {
+                    Ok(request) => request,
+                    Err(err) => {
+                        tracing::debug!(%err, "h3 server: failed to resolve request");
+                        return;
+                    }
+                };
+                if let Err(err) = serve_request(ctx, req, stream, service).await {
+                    tracing::debug!(err = %err, "h3 server: failed to serve request");
+                }
+            });
+        }
+    }
+}
+
+/// A configuration builder for HTTP/3 server connections.
+#[derive(Debug)]
+pub struct H3Config<'a> {
+    inner: &'a mut H3ConnBuilder,
+}
+
+impl<'a> H3Config<'a> {
+    pub(crate) fn new(inner: &'a mut H3ConnBuilder) -> Self {
+        Self { inner }
+    }
+
+    /// Set the maximum size of the (decoded) header section the client is allowed to send.
+    ///
+    /// Default is unlimited.
+    pub fn max_field_section_size(&mut self, size: u64) -> &mut Self {
+        self.inner.max_field_section_size = Some(size);
+        self
+    }
+
+    /// Set whether to send GREASE settings, frames and streams,
+    /// as to exercise the extension points of the clients.
+    ///
+    /// Default is true.
+    pub fn send_grease(&mut self, enabled: bool) -> &mut Self {
+        self.inner.send_grease = enabled;
+        self
+    }
+
+    /// Set whether to store the [`ClientHello`] of the QUIC handshake.
+    ///
+    /// When enabled, the [`SecureTransport`] inserted in the [`Context`] contains
+    /// the [`ClientHello`], and its [`Ja3`], [`Ja4`] and [`QuicTransportParameters`]
+    /// are inserted in the [`Context`] as well.
+    ///
+    /// Default is false.
+    ///
+    /// [`ClientHello`]: rama_net::tls::client::ClientHello
+    /// [`Ja3`]: rama_net::tls::client::Ja3
+    /// [`Ja4`]: rama_net::tls::client::Ja4
+    /// [`QuicTransportParameters`]: rama_net::tls::client::QuicTransportParameters
+    pub fn store_client_hello(&mut self, enabled: bool) -> &mut Self {
+        self.inner.store_client_hello = enabled;
+        self
+    }
+}
+
+/// Accept the QUIC connections of the [`quinn::Endpoint`],
+/// serving each of them using the given [`Service`] until the endpoint is closed
+/// or the (optional) [`ShutdownGuard`] is cancelled.
+pub(crate) async fn serve_endpoint<State, S>(
+    ctx: Context<State>,
+    endpoint: quinn::Endpoint,
+    service: S,
+    guard: Option<ShutdownGuard>,
+) where
+    State: Send + Sync + 'static,
+    S: Service<State, quinn::Incoming>,
+{
+    let local_addr = endpoint.local_addr().ok();
+    let service = Arc::new(service);
+    let mut cancelled_fut = pin!(cancelled(guard));
+
+    loop {
+        let incoming = tokio::select! {
+            _ = cancelled_fut.as_mut() => {
+                tracing::trace!("signal received: initiate graceful shutdown");
+                break;
+            }
+            incoming = endpoint.accept() => match incoming {
+                Some(incoming) => incoming,
+                None => break,
+            },
+        };
+
+        let service = service.clone();
+        let mut ctx = ctx.clone();
+        let exec = ctx.executor().clone();
+        exec.spawn_task(async move {
+            ctx.insert(SocketInfo::new(local_addr, incoming.remote_address()));
+            let _ = service.serve(ctx, incoming).await;
+        });
+    }
+}
+
+/// Bind a QUIC [`quinn::Endpoint`] to the given address, accepting connections
+/// using the given [`quinn::ServerConfig`].
+pub(crate) fn bind_endpoint(
+    addr: SocketAddr,
+    config: quinn::ServerConfig,
+) -> Result<quinn::Endpoint, OpaqueError> {
+    quinn::Endpoint::server(config, addr).context("h3 server: bind quic endpoint")
+}
+
+async fn cancelled(guard: Option<ShutdownGuard>) {
+    match guard {
+        Some(guard) => guard.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Insert the [`SecureTransport`] (and the fingerprints derived from its [`ClientHello`])
+/// and the [`ClientIdentity`] of the QUIC connection in the [`Context`].
+///
+/// [`ClientHello`]: rama_net::tls::client::ClientHello
+fn insert_connection_info<State>(ctx: &mut Context<State>, conn: &quinn::Connection) {
+    let client_hello = conn
+        .handshake_data()
+        .and_then(|data| data.downcast::<CapturedHandshakeData>().ok())
+        .and_then(|data| data.client_hello);
+
+    let secure_transport = match client_hello {
+        Some(hello) => {
+            ctx.insert(hello.ja3());
+            ctx.insert(hello.ja4());
+            if let Some(params) = hello.ext_quic_transport_parameters() {
+                ctx.insert(params);
+            }
+            SecureTransport::with_client_hello(hello)
+        }
+        None => SecureTransport::default(),
+    };
+    ctx.insert(secure_transport);
+
+    let Some(certs) = conn
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+    else {
+        return;
+    };
+    match ClientIdentity::from_der_chain(certs.iter().map(|cert| cert.as_ref())) {
+        Ok(identity) => {
+            ctx.insert(identity);
+        }
+        Err(err) => tracing::warn!(err = %err, "failed to parse quic peer certificate"),
+    }
+}
+
+async fn serve_request<State, S, Response>(
+    ctx: Context<State>,
+    req: Request<()>,
+    stream: h3::server::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+    service: Arc<S>,
+) -> HttpServeResult
+where
+    State: Send + Sync + 'static,
+    S: Service<State, Request, Response = Response, Error = Infallible>,
+    Response: IntoResponse + Send + 'static,
+{
+    let (mut send, recv) = stream.split();
+
+    let body_limit = ctx.get::<BodyLimit>().cloned();
+    let body = H3Body::new(recv);
+    let req = req.map(|()| match body_limit.and_then(|limit| limit.request()) {
+        Some(limit) => Body::with_limit(body, limit),
+        None => Body::new(body),
+    });
+
+    let resp = service.serve(ctx, req).await.into_response();
+    let resp = match body_limit.and_then(|limit| limit.response()) {
+        Some(limit) => resp.map(|body| Body::with_limit(body, limit)),
+        None => resp,
+    };
+
+    let (parts, body) = resp.into_parts();
+    send.send_response(rama_http_types::Response::from_parts(parts, ()))
+        .await
+        .context("h3 server: send response")?;
+    send_body(&mut send, body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::H3Connector, server::HttpServer};
+    use rama_core::service::service_fn;
+    use rama_http_types::{BodyExtractExt, Response};
+    use rama_net::{
+        client::EstablishedClientConnection,
+        tls::client::{Ja4, QuicTransportParameters},
+    };
+    use rama_tcp::client::IpPreference;
+    use rama_tls::{
+        dep::rcgen,
+        rustls::dep::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+    };
+
+    fn self_signed_server_config() -> Arc<ServerConfig> {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["localhost".to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der())),
+            )
+            .unwrap();
+        Arc::new(config)
+    }
+
+    #[tokio::test]
+    async fn test_h3_roundtrip_with_client_hello() {
+        let mut server = HttpServer::h3(Executor::default());
+        server.h3_mut().store_client_hello(true);
+        let config = server
+            .quic_server_config(self_signed_server_config())
+            .unwrap();
+        let endpoint = bind_endpoint(([127, 0, 0, 1], 0).into(), config).unwrap();
+        let addr = endpoint.local_addr().unwrap();
+
+        let svc = server.service(service_fn(|ctx: Context<()>, req: Request| async move {
+            let ja4 = ctx.get::<Ja4>().unwrap().to_string();
+            assert!(ctx.get::<QuicTransportParameters>().is_some());
+            let body = req.into_body().try_into_string().await.unwrap();
+            Ok::<_, Infallible>(Response::new(Body::from(format!("{ja4} {body}"))))
+        }));
+        tokio::spawn(serve_endpoint(Context::default(), endpoint, svc, None));
+
+        let req = Request::builder()
+            .uri(format!("https://localhost:{}/", addr.port()))
+            .version(rama_http_types::Version::HTTP_3)
+            .body(Body::from("hello"))
+            .unwrap();
+        let mut ctx = Context::default();
+        ctx.insert(IpPreference::Ipv4Only);
+        let EstablishedClientConnection { ctx, req, conn, .. } =
+            H3Connector::new().serve(ctx, req).await.unwrap();
+        let resp = conn.serve(ctx, req).await.unwrap();
+
+        let body = resp.try_into_string().await.unwrap();
+        assert!(body.starts_with("q13d"), "unexpected ja4: {body}");
+        assert!(body.ends_with(" hello"), "unexpected body: {body}");
+    }
+}
@@ -0,0 +1,175 @@
+//! middleware to advertise alternative services (e.g. HTTP/3) using the `Alt-Svc` header
+//!
+//! See [`AltSvcLayer`] for more details.
+//!
+//! # Reference
+//!
+//! - <https://www.rfc-editor.org/rfc/rfc7838.html>
+//! - <https://www.rfc-editor.org/rfc/rfc9114.html#section-3.1.1>
+
+use rama_core::{Context, Layer, Service};
+use rama_http_types::{header::ALT_SVC, HeaderValue, Request, Response};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, time::Duration};
+
+/// The default max age of an advertised alternative service,
+/// as defined by [RFC 7838](https://www.rfc-editor.org/rfc/rfc7838.html#section-3.1).
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+/// A [`Layer`] that produces an [`AltSvcService`],
+/// advertising an HTTP/3 server to the clients of the wrapped service.
+///
+/// Typically used for the HTTP/1.1 and h2 server of an origin which
+/// is also served over HTTP/3 (see `HttpServer::h3`), such that
+/// clients can switch to HTTP/3 for their future requests.
+///
+/// The `Alt-Svc` header is only added in case the response doesn't define one already.
+pub struct AltSvcLayer {
+    port: u16,
+    max_age: Duration,
+}
+
+impl AltSvcLayer {
+    /// Create a new [`AltSvcLayer`], advertising an HTTP/3 server
+    /// on the given (UDP) port of the same host.
+    pub const fn h3(port: u16) -> Self {
+        Self {
+            port,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// Set the duration for which the client can consider the alternative service fresh.
+    ///
+    /// Default is 24 hours.
+    pub fn set_max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Replace this [`AltSvcLayer`] with the max age set.
+    ///
+    /// See [`AltSvcLayer::set_max_age`] for more information.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    fn header_value(&self) -> HeaderValue {
+        let value = format!(r#"h3=":{}"; ma={}"#, self.port, self.max_age.as_secs());
+        HeaderValue::try_from(value).expect("alt-svc header value is always valid")
+    }
+}
+
+impl<S> Layer<S> for AltSvcLayer {
+    type Service = AltSvcService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AltSvcService {
+            inner,
+            value: self.header_value(),
+        }
+    }
+}
+
+/// A [`Service`] which adds an `Alt-Svc` header to the responses of the inner service.
+///
+/// See [`AltSvcLayer`] for more details.
+pub struct AltSvcService<S> {
+    inner: S,
+    value: HeaderValue,
+}
+
+impl<S> AltSvcService<S> {
+    /// Create a new [`AltSvcService`], advertising an HTTP/3 server
+    /// on the given (UDP) port of the same host.
+    pub fn h3(inner: S, port: u16) -> Self {
+        AltSvcLayer::h3(port).layer(inner)
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for AltSvcService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AltSvcService")
+            .field("inner", &self.inner)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for AltSvcService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<State, S, ReqBody, ResBody> Service<State, Request<ReqBody>> for AltSvcService<S>
+where
+    State: Send + Sync + 'static,
+    S: Service<State, Request<ReqBody>, Response = Response<ResBody>>,
+    ReqBody: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let mut res = self.inner.serve(ctx, req).await?;
+        if !res.headers().contains_key(ALT_SVC) {
+            res.headers_mut().insert(ALT_SVC, self.value.clone());
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use rama_http_types::Body;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_alt_svc_h3() {
+        let svc = AltSvcLayer::h3(8443)
+            .with_max_age(Duration::from_secs(3600))
+            .layer(service_fn(|_req: Request| async move {
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            }));
+
+        let res = svc
+            .serve(Context::default(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(res.headers()[ALT_SVC], r#"h3=":8443"; ma=3600"#);
+    }
+
+    #[tokio::test]
+    async fn test_alt_svc_keeps_existing_header() {
+        let svc = AltSvcService::h3(
+            service_fn(|_req: Request| async move {
+                let mut res = Response::new(Body::empty());
+                res.headers_mut()
+                    .insert(ALT_SVC, HeaderValue::from_static("clear"));
+                Ok::<_, Infallible>(res)
+            }),
+            443,
+        );
+
+        let res = svc
+            .serve(Context::default(), Request::new(Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(res.headers()[ALT_SVC], "clear");
+    }
+}
@@ -1,3 +1,4 @@
 //! rama http backend server layers
 
+pub mod alt_svc;
 pub mod upgrade;
@@ -11,3 +11,6 @@ mod hyper_conn;
 mod svc_hyper;
 
 pub mod layer;
+
+#[cfg(feature = "http3")]
+pub mod h3;
@@ -21,9 +21,17 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::ToSocketAddrs;
 
+#[cfg(feature = "http3")]
+use super::h3::{bind_endpoint, serve_endpoint, H3Config, H3ConnBuilder};
+#[cfg(feature = "http3")]
+use rama_core::error::{BoxError, ErrorContext, OpaqueError};
+#[cfg(feature = "http3")]
+use rama_tls::rustls::dep::rustls::ServerConfig;
+
 /// A builder for configuring and listening over HTTP using a [`Service`].
 ///
 /// Supported Protocols: HTTP/1, H2, Auto (HTTP/1 + H2)
+/// and H3 (over QUIC, requires the `http3` feature).
 ///
 /// [`Service`]: rama_core::Service
 pub struct HttpServer<B> {
@@ -733,6 +741,176 @@ where
     }
 }
 
+#[cfg(feature = "http3")]
+impl HttpServer<H3ConnBuilder> {
+    /// Create a new h3 `Builder` with default settings.
+    pub fn h3(exec: Executor) -> Self {
+        Self {
+            builder: H3ConnBuilder::new(exec),
+        }
+    }
+
+    /// H3 configuration.
+    pub fn h3_mut(&mut self) -> H3Config<'_> {
+        H3Config::new(&mut self.builder)
+    }
+
+    /// Create the QUIC [`quinn::ServerConfig`] used to accept HTTP/3 connections,
+    /// securing them using the given rustls [`ServerConfig`].
+    ///
+    /// The `h3` ALPN protocol is set in case the [`ServerConfig`] defines no ALPN protocols.
+    ///
+    /// Use it to create your own [`quinn::Endpoint`], of which the accepted
+    /// connections can be served using [`Self::serve`] or [`Self::service`].
+    pub fn quic_server_config(
+        &self,
+        tls_config: Arc<ServerConfig>,
+    ) -> Result<quinn::ServerConfig, OpaqueError> {
+        self.builder.quic_server_config(tls_config)
+    }
+
+    /// Turn this `HttpServer` into a [`Service`] that can be used to serve
+    /// incoming QUIC connections as HTTP/3.
+    pub fn service<State, S, Response>(self, service: S) -> HttpService<H3ConnBuilder, S, State>
+    where
+        S: Service<State, Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+    {
+        HttpService::new(self.builder, service)
+    }
+
+    /// Serve a single incoming QUIC connection as HTTP/3.
+    pub async fn serve<State, S, Response>(
+        &self,
+        ctx: Context<State>,
+        incoming: quinn::Incoming,
+        service: S,
+    ) -> HttpServeResult
+    where
+        State: Send + Sync + 'static,
+        S: Service<State, Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+    {
+        self.builder
+            .serve_connection(ctx, incoming, Arc::new(service))
+            .await
+    }
+
+    /// Listen for QUIC connections on the given address, serving HTTP/3 connections
+    /// secured using the given rustls [`ServerConfig`].
+    ///
+    /// It's a shortcut in case you don't need to operate on the transport layer directly.
+    pub async fn listen<S, Response, A>(
+        self,
+        addr: A,
+        tls_config: Arc<ServerConfig>,
+        service: S,
+    ) -> HttpServeResult
+    where
+        S: Service<(), Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+        A: ToSocketAddrs,
+    {
+        self.listen_with_ctx(Context::default(), addr, tls_config, service, None)
+            .await
+    }
+
+    /// Listen gracefully for QUIC connections on the given address, serving HTTP/3 connections.
+    ///
+    /// Same as [`Self::listen`], but it will respect the given [`ShutdownGuard`],
+    /// and also pass it to the service.
+    ///
+    /// [`ShutdownGuard`]: rama_core::graceful::ShutdownGuard
+    pub async fn listen_graceful<S, Response, A>(
+        self,
+        guard: ShutdownGuard,
+        addr: A,
+        tls_config: Arc<ServerConfig>,
+        service: S,
+    ) -> HttpServeResult
+    where
+        S: Service<(), Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+        A: ToSocketAddrs,
+    {
+        let ctx = Context::new(Arc::new(()), Executor::graceful(guard.clone()));
+        self.listen_with_ctx(ctx, addr, tls_config, service, Some(guard))
+            .await
+    }
+
+    /// Listen for QUIC connections on the given address, serving HTTP/3 connections.
+    ///
+    /// Same as [`Self::listen`], but including the given state in the [`Service`]'s [`Context`].
+    ///
+    /// [`Service`]: rama_core::Service
+    /// [`Context`]: rama_core::Context
+    pub async fn listen_with_state<State, S, Response, A>(
+        self,
+        state: State,
+        addr: A,
+        tls_config: Arc<ServerConfig>,
+        service: S,
+    ) -> HttpServeResult
+    where
+        State: Send + Sync + 'static,
+        S: Service<State, Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+        A: ToSocketAddrs,
+    {
+        let ctx = Context::new(Arc::new(state), Executor::new());
+        self.listen_with_ctx(ctx, addr, tls_config, service, None)
+            .await
+    }
+
+    /// Listen gracefully for QUIC connections on the given address, serving HTTP/3 connections.
+    ///
+    /// Same as [`Self::listen_graceful`], but including the given state in the [`Service`]'s [`Context`].
+    ///
+    /// [`Service`]: rama_core::Service
+    /// [`Context`]: rama_core::Context
+    pub async fn listen_graceful_with_state<State, S, Response, A>(
+        self,
+        guard: ShutdownGuard,
+        state: State,
+        addr: A,
+        tls_config: Arc<ServerConfig>,
+        service: S,
+    ) -> HttpServeResult
+    where
+        State: Send + Sync + 'static,
+        S: Service<State, Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+        A: ToSocketAddrs,
+    {
+        let ctx = Context::new(Arc::new(state), Executor::graceful(guard.clone()));
+        self.listen_with_ctx(ctx, addr, tls_config, service, Some(guard))
+            .await
+    }
+
+    async fn listen_with_ctx<State, S, Response, A>(
+        self,
+        ctx: Context<State>,
+        addr: A,
+        tls_config: Arc<ServerConfig>,
+        service: S,
+        guard: Option<ShutdownGuard>,
+    ) -> HttpServeResult
+    where
+        State: Send + Sync + 'static,
+        S: Service<State, Request, Response = Response, Error = Infallible>,
+        Response: IntoResponse + Send + 'static,
+        A: ToSocketAddrs,
+    {
+        let addr = tokio::net::lookup_host(addr)
+            .await?
+            .next()
+            .context("h3 server: resolve listen address")?;
+        let endpoint = bind_endpoint(addr, self.quic_server_config(tls_config)?)?;
+        serve_endpoint(ctx, endpoint, self.service(service), guard).await;
+        Ok(())
+    }
+}
+
 /// A [`Service`] that can be used to serve IO Byte streams (e.g. a TCP Stream) as HTTP.
 pub struct HttpService<B, S, State> {
     builder: Arc<B>,
@@ -786,3 +964,23 @@ where
         self.builder.hyper_serve_connection(ctx, stream, service)
     }
 }
+
+#[cfg(feature = "http3")]
+impl<State, S, Response> Service<State, quinn::Incoming> for HttpService<H3ConnBuilder, S, State>
+where
+    State: Send + Sync + 'static,
+    S: Service<State, Request, Response = Response, Error = Infallible>,
+    Response: IntoResponse + Send + 'static,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    fn serve(
+        &self,
+        ctx: Context<State>,
+        incoming: quinn::Incoming,
+    ) -> impl Future<Output = Result<Self::Response, Self::Error>> + Send + '_ {
+        let service = self.service.clone();
+        self.builder.serve_connection(ctx, incoming, service)
+    }
+}
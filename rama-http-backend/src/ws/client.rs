@@ -0,0 +1,298 @@
+use super::{
+    handshake::{accept_key, header_contains_token},
+    Role, WebSocket, WebSocketConfig,
+};
+use crate::{client::DisableConnectionPool, server::layer::upgrade::Upgraded};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use rama_core::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    Context, Service,
+};
+use rama_http_types::{
+    header::{
+        CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
+    },
+    HeaderValue, Method, Request, Response, Scheme, StatusCode, Uri, Version,
+};
+use rama_utils::macros::define_inner_service_accessors;
+
+#[derive(Debug, Clone)]
+/// A [`Service`] which establishes a client [`WebSocket`] by performing
+/// the opening handshake using the inner http client, e.g. the [`HttpClient`].
+///
+/// The request is sent as an HTTP/1.1 `GET` request, of which the `ws` and `wss` schemes
+/// are mapped to `http` and `https` respectively. The handshake headers are set
+/// by this connector, overwriting those already present. Connection pooling is disabled
+/// for the request, as the connection is taken over by the [`WebSocket`].
+///
+/// [`HttpClient`]: crate::client::HttpClient
+pub struct WebSocketConnector<S> {
+    inner: S,
+    config: WebSocketConfig,
+    protocols: Vec<String>,
+}
+
+impl<S> WebSocketConnector<S> {
+    /// Create a new [`WebSocketConnector`].
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            config: WebSocketConfig::default(),
+            protocols: Vec::new(),
+        }
+    }
+
+    /// Set the [`WebSocketConfig`] of the established [`WebSocket`]s.
+    pub fn set_config(&mut self, config: WebSocketConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// Replace this [`WebSocketConnector`] with the [`WebSocketConfig`]
+    /// of the established [`WebSocket`]s set.
+    pub fn with_config(mut self, config: WebSocketConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the subprotocols to offer, in order of preference.
+    pub fn set_protocols<I, P>(&mut self, protocols: I) -> &mut Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Replace this [`WebSocketConnector`] with the subprotocols to offer,
+    /// in order of preference, set.
+    pub fn with_protocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    define_inner_service_accessors!();
+
+    /// Validate the handshake response of the server,
+    /// returning the [`WebSocket`] builder function for the upgraded connection.
+    fn validate(
+        &self,
+        resp: &Response,
+        key: &HeaderValue,
+    ) -> Result<impl FnOnce(Upgraded) -> WebSocket<Upgraded>, OpaqueError> {
+        if resp.status() != StatusCode::SWITCHING_PROTOCOLS {
+            return Err(OpaqueError::from_display(format!(
+                "websocket: handshake failed: unexpected status {}",
+                resp.status()
+            )));
+        }
+
+        let headers = resp.headers();
+        if !header_contains_token(headers, &CONNECTION, "upgrade")
+            || !header_contains_token(headers, &UPGRADE, "websocket")
+        {
+            return Err(OpaqueError::from_display(
+                "websocket: handshake failed: missing connection upgrade",
+            ));
+        }
+        if headers.get(SEC_WEBSOCKET_ACCEPT) != Some(&accept_key(key.as_bytes())) {
+            return Err(OpaqueError::from_display(
+                "websocket: handshake failed: invalid accept key",
+            ));
+        }
+
+        let protocol = match headers.get(SEC_WEBSOCKET_PROTOCOL) {
+            Some(value) => {
+                let protocol = value
+                    .to_str()
+                    .ok()
+                    .filter(|protocol| self.protocols.iter().any(|p| p == protocol))
+                    .ok_or_else(|| {
+                        OpaqueError::from_display(
+                            "websocket: handshake failed: server selected protocol not offered",
+                        )
+                    })?;
+                Some(protocol.to_owned())
+            }
+            None => None,
+        };
+
+        let per_message_deflate = match self.config.per_message_deflate() {
+            Some(config) => config.negotiate_response(headers)?,
+            None if headers.contains_key(SEC_WEBSOCKET_EXTENSIONS) => {
+                return Err(OpaqueError::from_display(
+                    "websocket: handshake failed: server accepted extension(s) not offered",
+                ))
+            }
+            None => None,
+        };
+
+        let config = self.config.clone();
+        Ok(move |upgraded| {
+            let mut socket = WebSocket::new(upgraded, Role::Client, config);
+            if let Some(protocol) = protocol {
+                socket = socket.with_protocol(protocol);
+            }
+            if let Some(negotiated) = per_message_deflate.as_ref() {
+                socket = socket.with_per_message_deflate(negotiated);
+            }
+            socket
+        })
+    }
+}
+
+impl<S, State, Body> Service<State, Request<Body>> for WebSocketConnector<S>
+where
+    State: Send + Sync + 'static,
+    S: Service<State, Request<Body>, Response = Response, Error: Into<BoxError>>,
+    Body: Send + 'static,
+{
+    type Response = WebSocket<Upgraded>;
+    type Error = OpaqueError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        let (mut parts, body) = req.into_parts();
+
+        let mut uri_parts = parts.uri.into_parts();
+        uri_parts.scheme = match uri_parts.scheme {
+            Some(scheme) if scheme.as_str().eq_ignore_ascii_case("ws") => Some(Scheme::HTTP),
+            Some(scheme) if scheme.as_str().eq_ignore_ascii_case("wss") => Some(Scheme::HTTPS),
+            scheme => scheme,
+        };
+        parts.uri = Uri::from_parts(uri_parts).context("websocket: invalid request uri")?;
+        parts.method = Method::GET;
+        parts.version = Version::HTTP_11;
+
+        let key = HeaderValue::try_from(BASE64.encode(rand::random::<[u8; 16]>()))
+            .expect("base64 is a valid header value");
+        let headers = &mut parts.headers;
+        headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+        headers.insert(SEC_WEBSOCKET_KEY, key.clone());
+        if self.protocols.is_empty() {
+            headers.remove(SEC_WEBSOCKET_PROTOCOL);
+        } else {
+            headers.insert(
+                SEC_WEBSOCKET_PROTOCOL,
+                HeaderValue::try_from(self.protocols.join(", "))
+                    .context("websocket: invalid protocol")?,
+            );
+        }
+        match self.config.per_message_deflate() {
+            Some(config) => {
+                headers.insert(SEC_WEBSOCKET_EXTENSIONS, config.header_value());
+            }
+            None => {
+                headers.remove(SEC_WEBSOCKET_EXTENSIONS);
+            }
+        }
+
+        ctx.insert(DisableConnectionPool::new());
+
+        let uri = parts.uri.clone();
+        let mut resp = self
+            .inner
+            .serve(ctx, Request::from_parts(parts, body))
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()))
+            .with_context(|| format!("websocket: send upgrade request to {uri}"))?;
+
+        let new_socket = self.validate(&resp, &key)?;
+        let upgraded = hyper::upgrade::on(&mut resp)
+            .await
+            .context("websocket: upgrade connection")?;
+        Ok(new_socket(Upgraded::new(upgraded)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::HttpClient,
+        server::{layer::upgrade::UpgradeLayer, HttpServer},
+        ws::{
+            Message, PerMessageDeflateConfig, WebSocketAcceptor, WebSocketMatcher, WebSocketService,
+        },
+    };
+    use rama_core::{rt::Executor, service::service_fn, Layer};
+    use rama_http_types::{Body, IntoResponse};
+    use rama_tcp::server::TcpListener;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_websocket_connector_echo() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let config =
+            WebSocketConfig::new().with_per_message_deflate(PerMessageDeflateConfig::new());
+        let http_service = HttpServer::auto(Executor::default()).service(
+            UpgradeLayer::new(
+                WebSocketMatcher::new(),
+                WebSocketAcceptor::new()
+                    .with_protocols(["echo"])
+                    .with_config(config.clone()),
+                WebSocketService::new(service_fn(|mut socket: WebSocket<Upgraded>| async move {
+                    assert_eq!(socket.protocol(), Some("echo"));
+                    while let Some(message) = socket.recv().await? {
+                        if message.is_data() {
+                            socket.send(message).await?;
+                        }
+                    }
+                    Ok::<_, OpaqueError>(())
+                })),
+            )
+            .layer(service_fn(|| async {
+                Ok::<_, Infallible>(StatusCode::NOT_FOUND.into_response())
+            })),
+        );
+        tokio::spawn(listener.serve(http_service));
+
+        let connector = WebSocketConnector::new(HttpClient::default())
+            .with_protocols(["chat", "echo"])
+            .with_config(config);
+        let req = Request::builder()
+            .uri(format!("ws://{addr}/echo"))
+            .body(Body::empty())
+            .unwrap();
+        let mut socket = connector.serve(Context::default(), req).await.unwrap();
+        assert_eq!(socket.protocol(), Some("echo"));
+
+        for message in [Message::text("hello"), Message::binary(vec![0xab; 10_000])] {
+            socket.send(message.clone()).await.unwrap();
+            assert_eq!(socket.recv().await.unwrap(), Some(message));
+        }
+        socket.close(None).await.unwrap();
+
+        // the connector fails in case the server does not upgrade the connection
+        let req = Request::builder()
+            .uri(format!("http://{addr}/echo"))
+            .body(Body::empty())
+            .unwrap();
+        assert!(
+            WebSocketConnector::new(service_fn(|req: Request<Body>| async move {
+                // strip the upgrade header, such that the server does not match the request
+                let (mut parts, body) = req.into_parts();
+                parts.headers.remove(UPGRADE);
+                HttpClient::default()
+                    .serve(Context::default(), Request::from_parts(parts, body))
+                    .await
+            }))
+            .serve(Context::default(), req)
+            .await
+            .is_err()
+        );
+    }
+}
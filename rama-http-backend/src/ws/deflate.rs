@@ -0,0 +1,454 @@
+use super::{frame::FrameError, CloseCode, Role};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_http_types::{
+    header::{HeaderMap, SEC_WEBSOCKET_EXTENSIONS},
+    HeaderValue,
+};
+use std::fmt;
+
+const EXTENSION_NAME: &str = "permessage-deflate";
+
+/// The trailer of a deflate block flushed using a sync flush,
+/// which is stripped from (and appended to) the compressed message payloads.
+const DEFLATE_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// The (maximum) LZ77 window size, in bits, used for compression.
+const MAX_WINDOW_BITS: u8 = 15;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Configuration of the `permessage-deflate` WebSocket extension,
+/// as defined in [RFC 7692](https://datatracker.ietf.org/doc/html/rfc7692).
+///
+/// Used as the parameters to offer (client) or accept (server),
+/// as well as the parameters negotiated as part of the opening handshake.
+///
+/// Messages are always compressed using the maximum LZ77 window size (of 15 bits),
+/// as such offers which restrict the window size of the server are declined.
+pub struct PerMessageDeflateConfig {
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+}
+
+impl PerMessageDeflateConfig {
+    /// Create a new default [`PerMessageDeflateConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether or not the server resets its compression context after each message.
+    pub fn set_server_no_context_takeover(&mut self, enabled: bool) -> &mut Self {
+        self.server_no_context_takeover = enabled;
+        self
+    }
+
+    /// Replace this [`PerMessageDeflateConfig`] with whether or not
+    /// the server resets its compression context after each message.
+    pub fn with_server_no_context_takeover(mut self, enabled: bool) -> Self {
+        self.server_no_context_takeover = enabled;
+        self
+    }
+
+    /// Returns `true` in case the server resets its compression context after each message.
+    pub fn server_no_context_takeover(&self) -> bool {
+        self.server_no_context_takeover
+    }
+
+    /// Set whether or not the client resets its compression context after each message.
+    pub fn set_client_no_context_takeover(&mut self, enabled: bool) -> &mut Self {
+        self.client_no_context_takeover = enabled;
+        self
+    }
+
+    /// Replace this [`PerMessageDeflateConfig`] with whether or not
+    /// the client resets its compression context after each message.
+    pub fn with_client_no_context_takeover(mut self, enabled: bool) -> Self {
+        self.client_no_context_takeover = enabled;
+        self
+    }
+
+    /// Returns `true` in case the client resets its compression context after each message.
+    pub fn client_no_context_takeover(&self) -> bool {
+        self.client_no_context_takeover
+    }
+
+    /// The value of the `Sec-WebSocket-Extensions` header
+    /// used to offer (client) or accept (server) these parameters.
+    pub(super) fn header_value(&self) -> HeaderValue {
+        let mut value = EXTENSION_NAME.to_owned();
+        if self.server_no_context_takeover {
+            value.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            value.push_str("; client_no_context_takeover");
+        }
+        HeaderValue::try_from(value).expect("valid header value")
+    }
+
+    /// Negotiate the parameters to accept, as a server,
+    /// from the extensions offered by the client.
+    ///
+    /// Returns `None` in case none of the offers (if any) are acceptable.
+    pub(super) fn negotiate_offer(&self, headers: &HeaderMap) -> Option<Self> {
+        let extensions = parse_extensions(headers).ok()?;
+        extensions
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(EXTENSION_NAME))
+            .find_map(|(_, params)| self.accept_params(params))
+    }
+
+    fn accept_params(&self, params: &[(&str, Option<&str>)]) -> Option<Self> {
+        let mut accepted = self.clone();
+        for (i, (name, value)) in params.iter().enumerate() {
+            if params[..i].iter().any(|(other, _)| other == name) {
+                return None;
+            }
+            match (*name, *value) {
+                ("server_no_context_takeover", None) => accepted.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => accepted.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) => {
+                    if parse_window_bits(bits)? != MAX_WINDOW_BITS {
+                        return None;
+                    }
+                }
+                // the client can compress using any window size,
+                // as we decompress using the maximum window size
+                ("client_max_window_bits", bits) => {
+                    if let Some(bits) = bits {
+                        parse_window_bits(bits)?;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(accepted)
+    }
+
+    /// Negotiate the parameters accepted by the server,
+    /// as part of its response to the (client) offer made using these parameters.
+    ///
+    /// Returns `None` in case the server declined the offer.
+    pub(super) fn negotiate_response(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<Option<Self>, OpaqueError> {
+        let extensions = parse_extensions(headers)?;
+        let params = match extensions.as_slice() {
+            [] => return Ok(None),
+            [(name, params)] if name.eq_ignore_ascii_case(EXTENSION_NAME) => params,
+            _ => {
+                return Err(OpaqueError::from_display(
+                    "websocket: server accepted unsupported extension(s)",
+                ))
+            }
+        };
+
+        let mut accepted = Self {
+            server_no_context_takeover: false,
+            client_no_context_takeover: self.client_no_context_takeover,
+        };
+        for (name, value) in params {
+            match (*name, *value) {
+                ("server_no_context_takeover", None) => accepted.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => accepted.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) if parse_window_bits(bits).is_some() => (),
+                ("client_max_window_bits", Some(bits))
+                    if parse_window_bits(bits) == Some(MAX_WINDOW_BITS) => {}
+                _ => {
+                    return Err(OpaqueError::from_display(format!(
+                        "websocket: invalid {EXTENSION_NAME} parameter accepted by server: {name}"
+                    )))
+                }
+            }
+        }
+        Ok(Some(accepted))
+    }
+}
+
+type Extension<'a> = (&'a str, Vec<(&'a str, Option<&'a str>)>);
+
+/// Parse the extensions, and their parameters, found in the `Sec-WebSocket-Extensions` header(s).
+fn parse_extensions(headers: &HeaderMap) -> Result<Vec<Extension<'_>>, OpaqueError> {
+    let mut extensions = Vec::new();
+    for value in headers.get_all(SEC_WEBSOCKET_EXTENSIONS) {
+        let value = value
+            .to_str()
+            .context("websocket: invalid Sec-WebSocket-Extensions header")?;
+        for extension in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut parts = extension.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let params = parts
+                .filter(|param| !param.is_empty())
+                .map(|param| match param.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                    None => (param, None),
+                })
+                .collect();
+            extensions.push((name, params));
+        }
+    }
+    Ok(extensions)
+}
+
+fn parse_window_bits(value: &str) -> Option<u8> {
+    value
+        .parse()
+        .ok()
+        .filter(|bits| (8..=MAX_WINDOW_BITS).contains(bits))
+}
+
+/// The (de)compression state of a [`WebSocket`] for which
+/// the `permessage-deflate` extension was negotiated.
+///
+/// [`WebSocket`]: super::WebSocket
+pub(super) struct Deflate {
+    compress: Compress,
+    decompress: Decompress,
+    compress_reset: bool,
+    decompress_reset: bool,
+}
+
+impl fmt::Debug for Deflate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Deflate")
+            .field("compress_reset", &self.compress_reset)
+            .field("decompress_reset", &self.decompress_reset)
+            .finish()
+    }
+}
+
+impl Deflate {
+    pub(super) fn new(config: &PerMessageDeflateConfig, role: Role) -> Self {
+        let (compress_reset, decompress_reset) = match role {
+            Role::Client => (
+                config.client_no_context_takeover,
+                config.server_no_context_takeover,
+            ),
+            Role::Server => (
+                config.server_no_context_takeover,
+                config.client_no_context_takeover,
+            ),
+        };
+        Self {
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+            compress_reset,
+            decompress_reset,
+        }
+    }
+
+    /// Compress the payload of a message.
+    pub(super) fn compress(&mut self, payload: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+        let mut output = Vec::with_capacity(payload.len() / 2 + 64);
+        let total_in = self.compress.total_in();
+        loop {
+            let consumed = (self.compress.total_in() - total_in) as usize;
+            if output.capacity() - output.len() < 64 {
+                output.reserve(output.capacity().max(64));
+            }
+            self.compress
+                .compress_vec(&payload[consumed..], &mut output, FlushCompress::Sync)
+                .context("websocket: compress message")?;
+
+            // the sync flush is complete once all input is consumed
+            // without filling up the output buffer
+            let consumed = (self.compress.total_in() - total_in) as usize;
+            if consumed == payload.len() && output.len() < output.capacity() {
+                break;
+            }
+        }
+
+        if output.ends_with(&DEFLATE_TRAILER) {
+            output.truncate(output.len() - DEFLATE_TRAILER.len());
+        }
+        if self.compress_reset {
+            self.compress.reset();
+        }
+        Ok(output)
+    }
+
+    /// Decompress the payload of a message,
+    /// failing in case it exceeds the given maximum size.
+    pub(super) fn decompress(
+        &mut self,
+        payload: &[u8],
+        max_size: Option<usize>,
+    ) -> Result<Vec<u8>, FrameError> {
+        const INVALID: FrameError = FrameError {
+            code: CloseCode::InvalidPayload,
+            reason: "invalid compressed payload",
+        };
+
+        let mut input = Vec::with_capacity(payload.len() + DEFLATE_TRAILER.len());
+        input.extend_from_slice(payload);
+        input.extend_from_slice(&DEFLATE_TRAILER);
+
+        let mut output =
+            Vec::with_capacity((payload.len() * 2 + 64).min(max_size.unwrap_or(usize::MAX)));
+        let mut consumed = 0;
+        loop {
+            if output.capacity() - output.len() < 64 {
+                output.reserve(output.capacity().max(64));
+            }
+            let (total_in, written) = (self.decompress.total_in(), output.len());
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|_| INVALID)?;
+            let progress = (self.decompress.total_in() - total_in) as usize;
+            consumed += progress;
+
+            if max_size.is_some_and(|max| output.len() > max) {
+                return Err(FrameError {
+                    code: CloseCode::MessageTooBig,
+                    reason: "message too large",
+                });
+            }
+            if status == Status::StreamEnd {
+                // the peer finished the stream (which it should not),
+                // so the next message starts a new one
+                self.decompress.reset(false);
+            }
+            if consumed == input.len() && output.len() < output.capacity() {
+                break;
+            }
+            if progress == 0 && output.len() == written && status != Status::StreamEnd {
+                return Err(INVALID);
+            }
+        }
+
+        if self.decompress_reset {
+            self.decompress.reset(false);
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extension_headers(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(SEC_WEBSOCKET_EXTENSIONS, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_negotiate_offer() {
+        let config = PerMessageDeflateConfig::new();
+
+        for (offer, expected) in [
+            ("", None),
+            ("x-webkit-deflate-frame", None),
+            ("permessage-deflate", Some(config.clone())),
+            (
+                "permessage-deflate; client_max_window_bits",
+                Some(config.clone()),
+            ),
+            (
+                "permessage-deflate; server_no_context_takeover; client_max_window_bits=10",
+                Some(config.clone().with_server_no_context_takeover(true)),
+            ),
+            // restricting the server window is not supported, but the fallback offer is
+            (
+                "permessage-deflate; server_max_window_bits=10, permessage-deflate; client_no_context_takeover",
+                Some(config.clone().with_client_no_context_takeover(true)),
+            ),
+            ("permessage-deflate; server_max_window_bits=10", None),
+            ("permessage-deflate; server_max_window_bits=16", None),
+            ("permessage-deflate; foo", None),
+            (
+                "permessage-deflate; server_no_context_takeover; server_no_context_takeover",
+                None,
+            ),
+        ] {
+            assert_eq!(
+                config.negotiate_offer(&extension_headers(offer)),
+                expected,
+                "{offer}"
+            );
+        }
+
+        let config = config.with_client_no_context_takeover(true);
+        let accepted = config
+            .negotiate_offer(&extension_headers("permessage-deflate"))
+            .unwrap();
+        assert!(accepted.client_no_context_takeover());
+        assert_eq!(
+            accepted.header_value(),
+            "permessage-deflate; client_no_context_takeover"
+        );
+    }
+
+    #[test]
+    fn test_negotiate_response() {
+        let config = PerMessageDeflateConfig::new();
+
+        assert_eq!(config.negotiate_response(&HeaderMap::new()).unwrap(), None);
+        assert_eq!(
+            config
+                .negotiate_response(&extension_headers(
+                    "permessage-deflate; server_no_context_takeover; server_max_window_bits=10"
+                ))
+                .unwrap(),
+            Some(config.clone().with_server_no_context_takeover(true)),
+        );
+
+        for response in [
+            "x-webkit-deflate-frame",
+            "permessage-deflate, permessage-deflate",
+            "permessage-deflate; client_max_window_bits=10",
+            "permessage-deflate; foo",
+        ] {
+            assert!(
+                config
+                    .negotiate_response(&extension_headers(response))
+                    .is_err(),
+                "{response}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_deflate_roundtrip() {
+        for no_context_takeover in [false, true] {
+            let config = PerMessageDeflateConfig::new()
+                .with_server_no_context_takeover(no_context_takeover)
+                .with_client_no_context_takeover(no_context_takeover);
+            let mut client = Deflate::new(&config, Role::Client);
+            let mut server = Deflate::new(&config, Role::Server);
+
+            for payload in [&b"Hello"[..], &b""[..], &b"Hello"[..], &[b'a'; 100_000][..]] {
+                let compressed = client.compress(payload).unwrap();
+                assert!(!compressed.ends_with(&DEFLATE_TRAILER));
+                assert_eq!(server.decompress(&compressed, None).unwrap(), payload);
+
+                let compressed = server.compress(payload).unwrap();
+                assert_eq!(client.decompress(&compressed, None).unwrap(), payload);
+            }
+        }
+    }
+
+    #[test]
+    fn test_deflate_rfc_example() {
+        let mut deflate = Deflate::new(&PerMessageDeflateConfig::new(), Role::Client);
+        let payload = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+        assert_eq!(deflate.decompress(&payload, None).unwrap(), b"Hello");
+        // the context is taken over by the next message
+        assert_eq!(
+            deflate
+                .decompress(&[0xf2, 0x00, 0x11, 0x00, 0x00], None)
+                .unwrap(),
+            b"Hello"
+        );
+
+        let err = deflate.decompress(&payload, Some(4)).unwrap_err();
+        assert_eq!(err.code, CloseCode::MessageTooBig);
+
+        let err = Deflate::new(&PerMessageDeflateConfig::new(), Role::Client)
+            .decompress(&[0xff, 0xff, 0xff], None)
+            .unwrap_err();
+        assert_eq!(err.code, CloseCode::InvalidPayload);
+    }
+}
@@ -0,0 +1,345 @@
+use super::{CloseCode, Role};
+use bytes::{Buf, BufMut, BytesMut};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The opcode of a WebSocket [`Frame`], as defined in
+/// [RFC 6455, section 5.2](https://datatracker.ietf.org/doc/html/rfc6455#section-5.2).
+pub enum OpCode {
+    /// Continuation of a fragmented message.
+    Continuation,
+    /// (First frame of a) text message.
+    Text,
+    /// (First frame of a) binary message.
+    Binary,
+    /// Close control frame.
+    Close,
+    /// Ping control frame.
+    Ping,
+    /// Pong control frame.
+    Pong,
+}
+
+impl OpCode {
+    /// Returns `true` in case this is the opcode of a control frame.
+    pub fn is_control(&self) -> bool {
+        matches!(self, Self::Close | Self::Ping | Self::Pong)
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            _ => return None,
+        })
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+/// A single (unmasked) WebSocket frame.
+///
+/// Masking is applied (and removed) by the [`WebSocket`] in function of its [`Role`].
+///
+/// [`WebSocket`]: super::WebSocket
+pub struct Frame {
+    /// Whether or not this is the final frame of a message.
+    pub fin: bool,
+    /// The first reserved bit, used by the `permessage-deflate` extension
+    /// to mark the first frame of a compressed message.
+    pub rsv1: bool,
+    /// The [`OpCode`] of the frame.
+    pub opcode: OpCode,
+    /// The (unmasked) payload of the frame.
+    pub payload: Vec<u8>,
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frame")
+            .field("fin", &self.fin)
+            .field("rsv1", &self.rsv1)
+            .field("opcode", &self.opcode)
+            .field("payload_len", &self.payload.len())
+            .finish()
+    }
+}
+
+impl Frame {
+    /// Create a new final [`Frame`] for the given [`OpCode`] and payload.
+    pub fn new(opcode: OpCode, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            fin: true,
+            rsv1: false,
+            opcode,
+            payload: payload.into(),
+        }
+    }
+
+    /// Encode this frame into the given buffer, masking it with the given key, if any.
+    pub(super) fn encode(&self, mask: Option<[u8; 4]>, buf: &mut BytesMut) {
+        let mut b0 = self.opcode.as_u8();
+        if self.fin {
+            b0 |= 0x80;
+        }
+        if self.rsv1 {
+            b0 |= 0x40;
+        }
+        let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+
+        let len = self.payload.len();
+        buf.reserve(len + 14);
+        buf.put_u8(b0);
+        if len < 126 {
+            buf.put_u8(mask_bit | len as u8);
+        } else if len <= u16::MAX as usize {
+            buf.put_u8(mask_bit | 126);
+            buf.put_u16(len as u16);
+        } else {
+            buf.put_u8(mask_bit | 127);
+            buf.put_u64(len as u64);
+        }
+
+        match mask {
+            Some(key) => {
+                buf.put_slice(&key);
+                let start = buf.len();
+                buf.put_slice(&self.payload);
+                apply_mask(&mut buf[start..], key);
+            }
+            None => buf.put_slice(&self.payload),
+        }
+    }
+
+    /// Decode a single frame from the given buffer, as received by the given [`Role`].
+    ///
+    /// Returns `None` in case the buffer does not yet contain a complete frame.
+    pub(super) fn decode(
+        buf: &mut BytesMut,
+        role: Role,
+        max_frame_size: Option<usize>,
+    ) -> Result<Option<Self>, FrameError> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+        let (b0, b1) = (buf[0], buf[1]);
+
+        if b0 & 0x30 != 0 {
+            return Err(FrameError::protocol("reserved bits set"));
+        }
+        let opcode =
+            OpCode::from_u8(b0 & 0x0F).ok_or_else(|| FrameError::protocol("reserved opcode"))?;
+
+        let masked = b1 & 0x80 != 0;
+        match role {
+            Role::Server if !masked => {
+                return Err(FrameError::protocol("unmasked frame from client"))
+            }
+            Role::Client if masked => return Err(FrameError::protocol("masked frame from server")),
+            _ => (),
+        }
+
+        let mut header_len = 2;
+        let len = match b1 & 0x7F {
+            126 => {
+                header_len += 2;
+                if buf.len() < header_len {
+                    return Ok(None);
+                }
+                u16::from_be_bytes([buf[2], buf[3]]) as u64
+            }
+            127 => {
+                header_len += 8;
+                if buf.len() < header_len {
+                    return Ok(None);
+                }
+                let len = u64::from_be_bytes(buf[2..10].try_into().unwrap());
+                if len >> 63 != 0 {
+                    return Err(FrameError::protocol("invalid payload length"));
+                }
+                len
+            }
+            len => len as u64,
+        };
+
+        if opcode.is_control() {
+            if b0 & 0x80 == 0 {
+                return Err(FrameError::protocol("fragmented control frame"));
+            }
+            if len > 125 {
+                return Err(FrameError::protocol("control frame too large"));
+            }
+        }
+        if max_frame_size.is_some_and(|max| len > max as u64) {
+            return Err(FrameError {
+                code: CloseCode::MessageTooBig,
+                reason: "frame too large",
+            });
+        }
+        let len = len as usize;
+
+        let mask = if masked {
+            header_len += 4;
+            if buf.len() < header_len {
+                return Ok(None);
+            }
+            Some(<[u8; 4]>::try_from(&buf[header_len - 4..header_len]).unwrap())
+        } else {
+            None
+        };
+
+        if buf.len() < header_len + len {
+            buf.reserve(header_len + len - buf.len());
+            return Ok(None);
+        }
+
+        buf.advance(header_len);
+        let mut payload = buf.split_to(len).to_vec();
+        if let Some(key) = mask {
+            apply_mask(&mut payload, key);
+        }
+
+        Ok(Some(Self {
+            fin: b0 & 0x80 != 0,
+            rsv1: b0 & 0x40 != 0,
+            opcode,
+            payload,
+        }))
+    }
+}
+
+/// Apply (or remove) the given masking key to the given payload.
+fn apply_mask(payload: &mut [u8], key: [u8; 4]) {
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= key[i & 3];
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A violation of the WebSocket protocol by the peer,
+/// which results in the connection being closed with the given [`CloseCode`].
+pub(super) struct FrameError {
+    pub(super) code: CloseCode,
+    pub(super) reason: &'static str,
+}
+
+impl FrameError {
+    pub(super) const fn protocol(reason: &'static str) -> Self {
+        Self {
+            code: CloseCode::ProtocolError,
+            reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_encode_decode_roundtrip() {
+        for (len, header_len) in [(0, 2), (125, 2), (126, 4), (65535, 4), (65536, 10)] {
+            for (role, mask) in [(Role::Client, None), (Role::Server, Some([1, 2, 3, 4]))] {
+                let frame = Frame::new(OpCode::Binary, vec![0xab; len]);
+                let mut buf = BytesMut::new();
+                frame.encode(mask, &mut buf);
+                assert_eq!(
+                    buf.len(),
+                    header_len + len + mask.map(|_| 4).unwrap_or_default()
+                );
+
+                // incomplete frames are not decoded
+                let mut partial = BytesMut::from(&buf[..buf.len() - 1]);
+                assert_eq!(Frame::decode(&mut partial, role, None), Ok(None));
+
+                let decoded = Frame::decode(&mut buf, role, None).unwrap().unwrap();
+                assert_eq!(decoded, frame);
+                assert!(buf.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_decode_rfc_examples() {
+        // single-frame unmasked text message
+        let mut buf = BytesMut::from(&[0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f][..]);
+        let frame = Frame::decode(&mut buf, Role::Client, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, Frame::new(OpCode::Text, "Hello"));
+
+        // single-frame masked text message
+        let mut buf = BytesMut::from(
+            &[
+                0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+            ][..],
+        );
+        let frame = Frame::decode(&mut buf, Role::Server, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, Frame::new(OpCode::Text, "Hello"));
+
+        // fragmented unmasked text message
+        let mut buf = BytesMut::from(&[0x01, 0x03, 0x48, 0x65, 0x6c, 0x80, 0x02, 0x6c, 0x6f][..]);
+        let first = Frame::decode(&mut buf, Role::Client, None)
+            .unwrap()
+            .unwrap();
+        assert!(!first.fin);
+        assert_eq!(first.opcode, OpCode::Text);
+        let second = Frame::decode(&mut buf, Role::Client, None)
+            .unwrap()
+            .unwrap();
+        assert!(second.fin);
+        assert_eq!(second.opcode, OpCode::Continuation);
+        assert_eq!(second.payload, b"lo");
+    }
+
+    #[test]
+    fn test_frame_decode_protocol_errors() {
+        for (bytes, role) in [
+            // unmasked frame received by server
+            (&[0x81, 0x00][..], Role::Server),
+            // masked frame received by client
+            (&[0x81, 0x80, 0, 0, 0, 0][..], Role::Client),
+            // reserved bits
+            (&[0xA1, 0x00][..], Role::Client),
+            // reserved opcode
+            (&[0x83, 0x00][..], Role::Client),
+            // fragmented control frame
+            (&[0x09, 0x00][..], Role::Client),
+            // too large control frame
+            (&[0x89, 0x7E, 0x00, 0x7E][..], Role::Client),
+        ] {
+            let err = Frame::decode(&mut BytesMut::from(bytes), role, None).unwrap_err();
+            assert_eq!(err.code, CloseCode::ProtocolError, "{bytes:?}");
+        }
+
+        let err = Frame::decode(
+            &mut BytesMut::from(&[0x82, 0x7E, 0x01, 0x00][..]),
+            Role::Client,
+            Some(255),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, CloseCode::MessageTooBig);
+    }
+}
@@ -0,0 +1,412 @@
+use super::{PerMessageDeflateConfig, Role, WebSocket, WebSocketConfig};
+use crate::server::layer::upgrade::Upgraded;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use rama_core::{context::Extensions, error::BoxError, matcher::Matcher, Context, Service};
+use rama_http_types::{
+    header::{
+        CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
+    },
+    HeaderMap, HeaderName, HeaderValue, IntoResponse, Method, Request, Response, StatusCode,
+    Version,
+};
+use rama_utils::macros::define_inner_service_accessors;
+use sha1::{Digest, Sha1};
+use std::convert::Infallible;
+
+/// The GUID appended to the key of the client to compute the accept key of the server.
+const ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The only WebSocket version supported, as defined by RFC 6455.
+const VERSION: &str = "13";
+
+/// Compute the value of the `Sec-WebSocket-Accept` header for the given `Sec-WebSocket-Key`.
+pub(super) fn accept_key(key: &[u8]) -> HeaderValue {
+    let mut sha1 = Sha1::new();
+    sha1.update(key);
+    sha1.update(ACCEPT_GUID);
+    HeaderValue::try_from(BASE64.encode(sha1.finalize())).expect("base64 is a valid header value")
+}
+
+/// The comma separated tokens found in the header(s) with the given name.
+pub(super) fn header_tokens<'a>(
+    headers: &'a HeaderMap,
+    name: &HeaderName,
+) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Returns `true` in case the header(s) with the given name contain the given token.
+pub(super) fn header_contains_token(headers: &HeaderMap, name: &HeaderName, token: &str) -> bool {
+    header_tokens(headers, name).any(|value| value.eq_ignore_ascii_case(token))
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A [`Matcher`] which matches WebSocket upgrade requests,
+/// meaning `GET` requests with an `Upgrade: websocket` header.
+///
+/// Use it together with the [`WebSocketAcceptor`] and [`WebSocketService`]
+/// to serve WebSockets using the [`UpgradeLayer`].
+///
+/// [`UpgradeLayer`]: crate::server::layer::upgrade::UpgradeLayer
+pub struct WebSocketMatcher;
+
+impl WebSocketMatcher {
+    /// Create a new [`WebSocketMatcher`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<State> Matcher<State, Request> for WebSocketMatcher {
+    fn matches(&self, _ext: Option<&mut Extensions>, _ctx: &Context<State>, req: &Request) -> bool {
+        req.method() == Method::GET && header_contains_token(req.headers(), &UPGRADE, "websocket")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The responder of the [`UpgradeLayer`] which validates the opening handshake
+/// of a WebSocket upgrade request, and responds with `101 Switching Protocols`
+/// in case it is valid.
+///
+/// The subprotocol is selected in the order of preference of the client,
+/// from the protocols supported by this acceptor. The `permessage-deflate` extension
+/// is negotiated in case it is configured in the [`WebSocketConfig`].
+/// The negotiated parameters are used by the [`WebSocketService`] to establish the [`WebSocket`].
+///
+/// [`UpgradeLayer`]: crate::server::layer::upgrade::UpgradeLayer
+pub struct WebSocketAcceptor {
+    config: WebSocketConfig,
+    protocols: Vec<String>,
+}
+
+impl WebSocketAcceptor {
+    /// Create a new [`WebSocketAcceptor`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the [`WebSocketConfig`] of the accepted [`WebSocket`]s.
+    pub fn set_config(&mut self, config: WebSocketConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// Replace this [`WebSocketAcceptor`] with the [`WebSocketConfig`]
+    /// of the accepted [`WebSocket`]s set.
+    pub fn with_config(mut self, config: WebSocketConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the subprotocols supported by this [`WebSocketAcceptor`].
+    pub fn set_protocols<I, P>(&mut self, protocols: I) -> &mut Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Replace this [`WebSocketAcceptor`] with the subprotocols it supports set.
+    pub fn with_protocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// Validate the opening handshake of the client,
+/// returning the accept key in case it is valid.
+fn validate_request(req: &Request) -> Result<HeaderValue, StatusCode> {
+    if req.method() != Method::GET {
+        return Err(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    if req.version() != Version::HTTP_11 {
+        tracing::debug!(version = ?req.version(), "websocket: unsupported http version");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let headers = req.headers();
+    if !header_contains_token(headers, &CONNECTION, "upgrade")
+        || !header_contains_token(headers, &UPGRADE, "websocket")
+    {
+        tracing::debug!("websocket: missing connection upgrade");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if headers.get(SEC_WEBSOCKET_VERSION).map(|v| v.as_bytes()) != Some(VERSION.as_bytes()) {
+        tracing::debug!(version = ?headers.get(SEC_WEBSOCKET_VERSION), "websocket: unsupported version");
+        return Err(StatusCode::UPGRADE_REQUIRED);
+    }
+
+    match headers.get(SEC_WEBSOCKET_KEY) {
+        Some(key)
+            if BASE64
+                .decode(key.as_bytes())
+                .is_ok_and(|nonce| nonce.len() == 16) =>
+        {
+            Ok(accept_key(key.as_bytes()))
+        }
+        key => {
+            tracing::debug!(?key, "websocket: invalid key");
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+/// The parameters negotiated by the [`WebSocketAcceptor`],
+/// used by the [`WebSocketService`] to establish the [`WebSocket`].
+#[derive(Debug, Clone)]
+struct NegotiatedWebSocket {
+    config: WebSocketConfig,
+    protocol: Option<String>,
+    per_message_deflate: Option<PerMessageDeflateConfig>,
+}
+
+impl<State> Service<State, Request> for WebSocketAcceptor
+where
+    State: Send + Sync + 'static,
+{
+    type Response = (Response, Context<State>, Request);
+    type Error = Response;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let accept = validate_request(&req).map_err(|status| {
+            if status == StatusCode::UPGRADE_REQUIRED {
+                // inform the client about the version we do support
+                (
+                    status,
+                    [(SEC_WEBSOCKET_VERSION, HeaderValue::from_static(VERSION))],
+                )
+                    .into_response()
+            } else {
+                status.into_response()
+            }
+        })?;
+
+        let protocol = header_tokens(req.headers(), &SEC_WEBSOCKET_PROTOCOL)
+            .find(|offered| self.protocols.iter().any(|p| p == offered))
+            .map(ToOwned::to_owned);
+        let per_message_deflate = self
+            .config
+            .per_message_deflate()
+            .and_then(|config| config.negotiate_offer(req.headers()));
+
+        let mut resp = (
+            StatusCode::SWITCHING_PROTOCOLS,
+            [
+                (UPGRADE, HeaderValue::from_static("websocket")),
+                (CONNECTION, HeaderValue::from_static("upgrade")),
+                (SEC_WEBSOCKET_ACCEPT, accept),
+            ],
+        )
+            .into_response();
+        if let Some(protocol) = protocol.as_deref() {
+            if let Ok(value) = HeaderValue::from_str(protocol) {
+                resp.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, value);
+            }
+        }
+        if let Some(config) = per_message_deflate.as_ref() {
+            resp.headers_mut()
+                .insert(SEC_WEBSOCKET_EXTENSIONS, config.header_value());
+        }
+
+        tracing::trace!(?protocol, ?per_message_deflate, "websocket: accept upgrade");
+        ctx.insert(NegotiatedWebSocket {
+            config: self.config.clone(),
+            protocol,
+            per_message_deflate,
+        });
+
+        Ok((resp, ctx, req))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The handler of the [`UpgradeLayer`] which establishes the [`WebSocket`],
+/// as negotiated by the [`WebSocketAcceptor`], and serves it using the inner service.
+///
+/// Errors returned by the inner service are logged.
+///
+/// [`UpgradeLayer`]: crate::server::layer::upgrade::UpgradeLayer
+pub struct WebSocketService<S> {
+    inner: S,
+}
+
+impl<S> WebSocketService<S> {
+    /// Create a new [`WebSocketService`].
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S, State> Service<State, Upgraded> for WebSocketService<S>
+where
+    State: Send + Sync + 'static,
+    S: Service<State, WebSocket<Upgraded>, Response = (), Error: Into<BoxError>>,
+{
+    type Response = ();
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        upgraded: Upgraded,
+    ) -> Result<Self::Response, Self::Error> {
+        let socket = match ctx.remove::<NegotiatedWebSocket>() {
+            Some(negotiated) => {
+                let mut socket = WebSocket::new(upgraded, Role::Server, negotiated.config);
+                if let Some(protocol) = negotiated.protocol {
+                    socket = socket.with_protocol(protocol);
+                }
+                if let Some(config) = negotiated.per_message_deflate.as_ref() {
+                    socket = socket.with_per_message_deflate(config);
+                }
+                socket
+            }
+            None => {
+                tracing::debug!("websocket: no negotiated parameters found, use default config");
+                WebSocket::new(upgraded, Role::Server, WebSocketConfig::default())
+            }
+        };
+
+        if let Err(err) = self.inner.serve(ctx, socket).await {
+            let err: BoxError = err.into();
+            tracing::debug!(error = %err, "websocket: service error");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::Body;
+
+    fn upgrade_request() -> rama_http_types::dep::http::request::Builder {
+        Request::builder()
+            .uri("/chat")
+            .header(UPGRADE, "websocket")
+            .header(CONNECTION, "keep-alive, Upgrade")
+            .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+    }
+
+    #[test]
+    fn test_accept_key() {
+        assert_eq!(
+            accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_websocket_matcher() {
+        let ctx = Context::default();
+        let matcher = WebSocketMatcher::new();
+        assert!(matcher.matches(None, &ctx, &upgrade_request().body(Body::empty()).unwrap()));
+        assert!(!matcher.matches(
+            None,
+            &ctx,
+            &upgrade_request()
+                .method(Method::POST)
+                .body(Body::empty())
+                .unwrap()
+        ));
+        assert!(!matcher.matches(
+            None,
+            &ctx,
+            &Request::builder().uri("/chat").body(Body::empty()).unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_websocket_acceptor() {
+        let acceptor = WebSocketAcceptor::new()
+            .with_protocols(["chat", "superchat"])
+            .with_config(
+                WebSocketConfig::new().with_per_message_deflate(PerMessageDeflateConfig::new()),
+            );
+
+        let req = upgrade_request()
+            .header(SEC_WEBSOCKET_PROTOCOL, "superchat, chat")
+            .header(
+                SEC_WEBSOCKET_EXTENSIONS,
+                "permessage-deflate; client_max_window_bits",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let (resp, ctx, _) = acceptor.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(
+            resp.headers().get(SEC_WEBSOCKET_ACCEPT).unwrap(),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(
+            resp.headers().get(SEC_WEBSOCKET_PROTOCOL).unwrap(),
+            "superchat"
+        );
+        assert_eq!(
+            resp.headers().get(SEC_WEBSOCKET_EXTENSIONS).unwrap(),
+            "permessage-deflate"
+        );
+        let negotiated = ctx.get::<NegotiatedWebSocket>().unwrap();
+        assert_eq!(negotiated.protocol.as_deref(), Some("superchat"));
+        assert!(negotiated.per_message_deflate.is_some());
+
+        for (update, status) in [
+            (
+                (|req| *req.method_mut() = Method::POST) as fn(&mut Request),
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+            (
+                |req| *req.version_mut() = Version::HTTP_10,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                |req| {
+                    req.headers_mut().remove(CONNECTION);
+                },
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                |req| {
+                    req.headers_mut()
+                        .insert(SEC_WEBSOCKET_KEY, HeaderValue::from_static("invalid"));
+                },
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                |req| {
+                    req.headers_mut()
+                        .insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("8"));
+                },
+                StatusCode::UPGRADE_REQUIRED,
+            ),
+        ] {
+            let mut req = upgrade_request().body(Body::empty()).unwrap();
+            update(&mut req);
+
+            let resp = acceptor.serve(Context::default(), req).await.unwrap_err();
+            assert_eq!(resp.status(), status);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A WebSocket message, as sent and received by a [`WebSocket`].
+///
+/// [`WebSocket`]: super::WebSocket
+pub enum Message {
+    /// A (UTF-8) text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping, with an (optional) application payload of at most 125 bytes.
+    Ping(Vec<u8>),
+    /// A pong, with an (optional) application payload of at most 125 bytes.
+    Pong(Vec<u8>),
+    /// A close message, with an optional [`CloseFrame`].
+    Close(Option<CloseFrame>),
+}
+
+impl Message {
+    /// Create a new [`Message::Text`].
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Create a new [`Message::Binary`].
+    pub fn binary(data: impl Into<Vec<u8>>) -> Self {
+        Self::Binary(data.into())
+    }
+
+    /// Returns `true` in case this is a [`Message::Text`] or [`Message::Binary`].
+    pub fn is_data(&self) -> bool {
+        matches!(self, Self::Text(_) | Self::Binary(_))
+    }
+
+    /// Returns `true` in case this is a [`Message::Close`].
+    pub fn is_close(&self) -> bool {
+        matches!(self, Self::Close(_))
+    }
+
+    /// The length of the payload of this message.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Text(text) => text.len(),
+            Self::Binary(data) | Self::Ping(data) | Self::Pong(data) => data.len(),
+            Self::Close(frame) => frame.as_ref().map(|f| 2 + f.reason.len()).unwrap_or(0),
+        }
+    }
+
+    /// Returns `true` in case the payload of this message is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Binary(data)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The payload of a [`Message::Close`].
+pub struct CloseFrame {
+    /// The reason why the connection is closed.
+    pub code: CloseCode,
+    /// The (UTF-8) human readable reason, at most 123 bytes long.
+    pub reason: String,
+}
+
+impl CloseFrame {
+    /// Create a new [`CloseFrame`].
+    pub fn new(code: CloseCode, reason: impl Into<String>) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for CloseFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.reason.is_empty() {
+            write!(f, "{}", self.code)
+        } else {
+            write!(f, "{}: {}", self.code, self.reason)
+        }
+    }
+}
+
+macro_rules! close_codes {
+    ($($(#[$doc:meta])* $name:ident => $value:expr,)+) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        /// Status code of a [`CloseFrame`], as registered by
+        /// [IANA](https://www.iana.org/assignments/websocket/websocket.xhtml#close-code-number).
+        pub enum CloseCode {
+            $($(#[$doc])* $name,)+
+            /// Any other (e.g. application defined) status code.
+            Other(u16),
+        }
+
+        impl From<u16> for CloseCode {
+            fn from(value: u16) -> Self {
+                match value {
+                    $($value => Self::$name,)+
+                    value => Self::Other(value),
+                }
+            }
+        }
+
+        impl From<CloseCode> for u16 {
+            fn from(code: CloseCode) -> Self {
+                match code {
+                    $(CloseCode::$name => $value,)+
+                    CloseCode::Other(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for CloseCode {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    $(Self::$name => write!(f, concat!(stringify!($name), " ({})"), $value),)+
+                    Self::Other(value) => write!(f, "Other ({value})"),
+                }
+            }
+        }
+    };
+}
+
+close_codes! {
+    /// The purpose for which the connection was established has been fulfilled.
+    Normal => 1000,
+    /// An endpoint is going away, e.g. a server going down.
+    GoingAway => 1001,
+    /// An endpoint received a message violating the protocol.
+    ProtocolError => 1002,
+    /// An endpoint received a type of data it cannot accept.
+    UnsupportedData => 1003,
+    /// Reserved: no status code was present in the close frame.
+    NoStatusReceived => 1005,
+    /// Reserved: the connection was closed without a close frame.
+    Abnormal => 1006,
+    /// An endpoint received data inconsistent with the type of the message,
+    /// e.g. non UTF-8 data within a text message.
+    InvalidPayload => 1007,
+    /// An endpoint received a message violating its policy.
+    PolicyViolation => 1008,
+    /// An endpoint received a message too big to process.
+    MessageTooBig => 1009,
+    /// The client expected the server to negotiate one or more extensions.
+    MandatoryExtension => 1010,
+    /// The server encountered an unexpected condition.
+    InternalError => 1011,
+    /// The service is restarted.
+    ServiceRestart => 1012,
+    /// The service is (temporarily) overloaded.
+    TryAgainLater => 1013,
+    /// The gateway or proxy received an invalid response from the upstream server.
+    BadGateway => 1014,
+    /// Reserved: the TLS handshake failed.
+    TlsHandshake => 1015,
+}
+
+impl CloseCode {
+    /// Returns `true` in case this code is allowed to be sent in a [`CloseFrame`].
+    ///
+    /// The reserved codes, as well as the unassigned codes below 3000, are not allowed.
+    pub fn is_allowed(&self) -> bool {
+        matches!(u16::from(*self), 1000..=1003 | 1007..=1014 | 3000..=4999)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_code() {
+        assert_eq!(CloseCode::from(1000), CloseCode::Normal);
+        assert_eq!(u16::from(CloseCode::MessageTooBig), 1009);
+        assert_eq!(CloseCode::from(4000), CloseCode::Other(4000));
+        assert_eq!("Normal (1000)", CloseCode::Normal.to_string());
+        assert_eq!("Other (4000)", CloseCode::Other(4000).to_string());
+
+        assert!(CloseCode::Normal.is_allowed());
+        assert!(CloseCode::BadGateway.is_allowed());
+        assert!(CloseCode::Other(3000).is_allowed());
+        assert!(!CloseCode::NoStatusReceived.is_allowed());
+        assert!(!CloseCode::Abnormal.is_allowed());
+        assert!(!CloseCode::TlsHandshake.is_allowed());
+        assert!(!CloseCode::Other(1004).is_allowed());
+        assert!(!CloseCode::Other(2000).is_allowed());
+        assert!(!CloseCode::Other(5000).is_allowed());
+    }
+}
@@ -0,0 +1,74 @@
+//! WebSocket support, as defined in [RFC 6455](https://datatracker.ietf.org/doc/html/rfc6455),
+//! including the `permessage-deflate` extension of
+//! [RFC 7692](https://datatracker.ietf.org/doc/html/rfc7692).
+//!
+//! # Server
+//!
+//! WebSockets are served by plugging the [`WebSocketMatcher`] (matcher),
+//! [`WebSocketAcceptor`] (responder) and [`WebSocketService`] (handler)
+//! into the [`UpgradeLayer`]. The [`WebSocketAcceptor`] validates the handshake
+//! and negotiates the subprotocol and extensions, after which the [`WebSocketService`]
+//! serves the established [`WebSocket`] using its inner service.
+//!
+//! # Client
+//!
+//! The [`WebSocketConnector`] performs the opening handshake using an inner http client
+//! (e.g. the [`HttpClient`]), resulting in an established [`WebSocket`].
+//!
+//! # Proxy
+//!
+//! [`relay`] can be used to relay (and inspect) the messages between two [`WebSocket`]s,
+//! e.g. the one accepted from the client and the one connected to the server.
+//!
+//! [`UpgradeLayer`]: crate::server::layer::upgrade::UpgradeLayer
+//! [`HttpClient`]: crate::client::HttpClient
+
+use std::fmt;
+
+mod frame;
+#[doc(inline)]
+pub use frame::{Frame, OpCode};
+
+mod message;
+#[doc(inline)]
+pub use message::{CloseCode, CloseFrame, Message};
+
+mod deflate;
+#[doc(inline)]
+pub use deflate::PerMessageDeflateConfig;
+
+mod socket;
+#[doc(inline)]
+pub use socket::{WebSocket, WebSocketConfig};
+
+mod handshake;
+#[doc(inline)]
+pub use handshake::{WebSocketAcceptor, WebSocketMatcher, WebSocketService};
+
+mod client;
+#[doc(inline)]
+pub use client::WebSocketConnector;
+
+mod relay;
+#[doc(inline)]
+pub use relay::{relay, RelayDirection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The role of an endpoint of a [`WebSocket`] connection.
+///
+/// Frames sent by the client are masked, while frames sent by the server are not.
+pub enum Role {
+    /// The endpoint which initiated the connection.
+    Client,
+    /// The endpoint which accepted the connection.
+    Server,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Client => write!(f, "client"),
+            Self::Server => write!(f, "server"),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::{CloseCode, CloseFrame, Message, WebSocket};
+use rama_core::error::OpaqueError;
+use std::fmt;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The direction in which a [`Message`] is relayed.
+pub enum RelayDirection {
+    /// The message is relayed from the client to the server.
+    ClientToServer,
+    /// The message is relayed from the server to the client.
+    ServerToClient,
+}
+
+impl fmt::Display for RelayDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClientToServer => write!(f, "client -> server"),
+            Self::ServerToClient => write!(f, "server -> client"),
+        }
+    }
+}
+
+/// Relay the messages between the [`WebSocket`] of the client and that of the server,
+/// until both are closed.
+///
+/// Each received message is passed to `inspect`, which returns the message to relay,
+/// if any. Close messages are relayed as long as the other side is still open,
+/// such that the closing handshake is completed on both ends.
+///
+/// Disable the auto pong (see [`WebSocketConfig::set_auto_pong`]) of both sockets
+/// in case the pings are to be answered by the peers themselves.
+///
+/// In case one side fails, the other side is closed using [`CloseCode::BadGateway`]
+/// and the error is returned.
+///
+/// [`WebSocketConfig::set_auto_pong`]: super::WebSocketConfig::set_auto_pong
+pub async fn relay<C, S, F>(
+    client: &mut WebSocket<C>,
+    server: &mut WebSocket<S>,
+    mut inspect: F,
+) -> Result<(), OpaqueError>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnMut(RelayDirection, Message) -> Option<Message>,
+{
+    let (mut client_done, mut server_done) = (false, false);
+    while !client_done || !server_done {
+        tokio::select! {
+            result = client.recv(), if !client_done => match result {
+                Ok(Some(message)) => {
+                    if let Some(message) = inspect(RelayDirection::ClientToServer, message) {
+                        forward(server, message).await.inspect_err(|_| {
+                            tracing::debug!("websocket relay: failed to forward message to server");
+                        })?;
+                    }
+                }
+                Ok(None) => client_done = true,
+                Err(err) => {
+                    abort(server).await;
+                    return Err(err);
+                }
+            },
+            result = server.recv(), if !server_done => match result {
+                Ok(Some(message)) => {
+                    if let Some(message) = inspect(RelayDirection::ServerToClient, message) {
+                        forward(client, message).await.inspect_err(|_| {
+                            tracing::debug!("websocket relay: failed to forward message to client");
+                        })?;
+                    }
+                }
+                Ok(None) => server_done = true,
+                Err(err) => {
+                    abort(client).await;
+                    return Err(err);
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+async fn forward<T>(socket: &mut WebSocket<T>, message: Message) -> Result<(), OpaqueError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    if !socket.is_open() {
+        // the closing handshake of this side is already started (or completed)
+        return Ok(());
+    }
+    let message = match message {
+        // close codes reserved for local use are replaced by a normal closure
+        Message::Close(Some(close)) if !close.code.is_allowed() => {
+            Message::Close(Some(CloseFrame::new(CloseCode::Normal, close.reason)))
+        }
+        message => message,
+    };
+    socket.send(message).await
+}
+
+async fn abort<T>(socket: &mut WebSocket<T>)
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    if socket.is_open() {
+        let _ = socket
+            .send(Message::Close(Some(CloseFrame::new(
+                CloseCode::BadGateway,
+                "",
+            ))))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws::{Role, WebSocketConfig};
+    use tokio::io::duplex;
+
+    #[tokio::test]
+    async fn test_relay() {
+        let config = WebSocketConfig::new().with_auto_pong(false);
+        let (client, proxy_client) = duplex(1024);
+        let (proxy_server, server) = duplex(1024);
+        let mut client = WebSocket::new(client, Role::Client, WebSocketConfig::new());
+        let mut server = WebSocket::new(server, Role::Server, WebSocketConfig::new());
+        let mut proxy_client = WebSocket::new(proxy_client, Role::Server, config.clone());
+        let mut proxy_server = WebSocket::new(proxy_server, Role::Client, config);
+
+        let proxy = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            relay(
+                &mut proxy_client,
+                &mut proxy_server,
+                |direction, message| {
+                    seen.push(direction);
+                    match message {
+                        Message::Text(text) => Some(Message::Text(text.to_uppercase())),
+                        Message::Binary(_) => None,
+                        message => Some(message),
+                    }
+                },
+            )
+            .await
+            .unwrap();
+            seen
+        });
+
+        client.send(Message::binary(vec![1, 2, 3])).await.unwrap();
+        client.send(Message::text("hello")).await.unwrap();
+        assert_eq!(server.recv().await.unwrap(), Some(Message::text("HELLO")));
+
+        client.send(Message::Ping(b"ping".to_vec())).await.unwrap();
+        assert_eq!(
+            server.recv().await.unwrap(),
+            Some(Message::Ping(b"ping".to_vec()))
+        );
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::Pong(b"ping".to_vec()))
+        );
+
+        server.close(None).await.unwrap();
+        assert_eq!(client.recv().await.unwrap(), Some(Message::Close(None)));
+        assert_eq!(client.recv().await.unwrap(), None);
+
+        let (c2s, s2c) = (
+            RelayDirection::ClientToServer,
+            RelayDirection::ServerToClient,
+        );
+        assert_eq!(proxy.await.unwrap(), vec![c2s, c2s, c2s, s2c, s2c, c2s]);
+    }
+}
@@ -0,0 +1,692 @@
+use super::{
+    deflate::Deflate,
+    frame::{Frame, FrameError, OpCode},
+    CloseCode, CloseFrame, Message, PerMessageDeflateConfig, Role,
+};
+use bytes::{Buf, BytesMut};
+use rama_core::error::{ErrorContext, OpaqueError};
+use std::fmt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+/// Configuration of a [`WebSocket`].
+pub struct WebSocketConfig {
+    max_message_size: Option<usize>,
+    max_frame_size: Option<usize>,
+    fragment_size: Option<usize>,
+    auto_pong: bool,
+    per_message_deflate: Option<PerMessageDeflateConfig>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: Some(64 << 20),
+            max_frame_size: Some(16 << 20),
+            fragment_size: None,
+            auto_pong: true,
+            per_message_deflate: None,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    /// Create a new default [`WebSocketConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum size of a received message, 64 MiB by default.
+    ///
+    /// A message exceeding it closes the connection with [`CloseCode::MessageTooBig`].
+    pub fn set_max_message_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Replace this [`WebSocketConfig`] with the maximum size of a received message set,
+    /// 64 MiB by default.
+    ///
+    /// A message exceeding it closes the connection with [`CloseCode::MessageTooBig`].
+    pub fn with_max_message_size(mut self, size: Option<usize>) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Set the maximum size of a received frame, 16 MiB by default.
+    ///
+    /// A frame exceeding it closes the connection with [`CloseCode::MessageTooBig`].
+    pub fn set_max_frame_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Replace this [`WebSocketConfig`] with the maximum size of a received frame set,
+    /// 16 MiB by default.
+    ///
+    /// A frame exceeding it closes the connection with [`CloseCode::MessageTooBig`].
+    pub fn with_max_frame_size(mut self, size: Option<usize>) -> Self {
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Set the size of the fragments in which sent messages are split,
+    /// by default messages are sent as a single frame.
+    pub fn set_fragment_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.fragment_size = size.filter(|size| *size > 0);
+        self
+    }
+
+    /// Replace this [`WebSocketConfig`] with the size of the fragments
+    /// in which sent messages are split, by default messages are sent as a single frame.
+    pub fn with_fragment_size(mut self, size: Option<usize>) -> Self {
+        self.fragment_size = size.filter(|size| *size > 0);
+        self
+    }
+
+    /// Set whether or not received pings are answered automatically, enabled by default.
+    ///
+    /// Disable it in case you wish to reply to pings yourself,
+    /// e.g. when relaying them to another [`WebSocket`].
+    pub fn set_auto_pong(&mut self, enabled: bool) -> &mut Self {
+        self.auto_pong = enabled;
+        self
+    }
+
+    /// Replace this [`WebSocketConfig`] with whether or not received pings
+    /// are answered automatically, enabled by default.
+    ///
+    /// Disable it in case you wish to reply to pings yourself,
+    /// e.g. when relaying them to another [`WebSocket`].
+    pub fn with_auto_pong(mut self, enabled: bool) -> Self {
+        self.auto_pong = enabled;
+        self
+    }
+
+    /// Set the [`PerMessageDeflateConfig`] to offer (client) or accept (server)
+    /// during the opening handshake, disabled by default.
+    pub fn set_per_message_deflate(&mut self, config: PerMessageDeflateConfig) -> &mut Self {
+        self.per_message_deflate = Some(config);
+        self
+    }
+
+    /// Replace this [`WebSocketConfig`] with the [`PerMessageDeflateConfig`] to offer (client)
+    /// or accept (server) during the opening handshake, disabled by default.
+    pub fn with_per_message_deflate(mut self, config: PerMessageDeflateConfig) -> Self {
+        self.per_message_deflate = Some(config);
+        self
+    }
+
+    /// Returns the [`PerMessageDeflateConfig`] to offer (client) or accept (server), if any.
+    pub fn per_message_deflate(&self) -> Option<&PerMessageDeflateConfig> {
+        self.per_message_deflate.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Messages can be sent and received.
+    Open,
+    /// A close frame was sent, awaiting the close frame of the peer.
+    CloseSent,
+    /// The closing handshake is complete, the stream is to be shut down.
+    Closed,
+    /// The stream is shut down.
+    Terminated,
+}
+
+/// A message-level WebSocket stream, established over an (upgraded) stream,
+/// for which the opening handshake was already completed.
+///
+/// Ping, pong and close frames are handled as defined by the protocol:
+/// received pings are answered (see [`WebSocketConfig::set_auto_pong`]),
+/// received close frames are echoed and fragmented messages are reassembled.
+///
+/// Both [`WebSocket::recv`] and [`WebSocket::send`] are cancel safe,
+/// e.g. messages of two sockets can be received using [`tokio::select`].
+pub struct WebSocket<S> {
+    stream: S,
+    role: Role,
+    config: WebSocketConfig,
+    protocol: Option<String>,
+    deflate: Option<Deflate>,
+    state: State,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    fragments: Option<Fragments>,
+    pending: Option<Message>,
+}
+
+#[derive(Debug)]
+/// The frames received so far of a fragmented message.
+struct Fragments {
+    opcode: OpCode,
+    compressed: bool,
+    payload: Vec<u8>,
+}
+
+impl<S> fmt::Debug for WebSocket<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("role", &self.role)
+            .field("config", &self.config)
+            .field("protocol", &self.protocol)
+            .field("deflate", &self.deflate)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<S> WebSocket<S> {
+    /// Create a new [`WebSocket`] for the given stream,
+    /// as the given [`Role`], for which no extensions were negotiated.
+    pub fn new(stream: S, role: Role, config: WebSocketConfig) -> Self {
+        Self {
+            stream,
+            role,
+            config,
+            protocol: None,
+            deflate: None,
+            state: State::Open,
+            read_buf: BytesMut::with_capacity(READ_BUFFER_SIZE),
+            write_buf: BytesMut::new(),
+            fragments: None,
+            pending: None,
+        }
+    }
+
+    /// Replace this [`WebSocket`] with the subprotocol negotiated during the opening handshake.
+    pub fn with_protocol(mut self, protocol: impl Into<String>) -> Self {
+        self.protocol = Some(protocol.into());
+        self
+    }
+
+    /// Replace this [`WebSocket`] with the `permessage-deflate` parameters
+    /// negotiated during the opening handshake.
+    pub fn with_per_message_deflate(mut self, negotiated: &PerMessageDeflateConfig) -> Self {
+        self.deflate = Some(Deflate::new(negotiated, self.role));
+        self
+    }
+
+    /// Returns the [`Role`] of this [`WebSocket`].
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Returns the subprotocol negotiated during the opening handshake, if any.
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Returns `true` in case messages can still be sent using this [`WebSocket`],
+    /// meaning no close frame was sent yet.
+    pub fn is_open(&self) -> bool {
+        self.state == State::Open
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Consume this [`WebSocket`], returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> WebSocket<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Receive the next [`Message`] from the peer.
+    ///
+    /// Returns `None` once the closing handshake is complete,
+    /// after the [`Message::Close`] of the peer was returned.
+    ///
+    /// In case the peer violates the protocol the connection is closed
+    /// with the appropriate [`CloseCode`] and an error is returned.
+    pub async fn recv(&mut self) -> Result<Option<Message>, OpaqueError> {
+        loop {
+            self.flush().await?;
+            if let Some(message) = self.pending.take() {
+                return Ok(Some(message));
+            }
+
+            match self.state {
+                State::Open | State::CloseSent => (),
+                State::Closed => {
+                    self.state = State::Terminated;
+                    let _ = self.stream.shutdown().await;
+                    return Ok(None);
+                }
+                State::Terminated => return Ok(None),
+            }
+
+            match Frame::decode(&mut self.read_buf, self.role, self.config.max_frame_size) {
+                Ok(Some(frame)) => {
+                    tracing::trace!(role = %self.role, ?frame, "websocket: frame received");
+                    match self.on_frame(frame) {
+                        Ok(message) => self.pending = message,
+                        Err(err) => return Err(self.fail(err).await),
+                    }
+                }
+                Ok(None) => {
+                    if self.read_buf.capacity() - self.read_buf.len() < READ_BUFFER_SIZE {
+                        self.read_buf.reserve(READ_BUFFER_SIZE);
+                    }
+                    let n = self
+                        .stream
+                        .read_buf(&mut self.read_buf)
+                        .await
+                        .context("websocket: read from stream")?;
+                    if n == 0 {
+                        self.state = State::Terminated;
+                        return Err(OpaqueError::from_display(format!(
+                            "websocket: connection closed without close frame ({})",
+                            CloseCode::Abnormal
+                        )));
+                    }
+                }
+                Err(err) => return Err(self.fail(err).await),
+            }
+        }
+    }
+
+    /// Send a [`Message`] to the peer.
+    ///
+    /// Sending a [`Message::Close`] starts the closing handshake,
+    /// after which no more messages can be sent. Keep receiving messages
+    /// until `None` is returned to complete the closing handshake, or use [`WebSocket::close`].
+    pub async fn send(&mut self, message: Message) -> Result<(), OpaqueError> {
+        self.write_message(message)?;
+        self.flush().await
+    }
+
+    /// Close the connection, using the given [`CloseFrame`] if any,
+    /// and wait for the peer to complete the closing handshake.
+    ///
+    /// All messages received in the meantime are discarded.
+    pub async fn close(&mut self, frame: Option<CloseFrame>) -> Result<(), OpaqueError> {
+        if self.state == State::Open {
+            self.send(Message::Close(frame)).await?;
+        }
+        while self.recv().await?.is_some() {}
+        Ok(())
+    }
+
+    /// Flush all buffered frames to the stream.
+    async fn flush(&mut self) -> Result<(), OpaqueError> {
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+        while !self.write_buf.is_empty() {
+            let n = self
+                .stream
+                .write(&self.write_buf)
+                .await
+                .context("websocket: write to stream")?;
+            if n == 0 {
+                return Err(OpaqueError::from_display(
+                    "websocket: stream closed while writing",
+                ));
+            }
+            self.write_buf.advance(n);
+        }
+        self.stream.flush().await.context("websocket: flush stream")
+    }
+
+    /// Fail the connection because of the given protocol violation by the peer,
+    /// by sending a close frame (if still possible) and shutting down the stream.
+    async fn fail(&mut self, err: FrameError) -> OpaqueError {
+        tracing::debug!(role = %self.role, code = %err.code, reason = err.reason, "websocket: fail connection");
+        if self.state == State::Open {
+            self.write_frame(Frame::new(
+                OpCode::Close,
+                close_payload(err.code, err.reason),
+            ));
+        }
+        self.state = State::Terminated;
+        let _ = self.flush().await;
+        let _ = self.stream.shutdown().await;
+        OpaqueError::from_display(format!("websocket: {} ({})", err.reason, err.code))
+    }
+}
+
+impl<S> WebSocket<S> {
+    /// Process a received frame, returning the message it completes, if any.
+    fn on_frame(&mut self, frame: Frame) -> Result<Option<Message>, FrameError> {
+        if frame.rsv1 && (frame.opcode.is_control() || frame.opcode == OpCode::Continuation) {
+            return Err(FrameError::protocol("reserved bit set"));
+        }
+
+        match frame.opcode {
+            OpCode::Ping => {
+                if self.config.auto_pong && self.state == State::Open {
+                    self.write_frame(Frame::new(OpCode::Pong, frame.payload.clone()));
+                }
+                Ok(Some(Message::Ping(frame.payload)))
+            }
+            OpCode::Pong => Ok(Some(Message::Pong(frame.payload))),
+            OpCode::Close => {
+                let close = parse_close_payload(&frame.payload)?;
+                if self.state == State::Open {
+                    // echo the status code to complete the closing handshake
+                    let payload = close
+                        .as_ref()
+                        .map(|close| close_payload(close.code, ""))
+                        .unwrap_or_default();
+                    self.write_frame(Frame::new(OpCode::Close, payload));
+                }
+                self.state = State::Closed;
+                Ok(Some(Message::Close(close)))
+            }
+            OpCode::Text | OpCode::Binary => {
+                if self.fragments.is_some() {
+                    return Err(FrameError::protocol("expected continuation frame"));
+                }
+                if frame.rsv1 && self.deflate.is_none() {
+                    return Err(FrameError::protocol("reserved bit set"));
+                }
+                if frame.fin {
+                    return self
+                        .message(frame.opcode, frame.rsv1, frame.payload)
+                        .map(Some);
+                }
+                self.check_message_size(frame.payload.len())?;
+                self.fragments = Some(Fragments {
+                    opcode: frame.opcode,
+                    compressed: frame.rsv1,
+                    payload: frame.payload,
+                });
+                Ok(None)
+            }
+            OpCode::Continuation => {
+                let fragments = self
+                    .fragments
+                    .as_mut()
+                    .ok_or(FrameError::protocol("unexpected continuation frame"))?;
+                fragments.payload.extend_from_slice(&frame.payload);
+                let size = fragments.payload.len();
+                self.check_message_size(size)?;
+                if !frame.fin {
+                    return Ok(None);
+                }
+                let fragments = self.fragments.take().expect("fragments");
+                self.message(fragments.opcode, fragments.compressed, fragments.payload)
+                    .map(Some)
+            }
+        }
+    }
+
+    /// Create the (complete) data message received.
+    fn message(
+        &mut self,
+        opcode: OpCode,
+        compressed: bool,
+        payload: Vec<u8>,
+    ) -> Result<Message, FrameError> {
+        let payload = match self.deflate.as_mut() {
+            Some(deflate) if compressed => {
+                deflate.decompress(&payload, self.config.max_message_size)?
+            }
+            _ => {
+                self.check_message_size(payload.len())?;
+                payload
+            }
+        };
+        if opcode == OpCode::Text {
+            String::from_utf8(payload)
+                .map(Message::Text)
+                .map_err(|_| FrameError {
+                    code: CloseCode::InvalidPayload,
+                    reason: "invalid utf-8 text message",
+                })
+        } else {
+            Ok(Message::Binary(payload))
+        }
+    }
+
+    fn check_message_size(&self, size: usize) -> Result<(), FrameError> {
+        if self.config.max_message_size.is_some_and(|max| size > max) {
+            return Err(FrameError {
+                code: CloseCode::MessageTooBig,
+                reason: "message too large",
+            });
+        }
+        Ok(())
+    }
+
+    /// Buffer the given message, to be written by the next flush.
+    fn write_message(&mut self, message: Message) -> Result<(), OpaqueError> {
+        if self.state != State::Open {
+            return Err(OpaqueError::from_display(
+                "websocket: cannot send message after close",
+            ));
+        }
+
+        let (opcode, payload) = match message {
+            Message::Text(text) => (OpCode::Text, text.into_bytes()),
+            Message::Binary(data) => (OpCode::Binary, data),
+            Message::Ping(data) | Message::Pong(data) if data.len() > 125 => {
+                return Err(OpaqueError::from_display(
+                    "websocket: control message payload too large",
+                ));
+            }
+            Message::Ping(data) => {
+                self.write_frame(Frame::new(OpCode::Ping, data));
+                return Ok(());
+            }
+            Message::Pong(data) => {
+                self.write_frame(Frame::new(OpCode::Pong, data));
+                return Ok(());
+            }
+            Message::Close(close) => {
+                let payload = match close {
+                    Some(close) => {
+                        if !close.code.is_allowed() {
+                            return Err(OpaqueError::from_display(format!(
+                                "websocket: close code not allowed to be sent: {}",
+                                close.code
+                            )));
+                        }
+                        if close.reason.len() > 123 {
+                            return Err(OpaqueError::from_display(
+                                "websocket: close reason too long",
+                            ));
+                        }
+                        close_payload(close.code, &close.reason)
+                    }
+                    None => Vec::new(),
+                };
+                self.write_frame(Frame::new(OpCode::Close, payload));
+                self.state = State::CloseSent;
+                return Ok(());
+            }
+        };
+
+        let (payload, compressed) = match self.deflate.as_mut() {
+            Some(deflate) => (deflate.compress(&payload)?, true),
+            None => (payload, false),
+        };
+
+        let fragment_size = self.config.fragment_size.unwrap_or(usize::MAX);
+        if payload.len() <= fragment_size {
+            self.write_frame(Frame {
+                fin: true,
+                rsv1: compressed,
+                opcode,
+                payload,
+            });
+            return Ok(());
+        }
+
+        let mut chunks = payload.chunks(fragment_size).peekable();
+        let mut first = true;
+        while let Some(chunk) = chunks.next() {
+            self.write_frame(Frame {
+                fin: chunks.peek().is_none(),
+                rsv1: compressed && first,
+                opcode: if first { opcode } else { OpCode::Continuation },
+                payload: chunk.to_vec(),
+            });
+            first = false;
+        }
+        Ok(())
+    }
+
+    /// Buffer the given frame, masking it in case we are the client.
+    fn write_frame(&mut self, frame: Frame) {
+        tracing::trace!(role = %self.role, ?frame, "websocket: frame sent");
+        let mask = match self.role {
+            Role::Client => Some(rand::random()),
+            Role::Server => None,
+        };
+        frame.encode(mask, &mut self.write_buf);
+    }
+}
+
+fn close_payload(code: CloseCode, reason: &str) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(2 + reason.len());
+    payload.extend_from_slice(&u16::from(code).to_be_bytes());
+    payload.extend_from_slice(reason.as_bytes());
+    payload
+}
+
+fn parse_close_payload(payload: &[u8]) -> Result<Option<CloseFrame>, FrameError> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err(FrameError::protocol("invalid close frame payload")),
+        [hi, lo, reason @ ..] => {
+            let code = CloseCode::from(u16::from_be_bytes([*hi, *lo]));
+            if !code.is_allowed() {
+                return Err(FrameError::protocol("invalid close code"));
+            }
+            let reason = std::str::from_utf8(reason).map_err(|_| FrameError {
+                code: CloseCode::InvalidPayload,
+                reason: "invalid utf-8 close reason",
+            })?;
+            Ok(Some(CloseFrame::new(code, reason)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, DuplexStream};
+
+    fn socket_pair(config: WebSocketConfig) -> (WebSocket<DuplexStream>, WebSocket<DuplexStream>) {
+        let (client, server) = duplex(64 * 1024);
+        (
+            WebSocket::new(client, Role::Client, config.clone()),
+            WebSocket::new(server, Role::Server, config),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_websocket_messages() {
+        let deflate = PerMessageDeflateConfig::new();
+        for (config, compressed) in [
+            (WebSocketConfig::new(), false),
+            (WebSocketConfig::new().with_fragment_size(Some(3)), false),
+            (WebSocketConfig::new().with_fragment_size(Some(3)), true),
+        ] {
+            let (mut client, mut server) = socket_pair(config);
+            if compressed {
+                client = client.with_per_message_deflate(&deflate);
+                server = server.with_per_message_deflate(&deflate);
+            }
+
+            for message in [
+                Message::text("Hello, WebSocket!"),
+                Message::binary(vec![0xab; 1000]),
+                Message::text(""),
+            ] {
+                client.send(message.clone()).await.unwrap();
+                assert_eq!(server.recv().await.unwrap(), Some(message.clone()));
+                server.send(message.clone()).await.unwrap();
+                assert_eq!(client.recv().await.unwrap(), Some(message));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_websocket_ping_pong_and_close() {
+        let (mut client, mut server) = socket_pair(WebSocketConfig::new());
+
+        client.send(Message::Ping(b"ping".to_vec())).await.unwrap();
+        assert_eq!(
+            server.recv().await.unwrap(),
+            Some(Message::Ping(b"ping".to_vec()))
+        );
+        server.send(Message::text("hi")).await.unwrap();
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::Pong(b"ping".to_vec()))
+        );
+        assert_eq!(client.recv().await.unwrap(), Some(Message::text("hi")));
+
+        let close = CloseFrame::new(CloseCode::Normal, "bye");
+        client
+            .send(Message::Close(Some(close.clone())))
+            .await
+            .unwrap();
+        assert!(!client.is_open());
+        assert!(client.send(Message::text("too late")).await.is_err());
+
+        assert_eq!(
+            server.recv().await.unwrap(),
+            Some(Message::Close(Some(close)))
+        );
+        assert_eq!(server.recv().await.unwrap(), None);
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::Close(Some(CloseFrame::new(CloseCode::Normal, ""))))
+        );
+        assert_eq!(client.recv().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_protocol_violation() {
+        let (client, server) = duplex(1024);
+        let mut client = WebSocket::new(client, Role::Client, WebSocketConfig::new());
+        // raw server sending an invalid utf-8 text message
+        let mut server = server;
+        server.write_all(&[0x81, 0x02, 0xc3, 0x28]).await.unwrap();
+
+        let err = client.recv().await.unwrap_err();
+        assert!(err.to_string().contains("1007"), "{err}");
+
+        let mut buf = BytesMut::new();
+        server.read_buf(&mut buf).await.unwrap();
+        let frame = Frame::decode(&mut buf, Role::Server, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.opcode, OpCode::Close);
+        assert_eq!(frame.payload[..2], 1007u16.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_message_too_big() {
+        let (mut client, server) = socket_pair(WebSocketConfig::new().with_fragment_size(Some(4)));
+        let mut server = WebSocket::new(
+            server.into_inner(),
+            Role::Server,
+            WebSocketConfig::new().with_max_message_size(Some(6)),
+        );
+
+        client.send(Message::text("0123456789")).await.unwrap();
+        let err = server.recv().await.unwrap_err();
+        assert!(err.to_string().contains("1009"), "{err}");
+        assert!(matches!(
+            client.recv().await.unwrap(),
+            Some(Message::Close(Some(CloseFrame {
+                code: CloseCode::MessageTooBig,
+                ..
+            })))
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use rama_error::{ErrorContext, OpaqueError};
+use serde::Serialize;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[must_use]
+/// A Server-Sent Event (SSE), as sent by an [`Sse`] response
+/// or received via an [`EventStream`].
+///
+/// [`Sse`]: super::Sse
+/// [`EventStream`]: super::EventStream
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    retry: Option<Duration>,
+    comments: Vec<String>,
+}
+
+impl Event {
+    /// Create a new empty [`Event`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the identifier of this [`Event`], if any.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Set the identifier of this [`Event`],
+    /// used by clients to resume the stream using the `Last-Event-ID` header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the identifier contains a newline, carriage return or null character.
+    pub fn set_id(&mut self, id: impl Into<String>) -> &mut Self {
+        let id = id.into();
+        assert!(
+            !id.contains(['\n', '\r', '\0']),
+            "SSE id cannot contain newlines, carriage returns or null characters"
+        );
+        self.id = Some(id);
+        self
+    }
+
+    /// Replace this [`Event`] with the identifier set.
+    ///
+    /// See [`Event::set_id`] for more information.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.set_id(id);
+        self
+    }
+
+    /// Return the type of this [`Event`], if any.
+    ///
+    /// Clients consider events without a type as `message` events.
+    pub fn event(&self) -> Option<&str> {
+        self.event.as_deref()
+    }
+
+    /// Set the type of this [`Event`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type contains a newline or carriage return.
+    pub fn set_event(&mut self, event: impl Into<String>) -> &mut Self {
+        let event = event.into();
+        assert!(
+            !event.contains(['\n', '\r']),
+            "SSE event type cannot contain newlines or carriage returns"
+        );
+        self.event = Some(event);
+        self
+    }
+
+    /// Replace this [`Event`] with the type set.
+    ///
+    /// See [`Event::set_event`] for more information.
+    pub fn with_event(mut self, event: impl Into<String>) -> Self {
+        self.set_event(event);
+        self
+    }
+
+    /// Return the data of this [`Event`], if any.
+    pub fn data(&self) -> Option<&str> {
+        self.data.as_deref()
+    }
+
+    /// Set the data of this [`Event`].
+    ///
+    /// Data containing newlines is sent as multiple `data` fields,
+    /// which clients join again using newlines.
+    pub fn set_data(&mut self, data: impl Into<String>) -> &mut Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Replace this [`Event`] with the data set.
+    ///
+    /// See [`Event::set_data`] for more information.
+    pub fn with_data(mut self, data: impl Into<String>) -> Self {
+        self.set_data(data);
+        self
+    }
+
+    /// Try to set the data of this [`Event`] to the JSON serialization of the given value.
+    pub fn try_set_json_data<T: Serialize>(&mut self, data: &T) -> Result<&mut Self, OpaqueError> {
+        let data = serde_json::to_string(data).context("serialize SSE data as JSON")?;
+        Ok(self.set_data(data))
+    }
+
+    /// Try to replace this [`Event`] with the data set
+    /// to the JSON serialization of the given value.
+    pub fn try_with_json_data<T: Serialize>(mut self, data: &T) -> Result<Self, OpaqueError> {
+        self.try_set_json_data(data)?;
+        Ok(self)
+    }
+
+    /// Return the reconnection time of this [`Event`], if any.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// Set the reconnection time, which clients use to wait before reconnecting
+    /// in case the stream got interrupted.
+    pub fn set_retry(&mut self, retry: Duration) -> &mut Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Replace this [`Event`] with the reconnection time set.
+    ///
+    /// See [`Event::set_retry`] for more information.
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.set_retry(retry);
+        self
+    }
+
+    /// Return the comments of this [`Event`].
+    ///
+    /// Comments are ignored by clients, and are as such never set for a received [`Event`].
+    pub fn comments(&self) -> &[String] {
+        &self.comments[..]
+    }
+
+    /// Add a comment to this [`Event`].
+    ///
+    /// Comments containing newlines are sent as multiple comment lines.
+    pub fn add_comment(&mut self, comment: impl Into<String>) -> &mut Self {
+        self.comments.push(comment.into());
+        self
+    }
+
+    /// Replace this [`Event`] with the comment added.
+    ///
+    /// See [`Event::add_comment`] for more information.
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.add_comment(comment);
+        self
+    }
+
+    pub(super) fn set_parsed(
+        &mut self,
+        id: Option<String>,
+        event: Option<String>,
+        data: String,
+        retry: Option<Duration>,
+    ) {
+        self.id = id;
+        self.event = event;
+        self.data = Some(data);
+        self.retry = retry;
+    }
+
+    /// Serialize this [`Event`] in the `text/event-stream` format.
+    pub(super) fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        for comment in &self.comments {
+            for line in lines(comment) {
+                put_field(&mut buf, "", line);
+            }
+        }
+        if let Some(id) = &self.id {
+            put_field(&mut buf, "id", id);
+        }
+        if let Some(event) = &self.event {
+            put_field(&mut buf, "event", event);
+        }
+        if let Some(retry) = self.retry {
+            put_field(&mut buf, "retry", &retry.as_millis().to_string());
+        }
+        if let Some(data) = &self.data {
+            for line in lines(data) {
+                put_field(&mut buf, "data", line);
+            }
+        }
+        buf.put_u8(b'\n');
+        buf.freeze()
+    }
+}
+
+fn put_field(buf: &mut BytesMut, name: &str, value: &str) {
+    buf.put_slice(name.as_bytes());
+    buf.put_u8(b':');
+    if !value.is_empty() {
+        buf.put_u8(b' ');
+        buf.put_slice(value.as_bytes());
+    }
+    buf.put_u8(b'\n');
+}
+
+/// Split the value in lines, using any of the line terminators
+/// supported by the `text/event-stream` format.
+fn lines(value: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(value);
+    std::iter::from_fn(move || {
+        let value = rest?;
+        match value.find(['\n', '\r']) {
+            Some(index) => {
+                let skip = if value[index..].starts_with("\r\n") {
+                    2
+                } else {
+                    1
+                };
+                rest = Some(&value[index + skip..]);
+                Some(&value[..index])
+            }
+            None => {
+                rest = None;
+                Some(value)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_to_bytes() {
+        let event = Event::new()
+            .with_comment("hello")
+            .with_id("42")
+            .with_event("stats")
+            .with_retry(Duration::from_secs(3))
+            .with_data("a\nb\r\nc");
+        assert_eq!(
+            event.to_bytes(),
+            ": hello\nid: 42\nevent: stats\nretry: 3000\ndata: a\ndata: b\ndata: c\n\n"
+        );
+
+        assert_eq!(Event::new().with_data("").to_bytes(), "data:\n\n");
+        assert_eq!(Event::new().with_comment("").to_bytes(), ":\n\n");
+    }
+
+    #[test]
+    fn test_event_json_data() {
+        let event = Event::new()
+            .try_with_json_data(&serde_json::json!({"rps": 42}))
+            .unwrap();
+        assert_eq!(event.data(), Some(r#"{"rps":42}"#));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_event_type_with_newline() {
+        let _ = Event::new().with_event("a\nb");
+    }
+}
@@ -0,0 +1,280 @@
+//! Server-Sent Events (SSE) responses and client-side parsing.
+//!
+//! Use [`Sse`] to respond with a stream of [`Event`]s,
+//! and [`EventStream`] to consume such a response as a client.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_http_types::response::{sse::{Event, KeepAlive, Sse}, IntoResponse};
+//! use std::{convert::Infallible, time::Duration};
+//!
+//! async fn handler() -> impl IntoResponse {
+//!     let events = futures_lite::stream::iter((0..3).map(|n| {
+//!         Ok::<_, Infallible>(Event::new().with_event("tick").with_data(n.to_string()))
+//!     }));
+//!     Sse::new(events).with_keep_alive(KeepAlive::new().with_interval(Duration::from_secs(5)))
+//! }
+//! ```
+//!
+//! # Reference
+//!
+//! - <https://html.spec.whatwg.org/multipage/server-sent-events.html>
+
+use crate::{header, Body, HeaderValue, IntoResponse, Response};
+use bytes::Bytes;
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use rama_error::BoxError;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+use tokio::time::{Instant, Sleep};
+
+mod event;
+#[doc(inline)]
+pub use event::Event;
+
+mod stream;
+#[doc(inline)]
+pub use stream::EventStream;
+
+/// The media type of Server-Sent Events.
+const TEXT_EVENT_STREAM: &str = "text/event-stream";
+
+/// A Server-Sent Events (SSE) response, streaming [`Event`]s to the client.
+///
+/// Will automatically get `Content-Type: text/event-stream`
+/// and `Cache-Control: no-cache`.
+///
+/// Responses are not compressed by the compression layer of `rama-http`
+/// when using its default predicate, as compression would buffer
+/// the events instead of sending them as soon as they are produced.
+#[must_use]
+pub struct Sse<S> {
+    stream: S,
+    keep_alive: Option<KeepAlive>,
+}
+
+impl<S> Sse<S> {
+    /// Create a new [`Sse`] response, streaming the [`Event`]s of the given stream.
+    ///
+    /// Keep-alive comments are not sent unless enabled using [`Sse::with_keep_alive`].
+    pub fn new<E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Event, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        Self {
+            stream,
+            keep_alive: None,
+        }
+    }
+
+    /// Set the [`KeepAlive`] used to send comments to the client
+    /// in case no [`Event`] was sent for a while, such that
+    /// (proxy) connections are not closed because of inactivity.
+    pub fn set_keep_alive(&mut self, keep_alive: KeepAlive) -> &mut Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    /// Replace this [`Sse`] response with the [`KeepAlive`] set.
+    ///
+    /// See [`Sse::set_keep_alive`] for more information.
+    pub fn with_keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+}
+
+impl<S> fmt::Debug for Sse<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sse")
+            .field("stream", &format_args!("{}", std::any::type_name::<S>()))
+            .field("keep_alive", &self.keep_alive)
+            .finish()
+    }
+}
+
+impl<S, E> IntoResponse for Sse<S>
+where
+    S: Stream<Item = Result<Event, E>> + Send + 'static,
+    E: Into<BoxError>,
+{
+    fn into_response(self) -> Response {
+        let keep_alive = self.keep_alive.map(|keep_alive| {
+            let sleep = Box::pin(tokio::time::sleep(keep_alive.interval));
+            (keep_alive.to_bytes(), keep_alive.interval, sleep)
+        });
+        (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(TEXT_EVENT_STREAM),
+                ),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            Body::from_stream(SseStream {
+                stream: self.stream,
+                keep_alive,
+            }),
+        )
+            .into_response()
+    }
+}
+
+pin_project! {
+    struct SseStream<S> {
+        #[pin]
+        stream: S,
+        keep_alive: Option<(Bytes, Duration, Pin<Box<Sleep>>)>,
+    }
+}
+
+impl<S, E> Stream for SseStream<S>
+where
+    S: Stream<Item = Result<Event, E>>,
+    E: Into<BoxError>,
+{
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        match this.stream.poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                if let Some((_, interval, sleep)) = this.keep_alive.as_mut() {
+                    sleep.as_mut().reset(Instant::now() + *interval);
+                }
+                Poll::Ready(Some(Ok(event.to_bytes())))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => {
+                let Some((comment, interval, sleep)) = this.keep_alive.as_mut() else {
+                    return Poll::Pending;
+                };
+                ready!(sleep.as_mut().poll(cx));
+                sleep.as_mut().reset(Instant::now() + *interval);
+                Poll::Ready(Some(Ok(comment.clone())))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Configuration of the keep-alive comments sent by an [`Sse`] response.
+pub struct KeepAlive {
+    interval: Duration,
+    text: String,
+}
+
+impl KeepAlive {
+    /// Create a new [`KeepAlive`], sending an empty comment
+    /// after 15 seconds of inactivity.
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            text: String::new(),
+        }
+    }
+
+    /// Set the duration of inactivity after which a keep-alive comment is sent.
+    ///
+    /// Default is 15 seconds.
+    pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Replace this [`KeepAlive`] with the interval set.
+    ///
+    /// See [`KeepAlive::set_interval`] for more information.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the text of the keep-alive comment.
+    ///
+    /// Default is an empty comment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the text contains a newline or carriage return.
+    pub fn set_text(&mut self, text: impl Into<String>) -> &mut Self {
+        let text = text.into();
+        assert!(
+            !text.contains(['\n', '\r']),
+            "SSE keep-alive text cannot contain newlines or carriage returns"
+        );
+        self.text = text;
+        self
+    }
+
+    /// Replace this [`KeepAlive`] with the text set.
+    ///
+    /// See [`KeepAlive::set_text`] for more information.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.set_text(text);
+        self
+    }
+
+    fn to_bytes(&self) -> Bytes {
+        Event::new().with_comment(self.text.clone()).to_bytes()
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+    use futures_lite::StreamExt;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_sse_response() {
+        let events = futures_lite::stream::iter([
+            Ok::<_, Infallible>(Event::new().with_data("hello")),
+            Ok(Event::new().with_event("bye").with_data("world")),
+        ]);
+        let resp = Sse::new(events).into_response();
+
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], TEXT_EVENT_STREAM);
+        assert_eq!(resp.headers()[header::CACHE_CONTROL], "no-cache");
+
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "data: hello\n\nevent: bye\ndata: world\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_sse_keep_alive() {
+        let events =
+            futures_lite::stream::iter([Ok::<_, Infallible>(Event::new().with_data("hello"))])
+                .chain(futures_lite::stream::pending());
+        let resp = Sse::new(events)
+            .with_keep_alive(
+                KeepAlive::new()
+                    .with_interval(Duration::from_millis(10))
+                    .with_text("ping"),
+            )
+            .into_response();
+
+        let mut body = resp.into_body();
+        for expected in ["data: hello\n\n", ": ping\n\n", ": ping\n\n"] {
+            let frame = body.frame().await.unwrap().unwrap();
+            assert_eq!(frame.into_data().unwrap(), expected);
+        }
+    }
+}
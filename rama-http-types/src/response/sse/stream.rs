@@ -0,0 +1,416 @@
+use super::{Event, TEXT_EVENT_STREAM};
+use crate::{dep::http_body, header, Response};
+use bytes::{Buf, BytesMut};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use rama_error::{BoxError, OpaqueError};
+use std::{
+    fmt,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+/// The default maximum size of a single event, see [`EventStream::with_max_event_size`].
+const DEFAULT_MAX_EVENT_SIZE: usize = 1024 * 1024;
+
+pin_project! {
+    /// A [`Stream`] of [`Event`]s, parsed from a `text/event-stream` body.
+    ///
+    /// Used by clients (e.g. consumers of the `HttpClient`) to consume
+    /// Server-Sent Events, such as the ones sent using an [`Sse`] response.
+    ///
+    /// Comments (e.g. keep-alive comments) are ignored,
+    /// as are events which define no data.
+    ///
+    /// An error is returned, ending the stream, in case an event
+    /// exceeds the max event size, see [`EventStream::with_max_event_size`].
+    ///
+    /// [`Sse`]: super::Sse
+    pub struct EventStream<B> {
+        #[pin]
+        body: B,
+        parser: EventParser,
+        done: bool,
+    }
+}
+
+impl<B> EventStream<B> {
+    /// Create a new [`EventStream`], parsing the [`Event`]s of the given body.
+    pub fn new(body: B) -> Self {
+        Self {
+            body,
+            parser: EventParser::default(),
+            done: false,
+        }
+    }
+
+    /// Set the maximum size (in bytes) of the fields of a single event,
+    /// as to bound the memory used to parse it.
+    ///
+    /// Defaults to 1 MiB.
+    pub fn with_max_event_size(mut self, size: usize) -> Self {
+        self.parser.max_event_size = size;
+        self
+    }
+
+    /// Set the maximum size (in bytes) of the fields of a single event,
+    /// as to bound the memory used to parse it.
+    ///
+    /// Defaults to 1 MiB.
+    pub fn set_max_event_size(&mut self, size: usize) -> &mut Self {
+        self.parser.max_event_size = size;
+        self
+    }
+
+    /// Return the last event identifier received, if any.
+    ///
+    /// It is to be sent as the `Last-Event-ID` header when reconnecting,
+    /// such that the server can resume the stream.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.parser.last_event_id.as_deref()
+    }
+
+    /// Return the last reconnection time received, if any.
+    pub fn retry(&self) -> Option<Duration> {
+        self.parser.retry
+    }
+}
+
+impl<B> EventStream<B>
+where
+    B: http_body::Body,
+{
+    /// Try to create a new [`EventStream`] from the body of the given [`Response`],
+    /// failing in case it is not a `text/event-stream` response.
+    pub fn try_from_response(resp: Response<B>) -> Result<Self, OpaqueError> {
+        let is_event_stream = resp
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case(TEXT_EVENT_STREAM));
+        if !is_event_stream {
+            return Err(OpaqueError::from_display(
+                "response is not a text/event-stream response",
+            ));
+        }
+        Ok(Self::new(resp.into_body()))
+    }
+}
+
+impl<B> fmt::Debug for EventStream<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStream")
+            .field("parser", &self.parser)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl<B> Stream for EventStream<B>
+where
+    B: http_body::Body<Error: Into<BoxError>>,
+{
+    type Item = Result<Event, BoxError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            match this.parser.next_event() {
+                Ok(Some(event)) => return Poll::Ready(Some(Ok(event))),
+                Ok(None) => (),
+                Err(err) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+            }
+            if *this.done {
+                return Poll::Ready(None);
+            }
+            match ready!(this.body.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    if let Ok(mut data) = frame.into_data() {
+                        while data.has_remaining() {
+                            let chunk = data.chunk();
+                            let len = chunk.len();
+                            this.parser.buffer.extend_from_slice(chunk);
+                            data.advance(len);
+                        }
+                    }
+                }
+                Some(Err(err)) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+                None => {
+                    // an incomplete event at the end of the stream is discarded
+                    *this.done = true;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EventParser {
+    buffer: BytesMut,
+    max_event_size: usize,
+    event_size: usize,
+    bom_checked: bool,
+    skip_lf: bool,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    event_retry: Option<Duration>,
+}
+
+impl Default for EventParser {
+    fn default() -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            max_event_size: DEFAULT_MAX_EVENT_SIZE,
+            event_size: 0,
+            bom_checked: false,
+            skip_lf: false,
+            last_event_id: None,
+            retry: None,
+            id: None,
+            event: None,
+            data: None,
+            event_retry: None,
+        }
+    }
+}
+
+impl EventParser {
+    /// Parse the buffered lines, until an [`Event`] is dispatched
+    /// or no complete line remains.
+    ///
+    /// An error is returned in case the event being parsed exceeds the max event size.
+    fn next_event(&mut self) -> Result<Option<Event>, OpaqueError> {
+        loop {
+            if self.skip_lf && !self.buffer.is_empty() {
+                if self.buffer[0] == b'\n' {
+                    self.buffer.advance(1);
+                }
+                self.skip_lf = false;
+            }
+            if !self.bom_checked {
+                if self.buffer.len() < 3 && b"\xEF\xBB\xBF".starts_with(&self.buffer) {
+                    return Ok(None);
+                }
+                if self.buffer.starts_with(b"\xEF\xBB\xBF") {
+                    self.buffer.advance(3);
+                }
+                self.bom_checked = true;
+            }
+
+            // comments are not part of an event, and thus do not count towards its size
+            let is_comment = self.buffer.first() == Some(&b':');
+            let Some(index) = self.buffer.iter().position(|b| *b == b'\n' || *b == b'\r') else {
+                // an incomplete comment is limited as well, as it is buffered until complete
+                let size = if is_comment {
+                    self.buffer.len()
+                } else {
+                    self.event_size + self.buffer.len()
+                };
+                if size > self.max_event_size {
+                    return Err(self.max_event_size_exceeded());
+                }
+                return Ok(None);
+            };
+            if !is_comment {
+                self.event_size += index;
+                if self.event_size > self.max_event_size {
+                    return Err(self.max_event_size_exceeded());
+                }
+            }
+
+            self.skip_lf = self.buffer[index] == b'\r';
+            let line = self.buffer.split_to(index + 1);
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line[..index])) {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    /// Discard the buffered input and the event being parsed,
+    /// returning the error to end the stream with.
+    fn max_event_size_exceeded(&mut self) -> OpaqueError {
+        self.buffer.clear();
+        self.dispatch();
+        OpaqueError::from_display(format!(
+            "event stream: event exceeds the max event size of {} bytes",
+            self.max_event_size
+        ))
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_owned()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_owned()),
+            },
+            "id" if !value.contains('\0') => {
+                self.id = Some(value.to_owned());
+                self.last_event_id = Some(value.to_owned());
+            }
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    let retry = Duration::from_millis(ms);
+                    self.retry = Some(retry);
+                    self.event_retry = Some(retry);
+                }
+            }
+            _ => (),
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        self.event_size = 0;
+        let id = self.id.take();
+        let event = self.event.take();
+        let retry = self.event_retry.take();
+        let data = self.data.take()?;
+
+        let mut ev = Event::new();
+        ev.set_parsed(id, event, data, retry);
+        Some(ev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{response::sse::Sse, Body, IntoResponse};
+    use futures_lite::StreamExt;
+    use std::convert::Infallible;
+
+    async fn parse_chunks(chunks: Vec<&'static str>) -> Vec<Event> {
+        let stream = futures_lite::stream::iter(chunks.into_iter().map(Ok::<_, Infallible>));
+        EventStream::new(Body::from_stream(stream))
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_parse() {
+        let events = parse_chunks(vec![
+            "\u{FEFF}: keep-alive\n\n",
+            "id: 1\nevent: stats\nretry: 1000\ndata: a\ndata:b\n\n",
+            "data\n\n",
+            "id: 2\n\n",
+            "data: c\r\n\r",
+            "\ndata: d\rdata: e\r\rdata: incomplete",
+        ])
+        .await;
+
+        assert_eq!(
+            events,
+            vec![
+                Event::new()
+                    .with_id("1")
+                    .with_event("stats")
+                    .with_retry(Duration::from_secs(1))
+                    .with_data("a\nb"),
+                Event::new().with_data(""),
+                Event::new().with_data("c"),
+                Event::new().with_data("d\ne"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_split_lines() {
+        let events = parse_chunks(vec!["da", "ta: he", "llo\r", "\n", "\r\n"]).await;
+        assert_eq!(events, vec![Event::new().with_data("hello")]);
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_max_event_size() {
+        let parse = |chunks: Vec<&'static str>| async move {
+            let stream = futures_lite::stream::iter(chunks.into_iter().map(Ok::<_, Infallible>));
+            let mut stream = EventStream::new(Body::from_stream(stream)).with_max_event_size(16);
+            let mut results = Vec::new();
+            while let Some(result) = stream.next().await {
+                results.push(result.map_err(|err| err.to_string()));
+            }
+            results
+        };
+
+        // comments do not count towards the size of an event
+        let results = parse(vec![": keep-alive comment\n", "data: 0123456789\n\n"]).await;
+        assert_eq!(results, vec![Ok(Event::new().with_data("0123456789"))]);
+
+        // the size is bound across lines, as well as for a line which is not yet complete
+        for chunks in [
+            vec!["data: 0123456789\n", "data: 0123456789\n\n"],
+            vec!["data: 0123456789", "0123456789"],
+            vec![": an incomplete comment"],
+        ] {
+            let results = parse(chunks).await;
+            assert_eq!(results.len(), 1);
+            assert!(results[0].is_err());
+        }
+
+        let results = parse(vec!["data: a\n\ndata: 0123456789012345\n\ndata: b\n\n"]).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], Ok(Event::new().with_data("a")));
+        assert!(results[1].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_from_sse_response() {
+        let sent = vec![
+            Event::new().with_id("a").with_data("multi\nline"),
+            Event::new()
+                .with_comment("ignored")
+                .with_event("json")
+                .try_with_json_data(&[1, 2, 3])
+                .unwrap(),
+        ];
+        let resp = Sse::new(futures_lite::stream::iter(
+            sent.clone().into_iter().map(Ok::<_, Infallible>),
+        ))
+        .into_response();
+
+        let mut stream = EventStream::try_from_response(resp).unwrap();
+        let mut received = Vec::new();
+        while let Some(event) = stream.next().await {
+            received.push(event.unwrap());
+        }
+
+        assert_eq!(received[0], sent[0]);
+        assert_eq!(
+            received[1],
+            Event::new().with_event("json").with_data("[1,2,3]")
+        );
+        assert_eq!(stream.last_event_id(), Some("a"));
+    }
+
+    #[test]
+    fn test_event_stream_from_non_sse_response() {
+        let resp = Response::new(Body::from("hello"));
+        assert!(EventStream::try_from_response(resp).is_err());
+    }
+}
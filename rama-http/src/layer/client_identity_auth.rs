@@ -0,0 +1,189 @@
+//! Middleware that authorizes requests based on the [`ClientIdentity`]
+//! of the mutual TLS (mTLS) connection they are received on.
+//!
+//! If the request is not authorized a `403 Forbidden` response will be sent.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_http::layer::client_identity_auth::ClientIdentityAuthLayer;
+//! use rama_http::{Body, Request, Response, StatusCode};
+//! use rama_net::tls::server::ClientIdentityPattern;
+//! use rama_core::service::service_fn;
+//! use rama_core::{Context, Service, Layer};
+//! use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let service = ClientIdentityAuthLayer::new(vec![
+//!     ClientIdentityPattern::DnsSan("*.internal.example.com".to_owned()),
+//!     ClientIdentityPattern::UriSan("spiffe://example.com/*".to_owned()),
+//! ])
+//! .layer(service_fn(|_: Request| async {
+//!     Ok::<_, Infallible>(Response::new(Body::empty()))
+//! }));
+//!
+//! // requests received on a connection without (authorized) client identity are rejected
+//! let request = Request::builder().body(Body::empty()).unwrap();
+//! let response = service.serve(Context::default(), request).await.unwrap();
+//! assert_eq!(StatusCode::FORBIDDEN, response.status());
+//! # }
+//! ```
+//!
+//! [`ClientIdentity`]: rama_net::tls::server::ClientIdentity
+
+use crate::{Request, Response, StatusCode};
+use rama_core::{Context, Layer, Service};
+use rama_net::tls::server::{ClientIdentity, ClientIdentityAuthority};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+/// Layer that applies the [`ClientIdentityAuthService`] middleware which authorizes
+/// requests based on the [`ClientIdentity`] of the connection.
+///
+/// See the [module docs](self) for an example.
+#[derive(Debug, Clone)]
+pub struct ClientIdentityAuthLayer<A> {
+    authority: A,
+}
+
+impl<A> ClientIdentityAuthLayer<A> {
+    /// Creates a new [`ClientIdentityAuthLayer`].
+    pub const fn new(authority: A) -> Self {
+        Self { authority }
+    }
+}
+
+impl<A, S> Layer<S> for ClientIdentityAuthLayer<A>
+where
+    A: ClientIdentityAuthority + Clone,
+{
+    type Service = ClientIdentityAuthService<A, S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientIdentityAuthService::new(self.authority.clone(), inner)
+    }
+}
+
+/// Middleware that authorizes requests based on the [`ClientIdentity`] of the connection.
+///
+/// If the request is not authorized a `403 Forbidden` response will be sent.
+///
+/// See the [module docs](self) for an example.
+pub struct ClientIdentityAuthService<A, S> {
+    authority: A,
+    inner: S,
+}
+
+impl<A, S> ClientIdentityAuthService<A, S> {
+    /// Creates a new [`ClientIdentityAuthService`].
+    pub const fn new(authority: A, inner: S) -> Self {
+        Self { authority, inner }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<A: fmt::Debug, S: fmt::Debug> fmt::Debug for ClientIdentityAuthService<A, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentityAuthService")
+            .field("authority", &self.authority)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<A: Clone, S: Clone> Clone for ClientIdentityAuthService<A, S> {
+    fn clone(&self) -> Self {
+        ClientIdentityAuthService {
+            authority: self.authority.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A, S, State, ReqBody, ResBody> Service<State, Request<ReqBody>>
+    for ClientIdentityAuthService<A, S>
+where
+    A: ClientIdentityAuthority,
+    S: Service<State, Request<ReqBody>, Response = Response<ResBody>>,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+    State: Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        if let Some(identity) = ctx.get::<ClientIdentity>().cloned() {
+            if let Some(ext) = self.authority.authorized(identity).await {
+                ctx.extend(ext);
+                return self.inner.serve(ctx, req).await;
+            }
+        }
+
+        Ok(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .body(Default::default())
+            .unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Body;
+    use rama_core::service::service_fn;
+    use rama_net::{tls::server::ClientIdentityPattern, user::UserId};
+    use std::convert::Infallible;
+
+    fn client_identity(common_name: &str) -> ClientIdentity {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, common_name);
+        let cert = params.self_signed(&key_pair).unwrap();
+        ClientIdentity::from_der_chain([cert.der().to_vec()]).unwrap()
+    }
+
+    async fn serve(ctx: Context<()>) -> Response {
+        let service =
+            ClientIdentityAuthLayer::new(ClientIdentityPattern::CommonName("admin-*".to_owned()))
+                .layer(service_fn(|ctx: Context<()>, _: Request| async move {
+                    let user_id = ctx.get::<UserId>().unwrap();
+                    assert_eq!(user_id, "admin-john");
+                    Ok::<_, Infallible>(Response::new(Body::empty()))
+                }));
+        service
+            .serve(ctx, Request::builder().body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_identity_auth_authorized() {
+        let mut ctx = Context::default();
+        ctx.insert(client_identity("admin-john"));
+        assert_eq!(serve(ctx).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_client_identity_auth_unauthorized() {
+        let mut ctx = Context::default();
+        ctx.insert(client_identity("john"));
+        assert_eq!(serve(ctx).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_client_identity_auth_no_identity() {
+        assert_eq!(
+            serve(Context::default()).await.status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+}
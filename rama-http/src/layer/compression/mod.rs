@@ -357,6 +357,41 @@ mod tests {
         assert!(res.headers().get(CONTENT_ENCODING).is_none());
     }
 
+    #[tokio::test]
+    async fn doesnt_compress_sse() {
+        use crate::response::{
+            sse::{Event, Sse},
+            IntoResponse,
+        };
+
+        async fn handle(_req: Request<Body>) -> Result<Response<Body>, Infallible> {
+            let events = futures_lite::stream::iter(
+                (0..2)
+                    .map(|n| Ok::<_, Infallible>(Event::new().with_data("a".repeat(64 * n + 64)))),
+            );
+            Ok(Sse::new(events).into_response())
+        }
+
+        let svc = Compression::new(service_fn(handle));
+
+        let res = svc
+            .serve(
+                Context::default(),
+                Request::builder()
+                    .header(ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+
+        // events are not buffered, but passed through as soon as they are produced
+        let mut body = res.into_body();
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap().len(), "data: \n\n".len() + 64);
+    }
+
     #[tokio::test]
     async fn does_compress_svg() {
         async fn handle(_req: Request<Body>) -> Result<Response<Body>, Infallible> {
@@ -0,0 +1,334 @@
+//! Middleware which stores the cookies received from servers
+//! and sends them along with the next requests, as browsers do.
+//!
+//! See [`CookieStoreLayer`] for more details.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{service::service_fn, Context, Layer, Service};
+//! use rama_http::layer::cookie_store::{CookieStore, CookieStoreLayer};
+//! use rama_http::{header, Body, Request, Response};
+//! use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let store = CookieStore::new();
+//! let client = CookieStoreLayer::new()
+//!     .with_store(store.clone())
+//!     .layer(service_fn(|req: Request| async move {
+//!         let mut res = Response::new(Body::empty());
+//!         if !req.headers().contains_key(header::COOKIE) {
+//!             res.headers_mut()
+//!                 .insert(header::SET_COOKIE, "session=42".parse().unwrap());
+//!         }
+//!         Ok::<_, Infallible>(res)
+//!     }));
+//!
+//! let req = Request::builder()
+//!     .uri("http://example.com/")
+//!     .body(Body::empty())
+//!     .unwrap();
+//! client.serve(Context::default(), req).await.unwrap();
+//!
+//! assert_eq!(store.cookies()[0].value(), "42");
+//! # }
+//! ```
+
+use crate::{header, HeaderValue, Request, Response, Uri};
+use rama_core::{
+    error::{BoxError, ErrorContext},
+    Context, Layer, Service,
+};
+use rama_net::{address::Host, http::RequestContext};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+mod store;
+#[doc(inline)]
+pub use store::CookieStore;
+
+use store::{registrable_domain, RequestOrigin, SiteContext};
+
+/// A [`Layer`] that produces a [`CookieStoreService`],
+/// which stores the cookies set by servers in a [`CookieStore`]
+/// and adds the matching cookies to the requests it sends.
+///
+/// Place it between the follow redirect layer (if any) and the client,
+/// such that the cookies are also stored and sent for redirected requests.
+///
+/// `SameSite` cookies are honoured based on the `Origin` or `Referer` header
+/// of the request: in case that header defines another site, `Strict` cookies
+/// are not sent and `Lax` cookies only for safe methods (e.g. `GET`).
+/// Requests without either header are considered same-site. Sites are compared
+/// by registrable domain, using the [Public Suffix List](https://publicsuffix.org/),
+/// such that e.g. `a.example.com` and `b.example.com` are the same site,
+/// while `a.github.io` and `b.github.io` are not.
+#[derive(Debug, Clone, Default)]
+pub struct CookieStoreLayer {
+    store: CookieStore,
+}
+
+impl CookieStoreLayer {
+    /// Create a new [`CookieStoreLayer`] with a new empty [`CookieStore`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the [`CookieStore`] used by the produced services,
+    /// e.g. to share it with other clients or inspect it afterwards.
+    pub fn set_store(&mut self, store: CookieStore) -> &mut Self {
+        self.store = store;
+        self
+    }
+
+    /// Replace this [`CookieStoreLayer`] with the [`CookieStore`] set.
+    ///
+    /// See [`CookieStoreLayer::set_store`] for more information.
+    pub fn with_store(mut self, store: CookieStore) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Return the [`CookieStore`] used by the produced services.
+    pub fn store(&self) -> &CookieStore {
+        &self.store
+    }
+}
+
+impl<S> Layer<S> for CookieStoreLayer {
+    type Service = CookieStoreService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CookieStoreService {
+            inner,
+            store: self.store.clone(),
+        }
+    }
+}
+
+/// A [`Service`] which sends and stores cookies using a [`CookieStore`].
+///
+/// See [`CookieStoreLayer`] for more details.
+pub struct CookieStoreService<S> {
+    inner: S,
+    store: CookieStore,
+}
+
+impl<S> CookieStoreService<S> {
+    /// Create a new [`CookieStoreService`] with a new empty [`CookieStore`].
+    pub fn new(inner: S) -> Self {
+        CookieStoreLayer::new().layer(inner)
+    }
+
+    /// Return the [`CookieStore`] used by this service.
+    pub fn store(&self) -> &CookieStore {
+        &self.store
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for CookieStoreService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieStoreService")
+            .field("inner", &self.inner)
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for CookieStoreService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<State, S, ReqBody, ResBody> Service<State, Request<ReqBody>> for CookieStoreService<S>
+where
+    State: Send + Sync + 'static,
+    S: Service<State, Request<ReqBody>, Response = Response<ResBody>, Error: Into<BoxError>>,
+    ReqBody: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        mut req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        // computed for each request (instead of using the one in the context),
+        // as the request might be a redirect to another origin
+        let request_ctx = RequestContext::try_from((&ctx, &req))
+            .context("CookieStoreService: compute RequestContext")?;
+        let origin = RequestOrigin {
+            secure: request_ctx.protocol.is_secure(),
+            host: host_str(request_ctx.authority.host()),
+            path: req.uri().path().to_owned(),
+        };
+
+        let site = site_context(&req, &origin.host);
+        if let Some(cookies) = self.store.cookie_header(&origin, site) {
+            let value = match req.headers().get(header::COOKIE) {
+                Some(existing) => {
+                    let mut value = existing.as_bytes().to_vec();
+                    value.extend_from_slice(b"; ");
+                    value.extend_from_slice(cookies.as_bytes());
+                    HeaderValue::from_bytes(&value).unwrap_or(cookies)
+                }
+                None => cookies,
+            };
+            req.headers_mut().insert(header::COOKIE, value);
+        }
+
+        let res = self.inner.serve(ctx, req).await.map_err(Into::into)?;
+        self.store.store_response_cookies(&origin, res.headers());
+        Ok(res)
+    }
+}
+
+fn host_str(host: &Host) -> String {
+    match host {
+        Host::Name(domain) => domain.as_str().trim_end_matches('.').to_ascii_lowercase(),
+        Host::Address(ip) => ip.to_string(),
+    }
+}
+
+/// Determine whether the request is cross-site, using its `Origin` or `Referer` header.
+fn site_context<Body>(req: &Request<Body>, host: &str) -> SiteContext {
+    let Some(initiator) = req
+        .headers()
+        .get(header::ORIGIN)
+        .or_else(|| req.headers().get(header::REFERER))
+    else {
+        return SiteContext::SameSite;
+    };
+
+    let initiator_host = initiator
+        .to_str()
+        .ok()
+        .and_then(|value| value.parse::<Uri>().ok())
+        .and_then(|uri| {
+            uri.host()
+                .map(|host| host.trim_end_matches('.').to_ascii_lowercase())
+        });
+    match initiator_host {
+        Some(initiator_host)
+            if initiator_host == host
+                || registrable_domain(host)
+                    .is_some_and(|site| registrable_domain(&initiator_host) == Some(site)) =>
+        {
+            SiteContext::SameSite
+        }
+        _ => SiteContext::cross_site(req.method()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Body;
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn test_cookie_store_service() {
+        let svc = CookieStoreService::new(service_fn(|req: Request| async move {
+            let cookie = req
+                .headers()
+                .get(header::COOKIE)
+                .map(|value| value.to_str().unwrap().to_owned())
+                .unwrap_or_default();
+            let mut res = Response::new(Body::empty());
+            res.headers_mut()
+                .append(header::SET_COOKIE, "a=1; Path=/".parse().unwrap());
+            res.headers_mut().append(
+                header::SET_COOKIE,
+                "strict=2; Path=/; SameSite=Strict".parse().unwrap(),
+            );
+            res.headers_mut().append(
+                header::SET_COOKIE,
+                "secure=3; Path=/; Secure".parse().unwrap(),
+            );
+            res.headers_mut()
+                .insert("x-cookie", HeaderValue::try_from(cookie).unwrap());
+            Ok::<_, Infallible>(res)
+        }));
+
+        let send = |uri: &'static str, headers: &'static [(&'static str, &'static str)]| {
+            let svc = &svc;
+            async move {
+                let mut req = Request::builder().uri(uri);
+                for (name, value) in headers {
+                    req = req.header(*name, *value);
+                }
+                let res = svc
+                    .serve(Context::default(), req.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                res.headers()["x-cookie"].to_str().unwrap().to_owned()
+            }
+        };
+
+        assert_eq!(send("https://example.com/", &[]).await, "");
+        assert_eq!(
+            send("https://www.example.com/", &[]).await,
+            "",
+            "host-only cookies are not sent to subdomains"
+        );
+        assert_eq!(
+            send("https://example.com/foo", &[("cookie", "x=0")]).await,
+            "x=0; a=1; strict=2; secure=3"
+        );
+        assert_eq!(
+            send("http://example.com/", &[]).await,
+            "a=1; strict=2",
+            "secure cookies are only sent over secure connections"
+        );
+        assert_eq!(
+            send(
+                "https://example.com/",
+                &[("referer", "https://other.org/page")]
+            )
+            .await,
+            "a=1; secure=3",
+            "strict cookies are not sent for cross-site requests"
+        );
+        assert_eq!(
+            send("https://example.com/", &[("origin", "https://example.com")]).await,
+            "a=1; strict=2; secure=3"
+        );
+        assert_eq!(
+            send(
+                "https://example.com/",
+                &[("origin", "https://www.example.com")]
+            )
+            .await,
+            "a=1; strict=2; secure=3",
+            "subdomains are the same site"
+        );
+        assert_eq!(
+            send("https://example.com/", &[("origin", "https://com")]).await,
+            "a=1; secure=3",
+            "public suffixes are another site"
+        );
+
+        assert_eq!(svc.store().len(), 6);
+
+        assert_eq!(send("https://example.github.io/", &[]).await, "");
+        assert_eq!(
+            send(
+                "https://example.github.io/",
+                &[("origin", "https://evil.github.io")]
+            )
+            .await,
+            "a=1; secure=3",
+            "sites under a public suffix are another site"
+        );
+    }
+}
@@ -0,0 +1,700 @@
+use crate::dep::cookie::{Cookie, SameSite};
+use crate::{header, HeaderMap, HeaderValue, Method};
+use parking_lot::Mutex;
+use std::{cmp::Ordering, sync::Arc, time::SystemTime};
+
+#[derive(Debug, Clone, Default)]
+/// A client-side cookie store, following the storage and retrieval rules
+/// of [RFC 6265](https://www.rfc-editor.org/rfc/rfc6265.html).
+///
+/// The store is cheap to clone, all clones share the same cookies.
+/// Used by the [`CookieStoreLayer`], but can also be inspected or cleared directly.
+///
+/// Cookies with a `Domain` attribute that is a public suffix according to the
+/// [Public Suffix List](https://publicsuffix.org/) (e.g. `com`, `co.uk` or `github.io`)
+/// are rejected, unless it is the request host itself.
+///
+/// The limits of [RFC 6265 section 6.1](https://www.rfc-editor.org/rfc/rfc6265.html#section-6.1)
+/// are applied: cookies larger than 4096 bytes are rejected, and the oldest cookies are
+/// evicted once more than 50 cookies are stored for a domain or more than 3000 in total.
+///
+/// [`CookieStoreLayer`]: super::CookieStoreLayer
+pub struct CookieStore {
+    inner: Arc<Mutex<StoreInner>>,
+}
+
+/// Maximum size of a cookie, as measured by the length of its `Set-Cookie` header value.
+const MAX_COOKIE_SIZE: usize = 4096;
+/// Maximum number of cookies stored per domain.
+const MAX_COOKIES_PER_DOMAIN: usize = 50;
+/// Maximum number of cookies stored in total.
+const MAX_COOKIES: usize = 3000;
+
+#[derive(Debug, Default)]
+struct StoreInner {
+    cookies: Vec<StoredCookie>,
+    next_index: u64,
+}
+
+#[derive(Debug)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    expires: Option<SystemTime>,
+    /// Used to order cookies with the same path length by creation.
+    index: u64,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn to_cookie(&self) -> Cookie<'static> {
+        let mut cookie = Cookie::new(self.name.clone(), self.value.clone());
+        if !self.host_only {
+            cookie.set_domain(self.domain.clone());
+        }
+        cookie.set_path(self.path.clone());
+        cookie.set_secure(self.secure);
+        cookie.set_http_only(self.http_only);
+        if let Some(same_site) = self.same_site {
+            cookie.set_same_site(same_site);
+        }
+        if let Some(expires) = self.expires {
+            cookie.set_expires(crate::dep::cookie::time::OffsetDateTime::from(expires));
+        }
+        cookie
+    }
+}
+
+/// The origin of a request, as relevant for the [`CookieStore`].
+#[derive(Debug, Clone)]
+pub(super) struct RequestOrigin {
+    /// Whether the request is sent over a secure protocol (e.g. https).
+    pub(super) secure: bool,
+    /// The (lowercase) host of the request, without trailing dot.
+    pub(super) host: String,
+    /// The path of the request uri.
+    pub(super) path: String,
+}
+
+/// Whether the request is made in a cross-site context,
+/// as relevant for the `SameSite` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SiteContext {
+    SameSite,
+    CrossSite { safe_method: bool },
+}
+
+impl SiteContext {
+    pub(super) fn cross_site(method: &Method) -> Self {
+        Self::CrossSite {
+            safe_method: matches!(
+                *method,
+                Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+            ),
+        }
+    }
+}
+
+impl CookieStore {
+    /// Create a new empty [`CookieStore`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return all (unexpired) cookies in this [`CookieStore`].
+    ///
+    /// The `Domain` attribute is only set for cookies which are not limited to the exact host.
+    pub fn cookies(&self) -> Vec<Cookie<'static>> {
+        let now = SystemTime::now();
+        let mut inner = self.inner.lock();
+        inner.cookies.retain(|cookie| !cookie.is_expired(now));
+        inner.cookies.iter().map(StoredCookie::to_cookie).collect()
+    }
+
+    /// Return the amount of (possibly expired) cookies in this [`CookieStore`].
+    pub fn len(&self) -> usize {
+        self.inner.lock().cookies.len()
+    }
+
+    /// Returns true if this [`CookieStore`] contains no cookies.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().cookies.is_empty()
+    }
+
+    /// Remove all cookies from this [`CookieStore`].
+    pub fn clear(&self) {
+        self.inner.lock().cookies.clear();
+    }
+
+    /// Store the cookies of the `Set-Cookie` headers found in the given response headers.
+    pub(super) fn store_response_cookies(&self, origin: &RequestOrigin, headers: &HeaderMap) {
+        let now = SystemTime::now();
+        let mut inner = self.inner.lock();
+        for value in headers.get_all(header::SET_COOKIE) {
+            if value.len() > MAX_COOKIE_SIZE {
+                tracing::trace!(
+                    size = value.len(),
+                    host = origin.host,
+                    "CookieStore: ignore too large cookie"
+                );
+                continue;
+            }
+            let Some(cookie) = value
+                .to_str()
+                .ok()
+                .and_then(|value| Cookie::parse(value.to_owned()).ok())
+            else {
+                continue;
+            };
+            match stored_cookie(origin, &cookie, now) {
+                Some((stored, expired)) => inner.insert(stored, expired, now),
+                None => tracing::trace!(
+                    name = cookie.name(),
+                    host = origin.host,
+                    "CookieStore: ignore rejected cookie"
+                ),
+            }
+        }
+    }
+
+    /// Return the `Cookie` header value for a request with the given origin, if any cookie matches.
+    pub(super) fn cookie_header(
+        &self,
+        origin: &RequestOrigin,
+        site: SiteContext,
+    ) -> Option<HeaderValue> {
+        let now = SystemTime::now();
+        let mut inner = self.inner.lock();
+        inner.cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matches: Vec<_> = inner
+            .cookies
+            .iter()
+            .filter(|cookie| {
+                (if cookie.host_only {
+                    cookie.domain == origin.host
+                } else {
+                    domain_match(&origin.host, &cookie.domain)
+                }) && path_match(&origin.path, &cookie.path)
+                    && (!cookie.secure || origin.secure)
+                    && match (site, cookie.same_site) {
+                        (SiteContext::SameSite, _) => true,
+                        (SiteContext::CrossSite { .. }, Some(SameSite::Strict)) => false,
+                        (SiteContext::CrossSite { safe_method }, Some(SameSite::Lax)) => {
+                            safe_method
+                        }
+                        (SiteContext::CrossSite { .. }, _) => true,
+                    }
+            })
+            .collect();
+        if matches.is_empty() {
+            return None;
+        }
+
+        // longer paths first, then the oldest cookies
+        matches.sort_by(|a, b| match b.path.len().cmp(&a.path.len()) {
+            Ordering::Equal => a.index.cmp(&b.index),
+            ordering => ordering,
+        });
+
+        let value = matches
+            .iter()
+            .map(|cookie| {
+                if cookie.name.is_empty() {
+                    cookie.value.clone()
+                } else {
+                    format!("{}={}", cookie.name, cookie.value)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        HeaderValue::try_from(value).ok()
+    }
+}
+
+impl StoreInner {
+    /// Insert the cookie, replacing the cookie with the same name, domain and path.
+    ///
+    /// The oldest cookies are evicted in case the cookie
+    /// exceeds the number of cookies allowed for its domain or in total.
+    fn insert(&mut self, mut cookie: StoredCookie, expired: bool, now: SystemTime) {
+        let existing = self.cookies.iter().position(|existing| {
+            existing.name == cookie.name
+                && existing.domain == cookie.domain
+                && existing.path == cookie.path
+        });
+        match existing {
+            Some(index) if expired => {
+                self.cookies.remove(index);
+            }
+            Some(index) => {
+                // keep the creation order of the replaced cookie
+                cookie.index = self.cookies[index].index;
+                self.cookies[index] = cookie;
+            }
+            None if expired => (),
+            None => {
+                cookie.index = self.next_index;
+                self.next_index += 1;
+                let domain = cookie.domain.clone();
+                self.cookies.push(cookie);
+                self.evict(&domain, now);
+            }
+        }
+    }
+
+    /// Evict the oldest cookies (expired cookies first) in case more cookies
+    /// are stored for the given domain or in total than allowed.
+    fn evict(&mut self, domain: &str, now: SystemTime) {
+        let domain_count = |cookies: &[StoredCookie]| {
+            cookies
+                .iter()
+                .filter(|cookie| cookie.domain == domain)
+                .count()
+        };
+        if domain_count(&self.cookies) <= MAX_COOKIES_PER_DOMAIN
+            && self.cookies.len() <= MAX_COOKIES
+        {
+            return;
+        }
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+
+        // cookies are kept in creation order, as replaced cookies keep their position
+        if domain_count(&self.cookies) > MAX_COOKIES_PER_DOMAIN {
+            if let Some(index) = self
+                .cookies
+                .iter()
+                .position(|cookie| cookie.domain == domain)
+            {
+                self.cookies.remove(index);
+            }
+        }
+        if self.cookies.len() > MAX_COOKIES {
+            self.cookies.remove(0);
+        }
+    }
+}
+
+/// Create the [`StoredCookie`] for the cookie received from the given origin,
+/// together with whether it is already expired (and should as such be removed).
+///
+/// Returns `None` in case the cookie has to be rejected.
+fn stored_cookie(
+    origin: &RequestOrigin,
+    cookie: &Cookie<'_>,
+    now: SystemTime,
+) -> Option<(StoredCookie, bool)> {
+    let secure = cookie.secure().unwrap_or_default();
+    if secure && !origin.secure {
+        return None;
+    }
+
+    let (domain, host_only) = match cookie.domain().map(|domain| domain.to_ascii_lowercase()) {
+        Some(domain) if !domain.is_empty() => {
+            let domain = domain.trim_end_matches('.').to_owned();
+            if domain != origin.host
+                && (registrable_domain(&domain).is_none() || !domain_match(&origin.host, &domain))
+            {
+                return None;
+            }
+            (domain, false)
+        }
+        _ => (origin.host.clone(), true),
+    };
+
+    let path = match cookie.path() {
+        Some(path) if path.starts_with('/') => path.to_owned(),
+        _ => default_path(&origin.path).to_owned(),
+    };
+
+    let name = cookie.name();
+    if name.starts_with("__Secure-") && !secure {
+        return None;
+    }
+    if name.starts_with("__Host-") && (!secure || !host_only || path != "/") {
+        return None;
+    }
+
+    let same_site = cookie.same_site();
+    if same_site == Some(SameSite::None) && !secure {
+        return None;
+    }
+
+    let (expires, expired) = match cookie.max_age() {
+        Some(max_age) if max_age.is_positive() => (
+            std::time::Duration::try_from(max_age)
+                .ok()
+                .and_then(|max_age| now.checked_add(max_age)),
+            false,
+        ),
+        Some(_) => (None, true),
+        None => match cookie.expires_datetime() {
+            Some(expires) => {
+                let expires = SystemTime::from(expires);
+                (Some(expires), expires <= now)
+            }
+            None => (None, false),
+        },
+    };
+
+    Some((
+        StoredCookie {
+            name: name.to_owned(),
+            value: cookie.value().to_owned(),
+            domain,
+            host_only,
+            path,
+            secure,
+            http_only: cookie.http_only().unwrap_or_default(),
+            same_site,
+            expires,
+            index: 0,
+        },
+        expired,
+    ))
+}
+
+/// The registrable domain of the host, being its public suffix according to the
+/// [Public Suffix List](https://publicsuffix.org/) and the label before it,
+/// or `None` in case the host is an IP address or a public suffix itself.
+pub(super) fn registrable_domain(host: &str) -> Option<&str> {
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+    psl::domain_str(host)
+}
+
+/// Domain matching, as defined in
+/// [RFC 6265 section 5.1.3](https://www.rfc-editor.org/rfc/rfc6265.html#section-5.1.3).
+pub(super) fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    host.strip_suffix(domain)
+        .is_some_and(|prefix| prefix.ends_with('.'))
+        && host.parse::<std::net::IpAddr>().is_err()
+}
+
+/// Path matching, as defined in
+/// [RFC 6265 section 5.1.4](https://www.rfc-editor.org/rfc/rfc6265.html#section-5.1.4).
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/')
+                || request_path.as_bytes().get(cookie_path.len()) == Some(&b'/')))
+}
+
+/// The default cookie path, as defined in
+/// [RFC 6265 section 5.1.4](https://www.rfc-editor.org/rfc/rfc6265.html#section-5.1.4).
+fn default_path(request_path: &str) -> &str {
+    if !request_path.starts_with('/') {
+        return "/";
+    }
+    match request_path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &request_path[..index],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(secure: bool, host: &str, path: &str) -> RequestOrigin {
+        RequestOrigin {
+            secure,
+            host: host.to_owned(),
+            path: path.to_owned(),
+        }
+    }
+
+    fn set_cookies(store: &CookieStore, origin: &RequestOrigin, values: &[&'static str]) {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(header::SET_COOKIE, HeaderValue::from_static(value));
+        }
+        store.store_response_cookies(origin, &headers);
+    }
+
+    fn cookie_header(store: &CookieStore, origin: &RequestOrigin) -> Option<String> {
+        store
+            .cookie_header(origin, SiteContext::SameSite)
+            .map(|value| value.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn test_domain_and_path_match() {
+        assert!(domain_match("example.com", "example.com"));
+        assert!(domain_match("www.example.com", "example.com"));
+        assert!(!domain_match("wwwexample.com", "example.com"));
+        assert!(!domain_match("example.com", "www.example.com"));
+        assert!(!domain_match("1.2.3.4", "2.3.4"));
+
+        assert!(path_match("/foo", "/foo"));
+        assert!(path_match("/foo/bar", "/foo"));
+        assert!(path_match("/foo/bar", "/foo/"));
+        assert!(!path_match("/foobar", "/foo"));
+        assert!(!path_match("/", "/foo"));
+
+        assert_eq!(default_path(""), "/");
+        assert_eq!(default_path("/"), "/");
+        assert_eq!(default_path("/foo"), "/");
+        assert_eq!(default_path("/foo/bar"), "/foo");
+        assert_eq!(default_path("/foo/bar/"), "/foo/bar");
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        for (host, expected) in [
+            ("example.com", Some("example.com")),
+            ("a.b.example.com", Some("example.com")),
+            ("example.co.uk", Some("example.co.uk")),
+            ("www.example.co.uk", Some("example.co.uk")),
+            ("example.co", Some("example.co")),
+            ("www.example.co", Some("example.co")),
+            ("evil.github.io", Some("evil.github.io")),
+            ("www.example.ne.jp", Some("example.ne.jp")),
+            ("app.herokuapp.com", Some("app.herokuapp.com")),
+            ("localhost", None),
+            ("com", None),
+            ("co.uk", None),
+            ("ne.jp", None),
+            ("gob.mx", None),
+            ("github.io", None),
+            ("herokuapp.com", None),
+            ("127.0.0.1", None),
+            ("::1", None),
+        ] {
+            assert_eq!(registrable_domain(host), expected, "host: {host}");
+        }
+    }
+
+    #[test]
+    fn test_cookie_store_domain() {
+        let store = CookieStore::new();
+        set_cookies(
+            &store,
+            &origin(false, "www.example.com", "/"),
+            &[
+                "host=1",
+                "parent=2; Domain=.Example.com",
+                "other=3; Domain=other.com",
+                "tld=4; Domain=com",
+                "child=5; Domain=sub.www.example.com",
+            ],
+        );
+        assert_eq!(store.len(), 2);
+
+        set_cookies(
+            &store,
+            &origin(false, "evil.co.uk", "/"),
+            &["suffix=6; Domain=co.uk", "own=7; Domain=evil.co.uk"],
+        );
+        assert_eq!(store.len(), 3);
+        assert_eq!(
+            cookie_header(&store, &origin(false, "other.co.uk", "/")),
+            None
+        );
+
+        set_cookies(
+            &store,
+            &origin(false, "evil.github.io", "/"),
+            &["suffix=8; Domain=github.io"],
+        );
+        assert_eq!(store.len(), 3);
+
+        assert_eq!(
+            cookie_header(&store, &origin(false, "www.example.com", "/")).as_deref(),
+            Some("host=1; parent=2")
+        );
+        assert_eq!(
+            cookie_header(&store, &origin(false, "api.example.com", "/")).as_deref(),
+            Some("parent=2")
+        );
+        assert_eq!(
+            cookie_header(&store, &origin(false, "a.www.example.com", "/")).as_deref(),
+            Some("parent=2")
+        );
+        assert_eq!(
+            cookie_header(&store, &origin(false, "example.org", "/")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cookie_store_path_order_and_replace() {
+        let store = CookieStore::new();
+        let o = origin(false, "example.com", "/docs/page");
+        set_cookies(
+            &store,
+            &o,
+            &[
+                "a=1",
+                "b=2; Path=/docs/page",
+                "c=3; Path=/",
+                "d=4; Path=/api",
+            ],
+        );
+
+        assert_eq!(
+            cookie_header(&store, &o).as_deref(),
+            Some("b=2; a=1; c=3"),
+            "default path /docs sorted before /"
+        );
+        assert_eq!(
+            cookie_header(&store, &origin(false, "example.com", "/api/x")).as_deref(),
+            Some("d=4; c=3")
+        );
+
+        set_cookies(&store, &o, &["a=10; Path=/docs"]);
+        assert_eq!(store.len(), 4);
+        assert_eq!(cookie_header(&store, &o).as_deref(), Some("b=2; a=10; c=3"));
+    }
+
+    #[test]
+    fn test_cookie_store_secure() {
+        let store = CookieStore::new();
+        set_cookies(
+            &store,
+            &origin(false, "example.com", "/"),
+            &["insecure=1; Secure", "__Secure-a=1", "plain=1"],
+        );
+        assert_eq!(store.len(), 1);
+
+        set_cookies(
+            &store,
+            &origin(true, "example.com", "/"),
+            &[
+                "secure=2; Secure",
+                "__Secure-b=2; Secure",
+                "__Host-c=3; Secure; Path=/",
+                "__Host-d=4; Secure; Path=/; Domain=example.com",
+            ],
+        );
+        assert_eq!(store.len(), 4);
+
+        assert_eq!(
+            cookie_header(&store, &origin(false, "example.com", "/")).as_deref(),
+            Some("plain=1")
+        );
+        assert_eq!(
+            cookie_header(&store, &origin(true, "example.com", "/")).as_deref(),
+            Some("plain=1; secure=2; __Secure-b=2; __Host-c=3")
+        );
+    }
+
+    #[test]
+    fn test_cookie_store_same_site() {
+        let store = CookieStore::new();
+        let o = origin(true, "example.com", "/");
+        set_cookies(
+            &store,
+            &o,
+            &[
+                "strict=1; SameSite=Strict",
+                "lax=2; SameSite=Lax",
+                "none=3; SameSite=None; Secure",
+                "insecure_none=4; SameSite=None",
+                "default=5",
+            ],
+        );
+        assert_eq!(store.len(), 4);
+
+        let header = |site| {
+            store
+                .cookie_header(&o, site)
+                .map(|value| value.to_str().unwrap().to_owned())
+        };
+        assert_eq!(
+            header(SiteContext::SameSite).as_deref(),
+            Some("strict=1; lax=2; none=3; default=5")
+        );
+        assert_eq!(
+            header(SiteContext::cross_site(&Method::GET)).as_deref(),
+            Some("lax=2; none=3; default=5")
+        );
+        assert_eq!(
+            header(SiteContext::cross_site(&Method::POST)).as_deref(),
+            Some("none=3; default=5")
+        );
+    }
+
+    #[test]
+    fn test_cookie_store_expiry() {
+        let store = CookieStore::new();
+        let o = origin(false, "example.com", "/");
+        set_cookies(
+            &store,
+            &o,
+            &[
+                "a=1; Max-Age=3600",
+                "b=2; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+                "c=3; Max-Age=3600; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+                "d=4",
+            ],
+        );
+        assert_eq!(cookie_header(&store, &o).as_deref(), Some("a=1; c=3; d=4"));
+
+        set_cookies(
+            &store,
+            &o,
+            &["a=; Max-Age=0", "d=; Expires=Thu, 01 Jan 1970 00:00:00 GMT"],
+        );
+        assert_eq!(cookie_header(&store, &o).as_deref(), Some("c=3"));
+
+        let cookies = store.cookies();
+        assert_eq!(cookies.len(), 1);
+        assert!(cookies[0].expires_datetime().is_some());
+
+        store.clear();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_cookie_store_limits() {
+        let store = CookieStore::new();
+        let example = origin(false, "example.com", "/");
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::SET_COOKIE,
+            HeaderValue::try_from(format!("large={}", "x".repeat(MAX_COOKIE_SIZE))).unwrap(),
+        );
+        store.store_response_cookies(&example, &headers);
+        assert!(store.is_empty());
+
+        for domain in 0..=MAX_COOKIES / MAX_COOKIES_PER_DOMAIN {
+            let mut headers = HeaderMap::new();
+            for n in 0..=MAX_COOKIES_PER_DOMAIN {
+                headers.append(
+                    header::SET_COOKIE,
+                    HeaderValue::try_from(format!("c{n}={domain}")).unwrap(),
+                );
+            }
+            store.store_response_cookies(
+                &origin(false, &format!("{domain}.example"), "/"),
+                &headers,
+            );
+        }
+        assert_eq!(store.len(), MAX_COOKIES);
+
+        // the oldest cookie of each domain is evicted, followed by the oldest domain
+        assert_eq!(
+            cookie_header(&store, &origin(false, "0.example", "/")),
+            None
+        );
+        let cookies = cookie_header(&store, &origin(false, "1.example", "/")).unwrap();
+        assert!(cookies.starts_with("c1=1;"), "{cookies}");
+        let cookies = cookie_header(&store, &origin(false, "60.example", "/")).unwrap();
+        assert!(cookies.starts_with("c1=60;"), "{cookies}");
+    }
+}
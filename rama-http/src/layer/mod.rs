@@ -20,6 +20,7 @@ pub mod auth;
 pub mod body_limit;
 pub mod catch_panic;
 pub mod classify;
+pub mod cookie_store;
 pub mod cors;
 pub mod dns;
 pub mod error_handling;
@@ -48,6 +49,9 @@ pub mod validate_request;
 #[cfg(feature = "telemetry")]
 pub mod opentelemetry;
 
+#[cfg(feature = "tls")]
+pub mod client_identity_auth;
+
 pub(crate) mod util;
 
 #[cfg(feature = "compression")]
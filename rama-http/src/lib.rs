@@ -79,4 +79,7 @@ pub mod dep {
 
     #[doc(inline)]
     pub use ::rama_http_types::dep::{http, http_body, http_body_util, mime, mime_guess};
+
+    #[doc(inline)]
+    pub use ::cookie;
 }
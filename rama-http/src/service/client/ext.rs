@@ -604,6 +604,34 @@ where
         self
     }
 
+    /// Set the given [`MultipartForm`] as a `multipart/form-data` [`Body`] in the [`Request`].
+    ///
+    /// The `Content-Type` header is always overwritten,
+    /// as it has to define the boundary of the [`MultipartForm`].
+    ///
+    /// [`Body`]: crate::Body
+    /// [`MultipartForm`]: super::MultipartForm
+    pub fn multipart(mut self, form: super::MultipartForm) -> Self {
+        let content_type = form.content_type();
+        self.state = match self.state {
+            RequestBuilderState::PreBody(builder) => match builder
+                .header(crate::header::CONTENT_TYPE, content_type)
+                .body(form.into_body())
+            {
+                Ok(req) => RequestBuilderState::PostBody(req),
+                Err(err) => RequestBuilderState::Error(OpaqueError::from_std(err)),
+            },
+            RequestBuilderState::PostBody(mut req) => {
+                req.headers_mut()
+                    .insert(crate::header::CONTENT_TYPE, content_type);
+                *req.body_mut() = form.into_body();
+                RequestBuilderState::PostBody(req)
+            }
+            RequestBuilderState::Error(err) => RequestBuilderState::Error(err),
+        };
+        self
+    }
+
     /// Set the http [`Version`] of this [`Request`].
     ///
     /// [`Version`]: crate::Version
@@ -3,3 +3,7 @@
 mod ext;
 #[doc(inline)]
 pub use ext::{HttpClientExt, IntoUrl, RequestBuilder};
+
+mod multipart;
+#[doc(inline)]
+pub use multipart::{MultipartForm, Part};
@@ -0,0 +1,335 @@
+use crate::dep::mime_guess;
+use crate::{header, Body, HeaderMap, HeaderName, HeaderValue};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_lite::{Stream, StreamExt};
+use rama_core::error::{BoxError, ErrorContext, OpaqueError};
+use std::{borrow::Cow, path::Path, pin::Pin};
+use tokio_util::io::ReaderStream;
+
+type PartStream = Pin<Box<dyn Stream<Item = Result<Bytes, BoxError>> + Send + 'static>>;
+
+#[derive(Debug)]
+/// A `multipart/form-data` body, to be sent using [`RequestBuilder::multipart`].
+///
+/// The data of the parts is streamed, such that (large) files
+/// do not have to be loaded in memory.
+///
+/// # Example
+///
+/// ```
+/// use rama_http::service::client::{MultipartForm, Part};
+///
+/// # fn example() -> Result<(), rama_core::error::OpaqueError> {
+/// let form = MultipartForm::new()
+///     .with_text("title", "holiday")
+///     .with_part("photo", Part::file("photo.jpg")?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`RequestBuilder::multipart`]: super::RequestBuilder::multipart
+pub struct MultipartForm {
+    boundary: String,
+    parts: Vec<(Cow<'static, str>, Part)>,
+}
+
+impl Default for MultipartForm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultipartForm {
+    /// Create a new empty [`MultipartForm`] with a random boundary.
+    pub fn new() -> Self {
+        Self {
+            boundary: format!("rama-boundary-{}", uuid::Uuid::new_v4().simple()),
+            parts: Vec::new(),
+        }
+    }
+
+    /// Return the boundary used to separate the parts of this [`MultipartForm`].
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Return the `Content-Type` header value to use for this [`MultipartForm`].
+    pub fn content_type(&self) -> HeaderValue {
+        HeaderValue::try_from(format!("multipart/form-data; boundary={}", self.boundary))
+            .expect("multipart content type is always valid")
+    }
+
+    /// Add a text field to this [`MultipartForm`].
+    pub fn add_text(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) -> &mut Self {
+        self.add_part(name, Part::text(value))
+    }
+
+    /// Replace this [`MultipartForm`] with the text field added.
+    pub fn with_text(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.add_text(name, value);
+        self
+    }
+
+    /// Add a [`Part`] to this [`MultipartForm`].
+    pub fn add_part(&mut self, name: impl Into<Cow<'static, str>>, part: Part) -> &mut Self {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    /// Replace this [`MultipartForm`] with the [`Part`] added.
+    pub fn with_part(mut self, name: impl Into<Cow<'static, str>>, part: Part) -> Self {
+        self.add_part(name, part);
+        self
+    }
+
+    /// Turn this [`MultipartForm`] into a streaming [`Body`].
+    pub fn into_body(self) -> Body {
+        let mut stream: PartStream = Box::pin(futures_lite::stream::empty());
+        for (name, part) in self.parts {
+            let head = part.head(&self.boundary, &name);
+            stream = Box::pin(
+                stream
+                    .chain(futures_lite::stream::once(Ok(head)))
+                    .chain(part.data)
+                    .chain(futures_lite::stream::once(Ok(Bytes::from_static(b"\r\n")))),
+            );
+        }
+        let tail = Bytes::from(format!("--{}--\r\n", self.boundary));
+        Body::from_stream(stream.chain(futures_lite::stream::once(Ok(tail))))
+    }
+}
+
+/// A single part of a [`MultipartForm`].
+pub struct Part {
+    data: PartStream,
+    file_name: Option<Cow<'static, str>>,
+    headers: HeaderMap,
+}
+
+impl std::fmt::Debug for Part {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Part")
+            .field("file_name", &self.file_name)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+impl Part {
+    fn new(data: PartStream) -> Self {
+        Self {
+            data,
+            file_name: None,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Create a new [`Part`] containing the given text.
+    pub fn text(value: impl Into<Cow<'static, str>>) -> Self {
+        let bytes = match value.into() {
+            Cow::Borrowed(value) => Bytes::from_static(value.as_bytes()),
+            Cow::Owned(value) => Bytes::from(value),
+        };
+        Self::bytes(bytes)
+    }
+
+    /// Create a new [`Part`] containing the given bytes.
+    pub fn bytes(value: impl Into<Bytes>) -> Self {
+        Self::new(Box::pin(futures_lite::stream::once(Ok(value.into()))))
+    }
+
+    /// Create a new [`Part`] streaming the data of the given [`Body`].
+    pub fn stream(body: impl Into<Body>) -> Self {
+        Self::new(Box::pin(body.into().into_data_stream()))
+    }
+
+    /// Create a new [`Part`] streaming the content of the file at the given path.
+    ///
+    /// The file name and `Content-Type` are derived from the path,
+    /// and can be overwritten using [`Part::with_file_name`] and [`Part::with_content_type`].
+    ///
+    /// The file is opened immediately, such that errors are reported early,
+    /// while its content is only read when the [`Body`] is streamed.
+    pub fn file(path: impl AsRef<Path>) -> Result<Self, OpaqueError> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("open multipart file '{}'", path.display()))?;
+
+        let mut part = Self::new(Box::pin(
+            ReaderStream::new(tokio::fs::File::from_std(file))
+                .map(|result| result.map_err(Into::into)),
+        ));
+        if let Some(file_name) = path.file_name() {
+            part.file_name = Some(file_name.to_string_lossy().into_owned().into());
+        }
+        let mime = mime_guess::from_path(path).first_or_octet_stream();
+        part.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::try_from(mime.as_ref()).context("multipart file content type")?,
+        );
+        Ok(part)
+    }
+
+    /// Set the file name of this [`Part`].
+    pub fn set_file_name(&mut self, file_name: impl Into<Cow<'static, str>>) -> &mut Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Replace this [`Part`] with the file name set.
+    pub fn with_file_name(mut self, file_name: impl Into<Cow<'static, str>>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Set the `Content-Type` of this [`Part`].
+    pub fn set_content_type(&mut self, content_type: HeaderValue) -> &mut Self {
+        self.headers.insert(header::CONTENT_TYPE, content_type);
+        self
+    }
+
+    /// Replace this [`Part`] with the `Content-Type` set.
+    pub fn with_content_type(mut self, content_type: HeaderValue) -> Self {
+        self.set_content_type(content_type);
+        self
+    }
+
+    /// Set a header of this [`Part`].
+    ///
+    /// The `Content-Disposition` header is always generated
+    /// and can as such not be set using this method.
+    pub fn set_header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        if name != header::CONTENT_DISPOSITION {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Replace this [`Part`] with the header set.
+    ///
+    /// See [`Part::set_header`] for more information.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.set_header(name, value);
+        self
+    }
+
+    /// Encode the boundary and headers preceding the data of this [`Part`].
+    fn head(&self, boundary: &str, name: &str) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_slice(b"--");
+        buf.put_slice(boundary.as_bytes());
+        buf.put_slice(b"\r\nContent-Disposition: form-data; name=\"");
+        buf.put_slice(escape_quoted(name).as_bytes());
+        buf.put_u8(b'"');
+        if let Some(file_name) = &self.file_name {
+            buf.put_slice(b"; filename=\"");
+            buf.put_slice(escape_quoted(file_name).as_bytes());
+            buf.put_u8(b'"');
+        }
+        buf.put_slice(b"\r\n");
+        for (name, value) in &self.headers {
+            buf.put_slice(name.as_str().as_bytes());
+            buf.put_slice(b": ");
+            buf.put_slice(value.as_bytes());
+            buf.put_slice(b"\r\n");
+        }
+        buf.put_slice(b"\r\n");
+        buf.freeze()
+    }
+}
+
+/// Escape a name or file name as done by browsers for `multipart/form-data`,
+/// such that it can be used as a quoted `Content-Disposition` parameter.
+fn escape_quoted(value: &str) -> Cow<'_, str> {
+    if !value.contains(['"', '\r', '\n']) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(
+        value
+            .replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn test_multipart_form_body() {
+        let mut form = MultipartForm::new().with_text("title", "hello").with_part(
+            "file",
+            Part::bytes("data")
+                .with_file_name("a\"b.txt")
+                .with_content_type(HeaderValue::from_static("text/plain")),
+        );
+        form.boundary = "X".to_owned();
+        assert_eq!(form.content_type(), "multipart/form-data; boundary=X");
+
+        let body = form.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            body,
+            concat!(
+                "--X\r\n",
+                "Content-Disposition: form-data; name=\"title\"\r\n",
+                "\r\n",
+                "hello\r\n",
+                "--X\r\n",
+                "Content-Disposition: form-data; name=\"file\"; filename=\"a%22b.txt\"\r\n",
+                "content-type: text/plain\r\n",
+                "\r\n",
+                "data\r\n",
+                "--X--\r\n",
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multipart_part_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.json");
+        std::fs::write(&path, "{}").unwrap();
+
+        let part = Part::file(&path).unwrap();
+        assert_eq!(part.file_name.as_deref(), Some("hello.json"));
+        assert_eq!(part.headers[header::CONTENT_TYPE], "application/json");
+
+        assert!(Part::file(dir.path().join("missing")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_form_roundtrip() {
+        use crate::service::web::extract::{FromRequest, Multipart};
+        use rama_core::Context;
+
+        let form = MultipartForm::new()
+            .with_text("a", "1")
+            .with_part("b", Part::stream("streamed").with_file_name("b.bin"));
+        let req = crate::Request::builder()
+            .header(header::CONTENT_TYPE, form.content_type())
+            .body(form.into_body())
+            .unwrap();
+
+        let mut multipart = Multipart::from_request(Context::default(), req)
+            .await
+            .unwrap();
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("a"));
+        assert_eq!(field.text().await.unwrap(), "1");
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("b"));
+        assert_eq!(field.file_name(), Some("b.bin"));
+        assert_eq!(field.bytes().await.unwrap(), "streamed");
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+}
@@ -20,6 +20,10 @@ mod form;
 #[doc(inline)]
 pub use form::*;
 
+mod multipart;
+#[doc(inline)]
+pub use multipart::*;
+
 /// Extractor to get the response body.
 #[derive(Debug)]
 pub struct Body(pub http::Body);
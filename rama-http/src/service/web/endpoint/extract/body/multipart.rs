@@ -0,0 +1,898 @@
+use crate::dep::http_body_util::{BodyExt, LengthLimitError};
+use crate::service::web::extract::FromRequest;
+use crate::utils::macros::{composite_http_rejection, define_http_rejection};
+use crate::{header, Body, BodyLimit, HeaderMap, HeaderName, HeaderValue, Request};
+use bytes::{Buf, Bytes, BytesMut};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_core::Context;
+use std::path::{Path, PathBuf};
+use tempfile::{NamedTempFile, TempPath};
+use tokio::io::AsyncWriteExt;
+
+/// The default size from which a spooled field is written to disk.
+const DEFAULT_SPOOL_THRESHOLD: usize = 1024 * 1024;
+
+/// The maximum size of the headers of a single field.
+const MAX_FIELD_HEADERS_SIZE: usize = 8 * 1024;
+
+define_http_rejection! {
+    #[status = UNSUPPORTED_MEDIA_TYPE]
+    #[body = "Multipart requests must have `Content-Type: multipart/form-data` with a boundary"]
+    /// Rejection type for [`Multipart`]
+    /// used if the `Content-Type` header is missing,
+    /// its value is not `multipart/form-data` or it defines no boundary.
+    pub struct InvalidMultipartContentType;
+}
+
+define_http_rejection! {
+    #[status = BAD_REQUEST]
+    #[body = "Failed to parse multipart body"]
+    /// Error type used if the body of a [`Multipart`] request is malformed.
+    pub struct MalformedMultipart(Error);
+}
+
+define_http_rejection! {
+    #[status = PAYLOAD_TOO_LARGE]
+    #[body = "Multipart body exceeds size limit"]
+    /// Error type used if the body or one of the fields of a [`Multipart`] request
+    /// exceeds the limits of the [`MultipartConfig`] (or [`BodyLimit`]).
+    pub struct MultipartTooLarge(Error);
+}
+
+define_http_rejection! {
+    #[status = INTERNAL_SERVER_ERROR]
+    #[body = "Failed to spool multipart field to disk"]
+    /// Error type used if a [`Field`] failed to be spooled to disk.
+    pub struct FailedToSpoolMultipartField(Error);
+}
+
+composite_http_rejection! {
+    /// Error used while reading the fields of a [`Multipart`] request.
+    ///
+    /// Contains one variant for each way reading a [`Multipart`] request can fail.
+    pub enum MultipartError {
+        MalformedMultipart,
+        MultipartTooLarge,
+        FailedToSpoolMultipartField,
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Configuration used by the [`Multipart`] extractor,
+/// which can be inserted in the [`Context`] to overwrite the defaults.
+///
+/// In case no total limit is defined, the request limit
+/// of the [`BodyLimit`] found in the [`Context`] (if any) is used.
+pub struct MultipartConfig {
+    field_limit: Option<usize>,
+    total_limit: Option<usize>,
+    spool_threshold: usize,
+    spool_dir: Option<PathBuf>,
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultipartConfig {
+    /// Create a new [`MultipartConfig`] with the default settings.
+    pub const fn new() -> Self {
+        Self {
+            field_limit: None,
+            total_limit: None,
+            spool_threshold: DEFAULT_SPOOL_THRESHOLD,
+            spool_dir: None,
+        }
+    }
+
+    /// Set the maximum size of the data of a single field.
+    ///
+    /// Default is no limit other than the total limit.
+    pub fn set_field_limit(&mut self, limit: usize) -> &mut Self {
+        self.field_limit = Some(limit);
+        self
+    }
+
+    /// Replace this [`MultipartConfig`] with the field limit set.
+    pub fn with_field_limit(mut self, limit: usize) -> Self {
+        self.field_limit = Some(limit);
+        self
+    }
+
+    /// Set the maximum size of the entire multipart body.
+    ///
+    /// Default is the request limit of the [`BodyLimit`] found in the [`Context`], if any.
+    pub fn set_total_limit(&mut self, limit: usize) -> &mut Self {
+        self.total_limit = Some(limit);
+        self
+    }
+
+    /// Replace this [`MultipartConfig`] with the total limit set.
+    pub fn with_total_limit(mut self, limit: usize) -> Self {
+        self.total_limit = Some(limit);
+        self
+    }
+
+    /// Set the size from which [`Field::spool`] writes the data of a field to disk.
+    ///
+    /// Default is 1 MiB.
+    pub fn set_spool_threshold(&mut self, threshold: usize) -> &mut Self {
+        self.spool_threshold = threshold;
+        self
+    }
+
+    /// Replace this [`MultipartConfig`] with the spool threshold set.
+    pub fn with_spool_threshold(mut self, threshold: usize) -> Self {
+        self.spool_threshold = threshold;
+        self
+    }
+
+    /// Set the directory in which [`Field::spool`] creates its temporary files.
+    ///
+    /// Default is the temporary directory of the OS.
+    pub fn set_spool_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.spool_dir = Some(dir.into());
+        self
+    }
+
+    /// Replace this [`MultipartConfig`] with the spool directory set.
+    pub fn with_spool_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spool_dir = Some(dir.into());
+        self
+    }
+}
+
+/// Extractor that parses `multipart/form-data` requests,
+/// commonly used for forms which upload files.
+///
+/// The fields are streamed one by one using [`Multipart::next_field`],
+/// such that large uploads do not have to be buffered in memory.
+/// Use [`Field::spool`] to have large fields written to a temporary file.
+///
+/// Limits can be configured by inserting a [`MultipartConfig`] in the [`Context`].
+///
+/// # Example
+///
+/// ```
+/// use rama_http::service::web::{extract::Multipart, WebService};
+///
+/// let service = WebService::<()>::default().post("/upload", |mut multipart: Multipart| async move {
+///     while let Some(field) = multipart.next_field().await? {
+///         let name = field.name().unwrap_or_default().to_owned();
+///         let data = field.bytes().await?;
+///         println!("field {name} is {} bytes", data.len());
+///     }
+///     Ok::<_, rama_http::service::web::extract::MultipartError>(())
+/// });
+/// ```
+#[derive(Debug)]
+pub struct Multipart {
+    parser: Parser,
+    field_limit: Option<usize>,
+    spool_threshold: usize,
+    spool_dir: Option<PathBuf>,
+}
+
+impl<S> FromRequest<S> for Multipart
+where
+    S: Send + Sync + 'static,
+{
+    type Rejection = InvalidMultipartContentType;
+
+    async fn from_request(ctx: Context<S>, req: Request) -> Result<Self, Self::Rejection> {
+        let boundary = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<mime::Mime>().ok())
+            .filter(|mime| mime.type_() == mime::MULTIPART && mime.subtype() == mime::FORM_DATA)
+            .and_then(|mime| {
+                mime.get_param(mime::BOUNDARY)
+                    .map(|b| b.as_str().to_owned())
+            })
+            .filter(|boundary| !boundary.is_empty())
+            .ok_or(InvalidMultipartContentType)?;
+
+        let config = ctx.get::<MultipartConfig>().cloned().unwrap_or_default();
+        let total_limit = config
+            .total_limit
+            .or_else(|| ctx.get::<BodyLimit>().and_then(BodyLimit::request));
+
+        Ok(Self {
+            parser: Parser::new(req.into_body(), &boundary, total_limit),
+            field_limit: config.field_limit,
+            spool_threshold: config.spool_threshold,
+            spool_dir: config.spool_dir,
+        })
+    }
+}
+
+impl Multipart {
+    /// Return the next [`Field`], or `None` in case all fields were read.
+    ///
+    /// The remaining data of a previous [`Field`] which was not (fully) read is skipped.
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_>>, MultipartError> {
+        while self.parser.state == ParserState::Data {
+            self.parser.next_chunk().await?;
+        }
+        let Some(headers) = self.parser.next_headers().await? else {
+            return Ok(None);
+        };
+
+        let (name, file_name) = headers
+            .get(header::CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_content_disposition)
+            .unwrap_or_default();
+
+        Ok(Some(Field {
+            multipart: self,
+            headers,
+            name,
+            file_name,
+            size: 0,
+        }))
+    }
+}
+
+/// A single field of a [`Multipart`] request.
+#[derive(Debug)]
+pub struct Field<'a> {
+    multipart: &'a mut Multipart,
+    headers: HeaderMap,
+    name: Option<String>,
+    file_name: Option<String>,
+    size: usize,
+}
+
+impl Field<'_> {
+    /// Return the name of this [`Field`], as defined by its `Content-Disposition` header.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Return the file name of this [`Field`], as defined by its `Content-Disposition` header.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Return the `Content-Type` of this [`Field`], if defined.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Return all headers of this [`Field`].
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Return the next chunk of data of this [`Field`],
+    /// or `None` in case all data was read.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        let Some(chunk) = self.multipart.parser.next_chunk().await? else {
+            return Ok(None);
+        };
+        self.size += chunk.len();
+        if let Some(limit) = self.multipart.field_limit {
+            if self.size > limit {
+                return Err(MultipartTooLarge::from_display(format!(
+                    "field exceeds limit of {limit} bytes"
+                ))
+                .into());
+            }
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Collect the data of this [`Field`] in memory.
+    pub async fn bytes(mut self) -> Result<Bytes, MultipartError> {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf.freeze())
+    }
+
+    /// Collect the data of this [`Field`] in memory as an utf-8 string.
+    pub async fn text(self) -> Result<String, MultipartError> {
+        let bytes = self.bytes().await?;
+        String::from_utf8(bytes.into()).map_err(|err| MalformedMultipart::from_err(err).into())
+    }
+
+    /// Collect the data of this [`Field`], keeping it in memory
+    /// unless it exceeds the spool threshold of the [`MultipartConfig`],
+    /// in which case it is written to a temporary file.
+    pub async fn spool(mut self) -> Result<SpooledField, MultipartError> {
+        let threshold = self.multipart.spool_threshold;
+        let mut buf = BytesMut::new();
+        let mut file = None;
+
+        while let Some(chunk) = self.chunk().await? {
+            match file.as_mut() {
+                Some((file, _)) => write_spool_file(file, &chunk).await?,
+                None if buf.len() + chunk.len() > threshold => {
+                    let (mut spool_file, path) = self.create_spool_file()?;
+                    write_spool_file(&mut spool_file, &buf).await?;
+                    write_spool_file(&mut spool_file, &chunk).await?;
+                    buf.clear();
+                    file = Some((spool_file, path));
+                }
+                None => buf.extend_from_slice(&chunk),
+            }
+        }
+
+        let data = match file {
+            Some((mut file, path)) => {
+                file.flush()
+                    .await
+                    .map_err(FailedToSpoolMultipartField::from_err)?;
+                SpooledData::Disk {
+                    path,
+                    len: self.size,
+                }
+            }
+            None => SpooledData::Memory(buf.freeze()),
+        };
+
+        Ok(SpooledField {
+            name: self.name,
+            file_name: self.file_name,
+            headers: self.headers,
+            data,
+        })
+    }
+
+    fn create_spool_file(&self) -> Result<(tokio::fs::File, TempPath), MultipartError> {
+        let file = match &self.multipart.spool_dir {
+            Some(dir) => NamedTempFile::new_in(dir),
+            None => NamedTempFile::new(),
+        }
+        .map_err(FailedToSpoolMultipartField::from_err)?;
+        let (file, path) = file.into_parts();
+        Ok((tokio::fs::File::from_std(file), path))
+    }
+}
+
+async fn write_spool_file(file: &mut tokio::fs::File, data: &[u8]) -> Result<(), MultipartError> {
+    file.write_all(data)
+        .await
+        .map_err(|err| FailedToSpoolMultipartField::from_err(err).into())
+}
+
+/// A [`Field`] of which the data was collected using [`Field::spool`].
+#[derive(Debug)]
+pub struct SpooledField {
+    name: Option<String>,
+    file_name: Option<String>,
+    headers: HeaderMap,
+    data: SpooledData,
+}
+
+#[derive(Debug)]
+enum SpooledData {
+    Memory(Bytes),
+    Disk { path: TempPath, len: usize },
+}
+
+impl SpooledField {
+    /// Return the name of this [`SpooledField`], as defined by its `Content-Disposition` header.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Return the file name of this [`SpooledField`], as defined by its `Content-Disposition` header.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Return the `Content-Type` of this [`SpooledField`], if defined.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Return all headers of this [`SpooledField`].
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Return the size of the data of this [`SpooledField`].
+    pub fn len(&self) -> usize {
+        match &self.data {
+            SpooledData::Memory(bytes) => bytes.len(),
+            SpooledData::Disk { len, .. } => *len,
+        }
+    }
+
+    /// Returns true if this [`SpooledField`] has no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the path of the temporary file containing the data,
+    /// in case it was written to disk.
+    ///
+    /// The temporary file is removed when this [`SpooledField`] is dropped,
+    /// use [`SpooledField::persist`] to keep it.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.data {
+            SpooledData::Memory(_) => None,
+            SpooledData::Disk { path, .. } => Some(path),
+        }
+    }
+
+    /// Read the data of this [`SpooledField`] in memory.
+    pub async fn bytes(&self) -> Result<Bytes, OpaqueError> {
+        match &self.data {
+            SpooledData::Memory(bytes) => Ok(bytes.clone()),
+            SpooledData::Disk { path, .. } => tokio::fs::read(path)
+                .await
+                .map(Bytes::from)
+                .context("read spooled multipart field"),
+        }
+    }
+
+    /// Store the data of this [`SpooledField`] at the given path.
+    pub async fn persist(self, to: impl AsRef<Path>) -> Result<(), OpaqueError> {
+        let to = to.as_ref();
+        match self.data {
+            SpooledData::Memory(bytes) => tokio::fs::write(to, bytes)
+                .await
+                .context("write multipart field"),
+            SpooledData::Disk { path, .. } => match path.persist(to) {
+                Ok(()) => Ok(()),
+                // e.g. when the target is on another filesystem
+                Err(err) => tokio::fs::copy(&err.path, to)
+                    .await
+                    .map(|_| ())
+                    .context("copy spooled multipart field"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParserState {
+    /// Searching for the first boundary.
+    Preamble,
+    /// A boundary was found, headers (or the end of the body) are next.
+    Boundary,
+    /// Reading the data of a field.
+    Data,
+    /// The closing boundary was found.
+    Done,
+}
+
+#[derive(Debug)]
+struct Parser {
+    body: Body,
+    buffer: BytesMut,
+    /// The delimiter preceding each boundary: `\r\n--{boundary}`.
+    delimiter: Vec<u8>,
+    state: ParserState,
+    eof: bool,
+    total_read: usize,
+    total_limit: Option<usize>,
+}
+
+impl Parser {
+    fn new(body: Body, boundary: &str, total_limit: Option<usize>) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Self {
+            body,
+            // the first boundary is not required to be preceded by a newline
+            buffer: BytesMut::from(&b"\r\n"[..]),
+            delimiter,
+            state: ParserState::Preamble,
+            eof: false,
+            total_read: 0,
+            total_limit,
+        }
+    }
+
+    /// Read more data from the body in the buffer,
+    /// returning an error in case the body ended.
+    async fn fill(&mut self) -> Result<(), MultipartError> {
+        if self.eof {
+            return Err(MalformedMultipart::from_display("incomplete multipart body").into());
+        }
+        loop {
+            let Some(frame) = self.body.frame().await else {
+                self.eof = true;
+                return Ok(());
+            };
+            let frame = frame.map_err(|err| -> MultipartError {
+                if err.downcast_ref::<LengthLimitError>().is_some() {
+                    MultipartTooLarge::from_display("body exceeds body limit").into()
+                } else {
+                    MalformedMultipart(err).into()
+                }
+            })?;
+            if let Ok(data) = frame.into_data() {
+                if data.is_empty() {
+                    continue;
+                }
+                self.total_read += data.len();
+                if let Some(limit) = self.total_limit {
+                    if self.total_read > limit {
+                        return Err(MultipartTooLarge::from_display(format!(
+                            "body exceeds limit of {limit} bytes"
+                        ))
+                        .into());
+                    }
+                }
+                self.buffer.extend_from_slice(&data);
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parse the headers of the next field,
+    /// or return `None` in case the closing boundary was reached.
+    async fn next_headers(&mut self) -> Result<Option<HeaderMap>, MultipartError> {
+        loop {
+            match self.state {
+                ParserState::Done => return Ok(None),
+                ParserState::Data => unreachable!("field data is skipped before reading headers"),
+                ParserState::Preamble => {
+                    match memchr::memmem::find(&self.buffer, &self.delimiter) {
+                        Some(index) => {
+                            self.buffer.advance(index + self.delimiter.len());
+                            self.state = ParserState::Boundary;
+                        }
+                        None => {
+                            // keep what could be the start of the delimiter
+                            let keep = self.delimiter.len() - 1;
+                            if self.buffer.len() > keep {
+                                self.buffer.advance(self.buffer.len() - keep);
+                            }
+                            self.fill().await?;
+                        }
+                    }
+                }
+                ParserState::Boundary => {
+                    if self.buffer.len() < 2 {
+                        self.fill().await?;
+                        continue;
+                    }
+                    if self.buffer.starts_with(b"--") {
+                        self.state = ParserState::Done;
+                        return Ok(None);
+                    }
+                    let Some(index) = memchr::memmem::find(&self.buffer, b"\r\n\r\n") else {
+                        if self.buffer.len() > MAX_FIELD_HEADERS_SIZE {
+                            return Err(MalformedMultipart::from_display(
+                                "field headers exceed size limit",
+                            )
+                            .into());
+                        }
+                        self.fill().await?;
+                        continue;
+                    };
+                    let block = self.buffer.split_to(index + 4);
+                    let headers = parse_headers(&block[..index])?;
+                    self.state = ParserState::Data;
+                    return Ok(Some(headers));
+                }
+            }
+        }
+    }
+
+    /// Return the next chunk of data of the current field,
+    /// or `None` in case the end of its data was reached.
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        if self.state != ParserState::Data {
+            return Ok(None);
+        }
+        loop {
+            if let Some(index) = memchr::memmem::find(&self.buffer, &self.delimiter) {
+                let chunk = self.buffer.split_to(index).freeze();
+                self.buffer.advance(self.delimiter.len());
+                self.state = ParserState::Boundary;
+                return Ok(Some(chunk));
+            }
+            // keep what could be the start of the delimiter
+            let keep = self.delimiter.len() - 1;
+            if self.buffer.len() > keep {
+                let len = self.buffer.len() - keep;
+                return Ok(Some(self.buffer.split_to(len).freeze()));
+            }
+            self.fill().await?;
+        }
+    }
+}
+
+/// Parse the headers of a field, which are preceded by a
+/// (possibly empty) remainder of the boundary line.
+fn parse_headers(block: &[u8]) -> Result<HeaderMap, MultipartError> {
+    let mut lines = block
+        .split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+
+    // transport padding after the boundary
+    if lines
+        .next()
+        .is_some_and(|line| line.iter().any(|b| !b.is_ascii_whitespace()))
+    {
+        return Err(MalformedMultipart::from_display("invalid multipart boundary line").into());
+    }
+
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        let Some(index) = line.iter().position(|b| *b == b':') else {
+            return Err(MalformedMultipart::from_display("invalid multipart field header").into());
+        };
+        let name = HeaderName::from_bytes(&line[..index]).map_err(MalformedMultipart::from_err)?;
+        let value = HeaderValue::from_bytes(line[index + 1..].trim_ascii())
+            .map_err(MalformedMultipart::from_err)?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// Parse the name and file name from a `Content-Disposition` header value.
+fn parse_content_disposition(value: &str) -> (Option<String>, Option<String>) {
+    let mut name = None;
+    let mut file_name = None;
+    let mut file_name_ext = None;
+
+    for (key, value) in content_disposition_params(value) {
+        if key.eq_ignore_ascii_case("name") {
+            name = Some(value);
+        } else if key.eq_ignore_ascii_case("filename") {
+            file_name = Some(value);
+        } else if key.eq_ignore_ascii_case("filename*") {
+            // RFC 5987: charset'language'percent-encoded-value
+            file_name_ext = value
+                .splitn(3, '\'')
+                .nth(2)
+                .and_then(|value| {
+                    percent_encoding::percent_decode_str(value)
+                        .decode_utf8()
+                        .ok()
+                })
+                .map(|value| value.into_owned());
+        }
+    }
+
+    (name, file_name_ext.or(file_name))
+}
+
+/// Iterate over the parameters of a `Content-Disposition` header value,
+/// unquoting quoted values.
+fn content_disposition_params(value: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    let mut rest = value
+        .split_once(';')
+        .map(|(_, params)| params)
+        .unwrap_or_default();
+    std::iter::from_fn(move || loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        if rest.is_empty() {
+            return None;
+        }
+        let (key, after_key) = match rest.find(['=', ';']) {
+            Some(index) if rest.as_bytes()[index] == b'=' => (&rest[..index], &rest[index + 1..]),
+            Some(index) => {
+                rest = &rest[index..];
+                continue;
+            }
+            None => {
+                rest = "";
+                return None;
+            }
+        };
+        let key = key.trim().to_owned();
+        let after_key = after_key.trim_start();
+
+        let value = if let Some(quoted) = after_key.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, c)) = chars.next() {
+                            value.push(c);
+                        }
+                    }
+                    '"' => {
+                        end = index + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            rest = &quoted[end..];
+            value
+        } else {
+            let end = after_key.find(';').unwrap_or(after_key.len());
+            let value = after_key[..end].trim().to_owned();
+            rest = &after_key[end..];
+            value
+        };
+        return Some((key, value));
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::service::web::WebService;
+    use crate::{Method, StatusCode};
+    use rama_core::Service;
+
+    const BODY: &str = concat!(
+        "preamble\r\n",
+        "--X-BOUNDARY\r\n",
+        "Content-Disposition: form-data; name=\"title\"\r\n",
+        "\r\n",
+        "hello\r\nworld\r\n",
+        "--X-BOUNDARY\r\n",
+        "Content-Disposition: form-data; name=\"skipped\"\r\n",
+        "\r\n",
+        "not read\r\n",
+        "--X-BOUNDARY \r\n",
+        "Content-Disposition: form-data; name=\"file\"; filename=\"a \\\"b\\\".txt\"\r\n",
+        "Content-Type: text/plain\r\n",
+        "\r\n",
+        "--X-BOUNDAR\r\n",
+        "--X-BOUNDARY--\r\n",
+        "epilogue",
+    );
+
+    fn multipart_request(body: impl Into<Body>) -> Request {
+        Request::builder()
+            .method(Method::POST)
+            .header("content-type", "multipart/form-data; boundary=X-BOUNDARY")
+            .body(body.into())
+            .unwrap()
+    }
+
+    async fn extract(ctx: Context<()>, req: Request) -> Multipart {
+        Multipart::from_request(ctx, req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_multipart_fields() {
+        let chunks: Vec<Result<_, std::convert::Infallible>> = BODY
+            .as_bytes()
+            .chunks(3)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        let body = Body::from_stream(futures_lite::stream::iter(chunks));
+        let mut multipart = extract(Context::default(), multipart_request(body)).await;
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("title"));
+        assert_eq!(field.file_name(), None);
+        assert_eq!(field.text().await.unwrap(), "hello\r\nworld");
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("skipped"));
+        drop(field);
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("file"));
+        assert_eq!(field.file_name(), Some("a \"b\".txt"));
+        assert_eq!(field.content_type(), Some("text/plain"));
+        assert_eq!(field.bytes().await.unwrap(), "--X-BOUNDAR");
+
+        assert!(multipart.next_field().await.unwrap().is_none());
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_spool() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ctx = Context::default();
+        ctx.insert(
+            MultipartConfig::new()
+                .with_spool_threshold(11)
+                .with_spool_dir(dir.path()),
+        );
+        let mut multipart = extract(ctx, multipart_request(BODY)).await;
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        let spooled = field.spool().await.unwrap();
+        assert_eq!(spooled.name(), Some("title"));
+        assert_eq!(spooled.len(), 12);
+        let path = spooled.path().unwrap().to_owned();
+        assert!(path.starts_with(dir.path()));
+        assert_eq!(spooled.bytes().await.unwrap(), "hello\r\nworld");
+
+        let target = dir.path().join("title.txt");
+        spooled.persist(&target).await.unwrap();
+        assert!(!path.exists());
+        assert_eq!(std::fs::read(&target).unwrap(), b"hello\r\nworld");
+
+        let _ = multipart.next_field().await.unwrap().unwrap();
+        let field = multipart.next_field().await.unwrap().unwrap();
+        let spooled = field.spool().await.unwrap();
+        assert!(spooled.path().is_none());
+        assert_eq!(spooled.bytes().await.unwrap(), "--X-BOUNDAR");
+    }
+
+    #[tokio::test]
+    async fn test_multipart_limits() {
+        let mut ctx = Context::default();
+        ctx.insert(MultipartConfig::new().with_field_limit(8));
+        let mut multipart = extract(ctx, multipart_request(BODY)).await;
+        let field = multipart.next_field().await.unwrap().unwrap();
+        let err = field.bytes().await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut ctx = Context::default();
+        ctx.insert(BodyLimit::request_only(32));
+        let mut multipart = extract(ctx, multipart_request(BODY)).await;
+        let err = multipart.next_field().await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_malformed() {
+        let body = "--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nincomplete";
+        let mut multipart = extract(Context::default(), multipart_request(body)).await;
+        let field = multipart.next_field().await.unwrap().unwrap();
+        let err = field.bytes().await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_service() {
+        let service = WebService::default().post("/", |mut multipart: Multipart| async move {
+            let mut names = Vec::new();
+            while let Some(field) = multipart.next_field().await? {
+                names.push(field.name().unwrap_or_default().to_owned());
+            }
+            assert_eq!(names, ["title", "skipped", "file"]);
+            Ok::<_, MultipartError>(())
+        });
+
+        let resp = service
+            .serve(Context::default(), multipart_request(BODY))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .header("content-type", "multipart/form-data")
+            .body(Body::from(BODY))
+            .unwrap();
+        let resp = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn test_parse_content_disposition() {
+        for (value, expected) in [
+            ("form-data; name=\"a\"", (Some("a"), None)),
+            (
+                "form-data; name=a; filename=b.txt",
+                (Some("a"), Some("b.txt")),
+            ),
+            (
+                "form-data; name=\"a;b\"; filename=\"c\"",
+                (Some("a;b"), Some("c")),
+            ),
+            (
+                "form-data; name=\"f\"; filename=\"x\"; filename*=UTF-8''%E2%82%AC.txt",
+                (Some("f"), Some("€.txt")),
+            ),
+            ("form-data", (None, None)),
+        ] {
+            let (name, file_name) = parse_content_disposition(value);
+            assert_eq!(
+                (name.as_deref(), file_name.as_deref()),
+                expected,
+                "value: {value}"
+            );
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use super::FromRequestParts;
+use crate::dep::http::request::Parts;
+use crate::utils::macros::define_http_rejection;
+use rama_core::Context;
+use rama_net::tls::server;
+use rama_utils::macros::impl_deref;
+
+/// Extractor to get a clone of the [`ClientIdentity`] of the
+/// mutual TLS (mTLS) connection from the [`Context`].
+///
+/// [`ClientIdentity`]: rama_net::tls::server::ClientIdentity
+/// [`Context`]: rama_core::Context
+#[derive(Debug, Clone)]
+pub struct ClientIdentity(pub server::ClientIdentity);
+
+impl_deref!(ClientIdentity: server::ClientIdentity);
+
+define_http_rejection! {
+    #[status = UNAUTHORIZED]
+    #[body = "No client certificate presented"]
+    /// Rejection type used if the [`ClientIdentity`] extractor is unable to
+    /// find the client identity, as no client certificate was presented.
+    pub struct MissingClientIdentity;
+}
+
+impl<S> FromRequestParts<S> for ClientIdentity
+where
+    S: Send + Sync + 'static,
+{
+    type Rejection = MissingClientIdentity;
+
+    async fn from_request_parts(ctx: &Context<S>, _parts: &Parts) -> Result<Self, Self::Rejection> {
+        ctx.get::<server::ClientIdentity>()
+            .cloned()
+            .map(ClientIdentity)
+            .ok_or(MissingClientIdentity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dep::http_body_util::BodyExt as _;
+    use crate::service::web::WebService;
+    use crate::{Body, Request, StatusCode};
+    use rama_core::Service;
+
+    #[tokio::test]
+    async fn test_client_identity_extractor() {
+        let svc =
+            WebService::default().get("/", |ClientIdentity(identity): ClientIdentity| async move {
+                identity
+                    .certificate()
+                    .common_name()
+                    .unwrap_or_default()
+                    .to_owned()
+            });
+
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "client");
+        let cert = params.self_signed(&key_pair).unwrap();
+
+        let mut ctx = Context::default();
+        ctx.insert(server::ClientIdentity::from_der_chain([cert.der().to_vec()]).unwrap());
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let res = svc.serve(ctx, req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "client");
+
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let res = svc.serve(Context::default(), req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+}
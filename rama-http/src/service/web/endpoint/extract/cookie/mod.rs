@@ -0,0 +1,213 @@
+//! Cookie extractors, which can also be returned as part of a response
+//! to set or remove cookies.
+//!
+//! - [`CookieJar`]: plain cookies;
+//! - [`SignedCookieJar`]: cookies which are signed, such that they cannot be tampered with;
+//! - [`PrivateCookieJar`]: cookies which are encrypted, such that they can also not be read.
+//!
+//! The [`Key`] used for signed and private cookies is taken from the state,
+//! which is required to implement `AsRef<Key>`.
+
+use super::FromRequestParts;
+use crate::dep::http::request::Parts;
+use crate::response::{IntoResponse, IntoResponseParts, Response, ResponseParts};
+use crate::{header, HeaderMap, HeaderValue};
+use rama_core::Context;
+use std::convert::Infallible;
+
+#[doc(inline)]
+pub use ::cookie::{Cookie, Expiration, Key, SameSite};
+
+mod signed;
+#[doc(inline)]
+pub use signed::SignedCookieJar;
+
+mod private;
+#[doc(inline)]
+pub use private::PrivateCookieJar;
+
+/// Extractor that grabs the cookies from the request
+/// and allows to add or remove cookies, by returning it
+/// as part of the response.
+///
+/// # Example
+///
+/// ```
+/// use rama_http::service::web::{
+///     extract::cookie::{Cookie, CookieJar},
+///     WebService,
+/// };
+///
+/// let service = WebService::<()>::default().get("/", |jar: CookieJar| async move {
+///     let visits: u64 = jar
+///         .get("visits")
+///         .and_then(|cookie| cookie.value().parse().ok())
+///         .unwrap_or_default();
+///     jar.with_cookie(Cookie::new("visits", (visits + 1).to_string()))
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    jar: ::cookie::CookieJar,
+}
+
+impl<S> FromRequestParts<S> for CookieJar
+where
+    S: Send + Sync + 'static,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(_ctx: &Context<S>, parts: &Parts) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+impl CookieJar {
+    /// Create a new empty [`CookieJar`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new [`CookieJar`] containing the cookies of the `Cookie` headers
+    /// found in the given [`HeaderMap`].
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut jar = ::cookie::CookieJar::new();
+        for cookie in cookies_from_request(headers) {
+            jar.add_original(cookie);
+        }
+        Self { jar }
+    }
+
+    /// Return the [`Cookie`] with the given name, if any.
+    pub fn get(&self, name: &str) -> Option<&Cookie<'static>> {
+        self.jar.get(name)
+    }
+
+    /// Add a [`Cookie`] to this [`CookieJar`], which is set when returned as part of a response.
+    pub fn add(&mut self, cookie: impl Into<Cookie<'static>>) -> &mut Self {
+        self.jar.add(cookie);
+        self
+    }
+
+    /// Replace this [`CookieJar`] with the [`Cookie`] added.
+    ///
+    /// See [`CookieJar::add`] for more information.
+    pub fn with_cookie(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.add(cookie);
+        self
+    }
+
+    /// Remove a [`Cookie`] from this [`CookieJar`], which is removed
+    /// from the client when returned as part of a response.
+    ///
+    /// The path and domain of the given [`Cookie`] have to match
+    /// the ones used when the cookie was set.
+    pub fn remove(&mut self, cookie: impl Into<Cookie<'static>>) -> &mut Self {
+        self.jar.remove(cookie);
+        self
+    }
+
+    /// Replace this [`CookieJar`] with the [`Cookie`] removed.
+    ///
+    /// See [`CookieJar::remove`] for more information.
+    pub fn without_cookie(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.remove(cookie);
+        self
+    }
+
+    /// Iterate over all cookies in this [`CookieJar`].
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie<'static>> {
+        self.jar.iter()
+    }
+}
+
+impl IntoResponseParts for CookieJar {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        set_cookies(&self.jar, res.headers_mut());
+        Ok(res)
+    }
+}
+
+impl IntoResponse for CookieJar {
+    fn into_response(self) -> Response {
+        (self, ()).into_response()
+    }
+}
+
+/// Parse the cookies of the `Cookie` headers found in the given [`HeaderMap`],
+/// ignoring invalid cookies.
+fn cookies_from_request(headers: &HeaderMap) -> impl Iterator<Item = Cookie<'static>> + '_ {
+    headers
+        .get_all(header::COOKIE)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| Cookie::parse_encoded(cookie.trim().to_owned()).ok())
+}
+
+/// Append a `Set-Cookie` header for each cookie added to or removed from the jar.
+fn set_cookies(jar: &::cookie::CookieJar, headers: &mut HeaderMap) {
+    for cookie in jar.delta() {
+        if let Ok(value) = HeaderValue::try_from(cookie.encoded().to_string()) {
+            headers.append(header::SET_COOKIE, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::web::{extract::FromRequest, WebService};
+    use crate::{Body, Request, StatusCode};
+    use rama_core::Service;
+
+    #[tokio::test]
+    async fn test_cookie_jar_from_request() {
+        let req = Request::builder()
+            .header(header::COOKIE, "a=1; b=hello%20world")
+            .header(header::COOKIE, "c=3; invalid")
+            .body(Body::empty())
+            .unwrap();
+        let jar = CookieJar::from_request(Context::default(), req)
+            .await
+            .unwrap();
+
+        assert_eq!(jar.get("a").unwrap().value(), "1");
+        assert_eq!(jar.get("b").unwrap().value(), "hello world");
+        assert_eq!(jar.get("c").unwrap().value(), "3");
+        assert!(jar.get("invalid").is_none());
+        assert_eq!(jar.iter().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cookie_jar_into_response() {
+        let service = WebService::<()>::default().get("/", |jar: CookieJar| async move {
+            (
+                jar.with_cookie(Cookie::build(("session", "42")).path("/").http_only(true))
+                    .without_cookie("old"),
+                StatusCode::CREATED,
+            )
+        });
+
+        let req = Request::builder()
+            .uri("/")
+            .header(header::COOKIE, "old=1; kept=2")
+            .body(Body::empty())
+            .unwrap();
+        let resp = service.serve(Context::default(), req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let mut set_cookies: Vec<_> = resp
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .into_iter()
+            .map(|value| value.to_str().unwrap().to_owned())
+            .collect();
+        set_cookies.sort();
+        assert_eq!(set_cookies.len(), 2);
+        assert!(set_cookies[0].starts_with("old=; Max-Age=0"));
+        assert_eq!(set_cookies[1], "session=42; HttpOnly; Path=/");
+    }
+}
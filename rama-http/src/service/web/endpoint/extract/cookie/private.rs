@@ -0,0 +1,189 @@
+use super::{cookies_from_request, set_cookies, Cookie, Key};
+use crate::dep::http::request::Parts;
+use crate::response::{IntoResponse, IntoResponseParts, Response, ResponseParts};
+use crate::service::web::extract::FromRequestParts;
+use crate::HeaderMap;
+use rama_core::Context;
+use std::{convert::Infallible, fmt};
+
+/// Extractor that grabs the private cookies from the request
+/// and allows to add or remove private cookies, by returning it
+/// as part of the response.
+///
+/// Private cookies are encrypted and authenticated, such that they can neither
+/// be read nor tampered with by the client: cookies which fail to decrypt are ignored.
+///
+/// The [`Key`] is taken from the state, which has to implement `AsRef<Key>`.
+///
+/// # Example
+///
+/// ```
+/// use rama_http::service::web::{
+///     extract::cookie::{Cookie, Key, PrivateCookieJar},
+///     WebService,
+/// };
+///
+/// struct AppState {
+///     key: Key,
+/// }
+///
+/// impl AsRef<Key> for AppState {
+///     fn as_ref(&self) -> &Key {
+///         &self.key
+///     }
+/// }
+///
+/// let service = WebService::<AppState>::default().get("/", |jar: PrivateCookieJar| async move {
+///     match jar.get("user") {
+///         Some(cookie) => (jar, format!("welcome back {}", cookie.value())),
+///         None => (jar.with_cookie(Cookie::new("user", "rama")), "welcome".to_owned()),
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct PrivateCookieJar {
+    jar: ::cookie::CookieJar,
+    key: Key,
+}
+
+impl fmt::Debug for PrivateCookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrivateCookieJar")
+            .field("jar", &self.jar)
+            .field("key", &"REDACTED")
+            .finish()
+    }
+}
+
+impl<S> FromRequestParts<S> for PrivateCookieJar
+where
+    S: AsRef<Key> + Send + Sync + 'static,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(ctx: &Context<S>, parts: &Parts) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(
+            &parts.headers,
+            ctx.state().as_ref().clone(),
+        ))
+    }
+}
+
+impl PrivateCookieJar {
+    /// Create a new empty [`PrivateCookieJar`], using the given [`Key`].
+    pub fn new(key: Key) -> Self {
+        Self {
+            jar: Default::default(),
+            key,
+        }
+    }
+
+    /// Create a new [`PrivateCookieJar`] containing the cookies of the `Cookie` headers
+    /// found in the given [`HeaderMap`], which are decrypted using the given [`Key`].
+    pub fn from_headers(headers: &HeaderMap, key: Key) -> Self {
+        let mut jar = ::cookie::CookieJar::new();
+        let mut private = jar.private_mut(&key);
+        for cookie in cookies_from_request(headers) {
+            if let Some(cookie) = private.decrypt(cookie) {
+                private.add_original(cookie);
+            }
+        }
+        Self { jar, key }
+    }
+
+    /// Return the [`Cookie`] with the given name, if any.
+    ///
+    /// The returned [`Cookie`] contains the decrypted value.
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.jar.private(&self.key).get(name)
+    }
+
+    /// Add a [`Cookie`] to this [`PrivateCookieJar`], which is encrypted
+    /// and set when returned as part of a response.
+    pub fn add(&mut self, cookie: impl Into<Cookie<'static>>) -> &mut Self {
+        self.jar.private_mut(&self.key).add(cookie);
+        self
+    }
+
+    /// Replace this [`PrivateCookieJar`] with the [`Cookie`] added.
+    ///
+    /// See [`PrivateCookieJar::add`] for more information.
+    pub fn with_cookie(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.add(cookie);
+        self
+    }
+
+    /// Remove a [`Cookie`] from this [`PrivateCookieJar`], which is removed
+    /// from the client when returned as part of a response.
+    ///
+    /// The path and domain of the given [`Cookie`] have to match
+    /// the ones used when the cookie was set.
+    pub fn remove(&mut self, cookie: impl Into<Cookie<'static>>) -> &mut Self {
+        self.jar.private_mut(&self.key).remove(cookie);
+        self
+    }
+
+    /// Replace this [`PrivateCookieJar`] with the [`Cookie`] removed.
+    ///
+    /// See [`PrivateCookieJar::remove`] for more information.
+    pub fn without_cookie(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.remove(cookie);
+        self
+    }
+
+    /// Iterate over all decrypted cookies in this [`PrivateCookieJar`].
+    pub fn iter(&self) -> impl Iterator<Item = Cookie<'static>> + '_ {
+        let private = self.jar.private(&self.key);
+        self.jar
+            .iter()
+            .filter_map(move |cookie| private.get(cookie.name()))
+    }
+}
+
+impl IntoResponseParts for PrivateCookieJar {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        set_cookies(&self.jar, res.headers_mut());
+        Ok(res)
+    }
+}
+
+impl IntoResponse for PrivateCookieJar {
+    fn into_response(self) -> Response {
+        (self, ()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{header, HeaderValue};
+
+    #[test]
+    fn test_private_cookie_jar_roundtrip() {
+        let key = Key::generate();
+
+        let jar = PrivateCookieJar::new(key.clone()).with_cookie(Cookie::new("user", "rama"));
+        let resp = jar.into_response();
+        let set_cookie = resp.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(set_cookie.starts_with("user="));
+        assert!(!set_cookie.contains("rama"));
+
+        let cookie = set_cookie.split(';').next().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::try_from(format!("{cookie}; plain=1")).unwrap(),
+        );
+
+        let jar = PrivateCookieJar::from_headers(&headers, key);
+        assert_eq!(jar.get("user").unwrap().value(), "rama");
+        assert!(jar.get("plain").is_none());
+        assert_eq!(jar.iter().count(), 1);
+
+        // a different key cannot decrypt the cookie
+        let jar = PrivateCookieJar::from_headers(&headers, Key::generate());
+        assert!(jar.get("user").is_none());
+    }
+}
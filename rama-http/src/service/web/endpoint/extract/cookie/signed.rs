@@ -0,0 +1,190 @@
+use super::{cookies_from_request, set_cookies, Cookie, Key};
+use crate::dep::http::request::Parts;
+use crate::response::{IntoResponse, IntoResponseParts, Response, ResponseParts};
+use crate::service::web::extract::FromRequestParts;
+use crate::HeaderMap;
+use rama_core::Context;
+use std::{convert::Infallible, fmt};
+
+/// Extractor that grabs the signed cookies from the request
+/// and allows to add or remove signed cookies, by returning it
+/// as part of the response.
+///
+/// Signed cookies can be read by the client, but cannot be tampered with:
+/// cookies of which the signature is invalid are ignored.
+///
+/// The [`Key`] is taken from the state, which has to implement `AsRef<Key>`.
+///
+/// # Example
+///
+/// ```
+/// use rama_http::service::web::{
+///     extract::cookie::{Cookie, Key, SignedCookieJar},
+///     WebService,
+/// };
+///
+/// struct AppState {
+///     key: Key,
+/// }
+///
+/// impl AsRef<Key> for AppState {
+///     fn as_ref(&self) -> &Key {
+///         &self.key
+///     }
+/// }
+///
+/// let service = WebService::<AppState>::default().get("/", |jar: SignedCookieJar| async move {
+///     match jar.get("user") {
+///         Some(cookie) => (jar, format!("welcome back {}", cookie.value())),
+///         None => (jar.with_cookie(Cookie::new("user", "rama")), "welcome".to_owned()),
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct SignedCookieJar {
+    jar: ::cookie::CookieJar,
+    key: Key,
+}
+
+impl fmt::Debug for SignedCookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedCookieJar")
+            .field("jar", &self.jar)
+            .field("key", &"REDACTED")
+            .finish()
+    }
+}
+
+impl<S> FromRequestParts<S> for SignedCookieJar
+where
+    S: AsRef<Key> + Send + Sync + 'static,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(ctx: &Context<S>, parts: &Parts) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(
+            &parts.headers,
+            ctx.state().as_ref().clone(),
+        ))
+    }
+}
+
+impl SignedCookieJar {
+    /// Create a new empty [`SignedCookieJar`], using the given [`Key`].
+    pub fn new(key: Key) -> Self {
+        Self {
+            jar: Default::default(),
+            key,
+        }
+    }
+
+    /// Create a new [`SignedCookieJar`] containing the cookies of the `Cookie` headers
+    /// found in the given [`HeaderMap`], which are verified using the given [`Key`].
+    pub fn from_headers(headers: &HeaderMap, key: Key) -> Self {
+        let mut jar = ::cookie::CookieJar::new();
+        let mut signed = jar.signed_mut(&key);
+        for cookie in cookies_from_request(headers) {
+            if let Some(cookie) = signed.verify(cookie) {
+                signed.add_original(cookie);
+            }
+        }
+        Self { jar, key }
+    }
+
+    /// Return the [`Cookie`] with the given name, if any.
+    ///
+    /// The returned [`Cookie`] contains the verified value,
+    /// without its signature.
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.jar.signed(&self.key).get(name)
+    }
+
+    /// Add a [`Cookie`] to this [`SignedCookieJar`], which is signed
+    /// and set when returned as part of a response.
+    pub fn add(&mut self, cookie: impl Into<Cookie<'static>>) -> &mut Self {
+        self.jar.signed_mut(&self.key).add(cookie);
+        self
+    }
+
+    /// Replace this [`SignedCookieJar`] with the [`Cookie`] added.
+    ///
+    /// See [`SignedCookieJar::add`] for more information.
+    pub fn with_cookie(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.add(cookie);
+        self
+    }
+
+    /// Remove a [`Cookie`] from this [`SignedCookieJar`], which is removed
+    /// from the client when returned as part of a response.
+    ///
+    /// The path and domain of the given [`Cookie`] have to match
+    /// the ones used when the cookie was set.
+    pub fn remove(&mut self, cookie: impl Into<Cookie<'static>>) -> &mut Self {
+        self.jar.signed_mut(&self.key).remove(cookie);
+        self
+    }
+
+    /// Replace this [`SignedCookieJar`] with the [`Cookie`] removed.
+    ///
+    /// See [`SignedCookieJar::remove`] for more information.
+    pub fn without_cookie(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.remove(cookie);
+        self
+    }
+
+    /// Iterate over all verified cookies in this [`SignedCookieJar`].
+    pub fn iter(&self) -> impl Iterator<Item = Cookie<'static>> + '_ {
+        let signed = self.jar.signed(&self.key);
+        self.jar
+            .iter()
+            .filter_map(move |cookie| signed.get(cookie.name()))
+    }
+}
+
+impl IntoResponseParts for SignedCookieJar {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        set_cookies(&self.jar, res.headers_mut());
+        Ok(res)
+    }
+}
+
+impl IntoResponse for SignedCookieJar {
+    fn into_response(self) -> Response {
+        (self, ()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{header, HeaderValue};
+
+    #[test]
+    fn test_signed_cookie_jar_roundtrip() {
+        let key = Key::generate();
+
+        let jar = SignedCookieJar::new(key.clone()).with_cookie(Cookie::new("user", "rama"));
+        let resp = jar.into_response();
+        let set_cookie = resp.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(set_cookie.starts_with("user=") && set_cookie.ends_with("rama"));
+        assert_ne!(set_cookie, "user=rama");
+
+        let cookie = set_cookie.split(';').next().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::try_from(format!("{cookie}; plain=1")).unwrap(),
+        );
+
+        let jar = SignedCookieJar::from_headers(&headers, key);
+        assert_eq!(jar.get("user").unwrap().value(), "rama");
+        assert!(jar.get("plain").is_none());
+        assert_eq!(jar.iter().count(), 1);
+
+        // a different key cannot verify the cookie
+        let jar = SignedCookieJar::from_headers(&headers, Key::generate());
+        assert!(jar.get("user").is_none());
+    }
+}
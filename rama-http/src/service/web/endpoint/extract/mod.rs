@@ -36,13 +36,23 @@ mod dns;
 #[doc(inline)]
 pub use dns::Dns;
 
+#[cfg(feature = "tls")]
+mod client_identity;
+#[cfg(feature = "tls")]
+#[doc(inline)]
+pub use client_identity::{ClientIdentity, MissingClientIdentity};
+
 mod typed_header;
 #[doc(inline)]
 pub use typed_header::{TypedHeader, TypedHeaderRejection, TypedHeaderRejectionReason};
 
+pub mod cookie;
+
 mod body;
 #[doc(inline)]
-pub use body::{Body, Bytes, Form, Json, Text};
+pub use body::{
+    Body, Bytes, Field, Form, Json, Multipart, MultipartConfig, MultipartError, SpooledField, Text,
+};
 
 mod private {
     #[derive(Debug, Clone, Copy)]
@@ -0,0 +1,33 @@
+use super::Authority;
+use rama_utils::macros::impl_deref;
+
+/// Target [`Authority`] to forward to, which the (tcp and udp) `Forwarder` services
+/// require to be present in the [`Context`] when created using `Forwarder::ctx`.
+///
+/// [`Context`]: rama_core::Context
+#[derive(Debug, Clone)]
+pub struct ForwardAuthority(Authority);
+
+impl ForwardAuthority {
+    /// Create a new [`ForwardAuthority`] for the given target [`Authority`].
+    pub fn new(authority: impl Into<Authority>) -> Self {
+        Self(authority.into())
+    }
+}
+
+impl<A> From<A> for ForwardAuthority
+where
+    A: Into<Authority>,
+{
+    fn from(authority: A) -> Self {
+        Self::new(authority)
+    }
+}
+
+impl AsRef<Authority> for ForwardAuthority {
+    fn as_ref(&self) -> &Authority {
+        &self.0
+    }
+}
+
+impl_deref!(ForwardAuthority: Authority);
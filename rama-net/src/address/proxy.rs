@@ -3,7 +3,7 @@ use crate::{proto::try_to_extract_protocol_from_uri_scheme, user::ProxyCredentia
 use rama_core::error::{ErrorContext, OpaqueError};
 use std::{fmt::Display, str::FromStr};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Address of a proxy that can be connected to.
 pub struct ProxyAddress {
     /// [`Protocol`] used by the proxy.
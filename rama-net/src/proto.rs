@@ -45,6 +45,15 @@ enum ProtocolKind {
     ///
     /// The difference with [`Self::Socks5`] is that the proxy resolves the URL hostname.
     Socks5h,
+    /// The `socks4` protocol.
+    ///
+    /// <https://www.openssh.com/txt/socks4.protocol>
+    Socks4,
+    /// The `socks4a` protocol.
+    ///
+    /// Extension of [`Self::Socks4`] which allows the proxy to resolve the hostname,
+    /// documented at <https://www.openssh.com/txt/socks4a.protocol>.
+    Socks4a,
     /// Custom protocol.
     Custom(Cow<'static, str>),
 }
@@ -53,6 +62,8 @@ const SCHEME_HTTP: &str = "http";
 const SCHEME_HTTPS: &str = "https";
 const SCHEME_SOCKS5: &str = "socks5";
 const SCHEME_SOCKS5H: &str = "socks5h";
+const SCHEME_SOCKS4: &str = "socks4";
+const SCHEME_SOCKS4A: &str = "socks4a";
 const SCHEME_WS: &str = "ws";
 const SCHEME_WSS: &str = "wss";
 
@@ -75,6 +86,12 @@ impl Protocol {
     /// `SOCKS5H` protocol.
     pub const SOCKS5H: Self = Protocol(ProtocolKind::Socks5h);
 
+    /// `SOCKS4` protocol.
+    pub const SOCKS4: Self = Protocol(ProtocolKind::Socks4);
+
+    /// `SOCKS4A` protocol.
+    pub const SOCKS4A: Self = Protocol(ProtocolKind::Socks4a);
+
     /// Creates a Protocol from a str a compile time.
     ///
     /// This function requires the static string to be a valid protocol.
@@ -98,6 +115,10 @@ impl Protocol {
             ProtocolKind::Socks5
         } else if eq_ignore_ascii_case!(s, SCHEME_SOCKS5H) {
             ProtocolKind::Socks5h
+        } else if eq_ignore_ascii_case!(s, SCHEME_SOCKS4) {
+            ProtocolKind::Socks4
+        } else if eq_ignore_ascii_case!(s, SCHEME_SOCKS4A) {
+            ProtocolKind::Socks4a
         } else if eq_ignore_ascii_case!(s, SCHEME_WS) {
             ProtocolKind::Ws
         } else if eq_ignore_ascii_case!(s, SCHEME_WSS) {
@@ -117,6 +138,8 @@ impl Protocol {
             | ProtocolKind::Wss
             | ProtocolKind::Socks5
             | ProtocolKind::Socks5h
+            | ProtocolKind::Socks4
+            | ProtocolKind::Socks4a
             | ProtocolKind::Custom(_) => false,
         }
     }
@@ -129,6 +152,8 @@ impl Protocol {
             | ProtocolKind::Https
             | ProtocolKind::Socks5
             | ProtocolKind::Socks5h
+            | ProtocolKind::Socks4
+            | ProtocolKind::Socks4a
             | ProtocolKind::Custom(_) => false,
         }
     }
@@ -142,6 +167,8 @@ impl Protocol {
             | ProtocolKind::Ws
             | ProtocolKind::Wss
             | ProtocolKind::Socks5h
+            | ProtocolKind::Socks4
+            | ProtocolKind::Socks4a
             | ProtocolKind::Custom(_) => false,
         }
     }
@@ -155,6 +182,38 @@ impl Protocol {
             | ProtocolKind::Https
             | ProtocolKind::Ws
             | ProtocolKind::Wss
+            | ProtocolKind::Socks4
+            | ProtocolKind::Socks4a
+            | ProtocolKind::Custom(_) => false,
+        }
+    }
+
+    /// Returns `true` if this protocol is socks4.
+    pub fn is_socks4(&self) -> bool {
+        match &self.0 {
+            ProtocolKind::Socks4 => true,
+            ProtocolKind::Http
+            | ProtocolKind::Https
+            | ProtocolKind::Ws
+            | ProtocolKind::Wss
+            | ProtocolKind::Socks5
+            | ProtocolKind::Socks5h
+            | ProtocolKind::Socks4a
+            | ProtocolKind::Custom(_) => false,
+        }
+    }
+
+    /// Returns `true` if this protocol is socks4a.
+    pub fn is_socks4a(&self) -> bool {
+        match &self.0 {
+            ProtocolKind::Socks4a => true,
+            ProtocolKind::Http
+            | ProtocolKind::Https
+            | ProtocolKind::Ws
+            | ProtocolKind::Wss
+            | ProtocolKind::Socks5
+            | ProtocolKind::Socks5h
+            | ProtocolKind::Socks4
             | ProtocolKind::Custom(_) => false,
         }
     }
@@ -167,6 +226,8 @@ impl Protocol {
             | ProtocolKind::Http
             | ProtocolKind::Socks5
             | ProtocolKind::Socks5h
+            | ProtocolKind::Socks4
+            | ProtocolKind::Socks4a
             | ProtocolKind::Custom(_) => false,
         }
     }
@@ -178,7 +239,11 @@ impl Protocol {
         match &self.0 {
             ProtocolKind::Https | ProtocolKind::Wss => 443,
             ProtocolKind::Http | ProtocolKind::Ws => 80,
-            ProtocolKind::Socks5 | ProtocolKind::Socks5h | ProtocolKind::Custom(_) => 80, // \_(ツ)_/¯
+            ProtocolKind::Socks5
+            | ProtocolKind::Socks5h
+            | ProtocolKind::Socks4
+            | ProtocolKind::Socks4a
+            | ProtocolKind::Custom(_) => 80, // \_(ツ)_/¯
         }
     }
 
@@ -191,6 +256,8 @@ impl Protocol {
             ProtocolKind::Wss => "wss",
             ProtocolKind::Socks5 => "socks5",
             ProtocolKind::Socks5h => "socks5h",
+            ProtocolKind::Socks4 => "socks4",
+            ProtocolKind::Socks4a => "socks4a",
             ProtocolKind::Custom(s) => s.as_ref(),
         }
     }
@@ -212,6 +279,10 @@ fn try_to_convert_str_to_non_custom_protocol(
         ProtocolKind::Socks5
     } else if eq_ignore_ascii_case!(s, SCHEME_SOCKS5H) {
         ProtocolKind::Socks5h
+    } else if eq_ignore_ascii_case!(s, SCHEME_SOCKS4) {
+        ProtocolKind::Socks4
+    } else if eq_ignore_ascii_case!(s, SCHEME_SOCKS4A) {
+        ProtocolKind::Socks4a
     } else if eq_ignore_ascii_case!(s, SCHEME_WS) {
         ProtocolKind::Ws
     } else if eq_ignore_ascii_case!(s, SCHEME_WSS) {
@@ -284,6 +355,8 @@ impl PartialEq<str> for Protocol {
             ProtocolKind::Http => other.eq_ignore_ascii_case(SCHEME_HTTP) || other.is_empty(),
             ProtocolKind::Socks5 => other.eq_ignore_ascii_case(SCHEME_SOCKS5),
             ProtocolKind::Socks5h => other.eq_ignore_ascii_case(SCHEME_SOCKS5H),
+            ProtocolKind::Socks4 => other.eq_ignore_ascii_case(SCHEME_SOCKS4),
+            ProtocolKind::Socks4a => other.eq_ignore_ascii_case(SCHEME_SOCKS4A),
             ProtocolKind::Ws => other.eq_ignore_ascii_case("ws"),
             ProtocolKind::Wss => other.eq_ignore_ascii_case("wss"),
             ProtocolKind::Custom(s) => other.eq_ignore_ascii_case(s),
@@ -436,6 +509,8 @@ mod tests {
         assert_eq!("wss".parse(), Ok(Protocol::WSS));
         assert_eq!("socks5".parse(), Ok(Protocol::SOCKS5));
         assert_eq!("socks5h".parse(), Ok(Protocol::SOCKS5H));
+        assert_eq!("socks4".parse(), Ok(Protocol::SOCKS4));
+        assert_eq!("socks4a".parse(), Ok(Protocol::SOCKS4A));
         assert_eq!("custom".parse(), Ok(Protocol::from_static("custom")));
     }
 
@@ -443,7 +518,7 @@ mod tests {
     #[test]
     fn test_from_http_scheme() {
         for s in [
-            "http", "https", "ws", "wss", "socks5", "socks5h", "", "custom",
+            "http", "https", "ws", "wss", "socks5", "socks5h", "socks4", "socks4a", "", "custom",
         ]
         .iter()
         {
@@ -474,6 +549,8 @@ mod tests {
             ("wss://example.com", Some((Some(Protocol::WSS), 6))),
             ("socks5://example.com", Some((Some(Protocol::SOCKS5), 9))),
             ("socks5h://example.com", Some((Some(Protocol::SOCKS5H), 10))),
+            ("socks4://example.com", Some((Some(Protocol::SOCKS4), 9))),
+            ("socks4a://example.com", Some((Some(Protocol::SOCKS4A), 10))),
             (
                 "custom://example.com",
                 Some((Some(Protocol::from_static("custom")), 9)),
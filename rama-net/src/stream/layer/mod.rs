@@ -12,5 +12,8 @@ pub use tracker::{
 #[cfg(feature = "http")]
 pub mod http;
 
+#[cfg(feature = "tls")]
+pub mod tls;
+
 #[cfg(feature = "telemetry")]
 pub mod opentelemetry;
@@ -0,0 +1,374 @@
+use crate::stream::{ChainReader, HeapReader, Stream};
+use crate::tls::client::{parse_client_hello_records, ClientHello};
+use rama_core::{error::BoxError, Context, Layer, Service};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, time::Duration};
+use tokio::io::AsyncReadExt;
+
+/// Default maximum amount of bytes that is read by the [`ClientHelloPeekService`]
+/// in an attempt to parse the [`ClientHello`].
+///
+/// [`ClientHello`]: crate::tls::client::ClientHello
+const DEFAULT_MAX_PEEK_SIZE: usize = 16 * 1024;
+
+/// Default maximum duration that the [`ClientHelloPeekService`] waits
+/// for the [`ClientHello`] to be received.
+///
+/// [`ClientHello`]: crate::tls::client::ClientHello
+const DEFAULT_PEEK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A [`Layer`] that parses the [`ClientHello`] from the start of a raw [`Stream`],
+/// without consuming it.
+///
+/// See [`ClientHelloPeekService`] for more information.
+///
+/// [`ClientHello`]: crate::tls::client::ClientHello
+#[derive(Debug, Clone)]
+pub struct ClientHelloPeekLayer {
+    max_peek_size: usize,
+    peek_timeout: Duration,
+}
+
+impl ClientHelloPeekLayer {
+    /// Create a new [`ClientHelloPeekLayer`].
+    pub const fn new() -> Self {
+        Self {
+            max_peek_size: DEFAULT_MAX_PEEK_SIZE,
+            peek_timeout: DEFAULT_PEEK_TIMEOUT,
+        }
+    }
+
+    /// Set the maximum amount of bytes that is read
+    /// in an attempt to parse the [`ClientHello`].
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub const fn with_max_peek_size(mut self, size: usize) -> Self {
+        self.max_peek_size = size;
+        self
+    }
+
+    /// Set the maximum amount of bytes that is read
+    /// in an attempt to parse the [`ClientHello`].
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub fn set_max_peek_size(&mut self, size: usize) -> &mut Self {
+        self.max_peek_size = size;
+        self
+    }
+
+    /// Set the maximum duration to wait for the [`ClientHello`] to be received,
+    /// protecting against clients which send their data (too) slowly.
+    ///
+    /// Once the timeout is reached, the stream is passed on as-is
+    /// without a [`ClientHello`] inserted in the [`Context`].
+    ///
+    /// Defaults to 10 seconds.
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub const fn with_peek_timeout(mut self, timeout: Duration) -> Self {
+        self.peek_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum duration to wait for the [`ClientHello`] to be received,
+    /// protecting against clients which send their data (too) slowly.
+    ///
+    /// Once the timeout is reached, the stream is passed on as-is
+    /// without a [`ClientHello`] inserted in the [`Context`].
+    ///
+    /// Defaults to 10 seconds.
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub fn set_peek_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.peek_timeout = timeout;
+        self
+    }
+}
+
+impl Default for ClientHelloPeekLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for ClientHelloPeekLayer {
+    type Service = ClientHelloPeekService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientHelloPeekService {
+            inner,
+            max_peek_size: self.max_peek_size,
+            peek_timeout: self.peek_timeout,
+        }
+    }
+}
+
+/// A [`Service`] that parses the [`ClientHello`] from the start of a raw [`Stream`],
+/// without consuming it.
+///
+/// The parsed [`ClientHello`] is inserted in the [`Context`],
+/// such that it can be used for fingerprinting or routing (e.g. on the SNI value)
+/// prior to (or without) terminating the TLS connection,
+/// regardless of the TLS implementation in use.
+///
+/// All bytes read are passed on as-is to the inner [`Service`].
+/// In case the stream does not start with a (valid) [`ClientHello`],
+/// the stream is passed on without a [`ClientHello`] inserted in the [`Context`].
+///
+/// [`ClientHello`]: crate::tls::client::ClientHello
+pub struct ClientHelloPeekService<S> {
+    inner: S,
+    max_peek_size: usize,
+    peek_timeout: Duration,
+}
+
+impl<S> ClientHelloPeekService<S> {
+    /// Create a new [`ClientHelloPeekService`].
+    ///
+    /// See [`ClientHelloPeekService`] for more information.
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            max_peek_size: DEFAULT_MAX_PEEK_SIZE,
+            peek_timeout: DEFAULT_PEEK_TIMEOUT,
+        }
+    }
+
+    /// Set the maximum amount of bytes that is read
+    /// in an attempt to parse the [`ClientHello`].
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub const fn with_max_peek_size(mut self, size: usize) -> Self {
+        self.max_peek_size = size;
+        self
+    }
+
+    /// Set the maximum amount of bytes that is read
+    /// in an attempt to parse the [`ClientHello`].
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub fn set_max_peek_size(&mut self, size: usize) -> &mut Self {
+        self.max_peek_size = size;
+        self
+    }
+
+    /// Set the maximum duration to wait for the [`ClientHello`] to be received,
+    /// protecting against clients which send their data (too) slowly.
+    ///
+    /// Once the timeout is reached, the stream is passed on as-is
+    /// without a [`ClientHello`] inserted in the [`Context`].
+    ///
+    /// Defaults to 10 seconds.
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub const fn with_peek_timeout(mut self, timeout: Duration) -> Self {
+        self.peek_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum duration to wait for the [`ClientHello`] to be received,
+    /// protecting against clients which send their data (too) slowly.
+    ///
+    /// Once the timeout is reached, the stream is passed on as-is
+    /// without a [`ClientHello`] inserted in the [`Context`].
+    ///
+    /// Defaults to 10 seconds.
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    pub fn set_peek_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.peek_timeout = timeout;
+        self
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for ClientHelloPeekService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientHelloPeekService")
+            .field("inner", &self.inner)
+            .field("max_peek_size", &self.max_peek_size)
+            .field("peek_timeout", &self.peek_timeout)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for ClientHelloPeekService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            max_peek_size: self.max_peek_size,
+            peek_timeout: self.peek_timeout,
+        }
+    }
+}
+
+impl<State, S, IO> Service<State, IO> for ClientHelloPeekService<S>
+where
+    State: Send + Sync + 'static,
+    S: Service<
+        State,
+        tokio::io::Join<ChainReader<HeapReader, tokio::io::ReadHalf<IO>>, tokio::io::WriteHalf<IO>>,
+        Error: Into<BoxError>,
+    >,
+    IO: Stream + Unpin,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        mut stream: IO,
+    ) -> Result<Self::Response, Self::Error> {
+        let mut buffer = Vec::with_capacity(1024);
+        let peek = peek_client_hello(&mut stream, &mut buffer, self.max_peek_size);
+        match tokio::time::timeout(self.peek_timeout, peek).await {
+            Ok(Ok(Some(hello))) => {
+                ctx.insert(hello);
+            }
+            Ok(Ok(None)) => (),
+            Ok(Err(err)) => return Err(err.into()),
+            Err(_) => {
+                tracing::debug!(
+                    "client hello peek: no client hello received within {:?} ({} bytes read)",
+                    self.peek_timeout,
+                    buffer.len()
+                );
+            }
+        }
+
+        // put back the data that is peeked
+        let (r, w) = tokio::io::split(stream);
+        let r = ChainReader::new(HeapReader::new(buffer), r);
+        let stream = tokio::io::join(r, w);
+
+        self.inner.serve(ctx, stream).await.map_err(Into::into)
+    }
+}
+
+/// Read from the stream into the buffer until a [`ClientHello`]
+/// can be parsed, or it is clear that the stream does not start with one.
+///
+/// [`ClientHello`]: crate::tls::client::ClientHello
+async fn peek_client_hello<IO: Stream + Unpin>(
+    stream: &mut IO,
+    buffer: &mut Vec<u8>,
+    max_peek_size: usize,
+) -> std::io::Result<Option<ClientHello>> {
+    loop {
+        if stream.read_buf(buffer).await? == 0 {
+            tracing::trace!(
+                "client hello peek: eof reached after {} bytes",
+                buffer.len()
+            );
+            return Ok(None);
+        }
+        match parse_client_hello_records(buffer) {
+            Ok(Some(hello)) => return Ok(Some(hello)),
+            Ok(None) if buffer.len() < max_peek_size => (),
+            Ok(None) => {
+                tracing::debug!(
+                    "client hello peek: no client hello found within {} bytes",
+                    max_peek_size
+                );
+                return Ok(None);
+            }
+            Err(err) => {
+                tracing::trace!(err = %err, "client hello peek: no client hello found");
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{client::test_utils::client_hello_record, CipherSuite};
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+    use tokio_test::io::{Builder, Mock};
+
+    type PeekedStream = tokio::io::Join<
+        ChainReader<HeapReader, tokio::io::ReadHalf<Mock>>,
+        tokio::io::WriteHalf<Mock>,
+    >;
+
+    #[tokio::test]
+    async fn test_client_hello_peek() {
+        let record = client_hello_record(&[]);
+        let stream = Builder::new()
+            .read(&record[..20])
+            .read(&record[20..])
+            .read(b"foo")
+            .build();
+
+        let svc = ClientHelloPeekLayer::new().layer(service_fn(
+            move |ctx: Context<()>, mut stream: PeekedStream| {
+                let record = record.clone();
+                async move {
+                    let hello: &ClientHello = ctx.get().unwrap();
+                    assert_eq!(
+                        hello.cipher_suites(),
+                        &[CipherSuite::TLS13_AES_128_GCM_SHA256]
+                    );
+
+                    let mut data = Vec::new();
+                    stream.read_to_end(&mut data).await.unwrap();
+                    assert_eq!(&data[..record.len()], record);
+                    assert_eq!(&data[record.len()..], b"foo");
+                    Ok::<_, Infallible>(())
+                }
+            },
+        ));
+
+        svc.serve(Context::default(), stream).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_hello_peek_no_tls() {
+        let stream = Builder::new().read(b"GET / HTTP/1.1\r\n\r\n").build();
+
+        let svc = ClientHelloPeekLayer::new().layer(service_fn(
+            |ctx: Context<()>, mut stream: PeekedStream| async move {
+                assert!(!ctx.contains::<ClientHello>());
+
+                let mut data = Vec::new();
+                stream.read_to_end(&mut data).await.unwrap();
+                assert_eq!(data, b"GET / HTTP/1.1\r\n\r\n");
+                Ok::<_, Infallible>(())
+            },
+        ));
+
+        svc.serve(Context::default(), stream).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_hello_peek_timeout() {
+        let record = client_hello_record(&[]);
+        let stream = Builder::new()
+            .read(&record[..20])
+            .wait(Duration::from_millis(200))
+            .read(&record[20..])
+            .build();
+
+        let svc = ClientHelloPeekLayer::new()
+            .with_peek_timeout(Duration::from_millis(50))
+            .layer(service_fn(
+                move |ctx: Context<()>, mut stream: PeekedStream| {
+                    let record = record.clone();
+                    async move {
+                        assert!(!ctx.contains::<ClientHello>());
+
+                        let mut data = Vec::new();
+                        stream.read_to_end(&mut data).await.unwrap();
+                        assert_eq!(data, record);
+                        Ok::<_, Infallible>(())
+                    }
+                },
+            ));
+
+        svc.serve(Context::default(), stream).await.unwrap();
+    }
+}
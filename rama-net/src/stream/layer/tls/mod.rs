@@ -0,0 +1,6 @@
+//! Layers that can be used to inspect the TLS handshake of a stream,
+//! without depending on a specific TLS implementation.
+
+mod client_hello;
+#[doc(inline)]
+pub use client_hello::{ClientHelloPeekLayer, ClientHelloPeekService};
@@ -26,6 +26,12 @@ mod ip;
 #[doc(inline)]
 pub use ip::IpNetMatcher;
 
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "tls")]
+#[doc(inline)]
+pub use tls::{AlpnMatcher, SniMatcher};
+
 use rama_core::{context::Extensions, matcher::IteratorMatcherExt, Context};
 use std::{fmt, sync::Arc};
 
@@ -78,6 +84,16 @@ enum SocketMatcherKind<State, Socket> {
     /// [`IpNet`]: ipnet::IpNet
     /// [`SocketAddr`]: std::net::SocketAddr
     IpNet(IpNetMatcher),
+    #[cfg(feature = "tls")]
+    /// [`SniMatcher`], a matcher based on the server name (SNI) of the [`ClientHello`].
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    Sni(SniMatcher),
+    #[cfg(feature = "tls")]
+    /// [`AlpnMatcher`], a matcher based on the ALPN protocols advertised in the [`ClientHello`].
+    ///
+    /// [`ClientHello`]: crate::tls::client::ClientHello
+    Alpn(AlpnMatcher),
     /// zero or more matchers that all need to match in order for the matcher to return `true`.
     All(Vec<SocketMatcher<State, Socket>>),
     /// `true` if no matchers are defined, or any of the defined matcher match.
@@ -94,6 +110,10 @@ impl<State, Socket> Clone for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => Self::PrivateIpNet(matcher.clone()),
             Self::Port(matcher) => Self::Port(matcher.clone()),
             Self::IpNet(matcher) => Self::IpNet(matcher.clone()),
+            #[cfg(feature = "tls")]
+            Self::Sni(matcher) => Self::Sni(matcher.clone()),
+            #[cfg(feature = "tls")]
+            Self::Alpn(matcher) => Self::Alpn(matcher.clone()),
             Self::All(matcher) => Self::All(matcher.clone()),
             Self::Any(matcher) => Self::Any(matcher.clone()),
             Self::Custom(matcher) => Self::Custom(matcher.clone()),
@@ -109,6 +129,10 @@ impl<State, Socket> fmt::Debug for SocketMatcherKind<State, Socket> {
             Self::PrivateIpNet(matcher) => f.debug_tuple("PrivateIpNet").field(matcher).finish(),
             Self::Port(matcher) => f.debug_tuple("Port").field(matcher).finish(),
             Self::IpNet(matcher) => f.debug_tuple("IpNet").field(matcher).finish(),
+            #[cfg(feature = "tls")]
+            Self::Sni(matcher) => f.debug_tuple("Sni").field(matcher).finish(),
+            #[cfg(feature = "tls")]
+            Self::Alpn(matcher) => f.debug_tuple("Alpn").field(matcher).finish(),
             Self::All(matcher) => f.debug_tuple("All").field(matcher).finish(),
             Self::Any(matcher) => f.debug_tuple("Any").field(matcher).finish(),
             Self::Custom(_) => f.debug_tuple("Custom").finish(),
@@ -364,6 +388,95 @@ impl<State, Socket> SocketMatcher<State, Socket> {
         self.or(Self::optional_private_ip_net())
     }
 
+    /// Create a [`SniMatcher`] matcher, matching on the exact given [`Domain`].
+    ///
+    /// See [`SniMatcher::exact`] for more information.
+    ///
+    /// [`Domain`]: crate::address::Domain
+    #[cfg(feature = "tls")]
+    pub fn sni(domain: crate::address::Domain) -> Self {
+        Self {
+            kind: SocketMatcherKind::Sni(SniMatcher::exact(domain)),
+            negate: false,
+        }
+    }
+
+    /// Create a [`SniMatcher`] matcher, matching on the exact given [`Domain`]
+    /// or a subdomain of it.
+    ///
+    /// See [`SniMatcher::sub`] for more information.
+    ///
+    /// [`Domain`]: crate::address::Domain
+    #[cfg(feature = "tls")]
+    pub fn sub_sni(domain: crate::address::Domain) -> Self {
+        Self {
+            kind: SocketMatcherKind::Sni(SniMatcher::sub(domain)),
+            negate: false,
+        }
+    }
+
+    /// Add a [`SniMatcher`] matcher to also match on top of the existing set of [`SocketMatcher`] matchers.
+    ///
+    /// See [`Self::sni`] for more information.
+    #[cfg(feature = "tls")]
+    pub fn and_sni(self, domain: crate::address::Domain) -> Self {
+        self.and(Self::sni(domain))
+    }
+
+    /// Add a sub [`SniMatcher`] matcher to also match on top of the existing set of [`SocketMatcher`] matchers.
+    ///
+    /// See [`Self::sub_sni`] for more information.
+    #[cfg(feature = "tls")]
+    pub fn and_sub_sni(self, domain: crate::address::Domain) -> Self {
+        self.and(Self::sub_sni(domain))
+    }
+
+    /// Add a [`SniMatcher`] matcher to match as an alternative to the existing set of [`SocketMatcher`] matchers.
+    ///
+    /// See [`Self::sni`] for more information.
+    #[cfg(feature = "tls")]
+    pub fn or_sni(self, domain: crate::address::Domain) -> Self {
+        self.or(Self::sni(domain))
+    }
+
+    /// Add a sub [`SniMatcher`] matcher to match as an alternative to the existing set of [`SocketMatcher`] matchers.
+    ///
+    /// See [`Self::sub_sni`] for more information.
+    #[cfg(feature = "tls")]
+    pub fn or_sub_sni(self, domain: crate::address::Domain) -> Self {
+        self.or(Self::sub_sni(domain))
+    }
+
+    /// Create an [`AlpnMatcher`] matcher, matching if the given
+    /// [`ApplicationProtocol`] is advertised by the client.
+    ///
+    /// See [`AlpnMatcher::new`] for more information.
+    ///
+    /// [`ApplicationProtocol`]: crate::tls::ApplicationProtocol
+    #[cfg(feature = "tls")]
+    pub fn alpn(protocol: crate::tls::ApplicationProtocol) -> Self {
+        Self {
+            kind: SocketMatcherKind::Alpn(AlpnMatcher::new(protocol)),
+            negate: false,
+        }
+    }
+
+    /// Add an [`AlpnMatcher`] matcher to also match on top of the existing set of [`SocketMatcher`] matchers.
+    ///
+    /// See [`Self::alpn`] for more information.
+    #[cfg(feature = "tls")]
+    pub fn and_alpn(self, protocol: crate::tls::ApplicationProtocol) -> Self {
+        self.and(Self::alpn(protocol))
+    }
+
+    /// Add an [`AlpnMatcher`] matcher to match as an alternative to the existing set of [`SocketMatcher`] matchers.
+    ///
+    /// See [`Self::alpn`] for more information.
+    #[cfg(feature = "tls")]
+    pub fn or_alpn(self, protocol: crate::tls::ApplicationProtocol) -> Self {
+        self.or(Self::alpn(protocol))
+    }
+
     /// Create a matcher that matches according to a custom predicate.
     ///
     /// See [`rama_core::matcher::Matcher`] for more information.
@@ -455,6 +568,10 @@ where
             SocketMatcherKind::All(matchers) => matchers.iter().matches_and(ext, ctx, req),
             SocketMatcherKind::Any(matchers) => matchers.iter().matches_or(ext, ctx, req),
             SocketMatcherKind::Port(matcher) => matcher.matches(ext, ctx, req),
+            #[cfg(feature = "tls")]
+            SocketMatcherKind::Sni(matcher) => matcher.matches(ext, ctx, req),
+            #[cfg(feature = "tls")]
+            SocketMatcherKind::Alpn(matcher) => matcher.matches(ext, ctx, req),
             SocketMatcherKind::Custom(matcher) => matcher.matches(ext, ctx, req),
         }
     }
@@ -494,6 +611,10 @@ where
             SocketMatcherKind::Loopback(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::PrivateIpNet(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::Port(matcher) => matcher.matches(ext, ctx, stream),
+            #[cfg(feature = "tls")]
+            SocketMatcherKind::Sni(matcher) => matcher.matches(ext, ctx, stream),
+            #[cfg(feature = "tls")]
+            SocketMatcherKind::Alpn(matcher) => matcher.matches(ext, ctx, stream),
             SocketMatcherKind::All(matchers) => matchers.iter().matches_and(ext, ctx, stream),
             SocketMatcherKind::Any(matchers) => matchers.iter().matches_or(ext, ctx, stream),
             SocketMatcherKind::Custom(matcher) => matcher.matches(ext, ctx, stream),
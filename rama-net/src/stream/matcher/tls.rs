@@ -0,0 +1,185 @@
+use crate::address::Domain;
+use crate::tls::{client::ClientHello, ApplicationProtocol, SecureTransport};
+use rama_core::{context::Extensions, Context};
+
+/// Get the [`ClientHello`] from the [`Context`], either as peeked
+/// from the raw stream or as stored by the tls acceptor.
+fn client_hello<State>(ctx: &Context<State>) -> Option<&ClientHello> {
+    ctx.get::<ClientHello>()
+        .or_else(|| ctx.get::<SecureTransport>()?.client_hello())
+}
+
+#[derive(Debug, Clone)]
+/// Matcher based on the server name (SNI) of the [`ClientHello`].
+///
+/// The [`ClientHello`] is read from the [`Context`], as inserted by the
+/// [`ClientHelloPeekLayer`], or by a tls acceptor that stores the [`ClientHello`].
+/// This matcher will not match in case no [`ClientHello`] or server name could be found.
+///
+/// [`ClientHelloPeekLayer`]: crate::stream::layer::tls::ClientHelloPeekLayer
+pub struct SniMatcher {
+    domain: Domain,
+    sub: bool,
+}
+
+impl SniMatcher {
+    /// Create a new [`SniMatcher`] that matches
+    /// if the server name is equal to the given [`Domain`].
+    pub const fn exact(domain: Domain) -> Self {
+        Self { domain, sub: false }
+    }
+
+    /// Create a new [`SniMatcher`] that matches if the server name
+    /// is equal to the given [`Domain`] or one of its subdomains.
+    pub const fn sub(domain: Domain) -> Self {
+        Self { domain, sub: true }
+    }
+}
+
+impl<State, Request> rama_core::matcher::Matcher<State, Request> for SniMatcher {
+    fn matches(&self, _ext: Option<&mut Extensions>, ctx: &Context<State>, _req: &Request) -> bool {
+        let Some(server_name) = client_hello(ctx).and_then(|hello| hello.ext_server_name()) else {
+            return false;
+        };
+        if self.sub {
+            self.domain.is_parent_of(server_name)
+        } else {
+            self.domain == *server_name
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Matcher based on the ALPN protocols advertised in the [`ClientHello`].
+///
+/// The [`ClientHello`] is read from the [`Context`], as inserted by the
+/// [`ClientHelloPeekLayer`], or by a tls acceptor that stores the [`ClientHello`].
+/// This matcher will not match in case no [`ClientHello`] or ALPN extension could be found.
+///
+/// [`ClientHelloPeekLayer`]: crate::stream::layer::tls::ClientHelloPeekLayer
+pub struct AlpnMatcher {
+    protocol: ApplicationProtocol,
+}
+
+impl AlpnMatcher {
+    /// Create a new [`AlpnMatcher`] that matches if
+    /// the given [`ApplicationProtocol`] is advertised by the client.
+    pub const fn new(protocol: ApplicationProtocol) -> Self {
+        Self { protocol }
+    }
+}
+
+impl<State, Request> rama_core::matcher::Matcher<State, Request> for AlpnMatcher {
+    fn matches(&self, _ext: Option<&mut Extensions>, ctx: &Context<State>, _req: &Request) -> bool {
+        client_hello(ctx)
+            .and_then(|hello| hello.ext_alpn())
+            .map(|protocols| protocols.contains(&self.protocol))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tls::client::{parse_client_hello_records, test_utils::client_hello_record};
+    use rama_core::matcher::Matcher;
+
+    fn client_hello_with(extensions: &[u8]) -> ClientHello {
+        parse_client_hello_records(&client_hello_record(extensions))
+            .unwrap()
+            .unwrap()
+    }
+
+    fn client_hello() -> ClientHello {
+        client_hello_with(&[
+            // server name: api.example.com
+            0x00, 0x00, 0x00, 0x14, 0x00, 0x12, 0x00, 0x00, 0x0f, b'a', b'p', b'i', b'.', b'e',
+            b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm',
+            // alpn: h2, http/1.1
+            0x00, 0x10, 0x00, 0x0e, 0x00, 0x0c, 0x02, b'h', b'2', 0x08, b'h', b't', b't', b'p',
+            b'/', b'1', b'.', b'1',
+        ])
+    }
+
+    #[test]
+    fn test_sni_matcher() {
+        let mut ctx = Context::default();
+
+        // no client hello available
+        assert!(!SniMatcher::sub(Domain::from_static("example.com")).matches(None, &ctx, &()));
+
+        ctx.insert(client_hello());
+        for (matcher, expected) in [
+            (
+                SniMatcher::exact(Domain::from_static("api.example.com")),
+                true,
+            ),
+            (
+                SniMatcher::exact(Domain::from_static("API.example.com")),
+                true,
+            ),
+            (SniMatcher::exact(Domain::from_static("example.com")), false),
+            (SniMatcher::sub(Domain::from_static("example.com")), true),
+            (
+                SniMatcher::sub(Domain::from_static("api.example.com")),
+                true,
+            ),
+            (
+                SniMatcher::sub(Domain::from_static("www.example.com")),
+                false,
+            ),
+            (SniMatcher::sub(Domain::from_static("example.org")), false),
+        ] {
+            assert_eq!(matcher.matches(None, &ctx, &()), expected, "{matcher:?}");
+        }
+
+        // client hello without server name
+        ctx.insert(client_hello_with(&[]));
+        assert!(!SniMatcher::sub(Domain::from_static("example.com")).matches(None, &ctx, &()));
+    }
+
+    #[test]
+    fn test_sni_matcher_secure_transport() {
+        let mut ctx = Context::default();
+        ctx.insert(SecureTransport::with_client_hello(client_hello()));
+        assert!(SniMatcher::exact(Domain::from_static("api.example.com")).matches(None, &ctx, &()));
+    }
+
+    #[test]
+    fn test_alpn_matcher() {
+        let mut ctx = Context::default();
+
+        // no client hello available
+        assert!(!AlpnMatcher::new(ApplicationProtocol::HTTP_2).matches(None, &ctx, &()));
+
+        ctx.insert(client_hello());
+        assert!(AlpnMatcher::new(ApplicationProtocol::HTTP_2).matches(None, &ctx, &()));
+        assert!(AlpnMatcher::new(ApplicationProtocol::HTTP_11).matches(None, &ctx, &()));
+        assert!(!AlpnMatcher::new(ApplicationProtocol::HTTP_10).matches(None, &ctx, &()));
+
+        // client hello without alpn
+        ctx.insert(client_hello_with(&[]));
+        assert!(!AlpnMatcher::new(ApplicationProtocol::HTTP_2).matches(None, &ctx, &()));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_socket_matcher_tls() {
+        use crate::stream::matcher::SocketMatcher;
+        use rama_http_types::Request;
+
+        let matcher: SocketMatcher<(), Request<()>> =
+            SocketMatcher::sub_sni(Domain::from_static("example.com"))
+                .and_alpn(ApplicationProtocol::HTTP_2);
+        let req = Request::new(());
+
+        let mut ctx = Context::default();
+        assert!(!matcher.matches(None, &ctx, &req));
+
+        ctx.insert(client_hello());
+        assert!(matcher.matches(None, &ctx, &req));
+
+        let matcher = matcher.or_sni(Domain::from_static("example.org")).negate();
+        assert!(!matcher.matches(None, &ctx, &req));
+    }
+}
@@ -0,0 +1,405 @@
+use super::{ClientHello, ClientHelloExtension};
+use crate::tls::{ApplicationProtocol, ExtensionId, ProtocolVersion};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The JA3 fingerprint of a [`ClientHello`].
+///
+/// The JA3 string is composed out of the (legacy) version, cipher suites,
+/// extensions, supported groups and EC point formats of the [`ClientHello`],
+/// in the order they were defined, with all GREASE values filtered out.
+/// The JA3 fingerprint is the MD5 hash of that string.
+///
+/// It is inserted in the [`Context`] by the tls acceptors
+/// in case they are configured to store the [`ClientHello`].
+///
+/// [`Context`]: rama_core::Context
+///
+/// # Reference
+///
+/// - <https://github.com/salesforce/ja3>
+pub struct Ja3 {
+    full: String,
+    hash: String,
+}
+
+impl Ja3 {
+    /// Compute the [`Ja3`] fingerprint for the given [`ClientHello`].
+    pub fn compute(hello: &ClientHello) -> Self {
+        let version = u16::from(hello.protocol_version()).to_string();
+
+        let ciphers = join_decimal(hello.cipher_suites().iter().map(|cs| u16::from(*cs)));
+
+        let extensions = join_decimal(hello.extensions().iter().map(|ext| u16::from(ext.id())));
+
+        let groups = join_decimal(
+            hello
+                .ext_supported_groups()
+                .unwrap_or_default()
+                .iter()
+                .map(|group| u16::from(*group)),
+        );
+
+        let point_formats = hello
+            .ext_ec_point_formats()
+            .unwrap_or_default()
+            .iter()
+            .map(|format| u8::from(*format).to_string())
+            .collect::<Vec<_>>()
+            .join("-");
+
+        let full = format!("{version},{ciphers},{extensions},{groups},{point_formats}");
+        let hash = format!("{:x}", md5::compute(full.as_bytes()));
+        Self { full, hash }
+    }
+
+    /// Return the full JA3 string, which is the input of the [`Ja3::hash`].
+    pub fn full(&self) -> &str {
+        &self.full
+    }
+
+    /// Return the JA3 fingerprint, the (hex-encoded) MD5 hash of the [`Ja3::full`] string.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+impl fmt::Display for Ja3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.hash)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The JA4 fingerprint of a [`ClientHello`].
+///
+/// The JA4 fingerprint has the form `a_b_c`, where:
+///
+/// - `a` describes the transport (QUIC in case the `quic_transport_parameters`
+///   extension is present, TCP otherwise), the highest supported TLS version,
+///   whether or not SNI is present, the number of cipher suites and extensions,
+///   and the first and last character of the first ALPN value;
+/// - `b` is the truncated SHA256 hash of the sorted cipher suites;
+/// - `c` is the truncated SHA256 hash of the sorted extensions (without SNI and ALPN),
+///   followed by the signature algorithms in the order they were defined.
+///
+/// All GREASE values are filtered out. The raw (unhashed) form of the fingerprint,
+/// also known as `JA4_r`, is available via [`Ja4::to_raw_string`].
+///
+/// It is inserted in the [`Context`] by the tls acceptors
+/// in case they are configured to store the [`ClientHello`].
+///
+/// [`Context`]: rama_core::Context
+///
+/// # Reference
+///
+/// - <https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4.md>
+pub struct Ja4 {
+    a: String,
+    b_raw: String,
+    c_raw: String,
+}
+
+impl Ja4 {
+    /// Compute the [`Ja4`] fingerprint for the given [`ClientHello`].
+    pub fn compute(hello: &ClientHello) -> Self {
+        let version = hello
+            .supported_versions()
+            .and_then(|versions| {
+                versions
+                    .iter()
+                    .copied()
+                    .filter(|v| !is_grease(u16::from(*v)))
+                    .max_by_key(|v| version_rank(*v))
+            })
+            .unwrap_or_else(|| hello.protocol_version());
+
+        let transport = if hello
+            .extensions()
+            .iter()
+            .any(|ext| ext.id() == ExtensionId::QUIC_TRANSPORT_PARAMETERS)
+        {
+            'q'
+        } else {
+            't'
+        };
+
+        let sni = if hello
+            .extensions()
+            .iter()
+            .any(|ext| matches!(ext, ClientHelloExtension::ServerName(_)))
+        {
+            'd'
+        } else {
+            'i'
+        };
+
+        let mut ciphers: Vec<u16> = hello
+            .cipher_suites()
+            .iter()
+            .map(|cs| u16::from(*cs))
+            .filter(|cs| !is_grease(*cs))
+            .collect();
+
+        let extensions: Vec<u16> = hello
+            .extensions()
+            .iter()
+            .map(|ext| u16::from(ext.id()))
+            .filter(|id| !is_grease(*id))
+            .collect();
+
+        let alpn = hello
+            .ext_alpn()
+            .and_then(|alpns| alpns.first())
+            .map(alpn_chars)
+            .unwrap_or_else(|| "00".to_owned());
+
+        let a = format!(
+            "{transport}{}{sni}{:02}{:02}{alpn}",
+            version_str(version),
+            ciphers.len().min(99),
+            extensions.len().min(99),
+        );
+
+        ciphers.sort_unstable();
+        let b_raw = join_hex(ciphers.into_iter());
+
+        let mut sorted_extensions: Vec<u16> = extensions
+            .into_iter()
+            .filter(|id| {
+                *id != u16::from(ExtensionId::SERVER_NAME)
+                    && *id != u16::from(ExtensionId::APPLICATION_LAYER_PROTOCOL_NEGOTIATION)
+            })
+            .collect();
+        sorted_extensions.sort_unstable();
+        let mut c_raw = join_hex(sorted_extensions.into_iter());
+
+        let signature_algorithms = join_hex(
+            hello
+                .ext_signature_algorithms()
+                .unwrap_or_default()
+                .iter()
+                .map(|scheme| u16::from(*scheme))
+                .filter(|scheme| !is_grease(*scheme)),
+        );
+        if !signature_algorithms.is_empty() {
+            c_raw.push('_');
+            c_raw.push_str(&signature_algorithms);
+        }
+
+        Self { a, b_raw, c_raw }
+    }
+
+    /// Return the raw form of this [`Ja4`] fingerprint (`JA4_r`),
+    /// in which the `b` and `c` parts are not hashed.
+    pub fn to_raw_string(&self) -> String {
+        format!("{}_{}_{}", self.a, self.b_raw, self.c_raw)
+    }
+}
+
+impl fmt::Display for Ja4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}_{}_{}",
+            self.a,
+            truncated_sha256(&self.b_raw),
+            truncated_sha256(&self.c_raw)
+        )
+    }
+}
+
+impl ClientHello {
+    /// Compute the [`Ja3`] fingerprint of this [`ClientHello`].
+    pub fn ja3(&self) -> Ja3 {
+        Ja3::compute(self)
+    }
+
+    /// Compute the [`Ja4`] fingerprint of this [`ClientHello`].
+    pub fn ja4(&self) -> Ja4 {
+        Ja4::compute(self)
+    }
+}
+
+/// GREASE values ([RFC 8701](https://datatracker.ietf.org/doc/html/rfc8701))
+/// are of the form `0x?A?A`, with both bytes being equal.
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && (value >> 8) == (value & 0xff)
+}
+
+fn join_decimal(values: impl Iterator<Item = u16>) -> String {
+    values
+        .filter(|v| !is_grease(*v))
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn join_hex(values: impl Iterator<Item = u16>) -> String {
+    values
+        .map(|v| format!("{v:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn truncated_sha256(value: &str) -> String {
+    if value.is_empty() {
+        return "000000000000".to_owned();
+    }
+    let mut hash = hex::encode(Sha256::digest(value.as_bytes()));
+    hash.truncate(12);
+    hash
+}
+
+fn version_rank(version: ProtocolVersion) -> u8 {
+    match version {
+        ProtocolVersion::SSLv2 => 1,
+        ProtocolVersion::SSLv3 => 2,
+        ProtocolVersion::TLSv1_0 | ProtocolVersion::DTLSv1_0 => 3,
+        ProtocolVersion::TLSv1_1 => 4,
+        ProtocolVersion::TLSv1_2 | ProtocolVersion::DTLSv1_2 => 5,
+        ProtocolVersion::TLSv1_3 | ProtocolVersion::DTLSv1_3 => 6,
+        _ => 0,
+    }
+}
+
+fn version_str(version: ProtocolVersion) -> &'static str {
+    match version {
+        ProtocolVersion::SSLv2 => "s2",
+        ProtocolVersion::SSLv3 => "s3",
+        ProtocolVersion::TLSv1_0 => "10",
+        ProtocolVersion::TLSv1_1 => "11",
+        ProtocolVersion::TLSv1_2 => "12",
+        ProtocolVersion::TLSv1_3 => "13",
+        ProtocolVersion::DTLSv1_0 => "d1",
+        ProtocolVersion::DTLSv1_2 => "d2",
+        ProtocolVersion::DTLSv1_3 => "d3",
+        _ => "00",
+    }
+}
+
+/// Return the first and last character of the given ALPN value,
+/// or the first and last character of its hex representation
+/// in case either of those is not an alphanumeric ASCII character.
+fn alpn_chars(alpn: &ApplicationProtocol) -> String {
+    let bytes = alpn.as_bytes();
+    match (bytes.first(), bytes.last()) {
+        (Some(first), Some(last)) => {
+            if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                format!("{}{}", *first as char, *last as char)
+            } else {
+                let hex = hex::encode(bytes);
+                let mut chars = hex.chars();
+                match (chars.next(), chars.last()) {
+                    (Some(first), Some(last)) => format!("{first}{last}"),
+                    _ => "00".to_owned(),
+                }
+            }
+        }
+        _ => "00".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{
+        enums::CompressionAlgorithm, CipherSuite, ECPointFormat, SignatureScheme, SupportedGroup,
+    };
+
+    fn test_client_hello() -> ClientHello {
+        ClientHello {
+            protocol_version: ProtocolVersion::TLSv1_2,
+            cipher_suites: vec![
+                CipherSuite::Unknown(0x0a0a),
+                CipherSuite::TLS13_AES_128_GCM_SHA256,
+                CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+                CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            ],
+            compression_algorithms: vec![CompressionAlgorithm::Null],
+            extensions: vec![
+                ClientHelloExtension::Opaque {
+                    id: ExtensionId::from(0x1a1a),
+                    data: vec![],
+                },
+                ClientHelloExtension::ServerName(Some("example.com".parse().unwrap())),
+                ClientHelloExtension::SupportedGroups(vec![
+                    SupportedGroup::from(0x2a2a),
+                    SupportedGroup::X25519,
+                    SupportedGroup::SECP256R1,
+                ]),
+                ClientHelloExtension::ECPointFormats(vec![ECPointFormat::Uncompressed]),
+                ClientHelloExtension::SignatureAlgorithms(vec![
+                    SignatureScheme::ECDSA_NISTP256_SHA256,
+                    SignatureScheme::RSA_PSS_SHA256,
+                ]),
+                ClientHelloExtension::ApplicationLayerProtocolNegotiation(vec![
+                    ApplicationProtocol::HTTP_2,
+                    ApplicationProtocol::HTTP_11,
+                ]),
+                ClientHelloExtension::SupportedVersions(vec![
+                    ProtocolVersion::from(0x3a3a),
+                    ProtocolVersion::TLSv1_3,
+                    ProtocolVersion::TLSv1_2,
+                ]),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_ja3() {
+        let ja3 = test_client_hello().ja3();
+        assert_eq!(ja3.full(), "771,4865-49195-49199,0-10-11-13-16-43,29-23,0");
+        assert_eq!(ja3.hash(), "ca8e4c4650b66a379c9647d51e9d63e1");
+        assert_eq!(ja3.to_string(), ja3.hash());
+    }
+
+    #[test]
+    fn test_ja4() {
+        let ja4 = test_client_hello().ja4();
+        assert_eq!(
+            ja4.to_raw_string(),
+            "t13d0306h2_1301,c02b,c02f_000a,000b,000d,002b_0403,0804"
+        );
+        assert_eq!(ja4.to_string(), "t13d0306h2_28fa9b2900ea_fb71836bce29");
+    }
+
+    #[test]
+    fn test_ja4_without_extensions() {
+        let hello = ClientHello {
+            protocol_version: ProtocolVersion::TLSv1_2,
+            cipher_suites: vec![],
+            compression_algorithms: vec![],
+            extensions: vec![],
+        };
+        assert_eq!(
+            hello.ja4().to_string(),
+            "t12i000000_000000000000_000000000000"
+        );
+    }
+
+    #[test]
+    fn test_ja4_quic() {
+        let mut hello = test_client_hello();
+        hello.extensions.push(ClientHelloExtension::Opaque {
+            id: ExtensionId::QUIC_TRANSPORT_PARAMETERS,
+            data: vec![0x01, 0x01, 0x00],
+        });
+        assert!(hello.ja4().to_raw_string().starts_with("q13d0307h2_"));
+        assert_eq!(
+            hello
+                .ext_quic_transport_parameters()
+                .unwrap()
+                .max_idle_timeout(),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_alpn_chars() {
+        assert_eq!(alpn_chars(&ApplicationProtocol::HTTP_11), "h1");
+        assert_eq!(alpn_chars(&ApplicationProtocol::from(&[0xab, 0xcd])), "ad");
+        assert_eq!(alpn_chars(&ApplicationProtocol::from(&[])), "00");
+    }
+}
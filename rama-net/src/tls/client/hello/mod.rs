@@ -1,3 +1,4 @@
+use super::QuicTransportParameters;
 use crate::address::Domain;
 use crate::tls::{
     enums::CompressionAlgorithm, ApplicationProtocol, CipherSuite, ECPointFormat, ExtensionId,
@@ -10,7 +11,7 @@ mod rustls;
 #[cfg(feature = "boring")]
 mod boring;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// When a client first connects to a server, it is required to send
 /// the ClientHello as its first message.
 ///
@@ -20,12 +21,21 @@ mod boring;
 /// For Rama however we only focus on the parts which
 /// a user might want to inspect and/or set.
 pub struct ClientHello {
+    pub(super) protocol_version: ProtocolVersion,
     pub(super) cipher_suites: Vec<CipherSuite>,
     pub(super) compression_algorithms: Vec<CompressionAlgorithm>,
     pub(super) extensions: Vec<ClientHelloExtension>,
 }
 
 impl ClientHello {
+    /// Return the (legacy) [`ProtocolVersion`] defined in this [`ClientHello`].
+    ///
+    /// Since TLS 1.3 the actual supported versions are defined
+    /// in the [`ClientHelloExtension::SupportedVersions`] extension instead.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
     /// Return all [`CipherSuite`]s defined in this [`ClientHello`].
     pub fn cipher_suites(&self) -> &[CipherSuite] {
         &self.cipher_suites[..]
@@ -117,9 +127,25 @@ impl ClientHello {
         }
         None
     }
+
+    /// Return the QUIC transport parameters sent by this client
+    /// if it is set in the [`ClientHelloExtension`] defined in this [`ClientHello`].
+    ///
+    /// Only QUIC clients set this extension, its data is parsed on demand
+    /// and `None` is returned in case it is invalid.
+    pub fn ext_quic_transport_parameters(&self) -> Option<QuicTransportParameters> {
+        for ext in &self.extensions {
+            if let ClientHelloExtension::Opaque { id, data } = ext {
+                if *id == ExtensionId::QUIC_TRANSPORT_PARAMETERS {
+                    return QuicTransportParameters::parse(data).ok();
+                }
+            }
+        }
+        None
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Extensions that can be set in a [`ClientHello`] message by a TLS client.
 ///
 /// While its name may infer that an extension is by definition optional,
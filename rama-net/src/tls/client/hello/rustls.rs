@@ -1,5 +1,5 @@
 use super::ClientHelloExtension;
-use crate::tls::{ApplicationProtocol, CipherSuite, SignatureScheme};
+use crate::tls::{ApplicationProtocol, CipherSuite, ProtocolVersion, SignatureScheme};
 
 impl<'a> From<rustls::server::ClientHello<'a>> for super::ClientHello {
     fn from(value: rustls::server::ClientHello<'a>) -> Self {
@@ -30,6 +30,9 @@ impl<'a> From<rustls::server::ClientHello<'a>> for super::ClientHello {
         }
 
         Self {
+            // rustls does not expose the legacy version, which is however
+            // always TLS 1.2 for the TLS versions supported by rustls
+            protocol_version: ProtocolVersion::TLSv1_2,
             cipher_suites,
             compression_algorithms: vec![],
             extensions,
@@ -7,10 +7,31 @@
 //! By being implementation agnostic we have the advantage to be able to bridge
 //! easily between different implementations. Making it possible to run for example
 //! a Rustls proxy service but establish connections using BoringSSL.
+//!
+//! [`NegotiatedTlsParameters`] is the implementation agnostic type used to convey
+//! what was negotiated with the server of an outgoing TLS connection,
+//! if the connector is configured to store it. The [`NegotiatedApplicationProtocol`]
+//! on the other hand is always stored, as it defines the protocol spoken over the connection.
 
 mod hello;
 #[doc(inline)]
 pub use hello::{ClientHello, ClientHelloExtension};
 
-#[cfg(feature = "boring")]
+mod fingerprint;
+#[doc(inline)]
+pub use fingerprint::{Ja3, Ja4};
+
 mod parser;
+#[doc(inline)]
+pub use parser::{parse_client_hello, parse_client_hello_records};
+
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+mod quic;
+#[doc(inline)]
+pub use quic::{QuicTransportParameter, QuicTransportParameters};
+
+mod negotiated;
+#[doc(inline)]
+pub use negotiated::{NegotiatedApplicationProtocol, NegotiatedTlsParameters};
@@ -0,0 +1,47 @@
+use crate::tls::{server::PeerCertificate, ApplicationProtocol, CipherSuite, ProtocolVersion};
+use rama_core::error::OpaqueError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The parameters negotiated with the server during the handshake
+/// of an outgoing TLS connection, as found in its `ServerHello` (and onwards).
+///
+/// It is inserted in the [`Context`] by the https connectors,
+/// in case they are configured to store the server info.
+///
+/// [`Context`]: rama_core::Context
+pub struct NegotiatedTlsParameters {
+    /// The negotiated [`ProtocolVersion`].
+    pub protocol_version: ProtocolVersion,
+    /// The negotiated [`CipherSuite`].
+    pub cipher_suite: CipherSuite,
+    /// The [`ApplicationProtocol`] negotiated using ALPN, if any.
+    pub application_layer_protocol: Option<ApplicationProtocol>,
+    /// The DER encoded certificate chain presented by the server,
+    /// starting with its (leaf) certificate.
+    ///
+    /// Empty in case the server did not present any certificate,
+    /// e.g. because the session was resumed.
+    pub server_certificate_chain: Vec<Vec<u8>>,
+    /// Whether or not a previous session was resumed.
+    pub session_resumed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The [`ApplicationProtocol`] negotiated using ALPN for an outgoing TLS connection.
+///
+/// It is inserted in the [`Context`] by the https connectors in case one was negotiated,
+/// regardless of whether or not they are configured to store the server info,
+/// such that the protocol to be spoken over the connection is known.
+///
+/// [`Context`]: rama_core::Context
+pub struct NegotiatedApplicationProtocol(pub ApplicationProtocol);
+
+impl NegotiatedTlsParameters {
+    /// Parse the [`PeerCertificate`]s of the certificate chain presented by the server.
+    pub fn parse_server_certificate_chain(&self) -> Result<Vec<PeerCertificate>, OpaqueError> {
+        self.server_certificate_chain
+            .iter()
+            .map(|der| PeerCertificate::from_der(der.as_slice()))
+            .collect()
+    }
+}
@@ -18,8 +18,65 @@ use nom::{
 };
 use rama_core::error::OpaqueError;
 
+/// Maximum length of the plaintext fragment of a single TLS record,
+/// as defined in [RFC 8446, section 5.1](https://datatracker.ietf.org/doc/html/rfc8446#section-5.1).
+const MAX_RECORD_FRAGMENT_LEN: usize = 1 << 14;
+
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 0x01;
+
+/// Parse a [`ClientHello`] from the TLS record layer bytes,
+/// as they are sent by a client at the start of a TLS connection.
+///
+/// The client hello handshake message can be fragmented over multiple records.
+///
+/// Returns `Ok(None)` in case more bytes are required to parse the [`ClientHello`],
+/// and an error in case the bytes do not contain a (valid) [`ClientHello`].
+pub fn parse_client_hello_records(mut i: &[u8]) -> Result<Option<ClientHello>, OpaqueError> {
+    let mut handshake = Vec::new();
+    loop {
+        let Some(header) = i.get(..5) else {
+            return Ok(None);
+        };
+        if header[0] != CONTENT_TYPE_HANDSHAKE {
+            return Err(OpaqueError::from_display(
+                "parse client hello records: not a tls handshake record",
+            ));
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        if len == 0 || len > MAX_RECORD_FRAGMENT_LEN {
+            return Err(OpaqueError::from_display(
+                "parse client hello records: invalid record length",
+            ));
+        }
+        let Some(fragment) = i.get(5..5 + len) else {
+            return Ok(None);
+        };
+        handshake.extend_from_slice(fragment);
+        i = &i[5 + len..];
+
+        if handshake[0] != HANDSHAKE_TYPE_CLIENT_HELLO {
+            return Err(OpaqueError::from_display(
+                "parse client hello records: not a client hello handshake message",
+            ));
+        }
+        let Some(header) = handshake.get(1..4) else {
+            continue;
+        };
+        let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+        if let Some(message) = handshake.get(4..4 + len) {
+            return parse_client_hello(message).map(Some);
+        }
+    }
+}
+
+/// Parse a [`ClientHello`] from the bytes of a client hello handshake message,
+/// without the handshake message header.
+///
+/// See [`parse_client_hello_records`] to parse it
+/// from the TLS record layer bytes instead.
 #[inline]
-pub(crate) fn parse_client_hello(i: &[u8]) -> Result<ClientHello, OpaqueError> {
+pub fn parse_client_hello(i: &[u8]) -> Result<ClientHello, OpaqueError> {
     match parse_client_hello_inner(i) {
         Err(err) => Err(OpaqueError::from_display(format!(
             "parse client hello handshake message: {err:?}"
@@ -37,7 +94,7 @@ pub(crate) fn parse_client_hello(i: &[u8]) -> Result<ClientHello, OpaqueError> {
 }
 
 fn parse_client_hello_inner(i: &[u8]) -> IResult<&[u8], ClientHello> {
-    let (i, _version) = be_u16(i)?;
+    let (i, version) = be_u16(i)?;
     let (i, _random) = take(32usize)(i)?;
     let (i, sidlen) = verify(be_u8, |&n| n <= 32)(i)?;
     let (i, _sid) = cond(sidlen > 0, take(sidlen as usize))(i)?;
@@ -59,6 +116,7 @@ fn parse_client_hello_inner(i: &[u8]) -> IResult<&[u8], ClientHello> {
     Ok((
         i,
         ClientHello {
+            protocol_version: ProtocolVersion::from(version),
             cipher_suites,
             compression_algorithms,
             extensions,
@@ -275,6 +333,7 @@ fn parse_u16_type<T: From<u16>>(i: &[u8]) -> IResult<&[u8], Vec<T>> {
 mod tests {
     use super::*;
     use crate::address::Domain;
+    use crate::tls::client::test_utils::{client_hello_message, tls_record};
     use crate::tls::{ECPointFormat, ExtensionId, SignatureScheme, SupportedGroup};
 
     #[test]
@@ -282,6 +341,61 @@ mod tests {
         assert!(parse_client_hello(&[]).is_err());
     }
 
+    #[test]
+    fn test_parse_client_hello_records_single_record() {
+        let message = client_hello_message(&[
+            0x00, 0x0b, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02, // ec point formats
+        ]);
+        let bytes = tls_record(&message);
+
+        let hello = parse_client_hello_records(&bytes).unwrap().unwrap();
+        assert_eq!(hello.protocol_version(), ProtocolVersion::TLSv1_2);
+        assert_eq!(
+            hello.cipher_suites(),
+            &[CipherSuite::TLS13_AES_128_GCM_SHA256]
+        );
+        assert_eq!(hello.extensions().len(), 1);
+        assert_eq_ec_point_formats_extension(
+            &hello.extensions()[0],
+            &[
+                ECPointFormat::Uncompressed,
+                ECPointFormat::ANSIX962CompressedPrime,
+                ECPointFormat::ANSIX962CompressedChar2,
+            ],
+        );
+
+        for n in 0..bytes.len() {
+            assert!(parse_client_hello_records(&bytes[..n]).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_parse_client_hello_records_fragmented() {
+        let message = client_hello_message(&[]);
+        let mut bytes = tls_record(&message[..2]);
+        bytes.extend(tls_record(&message[2..20]));
+        let incomplete_len = bytes.len();
+        bytes.extend(tls_record(&message[20..]));
+
+        assert!(parse_client_hello_records(&bytes[..incomplete_len])
+            .unwrap()
+            .is_none());
+        let hello = parse_client_hello_records(&bytes).unwrap().unwrap();
+        assert_eq!(hello.cipher_suites().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_client_hello_records_failure() {
+        // http request
+        assert!(parse_client_hello_records(b"GET / HTTP/1.1\r\n").is_err());
+        // server hello handshake message
+        assert!(parse_client_hello_records(&tls_record(&[0x02, 0x00, 0x00, 0x00])).is_err());
+        // empty record
+        assert!(parse_client_hello_records(&[0x16, 0x03, 0x01, 0x00, 0x00]).is_err());
+        // invalid client hello
+        assert!(parse_client_hello_records(&tls_record(&[0x01, 0x00, 0x00, 0x01, 0xff])).is_err());
+    }
+
     #[test]
     fn test_parse_client_hello_pcap_dump_apple_itunes_bytes_success() {
         let client_hello = parse_client_hello(&[
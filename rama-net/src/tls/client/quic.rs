@@ -0,0 +1,210 @@
+use rama_core::error::OpaqueError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The QUIC transport parameters sent by a client
+/// as the `quic_transport_parameters` extension of its [`ClientHello`].
+///
+/// The parameters are kept in the order they were sent in,
+/// which (together with the GREASE parameters) makes them
+/// a useful input to fingerprint QUIC clients.
+///
+/// It is inserted in the [`Context`] by the QUIC (HTTP/3) server
+/// in case it is configured to store the [`ClientHello`].
+///
+/// [`ClientHello`]: super::ClientHello
+/// [`Context`]: rama_core::Context
+///
+/// # Reference
+///
+/// - <https://www.rfc-editor.org/rfc/rfc9000.html#section-18>
+pub struct QuicTransportParameters {
+    parameters: Vec<QuicTransportParameter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single (raw) parameter of the [`QuicTransportParameters`].
+pub struct QuicTransportParameter {
+    id: u64,
+    value: Vec<u8>,
+}
+
+impl QuicTransportParameter {
+    /// Return the identifier of this [`QuicTransportParameter`].
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Return the raw value of this [`QuicTransportParameter`].
+    pub fn value(&self) -> &[u8] {
+        &self.value[..]
+    }
+
+    /// Returns true if this [`QuicTransportParameter`] uses an identifier
+    /// reserved for GREASE, i.e. of the form `31 * N + 27`.
+    pub fn is_grease(&self) -> bool {
+        self.id >= 27 && (self.id - 27) % 31 == 0
+    }
+}
+
+macro_rules! varint_parameters {
+    ($($(#[$doc:meta])* $name:ident => $id:literal),+ $(,)?) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(&self) -> Option<u64> {
+                self.get_varint($id)
+            }
+        )+
+    };
+}
+
+impl QuicTransportParameters {
+    /// Parse the [`QuicTransportParameters`] from the raw data
+    /// of the `quic_transport_parameters` extension.
+    pub fn parse(mut data: &[u8]) -> Result<Self, OpaqueError> {
+        let mut parameters = Vec::new();
+        while !data.is_empty() {
+            let id = read_varint(&mut data).ok_or_else(|| {
+                OpaqueError::from_display("quic transport parameters: invalid id")
+            })?;
+            let len = read_varint(&mut data).ok_or_else(|| {
+                OpaqueError::from_display("quic transport parameters: invalid length")
+            })?;
+            let value = usize::try_from(len)
+                .ok()
+                .and_then(|len| data.get(..len))
+                .ok_or_else(|| {
+                    OpaqueError::from_display("quic transport parameters: truncated value")
+                })?;
+            data = &data[value.len()..];
+            parameters.push(QuicTransportParameter {
+                id,
+                value: value.to_vec(),
+            });
+        }
+        Ok(Self { parameters })
+    }
+
+    /// Return all [`QuicTransportParameter`]s, in the order they were sent in.
+    pub fn parameters(&self) -> &[QuicTransportParameter] {
+        &self.parameters[..]
+    }
+
+    /// Return the identifiers of all non-GREASE [`QuicTransportParameter`]s,
+    /// in the order they were sent in.
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.parameters
+            .iter()
+            .filter(|param| !param.is_grease())
+            .map(QuicTransportParameter::id)
+    }
+
+    /// Return the raw value of the first parameter with the given identifier, if any.
+    pub fn get(&self, id: u64) -> Option<&[u8]> {
+        self.parameters
+            .iter()
+            .find(|param| param.id == id)
+            .map(QuicTransportParameter::value)
+    }
+
+    /// Return the value of the first parameter with the given identifier
+    /// decoded as a variable-length integer, if any.
+    pub fn get_varint(&self, id: u64) -> Option<u64> {
+        let mut value = self.get(id)?;
+        read_varint(&mut value).filter(|_| value.is_empty())
+    }
+
+    varint_parameters! {
+        /// Return the `max_idle_timeout` (in milliseconds), if set.
+        max_idle_timeout => 0x01,
+        /// Return the `max_udp_payload_size`, if set.
+        max_udp_payload_size => 0x03,
+        /// Return the `initial_max_data`, if set.
+        initial_max_data => 0x04,
+        /// Return the `initial_max_stream_data_bidi_local`, if set.
+        initial_max_stream_data_bidi_local => 0x05,
+        /// Return the `initial_max_stream_data_bidi_remote`, if set.
+        initial_max_stream_data_bidi_remote => 0x06,
+        /// Return the `initial_max_stream_data_uni`, if set.
+        initial_max_stream_data_uni => 0x07,
+        /// Return the `initial_max_streams_bidi`, if set.
+        initial_max_streams_bidi => 0x08,
+        /// Return the `initial_max_streams_uni`, if set.
+        initial_max_streams_uni => 0x09,
+        /// Return the `ack_delay_exponent`, if set.
+        ack_delay_exponent => 0x0a,
+        /// Return the `max_ack_delay` (in milliseconds), if set.
+        max_ack_delay => 0x0b,
+        /// Return the `active_connection_id_limit`, if set.
+        active_connection_id_limit => 0x0e,
+        /// Return the `max_datagram_frame_size`, if set.
+        max_datagram_frame_size => 0x20,
+    }
+
+    /// Returns true if the client set the `disable_active_migration` parameter.
+    pub fn disable_active_migration(&self) -> bool {
+        self.get(0x0c).is_some()
+    }
+
+    /// Return the `initial_source_connection_id`, if set.
+    pub fn initial_source_connection_id(&self) -> Option<&[u8]> {
+        self.get(0x0f)
+    }
+}
+
+/// Read a QUIC variable-length integer,
+/// as defined in [RFC 9000, section 16](https://www.rfc-editor.org/rfc/rfc9000.html#section-16).
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let first = *data.first()?;
+    let len = 1 << (first >> 6);
+    let bytes = data.get(..len)?;
+    let value = bytes[1..].iter().fold(u64::from(first & 0x3f), |value, b| {
+        (value << 8) | u64::from(*b)
+    });
+    *data = &data[len..];
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quic_transport_parameters() {
+        let data = [
+            0x01, 0x04, 0x80, 0x00, 0x75, 0x30, // max_idle_timeout: 30_000
+            0x04, 0x04, 0x80, 0x98, 0x96, 0x80, // initial_max_data: 10_000_000
+            0x0c, 0x00, // disable_active_migration
+            0x0f, 0x02, 0xab, 0xcd, // initial_source_connection_id
+            0x40, 0x3a, 0x01, 0xff, // GREASE (31 * 1 + 27)
+            0x08, 0x01, 0x40, // initial_max_streams_bidi: truncated varint
+        ];
+        let params = QuicTransportParameters::parse(&data).unwrap();
+        assert_eq!(params.parameters().len(), 6);
+        assert!(params.parameters()[4].is_grease());
+        assert_eq!(
+            params.ids().collect::<Vec<_>>(),
+            vec![0x01, 0x04, 0x0c, 0x0f, 0x08]
+        );
+        assert_eq!(params.max_idle_timeout(), Some(30_000));
+        assert_eq!(params.initial_max_data(), Some(10_000_000));
+        assert!(params.disable_active_migration());
+        assert_eq!(
+            params.initial_source_connection_id(),
+            Some(&[0xab, 0xcd][..])
+        );
+        assert_eq!(params.get(0x3a), Some(&[0xff][..]));
+        assert_eq!(params.initial_max_streams_bidi(), None);
+        assert_eq!(params.max_ack_delay(), None);
+    }
+
+    #[test]
+    fn test_parse_quic_transport_parameters_invalid() {
+        for data in [&[0x01][..], &[0x01, 0x04, 0x80], &[0x40]] {
+            assert!(QuicTransportParameters::parse(data).is_err());
+        }
+        assert!(QuicTransportParameters::parse(&[])
+            .unwrap()
+            .parameters()
+            .is_empty());
+    }
+}
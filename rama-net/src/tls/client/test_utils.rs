@@ -0,0 +1,35 @@
+//! Test fixtures shared by the [`ClientHello`] parser, peek layer and matcher tests.
+//!
+//! [`ClientHello`]: super::ClientHello
+
+/// Create a TLS 1.2 `ClientHello` handshake message,
+/// offering only `TLS13_AES_128_GCM_SHA256` and the given raw extensions.
+pub(crate) fn client_hello_message(extensions: &[u8]) -> Vec<u8> {
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x42; 32]);
+    body.extend_from_slice(&[
+        0x00, // session id length
+        0x00, 0x02, 0x13, 0x01, // cipher suites
+        0x01, 0x00, // compression algorithms
+    ]);
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(extensions);
+    let len = (body.len() as u32).to_be_bytes();
+    let mut message = vec![0x01, len[1], len[2], len[3]];
+    message.extend(body);
+    message
+}
+
+/// Wrap the given fragment in a TLS handshake record.
+pub(crate) fn tls_record(fragment: &[u8]) -> Vec<u8> {
+    let len = (fragment.len() as u16).to_be_bytes();
+    let mut record = vec![0x16, 0x03, 0x01, len[0], len[1]];
+    record.extend_from_slice(fragment);
+    record
+}
+
+/// Create a single TLS record containing the [`client_hello_message`]
+/// for the given raw extensions.
+pub(crate) fn client_hello_record(extensions: &[u8]) -> Vec<u8> {
+    tls_record(&client_hello_message(extensions))
+}
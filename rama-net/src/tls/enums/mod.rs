@@ -102,7 +102,7 @@ macro_rules! enum_builder {
     ) => {
         $(#[$comment])*
         #[non_exhaustive]
-        #[derive(Debug, PartialEq, Eq, Clone)]
+        #[derive(Debug, PartialEq, Eq, Hash, Clone)]
         $enum_vis enum $enum_name {
             $( $enum_var),*
             ,Unknown(Vec<u8>)
@@ -8,6 +8,7 @@ pub use enums::{
 };
 
 pub mod client;
+pub mod server;
 
 #[derive(Debug, Clone)]
 /// Context information that can be provided `https` connectors`,
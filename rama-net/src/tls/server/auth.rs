@@ -0,0 +1,332 @@
+use super::{ClientIdentity, SubjectAltName};
+use crate::user::UserId;
+use rama_core::context::Extensions;
+use std::future::Future;
+
+/// The `ClientIdentityAuthority` trait is used to determine if a [`ClientIdentity`] is authorized.
+pub trait ClientIdentityAuthority: Send + Sync + 'static {
+    /// Returns the [`Extensions`] to add to the [`Context`] in case the identity is authorized,
+    /// otherwise `None`.
+    ///
+    /// [`Context`]: rama_core::Context
+    fn authorized(
+        &self,
+        identity: ClientIdentity,
+    ) -> impl Future<Output = Option<Extensions>> + Send + '_;
+}
+
+/// A synchronous version of [`ClientIdentityAuthority`], to be used for primitive implementations.
+pub trait ClientIdentityAuthoritySync: Send + Sync + 'static {
+    /// Returns `true` if the identity is authorized, otherwise `false`.
+    fn authorized(&self, ext: &mut Extensions, identity: &ClientIdentity) -> bool;
+}
+
+impl<A> ClientIdentityAuthority for A
+where
+    A: ClientIdentityAuthoritySync,
+{
+    async fn authorized(&self, identity: ClientIdentity) -> Option<Extensions> {
+        let mut ext = Extensions::new();
+        if ClientIdentityAuthoritySync::authorized(self, &mut ext, &identity) {
+            Some(ext)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, const N: usize> ClientIdentityAuthoritySync for [T; N]
+where
+    T: ClientIdentityAuthoritySync,
+{
+    fn authorized(&self, ext: &mut Extensions, identity: &ClientIdentity) -> bool {
+        self.iter().any(|t| t.authorized(ext, identity))
+    }
+}
+
+impl<T> ClientIdentityAuthoritySync for Vec<T>
+where
+    T: ClientIdentityAuthoritySync,
+{
+    fn authorized(&self, ext: &mut Extensions, identity: &ClientIdentity) -> bool {
+        self.iter().any(|t| t.authorized(ext, identity))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A [`ClientIdentityAuthoritySync`] which authorizes a [`ClientIdentity`]
+/// in case its (leaf) certificate matches the pattern.
+///
+/// Patterns can contain `*` wildcards, matching any sequence of characters,
+/// e.g. `*@example.com` or `spiffe://example.com/*`, with the exception of:
+///
+/// - DNS names, for which a `*` is only allowed in the left-most label and
+///   only matches within that label ([RFC 6125]), e.g. `*.example.com`;
+/// - subjects, for which a `*` only matches within a single attribute value,
+///   e.g. `O=Example, CN=*`.
+///
+/// DNS names and e-mail addresses are matched case-insensitive.
+///
+/// [RFC 6125]: https://datatracker.ietf.org/doc/html/rfc6125#section-6.4.3
+///
+/// When authorized, the matched value is inserted as the [`UserId::Username`].
+pub enum ClientIdentityPattern {
+    /// Match the distinguished name of the subject, e.g. `O=Example, CN=*`.
+    Subject(String),
+    /// Match the common name (CN) of the subject.
+    CommonName(String),
+    /// Match any of the DNS subject alternative names.
+    DnsSan(String),
+    /// Match any of the e-mail subject alternative names.
+    EmailSan(String),
+    /// Match any of the URI subject alternative names, e.g. SPIFFE IDs.
+    UriSan(String),
+}
+
+impl ClientIdentityPattern {
+    /// Returns the value of the given [`ClientIdentity`] matching this pattern, if any.
+    pub fn matches<'a>(&self, identity: &'a ClientIdentity) -> Option<&'a str> {
+        let cert = identity.certificate();
+        match self {
+            Self::Subject(pattern) => {
+                subject_match(pattern, cert.subject()).then_some(cert.subject())
+            }
+            Self::CommonName(pattern) => cert
+                .common_name()
+                .filter(|cn| wildcard_match(pattern, cn, false)),
+            Self::DnsSan(pattern) => cert.subject_alt_names().iter().find_map(|san| match san {
+                SubjectAltName::Dns(name) if dns_match(pattern, name) => Some(name.as_str()),
+                _ => None,
+            }),
+            Self::EmailSan(pattern) => cert.subject_alt_names().iter().find_map(|san| match san {
+                SubjectAltName::Email(email) if wildcard_match(pattern, email, true) => {
+                    Some(email.as_str())
+                }
+                _ => None,
+            }),
+            Self::UriSan(pattern) => cert.subject_alt_names().iter().find_map(|san| match san {
+                SubjectAltName::Uri(uri) if wildcard_match(pattern, uri, false) => {
+                    Some(uri.as_str())
+                }
+                _ => None,
+            }),
+        }
+    }
+}
+
+impl ClientIdentityAuthoritySync for ClientIdentityPattern {
+    fn authorized(&self, ext: &mut Extensions, identity: &ClientIdentity) -> bool {
+        match self.matches(identity) {
+            Some(value) => {
+                ext.insert(UserId::Username(value.to_owned()));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Returns `true` in case the DNS name matches the given pattern,
+/// where a `*` in the left-most label of the pattern matches
+/// any sequence of characters within the left-most label of the name.
+fn dns_match(pattern: &str, name: &str) -> bool {
+    let (pattern_label, pattern_rest) = pattern.split_once('.').unwrap_or((pattern, ""));
+    let (name_label, name_rest) = name.split_once('.').unwrap_or((name, ""));
+    !name_label.is_empty()
+        && wildcard_match(pattern_label, name_label, true)
+        && pattern_rest.eq_ignore_ascii_case(name_rest)
+}
+
+/// Returns `true` in case the distinguished name matches the given pattern,
+/// where `*` in the pattern matches any sequence of characters
+/// within a single (relative distinguished name) attribute value.
+fn subject_match(pattern: &str, subject: &str) -> bool {
+    const SEPARATORS: &[char] = &[',', '+'];
+    let patterns = pattern.split_inclusive(SEPARATORS);
+    let values = subject.split_inclusive(SEPARATORS);
+    patterns.clone().count() == values.clone().count()
+        && patterns
+            .zip(values)
+            .all(|(pattern, value)| wildcard_match(pattern, value, false))
+}
+
+/// Returns `true` in case the value matches the given pattern,
+/// where `*` in the pattern matches any sequence of characters.
+fn wildcard_match(pattern: &str, value: &str, ignore_case: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if ignore_case {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+
+    let (pattern, value) = (pattern.as_bytes(), value.as_bytes());
+    let (mut p, mut v) = (0, 0);
+    // position of the last `*` in the pattern, and the value position it was matched from
+    let mut backtrack = None;
+    while v < value.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if p < pattern.len() && eq(pattern[p], value[v]) {
+            p += 1;
+            v += 1;
+        } else if let Some((star_p, star_v)) = backtrack {
+            // let the last `*` consume one more character
+            p = star_p + 1;
+            v = star_v + 1;
+            backtrack = Some((star_p, star_v + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::server::identity::tests::client_cert_der;
+
+    #[test]
+    fn test_wildcard_match() {
+        for (pattern, value, ignore_case, expected) in [
+            ("example.com", "example.com", false, true),
+            ("example.com", "Example.COM", true, true),
+            ("example.com", "Example.COM", false, false),
+            ("*.example.com", "client.example.com", false, true),
+            ("*.example.com", "example.com", false, false),
+            ("*", "", false, true),
+            ("*", "anything", false, true),
+            (
+                "spiffe://example.com/*",
+                "spiffe://example.com/a/b",
+                false,
+                true,
+            ),
+            (
+                "spiffe://example.com/*",
+                "spiffe://example.org/a",
+                false,
+                false,
+            ),
+            ("a*b*c", "aXXbYYbZZc", false, true),
+            ("a*b*c", "aXXbYYbZZ", false, false),
+            ("", "", false, true),
+            ("", "a", false, false),
+        ] {
+            assert_eq!(
+                wildcard_match(pattern, value, ignore_case),
+                expected,
+                "pattern: {pattern}, value: {value}",
+            );
+        }
+    }
+
+    #[test]
+    fn test_dns_match() {
+        for (pattern, name, expected) in [
+            ("example.com", "EXAMPLE.com", true),
+            ("*.example.com", "client.example.com", true),
+            ("*.Example.com", "client.example.COM", true),
+            ("c*.example.com", "client.example.com", true),
+            ("*.example.com", "example.com", false),
+            ("*.example.com", ".example.com", false),
+            ("*.example.com", "a.b.example.com", false),
+            ("*.example.com", "a.b.evil.example.com", false),
+            ("*.example.com", "client.example.com.evil.org", false),
+            ("client.*.com", "client.example.com", false),
+            ("*", "client", true),
+            ("*", "client.example.com", false),
+        ] {
+            assert_eq!(
+                dns_match(pattern, name),
+                expected,
+                "pattern: {pattern}, name: {name}",
+            );
+        }
+    }
+
+    #[test]
+    fn test_subject_match() {
+        for (pattern, subject, expected) in [
+            ("O=Example, CN=*", "O=Example, CN=client", true),
+            ("O=*, CN=client", "O=Example, CN=client", true),
+            ("O=Example, CN=*", "O=Example, CN=client, OU=Admin", false),
+            ("O=Example, CN=*", "O=Example, CN=client+OU=Admin", false),
+            ("O=*", "O=Example, CN=client", false),
+            ("O=Example, CN=*", "O=Other, CN=client", false),
+            ("*", "O=Example", true),
+            ("*", "O=Example, CN=client", false),
+        ] {
+            assert_eq!(
+                subject_match(pattern, subject),
+                expected,
+                "pattern: {pattern}, subject: {subject}",
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_identity_pattern_authorized() {
+        let identity = ClientIdentity::from_der_chain([client_cert_der()]).unwrap();
+
+        for (pattern, expected) in [
+            (
+                ClientIdentityPattern::Subject("O=Example, CN=*".to_owned()),
+                Some("O=Example, CN=client"),
+            ),
+            (
+                ClientIdentityPattern::CommonName("client".to_owned()),
+                Some("client"),
+            ),
+            (ClientIdentityPattern::CommonName("server".to_owned()), None),
+            (
+                ClientIdentityPattern::DnsSan("*.EXAMPLE.com".to_owned()),
+                Some("client.example.com"),
+            ),
+            (
+                ClientIdentityPattern::DnsSan("*.example.org".to_owned()),
+                None,
+            ),
+            (
+                ClientIdentityPattern::EmailSan("*@example.com".to_owned()),
+                Some("client@example.com"),
+            ),
+            (
+                ClientIdentityPattern::UriSan("spiffe://example.com/*".to_owned()),
+                Some("spiffe://example.com/client"),
+            ),
+            (
+                ClientIdentityPattern::UriSan("spiffe://example.org/*".to_owned()),
+                None,
+            ),
+        ] {
+            let ext = ClientIdentityAuthority::authorized(&pattern, identity.clone()).await;
+            match expected {
+                Some(expected) => {
+                    let user_id: &UserId = ext.as_ref().unwrap().get().unwrap();
+                    assert_eq!(user_id, expected, "pattern: {pattern:?}");
+                }
+                None => assert!(ext.is_none(), "pattern: {pattern:?}"),
+            }
+        }
+
+        let patterns = vec![
+            ClientIdentityPattern::CommonName("server".to_owned()),
+            ClientIdentityPattern::DnsSan("client.example.com".to_owned()),
+        ];
+        assert!(
+            ClientIdentityAuthority::authorized(&patterns, identity.clone())
+                .await
+                .is_some()
+        );
+        assert!(ClientIdentityAuthority::authorized(
+            &Vec::<ClientIdentityPattern>::new(),
+            identity
+        )
+        .await
+        .is_none());
+    }
+}
@@ -0,0 +1,301 @@
+use rama_core::error::{ErrorContext, OpaqueError};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A subject alternative name (SAN) of a [`PeerCertificate`].
+///
+/// Only the name types typically used to identify clients are supported,
+/// all other types are ignored when parsing the certificate.
+pub enum SubjectAltName {
+    /// A DNS name, e.g. `client.example.com`.
+    Dns(String),
+    /// An e-mail address (RFC 822 name), e.g. `john@example.com`.
+    Email(String),
+    /// A URI, e.g. a SPIFFE ID such as `spiffe://example.com/service`.
+    Uri(String),
+    /// An IP address.
+    Ip(IpAddr),
+}
+
+impl fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns(name) => write!(f, "DNS:{name}"),
+            Self::Email(email) => write!(f, "email:{email}"),
+            Self::Uri(uri) => write!(f, "URI:{uri}"),
+            Self::Ip(ip) => write!(f, "IP:{ip}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A (parsed) X.509 certificate presented by the peer of a TLS connection.
+pub struct PeerCertificate {
+    der: Vec<u8>,
+    subject: String,
+    common_name: Option<String>,
+    issuer: String,
+    subject_alt_names: Vec<SubjectAltName>,
+    serial: String,
+    fingerprint: String,
+    spki_sha256: [u8; 32],
+    not_before: SystemTime,
+    not_after: SystemTime,
+}
+
+impl PeerCertificate {
+    /// Parse a [`PeerCertificate`] from the given DER encoded X.509 certificate.
+    pub fn from_der(der: impl Into<Vec<u8>>) -> Result<Self, OpaqueError> {
+        let der = der.into();
+        let (_, cert) =
+            x509_parser::parse_x509_certificate(&der).context("parse x509 certificate")?;
+
+        let subject = cert.subject().to_string();
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(ToOwned::to_owned);
+        let issuer = cert.issuer().to_string();
+        let subject_alt_names = parse_subject_alt_names(&cert)?;
+        let serial = hex::encode(cert.raw_serial());
+        let not_before = timestamp_to_system_time(cert.validity().not_before.timestamp());
+        let not_after = timestamp_to_system_time(cert.validity().not_after.timestamp());
+        let fingerprint = hex::encode(Sha256::digest(&der));
+        let spki_sha256 = Sha256::digest(cert.public_key().raw).into();
+
+        Ok(Self {
+            der,
+            subject,
+            common_name,
+            issuer,
+            subject_alt_names,
+            serial,
+            fingerprint,
+            spki_sha256,
+            not_before,
+            not_after,
+        })
+    }
+
+    /// The DER encoded certificate.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The distinguished name of the subject, e.g. `CN=client, O=Example`.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// The (first) common name (CN) of the subject, if any.
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_deref()
+    }
+
+    /// The distinguished name of the issuer, e.g. `CN=Example CA, O=Example`.
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// The subject alternative names (SAN) of the certificate.
+    pub fn subject_alt_names(&self) -> &[SubjectAltName] {
+        &self.subject_alt_names
+    }
+
+    /// The serial number of the certificate, hex encoded.
+    pub fn serial(&self) -> &str {
+        &self.serial
+    }
+
+    /// The SHA-256 fingerprint of the (DER encoded) certificate, hex encoded.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// The SHA-256 digest of the (DER encoded) subject public key info (SPKI) of the certificate,
+    /// as used for public key pinning.
+    pub fn spki_sha256(&self) -> &[u8; 32] {
+        &self.spki_sha256
+    }
+
+    /// The moment from which the certificate is valid.
+    pub fn not_before(&self) -> SystemTime {
+        self.not_before
+    }
+
+    /// The moment until which the certificate is valid.
+    pub fn not_after(&self) -> SystemTime {
+        self.not_after
+    }
+
+    /// Returns `true` in case the certificate is valid at the given moment.
+    pub fn is_valid_at(&self, time: SystemTime) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+}
+
+fn parse_subject_alt_names(cert: &X509Certificate<'_>) -> Result<Vec<SubjectAltName>, OpaqueError> {
+    let Some(san) = cert
+        .subject_alternative_name()
+        .context("parse subject alternative name extension")?
+    else {
+        return Ok(Vec::new());
+    };
+    Ok(san
+        .value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(name) => Some(SubjectAltName::Dns((*name).to_owned())),
+            GeneralName::RFC822Name(email) => Some(SubjectAltName::Email((*email).to_owned())),
+            GeneralName::URI(uri) => Some(SubjectAltName::Uri((*uri).to_owned())),
+            GeneralName::IPAddress(ip) => match ip.len() {
+                4 => <[u8; 4]>::try_from(*ip)
+                    .ok()
+                    .map(|ip| SubjectAltName::Ip(Ipv4Addr::from(ip).into())),
+                16 => <[u8; 16]>::try_from(*ip)
+                    .ok()
+                    .map(|ip| SubjectAltName::Ip(Ipv6Addr::from(ip).into())),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect())
+}
+
+fn timestamp_to_system_time(timestamp: i64) -> SystemTime {
+    if timestamp >= 0 {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp as u64)
+    } else {
+        SystemTime::UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The identity of the client of a mutual TLS (mTLS) connection,
+/// the certificate (chain) it presented and the server verified during the handshake.
+///
+/// It is inserted in the [`Context`] by the tls acceptors
+/// in case the client presented a certificate.
+///
+/// [`Context`]: rama_core::Context
+pub struct ClientIdentity {
+    chain: Arc<[PeerCertificate]>,
+}
+
+impl ClientIdentity {
+    /// Create a [`ClientIdentity`] from the given DER encoded certificate chain,
+    /// starting with the (leaf) certificate of the client.
+    pub fn from_der_chain<I, C>(chain: I) -> Result<Self, OpaqueError>
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<Vec<u8>>,
+    {
+        let chain = chain
+            .into_iter()
+            .map(PeerCertificate::from_der)
+            .collect::<Result<Vec<_>, _>>()?;
+        if chain.is_empty() {
+            return Err(OpaqueError::from_display(
+                "client identity: empty certificate chain",
+            ));
+        }
+        Ok(Self {
+            chain: chain.into(),
+        })
+    }
+
+    /// The (leaf) certificate of the client.
+    pub fn certificate(&self) -> &PeerCertificate {
+        &self.chain[0]
+    }
+
+    /// The certificate chain presented by the client,
+    /// starting with its (leaf) certificate.
+    pub fn chain(&self) -> &[PeerCertificate] {
+        &self.chain
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    // self-signed certificate for `O=Example, CN=client`, valid from 2024-01-01 until 2034-01-01,
+    // with the SANs `DNS:client.example.com`, `email:client@example.com`,
+    // `URI:spiffe://example.com/client` and `IP:127.0.0.1`
+    const CLIENT_CERT_PEM: &str = r#"-----BEGIN CERTIFICATE-----
+MIIB9DCCAZmgAwIBAgIUQhFkfA6+mWGtyvULC55P38bu29cwCgYIKoZIzj0EAwIw
+IzEQMA4GA1UECgwHRXhhbXBsZTEPMA0GA1UEAwwGY2xpZW50MB4XDTI0MDEwMTAw
+MDAwMFoXDTM0MDEwMTAwMDAwMFowIzEQMA4GA1UECgwHRXhhbXBsZTEPMA0GA1UE
+AwwGY2xpZW50MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEPMzFoJm3N41h3kXd
+8CdV7MLs5Q/F3+iUb2B8zdncr3fp7ap4FGUPz0I53ph7TmAjXWxUxon4KGWEgiiw
+e+E84aOBqjCBpzAdBgNVHQ4EFgQUhwiZTKiZSNf5GQAxe7qK8aPgI6IwHwYDVR0j
+BBgwFoAUhwiZTKiZSNf5GQAxe7qK8aPgI6IwDwYDVR0TAQH/BAUwAwEB/zBUBgNV
+HREETTBLghJjbGllbnQuZXhhbXBsZS5jb22BEmNsaWVudEBleGFtcGxlLmNvbYYb
+c3BpZmZlOi8vZXhhbXBsZS5jb20vY2xpZW50hwR/AAABMAoGCCqGSM49BAMCA0kA
+MEYCIQDOEw5We+6o6sAprOYSnlOaCyVhwu/yEvXs+fMq4pisLAIhAOaAo7IZ7pmv
+xXgtioLIvoUtHlHmyG4qyfaQm7r0WGSa
+-----END CERTIFICATE-----"#;
+
+    pub(in crate::tls::server) fn client_cert_der() -> Vec<u8> {
+        let b64: String = CLIENT_CERT_PEM
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        base64::Engine::decode(&base64::engine::general_purpose::STANDARD, b64).unwrap()
+    }
+
+    #[test]
+    fn test_peer_certificate_from_der() {
+        let der = client_cert_der();
+        let cert = PeerCertificate::from_der(der.clone()).unwrap();
+        assert_eq!(cert.der(), der);
+        assert_eq!(cert.subject(), "O=Example, CN=client");
+        assert_eq!(cert.common_name(), Some("client"));
+        assert_eq!(cert.issuer(), "O=Example, CN=client");
+        assert_eq!(
+            cert.subject_alt_names(),
+            [
+                SubjectAltName::Dns("client.example.com".to_owned()),
+                SubjectAltName::Email("client@example.com".to_owned()),
+                SubjectAltName::Uri("spiffe://example.com/client".to_owned()),
+                SubjectAltName::Ip(Ipv4Addr::LOCALHOST.into()),
+            ]
+        );
+        assert_eq!(cert.fingerprint(), hex::encode(Sha256::digest(&der)));
+        assert_eq!(
+            hex::encode(cert.spki_sha256()),
+            "d82a62c52e1c929af9b79464f41f03e8fb92030ec3ac8e89162a88d360f0f8b7"
+        );
+        assert_eq!(
+            cert.not_before(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200)
+        );
+        assert_eq!(
+            cert.not_after(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(2_019_686_400)
+        );
+        assert!(cert.is_valid_at(SystemTime::UNIX_EPOCH + Duration::from_secs(1_800_000_000)));
+        assert!(!cert.is_valid_at(SystemTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_client_identity_from_der_chain() {
+        let identity = ClientIdentity::from_der_chain([client_cert_der()]).unwrap();
+        assert_eq!(identity.chain().len(), 1);
+        assert_eq!(identity.certificate().common_name(), Some("client"));
+
+        assert!(ClientIdentity::from_der_chain(Vec::<Vec<u8>>::new()).is_err());
+        assert!(ClientIdentity::from_der_chain([b"not a certificate".to_vec()]).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+//! TLS implementation agnostic server types
+//!
+//! [`ClientIdentity`] is used in Rama as the implementation agnostic type
+//! to convey the certificate (chain) presented by the client of an incoming
+//! mutual TLS (mTLS) connection. It is inserted in the [`Context`] by the tls acceptors,
+//! and can be authorized using a [`ClientIdentityAuthority`].
+//!
+//! [`Context`]: rama_core::Context
+
+mod identity;
+#[doc(inline)]
+pub use identity::{ClientIdentity, PeerCertificate, SubjectAltName};
+
+mod auth;
+#[doc(inline)]
+pub use auth::{ClientIdentityAuthority, ClientIdentityAuthoritySync, ClientIdentityPattern};
@@ -71,6 +71,20 @@ impl<T: UsernameLabelParser> AuthoritySync<Basic, T> for Basic {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// An [`Authority`] which authorizes no-one,
+/// used as the default in places where authorization is optional.
+pub struct NoAuthority;
+
+impl<C, L> AuthoritySync<C, L> for NoAuthority
+where
+    C: Credentials + Send + 'static,
+{
+    fn authorized(&self, _ext: &mut Extensions, _credentials: &C) -> bool {
+        false
+    }
+}
+
 impl<C, L, T, const N: usize> AuthoritySync<C, L> for [T; N]
 where
     C: Credentials + Send + 'static,
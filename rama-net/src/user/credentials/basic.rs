@@ -161,6 +161,13 @@ impl PartialEq<Basic> for Basic {
 
 impl Eq for Basic {}
 
+impl std::hash::Hash for Basic {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.username().hash(state);
+        self.password().hash(state);
+    }
+}
+
 const BASIC_SCHEME: &str = "Basic";
 
 #[cfg(feature = "http")]
@@ -0,0 +1,454 @@
+use crate::proto::{
+    client::{Header, Request, UsernamePasswordRequest},
+    server::{Header as MethodSelection, Reply, UsernamePasswordResponse},
+    Command, ReplyKind, SocksMethod,
+};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Layer, Service,
+};
+use rama_net::{
+    address::{Authority, Host, ProxyAddress},
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+    transport::TryRefIntoTransportContext,
+    user::ProxyCredential,
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, net::IpAddr};
+
+/// A connector which can be used to establish a connection over a Socks5 Proxy.
+///
+/// This behaviour is optional and only triggered in case there
+/// is a [`ProxyAddress`] found in the [`Context`] with
+/// the `socks5` or `socks5h` protocol.
+///
+/// For `socks5` the target domain is resolved locally, using the [`Dns`]
+/// resolver found in the [`Context`], while for `socks5h` the domain
+/// is sent as-is, leaving the resolution up to the proxy.
+///
+/// [`Dns`]: rama_core::dns::Dns
+pub struct Socks5ProxyConnector<S> {
+    inner: S,
+    required: bool,
+}
+
+impl<S: fmt::Debug> fmt::Debug for Socks5ProxyConnector<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks5ProxyConnector")
+            .field("inner", &self.inner)
+            .field("required", &self.required)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for Socks5ProxyConnector<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            required: self.required,
+        }
+    }
+}
+
+impl<S> Socks5ProxyConnector<S> {
+    fn new(inner: S, required: bool) -> Self {
+        Self { inner, required }
+    }
+
+    /// Create a new [`Socks5ProxyConnector`]
+    /// which will only connect via a socks5 proxy in case the [`ProxyAddress`] is available
+    /// in the [`Context`].
+    pub fn optional(inner: S) -> Self {
+        Self::new(inner, false)
+    }
+
+    /// Create a new [`Socks5ProxyConnector`]
+    /// which will always connect via a socks5 proxy, but fail in case the [`ProxyAddress`] is
+    /// not available in the [`Context`].
+    pub fn required(inner: S) -> Self {
+        Self::new(inner, true)
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S, State, Request> Service<State, Request> for Socks5ProxyConnector<S>
+where
+    S: ConnectorService<State, Request, Connection: Stream + Unpin, Error: Into<BoxError>>,
+    State: Send + Sync + 'static,
+    Request: TryRefIntoTransportContext<State, Error: Into<BoxError> + Send + Sync + 'static>
+        + Send
+        + 'static,
+{
+    type Response = EstablishedClientConnection<S::Connection, State, Request>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let address = ctx
+            .get::<ProxyAddress>()
+            .filter(|address| is_socks5_proxy(address))
+            .cloned();
+
+        let transport_ctx = ctx
+            .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
+            .map_err(|err| {
+                OpaqueError::from_boxed(err.into())
+                    .context("socks5 proxy connector: get transport context")
+            })?
+            .clone();
+
+        let established_conn =
+            self.inner
+                .connect(ctx, req)
+                .await
+                .map_err(|err| match address.as_ref() {
+                    Some(address) => OpaqueError::from_boxed(err.into())
+                        .context(format!("establish connection to proxy {}", address)),
+                    None => {
+                        OpaqueError::from_boxed(err.into()).context("establish connection target")
+                    }
+                })?;
+
+        // return early in case we did not use a proxy
+        let address = match address {
+            Some(address) => address,
+            None => {
+                return if self.required {
+                    Err("socks5 proxy required but none is defined".into())
+                } else {
+                    tracing::trace!("socks5 proxy connector: no proxy required or set: proceed with direct connection");
+                    Ok(established_conn)
+                };
+            }
+        };
+        // and do the handshake otherwise...
+
+        let EstablishedClientConnection {
+            ctx,
+            req,
+            mut conn,
+            addr,
+        } = established_conn;
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            "socks5 proxy connector: connected to proxy",
+        );
+
+        let remote_dns = address
+            .protocol
+            .as_ref()
+            .map(|p| p.is_socks5h())
+            .unwrap_or_default();
+        let destination = if remote_dns {
+            transport_ctx.authority.clone()
+        } else {
+            resolve_authority(&ctx, transport_ctx.authority.clone()).await?
+        };
+
+        handshake(&mut conn, address.credential.as_ref(), destination).await?;
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            "socks5 proxy connector: connected to proxy: tunnel established",
+        );
+        Ok(EstablishedClientConnection {
+            ctx,
+            req,
+            conn,
+            addr,
+        })
+    }
+}
+
+fn is_socks5_proxy(address: &ProxyAddress) -> bool {
+    address
+        .protocol
+        .as_ref()
+        .map(|p| p.is_socks5() || p.is_socks5h())
+        .unwrap_or_default()
+}
+
+/// Resolve the domain of the given [`Authority`] (if any) to an IP address,
+/// preferring IPv4 over IPv6, such that the proxy doesn't have to.
+async fn resolve_authority<State>(
+    ctx: &Context<State>,
+    authority: Authority,
+) -> Result<Authority, OpaqueError> {
+    let (host, port) = authority.into_parts();
+    let domain = match host {
+        Host::Name(domain) => domain,
+        Host::Address(ip) => return Ok((ip, port).into()),
+    };
+
+    let ip: Option<IpAddr> = match ctx.dns().ipv4_lookup(domain.clone()).await {
+        Ok(mut it) => it.next().map(Into::into),
+        Err(err) => {
+            tracing::trace!(%domain, error = %err, "socks5 proxy connector: ipv4 lookup failed");
+            None
+        }
+    };
+    let ip = match ip {
+        Some(ip) => ip,
+        None => ctx
+            .dns()
+            .ipv6_lookup(domain.clone())
+            .await
+            .context("socks5 proxy connector: resolve target domain")?
+            .next()
+            .map(Into::into)
+            .ok_or_else(|| {
+                OpaqueError::from_display(format!(
+                    "socks5 proxy connector: no ip address found for {domain}"
+                ))
+            })?,
+    };
+
+    Ok((ip, port).into())
+}
+
+async fn handshake<S: Stream + Unpin>(
+    stream: &mut S,
+    credential: Option<&ProxyCredential>,
+    destination: Authority,
+) -> Result<(), BoxError> {
+    let basic = match credential {
+        Some(ProxyCredential::Basic(basic)) => Some(basic),
+        Some(ProxyCredential::Bearer(_)) => {
+            return Err(OpaqueError::from_display(
+                "socks5 proxy connector: bearer credentials are not supported",
+            )
+            .into_boxed());
+        }
+        None => None,
+    };
+
+    let methods = if basic.is_some() {
+        vec![
+            SocksMethod::NoAuthenticationRequired,
+            SocksMethod::UsernamePassword,
+        ]
+    } else {
+        vec![SocksMethod::NoAuthenticationRequired]
+    };
+    Header::new(methods)
+        .write_to(stream)
+        .await
+        .context("socks5 proxy connector: write client header")?;
+
+    let MethodSelection { method } = MethodSelection::read_from(stream)
+        .await
+        .context("socks5 proxy connector: read method selection")?;
+
+    match (method, basic) {
+        (SocksMethod::NoAuthenticationRequired, _) => (),
+        (SocksMethod::UsernamePassword, Some(basic)) => {
+            UsernamePasswordRequest::new(basic.clone())
+                .write_to(stream)
+                .await
+                .context("socks5 proxy connector: write username/password request")?;
+            let response = UsernamePasswordResponse::read_from(stream)
+                .await
+                .context("socks5 proxy connector: read username/password response")?;
+            if !response.is_success() {
+                return Err(OpaqueError::from_display(
+                    "socks5 proxy connector: proxy rejected credentials",
+                )
+                .into_boxed());
+            }
+        }
+        (method, _) => {
+            return Err(OpaqueError::from_display(format!(
+                "socks5 proxy connector: unexpected method selected by proxy: {method:?}"
+            ))
+            .into_boxed());
+        }
+    }
+
+    Request::new(Command::Connect, destination)
+        .write_to(stream)
+        .await
+        .context("socks5 proxy connector: write connect request")?;
+
+    let reply = Reply::read_from(stream)
+        .await
+        .context("socks5 proxy connector: read connect reply")?;
+    if reply.reply != ReplyKind::Succeeded {
+        return Err(OpaqueError::from_display(format!(
+            "socks5 proxy connector: connect request failed: {:?}",
+            reply.reply
+        ))
+        .into_boxed());
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+/// A [`Layer`] which wraps the given service with a [`Socks5ProxyConnector`].
+///
+/// See [`Socks5ProxyConnector`] for more information.
+pub struct Socks5ProxyConnectorLayer {
+    required: bool,
+}
+
+impl Socks5ProxyConnectorLayer {
+    /// Create a new [`Socks5ProxyConnectorLayer`] which creates a [`Socks5ProxyConnector`]
+    /// which will only connect via a socks5 proxy in case the [`ProxyAddress`] is available
+    /// in the [`Context`].
+    pub fn optional() -> Self {
+        Self { required: false }
+    }
+
+    /// Create a new [`Socks5ProxyConnectorLayer`] which creates a [`Socks5ProxyConnector`]
+    /// which will always connect via a socks5 proxy, but fail in case the [`ProxyAddress`] is
+    /// not available in the [`Context`].
+    pub fn required() -> Self {
+        Self { required: true }
+    }
+}
+
+impl<S> Layer<S> for Socks5ProxyConnectorLayer {
+    type Service = Socks5ProxyConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Socks5ProxyConnector::new(inner, self.required)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::{dns::Dns, service::service_fn};
+    use rama_net::{user::Basic, Protocol};
+    use rama_tcp::client::Request as TcpRequest;
+    use std::convert::Infallible;
+    use tokio_test::io::{Builder, Mock};
+
+    fn mock_connector(
+        mock: Mock,
+    ) -> impl ConnectorService<(), TcpRequest, Connection: Stream + Unpin, Error: Into<BoxError>>
+    {
+        let mock = std::sync::Mutex::new(Some(mock));
+        service_fn(move |ctx: Context<()>, req: TcpRequest| {
+            let conn = mock.lock().unwrap().take().unwrap();
+            async move {
+                Ok::<_, Infallible>(EstablishedClientConnection {
+                    ctx,
+                    req,
+                    conn,
+                    addr: "127.0.0.1:1080".parse().unwrap(),
+                })
+            }
+        })
+    }
+
+    fn proxy_ctx(protocol: Protocol, credential: Option<ProxyCredential>) -> Context<()> {
+        let mut ctx = Context::default();
+        ctx.insert(ProxyAddress {
+            protocol: Some(protocol),
+            authority: "127.0.0.1:1080".parse().unwrap(),
+            credential,
+        });
+        ctx
+    }
+
+    #[tokio::test]
+    async fn test_socks5h_remote_dns() {
+        let conn = Builder::new()
+            .write(b"\x05\x01\x00")
+            .read(b"\x05\x00")
+            .write(b"\x05\x01\x00\x03\x0bexample.com\x01\xbb")
+            .read(b"\x05\x00\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        Socks5ProxyConnector::required(mock_connector(conn))
+            .connect(
+                proxy_ctx(Protocol::SOCKS5H, None),
+                TcpRequest::new("example.com:443".parse().unwrap()),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks5_local_dns_with_auth() {
+        let conn = Builder::new()
+            .write(b"\x05\x02\x00\x02")
+            .read(b"\x05\x02")
+            .write(b"\x01\x04john\x06secret")
+            .read(b"\x01\x00")
+            .write(b"\x05\x01\x00\x01\x7f\x00\x00\x02\x00\x50")
+            .read(b"\x05\x00\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let mut ctx = proxy_ctx(
+            Protocol::SOCKS5,
+            Some(ProxyCredential::Basic(Basic::new("john", "secret"))),
+        );
+        let mut dns = Dns::default();
+        dns.insert_overwrite("example.com", vec!["127.0.0.2".parse().unwrap()])
+            .unwrap();
+        *ctx.dns_mut() = dns;
+
+        Socks5ProxyConnector::optional(mock_connector(conn))
+            .connect(ctx, TcpRequest::new("example.com:80".parse().unwrap()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks5_connect_failure() {
+        let conn = Builder::new()
+            .write(b"\x05\x01\x00")
+            .read(b"\x05\x00")
+            .write(b"\x05\x01\x00\x01\x7f\x00\x00\x01\x00\x50")
+            .read(b"\x05\x04\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks5ProxyConnector::optional(mock_connector(conn))
+            .connect(
+                proxy_ctx(Protocol::SOCKS5, None),
+                TcpRequest::new("127.0.0.1:80".parse().unwrap()),
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("HostUnreachable"));
+    }
+
+    #[tokio::test]
+    async fn test_optional_ignores_http_proxy() {
+        let conn = Builder::new().build();
+
+        Socks5ProxyConnector::optional(mock_connector(conn))
+            .connect(
+                proxy_ctx(Protocol::HTTP, None),
+                TcpRequest::new("example.com:80".parse().unwrap()),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_required_without_proxy() {
+        let conn = Builder::new().build();
+
+        let err = Socks5ProxyConnector::required(mock_connector(conn))
+            .connect(
+                Context::default(),
+                TcpRequest::new("example.com:80".parse().unwrap()),
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("required"));
+    }
+}
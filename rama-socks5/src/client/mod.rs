@@ -0,0 +1,13 @@
+//! Socks5 and Socks4(a) Client support
+//!
+//! - Socks5: <https://datatracker.ietf.org/doc/html/rfc1928>
+//! - Socks4: <https://www.openssh.com/txt/socks4.protocol>
+//! - Socks4a: <https://www.openssh.com/txt/socks4a.protocol>
+
+mod connector;
+#[doc(inline)]
+pub use connector::{Socks5ProxyConnector, Socks5ProxyConnectorLayer};
+
+mod socks4;
+#[doc(inline)]
+pub use socks4::{Socks4ProxyConnector, Socks4ProxyConnectorLayer};
@@ -0,0 +1,384 @@
+use crate::proto::socks4::{Command, Reply, ReplyKind, Request};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Layer, Service,
+};
+use rama_net::{
+    address::{Authority, Host, ProxyAddress},
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+    transport::TryRefIntoTransportContext,
+    user::{ProxyCredential, UserId},
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, net::IpAddr};
+
+/// A connector which can be used to establish a connection over a Socks4 Proxy.
+///
+/// This behaviour is optional and only triggered in case there
+/// is a [`ProxyAddress`] found in the [`Context`] with
+/// the `socks4` or `socks4a` protocol.
+///
+/// For `socks4` the target domain is resolved locally to an IPv4 address,
+/// using the [`Dns`] resolver found in the [`Context`], while for `socks4a`
+/// the domain is sent as-is, leaving the resolution up to the proxy.
+///
+/// The username of a [`ProxyCredential::Basic`] credential is sent as the userid,
+/// its password is ignored as Socks4 has no support for passwords.
+///
+/// [`Dns`]: rama_core::dns::Dns
+pub struct Socks4ProxyConnector<S> {
+    inner: S,
+    required: bool,
+}
+
+impl<S: fmt::Debug> fmt::Debug for Socks4ProxyConnector<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks4ProxyConnector")
+            .field("inner", &self.inner)
+            .field("required", &self.required)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for Socks4ProxyConnector<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            required: self.required,
+        }
+    }
+}
+
+impl<S> Socks4ProxyConnector<S> {
+    fn new(inner: S, required: bool) -> Self {
+        Self { inner, required }
+    }
+
+    /// Create a new [`Socks4ProxyConnector`]
+    /// which will only connect via a socks4 proxy in case the [`ProxyAddress`] is available
+    /// in the [`Context`].
+    pub fn optional(inner: S) -> Self {
+        Self::new(inner, false)
+    }
+
+    /// Create a new [`Socks4ProxyConnector`]
+    /// which will always connect via a socks4 proxy, but fail in case the [`ProxyAddress`] is
+    /// not available in the [`Context`].
+    pub fn required(inner: S) -> Self {
+        Self::new(inner, true)
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S, State, Request> Service<State, Request> for Socks4ProxyConnector<S>
+where
+    S: ConnectorService<State, Request, Connection: Stream + Unpin, Error: Into<BoxError>>,
+    State: Send + Sync + 'static,
+    Request: TryRefIntoTransportContext<State, Error: Into<BoxError> + Send + Sync + 'static>
+        + Send
+        + 'static,
+{
+    type Response = EstablishedClientConnection<S::Connection, State, Request>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let address = ctx
+            .get::<ProxyAddress>()
+            .filter(|address| is_socks4_proxy(address))
+            .cloned();
+
+        let transport_ctx = ctx
+            .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
+            .map_err(|err| {
+                OpaqueError::from_boxed(err.into())
+                    .context("socks4 proxy connector: get transport context")
+            })?
+            .clone();
+
+        let established_conn =
+            self.inner
+                .connect(ctx, req)
+                .await
+                .map_err(|err| match address.as_ref() {
+                    Some(address) => OpaqueError::from_boxed(err.into())
+                        .context(format!("establish connection to proxy {}", address)),
+                    None => {
+                        OpaqueError::from_boxed(err.into()).context("establish connection target")
+                    }
+                })?;
+
+        // return early in case we did not use a proxy
+        let address = match address {
+            Some(address) => address,
+            None => {
+                return if self.required {
+                    Err("socks4 proxy required but none is defined".into())
+                } else {
+                    tracing::trace!("socks4 proxy connector: no proxy required or set: proceed with direct connection");
+                    Ok(established_conn)
+                };
+            }
+        };
+        // and do the handshake otherwise...
+
+        let EstablishedClientConnection {
+            ctx,
+            req,
+            mut conn,
+            addr,
+        } = established_conn;
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            "socks4 proxy connector: connected to proxy",
+        );
+
+        let user_id = match address.credential {
+            Some(ProxyCredential::Basic(basic)) => {
+                Some(UserId::Username(basic.username().to_owned()))
+            }
+            Some(ProxyCredential::Bearer(_)) => {
+                return Err(OpaqueError::from_display(
+                    "socks4 proxy connector: bearer credentials are not supported",
+                )
+                .into_boxed());
+            }
+            None => None,
+        };
+
+        let remote_dns = address
+            .protocol
+            .as_ref()
+            .map(|p| p.is_socks4a())
+            .unwrap_or_default();
+        let destination = if remote_dns {
+            transport_ctx.authority.clone()
+        } else {
+            resolve_authority(&ctx, transport_ctx.authority.clone()).await?
+        };
+
+        handshake(&mut conn, destination, user_id).await?;
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            "socks4 proxy connector: connected to proxy: tunnel established",
+        );
+        Ok(EstablishedClientConnection {
+            ctx,
+            req,
+            conn,
+            addr,
+        })
+    }
+}
+
+fn is_socks4_proxy(address: &ProxyAddress) -> bool {
+    address
+        .protocol
+        .as_ref()
+        .map(|p| p.is_socks4() || p.is_socks4a())
+        .unwrap_or_default()
+}
+
+/// Resolve the domain of the given [`Authority`] (if any) to an IPv4 address,
+/// as the Socks4 protocol has no support for IPv6 nor domain names.
+async fn resolve_authority<State>(
+    ctx: &Context<State>,
+    authority: Authority,
+) -> Result<Authority, OpaqueError> {
+    let (host, port) = authority.into_parts();
+    let domain = match host {
+        Host::Name(domain) => domain,
+        Host::Address(IpAddr::V4(ip)) => return Ok((ip, port).into()),
+        Host::Address(IpAddr::V6(ip)) => {
+            return Err(OpaqueError::from_display(format!(
+                "socks4 proxy connector: ipv6 target not supported: {ip}"
+            )));
+        }
+    };
+
+    let ip = ctx
+        .dns()
+        .ipv4_lookup(domain.clone())
+        .await
+        .context("socks4 proxy connector: resolve target domain")?
+        .next()
+        .ok_or_else(|| {
+            OpaqueError::from_display(format!(
+                "socks4 proxy connector: no ipv4 address found for {domain}"
+            ))
+        })?;
+
+    Ok((ip, port).into())
+}
+
+async fn handshake<S: Stream + Unpin>(
+    stream: &mut S,
+    destination: Authority,
+    user_id: Option<UserId>,
+) -> Result<(), BoxError> {
+    Request::new(Command::Connect, destination, user_id)
+        .write_to(stream)
+        .await
+        .context("socks4 proxy connector: write connect request")?;
+
+    let reply = Reply::read_from(stream)
+        .await
+        .context("socks4 proxy connector: read connect reply")?;
+    if reply.reply != ReplyKind::Granted {
+        return Err(OpaqueError::from_display(format!(
+            "socks4 proxy connector: connect request failed: {:?}",
+            reply.reply
+        ))
+        .into_boxed());
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+/// A [`Layer`] which wraps the given service with a [`Socks4ProxyConnector`].
+///
+/// See [`Socks4ProxyConnector`] for more information.
+pub struct Socks4ProxyConnectorLayer {
+    required: bool,
+}
+
+impl Socks4ProxyConnectorLayer {
+    /// Create a new [`Socks4ProxyConnectorLayer`] which creates a [`Socks4ProxyConnector`]
+    /// which will only connect via a socks4 proxy in case the [`ProxyAddress`] is available
+    /// in the [`Context`].
+    pub fn optional() -> Self {
+        Self { required: false }
+    }
+
+    /// Create a new [`Socks4ProxyConnectorLayer`] which creates a [`Socks4ProxyConnector`]
+    /// which will always connect via a socks4 proxy, but fail in case the [`ProxyAddress`] is
+    /// not available in the [`Context`].
+    pub fn required() -> Self {
+        Self { required: true }
+    }
+}
+
+impl<S> Layer<S> for Socks4ProxyConnectorLayer {
+    type Service = Socks4ProxyConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Socks4ProxyConnector::new(inner, self.required)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::{dns::Dns, service::service_fn};
+    use rama_net::{user::Basic, Protocol};
+    use rama_tcp::client::Request as TcpRequest;
+    use std::convert::Infallible;
+    use tokio_test::io::{Builder, Mock};
+
+    fn mock_connector(
+        mock: Mock,
+    ) -> impl ConnectorService<(), TcpRequest, Connection: Stream + Unpin, Error: Into<BoxError>>
+    {
+        let mock = std::sync::Mutex::new(Some(mock));
+        service_fn(move |ctx: Context<()>, req: TcpRequest| {
+            let conn = mock.lock().unwrap().take().unwrap();
+            async move {
+                Ok::<_, Infallible>(EstablishedClientConnection {
+                    ctx,
+                    req,
+                    conn,
+                    addr: "127.0.0.1:1080".parse().unwrap(),
+                })
+            }
+        })
+    }
+
+    fn proxy_ctx(protocol: Protocol, credential: Option<ProxyCredential>) -> Context<()> {
+        let mut ctx = Context::default();
+        ctx.insert(ProxyAddress {
+            protocol: Some(protocol),
+            authority: "127.0.0.1:1080".parse().unwrap(),
+            credential,
+        });
+        ctx
+    }
+
+    #[tokio::test]
+    async fn test_socks4a_remote_dns_with_user_id() {
+        let conn = Builder::new()
+            .write(b"\x04\x01\x01\xbb\x00\x00\x00\x01john\x00example.com\x00")
+            .read(b"\x00\x5a\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        Socks4ProxyConnector::required(mock_connector(conn))
+            .connect(
+                proxy_ctx(
+                    Protocol::SOCKS4A,
+                    Some(ProxyCredential::Basic(Basic::new("john", "secret"))),
+                ),
+                TcpRequest::new("example.com:443".parse().unwrap()),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks4_local_dns() {
+        let conn = Builder::new()
+            .write(b"\x04\x01\x00\x50\x7f\x00\x00\x02\x00")
+            .read(b"\x00\x5a\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let mut ctx = proxy_ctx(Protocol::SOCKS4, None);
+        let mut dns = Dns::default();
+        dns.insert_overwrite("example.com", vec!["127.0.0.2".parse().unwrap()])
+            .unwrap();
+        *ctx.dns_mut() = dns;
+
+        Socks4ProxyConnector::optional(mock_connector(conn))
+            .connect(ctx, TcpRequest::new("example.com:80".parse().unwrap()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks4_connect_rejected() {
+        let conn = Builder::new()
+            .write(b"\x04\x01\x00\x50\x7f\x00\x00\x01\x00")
+            .read(b"\x00\x5b\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks4ProxyConnector::optional(mock_connector(conn))
+            .connect(
+                proxy_ctx(Protocol::SOCKS4, None),
+                TcpRequest::new("127.0.0.1:80".parse().unwrap()),
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Rejected"));
+    }
+
+    #[tokio::test]
+    async fn test_optional_ignores_socks5_proxy() {
+        let conn = Builder::new().build();
+
+        Socks4ProxyConnector::optional(mock_connector(conn))
+            .connect(
+                proxy_ctx(Protocol::SOCKS5, None),
+                TcpRequest::new("example.com:80".parse().unwrap()),
+            )
+            .await
+            .unwrap();
+    }
+}
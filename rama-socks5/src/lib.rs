@@ -1,4 +1,8 @@
-//! SOCKS5 support for Rama.
+//! SOCKS5 and SOCKS4(a) support for Rama.
+//!
+//! - Socks5: <https://datatracker.ietf.org/doc/html/rfc1928>
+//! - Socks4: <https://www.openssh.com/txt/socks4.protocol>
+//! - Socks4a: <https://www.openssh.com/txt/socks4a.protocol>
 //!
 //! # Rama
 //!
@@ -54,5 +58,6 @@
 #![cfg_attr(test, allow(clippy::float_cmp))]
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
-/// TODO
-pub const PLACEHOLDER: bool = true;
+pub mod client;
+pub mod proto;
+pub mod server;
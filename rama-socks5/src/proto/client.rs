@@ -0,0 +1,227 @@
+//! Socks5 messages sent by the client.
+
+use super::{
+    read_authority, read_expected_u8, write_authority, Command, ProtocolError, SocksMethod,
+    SOCKS5_VERSION, USERNAME_PASSWORD_VERSION,
+};
+use rama_core::error::OpaqueError;
+use rama_net::{address::Authority, user::Basic};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Initial message sent by the client,
+/// offering the authentication methods it supports.
+///
+/// ```plain
+/// +----+----------+----------+
+/// |VER | NMETHODS | METHODS  |
+/// +----+----------+----------+
+/// | 1  |    1     | 1 to 255 |
+/// +----+----------+----------+
+/// ```
+pub struct Header {
+    /// Authentication methods supported by the client.
+    pub methods: Vec<SocksMethod>,
+}
+
+impl Header {
+    /// Create a new [`Header`] for the given methods.
+    pub fn new(methods: impl Into<Vec<SocksMethod>>) -> Self {
+        Self {
+            methods: methods.into(),
+        }
+    }
+
+    /// Read the [`Header`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, SOCKS5_VERSION).await?;
+        let n = r.read_u8().await? as usize;
+        let mut raw = vec![0u8; n];
+        r.read_exact(&mut raw).await?;
+        Ok(Self {
+            methods: raw.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Write the [`Header`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let n: u8 = self.methods.len().try_into().map_err(|_| {
+            ProtocolError::InvalidData(OpaqueError::from_display("too many socks5 methods"))
+        })?;
+        let mut buf = Vec::with_capacity(2 + self.methods.len());
+        buf.push(SOCKS5_VERSION);
+        buf.push(n);
+        buf.extend(self.methods.iter().copied().map(u8::from));
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Username/password authentication request sent by the client,
+/// in case the [`SocksMethod::UsernamePassword`] method was selected by the server.
+///
+/// ```plain
+/// +----+------+----------+------+----------+
+/// |VER | ULEN |  UNAME   | PLEN |  PASSWD  |
+/// +----+------+----------+------+----------+
+/// | 1  |  1   | 1 to 255 |  1   | 1 to 255 |
+/// +----+------+----------+------+----------+
+/// ```
+pub struct UsernamePasswordRequest {
+    /// Credentials of the client.
+    pub basic: Basic,
+}
+
+impl UsernamePasswordRequest {
+    /// Create a new [`UsernamePasswordRequest`] for the given credentials.
+    pub fn new(basic: Basic) -> Self {
+        Self { basic }
+    }
+
+    /// Read the [`UsernamePasswordRequest`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, USERNAME_PASSWORD_VERSION).await?;
+
+        let n = r.read_u8().await? as usize;
+        let mut username = vec![0u8; n];
+        r.read_exact(&mut username).await?;
+        let username = String::from_utf8(username)
+            .map_err(|err| ProtocolError::InvalidData(OpaqueError::from_std(err)))?;
+
+        let n = r.read_u8().await? as usize;
+        let mut password = vec![0u8; n];
+        r.read_exact(&mut password).await?;
+        let password = String::from_utf8(password)
+            .map_err(|err| ProtocolError::InvalidData(OpaqueError::from_std(err)))?;
+
+        Ok(Self {
+            basic: Basic::new(username, password),
+        })
+    }
+
+    /// Write the [`UsernamePasswordRequest`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let username = self.basic.username().as_bytes();
+        let password = self.basic.password().as_bytes();
+
+        let username_len: u8 = username.len().try_into().map_err(|_| {
+            ProtocolError::InvalidData(OpaqueError::from_display("socks5 username too long"))
+        })?;
+        let password_len: u8 = password.len().try_into().map_err(|_| {
+            ProtocolError::InvalidData(OpaqueError::from_display("socks5 password too long"))
+        })?;
+
+        let mut buf = Vec::with_capacity(3 + username.len() + password.len());
+        buf.push(USERNAME_PASSWORD_VERSION);
+        buf.push(username_len);
+        buf.extend_from_slice(username);
+        buf.push(password_len);
+        buf.extend_from_slice(password);
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Request sent by the client once the method-dependent sub-negotiation completed.
+///
+/// ```plain
+/// +----+-----+-------+------+----------+----------+
+/// |VER | CMD |  RSV  | ATYP | DST.ADDR | DST.PORT |
+/// +----+-----+-------+------+----------+----------+
+/// | 1  |  1  | X'00' |  1   | Variable |    2     |
+/// +----+-----+-------+------+----------+----------+
+/// ```
+pub struct Request {
+    /// Command requested by the client.
+    pub command: Command,
+    /// Destination requested by the client.
+    pub destination: Authority,
+}
+
+impl Request {
+    /// Create a new [`Request`].
+    pub fn new(command: Command, destination: Authority) -> Self {
+        Self {
+            command,
+            destination,
+        }
+    }
+
+    /// Read the [`Request`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, SOCKS5_VERSION).await?;
+        let command = r.read_u8().await?.into();
+        read_expected_u8(r, 2, 0x00).await?;
+        let destination = read_authority(r, 3).await?;
+        Ok(Self {
+            command,
+            destination,
+        })
+    }
+
+    /// Write the [`Request`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let mut buf = Vec::with_capacity(22);
+        buf.push(SOCKS5_VERSION);
+        buf.push(self.command.into());
+        buf.push(0x00);
+        write_authority(&mut buf, &self.destination)?;
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_header_roundtrip() {
+        let header = Header::new([
+            SocksMethod::NoAuthenticationRequired,
+            SocksMethod::UsernamePassword,
+        ]);
+        let mut buf = Vec::new();
+        header.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, [0x05, 0x02, 0x00, 0x02]);
+        assert_eq!(header, Header::read_from(&mut &buf[..]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_header_invalid_version() {
+        let buf = [0x04, 0x01, 0x00];
+        let err = Header::read_from(&mut &buf[..]).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ProtocolError::UnexpectedByte { pos: 0, byte: 0x04 }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_username_password_roundtrip() {
+        let request = UsernamePasswordRequest::new(Basic::new("john", "secret"));
+        let mut buf = Vec::new();
+        request.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, b"\x01\x04john\x06secret");
+        assert_eq!(
+            request,
+            UsernamePasswordRequest::read_from(&mut &buf[..])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_roundtrip() {
+        let request = Request::new(
+            Command::Connect,
+            Authority::try_from("example.com:443").unwrap(),
+        );
+        let mut buf = Vec::new();
+        request.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, b"\x05\x01\x00\x03\x0bexample.com\x01\xbb");
+        assert_eq!(request, Request::read_from(&mut &buf[..]).await.unwrap());
+    }
+}
@@ -0,0 +1,294 @@
+//! Socks5 Protocol types and (de)serialization utilities.
+//!
+//! As defined in [RFC 1928] and [RFC 1929] (username/password authentication).
+//! The legacy Socks4 and Socks4a protocols can be found in the [`socks4`] module.
+//!
+//! [RFC 1928]: https://datatracker.ietf.org/doc/html/rfc1928
+//! [RFC 1929]: https://datatracker.ietf.org/doc/html/rfc1929
+
+use rama_core::error::OpaqueError;
+use rama_net::address::{Authority, Domain, Host};
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub mod client;
+pub mod server;
+pub mod udp;
+
+macro_rules! byte_enum {
+    (
+        $(#[$m:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$vm:meta])*
+                $variant:ident => $value:literal,
+            )+
+        }
+    ) => {
+        $(#[$m])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $(
+                $(#[$vm])*
+                $variant,
+            )+
+            /// Unknown (or unsupported) value.
+            Unknown(u8),
+        }
+
+        impl From<u8> for $name {
+            fn from(value: u8) -> Self {
+                match value {
+                    $($value => Self::$variant,)+
+                    other => Self::Unknown(other),
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)+
+                    $name::Unknown(other) => other,
+                }
+            }
+        }
+    };
+}
+
+pub mod socks4;
+
+/// Version byte used by the Socks5 protocol.
+pub const SOCKS5_VERSION: u8 = 0x05;
+
+/// Version byte used by the username/password sub-negotiation.
+///
+/// As defined in [RFC 1929](https://datatracker.ietf.org/doc/html/rfc1929).
+pub const USERNAME_PASSWORD_VERSION: u8 = 0x01;
+
+byte_enum! {
+    /// Authentication method that can be negotiated between
+    /// a Socks5 client and server.
+    pub enum SocksMethod {
+        /// No authentication required.
+        NoAuthenticationRequired => 0x00,
+        /// GSSAPI authentication, as defined in RFC 1961.
+        GSSAPI => 0x01,
+        /// Username/password authentication, as defined in RFC 1929.
+        UsernamePassword => 0x02,
+        /// Returned by the server in case none of the offered methods are acceptable.
+        NoAcceptableMethods => 0xff,
+    }
+}
+
+byte_enum! {
+    /// Command requested by the Socks5 client.
+    pub enum Command {
+        /// Establish a TCP/IP stream connection.
+        Connect => 0x01,
+        /// Establish a TCP/IP port binding.
+        Bind => 0x02,
+        /// Associate a UDP port.
+        UdpAssociate => 0x03,
+    }
+}
+
+byte_enum! {
+    /// Type of the address encoded in a Socks5 request or reply.
+    pub enum AddressType {
+        /// IPv4 address (4 bytes).
+        IPv4 => 0x01,
+        /// Fully qualified domain name (length prefixed).
+        DomainName => 0x03,
+        /// IPv6 address (16 bytes).
+        IPv6 => 0x04,
+    }
+}
+
+byte_enum! {
+    /// Reply code sent by the Socks5 server in response to a [`Command`].
+    pub enum ReplyKind {
+        /// Succeeded.
+        Succeeded => 0x00,
+        /// General SOCKS server failure.
+        GeneralServerFailure => 0x01,
+        /// Connection not allowed by ruleset.
+        ConnectionNotAllowed => 0x02,
+        /// Network unreachable.
+        NetworkUnreachable => 0x03,
+        /// Host unreachable.
+        HostUnreachable => 0x04,
+        /// Connection refused.
+        ConnectionRefused => 0x05,
+        /// TTL expired.
+        TtlExpired => 0x06,
+        /// Command not supported.
+        CommandNotSupported => 0x07,
+        /// Address type not supported.
+        AddressTypeNotSupported => 0x08,
+    }
+}
+
+#[derive(Debug)]
+/// Error that can be returned while (de)serializing Socks5 protocol messages.
+pub enum ProtocolError {
+    /// I/O error while reading or writing the message.
+    IO(io::Error),
+    /// Unexpected byte encountered at the given position.
+    UnexpectedByte {
+        /// Position (in bytes) within the message.
+        pos: usize,
+        /// Byte that was encountered.
+        byte: u8,
+    },
+    /// Message contained data that could not be interpreted.
+    InvalidData(OpaqueError),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::IO(err) => write!(f, "socks5 protocol: i/o error: {err}"),
+            ProtocolError::UnexpectedByte { pos, byte } => write!(
+                f,
+                "socks5 protocol: unexpected byte {byte:#04x} at position {pos}"
+            ),
+            ProtocolError::InvalidData(err) => write!(f, "socks5 protocol: invalid data: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtocolError::IO(err) => Some(err),
+            ProtocolError::UnexpectedByte { .. } => None,
+            ProtocolError::InvalidData(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for ProtocolError {
+    fn from(value: io::Error) -> Self {
+        ProtocolError::IO(value)
+    }
+}
+
+/// Read a Socks5 encoded [`Authority`], starting with the [`AddressType`] byte,
+/// where `pos` is the position of that byte within the message (used for errors).
+pub(crate) async fn read_authority<R: AsyncRead + Unpin>(
+    r: &mut R,
+    pos: usize,
+) -> Result<Authority, ProtocolError> {
+    let host = match AddressType::from(r.read_u8().await?) {
+        AddressType::IPv4 => {
+            let mut octets = [0u8; 4];
+            r.read_exact(&mut octets).await?;
+            Host::Address(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        AddressType::IPv6 => {
+            let mut octets = [0u8; 16];
+            r.read_exact(&mut octets).await?;
+            Host::Address(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        AddressType::DomainName => {
+            let len = r.read_u8().await? as usize;
+            let mut raw = vec![0u8; len];
+            r.read_exact(&mut raw).await?;
+            // some clients encode IP addresses as "domain" addresses,
+            // which is why we go via Host instead of Domain directly
+            Host::try_from(raw).map_err(ProtocolError::InvalidData)?
+        }
+        AddressType::Unknown(byte) => return Err(ProtocolError::UnexpectedByte { pos, byte }),
+    };
+    let port = r.read_u16().await?;
+    Ok(Authority::new(host, port))
+}
+
+/// Write a Socks5 encoded [`Authority`], starting with the [`AddressType`] byte.
+pub(crate) fn write_authority(
+    buf: &mut Vec<u8>,
+    authority: &Authority,
+) -> Result<(), ProtocolError> {
+    match authority.host() {
+        Host::Address(IpAddr::V4(ip)) => {
+            buf.push(AddressType::IPv4.into());
+            buf.extend_from_slice(&ip.octets());
+        }
+        Host::Address(IpAddr::V6(ip)) => {
+            buf.push(AddressType::IPv6.into());
+            buf.extend_from_slice(&ip.octets());
+        }
+        Host::Name(domain) => write_domain(buf, domain)?,
+    }
+    buf.extend_from_slice(&authority.port().to_be_bytes());
+    Ok(())
+}
+
+fn write_domain(buf: &mut Vec<u8>, domain: &Domain) -> Result<(), ProtocolError> {
+    let raw = domain.as_str().as_bytes();
+    let len: u8 = raw.len().try_into().map_err(|_| {
+        ProtocolError::InvalidData(OpaqueError::from_display(
+            "domain name too long to be socks5 encoded",
+        ))
+    })?;
+    buf.push(AddressType::DomainName.into());
+    buf.push(len);
+    buf.extend_from_slice(raw);
+    Ok(())
+}
+
+/// Read a single byte and validate that it matches the expected value.
+pub(crate) async fn read_expected_u8<R: AsyncRead + Unpin>(
+    r: &mut R,
+    pos: usize,
+    expected: u8,
+) -> Result<(), ProtocolError> {
+    let byte = r.read_u8().await?;
+    if byte != expected {
+        return Err(ProtocolError::UnexpectedByte { pos, byte });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_authority_roundtrip() {
+        for authority in [
+            Authority::from(([127, 0, 0, 1], 8080)),
+            Authority::from(([0u8; 16], 443)),
+            Authority::new(Host::Name(Domain::from_static("example.com")), 80),
+        ] {
+            let mut buf = Vec::new();
+            write_authority(&mut buf, &authority).unwrap();
+            let result = read_authority(&mut &buf[..], 0).await.unwrap();
+            assert_eq!(authority, result);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authority_unknown_address_type() {
+        let buf = [0x02, 0x00, 0x50];
+        let err = read_authority(&mut &buf[..], 3).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ProtocolError::UnexpectedByte { pos: 3, byte: 0x02 }
+        ));
+    }
+
+    #[test]
+    fn test_byte_enum_roundtrip() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(byte, u8::from(ReplyKind::from(byte)));
+            assert_eq!(byte, u8::from(SocksMethod::from(byte)));
+        }
+        assert_eq!(Command::from(0x03), Command::UdpAssociate);
+        assert_eq!(Command::from(0x04), Command::Unknown(0x04));
+    }
+}
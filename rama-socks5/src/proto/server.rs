@@ -0,0 +1,186 @@
+//! Socks5 messages sent by the server.
+
+use super::{
+    read_authority, read_expected_u8, write_authority, ProtocolError, ReplyKind, SocksMethod,
+    SOCKS5_VERSION, USERNAME_PASSWORD_VERSION,
+};
+use rama_net::address::Authority;
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Message sent by the server in response to the client [`Header`],
+/// selecting one of the offered authentication methods.
+///
+/// ```plain
+/// +----+--------+
+/// |VER | METHOD |
+/// +----+--------+
+/// | 1  |   1    |
+/// +----+--------+
+/// ```
+///
+/// [`Header`]: super::client::Header
+pub struct Header {
+    /// Method selected by the server.
+    pub method: SocksMethod,
+}
+
+impl Header {
+    /// Create a new [`Header`] for the given method.
+    pub fn new(method: SocksMethod) -> Self {
+        Self { method }
+    }
+
+    /// Read the [`Header`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, SOCKS5_VERSION).await?;
+        let method = r.read_u8().await?.into();
+        Ok(Self { method })
+    }
+
+    /// Write the [`Header`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        w.write_all(&[SOCKS5_VERSION, self.method.into()]).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Response sent by the server to a [`UsernamePasswordRequest`].
+///
+/// ```plain
+/// +----+--------+
+/// |VER | STATUS |
+/// +----+--------+
+/// | 1  |   1    |
+/// +----+--------+
+/// ```
+///
+/// [`UsernamePasswordRequest`]: super::client::UsernamePasswordRequest
+pub struct UsernamePasswordResponse {
+    /// Status of the authentication, `0x00` means success,
+    /// any other value indicates failure.
+    pub status: u8,
+}
+
+impl UsernamePasswordResponse {
+    /// Create a new [`UsernamePasswordResponse`] indicating success.
+    pub fn success() -> Self {
+        Self { status: 0x00 }
+    }
+
+    /// Create a new [`UsernamePasswordResponse`] indicating failure.
+    pub fn failure() -> Self {
+        Self { status: 0x01 }
+    }
+
+    /// Returns `true` if this response indicates successful authentication.
+    pub fn is_success(&self) -> bool {
+        self.status == 0x00
+    }
+
+    /// Read the [`UsernamePasswordResponse`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, USERNAME_PASSWORD_VERSION).await?;
+        let status = r.read_u8().await?;
+        Ok(Self { status })
+    }
+
+    /// Write the [`UsernamePasswordResponse`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        w.write_all(&[USERNAME_PASSWORD_VERSION, self.status])
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Reply sent by the server in response to a client [`Request`].
+///
+/// ```plain
+/// +----+-----+-------+------+----------+----------+
+/// |VER | REP |  RSV  | ATYP | BND.ADDR | BND.PORT |
+/// +----+-----+-------+------+----------+----------+
+/// | 1  |  1  | X'00' |  1   | Variable |    2     |
+/// +----+-----+-------+------+----------+----------+
+/// ```
+///
+/// [`Request`]: super::client::Request
+pub struct Reply {
+    /// Reply code.
+    pub reply: ReplyKind,
+    /// Address bound by the server.
+    pub bind_address: Authority,
+}
+
+impl Reply {
+    /// Create a new [`Reply`] with the given [`ReplyKind`] and bind address.
+    pub fn new(reply: ReplyKind, bind_address: impl Into<Authority>) -> Self {
+        Self {
+            reply,
+            bind_address: bind_address.into(),
+        }
+    }
+
+    /// Create a new error [`Reply`] for the given [`ReplyKind`],
+    /// using an unspecified bind address.
+    pub fn error(reply: ReplyKind) -> Self {
+        Self::new(reply, SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+    }
+
+    /// Read the [`Reply`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, SOCKS5_VERSION).await?;
+        let reply = r.read_u8().await?.into();
+        read_expected_u8(r, 2, 0x00).await?;
+        let bind_address = read_authority(r, 3).await?;
+        Ok(Self {
+            reply,
+            bind_address,
+        })
+    }
+
+    /// Write the [`Reply`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let mut buf = Vec::with_capacity(22);
+        buf.push(SOCKS5_VERSION);
+        buf.push(self.reply.into());
+        buf.push(0x00);
+        write_authority(&mut buf, &self.bind_address)?;
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reply_roundtrip() {
+        let reply = Reply::new(
+            ReplyKind::Succeeded,
+            "127.0.0.1:1080".parse::<SocketAddr>().unwrap(),
+        );
+        let mut buf = Vec::new();
+        reply.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, [0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0x04, 0x38]);
+        assert_eq!(reply, Reply::read_from(&mut &buf[..]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_username_password_response() {
+        let buf = [0x01, 0x00];
+        let response = UsernamePasswordResponse::read_from(&mut &buf[..])
+            .await
+            .unwrap();
+        assert!(response.is_success());
+
+        let buf = [0x01, 0x01];
+        let response = UsernamePasswordResponse::read_from(&mut &buf[..])
+            .await
+            .unwrap();
+        assert!(!response.is_success());
+    }
+}
@@ -0,0 +1,297 @@
+//! Socks4 and Socks4a protocol messages.
+//!
+//! - Socks4: <https://www.openssh.com/txt/socks4.protocol>
+//! - Socks4a: <https://www.openssh.com/txt/socks4a.protocol>
+
+use super::{read_expected_u8, ProtocolError};
+use rama_core::error::OpaqueError;
+use rama_net::{
+    address::{Authority, Host},
+    user::UserId,
+};
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Version byte used by the Socks4 (and Socks4a) protocol.
+pub const SOCKS4_VERSION: u8 = 0x04;
+
+/// Version byte used in the reply sent by a Socks4 server.
+pub const SOCKS4_REPLY_VERSION: u8 = 0x00;
+
+/// Maximum length accepted for the null-terminated userid and domain fields.
+const MAX_FIELD_LEN: usize = 255;
+
+byte_enum! {
+    /// Command requested by the Socks4 client.
+    pub enum Command {
+        /// Establish a TCP/IP stream connection.
+        Connect => 0x01,
+        /// Establish a TCP/IP port binding.
+        Bind => 0x02,
+    }
+}
+
+byte_enum! {
+    /// Reply code sent by the Socks4 server in response to a [`Command`].
+    pub enum ReplyKind {
+        /// Request granted.
+        Granted => 0x5a,
+        /// Request rejected or failed.
+        Rejected => 0x5b,
+        /// Request rejected because the server cannot connect to identd on the client.
+        IdentdUnreachable => 0x5c,
+        /// Request rejected because the client program and identd report different user-ids.
+        IdentdMismatch => 0x5d,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Request sent by the Socks4 client.
+///
+/// ```plain
+/// +----+----+----+----+----+----+----+----+----+----+....+----+
+/// | VN | CD | DSTPORT |      DSTIP        | USERID       |NULL|
+/// +----+----+----+----+----+----+----+----+----+----+....+----+
+///    1    1      2              4           variable       1
+/// ```
+///
+/// In case of Socks4a the `DSTIP` is set to `0.0.0.x` (with `x` non-zero),
+/// and the null-terminated domain name follows the `USERID`.
+pub struct Request {
+    /// Command requested by the client.
+    pub command: Command,
+    /// Destination requested by the client,
+    /// which is a domain name only for Socks4a requests.
+    pub destination: Authority,
+    /// Userid of the client, if any.
+    ///
+    /// Utf-8 userids are mapped to [`UserId::Username`],
+    /// any other to [`UserId::Token`].
+    pub user_id: Option<UserId>,
+}
+
+impl Request {
+    /// Create a new [`Request`].
+    pub fn new(command: Command, destination: Authority, user_id: Option<UserId>) -> Self {
+        Self {
+            command,
+            destination,
+            user_id,
+        }
+    }
+
+    /// Returns `true` if this is a Socks4a request,
+    /// meaning the destination is a domain name to be resolved by the server.
+    pub fn is_socks4a(&self) -> bool {
+        matches!(self.destination.host(), Host::Name(_))
+    }
+
+    /// Read the [`Request`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, SOCKS4_VERSION).await?;
+        let command = r.read_u8().await?.into();
+        let port = r.read_u16().await?;
+        let mut octets = [0u8; 4];
+        r.read_exact(&mut octets).await?;
+
+        let user_id = read_nul_terminated(r).await?;
+        let user_id = if user_id.is_empty() {
+            None
+        } else {
+            Some(match String::from_utf8(user_id) {
+                Ok(username) => UserId::Username(username),
+                Err(err) => UserId::Token(err.into_bytes()),
+            })
+        };
+
+        let host = if is_socks4a_ip(octets) {
+            let domain = read_nul_terminated(r).await?;
+            Host::try_from(domain).map_err(ProtocolError::InvalidData)?
+        } else {
+            Host::Address(IpAddr::V4(Ipv4Addr::from(octets)))
+        };
+
+        Ok(Self {
+            command,
+            destination: Authority::new(host, port),
+            user_id,
+        })
+    }
+
+    /// Write the [`Request`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let mut buf = Vec::with_capacity(32);
+        buf.push(SOCKS4_VERSION);
+        buf.push(self.command.into());
+        buf.extend_from_slice(&self.destination.port().to_be_bytes());
+
+        let domain = match self.destination.host() {
+            Host::Address(IpAddr::V4(ip)) => {
+                buf.extend_from_slice(&ip.octets());
+                None
+            }
+            Host::Address(IpAddr::V6(_)) => {
+                return Err(ProtocolError::InvalidData(OpaqueError::from_display(
+                    "socks4 does not support ipv6 destinations",
+                )));
+            }
+            Host::Name(domain) => {
+                buf.extend_from_slice(&[0, 0, 0, 1]);
+                Some(domain)
+            }
+        };
+
+        match &self.user_id {
+            Some(UserId::Username(username)) => {
+                write_nul_terminated(&mut buf, username.as_bytes())?
+            }
+            Some(UserId::Token(token)) => write_nul_terminated(&mut buf, token)?,
+            None => buf.push(0),
+        }
+        if let Some(domain) = domain {
+            write_nul_terminated(&mut buf, domain.as_str().as_bytes())?;
+        }
+
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Reply sent by the Socks4 server in response to a client [`Request`].
+///
+/// ```plain
+/// +----+----+----+----+----+----+----+----+
+/// | VN | CD | DSTPORT |      DSTIP        |
+/// +----+----+----+----+----+----+----+----+
+///    1    1      2              4
+/// ```
+pub struct Reply {
+    /// Reply code.
+    pub reply: ReplyKind,
+    /// Address bound by the server,
+    /// ignored by clients for the `CONNECT` command.
+    pub bind_address: SocketAddrV4,
+}
+
+impl Reply {
+    /// Create a new [`Reply`] with the given [`ReplyKind`] and bind address.
+    pub fn new(reply: ReplyKind, bind_address: SocketAddrV4) -> Self {
+        Self {
+            reply,
+            bind_address,
+        }
+    }
+
+    /// Create a new [`Reply`] for the given [`ReplyKind`],
+    /// using an unspecified bind address.
+    pub fn unspecified(reply: ReplyKind) -> Self {
+        Self::new(reply, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))
+    }
+
+    /// Read the [`Reply`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, SOCKS4_REPLY_VERSION).await?;
+        let reply = r.read_u8().await?.into();
+        let port = r.read_u16().await?;
+        let mut octets = [0u8; 4];
+        r.read_exact(&mut octets).await?;
+        Ok(Self {
+            reply,
+            bind_address: SocketAddrV4::new(octets.into(), port),
+        })
+    }
+
+    /// Write the [`Reply`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let mut buf = [0u8; 8];
+        buf[0] = SOCKS4_REPLY_VERSION;
+        buf[1] = self.reply.into();
+        buf[2..4].copy_from_slice(&self.bind_address.port().to_be_bytes());
+        buf[4..].copy_from_slice(&self.bind_address.ip().octets());
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+/// Socks4a indicates a domain name follows by using an IP address of the form `0.0.0.x`,
+/// where `x` is non-zero.
+fn is_socks4a_ip(octets: [u8; 4]) -> bool {
+    octets[..3] == [0, 0, 0] && octets[3] != 0
+}
+
+async fn read_nul_terminated<R: AsyncRead + Unpin>(r: &mut R) -> Result<Vec<u8>, ProtocolError> {
+    let mut raw = Vec::new();
+    loop {
+        let byte = r.read_u8().await?;
+        if byte == 0 {
+            return Ok(raw);
+        }
+        if raw.len() >= MAX_FIELD_LEN {
+            return Err(ProtocolError::InvalidData(OpaqueError::from_display(
+                "socks4 null-terminated field too long",
+            )));
+        }
+        raw.push(byte);
+    }
+}
+
+fn write_nul_terminated(buf: &mut Vec<u8>, value: &[u8]) -> Result<(), ProtocolError> {
+    if value.len() > MAX_FIELD_LEN || value.contains(&0) {
+        return Err(ProtocolError::InvalidData(OpaqueError::from_display(
+            "invalid socks4 null-terminated field",
+        )));
+    }
+    buf.extend_from_slice(value);
+    buf.push(0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_socks4_request_roundtrip() {
+        let request = Request::new(
+            Command::Connect,
+            Authority::try_from("127.0.0.1:80").unwrap(),
+            Some(UserId::Username("john".to_owned())),
+        );
+        let mut buf = Vec::new();
+        request.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, b"\x04\x01\x00\x50\x7f\x00\x00\x01john\x00");
+        assert!(!request.is_socks4a());
+        assert_eq!(request, Request::read_from(&mut &buf[..]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_socks4a_request_roundtrip() {
+        let request = Request::new(
+            Command::Connect,
+            Authority::try_from("example.com:443").unwrap(),
+            None,
+        );
+        let mut buf = Vec::new();
+        request.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, b"\x04\x01\x01\xbb\x00\x00\x00\x01\x00example.com\x00");
+        assert!(request.is_socks4a());
+        assert_eq!(request, Request::read_from(&mut &buf[..]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_socks4_request_non_utf8_userid() {
+        let buf = b"\x04\x01\x00\x50\x7f\x00\x00\x01\xff\xfe\x00";
+        let request = Request::read_from(&mut &buf[..]).await.unwrap();
+        assert_eq!(request.user_id, Some(UserId::Token(vec![0xff, 0xfe])));
+    }
+
+    #[tokio::test]
+    async fn test_socks4_reply_roundtrip() {
+        let reply = Reply::unspecified(ReplyKind::Granted);
+        let mut buf = Vec::new();
+        reply.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, b"\x00\x5a\x00\x00\x00\x00\x00\x00");
+        assert_eq!(reply, Reply::read_from(&mut &buf[..]).await.unwrap());
+    }
+}
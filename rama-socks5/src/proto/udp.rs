@@ -0,0 +1,87 @@
+//! Socks5 messages used for the UDP relay, sent by both client and server.
+
+use super::{read_authority, read_expected_u8, write_authority, ProtocolError};
+use rama_net::address::Authority;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Header prepended to each UDP datagram relayed
+/// via the socks5 UDP relay server.
+///
+/// ```plain
+/// +----+------+------+----------+----------+----------+
+/// |RSV | FRAG | ATYP | DST.ADDR | DST.PORT |   DATA   |
+/// +----+------+------+----------+----------+----------+
+/// | 2  |  1   |  1   | Variable |    2     | Variable |
+/// +----+------+------+----------+----------+----------+
+/// ```
+///
+/// The `DATA` is not part of this header, and is the remainder of the datagram.
+pub struct UdpHeader {
+    /// Current fragment number, `0x00` for standalone datagrams.
+    pub fragment: u8,
+    /// Destination of the datagram when sent by the client,
+    /// or its origin when sent by the server.
+    pub destination: Authority,
+}
+
+impl UdpHeader {
+    /// Create a new [`UdpHeader`] for a standalone datagram.
+    pub fn new(destination: impl Into<Authority>) -> Self {
+        Self {
+            fragment: 0,
+            destination: destination.into(),
+        }
+    }
+
+    /// Returns `true` if this header indicates that the datagram is a fragment.
+    pub fn is_fragment(&self) -> bool {
+        self.fragment != 0
+    }
+
+    /// Read the [`UdpHeader`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        read_expected_u8(r, 0, 0x00).await?;
+        read_expected_u8(r, 1, 0x00).await?;
+        let fragment = r.read_u8().await?;
+        let destination = read_authority(r, 3).await?;
+        Ok(Self {
+            fragment,
+            destination,
+        })
+    }
+
+    /// Write the [`UdpHeader`] to the given buffer,
+    /// after which the datagram payload can be appended.
+    pub fn write_to_buf(&self, buf: &mut Vec<u8>) -> Result<(), ProtocolError> {
+        buf.extend_from_slice(&[0x00, 0x00, self.fragment]);
+        write_authority(buf, &self.destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn test_udp_header_roundtrip() {
+        let header = UdpHeader::new("127.0.0.1:53".parse::<SocketAddr>().unwrap());
+        let mut buf = Vec::new();
+        header.write_to_buf(&mut buf).unwrap();
+        buf.extend_from_slice(b"data");
+        assert_eq!(buf, b"\x00\x00\x00\x01\x7f\x00\x00\x01\x00\x35data");
+
+        let mut reader = &buf[..];
+        assert_eq!(header, UdpHeader::read_from(&mut reader).await.unwrap());
+        assert_eq!(reader, b"data");
+    }
+
+    #[tokio::test]
+    async fn test_udp_header_fragment() {
+        let buf = b"\x00\x00\x01\x03\x0bexample.com\x00\x35";
+        let header = UdpHeader::read_from(&mut &buf[..]).await.unwrap();
+        assert!(header.is_fragment());
+        assert_eq!(header.destination.to_string(), "example.com:53");
+    }
+}
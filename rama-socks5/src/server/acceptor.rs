@@ -0,0 +1,827 @@
+use super::{relay, udp::serve_udp_associate};
+use crate::proto::{
+    client::{Header, Request, UsernamePasswordRequest},
+    server::{Header as MethodSelection, Reply, UsernamePasswordResponse},
+    Command, ReplyKind, SocksMethod,
+};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Service,
+};
+use rama_net::{
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+    user::{
+        auth::{Authority, NoAuthority},
+        Basic,
+    },
+};
+use rama_tcp::client::{service::TcpConnector, Request as TcpRequest};
+use rama_udp::{
+    client::{service::UdpConnector, Request as UdpRequest},
+    UdpSocket,
+};
+use std::{
+    error::Error,
+    fmt, io,
+    marker::PhantomData,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+/// Socks5 server [`Service`], accepting Socks5 client connections.
+///
+/// It takes care of the method negotiation, the optional
+/// username/password sub-negotiation ([RFC 1929]) and the
+/// `CONNECT` command, after which the bytes are relayed
+/// between the client and the target server.
+///
+/// The `UDP ASSOCIATE` command is supported as well, but only
+/// if enabled using [`Socks5Acceptor::with_udp_associate`]
+/// or [`Socks5Acceptor::with_udp_connector`].
+///
+/// Authentication is only required in case an [`Authority`] is defined,
+/// using [`Socks5Acceptor::with_auth`]. The same username label parsers used
+/// in combination with the http `ProxyAuthLayer` (e.g. `ProxyFilterUsernameParser`)
+/// can be used to parse the username labels, see [`Socks5Acceptor::with_labels`].
+///
+/// [RFC 1929]: https://datatracker.ietf.org/doc/html/rfc1929
+pub struct Socks5Acceptor<C = TcpConnector, A = NoAuthority, L = (), U = UdpConnector> {
+    connector: C,
+    auth: Option<A>,
+    udp_connector: Option<Arc<U>>,
+    _phantom: PhantomData<fn(L)>,
+}
+
+impl<C: fmt::Debug, A: fmt::Debug, L, U: fmt::Debug> fmt::Debug for Socks5Acceptor<C, A, L, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks5Acceptor")
+            .field("connector", &self.connector)
+            .field("auth", &self.auth)
+            .field("udp_connector", &self.udp_connector)
+            .field(
+                "_phantom",
+                &format_args!("{}", std::any::type_name::<fn(L)>()),
+            )
+            .finish()
+    }
+}
+
+impl<C: Clone, A: Clone, L, U> Clone for Socks5Acceptor<C, A, L, U> {
+    fn clone(&self) -> Self {
+        Self {
+            connector: self.connector.clone(),
+            auth: self.auth.clone(),
+            udp_connector: self.udp_connector.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl Socks5Acceptor {
+    /// Create a new [`Socks5Acceptor`], which does not require
+    /// authentication and uses the default [`TcpConnector`]
+    /// to establish connections to the requested targets.
+    pub fn new() -> Self {
+        Self {
+            connector: TcpConnector::new(),
+            auth: None,
+            udp_connector: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl Default for Socks5Acceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, A, L, U> Socks5Acceptor<C, A, L, U> {
+    /// Require the client to authenticate using username/password credentials,
+    /// which are authorized using the given [`Authority`].
+    pub fn with_auth<A2>(self, auth: A2) -> Socks5Acceptor<C, A2, L, U> {
+        Socks5Acceptor {
+            connector: self.connector,
+            auth: Some(auth),
+            udp_connector: self.udp_connector,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Overwrite the Labels extract type
+    ///
+    /// This is used if the username contains labels that you need to extract out.
+    /// Example implementation is the [`UsernameOpaqueLabelParser`].
+    ///
+    /// You can provide your own extractor by implementing the [`UsernameLabelParser`] trait.
+    ///
+    /// [`UsernameOpaqueLabelParser`]: rama_core::username::UsernameOpaqueLabelParser
+    /// [`UsernameLabelParser`]: rama_core::username::UsernameLabelParser
+    pub fn with_labels<L2>(self) -> Socks5Acceptor<C, A, L2, U> {
+        Socks5Acceptor {
+            connector: self.connector,
+            auth: self.auth,
+            udp_connector: self.udp_connector,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set a custom "connector" for this acceptor, overwriting
+    /// the default [`TcpConnector`] used to establish
+    /// a connection to the target requested by the client.
+    pub fn with_connector<C2>(self, connector: C2) -> Socks5Acceptor<C2, A, L, U> {
+        Socks5Acceptor {
+            connector,
+            auth: self.auth,
+            udp_connector: self.udp_connector,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Enable support for the `UDP ASSOCIATE` command, which is disabled by default,
+    /// using the given "connector" to establish a [`UdpSocket`] "connected" to each
+    /// target requested by the client.
+    ///
+    /// This allows to apply the same policies (e.g. destination filters) to the
+    /// relayed datagrams as the ones applied by the connector used for `CONNECT`.
+    /// The [`UdpSocketTrackerHandle`] of the relay socket (facing the client) is
+    /// available in the [`Context`] passed to the connector, to track the
+    /// number of bytes relayed for the association.
+    ///
+    /// The UDP relay lives as long as the TCP control connection
+    /// that requested it. Fragmented datagrams are not supported and dropped.
+    ///
+    /// [`UdpSocket`]: rama_udp::UdpSocket
+    /// [`UdpSocketTrackerHandle`]: rama_udp::UdpSocketTrackerHandle
+    pub fn with_udp_connector<U2>(self, connector: U2) -> Socks5Acceptor<C, A, L, U2> {
+        Socks5Acceptor {
+            connector: self.connector,
+            auth: self.auth,
+            udp_connector: Some(Arc::new(connector)),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, A, L> Socks5Acceptor<C, A, L> {
+    /// Enable support for the `UDP ASSOCIATE` command, which is disabled by default,
+    /// using the default [`UdpConnector`] to reach the targets requested by the client.
+    ///
+    /// See [`Self::with_udp_connector`] for more information.
+    pub fn with_udp_associate(mut self) -> Self {
+        self.udp_connector = Some(Arc::new(UdpConnector::new()));
+        self
+    }
+
+    /// Enable support for the `UDP ASSOCIATE` command, which is disabled by default,
+    /// using the default [`UdpConnector`] to reach the targets requested by the client.
+    ///
+    /// See [`Self::with_udp_connector`] for more information.
+    pub fn set_udp_associate(&mut self) -> &mut Self {
+        self.udp_connector = Some(Arc::new(UdpConnector::new()));
+        self
+    }
+}
+
+impl<C, A, L, U> Socks5Acceptor<C, A, L, U> {
+    /// Negotiate the authentication method and authenticate the client if required,
+    /// returning the [`Request`] sent by the client once done.
+    async fn handshake<State, S>(
+        &self,
+        ctx: &mut Context<State>,
+        stream: &mut S,
+    ) -> Result<Request, BoxError>
+    where
+        S: Stream + Unpin,
+        A: Authority<Basic, L>,
+    {
+        let header = Header::read_from(stream)
+            .await
+            .context("socks5 server: read client header")?;
+
+        let method = if self.auth.is_some() {
+            SocksMethod::UsernamePassword
+        } else {
+            SocksMethod::NoAuthenticationRequired
+        };
+
+        if !header.methods.contains(&method) {
+            tracing::debug!(
+                methods = ?header.methods,
+                "socks5 server: no acceptable authentication method offered by client"
+            );
+            MethodSelection::new(SocksMethod::NoAcceptableMethods)
+                .write_to(stream)
+                .await
+                .context("socks5 server: write no acceptable methods")?;
+            return Err(OpaqueError::from_display(
+                "socks5 server: no acceptable authentication method",
+            )
+            .into_boxed());
+        }
+
+        MethodSelection::new(method)
+            .write_to(stream)
+            .await
+            .context("socks5 server: write method selection")?;
+
+        if let Some(auth) = &self.auth {
+            let UsernamePasswordRequest { basic } = UsernamePasswordRequest::read_from(stream)
+                .await
+                .context("socks5 server: read username/password request")?;
+
+            match auth.authorized(basic).await {
+                Some(ext) => {
+                    ctx.extend(ext);
+                    UsernamePasswordResponse::success()
+                        .write_to(stream)
+                        .await
+                        .context("socks5 server: write auth success response")?;
+                }
+                None => {
+                    UsernamePasswordResponse::failure()
+                        .write_to(stream)
+                        .await
+                        .context("socks5 server: write auth failure response")?;
+                    return Err(
+                        OpaqueError::from_display("socks5 server: unauthorized client")
+                            .into_boxed(),
+                    );
+                }
+            }
+        }
+
+        let request = Request::read_from(stream)
+            .await
+            .context("socks5 server: read client request")?;
+        Ok(request)
+    }
+}
+
+impl<State, S, C, A, L, U> Service<State, S> for Socks5Acceptor<C, A, L, U>
+where
+    State: Send + Sync + 'static,
+    S: Stream + Unpin,
+    C: ConnectorService<State, TcpRequest, Connection: Stream + Unpin, Error: Into<BoxError>>,
+    A: Authority<Basic, L>,
+    L: 'static,
+    U: ConnectorService<State, UdpRequest, Connection = UdpSocket, Error: Into<BoxError>>,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    async fn serve(&self, mut ctx: Context<State>, mut stream: S) -> Result<(), Self::Error> {
+        let request = self.handshake(&mut ctx, &mut stream).await?;
+
+        match request.command {
+            Command::Connect => {
+                tracing::trace!(
+                    destination = %request.destination,
+                    "socks5 server: connect command received",
+                );
+                self.serve_connect(ctx, stream, request).await
+            }
+            Command::UdpAssociate => match &self.udp_connector {
+                Some(udp_connector) => {
+                    tracing::trace!(
+                        client_address = %request.destination,
+                        "socks5 server: udp associate command received",
+                    );
+                    serve_udp_associate(ctx, stream, request, udp_connector.clone()).await
+                }
+                None => Self::unsupported_command(stream, Command::UdpAssociate).await,
+            },
+            command @ (Command::Bind | Command::Unknown(_)) => {
+                Self::unsupported_command(stream, command).await
+            }
+        }
+    }
+}
+
+impl<C, A, L, U> Socks5Acceptor<C, A, L, U> {
+    async fn unsupported_command<S>(mut stream: S, command: Command) -> Result<(), BoxError>
+    where
+        S: Stream + Unpin,
+    {
+        tracing::debug!(?command, "socks5 server: unsupported command received");
+        Reply::error(ReplyKind::CommandNotSupported)
+            .write_to(&mut stream)
+            .await
+            .context("socks5 server: write command not supported reply")?;
+        Err(
+            OpaqueError::from_display(format!("socks5 server: unsupported command: {command:?}"))
+                .into_boxed(),
+        )
+    }
+
+    async fn serve_connect<State, S>(
+        &self,
+        ctx: Context<State>,
+        mut stream: S,
+        request: Request,
+    ) -> Result<(), BoxError>
+    where
+        State: Send + Sync + 'static,
+        S: Stream + Unpin,
+        C: ConnectorService<State, TcpRequest, Connection: Stream + Unpin, Error: Into<BoxError>>,
+    {
+        let destination = request.destination;
+
+        let result = self
+            .connector
+            .connect(ctx, TcpRequest::new(destination.clone()))
+            .await
+            .map_err(Into::<BoxError>::into);
+
+        let EstablishedClientConnection { conn, addr, .. } = match result {
+            Ok(established) => established,
+            Err(err) => {
+                let reply = connect_error_reply(err.as_ref());
+                let err = OpaqueError::from_boxed(err)
+                    .with_context(|| format!("socks5 server: connect to {destination}"));
+                tracing::debug!(err = %err, ?reply, "socks5 server: failed to connect to target");
+                Reply::error(reply)
+                    .write_to(&mut stream)
+                    .await
+                    .context("socks5 server: write connect failure reply")?;
+                return Err(err.into_boxed());
+            }
+        };
+
+        tracing::trace!(
+            %destination,
+            %addr,
+            "socks5 server: connected to target",
+        );
+
+        // NOTE: the connector does not expose the local address bound for
+        // the established connection, so we reply with an unspecified address,
+        // which is what most clients expect (and ignore) in the CONNECT flow
+        Reply::new(
+            ReplyKind::Succeeded,
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        )
+        .write_to(&mut stream)
+        .await
+        .context("socks5 server: write connect success reply")?;
+
+        relay(stream, conn).await
+    }
+}
+
+/// Returns the [`ReplyKind`] for the given connect error ([RFC 1928 §6]),
+/// based on the first [`io::Error`] found in its chain of sources.
+///
+/// [RFC 1928 §6]: https://datatracker.ietf.org/doc/html/rfc1928#section-6
+fn connect_error_reply(err: &(dyn Error + 'static)) -> ReplyKind {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return match err.kind() {
+                io::ErrorKind::ConnectionRefused => ReplyKind::ConnectionRefused,
+                io::ErrorKind::TimedOut => ReplyKind::TtlExpired,
+                // matched by name, as these kinds are only stable since rust 1.83
+                kind => match format!("{kind:?}").as_str() {
+                    "NetworkUnreachable" => ReplyKind::NetworkUnreachable,
+                    "HostUnreachable" => ReplyKind::HostUnreachable,
+                    _ => ReplyKind::GeneralServerFailure,
+                },
+            };
+        }
+        source = err.source();
+    }
+    ReplyKind::GeneralServerFailure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::{
+        service::service_fn,
+        username::{UsernameLabels, UsernameOpaqueLabelParser},
+    };
+    use rama_net::user::UserId;
+    use std::convert::Infallible;
+    use tokio_test::io::Builder;
+
+    fn mock_connector(
+        expected: &'static str,
+    ) -> impl ConnectorService<(), TcpRequest, Connection: Stream + Unpin, Error: Into<BoxError>>
+    {
+        service_fn(move |ctx: Context<()>, req: TcpRequest| async move {
+            assert_eq!(req.authority().to_string(), expected);
+            Ok::<_, Infallible>(EstablishedClientConnection {
+                ctx,
+                req,
+                conn: Builder::new().write(b"ping").read(b"pong").build(),
+                addr: "127.0.0.1:443".parse().unwrap(),
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_connect_no_auth() {
+        let stream = Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x01\x00\x03\x0bexample.com\x01\xbb")
+            .write(b"\x05\x00\x00\x01\x00\x00\x00\x00\x00\x00")
+            .read(b"ping")
+            .write(b"pong")
+            .build();
+
+        Socks5Acceptor::new()
+            .with_connector(mock_connector("example.com:443"))
+            .serve(Context::default(), stream)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_failure_reply() {
+        let mut cases: Vec<(BoxError, u8)> = vec![
+            (
+                io::Error::from(io::ErrorKind::ConnectionRefused).into(),
+                0x05,
+            ),
+            (
+                OpaqueError::from_display("no route")
+                    .context("connect")
+                    .into_boxed(),
+                0x01,
+            ),
+            (io::Error::from(io::ErrorKind::TimedOut).into(), 0x06),
+            (io::Error::from(io::ErrorKind::Other).into(), 0x01),
+        ];
+        // ENETUNREACH and EHOSTUNREACH
+        #[cfg(target_os = "linux")]
+        cases.extend([
+            (io::Error::from_raw_os_error(101).into(), 0x03),
+            (io::Error::from_raw_os_error(113).into(), 0x04),
+        ]);
+
+        for (err, reply) in cases {
+            let err = std::sync::Mutex::new(Some(err));
+            let stream = Builder::new()
+                .read(b"\x05\x01\x00")
+                .write(b"\x05\x00")
+                .read(b"\x05\x01\x00\x03\x0bexample.com\x01\xbb")
+                .write(&[0x05, reply, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+                .build();
+
+            let connector = service_fn(move |_ctx: Context<()>, _req: TcpRequest| {
+                let err = err.lock().unwrap().take().unwrap();
+                async move {
+                    Err::<EstablishedClientConnection<tokio_test::io::Mock, (), TcpRequest>, _>(err)
+                }
+            });
+            assert!(Socks5Acceptor::new()
+                .with_connector(connector)
+                .serve(Context::default(), stream)
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_refused_reply() {
+        // bind to reserve a port, and close it again such that connecting to it is refused
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let mut request = b"\x05\x01\x00\x01\x7f\x00\x00\x01".to_vec();
+        request.extend_from_slice(&port.to_be_bytes());
+        let stream = Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(&request)
+            .write(b"\x05\x05\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        assert!(Socks5Acceptor::new()
+            .serve(Context::default(), stream)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_no_acceptable_method() {
+        let stream = Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\xff")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .with_auth(Basic::new("john", "secret"))
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no acceptable"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_auth_and_labels() {
+        let stream = Builder::new()
+            .read(b"\x05\x02\x00\x02")
+            .write(b"\x05\x02")
+            .read(b"\x01\x0ejohn-red-green\x06secret")
+            .write(b"\x01\x00")
+            .read(b"\x05\x01\x00\x01\x7f\x00\x00\x01\x1f\x90")
+            .write(b"\x05\x00\x00\x01\x00\x00\x00\x00\x00\x00")
+            .read(b"ping")
+            .write(b"pong")
+            .build();
+
+        let connector = service_fn(|ctx: Context<()>, req: TcpRequest| async move {
+            assert_eq!(req.authority().to_string(), "127.0.0.1:8080");
+            assert_eq!(ctx.get::<UserId>().unwrap(), "john");
+            assert_eq!(
+                ctx.get::<UsernameLabels>().unwrap().0,
+                vec!["red".to_owned(), "green".to_owned()]
+            );
+            Ok::<_, Infallible>(EstablishedClientConnection {
+                ctx,
+                req,
+                conn: Builder::new().write(b"ping").read(b"pong").build(),
+                addr: "127.0.0.1:8080".parse().unwrap(),
+            })
+        });
+
+        Socks5Acceptor::new()
+            .with_auth(vec![Basic::new("john", "secret")])
+            .with_labels::<UsernameOpaqueLabelParser>()
+            .with_connector(connector)
+            .serve(Context::default(), stream)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_auth_failure() {
+        let stream = Builder::new()
+            .read(b"\x05\x01\x02")
+            .write(b"\x05\x02")
+            .read(b"\x01\x04john\x05wrong")
+            .write(b"\x01\x01")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .with_auth(Basic::new("john", "secret"))
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn test_command_not_supported() {
+        let stream = Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x02\x00\x01\x7f\x00\x00\x01\x1f\x90")
+            .write(b"\x05\x07\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported command"));
+    }
+
+    #[tokio::test]
+    async fn test_udp_associate_disabled_by_default() {
+        let stream = Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x03\x00\x01\x00\x00\x00\x00\x00\x00")
+            .write(b"\x05\x07\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported command"));
+    }
+
+    #[tokio::test]
+    async fn test_udp_associate_relay() {
+        use crate::proto::udp::UdpHeader;
+        use rama_net::stream::SocketInfo;
+        use rama_udp::UdpSocket;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap();
+        let echo_task = tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (n, src) = echo.recv_from(&mut buf).await.unwrap();
+            echo.send_to(&buf[..n], src).await.unwrap();
+            buf[..n].to_vec()
+        });
+
+        let (mut control, server_stream) = tokio::io::duplex(1024);
+        let mut ctx = Context::default();
+        ctx.insert(SocketInfo::new(
+            Some("127.0.0.1:1080".parse().unwrap()),
+            "127.0.0.1:40000".parse().unwrap(),
+        ));
+        let acceptor = Socks5Acceptor::new().with_udp_associate();
+        let server = tokio::spawn(async move { acceptor.serve(ctx, server_stream).await });
+
+        control.write_all(b"\x05\x01\x00").await.unwrap();
+        let mut method = [0u8; 2];
+        control.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, *b"\x05\x00");
+        control
+            .write_all(b"\x05\x03\x00\x01\x00\x00\x00\x00\x00\x00")
+            .await
+            .unwrap();
+        let reply = Reply::read_from(&mut control).await.unwrap();
+        assert_eq!(reply.reply, ReplyKind::Succeeded);
+        let relay_addr = reply.bind_address.to_string();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        // fragmented datagrams are dropped
+        let mut datagram = Vec::new();
+        let mut header = UdpHeader::new(echo_addr);
+        header.fragment = 1;
+        header.write_to_buf(&mut datagram).unwrap();
+        datagram.extend_from_slice(b"fragment");
+        client.send_to(&datagram, &relay_addr).await.unwrap();
+
+        let mut datagram = Vec::new();
+        UdpHeader::new(echo_addr)
+            .write_to_buf(&mut datagram)
+            .unwrap();
+        datagram.extend_from_slice(b"ping");
+        client.send_to(&datagram, &relay_addr).await.unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = client.recv(&mut buf).await.unwrap();
+        let mut payload = &buf[..n];
+        let header = UdpHeader::read_from(&mut payload).await.unwrap();
+        assert_eq!(header, UdpHeader::new(echo_addr));
+        assert_eq!(payload, b"ping");
+        assert_eq!(echo_task.await.unwrap(), b"ping");
+
+        // closing the control connection ends the association
+        drop(control);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_udp_associate_connector_policy() {
+        use crate::proto::udp::UdpHeader;
+        use rama_core::error::OpaqueError;
+        use rama_udp::{UdpSocket, UdpSocketTrackerHandle};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            loop {
+                let (n, src) = echo.recv_from(&mut buf).await.unwrap();
+                echo.send_to(&buf[..n], src).await.unwrap();
+            }
+        });
+
+        let connector = service_fn(move |ctx: Context<()>, req: UdpRequest| async move {
+            assert!(ctx.contains::<UdpSocketTrackerHandle>());
+            if req.authority().port() != echo_addr.port() {
+                return Err(OpaqueError::from_display("destination denied"));
+            }
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.connect(echo_addr).await.unwrap();
+            Ok(EstablishedClientConnection {
+                ctx,
+                req,
+                conn: socket,
+                addr: echo_addr,
+            })
+        });
+
+        let (mut control, server_stream) = tokio::io::duplex(1024);
+        let acceptor = Socks5Acceptor::new().with_udp_connector(connector);
+        let server =
+            tokio::spawn(async move { acceptor.serve(Context::default(), server_stream).await });
+
+        control.write_all(b"\x05\x01\x00").await.unwrap();
+        let mut method = [0u8; 2];
+        control.read_exact(&mut method).await.unwrap();
+        control
+            .write_all(b"\x05\x03\x00\x01\x00\x00\x00\x00\x00\x00")
+            .await
+            .unwrap();
+        let reply = Reply::read_from(&mut control).await.unwrap();
+        assert_eq!(reply.reply, ReplyKind::Succeeded);
+        let relay_addr = reply.bind_address.to_string();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for (destination, payload) in [
+            (
+                SocketAddr::new(echo_addr.ip(), echo_addr.port() ^ 1),
+                "denied",
+            ),
+            (echo_addr, "allowed"),
+        ] {
+            let mut datagram = Vec::new();
+            UdpHeader::new(destination)
+                .write_to_buf(&mut datagram)
+                .unwrap();
+            datagram.extend_from_slice(payload.as_bytes());
+            client.send_to(&datagram, &relay_addr).await.unwrap();
+        }
+
+        let mut buf = [0u8; 64];
+        let n = client.recv(&mut buf).await.unwrap();
+        let mut payload = &buf[..n];
+        let header = UdpHeader::read_from(&mut payload).await.unwrap();
+        assert_eq!(header, UdpHeader::new(echo_addr));
+        assert_eq!(payload, b"allowed");
+
+        drop(control);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_udp_associate_pending_connect() {
+        use crate::proto::udp::UdpHeader;
+        use rama_udp::UdpSocket;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            loop {
+                let (n, src) = echo.recv_from(&mut buf).await.unwrap();
+                echo.send_to(&buf[..n], src).await.unwrap();
+            }
+        });
+
+        // connecting to any other destination never completes
+        let connector = service_fn(move |ctx: Context<()>, req: UdpRequest| async move {
+            if req.authority().port() != echo_addr.port() {
+                std::future::pending::<()>().await;
+            }
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.connect(echo_addr).await.unwrap();
+            Ok::<_, std::convert::Infallible>(EstablishedClientConnection {
+                ctx,
+                req,
+                conn: socket,
+                addr: echo_addr,
+            })
+        });
+
+        let (mut control, server_stream) = tokio::io::duplex(1024);
+        let acceptor = Socks5Acceptor::new().with_udp_connector(connector);
+        let server =
+            tokio::spawn(async move { acceptor.serve(Context::default(), server_stream).await });
+
+        control.write_all(b"\x05\x01\x00").await.unwrap();
+        let mut method = [0u8; 2];
+        control.read_exact(&mut method).await.unwrap();
+        control
+            .write_all(b"\x05\x03\x00\x01\x00\x00\x00\x00\x00\x00")
+            .await
+            .unwrap();
+        let reply = Reply::read_from(&mut control).await.unwrap();
+        assert_eq!(reply.reply, ReplyKind::Succeeded);
+        let relay_addr = reply.bind_address.to_string();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for (destination, payload) in [
+            (
+                SocketAddr::new(echo_addr.ip(), echo_addr.port() ^ 1),
+                "pending",
+            ),
+            (echo_addr, "allowed"),
+        ] {
+            let mut datagram = Vec::new();
+            UdpHeader::new(destination)
+                .write_to_buf(&mut datagram)
+                .unwrap();
+            datagram.extend_from_slice(payload.as_bytes());
+            client.send_to(&datagram, &relay_addr).await.unwrap();
+        }
+
+        let mut buf = [0u8; 64];
+        let n = client.recv(&mut buf).await.unwrap();
+        let mut payload = &buf[..n];
+        let header = UdpHeader::read_from(&mut payload).await.unwrap();
+        assert_eq!(header, UdpHeader::new(echo_addr));
+        assert_eq!(payload, b"allowed");
+
+        // the pending connect does not keep the association alive
+        drop(control);
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+//! Socks5 Server support
+//!
+//! <https://datatracker.ietf.org/doc/html/rfc1928>
+//!
+//! The legacy Socks4 and Socks4a protocols are supported as well,
+//! using the [`Socks4Acceptor`]. Use the [`SocksAcceptor`] in case
+//! you wish to serve both Socks4(a) and Socks5 clients on the same listener.
+
+use rama_core::error::{BoxError, ErrorExt};
+use rama_net::stream::Stream;
+use rama_tcp::utils::is_connection_error;
+
+mod acceptor;
+mod udp;
+#[doc(inline)]
+pub use acceptor::Socks5Acceptor;
+
+mod socks4;
+#[doc(inline)]
+pub use socks4::{Socks4Acceptor, Socks4UserId};
+
+mod sniff;
+#[doc(inline)]
+pub use sniff::SocksAcceptor;
+
+/// Relay the traffic between the client and the target
+/// until either side closes the connection.
+async fn relay<S, T>(mut stream: S, mut target: T) -> Result<(), BoxError>
+where
+    S: Stream + Unpin,
+    T: Stream + Unpin,
+{
+    match tokio::io::copy_bidirectional(&mut stream, &mut target).await {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            if is_connection_error(&err) {
+                Ok(())
+            } else {
+                Err(err
+                    .context("socks server: relay connect traffic")
+                    .into_boxed())
+            }
+        }
+    }
+}
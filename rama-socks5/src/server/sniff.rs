@@ -0,0 +1,181 @@
+use super::{Socks4Acceptor, Socks5Acceptor};
+use crate::proto::{socks4::SOCKS4_VERSION, SOCKS5_VERSION};
+use rama_core::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    Context, Service,
+};
+use rama_net::stream::{ChainReader, HeapReader, Stream};
+use tokio::io::AsyncReadExt;
+
+/// Socks [`Service`] which serves Socks5 and (optionally) Socks4 and Socks4a clients,
+/// dispatching to the relevant acceptor based on the version byte
+/// sent by the client as its first byte.
+///
+/// Socks4(a) clients are rejected unless a Socks4 acceptor is
+/// enabled using [`SocksAcceptor::with_socks4`]. Please note that Socks4
+/// has no support for passwords, and thus any authentication required by
+/// your Socks5 acceptor would be bypassed by clients that use Socks4 instead,
+/// unless the Socks4 acceptor authorizes its clients as well
+/// (see [`Socks4Acceptor::with_auth`]).
+#[derive(Debug, Clone)]
+pub struct SocksAcceptor<S4 = Socks4Acceptor, S5 = Socks5Acceptor> {
+    socks4: Option<S4>,
+    socks5: S5,
+}
+
+impl<S5> SocksAcceptor<Socks4Acceptor, S5> {
+    /// Create a new [`SocksAcceptor`], using the given acceptor for Socks5 clients,
+    /// and rejecting Socks4(a) clients.
+    pub fn new(socks5: S5) -> Self {
+        Self {
+            socks4: None,
+            socks5,
+        }
+    }
+}
+
+impl<S4, S5> SocksAcceptor<S4, S5> {
+    /// Serve Socks4(a) clients as well, using the given acceptor.
+    pub fn with_socks4<T>(self, socks4: T) -> SocksAcceptor<T, S5> {
+        SocksAcceptor {
+            socks4: Some(socks4),
+            socks5: self.socks5,
+        }
+    }
+
+    /// Serve Socks4(a) clients as well, using the given acceptor.
+    pub fn set_socks4(&mut self, socks4: S4) -> &mut Self {
+        self.socks4 = Some(socks4);
+        self
+    }
+}
+
+impl Default for SocksAcceptor {
+    fn default() -> Self {
+        Self::new(Socks5Acceptor::new())
+    }
+}
+
+impl<State, IO, S4, S5> Service<State, IO> for SocksAcceptor<S4, S5>
+where
+    State: Send + Sync + 'static,
+    IO: Stream + Unpin,
+    S4: Service<
+        State,
+        tokio::io::Join<ChainReader<HeapReader, tokio::io::ReadHalf<IO>>, tokio::io::WriteHalf<IO>>,
+        Error: Into<BoxError>,
+    >,
+    S5: Service<
+        State,
+        tokio::io::Join<ChainReader<HeapReader, tokio::io::ReadHalf<IO>>, tokio::io::WriteHalf<IO>>,
+        Response = S4::Response,
+        Error: Into<BoxError>,
+    >,
+{
+    type Response = S4::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        mut stream: IO,
+    ) -> Result<Self::Response, Self::Error> {
+        let version = stream
+            .read_u8()
+            .await
+            .context("socks server: read version byte")?;
+
+        // put back the version byte, as it is part of the protocol messages
+        let (r, w) = tokio::io::split(stream);
+        let r = ChainReader::new(HeapReader::new(vec![version]), r);
+        let stream = tokio::io::join(r, w);
+
+        match version {
+            SOCKS4_VERSION => match &self.socks4 {
+                Some(socks4) => {
+                    tracing::trace!("socks server: dispatch socks4 client");
+                    socks4.serve(ctx, stream).await.map_err(Into::into)
+                }
+                None => {
+                    Err(OpaqueError::from_display("socks server: socks4 is disabled").into_boxed())
+                }
+            },
+            SOCKS5_VERSION => {
+                tracing::trace!("socks server: dispatch socks5 client");
+                self.socks5.serve(ctx, stream).await.map_err(Into::into)
+            }
+            version => Err(OpaqueError::from_display(format!(
+                "socks server: unsupported version: {version:#04x}"
+            ))
+            .into_boxed()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+    use tokio_test::io::{Builder, Mock};
+
+    type SniffedStream = tokio::io::Join<
+        ChainReader<HeapReader, tokio::io::ReadHalf<Mock>>,
+        tokio::io::WriteHalf<Mock>,
+    >;
+
+    async fn read_two_bytes(
+        _ctx: Context<()>,
+        mut stream: SniffedStream,
+    ) -> Result<[u8; 2], Infallible> {
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).await.unwrap();
+        Ok(buf)
+    }
+
+    #[tokio::test]
+    async fn test_sniff_version() {
+        let acceptor =
+            SocksAcceptor::new(service_fn(read_two_bytes)).with_socks4(service_fn(read_two_bytes));
+
+        for input in [b"\x04\x01", b"\x05\x02"] {
+            let stream = Builder::new().read(input).build();
+            let output = acceptor.serve(Context::default(), stream).await.unwrap();
+            assert_eq!(&output, input);
+        }
+
+        let stream = Builder::new().read(b"\x06").build();
+        let err = acceptor
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported version"));
+    }
+
+    #[tokio::test]
+    async fn test_default_socks5_dispatch() {
+        let stream = Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x02\x00\x01\x7f\x00\x00\x01\x1f\x90")
+            .write(b"\x05\x07\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = SocksAcceptor::default()
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported command"));
+    }
+
+    #[tokio::test]
+    async fn test_socks4_disabled_by_default() {
+        let stream = Builder::new().read(b"\x04").build();
+
+        let err = SocksAcceptor::default()
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("socks4 is disabled"));
+    }
+}
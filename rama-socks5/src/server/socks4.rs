@@ -0,0 +1,323 @@
+use super::relay;
+use crate::proto::socks4::{Command, Reply, ReplyKind, Request};
+use rama_core::{
+    context::Extensions,
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Service,
+};
+use rama_net::{
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+    user::{
+        auth::{Authority, NoAuthority},
+        UserId,
+    },
+};
+use rama_tcp::client::{service::TcpConnector, Request as TcpRequest};
+use rama_utils::macros::impl_deref;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The userid sent by a Socks4 client, as inserted by the [`Socks4Acceptor`]
+/// in the [`Context`].
+///
+/// This is a distinct type from [`UserId`], as the Socks4 protocol has no support
+/// for passwords, and thus this userid is only claimed by the client,
+/// without being authenticated in any way.
+pub struct Socks4UserId(pub UserId);
+
+impl_deref!(Socks4UserId: UserId);
+
+impl Authority<Socks4UserId, ()> for NoAuthority {
+    async fn authorized(&self, _user_id: Socks4UserId) -> Option<Extensions> {
+        None
+    }
+}
+
+/// Socks4 server [`Service`], accepting Socks4 and Socks4a client connections.
+///
+/// Only the `CONNECT` command is supported, after which the bytes are relayed
+/// between the client and the target server.
+///
+/// The userid sent by the client (if any) is inserted in the [`Context`]
+/// as a [`Socks4UserId`]. Please note that the Socks4 protocol has no support
+/// for passwords, so this userid is not authenticated in any way.
+///
+/// Use [`Socks4Acceptor::with_auth`] to only allow the clients
+/// authorized by an [`Authority`], e.g. based on their userid.
+pub struct Socks4Acceptor<C = TcpConnector, A = NoAuthority> {
+    connector: C,
+    auth: Option<A>,
+}
+
+impl<C: fmt::Debug, A: fmt::Debug> fmt::Debug for Socks4Acceptor<C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks4Acceptor")
+            .field("connector", &self.connector)
+            .field("auth", &self.auth)
+            .finish()
+    }
+}
+
+impl<C: Clone, A: Clone> Clone for Socks4Acceptor<C, A> {
+    fn clone(&self) -> Self {
+        Self {
+            connector: self.connector.clone(),
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+impl Socks4Acceptor {
+    /// Create a new [`Socks4Acceptor`], which accepts all clients and uses
+    /// the default [`TcpConnector`] to establish connections to the requested targets.
+    pub fn new() -> Self {
+        Self {
+            connector: TcpConnector::new(),
+            auth: None,
+        }
+    }
+}
+
+impl Default for Socks4Acceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, A> Socks4Acceptor<C, A> {
+    /// Set a custom "connector" for this acceptor, overwriting
+    /// the default [`TcpConnector`] used to establish
+    /// a connection to the target requested by the client.
+    pub fn with_connector<C2>(self, connector: C2) -> Socks4Acceptor<C2, A> {
+        Socks4Acceptor {
+            connector,
+            auth: self.auth,
+        }
+    }
+
+    /// Only accept the clients authorized by the given [`Authority`],
+    /// which is called with the [`Socks4UserId`] sent by the client.
+    ///
+    /// Clients which do not send a userid are rejected. The [`Extensions`]
+    /// returned by the [`Authority`] are added to the [`Context`].
+    pub fn with_auth<A2>(self, auth: A2) -> Socks4Acceptor<C, A2> {
+        Socks4Acceptor {
+            connector: self.connector,
+            auth: Some(auth),
+        }
+    }
+}
+
+impl<State, S, C, A> Service<State, S> for Socks4Acceptor<C, A>
+where
+    State: Send + Sync + 'static,
+    S: Stream + Unpin,
+    C: ConnectorService<State, TcpRequest, Connection: Stream + Unpin, Error: Into<BoxError>>,
+    A: Authority<Socks4UserId, ()>,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    async fn serve(&self, mut ctx: Context<State>, mut stream: S) -> Result<(), Self::Error> {
+        let request = Request::read_from(&mut stream)
+            .await
+            .context("socks4 server: read client request")?;
+
+        let user_id = request.user_id.map(Socks4UserId);
+
+        if let Some(auth) = &self.auth {
+            let ext = match user_id.clone() {
+                Some(user_id) => auth.authorized(user_id).await,
+                None => None,
+            };
+            match ext {
+                Some(ext) => ctx.extend(ext),
+                None => {
+                    tracing::debug!(?user_id, "socks4 server: unauthorized client");
+                    Reply::unspecified(ReplyKind::Rejected)
+                        .write_to(&mut stream)
+                        .await
+                        .context("socks4 server: write rejected reply")?;
+                    return Err(
+                        OpaqueError::from_display("socks4 server: unauthorized client")
+                            .into_boxed(),
+                    );
+                }
+            }
+        }
+
+        if let Some(user_id) = user_id {
+            ctx.insert(user_id);
+        }
+
+        match request.command {
+            Command::Connect => {
+                tracing::trace!(
+                    destination = %request.destination,
+                    "socks4 server: connect command received",
+                );
+            }
+            command @ (Command::Bind | Command::Unknown(_)) => {
+                tracing::debug!(?command, "socks4 server: unsupported command received");
+                Reply::unspecified(ReplyKind::Rejected)
+                    .write_to(&mut stream)
+                    .await
+                    .context("socks4 server: write rejected reply")?;
+                return Err(OpaqueError::from_display(format!(
+                    "socks4 server: unsupported command: {command:?}"
+                ))
+                .into_boxed());
+            }
+        }
+
+        let destination = request.destination;
+
+        let result = self
+            .connector
+            .connect(ctx, TcpRequest::new(destination.clone()))
+            .await
+            .map_err(|err| {
+                OpaqueError::from_boxed(err.into())
+                    .with_context(|| format!("socks4 server: connect to {destination}"))
+            });
+
+        let EstablishedClientConnection { conn, addr, .. } = match result {
+            Ok(established) => established,
+            Err(err) => {
+                tracing::debug!(err = %err, "socks4 server: failed to connect to target");
+                Reply::unspecified(ReplyKind::Rejected)
+                    .write_to(&mut stream)
+                    .await
+                    .context("socks4 server: write rejected reply")?;
+                return Err(err.into_boxed());
+            }
+        };
+
+        tracing::trace!(
+            %destination,
+            %addr,
+            "socks4 server: connected to target",
+        );
+
+        Reply::unspecified(ReplyKind::Granted)
+            .write_to(&mut stream)
+            .await
+            .context("socks4 server: write granted reply")?;
+
+        relay(stream, conn).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::service::service_fn;
+    use std::convert::Infallible;
+    use tokio_test::io::Builder;
+
+    #[tokio::test]
+    async fn test_socks4a_connect() {
+        let stream = Builder::new()
+            .read(b"\x04\x01\x01\xbb\x00\x00\x00\x01john\x00example.com\x00")
+            .write(b"\x00\x5a\x00\x00\x00\x00\x00\x00")
+            .read(b"ping")
+            .write(b"pong")
+            .build();
+
+        let connector = service_fn(|ctx: Context<()>, req: TcpRequest| async move {
+            assert_eq!(req.authority().to_string(), "example.com:443");
+            assert_eq!(
+                ctx.get::<Socks4UserId>(),
+                Some(&Socks4UserId(UserId::Username("john".to_owned())))
+            );
+            assert!(ctx.get::<UserId>().is_none());
+            Ok::<_, Infallible>(EstablishedClientConnection {
+                ctx,
+                req,
+                conn: Builder::new().write(b"ping").read(b"pong").build(),
+                addr: "127.0.0.1:443".parse().unwrap(),
+            })
+        });
+
+        Socks4Acceptor::new()
+            .with_connector(connector)
+            .serve(Context::default(), stream)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks4_bind_not_supported() {
+        let stream = Builder::new()
+            .read(b"\x04\x02\x00\x50\x7f\x00\x00\x01\x00")
+            .write(b"\x00\x5b\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks4Acceptor::new()
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported command"));
+    }
+
+    #[derive(Debug)]
+    struct AllowUser(&'static str);
+
+    impl Authority<Socks4UserId, ()> for AllowUser {
+        async fn authorized(&self, user_id: Socks4UserId) -> Option<Extensions> {
+            (user_id.0 == *self.0).then(|| {
+                let mut ext = Extensions::new();
+                ext.insert(user_id.0);
+                ext
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks4_auth() {
+        let stream = Builder::new()
+            .read(b"\x04\x01\x01\xbb\x7f\x00\x00\x01john\x00")
+            .write(b"\x00\x5a\x00\x00\x00\x00\x00\x00")
+            .read(b"ping")
+            .write(b"pong")
+            .build();
+
+        let connector = service_fn(|ctx: Context<()>, req: TcpRequest| async move {
+            assert_eq!(ctx.get::<UserId>().unwrap(), "john");
+            Ok::<_, Infallible>(EstablishedClientConnection {
+                ctx,
+                req,
+                conn: Builder::new().write(b"ping").read(b"pong").build(),
+                addr: "127.0.0.1:443".parse().unwrap(),
+            })
+        });
+
+        Socks4Acceptor::new()
+            .with_connector(connector)
+            .with_auth(AllowUser("john"))
+            .serve(Context::default(), stream)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socks4_auth_rejected() {
+        for input in [
+            &b"\x04\x01\x01\xbb\x7f\x00\x00\x01jane\x00"[..],
+            &b"\x04\x01\x01\xbb\x7f\x00\x00\x01\x00"[..],
+        ] {
+            let stream = Builder::new()
+                .read(input)
+                .write(b"\x00\x5b\x00\x00\x00\x00\x00\x00")
+                .build();
+
+            let err = Socks4Acceptor::new()
+                .with_auth(AllowUser("john"))
+                .serve(Context::default(), stream)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("unauthorized"));
+        }
+    }
+}
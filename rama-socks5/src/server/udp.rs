@@ -0,0 +1,358 @@
+use crate::proto::{client::Request, server::Reply, udp::UdpHeader, ReplyKind};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context,
+};
+use rama_net::{
+    address::{Authority, Host},
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::{SocketInfo, Stream},
+};
+use rama_udp::{client::Request as UdpRequest, UdpSocket, UdpSocketTracker};
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{io::AsyncReadExt, task::JoinSet};
+
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+const MAX_TARGETS: usize = 256;
+const MAX_PENDING_DATAGRAMS: usize = 16;
+
+/// Serve the `UDP ASSOCIATE` command, relaying datagrams between
+/// the client and the destinations it requests, for as long as the
+/// TCP control connection remains open.
+///
+/// A "connection" to each new destination is established using the given connector,
+/// such that the same policies (e.g. destination filters) apply as for `CONNECT`.
+/// These connections are established in the background, buffering the datagrams
+/// for the destination in the meantime, such that a slow destination does not
+/// hold up the relaying for the other destinations.
+pub(super) async fn serve_udp_associate<State, S, U>(
+    mut ctx: Context<State>,
+    mut stream: S,
+    request: Request,
+    connector: Arc<U>,
+) -> Result<(), BoxError>
+where
+    State: Send + Sync + 'static,
+    S: Stream + Unpin,
+    U: ConnectorService<State, UdpRequest, Connection = UdpSocket, Error: Into<BoxError>>,
+{
+    let socket_info = ctx.get::<SocketInfo>();
+    let peer_ip = socket_info.map(|info| info.peer_addr().ip());
+
+    // bind the relay on the same interface as the control connection,
+    // such that the client can reach it the same way
+    let bind_ip = socket_info
+        .and_then(|info| info.local_addr())
+        .map(|addr| addr.ip())
+        .unwrap_or(match peer_ip {
+            Some(IpAddr::V6(_)) => Ipv6Addr::UNSPECIFIED.into(),
+            _ => Ipv4Addr::UNSPECIFIED.into(),
+        });
+
+    let relay = match UdpSocket::bind((bind_ip, 0)).await {
+        Ok(socket) => Arc::new(UdpSocketTracker::new(socket)),
+        Err(err) => {
+            tracing::debug!(err = %err, %bind_ip, "socks5 server: failed to bind udp relay");
+            Reply::error(ReplyKind::GeneralServerFailure)
+                .write_to(&mut stream)
+                .await
+                .context("socks5 server: write general server failure reply")?;
+            return Err(err.context("socks5 server: bind udp relay").into_boxed());
+        }
+    };
+    let relay_addr = relay
+        .local_addr()
+        .context("socks5 server: get udp relay address")?;
+
+    // expose the relay counters to the connector (and its middleware)
+    ctx.insert(relay.handle());
+
+    Reply::new(ReplyKind::Succeeded, relay_addr)
+        .write_to(&mut stream)
+        .await
+        .context("socks5 server: write udp associate success reply")?;
+
+    tracing::trace!(%relay_addr, "socks5 server: udp relay bound");
+
+    let mut association = UdpAssociation {
+        filter: ClientFilter::new(request.destination, peer_ip),
+        client_addr: None,
+        targets: HashMap::new(),
+        pending: HashMap::new(),
+        connecting: JoinSet::new(),
+        inbound: JoinSet::new(),
+    };
+
+    let mut control_buf = [0u8; 64];
+    let mut client_buf = vec![0u8; MAX_DATAGRAM_SIZE];
+
+    let result = loop {
+        tokio::select! {
+            result = stream.read(&mut control_buf) => match result {
+                Ok(0) => break Ok(()),
+                Ok(_) => {
+                    tracing::trace!("socks5 server: ignore data received on udp control connection");
+                }
+                Err(err) => break Err(err),
+            },
+            result = relay.recv_from(&mut client_buf) => match result {
+                Ok((n, src)) => {
+                    association
+                        .relay_to_target(&ctx, &connector, src, &client_buf[..n])
+                        .await
+                }
+                Err(err) if is_recoverable_error(&err) => {
+                    tracing::trace!(err = %err, "socks5 server: ignore udp relay receive error");
+                }
+                Err(err) => break Err(err),
+            },
+            Some(result) = association.connecting.join_next() => {
+                if let Ok((destination, result)) = result {
+                    association.target_connected(&relay, destination, result).await;
+                }
+            },
+            Some(result) = association.inbound.join_next() => {
+                if let Ok(target) = result {
+                    association.targets.remove(&target);
+                }
+            },
+        }
+    };
+
+    tracing::debug!(
+        %relay_addr,
+        client_addr = ?association.client_addr,
+        read = relay.read(),
+        written = relay.written(),
+        "socks5 server: udp association closed",
+    );
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            if rama_tcp::utils::is_connection_error(&err) {
+                Ok(())
+            } else {
+                Err(err.context("socks5 server: udp relay").into_boxed())
+            }
+        }
+    }
+}
+
+/// Errors which only affect a single datagram (e.g. an ICMP
+/// port unreachable reported for a previous datagram),
+/// and thus should not end the association.
+fn is_recoverable_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Filter on the source address of datagrams received on the relay,
+/// as only the client that requested the association is allowed to use it.
+#[derive(Debug)]
+struct ClientFilter {
+    ip: Option<IpAddr>,
+    port: Option<u16>,
+}
+
+impl ClientFilter {
+    fn new(requested: Authority, peer_ip: Option<IpAddr>) -> Self {
+        let (host, port) = requested.into_parts();
+        let ip = match host {
+            Host::Address(ip) if !ip.is_unspecified() => Some(ip),
+            _ => peer_ip,
+        };
+        Self {
+            ip,
+            port: (port != 0).then_some(port),
+        }
+    }
+
+    fn matches(&self, addr: SocketAddr) -> bool {
+        self.ip.map(|ip| ip == addr.ip()).unwrap_or(true)
+            && self.port.map(|port| port == addr.port()).unwrap_or(true)
+    }
+}
+
+#[derive(Debug)]
+struct UdpAssociation {
+    filter: ClientFilter,
+    client_addr: Option<SocketAddr>,
+    targets: HashMap<Authority, Arc<UdpSocket>>,
+    /// Datagrams waiting for the connection to their target to be established.
+    pending: HashMap<Authority, Vec<Vec<u8>>>,
+    connecting: JoinSet<(Authority, Result<(UdpSocket, SocketAddr), OpaqueError>)>,
+    inbound: JoinSet<Authority>,
+}
+
+impl UdpAssociation {
+    async fn relay_to_target<State, U>(
+        &mut self,
+        ctx: &Context<State>,
+        connector: &Arc<U>,
+        src: SocketAddr,
+        datagram: &[u8],
+    ) where
+        State: Send + Sync + 'static,
+        U: ConnectorService<State, UdpRequest, Connection = UdpSocket, Error: Into<BoxError>>,
+    {
+        match self.client_addr {
+            Some(client_addr) if client_addr != src => {
+                tracing::trace!(%src, %client_addr, "socks5 server: drop datagram from unknown source");
+                return;
+            }
+            Some(_) => (),
+            None if self.filter.matches(src) => {
+                self.client_addr = Some(src);
+            }
+            None => {
+                tracing::trace!(%src, "socks5 server: drop datagram from unexpected source");
+                return;
+            }
+        }
+
+        let mut payload = datagram;
+        let header = match UdpHeader::read_from(&mut payload).await {
+            Ok(header) => header,
+            Err(err) => {
+                tracing::trace!(err = %err, "socks5 server: drop datagram with invalid header");
+                return;
+            }
+        };
+        if header.is_fragment() {
+            tracing::trace!(
+                fragment = header.fragment,
+                "socks5 server: drop fragmented datagram: fragmentation not supported"
+            );
+            return;
+        }
+
+        let destination = header.destination;
+        if let Some(socket) = self.targets.get(&destination) {
+            if let Err(err) = socket.send(payload).await {
+                tracing::debug!(err = %err, %destination, "socks5 server: failed to relay datagram to target");
+            }
+            return;
+        }
+
+        if let Some(datagrams) = self.pending.get_mut(&destination) {
+            if datagrams.len() < MAX_PENDING_DATAGRAMS {
+                datagrams.push(payload.to_vec());
+            } else {
+                tracing::trace!(%destination, "socks5 server: drop datagram: too many datagrams pending for target");
+            }
+            return;
+        }
+
+        if self.targets.len() + self.pending.len() >= MAX_TARGETS {
+            tracing::debug!(%destination, "socks5 server: drop datagram: max number of targets reached for udp association");
+            return;
+        }
+
+        self.pending
+            .insert(destination.clone(), vec![payload.to_vec()]);
+        let ctx = ctx.clone();
+        let connector = connector.clone();
+        self.connecting.spawn(async move {
+            let result = connector
+                .connect(ctx, UdpRequest::new(destination.clone()))
+                .await
+                .map(|EstablishedClientConnection { conn, addr, .. }| (conn, addr))
+                .map_err(|err| OpaqueError::from_boxed(err.into()))
+                .context("connect to udp target");
+            (destination, result)
+        });
+    }
+
+    /// Handle the outcome of establishing a "connection" to a new target,
+    /// relaying the datagrams pending for it and spawning a task
+    /// relaying the datagrams received from it to the client.
+    async fn target_connected(
+        &mut self,
+        relay: &Arc<UdpSocketTracker>,
+        destination: Authority,
+        result: Result<(UdpSocket, SocketAddr), OpaqueError>,
+    ) {
+        let datagrams = self.pending.remove(&destination).unwrap_or_default();
+
+        let (socket, addr) = match result {
+            Ok(conn) => conn,
+            Err(err) => {
+                tracing::debug!(
+                    err = %err,
+                    %destination,
+                    dropped = datagrams.len(),
+                    "socks5 server: drop datagrams for unreachable target",
+                );
+                return;
+            }
+        };
+        // only set once, prior to any target being requested
+        let Some(client_addr) = self.client_addr else {
+            return;
+        };
+
+        let socket = Arc::new(socket);
+        self.targets.insert(destination.clone(), socket.clone());
+        self.inbound.spawn(relay_to_client(
+            socket.clone(),
+            relay.clone(),
+            client_addr,
+            addr,
+            destination.clone(),
+        ));
+
+        for datagram in datagrams {
+            if let Err(err) = socket.send(&datagram).await {
+                tracing::debug!(err = %err, %destination, "socks5 server: failed to relay datagram to target");
+            }
+        }
+    }
+}
+
+/// Relay the datagrams received from a target to the client,
+/// until a non-recoverable error occurs, returning the target's [`Authority`].
+async fn relay_to_client(
+    socket: Arc<UdpSocket>,
+    relay: Arc<UdpSocketTracker>,
+    client_addr: SocketAddr,
+    target_addr: SocketAddr,
+    destination: Authority,
+) -> Authority {
+    let mut header = Vec::with_capacity(22);
+    if let Err(err) = UdpHeader::new(target_addr).write_to_buf(&mut header) {
+        tracing::debug!(err = %err, %target_addr, "socks5 server: failed to encode udp header");
+        return destination;
+    }
+
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    buf[..header.len()].copy_from_slice(&header);
+    let payload_offset = header.len();
+
+    loop {
+        match socket.recv(&mut buf[payload_offset..]).await {
+            Ok(n) => {
+                if let Err(err) = relay.send_to(&buf[..payload_offset + n], client_addr).await {
+                    tracing::debug!(err = %err, %client_addr, "socks5 server: failed to relay datagram to client");
+                }
+            }
+            Err(err) if is_recoverable_error(&err) => {
+                tracing::trace!(err = %err, %target_addr, "socks5 server: ignore udp target receive error");
+            }
+            Err(err) => {
+                tracing::debug!(err = %err, %target_addr, "socks5 server: stop relaying udp target");
+                return destination;
+            }
+        }
+    }
+}
@@ -1,37 +1,125 @@
-use rama_core::{
-    combinators::Either4,
-    dns::Dns,
-    error::{ErrorContext, OpaqueError},
-    Context,
-};
+use rama_core::{dns::Dns, error::OpaqueError, rt::Executor, Context};
 use rama_net::address::{Authority, Domain, Host};
+use rama_utils::macros::impl_deref;
 use std::{
+    collections::VecDeque,
+    fmt, io,
     net::{IpAddr, SocketAddr},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
     time::Duration,
 };
-use tokio::{
-    net::TcpStream,
-    sync::{
-        mpsc::{channel, Sender},
-        Semaphore,
-    },
-};
+use tokio::{net::TcpStream, sync::mpsc, task::JoinHandle, time::Instant};
+
+/// The default delay between two connection attempts,
+/// as recommended by [RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305#section-5).
+pub const DEFAULT_CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// The time to wait for the preferred address family to be resolved,
+/// in case the other family was resolved first, as recommended by
+/// [RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305#section-3).
+const RESOLUTION_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+/// Error of a failed connection attempt, which has the [`io::Error`] as its source,
+/// such that its kind remains available to the consumers of the connector
+/// (e.g. proxies replying why the connection failed).
+struct ConnectError {
+    msg: String,
+    err: io::Error,
+}
+
+impl ConnectError {
+    fn new(msg: impl Into<String>, err: io::Error) -> Self {
+        Self {
+            msg: msg.into(),
+            err,
+        }
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.msg, self.err)
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The IP address family preference used to establish a TCP connection
+/// to a domain, which can be inserted in the [`Context`] to overwrite
+/// the default preference for a request.
+pub enum IpPreference {
+    #[default]
+    /// Prefer IPv6 addresses, falling back to IPv4 addresses.
+    PreferIpv6,
+    /// Prefer IPv4 addresses, falling back to IPv6 addresses.
+    PreferIpv4,
+    /// Only use IPv4 addresses.
+    Ipv4Only,
+    /// Only use IPv6 addresses.
+    Ipv6Only,
+}
+
+impl IpPreference {
+    fn prefers_ipv6(self) -> bool {
+        matches!(self, Self::PreferIpv6 | Self::Ipv6Only)
+    }
+
+    fn allows_ipv4(self) -> bool {
+        !matches!(self, Self::Ipv6Only)
+    }
+
+    fn allows_ipv6(self) -> bool {
+        !matches!(self, Self::Ipv4Only)
+    }
+}
+
+/// The address to which a TCP connection was established,
+/// inserted in the [`Context`] by the `TcpConnector`.
+///
+/// In case of a domain this is the address which won the connection race.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectedAddress(SocketAddr);
+
+impl ConnectedAddress {
+    /// Create a new [`ConnectedAddress`] for the given [`SocketAddr`].
+    pub const fn new(addr: SocketAddr) -> Self {
+        Self(addr)
+    }
+
+    /// Returns the [`SocketAddr`] to which the connection was established.
+    pub const fn addr(&self) -> SocketAddr {
+        self.0
+    }
+}
+
+impl AsRef<SocketAddr> for ConnectedAddress {
+    fn as_ref(&self) -> &SocketAddr {
+        &self.0
+    }
+}
+
+impl_deref!(ConnectedAddress: SocketAddr);
 
 /// Establish a TCP connection for the given authority.
 ///
 /// In the case where the authority is already an IP address, we can directly connect to it.
-/// Otherwise, we'll try to establish a connection with dual-stack parallel connections,
-/// meaning that we'll try to connect to the domain using both IPv4 and IPv6,
-/// with multiple concurrent connection attempts.
+/// Otherwise, we'll try to establish a connection using the Happy Eyeballs v2 algorithm
+/// ([RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305)), meaning that the domain
+/// is resolved to both IPv4 and IPv6 addresses, which are attempted interleaved
+/// by address family, with a [`DEFAULT_CONNECTION_ATTEMPT_DELAY`] between attempts.
+///
+/// The family which is attempted first can be controlled by inserting
+/// an [`IpPreference`] in the [`Context`].
 pub async fn connect<State: Send + Sync + 'static>(
     ctx: &Context<State>,
     authority: Authority,
 ) -> Result<(TcpStream, SocketAddr), OpaqueError> {
-    connect_inner(ctx, authority, false).await
+    connect_inner(ctx, authority, false, DEFAULT_CONNECTION_ATTEMPT_DELAY).await
 }
 
 /// Establish a TCP connection for the given authority.
@@ -41,13 +129,14 @@ pub async fn connect_trusted<State: Send + Sync + 'static>(
     ctx: &Context<State>,
     authority: Authority,
 ) -> Result<(TcpStream, SocketAddr), OpaqueError> {
-    connect_inner(ctx, authority, true).await
+    connect_inner(ctx, authority, true, DEFAULT_CONNECTION_ATTEMPT_DELAY).await
 }
 
-async fn connect_inner<State>(
+pub(crate) async fn connect_inner<State>(
     ctx: &Context<State>,
     authority: Authority,
     trusted_only: bool,
+    attempt_delay: Duration,
 ) -> Result<(TcpStream, SocketAddr), OpaqueError>
 where
     State: Send + Sync + 'static,
@@ -58,156 +147,293 @@ where
         Host::Address(ip) => {
             // if the authority is already defined as an IP address, we can directly connect to it
             let addr = (ip, port).into();
-            let stream = TcpStream::connect(&addr)
-                .await
-                .context("establish tcp client connection")?;
+            let stream = TcpStream::connect(&addr).await.map_err(|err| {
+                OpaqueError::from_std(ConnectError::new("establish tcp client connection", err))
+            })?;
             return Ok((stream, addr));
         }
     };
 
-    //... otherwise we'll try to establish a connection,
-    // with dual-stack parallel connections...
-
-    let (tx, mut rx) = channel(1);
-
-    let connected = Arc::new(AtomicBool::new(false));
-    let sem = Arc::new(Semaphore::new(3));
-
-    // IPv6
-    let ipv6_tx = tx.clone();
-    let ipv6_domain = domain.clone();
-    let ipv6_dns = ctx.dns().clone();
-    let ipv6_connected = connected.clone();
-    let ipv6_sem = sem.clone();
-    ctx.spawn(tcp_connect(
-        ipv6_dns,
-        IpKind::Ipv6,
-        ipv6_domain,
-        port,
-        ipv6_tx,
-        ipv6_connected,
-        ipv6_sem,
-        trusted_only,
-    ));
-
-    // IPv4
-    let ipv4_tx = tx;
-    let ipv4_domain = domain.clone();
-    let ipv4_dns = ctx.dns().clone();
-    let ipv4_connected = connected.clone();
-    let ipv4_sem = sem;
-    ctx.spawn(tcp_connect(
-        ipv4_dns,
-        IpKind::Ipv4,
-        ipv4_domain,
+    let preference = ctx.get::<IpPreference>().copied().unwrap_or_default();
+    happy_eyeballs(
+        ctx.executor(),
+        ctx.dns().clone(),
+        domain,
         port,
-        ipv4_tx,
-        ipv4_connected,
-        ipv4_sem,
+        preference,
         trusted_only,
-    ));
-
-    // wait for the first connection to succeed,
-    // ignore the rest of the connections (sorry, but not sorry)
-    if let Some((stream, addr)) = rx.recv().await {
-        connected.store(true, Ordering::SeqCst);
-        return Ok((stream, addr));
-    }
-
-    Err(OpaqueError::from_display(format!(
-        "failed to connect to any resolved IP address for {domain} (port {port})"
-    )))
+        attempt_delay,
+    )
+    .await
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum IpKind {
     Ipv4,
     Ipv6,
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn tcp_connect(
+async fn lookup(dns: Dns, domain: Domain, ip_kind: IpKind, trusted_only: bool) -> Vec<IpAddr> {
+    let result = match (ip_kind, trusted_only) {
+        (IpKind::Ipv4, false) => dns
+            .ipv4_lookup(domain)
+            .await
+            .map(|it| it.map(IpAddr::V4).collect()),
+        (IpKind::Ipv4, true) => dns
+            .ipv4_lookup_trusted(domain)
+            .await
+            .map(|it| it.map(IpAddr::V4).collect()),
+        (IpKind::Ipv6, false) => dns
+            .ipv6_lookup(domain)
+            .await
+            .map(|it| it.map(IpAddr::V6).collect()),
+        (IpKind::Ipv6, true) => dns
+            .ipv6_lookup_trusted(domain)
+            .await
+            .map(|it| it.map(IpAddr::V6).collect()),
+    };
+    result.unwrap_or_else(|err| {
+        tracing::trace!(err = %err, "[{ip_kind:?}] failed to resolve domain");
+        Vec::new()
+    })
+}
+
+/// An event of the connection race, sent by its spawned tasks.
+enum RaceEvent {
+    Resolved(IpKind, Vec<IpAddr>),
+    Attempted(SocketAddr, io::Result<TcpStream>),
+}
+
+/// The tasks spawned for a connection race,
+/// aborted once the race is over (or cancelled).
+#[derive(Default)]
+struct RaceTasks(Vec<JoinHandle<()>>);
+
+impl RaceTasks {
+    fn spawn(
+        &mut self,
+        executor: &Executor,
+        tx: &mpsc::UnboundedSender<RaceEvent>,
+        event: impl std::future::Future<Output = RaceEvent> + Send + 'static,
+    ) {
+        let tx = tx.clone();
+        self.0.retain(|handle| !handle.is_finished());
+        self.0.push(executor.spawn_task(async move {
+            let _ = tx.send(event.await);
+        }));
+    }
+}
+
+impl Drop for RaceTasks {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+/// Race connection attempts to the addresses the domain resolves to,
+/// as described by [RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305).
+///
+/// The lookups and attempts are spawned using the given [`Executor`],
+/// such that they are tracked by its graceful shutdown (if any).
+async fn happy_eyeballs(
+    executor: &Executor,
     dns: Dns,
-    ip_kind: IpKind,
     domain: Domain,
     port: u16,
-    tx: Sender<(TcpStream, SocketAddr)>,
-    connected: Arc<AtomicBool>,
-    sem: Arc<Semaphore>,
+    preference: IpPreference,
     trusted_only: bool,
-) {
-    let ip_it = match (ip_kind, trusted_only) {
-        (IpKind::Ipv4, false) => match dns.ipv4_lookup(domain).await {
-            Ok(it) => Either4::A(it.map(IpAddr::V4)),
-            Err(err) => {
-                tracing::trace!(err = %err, "[{ip_kind:?}] failed to resolve domain to IPv4 addresses");
-                return;
-            }
-        },
-        (IpKind::Ipv4, true) => match dns.ipv4_lookup_trusted(domain).await {
-            Ok(it) => Either4::B(it.map(IpAddr::V4)),
-            Err(err) => {
-                tracing::trace!(err = %err, "[{ip_kind:?}] failed to resolve domain to trusted IPv4 addresses");
-                return;
-            }
-        },
-        (IpKind::Ipv6, false) => match dns.ipv6_lookup(domain).await {
-            Ok(it) => Either4::C(it.map(IpAddr::V6)),
-            Err(err) => {
-                tracing::trace!(err = %err, "[{ip_kind:?}] failed to resolve domain to IPv6 addresses");
-                return;
-            }
-        },
-        (IpKind::Ipv6, true) => match dns.ipv6_lookup_trusted(domain).await {
-            Ok(it) => Either4::D(it.map(IpAddr::V6)),
-            Err(err) => {
-                tracing::trace!(err = %err, "[{ip_kind:?}] failed to resolve domain to trusted IPv6 addresses");
-                return;
-            }
-        },
+    attempt_delay: Duration,
+) -> Result<(TcpStream, SocketAddr), OpaqueError> {
+    let (preferred_kind, other_kind) = if preference.prefers_ipv6() {
+        (IpKind::Ipv6, IpKind::Ipv4)
+    } else {
+        (IpKind::Ipv4, IpKind::Ipv6)
+    };
+    let allowed = |kind| match kind {
+        IpKind::Ipv4 => preference.allows_ipv4(),
+        IpKind::Ipv6 => preference.allows_ipv6(),
     };
 
-    for (index, ip) in ip_it.enumerate() {
-        let addr = (ip, port).into();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut tasks = RaceTasks::default();
 
-        let sem = sem.clone();
-        let tx = tx.clone();
-        let connected = connected.clone();
+    // resolve both address families concurrently
+    let mut pending_lookups = 0;
+    for kind in [preferred_kind, other_kind] {
+        if allowed(kind) {
+            let (dns, domain) = (dns.clone(), domain.clone());
+            tasks.spawn(executor, &tx, async move {
+                RaceEvent::Resolved(kind, lookup(dns, domain, kind, trusted_only).await)
+            });
+            pending_lookups += 1;
+        }
+    }
+    let mut preferred_resolved = !allowed(preferred_kind);
+    let mut resolution_deadline: Option<Instant> = None;
 
-        // back off retries exponentially
-        if index > 0 {
-            let delay = match ip_kind {
-                IpKind::Ipv4 => Duration::from_micros((35 * 2 * index) as u64),
-                IpKind::Ipv6 => Duration::from_micros((21 * 2 * index) as u64),
+    let mut preferred_addrs = VecDeque::new();
+    let mut other_addrs = VecDeque::new();
+    let mut next_is_preferred = true;
+
+    let mut pending_attempts = 0;
+    let mut next_attempt_at: Option<Instant> = None;
+    let mut attempt_count = 0;
+    let mut last_err: Option<io::Error> = None;
+
+    loop {
+        // start a new attempt if the resolution and attempt delays allow it
+        let now = Instant::now();
+        let resolution_ready =
+            preferred_resolved || resolution_deadline.map(|d| d <= now).unwrap_or_default();
+        let attempt_ready = next_attempt_at.map(|at| at <= now).unwrap_or(true);
+        if resolution_ready && attempt_ready {
+            let next = if next_is_preferred {
+                preferred_addrs
+                    .pop_front()
+                    .or_else(|| other_addrs.pop_front())
+            } else {
+                other_addrs
+                    .pop_front()
+                    .or_else(|| preferred_addrs.pop_front())
             };
-            tokio::time::sleep(delay).await;
+            if let Some(ip) = next {
+                next_is_preferred = !next_is_preferred;
+                let addr = SocketAddr::new(ip, port);
+                let index = attempt_count;
+                attempt_count += 1;
+                tracing::trace!("#{index}: tcp connect attempt to {addr}");
+                tasks.spawn(executor, &tx, async move {
+                    RaceEvent::Attempted(addr, TcpStream::connect(addr).await)
+                });
+                pending_attempts += 1;
+                next_attempt_at = Some(now + attempt_delay);
+            }
         }
 
-        if connected.load(Ordering::SeqCst) {
-            tracing::trace!("[{ip_kind:?}] #{index}: abort connect loop to {addr} (connection already established)");
-            return;
+        let has_addrs = !preferred_addrs.is_empty() || !other_addrs.is_empty();
+        if pending_attempts == 0 && pending_lookups == 0 && !has_addrs {
+            break;
         }
 
-        tokio::spawn(async move {
-            let _permit = sem.acquire().await.unwrap();
-            if connected.load(Ordering::SeqCst) {
-                tracing::trace!("[{ip_kind:?}] #{index}: abort spawned attempt to {addr} (connection already established)");
-                return;
-            }
-
-            tracing::trace!("[{ip_kind:?}] #{index}: tcp connect attempt to {addr}");
+        let wake_at = if !resolution_ready {
+            resolution_deadline
+        } else if has_addrs {
+            next_attempt_at
+        } else {
+            None
+        };
 
-            match TcpStream::connect(&addr).await {
-                Ok(stream) => {
-                    tracing::trace!("[{ip_kind:?}] #{index}: tcp connection stablished to {addr}");
-                    if let Err(err) = tx.send((stream, addr)).await {
-                        tracing::trace!(err = %err, "[{ip_kind:?}] #{index}: failed to send resolved IP address");
+        tokio::select! {
+            // the sender is kept alive by this function, so the channel is never closed
+            Some(event) = rx.recv() => match event {
+                RaceEvent::Resolved(kind, ips) => {
+                    pending_lookups -= 1;
+                    tracing::trace!("[{kind:?}] resolved {} address(es) for {domain}", ips.len());
+                    if kind == preferred_kind {
+                        preferred_resolved = true;
+                        preferred_addrs.extend(ips);
+                    } else {
+                        if !preferred_resolved {
+                            resolution_deadline = Some(Instant::now() + RESOLUTION_DELAY);
+                        }
+                        other_addrs.extend(ips);
                     }
                 }
-                Err(err) => {
-                    tracing::trace!(err = %err, "[{ip_kind:?}] #{index}: tcp connector failed to connect");
+                RaceEvent::Attempted(addr, Ok(stream)) => {
+                    tracing::trace!("tcp connection established to {addr}");
+                    // all other (losing) attempts are cancelled when dropping the tasks
+                    return Ok((stream, addr));
                 }
-            };
-        });
+                RaceEvent::Attempted(addr, Err(err)) => {
+                    pending_attempts -= 1;
+                    tracing::trace!(err = %err, "tcp connect attempt to {addr} failed");
+                    last_err = Some(err);
+                    // do not wait for the attempt delay in case an attempt failed
+                    next_attempt_at = None;
+                }
+            },
+            _ = sleep_until(wake_at), if wake_at.is_some() => (),
+        }
+    }
+
+    let msg = format!("failed to connect to any resolved IP address for {domain} (port {port})");
+    Err(match last_err {
+        Some(last_err) => OpaqueError::from_std(ConnectError::new(msg, last_err)),
+        None => OpaqueError::from_display(msg),
+    })
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn ctx_with_overwrite(ips: &[&str]) -> Context<()> {
+        let mut ctx = Context::default();
+        let mut dns = Dns::default();
+        dns.insert_overwrite(
+            "example.com",
+            ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+        )
+        .unwrap();
+        *ctx.dns_mut() = dns;
+        ctx
+    }
+
+    #[tokio::test]
+    async fn test_connect_falls_back_to_other_family() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // ipv6 is preferred by default, but nothing listens on [::1]
+        let ctx = ctx_with_overwrite(&["::1", "127.0.0.1"]);
+        let authority = Authority::new(Host::try_from("example.com").unwrap(), port);
+        let (_stream, addr) = connect(&ctx, authority).await.unwrap();
+        assert_eq!(addr, SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+
+    #[tokio::test]
+    async fn test_connect_ip_preference() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut ctx = ctx_with_overwrite(&["::1", "127.0.0.1"]);
+        ctx.insert(IpPreference::Ipv6Only);
+        let authority = Authority::new(Host::try_from("example.com").unwrap(), port);
+        let err = connect(&ctx, authority.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("failed to connect"));
+
+        ctx.insert(IpPreference::PreferIpv4);
+        let (_stream, addr) = connect(&ctx, authority).await.unwrap();
+        assert!(addr.is_ipv4());
+    }
+
+    #[tokio::test]
+    async fn test_connect_attempt_delay_races_unresponsive_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // 192.0.2.0/24 (TEST-NET-1) is not routable, so the attempt hangs
+        // (or fails), and the second attempt should win after the attempt delay
+        let mut ctx = ctx_with_overwrite(&["192.0.2.1", "127.0.0.1"]);
+        ctx.insert(IpPreference::Ipv4Only);
+        let authority = Authority::new(Host::try_from("example.com").unwrap(), port);
+        let (_stream, addr) = tokio::time::timeout(
+            Duration::from_secs(2),
+            connect_inner(&ctx, authority, false, Duration::from_millis(50)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(addr, SocketAddr::from(([127, 0, 0, 1], port)));
     }
 }
@@ -5,7 +5,9 @@ pub mod service;
 
 mod connect;
 #[doc(inline)]
-pub use connect::{connect, connect_trusted};
+pub use connect::{
+    connect, connect_trusted, ConnectedAddress, IpPreference, DEFAULT_CONNECTION_ATTEMPT_DELAY,
+};
 
 #[cfg(feature = "http")]
 mod request;
@@ -7,12 +7,27 @@ use rama_net::{
     client::EstablishedClientConnection,
     transport::{TransportProtocol, TryRefIntoTransportContext},
 };
+use std::time::Duration;
 use tokio::net::TcpStream;
 
+use crate::client::{connect::connect_inner, ConnectedAddress, DEFAULT_CONNECTION_ATTEMPT_DELAY};
+
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 /// A connector which can be used to establish a TCP connection to a server.
-pub struct TcpConnector;
+///
+/// Domains are connected to using the Happy Eyeballs v2 algorithm
+/// ([RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305)),
+/// for which the address family preference can be set per request
+/// by inserting an [`IpPreference`] in the [`Context`].
+///
+/// The address to which the connection was established
+/// is inserted in the [`Context`] as a [`ConnectedAddress`].
+///
+/// [`IpPreference`]: crate::client::IpPreference
+pub struct TcpConnector {
+    attempt_delay: Duration,
+}
 
 impl TcpConnector {
     /// Create a new [`TcpConnector`], which is used to establish a connection to a server.
@@ -20,7 +35,32 @@ impl TcpConnector {
     /// You can use middleware around the [`TcpConnector`]
     /// or add connection pools, retry logic and more.
     pub const fn new() -> Self {
-        TcpConnector
+        TcpConnector {
+            attempt_delay: DEFAULT_CONNECTION_ATTEMPT_DELAY,
+        }
+    }
+
+    /// Set the delay between two connection attempts
+    /// to the resolved addresses of a domain.
+    ///
+    /// Defaults to [`DEFAULT_CONNECTION_ATTEMPT_DELAY`].
+    pub const fn with_connection_attempt_delay(mut self, delay: Duration) -> Self {
+        self.attempt_delay = delay;
+        self
+    }
+
+    /// Set the delay between two connection attempts
+    /// to the resolved addresses of a domain.
+    ///
+    /// Defaults to [`DEFAULT_CONNECTION_ATTEMPT_DELAY`].
+    pub fn set_connection_attempt_delay(&mut self, delay: Duration) -> &mut Self {
+        self.attempt_delay = delay;
+        self
+    }
+
+    /// Returns the delay between two connection attempts.
+    pub const fn connection_attempt_delay(&self) -> Duration {
+        self.attempt_delay
     }
 }
 
@@ -45,9 +85,11 @@ where
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
         if let Some(proxy) = ctx.get::<ProxyAddress>() {
-            let (conn, addr) = crate::client::connect_trusted(&ctx, proxy.authority.clone())
-                .await
-                .context("tcp connector: conncept to proxy")?;
+            let (conn, addr) =
+                connect_inner(&ctx, proxy.authority.clone(), true, self.attempt_delay)
+                    .await
+                    .context("tcp connector: conncept to proxy")?;
+            ctx.insert(ConnectedAddress::new(addr));
             return Ok(EstablishedClientConnection {
                 ctx,
                 req,
@@ -75,9 +117,10 @@ where
         }
 
         let authority = transport_ctx.authority.clone();
-        let (conn, addr) = crate::client::connect(&ctx, authority)
+        let (conn, addr) = connect_inner(&ctx, authority, false, self.attempt_delay)
             .await
             .context("tcp connector: connect to server")?;
+        ctx.insert(ConnectedAddress::new(addr));
 
         Ok(EstablishedClientConnection {
             ctx,
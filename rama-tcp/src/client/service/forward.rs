@@ -5,43 +5,13 @@ use rama_core::{
     Context, Layer, Service,
 };
 use rama_net::{
-    address::Authority,
+    address::{Authority, ForwardAuthority},
     client::{ConnectorService, EstablishedClientConnection},
     stream::Stream,
 };
-use rama_utils::macros::impl_deref;
 use std::{fmt, ops::DerefMut};
 use tokio::sync::Mutex;
 
-/// [`Forwarder`] using [`Forwarder::ctx`] requires this struct
-/// to be present in the [`Context`].
-#[derive(Debug, Clone)]
-pub struct ForwardAuthority(Authority);
-
-impl ForwardAuthority {
-    /// Create a new [`ForwardAuthority`] for the given target [`Authority`].
-    pub fn new(authority: impl Into<Authority>) -> Self {
-        Self(authority.into())
-    }
-}
-
-impl<A> From<A> for ForwardAuthority
-where
-    A: Into<Authority>,
-{
-    fn from(authority: A) -> Self {
-        Self::new(authority)
-    }
-}
-
-impl AsRef<Authority> for ForwardAuthority {
-    fn as_ref(&self) -> &Authority {
-        &self.0
-    }
-}
-
-impl_deref!(ForwardAuthority: Authority);
-
 #[derive(Debug, Clone)]
 enum ForwarderKind {
     Static(Authority),
@@ -157,7 +127,7 @@ where
             ForwarderKind::Static(target) => target.clone(),
             ForwarderKind::Dynamic => ctx
                 .get::<ForwardAuthority>()
-                .map(|f| f.0.clone())
+                .map(|f| f.as_ref().clone())
                 .ok_or_else(|| {
                     OpaqueError::from_display("missing forward authority").into_boxed()
                 })?,
@@ -2,7 +2,10 @@
 
 mod forward;
 #[doc(inline)]
-pub use forward::{ForwardAuthority, Forwarder};
+pub use forward::Forwarder;
+
+#[doc(inline)]
+pub use rama_net::address::ForwardAuthority;
 
 mod connector;
 #[doc(inline)]
@@ -0,0 +1,249 @@
+//! Responders for the ACME challenges, used to prove control over a domain.
+
+use super::proto::ChallengeType;
+use crate::{cert_store::ServerCert, dep::rcgen};
+use parking_lot::RwLock;
+use rama_core::{
+    error::{ErrorContext, OpaqueError},
+    Context, Service,
+};
+use rama_http_types::{
+    header::CONTENT_TYPE, Body, HeaderValue, IntoResponse, Request, Response, StatusCode,
+};
+use rama_net::address::Domain;
+use ring::digest::{digest, SHA256};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+
+/// The path prefix under which the HTTP-01 challenges are served.
+pub const HTTP01_CHALLENGE_PATH_PREFIX: &str = "/.well-known/acme-challenge/";
+
+/// A responder for ACME challenges of a single [`ChallengeType`],
+/// used by the [`AcmeClient`] to prove control over the domains of an order.
+///
+/// [`AcmeClient`]: super::AcmeClient
+pub trait ChallengeResponder: Send + Sync + 'static {
+    /// The [`ChallengeType`] this responder can respond to.
+    fn challenge_type(&self) -> ChallengeType;
+
+    /// Prepare the response to the challenge with the given token for the given domain,
+    /// prior to the ACME server validating it.
+    fn prepare(
+        &self,
+        domain: &Domain,
+        token: &str,
+        key_authorization: &str,
+    ) -> Result<(), OpaqueError>;
+
+    /// Remove the response to the challenge, once it is validated (or failed).
+    fn cleanup(&self, domain: &Domain, token: &str);
+}
+
+#[derive(Debug, Clone, Default)]
+/// A [`ChallengeResponder`] for HTTP-01 challenges,
+/// see [RFC 8555 section 8.3](https://datatracker.ietf.org/doc/html/rfc8555#section-8.3).
+///
+/// It is also a [`Service`] which serves the key authorizations of the prepared challenges
+/// on [`HTTP01_CHALLENGE_PATH_PREFIX`], responding with `404 Not Found` to all other requests.
+/// This service has to be reachable on port 80 of the domains to validate,
+/// e.g. by mounting it in the router of the plain text http server.
+pub struct Http01ChallengeResponder {
+    key_authorizations: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl Http01ChallengeResponder {
+    /// Create a new [`Http01ChallengeResponder`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the key authorization for the given challenge token, if prepared.
+    pub fn key_authorization(&self, token: &str) -> Option<String> {
+        self.key_authorizations.read().get(token).cloned()
+    }
+}
+
+impl ChallengeResponder for Http01ChallengeResponder {
+    fn challenge_type(&self) -> ChallengeType {
+        ChallengeType::Http01
+    }
+
+    fn prepare(
+        &self,
+        _domain: &Domain,
+        token: &str,
+        key_authorization: &str,
+    ) -> Result<(), OpaqueError> {
+        self.key_authorizations
+            .write()
+            .insert(token.to_owned(), key_authorization.to_owned());
+        Ok(())
+    }
+
+    fn cleanup(&self, _domain: &Domain, token: &str) {
+        self.key_authorizations.write().remove(token);
+    }
+}
+
+impl<State, ReqBody> Service<State, Request<ReqBody>> for Http01ChallengeResponder
+where
+    State: Send + Sync + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        req: Request<ReqBody>,
+    ) -> Result<Self::Response, Self::Error> {
+        let key_authorization = req
+            .uri()
+            .path()
+            .strip_prefix(HTTP01_CHALLENGE_PATH_PREFIX)
+            .and_then(|token| self.key_authorization(token));
+        Ok(match key_authorization {
+            Some(key_authorization) => {
+                let mut resp = Response::new(Body::from(key_authorization));
+                resp.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/octet-stream"),
+                );
+                resp
+            }
+            None => StatusCode::NOT_FOUND.into_response(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A [`ChallengeResponder`] for TLS-ALPN-01 challenges,
+/// see [RFC 8737](https://datatracker.ietf.org/doc/html/rfc8737).
+///
+/// The TLS acceptor of the domains to validate has to present the challenge certificate
+/// returned by [`TlsAlpn01ChallengeResponder::get`] to clients which only offer
+/// the `acme-tls/1` ALPN protocol, e.g. by using
+/// `rama_tls::rustls::server::TlsAlpn01Provider` for a rustls acceptor.
+/// The boring acceptor does not support this challenge, use the
+/// [`Http01ChallengeResponder`] instead.
+pub struct TlsAlpn01ChallengeResponder {
+    certs: Arc<RwLock<HashMap<Domain, Arc<ServerCert>>>>,
+}
+
+impl TlsAlpn01ChallengeResponder {
+    /// Create a new [`TlsAlpn01ChallengeResponder`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the challenge certificate for the given server name, if prepared.
+    pub fn get(&self, server_name: &Domain) -> Option<Arc<ServerCert>> {
+        self.certs.read().get(server_name).cloned()
+    }
+}
+
+impl ChallengeResponder for TlsAlpn01ChallengeResponder {
+    fn challenge_type(&self) -> ChallengeType {
+        ChallengeType::TlsAlpn01
+    }
+
+    fn prepare(
+        &self,
+        domain: &Domain,
+        _token: &str,
+        key_authorization: &str,
+    ) -> Result<(), OpaqueError> {
+        let key_pair = rcgen::KeyPair::generate().context("generate challenge key pair")?;
+        let mut params = rcgen::CertificateParams::new(vec![domain.as_str().to_owned()])
+            .context("create challenge certificate params")?;
+        params.custom_extensions = vec![rcgen::CustomExtension::new_acme_identifier(
+            digest(&SHA256, key_authorization.as_bytes()).as_ref(),
+        )];
+        let cert = params
+            .self_signed(&key_pair)
+            .context("create challenge certificate")?;
+
+        let cert = ServerCert::new(vec![cert.der().to_vec()], key_pair.serialize_der())
+            .context("create challenge server certificate")?;
+        self.certs.write().insert(domain.clone(), Arc::new(cert));
+        Ok(())
+    }
+
+    fn cleanup(&self, domain: &Domain, _token: &str) {
+        self.certs.write().remove(domain);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_http_types::BodyExtractExt;
+
+    #[tokio::test]
+    async fn test_http01_challenge_responder() {
+        let responder = Http01ChallengeResponder::new();
+        let domain = Domain::example();
+
+        let request = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+
+        let resp = responder
+            .serve(
+                Context::default(),
+                request("/.well-known/acme-challenge/token"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        responder
+            .prepare(&domain, "token", "token.thumbprint")
+            .unwrap();
+        let resp = responder
+            .serve(
+                Context::default(),
+                request("/.well-known/acme-challenge/token"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.try_into_string().await.unwrap(), "token.thumbprint");
+
+        let resp = responder
+            .serve(Context::default(), request("/token"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        responder.cleanup(&domain, "token");
+        let resp = responder
+            .serve(
+                Context::default(),
+                request("/.well-known/acme-challenge/token"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_tls_alpn01_challenge_responder() {
+        let responder = TlsAlpn01ChallengeResponder::new();
+        let domain = Domain::example();
+        assert!(responder.get(&domain).is_none());
+
+        responder
+            .prepare(&domain, "token", "token.thumbprint")
+            .unwrap();
+        let cert = responder.get(&domain).unwrap();
+        let params =
+            rcgen::CertificateParams::from_ca_cert_der(&cert.cert_chain_der()[0].as_slice().into())
+                .unwrap();
+        assert_eq!(
+            params.subject_alt_names,
+            vec![rcgen::SanType::DnsName("example.com".try_into().unwrap())]
+        );
+
+        responder.cleanup(&domain, "token");
+        assert!(responder.get(&domain).is_none());
+    }
+}
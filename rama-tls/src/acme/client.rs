@@ -0,0 +1,796 @@
+use super::{
+    challenge::ChallengeResponder,
+    key::AccountKey,
+    proto::{
+        AccountResource, AccountStatus, Authorization, AuthorizationStatus, Directory, Finalize,
+        Identifier, NewAccount, NewOrder, Order, OrderStatus, Problem,
+    },
+};
+use crate::{cert_store::ServerCert, dep::rcgen, issuer::CertKeyType};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use parking_lot::Mutex;
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Service,
+};
+use rama_http_types::{
+    header::{ACCEPT, CONTENT_TYPE, LOCATION, RETRY_AFTER},
+    Body, BodyExtractExt, Method, Request, Response,
+};
+use rama_net::address::Domain;
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// The directory URL of the Let's Encrypt production environment.
+pub const LETS_ENCRYPT_PRODUCTION_DIRECTORY: &str =
+    "https://acme-v02.api.letsencrypt.org/directory";
+
+/// The directory URL of the Let's Encrypt staging environment.
+pub const LETS_ENCRYPT_STAGING_DIRECTORY: &str =
+    "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+/// Default interval between two polls of a pending ACME resource.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default maximum number of polls of a pending ACME resource.
+pub const DEFAULT_POLL_ATTEMPTS: usize = 30;
+
+/// Maximum duration to wait between two polls, as requested by the ACME server using `Retry-After`.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+const REPLAY_NONCE: &str = "replay-nonce";
+const MAX_BAD_NONCE_RETRIES: usize = 3;
+
+#[derive(Debug, Clone)]
+/// An ACME account, as registered with an ACME server.
+pub struct Account {
+    key: Arc<AccountKey>,
+    url: String,
+}
+
+impl Account {
+    /// The [`AccountKey`] of this account.
+    pub fn key(&self) -> &AccountKey {
+        &self.key
+    }
+
+    /// The URL of this account, which is also its key ID.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// An ACME client ([RFC 8555](https://datatracker.ietf.org/doc/html/rfc8555)),
+/// to obtain certificates from an ACME server such as Let's Encrypt.
+///
+/// The client is generic over the http client service `S` used to make the requests,
+/// typically the `rama::http::client::HttpClient`.
+pub struct AcmeClient<S> {
+    http: S,
+    directory: Directory,
+    nonce: Mutex<Option<String>>,
+    poll_interval: Duration,
+    poll_attempts: usize,
+}
+
+impl<S: fmt::Debug> fmt::Debug for AcmeClient<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcmeClient")
+            .field("http", &self.http)
+            .field("directory", &self.directory)
+            .field("poll_interval", &self.poll_interval)
+            .field("poll_attempts", &self.poll_attempts)
+            .finish()
+    }
+}
+
+impl<S> AcmeClient<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+{
+    /// Create a new [`AcmeClient`] for the ACME server with the given directory URL,
+    /// e.g. [`LETS_ENCRYPT_PRODUCTION_DIRECTORY`].
+    pub async fn new(http: S, directory_url: &str) -> Result<Self, OpaqueError> {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(directory_url)
+            .body(Body::empty())
+            .context("create acme directory request")?;
+        let resp = http
+            .serve(Context::default(), req)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()))
+            .context("fetch acme directory")?;
+        if !resp.status().is_success() {
+            return Err(OpaqueError::from_display(format!(
+                "fetch acme directory: unexpected status code: {}",
+                resp.status()
+            )));
+        }
+        let directory = resp.try_into_json().await.context("parse acme directory")?;
+        Ok(Self::with_directory(http, directory))
+    }
+
+    /// Create a new [`AcmeClient`] for the ACME server with the given [`Directory`].
+    pub fn with_directory(http: S, directory: Directory) -> Self {
+        Self {
+            http,
+            directory,
+            nonce: Mutex::new(None),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            poll_attempts: DEFAULT_POLL_ATTEMPTS,
+        }
+    }
+
+    /// Set the interval between two polls of a pending order or authorization,
+    /// defaulting to [`DEFAULT_POLL_INTERVAL`].
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Set the interval between two polls of a pending order or authorization,
+    /// defaulting to [`DEFAULT_POLL_INTERVAL`].
+    pub fn set_poll_interval(&mut self, interval: Duration) -> &mut Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Set the maximum number of polls of a pending order or authorization,
+    /// defaulting to [`DEFAULT_POLL_ATTEMPTS`].
+    pub fn with_poll_attempts(mut self, attempts: usize) -> Self {
+        self.poll_attempts = attempts;
+        self
+    }
+
+    /// Set the maximum number of polls of a pending order or authorization,
+    /// defaulting to [`DEFAULT_POLL_ATTEMPTS`].
+    pub fn set_poll_attempts(&mut self, attempts: usize) -> &mut Self {
+        self.poll_attempts = attempts;
+        self
+    }
+
+    /// The [`Directory`] of the ACME server.
+    pub fn directory(&self) -> &Directory {
+        &self.directory
+    }
+
+    /// Register a new [`Account`] with the given [`AccountKey`] and contact URLs
+    /// (e.g. `mailto:admin@example.com`), agreeing to the terms of service of the ACME server.
+    ///
+    /// In case an account already exists for the given key, that account is returned instead.
+    pub async fn new_account(
+        &self,
+        key: AccountKey,
+        contact: &[String],
+    ) -> Result<Account, OpaqueError> {
+        let payload = serde_json::to_value(NewAccount {
+            contact,
+            terms_of_service_agreed: true,
+        })
+        .context("serialize new account")?;
+        let resp = self
+            .post(&key, None, &self.directory.new_account, Some(&payload))
+            .await
+            .context("create acme account")?;
+        let url = location(&resp).context("create acme account")?;
+        let account: AccountResource = resp.try_into_json().await.context("parse acme account")?;
+        if account.status != AccountStatus::Valid {
+            return Err(OpaqueError::from_display(format!(
+                "create acme account: unexpected status: {:?}",
+                account.status
+            )));
+        }
+        Ok(Account {
+            key: Arc::new(key),
+            url,
+        })
+    }
+
+    /// Create a new [`Order`] for a certificate for the given domains.
+    pub async fn new_order(
+        &self,
+        account: &Account,
+        domains: &[Domain],
+    ) -> Result<Order, OpaqueError> {
+        let identifiers: Vec<_> = domains
+            .iter()
+            .map(|domain| Identifier::dns(domain.as_str().trim_end_matches('.')))
+            .collect();
+        let payload = serde_json::to_value(NewOrder {
+            identifiers: &identifiers,
+        })
+        .context("serialize new order")?;
+        let resp = self
+            .post_account(account, &self.directory.new_order, Some(&payload))
+            .await
+            .context("create acme order")?;
+        let url = location(&resp).context("create acme order")?;
+        let mut order: Order = resp.try_into_json().await.context("parse acme order")?;
+        order.url = url;
+        Ok(order)
+    }
+
+    /// Fetch the (current) state of the [`Order`] with the given URL.
+    pub async fn order(&self, account: &Account, url: &str) -> Result<Order, OpaqueError> {
+        self.fetch_order(account, url).await.map(|(order, _)| order)
+    }
+
+    /// Fetch the [`Order`] with the given URL,
+    /// together with the `Retry-After` duration requested by the server, if any.
+    async fn fetch_order(
+        &self,
+        account: &Account,
+        url: &str,
+    ) -> Result<(Order, Option<Duration>), OpaqueError> {
+        let resp = self
+            .post_account(account, url, None)
+            .await
+            .context("fetch acme order")?;
+        let retry_after = retry_after(&resp);
+        let mut order: Order = resp.try_into_json().await.context("parse acme order")?;
+        order.url = url.to_owned();
+        Ok((order, retry_after))
+    }
+
+    /// Fetch the [`Authorization`] with the given URL.
+    pub async fn authorization(
+        &self,
+        account: &Account,
+        url: &str,
+    ) -> Result<Authorization, OpaqueError> {
+        self.fetch_authorization(account, url)
+            .await
+            .map(|(authz, _)| authz)
+    }
+
+    /// Fetch the [`Authorization`] with the given URL,
+    /// together with the `Retry-After` duration requested by the server, if any.
+    async fn fetch_authorization(
+        &self,
+        account: &Account,
+        url: &str,
+    ) -> Result<(Authorization, Option<Duration>), OpaqueError> {
+        let resp = self
+            .post_account(account, url, None)
+            .await
+            .context("fetch acme authorization")?;
+        let retry_after = retry_after(&resp);
+        let authz = resp
+            .try_into_json()
+            .await
+            .context("parse acme authorization")?;
+        Ok((authz, retry_after))
+    }
+
+    /// Notify the ACME server that the challenge with the given URL is ready to be validated.
+    pub async fn challenge_ready(&self, account: &Account, url: &str) -> Result<(), OpaqueError> {
+        self.post_account(account, url, Some(&serde_json::json!({})))
+            .await
+            .context("respond to acme challenge")?;
+        Ok(())
+    }
+
+    /// Finalize the given (ready) [`Order`] with the given DER encoded CSR.
+    pub async fn finalize(
+        &self,
+        account: &Account,
+        order: &Order,
+        csr_der: &[u8],
+    ) -> Result<Order, OpaqueError> {
+        let payload = serde_json::to_value(Finalize {
+            csr: URL_SAFE_NO_PAD.encode(csr_der),
+        })
+        .context("serialize finalize")?;
+        let resp = self
+            .post_account(account, &order.finalize, Some(&payload))
+            .await
+            .context("finalize acme order")?;
+        let mut order_finalized: Order = resp.try_into_json().await.context("parse acme order")?;
+        order_finalized.url = order.url.clone();
+        Ok(order_finalized)
+    }
+
+    /// Download the PEM encoded certificate chain with the given URL.
+    pub async fn certificate(&self, account: &Account, url: &str) -> Result<String, OpaqueError> {
+        let resp = self
+            .post_account(account, url, None)
+            .await
+            .context("download acme certificate")?;
+        resp.try_into_string()
+            .await
+            .context("read acme certificate")
+    }
+
+    /// Obtain a certificate for the given domains, using the given [`ChallengeResponder`]
+    /// to prove control over these domains.
+    ///
+    /// This creates a new order, responds to the challenges of its authorizations,
+    /// and finalizes it with a CSR for a newly generated key pair of the given [`CertKeyType`].
+    pub async fn issue(
+        &self,
+        account: &Account,
+        domains: &[Domain],
+        responder: &impl ChallengeResponder,
+        key_type: CertKeyType,
+    ) -> Result<ServerCert, OpaqueError> {
+        if domains.is_empty() {
+            return Err(OpaqueError::from_display(
+                "issue acme certificate: no domains",
+            ));
+        }
+
+        let order = self.new_order(account, domains).await?;
+        for url in &order.authorizations {
+            self.authorize(account, url, responder).await?;
+        }
+
+        let order = self
+            .poll_order(account, &order.url, OrderStatus::Pending)
+            .await?;
+        if order.status != OrderStatus::Ready {
+            return Err(order_error(
+                "issue acme certificate: order not ready",
+                &order,
+            ));
+        }
+
+        let key_pair = rcgen::KeyPair::generate_for(key_type.signature_algorithm())
+            .context("generate certificate key pair")?;
+        let params = rcgen::CertificateParams::new(
+            domains
+                .iter()
+                .map(|domain| domain.as_str().trim_end_matches('.').to_owned())
+                .collect::<Vec<_>>(),
+        )
+        .context("create csr params")?;
+        let csr = params.serialize_request(&key_pair).context("create csr")?;
+
+        let order = self.finalize(account, &order, csr.der()).await?;
+        let order = self
+            .poll_order(account, &order.url, OrderStatus::Processing)
+            .await?;
+        let certificate = match (order.status, &order.certificate) {
+            (OrderStatus::Valid, Some(url)) => url,
+            _ => {
+                return Err(order_error(
+                    "issue acme certificate: order not valid",
+                    &order,
+                ))
+            }
+        };
+
+        let cert_chain = self.certificate(account, certificate).await?;
+        ServerCert::from_pem(&cert_chain, &key_pair.serialize_pem())
+    }
+
+    async fn authorize(
+        &self,
+        account: &Account,
+        url: &str,
+        responder: &impl ChallengeResponder,
+    ) -> Result<(), OpaqueError> {
+        let authz = self.authorization(account, url).await?;
+        if authz.status == AuthorizationStatus::Valid {
+            return Ok(());
+        }
+
+        let domain: Domain = authz
+            .identifier
+            .value
+            .parse()
+            .context("parse acme authorization identifier")?;
+        let challenge = authz
+            .challenges
+            .iter()
+            .find(|challenge| challenge.kind == responder.challenge_type())
+            .ok_or_else(|| {
+                OpaqueError::from_display(format!(
+                    "acme authorization for {domain}: no {:?} challenge offered",
+                    responder.challenge_type()
+                ))
+            })?;
+
+        let key_authorization = account.key.key_authorization(&challenge.token);
+        responder.prepare(&domain, &challenge.token, &key_authorization)?;
+        let result = self
+            .complete_challenge(account, url, &challenge.url)
+            .await
+            .with_context(|| format!("acme authorization for {domain}"));
+        responder.cleanup(&domain, &challenge.token);
+        result
+    }
+
+    async fn complete_challenge(
+        &self,
+        account: &Account,
+        authz_url: &str,
+        challenge_url: &str,
+    ) -> Result<(), OpaqueError> {
+        self.challenge_ready(account, challenge_url).await?;
+        let mut wait = self.poll_interval;
+        for _ in 0..self.poll_attempts {
+            tokio::time::sleep(wait).await;
+            let (authz, retry_after) = self.fetch_authorization(account, authz_url).await?;
+            match authz.status {
+                AuthorizationStatus::Pending => {
+                    wait = retry_after.unwrap_or(self.poll_interval);
+                }
+                AuthorizationStatus::Valid => return Ok(()),
+                status => {
+                    let problem = authz
+                        .challenges
+                        .into_iter()
+                        .find_map(|challenge| challenge.error);
+                    let msg = format!("unexpected authorization status: {status:?}");
+                    return Err(match problem {
+                        Some(problem) => problem.context(msg),
+                        None => OpaqueError::from_display(msg),
+                    });
+                }
+            }
+        }
+        Err(OpaqueError::from_display(
+            "authorization still pending after max poll attempts",
+        ))
+    }
+
+    async fn poll_order(
+        &self,
+        account: &Account,
+        url: &str,
+        pending_status: OrderStatus,
+    ) -> Result<Order, OpaqueError> {
+        for _ in 0..self.poll_attempts {
+            let (order, retry_after) = self.fetch_order(account, url).await?;
+            if order.status != pending_status {
+                return Ok(order);
+            }
+            tokio::time::sleep(retry_after.unwrap_or(self.poll_interval)).await;
+        }
+        Err(OpaqueError::from_display(format!(
+            "acme order still {pending_status:?} after max poll attempts"
+        )))
+    }
+
+    async fn post_account(
+        &self,
+        account: &Account,
+        url: &str,
+        payload: Option<&serde_json::Value>,
+    ) -> Result<Response, OpaqueError> {
+        self.post(&account.key, Some(&account.url), url, payload)
+            .await
+    }
+
+    async fn post(
+        &self,
+        key: &AccountKey,
+        key_id: Option<&str>,
+        url: &str,
+        payload: Option<&serde_json::Value>,
+    ) -> Result<Response, OpaqueError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+
+            let nonce = self.nonce().await?;
+            let jws = key.sign(key_id, &nonce, url, payload)?;
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri(url)
+                .header(CONTENT_TYPE, "application/jose+json")
+                .header(
+                    ACCEPT,
+                    "application/json, application/pem-certificate-chain",
+                )
+                .body(Body::from(
+                    serde_json::to_vec(&jws).context("serialize jws")?,
+                ))
+                .context("create acme request")?;
+
+            let resp = self.send(req).await?;
+            if resp.status().is_success() {
+                return Ok(resp);
+            }
+
+            let status = resp.status();
+            let problem: Problem = resp
+                .try_into_json()
+                .await
+                .with_context(|| format!("parse acme error response with status code: {status}"))?;
+            if problem.is_bad_nonce() && attempt < MAX_BAD_NONCE_RETRIES {
+                tracing::debug!(%url, "acme client: retry request with new nonce");
+                continue;
+            }
+            return Err(problem.into_opaque());
+        }
+    }
+
+    async fn nonce(&self) -> Result<String, OpaqueError> {
+        if let Some(nonce) = self.nonce.lock().take() {
+            return Ok(nonce);
+        }
+
+        let req = Request::builder()
+            .method(Method::HEAD)
+            .uri(&self.directory.new_nonce)
+            .body(Body::empty())
+            .context("create acme nonce request")?;
+        self.send(req).await.context("fetch acme nonce")?;
+        self.nonce
+            .lock()
+            .take()
+            .ok_or_else(|| OpaqueError::from_display("fetch acme nonce: no nonce returned"))
+    }
+
+    async fn send(&self, req: Request) -> Result<Response, OpaqueError> {
+        let resp = self
+            .http
+            .serve(Context::default(), req)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()))
+            .context("send acme request")?;
+        if let Some(nonce) = resp
+            .headers()
+            .get(REPLAY_NONCE)
+            .and_then(|value| value.to_str().ok())
+        {
+            *self.nonce.lock() = Some(nonce.to_owned());
+        }
+        Ok(resp)
+    }
+}
+
+fn location(resp: &Response) -> Result<String, OpaqueError> {
+    resp.headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| OpaqueError::from_display("missing location header"))
+}
+
+/// Returns the duration to wait prior to polling again, as requested by the ACME server
+/// using the `Retry-After` header (in seconds or as an http date), capped to [`MAX_RETRY_AFTER`].
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let value = resp.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let wait = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    };
+    Some(wait.min(MAX_RETRY_AFTER))
+}
+
+fn order_error(msg: &'static str, order: &Order) -> OpaqueError {
+    let msg = format!("{msg}: status {:?}", order.status);
+    match &order.error {
+        Some(problem) => problem.clone().context(msg),
+        None => OpaqueError::from_display(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acme::Http01ChallengeResponder;
+    use rama_http_types::StatusCode;
+    use serde_json::json;
+    use std::convert::Infallible;
+
+    const BASE: &str = "http://acme.test";
+
+    /// A minimal in-memory ACME server, which validates the HTTP-01 challenge
+    /// by checking the key authorization prepared in the responder.
+    struct MockAcmeServer {
+        responder: Http01ChallengeResponder,
+        key_authorization: String,
+        state: Mutex<MockState>,
+    }
+
+    #[derive(Default)]
+    struct MockState {
+        nonce: usize,
+        authz_valid: bool,
+        cert_pem: Option<String>,
+    }
+
+    impl MockAcmeServer {
+        fn add_nonce(&self, resp: &mut Response) {
+            let mut state = self.state.lock();
+            state.nonce += 1;
+            resp.headers_mut()
+                .insert(REPLAY_NONCE, state.nonce.to_string().parse().unwrap());
+        }
+
+        fn order(&self) -> serde_json::Value {
+            let state = self.state.lock();
+            let status = match (state.authz_valid, &state.cert_pem) {
+                (_, Some(_)) => "valid",
+                (true, None) => "ready",
+                (false, None) => "pending",
+            };
+            json!({
+                "status": status,
+                "identifiers": [{ "type": "dns", "value": "example.com" }],
+                "authorizations": [format!("{BASE}/authz/1")],
+                "finalize": format!("{BASE}/order/1/finalize"),
+                "certificate": state.cert_pem.as_ref().map(|_| format!("{BASE}/cert/1")),
+            })
+        }
+
+        fn authz(&self) -> serde_json::Value {
+            let status = if self.state.lock().authz_valid {
+                "valid"
+            } else {
+                "pending"
+            };
+            json!({
+                "status": status,
+                "identifier": { "type": "dns", "value": "example.com" },
+                "challenges": [
+                    { "type": "dns-01", "url": format!("{BASE}/chall/2"), "status": "pending", "token": "dns" },
+                    { "type": "http-01", "url": format!("{BASE}/chall/1"), "status": status, "token": "token" }
+                ]
+            })
+        }
+
+        fn finalize(&self, payload: serde_json::Value) {
+            let csr_der = URL_SAFE_NO_PAD
+                .decode(payload["csr"].as_str().unwrap())
+                .unwrap();
+            let csr = rcgen::CertificateSigningRequestParams::from_der(&csr_der.into()).unwrap();
+            let ca_key = rcgen::KeyPair::generate().unwrap();
+            let ca = rcgen::CertificateParams::new(Vec::new())
+                .unwrap()
+                .self_signed(&ca_key)
+                .unwrap();
+            let cert = csr.signed_by(&ca, &ca_key).unwrap();
+            self.state.lock().cert_pem = Some(format!("{}{}", cert.pem(), ca.pem()));
+        }
+    }
+
+    impl Service<(), Request> for MockAcmeServer {
+        type Response = Response;
+        type Error = Infallible;
+
+        async fn serve(&self, _ctx: Context<()>, req: Request) -> Result<Response, Infallible> {
+            let path = req.uri().path().to_owned();
+            let payload = if req.method() == Method::POST {
+                let jws: serde_json::Value = req.into_body().try_into_json().await.unwrap();
+                let payload = URL_SAFE_NO_PAD
+                    .decode(jws["payload"].as_str().unwrap())
+                    .unwrap();
+                (!payload.is_empty()).then(|| serde_json::from_slice(&payload).unwrap())
+            } else {
+                None
+            };
+
+            let (status, location, body) = match path.as_str() {
+                "/dir" => (
+                    StatusCode::OK,
+                    None,
+                    json!({
+                        "newNonce": format!("{BASE}/nonce"),
+                        "newAccount": format!("{BASE}/account"),
+                        "newOrder": format!("{BASE}/order"),
+                    }),
+                ),
+                "/nonce" => (StatusCode::OK, None, json!({})),
+                "/account" => (
+                    StatusCode::CREATED,
+                    Some(format!("{BASE}/account/1")),
+                    json!({ "status": "valid" }),
+                ),
+                "/order" => (
+                    StatusCode::CREATED,
+                    Some(format!("{BASE}/order/1")),
+                    self.order(),
+                ),
+                "/order/1" => (StatusCode::OK, None, self.order()),
+                "/authz/1" => (StatusCode::OK, None, self.authz()),
+                "/chall/1" => {
+                    let valid = self.responder.key_authorization("token").as_deref()
+                        == Some(self.key_authorization.as_str());
+                    self.state.lock().authz_valid = valid;
+                    (StatusCode::OK, None, json!({}))
+                }
+                "/order/1/finalize" => {
+                    self.finalize(payload.unwrap());
+                    (StatusCode::OK, None, self.order())
+                }
+                "/cert/1" => {
+                    let mut resp =
+                        Response::new(Body::from(self.state.lock().cert_pem.clone().unwrap()));
+                    self.add_nonce(&mut resp);
+                    return Ok(resp);
+                }
+                _ => (
+                    StatusCode::NOT_FOUND,
+                    None,
+                    json!({ "type": "urn:ietf:params:acme:error:malformed" }),
+                ),
+            };
+
+            let mut resp = Response::new(Body::from(body.to_string()));
+            *resp.status_mut() = status;
+            if let Some(location) = location {
+                resp.headers_mut()
+                    .insert(LOCATION, location.parse().unwrap());
+            }
+            self.add_nonce(&mut resp);
+            Ok(resp)
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let resp = |value: Option<&str>| {
+            let mut resp = Response::new(Body::empty());
+            if let Some(value) = value {
+                resp.headers_mut()
+                    .insert(RETRY_AFTER, value.parse().unwrap());
+            }
+            resp
+        };
+
+        assert_eq!(retry_after(&resp(None)), None);
+        assert_eq!(retry_after(&resp(Some("foo"))), None);
+        assert_eq!(retry_after(&resp(Some("3"))), Some(Duration::from_secs(3)));
+        assert_eq!(retry_after(&resp(Some("3600"))), Some(MAX_RETRY_AFTER));
+        assert_eq!(
+            retry_after(&resp(Some("Wed, 21 Oct 2015 07:28:00 GMT"))),
+            Some(Duration::ZERO)
+        );
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(30));
+        let wait = retry_after(&resp(Some(&date))).unwrap();
+        assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_acme_client_issue() {
+        let key = AccountKey::generate().unwrap();
+        let responder = Http01ChallengeResponder::new();
+        let server = MockAcmeServer {
+            responder: responder.clone(),
+            key_authorization: key.key_authorization("token"),
+            state: Mutex::new(MockState::default()),
+        };
+
+        let client = AcmeClient::new(server, &format!("{BASE}/dir"))
+            .await
+            .unwrap()
+            .with_poll_interval(Duration::from_millis(1));
+        let account = client
+            .new_account(key, &["mailto:admin@example.com".to_owned()])
+            .await
+            .unwrap();
+        assert_eq!(account.url(), format!("{BASE}/account/1"));
+
+        let cert = client
+            .issue(
+                &account,
+                &[Domain::example()],
+                &responder,
+                CertKeyType::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(cert.cert_chain_der().len(), 2);
+        let params =
+            rcgen::CertificateParams::from_ca_cert_der(&cert.cert_chain_der()[0].as_slice().into())
+                .unwrap();
+        assert_eq!(
+            params.subject_alt_names,
+            vec![rcgen::SanType::DnsName("example.com".try_into().unwrap())]
+        );
+
+        // the challenge response is cleaned up once validated
+        assert!(responder.key_authorization("token").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+//! ACME account key, used to sign the requests made to the ACME server
+//! as JSON Web Signatures ([RFC 7515](https://datatracker.ietf.org/doc/html/rfc7515)).
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rama_core::error::{ErrorContext, OpaqueError};
+use ring::{
+    digest::{digest, SHA256},
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair as _, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::Serialize;
+use std::fmt;
+
+/// The key of an ACME account, an ECDSA P-256 key pair,
+/// used to sign all requests made for that account (`ES256`).
+pub struct AccountKey {
+    key_pair: EcdsaKeyPair,
+    pkcs8: Vec<u8>,
+    rng: SystemRandom,
+}
+
+impl fmt::Debug for AccountKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountKey")
+            .field("thumbprint", &self.thumbprint())
+            .finish()
+    }
+}
+
+impl AccountKey {
+    /// Generate a new (random) [`AccountKey`].
+    pub fn generate() -> Result<Self, OpaqueError> {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .map_err(|_| OpaqueError::from_display("generate acme account key"))?;
+        Self::from_pkcs8_der(pkcs8.as_ref())
+    }
+
+    /// Create an [`AccountKey`] from the given DER encoded (PKCS#8) ECDSA P-256 private key.
+    pub fn from_pkcs8_der(der: &[u8]) -> Result<Self, OpaqueError> {
+        let rng = SystemRandom::new();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, der, &rng)
+            .map_err(|err| OpaqueError::from_display(err.to_string()))
+            .context("parse acme account key")?;
+        Ok(Self {
+            key_pair,
+            pkcs8: der.to_vec(),
+            rng,
+        })
+    }
+
+    /// Create an [`AccountKey`] from the given PEM encoded (PKCS#8) ECDSA P-256 private key.
+    pub fn from_pem(pem: &str) -> Result<Self, OpaqueError> {
+        let pem = pem::parse(pem).context("parse acme account key (pem)")?;
+        Self::from_pkcs8_der(pem.contents())
+    }
+
+    /// The DER encoded (PKCS#8) private key, e.g. to persist the [`AccountKey`].
+    pub fn private_key_der(&self) -> &[u8] {
+        &self.pkcs8
+    }
+
+    /// The PEM encoded (PKCS#8) private key, e.g. to persist the [`AccountKey`].
+    pub fn private_key_pem(&self) -> String {
+        pem::encode(&pem::Pem::new("PRIVATE KEY", self.pkcs8.clone()))
+    }
+
+    /// The JWK thumbprint of this key, as defined in
+    /// [RFC 7638](https://datatracker.ietf.org/doc/html/rfc7638).
+    pub fn thumbprint(&self) -> String {
+        // the members of the jwk are serialized in lexicographic order,
+        // without whitespace, as required for the thumbprint
+        let jwk = serde_json::to_vec(&self.jwk()).expect("serialize jwk");
+        URL_SAFE_NO_PAD.encode(digest(&SHA256, &jwk))
+    }
+
+    /// The key authorization for the given challenge token, as defined in
+    /// [RFC 8555 section 8.1](https://datatracker.ietf.org/doc/html/rfc8555#section-8.1).
+    pub fn key_authorization(&self, token: &str) -> String {
+        format!("{token}.{}", self.thumbprint())
+    }
+
+    fn jwk(&self) -> Jwk {
+        // uncompressed point: 0x04 || x || y
+        let public_key = self.key_pair.public_key().as_ref();
+        Jwk {
+            crv: "P-256",
+            kty: "EC",
+            x: URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+            y: URL_SAFE_NO_PAD.encode(&public_key[33..]),
+        }
+    }
+
+    /// Sign the given payload, using the JWK of this key in the protected header
+    /// when no key ID (account URL) is given.
+    ///
+    /// No payload results in an empty payload, as used for POST-as-GET requests.
+    pub(super) fn sign(
+        &self,
+        key_id: Option<&str>,
+        nonce: &str,
+        url: &str,
+        payload: Option<&serde_json::Value>,
+    ) -> Result<Jws, OpaqueError> {
+        let protected = Protected {
+            alg: "ES256",
+            jwk: match key_id {
+                Some(_) => None,
+                None => Some(self.jwk()),
+            },
+            kid: key_id,
+            nonce,
+            url,
+        };
+        let protected = URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&protected).context("serialize jws protected header")?);
+        let payload = match payload {
+            Some(payload) => URL_SAFE_NO_PAD
+                .encode(serde_json::to_vec(payload).context("serialize jws payload")?),
+            None => String::new(),
+        };
+
+        let signature = self
+            .key_pair
+            .sign(&self.rng, format!("{protected}.{payload}").as_bytes())
+            .map_err(|_| OpaqueError::from_display("sign jws"))?;
+
+        Ok(Jws {
+            protected,
+            payload,
+            signature: URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Jwk {
+    crv: &'static str,
+    kty: &'static str,
+    x: String,
+    y: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Protected<'a> {
+    alg: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jwk: Option<Jwk>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kid: Option<&'a str>,
+    nonce: &'a str,
+    url: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+/// A JSON Web Signature, using the flattened JSON serialization.
+pub(super) struct Jws {
+    protected: String,
+    payload: String,
+    signature: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+
+    #[test]
+    fn test_account_key_pem_roundtrip() {
+        let key = AccountKey::generate().unwrap();
+        let pem = key.private_key_pem();
+        let parsed = AccountKey::from_pem(&pem).unwrap();
+        assert_eq!(key.thumbprint(), parsed.thumbprint());
+        assert_eq!(
+            key.key_authorization("token"),
+            format!("token.{}", key.thumbprint())
+        );
+    }
+
+    #[test]
+    fn test_account_key_sign() {
+        let key = AccountKey::generate().unwrap();
+        let payload = serde_json::json!({"termsOfServiceAgreed": true});
+        let jws = key
+            .sign(
+                None,
+                "nonce",
+                "https://example.com/acme/new-account",
+                Some(&payload),
+            )
+            .unwrap();
+
+        let protected: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(&jws.protected).unwrap()).unwrap();
+        assert_eq!(protected["alg"], "ES256");
+        assert_eq!(protected["nonce"], "nonce");
+        assert_eq!(protected["jwk"]["kty"], "EC");
+        assert!(protected.get("kid").is_none());
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(
+                &URL_SAFE_NO_PAD.decode(&jws.payload).unwrap()
+            )
+            .unwrap(),
+            payload
+        );
+
+        let public_key = UnparsedPublicKey::new(
+            &ECDSA_P256_SHA256_FIXED,
+            key.key_pair.public_key().as_ref().to_vec(),
+        );
+        public_key
+            .verify(
+                format!("{}.{}", jws.protected, jws.payload).as_bytes(),
+                &URL_SAFE_NO_PAD.decode(&jws.signature).unwrap(),
+            )
+            .unwrap();
+
+        let jws = key
+            .sign(
+                Some("https://example.com/acme/acct/1"),
+                "nonce",
+                "url",
+                None,
+            )
+            .unwrap();
+        let protected: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(&jws.protected).unwrap()).unwrap();
+        assert_eq!(protected["kid"], "https://example.com/acme/acct/1");
+        assert!(protected.get("jwk").is_none());
+        assert!(jws.payload.is_empty());
+    }
+}
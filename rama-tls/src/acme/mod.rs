@@ -0,0 +1,57 @@
+//! ACME client for automatic certificate provisioning,
+//! as defined in [RFC 8555](https://datatracker.ietf.org/doc/html/rfc8555),
+//! e.g. to obtain certificates from [Let's Encrypt](https://letsencrypt.org).
+//!
+//! - [`AcmeClient`]: registers accounts, creates and finalizes orders
+//!   and completes their challenges using a [`ChallengeResponder`];
+//! - [`Http01ChallengeResponder`]: responds to HTTP-01 challenges, as a rama http [`Service`];
+//! - [`TlsAlpn01ChallengeResponder`]: responds to TLS-ALPN-01 challenges,
+//!   served by a rustls acceptor using `rama_tls::rustls::server::TlsAlpn01Provider`;
+//! - [`AcmeRenewer`]: keeps renewing an obtained certificate, swapping it into
+//!   the running acceptors using a [`LiveCertStoreSetter`].
+//!
+//! The HTTP-01 challenge can be used in combination with any acceptor (rustls and boring),
+//! while the TLS-ALPN-01 challenge is only supported for the rustls acceptor.
+//!
+//! The client can be tested against a local [pebble](https://github.com/letsencrypt/pebble)
+//! instance, its directory being served on `https://localhost:14000/dir` by default.
+//!
+//! [`Service`]: rama_core::Service
+//! [`LiveCertStoreSetter`]: crate::cert_store::LiveCertStoreSetter
+//!
+//! # Examples
+//!
+//! See the [Examples Directory](https://github.com/plabayo/rama/tree/main/examples):
+//!
+//! - [/examples/tls_acme.rs](https://github.com/plabayo/rama/tree/main/examples/tls_acme.rs):
+//!   obtains and renews a certificate over ACME for a rustls https server,
+//!   responding to the HTTP-01 challenges with a plain text http server.
+
+mod proto;
+#[doc(inline)]
+pub use proto::{
+    AccountResource, AccountStatus, Authorization, AuthorizationStatus, Challenge, ChallengeStatus,
+    ChallengeType, Directory, DirectoryMeta, Identifier, Order, OrderStatus, Problem,
+};
+
+mod key;
+#[doc(inline)]
+pub use key::AccountKey;
+
+mod challenge;
+#[doc(inline)]
+pub use challenge::{
+    ChallengeResponder, Http01ChallengeResponder, TlsAlpn01ChallengeResponder,
+    HTTP01_CHALLENGE_PATH_PREFIX,
+};
+
+mod client;
+#[doc(inline)]
+pub use client::{
+    Account, AcmeClient, DEFAULT_POLL_ATTEMPTS, DEFAULT_POLL_INTERVAL,
+    LETS_ENCRYPT_PRODUCTION_DIRECTORY, LETS_ENCRYPT_STAGING_DIRECTORY,
+};
+
+mod renew;
+#[doc(inline)]
+pub use renew::{AcmeRenewer, DEFAULT_RENEW_BEFORE, DEFAULT_RETRY_INTERVAL};
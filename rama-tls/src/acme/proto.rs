@@ -0,0 +1,346 @@
+//! ACME protocol types, as defined in
+//! [RFC 8555 section 7](https://datatracker.ietf.org/doc/html/rfc8555#section-7).
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// The directory of an ACME server, containing the URLs of its resources.
+pub struct Directory {
+    /// URL to request a new nonce.
+    pub new_nonce: String,
+    /// URL to create a new account.
+    pub new_account: String,
+    /// URL to create a new order.
+    pub new_order: String,
+    /// URL to revoke a certificate.
+    #[serde(default)]
+    pub revoke_cert: Option<String>,
+    /// URL to change the key of an account.
+    #[serde(default)]
+    pub key_change: Option<String>,
+    /// Metadata of the ACME server.
+    #[serde(default)]
+    pub meta: Option<DirectoryMeta>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// Metadata of an ACME server, part of its [`Directory`].
+pub struct DirectoryMeta {
+    /// URL of the current terms of service.
+    #[serde(default)]
+    pub terms_of_service: Option<String>,
+    /// URL of the website of the ACME server.
+    #[serde(default)]
+    pub website: Option<String>,
+    /// Hostnames which the ACME server recognizes as referring to itself for CAA purposes.
+    #[serde(default)]
+    pub caa_identities: Vec<String>,
+    /// Whether an external account binding is required to create a new account.
+    #[serde(default)]
+    pub external_account_required: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct NewAccount<'a> {
+    pub(super) contact: &'a [String],
+    pub(super) terms_of_service_agreed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The status of an ACME account.
+pub enum AccountStatus {
+    /// The account is valid.
+    Valid,
+    /// The account was deactivated by its owner.
+    Deactivated,
+    /// The account was revoked by the ACME server.
+    Revoked,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// An ACME account resource.
+pub struct AccountResource {
+    /// The status of the account.
+    pub status: AccountStatus,
+    /// The contact URLs of the account.
+    #[serde(default)]
+    pub contact: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// An identifier for which a certificate is requested.
+pub struct Identifier {
+    #[serde(rename = "type")]
+    /// The type of the identifier, e.g. `dns`.
+    pub kind: String,
+    /// The value of the identifier, e.g. `example.com`.
+    pub value: String,
+}
+
+impl Identifier {
+    /// Create a new DNS [`Identifier`] for the given (domain) name.
+    pub fn dns(value: impl Into<String>) -> Self {
+        Self {
+            kind: "dns".to_owned(),
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct NewOrder<'a> {
+    pub(super) identifiers: &'a [Identifier],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The status of an ACME [`Order`].
+pub enum OrderStatus {
+    /// Not all authorizations of the order are valid yet.
+    Pending,
+    /// All authorizations are valid, the order can be finalized.
+    Ready,
+    /// The certificate is being issued.
+    Processing,
+    /// The certificate was issued.
+    Valid,
+    /// The order failed and can no longer be used.
+    Invalid,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// An ACME order resource, a request for a certificate.
+pub struct Order {
+    /// The URL of the order, not part of the resource itself
+    /// but returned by the ACME server in the `Location` header.
+    #[serde(skip)]
+    pub url: String,
+    /// The status of the order.
+    pub status: OrderStatus,
+    /// The identifiers the order is for.
+    pub identifiers: Vec<Identifier>,
+    /// The URLs of the authorizations to complete for this order.
+    pub authorizations: Vec<String>,
+    /// The URL to finalize the order, once it is ready.
+    pub finalize: String,
+    /// The URL of the issued certificate (chain), once the order is valid.
+    #[serde(default)]
+    pub certificate: Option<String>,
+    /// The error which made the order invalid, if any.
+    #[serde(default)]
+    pub error: Option<Problem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct Finalize {
+    pub(super) csr: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The status of an ACME [`Authorization`].
+pub enum AuthorizationStatus {
+    /// None of the challenges is completed yet.
+    Pending,
+    /// One of the challenges was completed successfully.
+    Valid,
+    /// The authorization failed.
+    Invalid,
+    /// The authorization was deactivated by the client.
+    Deactivated,
+    /// The authorization expired.
+    Expired,
+    /// The authorization was revoked by the ACME server.
+    Revoked,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// An ACME authorization resource, the proof of control over an [`Identifier`].
+pub struct Authorization {
+    /// The identifier the authorization is for.
+    pub identifier: Identifier,
+    /// The status of the authorization.
+    pub status: AuthorizationStatus,
+    /// The challenges which can be completed to prove control over the identifier.
+    pub challenges: Vec<Challenge>,
+    /// Whether the authorization is for a wildcard domain name.
+    #[serde(default)]
+    pub wildcard: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+/// The type of an ACME [`Challenge`].
+pub enum ChallengeType {
+    #[serde(rename = "http-01")]
+    /// Prove control by serving the key authorization over HTTP,
+    /// see [RFC 8555 section 8.3](https://datatracker.ietf.org/doc/html/rfc8555#section-8.3).
+    Http01,
+    #[serde(rename = "dns-01")]
+    /// Prove control by provisioning a DNS TXT record,
+    /// see [RFC 8555 section 8.4](https://datatracker.ietf.org/doc/html/rfc8555#section-8.4).
+    Dns01,
+    #[serde(rename = "tls-alpn-01")]
+    /// Prove control by serving a specific certificate for the `acme-tls/1` ALPN protocol,
+    /// see [RFC 8737](https://datatracker.ietf.org/doc/html/rfc8737).
+    TlsAlpn01,
+    #[serde(other)]
+    /// A challenge type not known by rama.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The status of an ACME [`Challenge`].
+pub enum ChallengeStatus {
+    /// The challenge is not yet ready to be validated.
+    Pending,
+    /// The challenge is being validated by the ACME server.
+    Processing,
+    /// The challenge was validated successfully.
+    Valid,
+    /// The challenge failed.
+    Invalid,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// An ACME challenge, part of an [`Authorization`].
+pub struct Challenge {
+    #[serde(rename = "type")]
+    /// The type of the challenge.
+    pub kind: ChallengeType,
+    /// The URL of the challenge, used to respond to it.
+    pub url: String,
+    /// The status of the challenge.
+    pub status: ChallengeStatus,
+    /// The token of the challenge.
+    #[serde(default)]
+    pub token: String,
+    /// The error which made the challenge invalid, if any.
+    #[serde(default)]
+    pub error: Option<Problem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// An error returned by the ACME server,
+/// see [RFC 7807](https://datatracker.ietf.org/doc/html/rfc7807).
+pub struct Problem {
+    #[serde(rename = "type", default)]
+    /// The type of the problem, e.g. `urn:ietf:params:acme:error:badNonce`.
+    pub kind: String,
+    /// A human readable explanation of the problem.
+    #[serde(default)]
+    pub detail: Option<String>,
+    /// The HTTP status code of the problem.
+    #[serde(default)]
+    pub status: Option<u16>,
+}
+
+impl Problem {
+    /// Returns `true` in case the problem is caused by an invalid (e.g. expired) nonce,
+    /// in which case the request can be retried with a new nonce.
+    pub fn is_bad_nonce(&self) -> bool {
+        self.kind == "urn:ietf:params:acme:error:badNonce"
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "acme problem: {}", self.kind)?;
+        if let Some(status) = self.status {
+            write!(f, " ({status})")?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, ": {detail}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Problem {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_order() {
+        let order: Order = serde_json::from_str(
+            r#"{
+                "status": "pending",
+                "expires": "2016-01-05T14:09:07.99Z",
+                "identifiers": [
+                    { "type": "dns", "value": "www.example.org" },
+                    { "type": "dns", "value": "example.org" }
+                ],
+                "authorizations": [
+                    "https://example.com/acme/authz/PAniVnsZcis",
+                    "https://example.com/acme/authz/r4HqLzrSrpI"
+                ],
+                "finalize": "https://example.com/acme/order/TOlocE8rfgo/finalize"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(order.status, OrderStatus::Pending);
+        assert_eq!(order.identifiers[1], Identifier::dns("example.org"));
+        assert_eq!(order.authorizations.len(), 2);
+        assert!(order.certificate.is_none());
+    }
+
+    #[test]
+    fn test_deserialize_authorization() {
+        let authz: Authorization = serde_json::from_str(
+            r#"{
+                "status": "valid",
+                "expires": "2015-03-01T14:09:07.99Z",
+                "identifier": { "type": "dns", "value": "www.example.org" },
+                "challenges": [
+                    {
+                        "url": "https://example.com/acme/chall/prV_B7yEyA4",
+                        "type": "http-01",
+                        "status": "valid",
+                        "token": "DGyRejmCefe7v4NfDGDKfA",
+                        "validated": "2014-12-01T12:05:58.16Z"
+                    },
+                    {
+                        "url": "https://example.com/acme/chall/Rg5dV14Gh1Q",
+                        "type": "unknown-01",
+                        "status": "pending"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(authz.status, AuthorizationStatus::Valid);
+        assert_eq!(authz.challenges[0].kind, ChallengeType::Http01);
+        assert_eq!(authz.challenges[0].token, "DGyRejmCefe7v4NfDGDKfA");
+        assert_eq!(authz.challenges[1].kind, ChallengeType::Unknown);
+        assert!(!authz.wildcard);
+    }
+
+    #[test]
+    fn test_problem() {
+        let problem: Problem = serde_json::from_str(
+            r#"{
+                "type": "urn:ietf:params:acme:error:badNonce",
+                "detail": "JWS has an invalid anti-replay nonce",
+                "status": 400
+            }"#,
+        )
+        .unwrap();
+        assert!(problem.is_bad_nonce());
+        assert_eq!(
+            problem.to_string(),
+            "acme problem: urn:ietf:params:acme:error:badNonce (400): JWS has an invalid anti-replay nonce"
+        );
+    }
+}
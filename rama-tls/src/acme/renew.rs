@@ -0,0 +1,371 @@
+use super::{Account, AcmeClient, ChallengeResponder};
+use crate::{
+    cert_store::{LiveCertStoreSetter, ServerCert},
+    dep::rcgen,
+    issuer::CertKeyType,
+};
+use rama_core::{
+    error::{BoxError, ErrorContext, OpaqueError},
+    Service,
+};
+use rama_http_types::{Request, Response};
+use rama_net::address::Domain;
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+/// Default duration prior to the expiration of a certificate
+/// at which the [`AcmeRenewer`] renews it.
+pub const DEFAULT_RENEW_BEFORE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Default duration the [`AcmeRenewer`] waits before it retries
+/// to obtain a certificate after a failed attempt.
+pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Obtains a certificate using an [`AcmeClient`], and keeps renewing it
+/// prior to its expiration, swapping each (renewed) certificate into
+/// the running acceptors using a [`LiveCertStoreSetter`].
+///
+/// The certificate is set as the default certificate of the [`CertStore`],
+/// keeping the certificates of its server names as they are.
+///
+/// [`CertStore`]: crate::cert_store::CertStore
+pub struct AcmeRenewer<S, R> {
+    client: AcmeClient<S>,
+    account: Account,
+    domains: Vec<Domain>,
+    responder: R,
+    key_type: CertKeyType,
+    renew_before: Duration,
+    retry_interval: Duration,
+    cert: Option<ServerCert>,
+    on_renewed: Option<Box<dyn Fn(&ServerCert) + Send + Sync + 'static>>,
+}
+
+impl<S: fmt::Debug, R: fmt::Debug> fmt::Debug for AcmeRenewer<S, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcmeRenewer")
+            .field("client", &self.client)
+            .field("account", &self.account)
+            .field("domains", &self.domains)
+            .field("responder", &self.responder)
+            .field("key_type", &self.key_type)
+            .field("renew_before", &self.renew_before)
+            .field("retry_interval", &self.retry_interval)
+            .field("cert", &self.cert)
+            .finish()
+    }
+}
+
+impl<S, R> AcmeRenewer<S, R>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+    R: ChallengeResponder,
+{
+    /// Create a new [`AcmeRenewer`] for a certificate for the given domains,
+    /// obtained for the given [`Account`] using the given [`ChallengeResponder`].
+    pub fn new(
+        client: AcmeClient<S>,
+        account: Account,
+        domains: Vec<Domain>,
+        responder: R,
+    ) -> Self {
+        Self {
+            client,
+            account,
+            domains,
+            responder,
+            key_type: CertKeyType::default(),
+            renew_before: DEFAULT_RENEW_BEFORE,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+            cert: None,
+            on_renewed: None,
+        }
+    }
+
+    /// Set the [`CertKeyType`] of the obtained certificates.
+    pub fn with_key_type(mut self, key_type: CertKeyType) -> Self {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Set the [`CertKeyType`] of the obtained certificates.
+    pub fn set_key_type(&mut self, key_type: CertKeyType) -> &mut Self {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Set the duration prior to the expiration of the certificate at which it is renewed,
+    /// defaulting to [`DEFAULT_RENEW_BEFORE`].
+    ///
+    /// Certificates which are valid for less than twice this duration
+    /// are renewed once half of their (remaining) validity has passed.
+    pub fn with_renew_before(mut self, renew_before: Duration) -> Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// Set the duration prior to the expiration of the certificate at which it is renewed,
+    /// defaulting to [`DEFAULT_RENEW_BEFORE`].
+    ///
+    /// Certificates which are valid for less than twice this duration
+    /// are renewed once half of their (remaining) validity has passed.
+    pub fn set_renew_before(&mut self, renew_before: Duration) -> &mut Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// Set the duration to wait before retrying to obtain a certificate after a failed attempt,
+    /// defaulting to [`DEFAULT_RETRY_INTERVAL`].
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// Set the duration to wait before retrying to obtain a certificate after a failed attempt,
+    /// defaulting to [`DEFAULT_RETRY_INTERVAL`].
+    pub fn set_retry_interval(&mut self, retry_interval: Duration) -> &mut Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// Start with the given (previously obtained) certificate,
+    /// such that a new one is only obtained once it is due for renewal.
+    pub fn with_cert(mut self, cert: ServerCert) -> Self {
+        self.cert = Some(cert);
+        self
+    }
+
+    /// Start with the given (previously obtained) certificate,
+    /// such that a new one is only obtained once it is due for renewal.
+    pub fn set_cert(&mut self, cert: ServerCert) -> &mut Self {
+        self.cert = Some(cert);
+        self
+    }
+
+    /// Call the given function with each newly obtained certificate,
+    /// e.g. to persist it such that it can be reused after a restart.
+    pub fn with_on_renewed<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&ServerCert) + Send + Sync + 'static,
+    {
+        self.on_renewed = Some(Box::new(f));
+        self
+    }
+
+    /// Call the given function with each newly obtained certificate,
+    /// e.g. to persist it such that it can be reused after a restart.
+    pub fn set_on_renewed<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&ServerCert) + Send + Sync + 'static,
+    {
+        self.on_renewed = Some(Box::new(f));
+        self
+    }
+
+    /// Obtain and keep renewing the certificate, setting each (renewed) certificate
+    /// as the default certificate of the [`CertStore`] using [`LiveCertStoreSetter::set_default`].
+    ///
+    /// [`CertStore`]: crate::cert_store::CertStore
+    ///
+    /// In case obtaining a certificate fails, the current one (if any) remains in use
+    /// and a new attempt is made after the retry interval.
+    ///
+    /// The returned future runs forever, and is meant to be spawned as a (graceful) task.
+    pub async fn run(mut self, setter: LiveCertStoreSetter) {
+        if let Some(cert) = &self.cert {
+            setter.set_default(cert.clone());
+        }
+
+        let mut last_attempt_failed = false;
+        loop {
+            // a failed attempt is retried after the retry interval,
+            // instead of waiting again until the current certificate is due for renewal
+            if !last_attempt_failed {
+                let now = SystemTime::now();
+                let renew_at = self
+                    .cert
+                    .as_ref()
+                    .and_then(|cert| match cert_not_after(cert) {
+                        Ok(not_after) => Some(renew_at(now, not_after, self.renew_before)),
+                        Err(err) => {
+                            tracing::warn!(err = %err, "acme renewer: failed to parse certificate expiration");
+                            None
+                        }
+                    })
+                    .unwrap_or(now);
+                if let Ok(wait) = renew_at.duration_since(now) {
+                    tracing::debug!(?wait, "acme renewer: wait until certificate renewal");
+                    tokio::time::sleep(wait).await;
+                }
+            }
+
+            match self
+                .client
+                .issue(&self.account, &self.domains, &self.responder, self.key_type)
+                .await
+            {
+                Ok(cert) => {
+                    tracing::info!(domains = ?self.domains, "acme renewer: obtained certificate");
+                    if let Some(on_renewed) = &self.on_renewed {
+                        on_renewed(&cert);
+                    }
+                    setter.set_default(cert.clone());
+                    self.cert = Some(cert);
+                    last_attempt_failed = false;
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        err = %err,
+                        domains = ?self.domains,
+                        "acme renewer: failed to obtain certificate, retry later",
+                    );
+                    tokio::time::sleep(self.retry_interval).await;
+                    last_attempt_failed = true;
+                }
+            }
+        }
+    }
+}
+
+fn cert_not_after(cert: &ServerCert) -> Result<SystemTime, OpaqueError> {
+    let leaf = cert
+        .cert_chain_der()
+        .first()
+        .ok_or_else(|| OpaqueError::from_display("empty certificate chain"))?;
+    let params = rcgen::CertificateParams::from_ca_cert_der(&leaf.as_slice().into())
+        .context("parse certificate")?;
+    Ok(params.not_after.into())
+}
+
+/// Returns the moment at which a certificate expiring at `not_after` is to be renewed.
+fn renew_at(now: SystemTime, not_after: SystemTime, renew_before: Duration) -> SystemTime {
+    let remaining = not_after.duration_since(now).unwrap_or_default();
+    now + remaining.saturating_sub(renew_before).max(remaining / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        acme::{AccountKey, Directory, Http01ChallengeResponder},
+        cert_store::cert_store_updater,
+    };
+    use rama_core::service::service_fn;
+    use rama_http_types::{Body, Method, StatusCode};
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[test]
+    fn test_renew_at() {
+        let now = SystemTime::UNIX_EPOCH + 1000 * DAY;
+        for (valid_for, renew_before, expected) in [
+            (90 * DAY, 30 * DAY, 60 * DAY),
+            (6 * DAY, 30 * DAY, 3 * DAY),
+            (60 * DAY, 30 * DAY, 30 * DAY),
+        ] {
+            assert_eq!(
+                renew_at(now, now + valid_for, renew_before),
+                now + expected,
+                "valid_for: {valid_for:?}",
+            );
+        }
+        assert_eq!(renew_at(now, now - DAY, 30 * DAY), now);
+    }
+
+    #[test]
+    fn test_cert_not_after() {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["example.com".to_owned()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2030, 1, 1);
+        let cert = params.self_signed(&key_pair).unwrap();
+        let cert = ServerCert::from_pem(&cert.pem(), &key_pair.serialize_pem()).unwrap();
+        assert_eq!(
+            cert_not_after(&cert).unwrap(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_893_456_000)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_failed_renewal() {
+        let orders = Arc::new(AtomicUsize::new(0));
+        let http = service_fn({
+            let orders = orders.clone();
+            move |req: Request| {
+                let orders = orders.clone();
+                async move {
+                    let mut resp = match (req.method(), req.uri().path()) {
+                        (&Method::POST, "/new-account") => {
+                            let mut resp = Response::new(Body::from(r#"{"status":"valid"}"#));
+                            *resp.status_mut() = StatusCode::CREATED;
+                            resp.headers_mut()
+                                .insert("location", "http://acme.test/acct/1".parse().unwrap());
+                            resp
+                        }
+                        (&Method::POST, "/new-order") => {
+                            orders.fetch_add(1, Ordering::SeqCst);
+                            let mut resp = Response::new(Body::from(
+                                r#"{"type":"urn:ietf:params:acme:error:serverInternal"}"#,
+                            ));
+                            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                            resp
+                        }
+                        _ => Response::new(Body::empty()),
+                    };
+                    resp.headers_mut()
+                        .insert("replay-nonce", "nonce".parse().unwrap());
+                    Ok::<_, Infallible>(resp)
+                }
+            }
+        });
+        let client = AcmeClient::with_directory(
+            http,
+            Directory {
+                new_nonce: "http://acme.test/new-nonce".to_owned(),
+                new_account: "http://acme.test/new-account".to_owned(),
+                new_order: "http://acme.test/new-order".to_owned(),
+                revoke_cert: None,
+                key_change: None,
+                meta: None,
+            },
+        );
+        let account = client
+            .new_account(AccountKey::generate().unwrap(), &[])
+            .await
+            .unwrap();
+
+        // due for renewal in 60 days
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["example.com".to_owned()]).unwrap();
+        params.not_after = (SystemTime::now() + 90 * DAY).into();
+        let cert = params.self_signed(&key_pair).unwrap();
+        let cert = ServerCert::from_pem(&cert.pem(), &key_pair.serialize_pem()).unwrap();
+
+        let renewer = AcmeRenewer::new(
+            client,
+            account,
+            vec![Domain::from_static("example.com")],
+            Http01ChallengeResponder::new(),
+        )
+        .with_retry_interval(Duration::from_secs(60 * 60))
+        .with_cert(cert);
+        let (_store, setter) = cert_store_updater();
+        let task = tokio::spawn(renewer.run(setter));
+
+        tokio::time::sleep(60 * DAY + Duration::from_secs(60 * 60) / 2).await;
+        assert_eq!(orders.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_secs(3 * 60 * 60)).await;
+        assert_eq!(orders.load(Ordering::SeqCst), 4);
+
+        task.abort();
+    }
+}
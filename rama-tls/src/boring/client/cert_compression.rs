@@ -0,0 +1,108 @@
+//! Certificate compression ([RFC 8879](https://datatracker.ietf.org/doc/html/rfc8879))
+//! support for the boring client.
+//!
+//! The `boring` crate does not (yet) expose a safe API to register
+//! certificate compression algorithms, which is why this module
+//! registers them directly via the BoringSSL FFI.
+
+use crate::boring::dep::boring::ssl::SslConnectorBuilder;
+use boring_sys as ffi;
+use rama_core::error::OpaqueError;
+use std::io::Read;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Certificate compression algorithms which can be advertised by the client,
+/// as defined in [RFC 8879](https://datatracker.ietf.org/doc/html/rfc8879).
+pub enum CertificateCompressionAlgorithm {
+    /// zlib (RFC 1950) compression.
+    Zlib,
+    /// Brotli (RFC 7932) compression.
+    Brotli,
+}
+
+impl CertificateCompressionAlgorithm {
+    pub(super) fn register(self, builder: &mut SslConnectorBuilder) -> Result<(), OpaqueError> {
+        let (alg_id, decompress): (u16, ffi::ssl_cert_decompression_func_t) = match self {
+            Self::Zlib => (
+                ffi::TLSEXT_cert_compression_zlib as u16,
+                Some(decompress_zlib),
+            ),
+            Self::Brotli => (
+                ffi::TLSEXT_cert_compression_brotli as u16,
+                Some(decompress_brotli),
+            ),
+        };
+        // SAFETY: the context pointer is valid for the lifetime of the builder,
+        // and the registered callback upholds the BoringSSL decompression contract.
+        // Only the decompression direction is registered, given the client only needs
+        // to be able to receive a compressed server certificate.
+        #[allow(unsafe_code)]
+        let result = unsafe {
+            ffi::SSL_CTX_add_cert_compression_alg(builder.as_ptr(), alg_id, None, decompress)
+        };
+        if result == 1 {
+            Ok(())
+        } else {
+            Err(OpaqueError::from_display(format!(
+                "failed to register cert compression algorithm {self:?}"
+            )))
+        }
+    }
+}
+
+// SAFETY: only called by BoringSSL, with `input` pointing to `in_len` readable bytes
+// and `out` pointing to a location where the decompressed buffer can be stored.
+#[allow(unsafe_code)]
+unsafe extern "C" fn decompress_zlib(
+    _ssl: *mut ffi::SSL,
+    out: *mut *mut ffi::CRYPTO_BUFFER,
+    uncompressed_len: usize,
+    input: *const u8,
+    in_len: usize,
+) -> std::os::raw::c_int {
+    let input = std::slice::from_raw_parts(input, in_len);
+    let Some(buf) = decompress(flate2::read::ZlibDecoder::new(input), uncompressed_len) else {
+        return 0;
+    };
+    let buffer = ffi::CRYPTO_BUFFER_new(buf.as_ptr(), buf.len(), std::ptr::null_mut());
+    if buffer.is_null() {
+        return 0;
+    }
+    *out = buffer;
+    1
+}
+
+// SAFETY: only called by BoringSSL, with `input` pointing to `in_len` readable bytes
+// and `out` pointing to a location where the decompressed buffer can be stored.
+#[allow(unsafe_code)]
+unsafe extern "C" fn decompress_brotli(
+    _ssl: *mut ffi::SSL,
+    out: *mut *mut ffi::CRYPTO_BUFFER,
+    uncompressed_len: usize,
+    input: *const u8,
+    in_len: usize,
+) -> std::os::raw::c_int {
+    let input = std::slice::from_raw_parts(input, in_len);
+    let Some(buf) = decompress(brotli::Decompressor::new(input, 4096), uncompressed_len) else {
+        return 0;
+    };
+    let buffer = ffi::CRYPTO_BUFFER_new(buf.as_ptr(), buf.len(), std::ptr::null_mut());
+    if buffer.is_null() {
+        return 0;
+    }
+    *out = buffer;
+    1
+}
+
+/// Decompress the certificate from the given reader,
+/// failing in case the decompressed size does not match the expected size.
+fn decompress(reader: impl Read, uncompressed_len: usize) -> Option<Vec<u8>> {
+    let mut buf = Vec::with_capacity(uncompressed_len);
+    // read one byte more than expected, such that an oversized certificate is detected
+    reader
+        .take(uncompressed_len as u64 + 1)
+        .read_to_end(&mut buf)
+        .ok()
+        .filter(|_| buf.len() == uncompressed_len)?;
+    Some(buf)
+}
@@ -0,0 +1,183 @@
+use super::CertificateCompressionAlgorithm;
+use crate::boring::dep::boring::{
+    pkey::{PKey, Private},
+    ssl::{
+        ConnectConfiguration, SslConnector, SslCurve, SslMethod, SslSignatureAlgorithm,
+        SslVerifyMode,
+    },
+    x509::{store::X509StoreBuilder, X509},
+};
+use crate::keylog::KeyLogIntent;
+use crate::types::ApplicationProtocol;
+use crate::verify::ServerVerifyMode;
+use rama_core::error::{ErrorContext, OpaqueError};
+
+#[derive(Clone, Debug, Default)]
+/// Common configuration for a set of client sessions.
+///
+/// The default configuration does not verify the server certificate,
+/// advertises no ALPN protocols and leaves all other options to the BoringSSL defaults.
+pub struct ClientConfig {
+    /// Set the cipher list (OpenSSL cipher string format) used for TLS 1.2 and below.
+    pub cipher_list: Option<String>,
+    /// Set the curves (supported groups) to advertise, in order of preference.
+    pub curves: Option<Vec<SslCurve>>,
+    /// Set the signature algorithms to advertise, in order of preference.
+    pub verify_algorithm_prefs: Option<Vec<SslSignatureAlgorithm>>,
+    /// Set the ALPN protocols to advertise, in order of preference.
+    pub alpn_protocols: Vec<ApplicationProtocol>,
+    /// Define how the server certificate is verified.
+    pub server_verify_mode: ServerVerifyMode,
+    /// Root certificates used to verify the server certificate,
+    /// instead of the default verify paths of the system.
+    ///
+    /// Only used in case the [`ServerVerifyMode`] is [`ServerVerifyMode::Auto`].
+    pub root_certs: Option<Vec<X509>>,
+    /// Client certificate (chain) and private key used to authenticate
+    /// with the server (mTLS), if requested by the server.
+    pub client_auth: Option<ClientAuth>,
+    /// Enable GREASE ([RFC 8701](https://datatracker.ietf.org/doc/html/rfc8701)).
+    pub grease_enabled: bool,
+    /// Randomly permute the order of the ClientHello extensions
+    /// for each connection, as done by Chromium-based clients.
+    pub permute_extensions: bool,
+    /// Certificate compression algorithms to advertise
+    /// ([RFC 8879](https://datatracker.ietf.org/doc/html/rfc8879)),
+    /// in order of preference.
+    pub cert_compression_algorithms: Vec<CertificateCompressionAlgorithm>,
+    /// Write logging information to facilitate tls interception.
+    pub keylog_intent: KeyLogIntent,
+}
+
+impl ClientConfig {
+    /// Create a new [`ClientConfig`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the connect configuration for a single client session,
+    /// using this [`ClientConfig`].
+    pub(super) fn connect_configuration(&self) -> Result<ConnectConfiguration, OpaqueError> {
+        let mut cfg_builder = SslConnector::builder(SslMethod::tls_client())
+            .context("create ssl connector builder")?;
+
+        if let Some(cipher_list) = self.cipher_list.as_deref() {
+            cfg_builder
+                .set_cipher_list(cipher_list)
+                .context("build boring ssl connector: set cipher list")?;
+        }
+
+        if let Some(curves) = self.curves.as_deref() {
+            cfg_builder
+                .set_curves(curves)
+                .context("build boring ssl connector: set curves")?;
+        }
+
+        if let Some(prefs) = self.verify_algorithm_prefs.as_deref() {
+            cfg_builder
+                .set_verify_algorithm_prefs(prefs)
+                .context("build boring ssl connector: set signature algorithms")?;
+        }
+
+        if !self.alpn_protocols.is_empty() {
+            let mut buf = vec![];
+            for alpn in &self.alpn_protocols {
+                alpn.encode_wire_format(&mut buf)
+                    .context("build boring ssl connector: encode alpn")?;
+            }
+            cfg_builder
+                .set_alpn_protos(&buf[..])
+                .context("build boring ssl connector: set alpn")?;
+        }
+
+        match self.server_verify_mode {
+            ServerVerifyMode::Auto => {
+                match self.root_certs.as_deref() {
+                    Some(root_certs) => {
+                        let mut store_builder = X509StoreBuilder::new()
+                            .context("build boring ssl connector: create root cert store")?;
+                        for cert in root_certs {
+                            store_builder.add_cert(cert.clone()).context(
+                                "build boring ssl connector: add root certificate (x509)",
+                            )?;
+                        }
+                        cfg_builder.set_cert_store(store_builder.build());
+                    }
+                    None => cfg_builder
+                        .set_default_verify_paths()
+                        .context("build boring ssl connector: set default verify paths")?,
+                }
+                cfg_builder.set_verify(SslVerifyMode::PEER);
+            }
+            ServerVerifyMode::Disable => {
+                cfg_builder.set_custom_verify_callback(SslVerifyMode::NONE, |_| Ok(()));
+                cfg_builder.set_verify(SslVerifyMode::NONE);
+            }
+        }
+
+        if let Some(client_auth) = &self.client_auth {
+            for (i, cert) in client_auth.cert_chain.iter().enumerate() {
+                if i == 0 {
+                    cfg_builder
+                        .set_certificate(cert.as_ref())
+                        .context("build boring ssl connector: set client certificate (x509)")?;
+                } else {
+                    cfg_builder.add_extra_chain_cert(cert.clone()).context(
+                        "build boring ssl connector: add extra chain certificate (x509)",
+                    )?;
+                }
+            }
+            cfg_builder
+                .set_private_key(client_auth.private_key.as_ref())
+                .context("build boring ssl connector: set client private key")?;
+            cfg_builder
+                .check_private_key()
+                .context("build boring ssl connector: check client private key")?;
+        }
+
+        cfg_builder.set_grease_enabled(self.grease_enabled);
+        cfg_builder.set_permute_extensions(self.permute_extensions);
+
+        for algorithm in &self.cert_compression_algorithms {
+            algorithm
+                .register(&mut cfg_builder)
+                .context("build boring ssl connector: add cert compression algorithm")?;
+        }
+
+        if let Some(writer) = self
+            .keylog_intent
+            .writer()
+            .context("build boring ssl connector: set keylog")?
+        {
+            cfg_builder.set_keylog_callback(move |_, line| writer.write_line(line));
+        }
+
+        let verify_hostname = matches!(self.server_verify_mode, ServerVerifyMode::Auto);
+        let cfg = cfg_builder
+            .build()
+            .configure()
+            .context("create ssl connector configuration")?
+            .use_server_name_indication(true)
+            .verify_hostname(verify_hostname);
+        Ok(cfg)
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Client certificate (chain) and private key used for mTLS.
+pub struct ClientAuth {
+    /// Client certificate chain, starting with the leaf certificate.
+    pub cert_chain: Vec<X509>,
+    /// Private key of the client (leaf) certificate.
+    pub private_key: PKey<Private>,
+}
+
+impl ClientAuth {
+    /// Create a new [`ClientAuth`].
+    pub const fn new(cert_chain: Vec<X509>, private_key: PKey<Private>) -> Self {
+        Self {
+            cert_chain,
+            private_key,
+        }
+    }
+}
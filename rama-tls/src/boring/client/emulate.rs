@@ -0,0 +1,201 @@
+use super::{CertificateCompressionAlgorithm, ClientConfig};
+use crate::boring::dep::boring::ssl::{SslCurve, SslSignatureAlgorithm};
+use crate::types::{
+    client::{ClientHello, ClientHelloExtension},
+    CipherSuite, ExtensionId, SignatureScheme, SupportedGroup,
+};
+
+impl ClientConfig {
+    /// Overwrite the fingerprint-relevant settings of this [`ClientConfig`],
+    /// such that the handshake reproduces the given [`ClientHello`]
+    /// as closely as BoringSSL allows.
+    ///
+    /// The following properties are emulated:
+    ///
+    /// - the order of the (TLS 1.2 and below) cipher suites;
+    /// - the supported groups (curves);
+    /// - the signature algorithms;
+    /// - the ALPN protocols;
+    /// - GREASE, in case the [`ClientHello`] contains GREASE values;
+    /// - certificate compression algorithms.
+    ///
+    /// Values not supported by BoringSSL are skipped. BoringSSL does not allow
+    /// to define the order of the extensions, which is why extension permutation
+    /// is enabled in case the [`ClientHello`] contains GREASE values, as this is
+    /// what the Chromium-based clients that send such hellos do as well.
+    ///
+    /// Settings unrelated to the fingerprint, such as the server verification
+    /// and client authentication, are left untouched.
+    pub fn with_emulated_client_hello(mut self, hello: &ClientHello) -> Self {
+        self.set_emulated_client_hello(hello);
+        self
+    }
+
+    /// Overwrite the fingerprint-relevant settings of this [`ClientConfig`],
+    /// such that the handshake reproduces the given [`ClientHello`]
+    /// as closely as BoringSSL allows.
+    ///
+    /// See [`ClientConfig::with_emulated_client_hello`] for more information.
+    pub fn set_emulated_client_hello(&mut self, hello: &ClientHello) -> &mut Self {
+        let cipher_list: Vec<_> = hello
+            .cipher_suites()
+            .iter()
+            .copied()
+            .filter_map(cipher_suite_name)
+            .collect();
+        self.cipher_list = if cipher_list.is_empty() {
+            None
+        } else {
+            Some(cipher_list.join(":"))
+        };
+
+        self.curves = hello
+            .ext_supported_groups()
+            .map(|groups| groups.iter().copied().filter_map(ssl_curve).collect());
+
+        self.verify_algorithm_prefs = hello.ext_signature_algorithms().map(|schemes| {
+            schemes
+                .iter()
+                .copied()
+                .filter_map(ssl_signature_algorithm)
+                .collect()
+        });
+
+        self.alpn_protocols = hello.ext_alpn().map(<[_]>::to_vec).unwrap_or_default();
+
+        let grease = hello
+            .cipher_suites()
+            .iter()
+            .any(|cs| is_grease(u16::from(*cs)))
+            || hello
+                .extensions()
+                .iter()
+                .any(|ext| is_grease(u16::from(ext.id())));
+        self.grease_enabled = grease;
+        self.permute_extensions = grease;
+
+        self.cert_compression_algorithms = hello
+            .extensions()
+            .iter()
+            .find_map(|ext| match ext {
+                ClientHelloExtension::Opaque { id, data }
+                    if *id == ExtensionId::COMPRESS_CERTIFICATE =>
+                {
+                    Some(cert_compression_algorithms(data))
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        self
+    }
+}
+
+impl From<&ClientHello> for ClientConfig {
+    fn from(hello: &ClientHello) -> Self {
+        ClientConfig::default().with_emulated_client_hello(hello)
+    }
+}
+
+/// GREASE values ([RFC 8701](https://datatracker.ietf.org/doc/html/rfc8701))
+/// are of the form `0x?A?A`, with both bytes being equal.
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && (value >> 8) == (value & 0xff)
+}
+
+/// Returns the (IANA) standard name of the given TLS 1.2 (or below) [`CipherSuite`],
+/// which is a name understood by the BoringSSL cipher list parser.
+fn cipher_suite_name(cs: CipherSuite) -> Option<String> {
+    match cs {
+        CipherSuite::Unknown(_) => None,
+        // TLS 1.3 cipher suites are not configurable in BoringSSL
+        cs if (0x1301..=0x1305).contains(&u16::from(cs)) => None,
+        // variant names of known cipher suites equal their standard name
+        cs => Some(format!("{cs:?}")),
+    }
+}
+
+fn ssl_curve(group: SupportedGroup) -> Option<SslCurve> {
+    Some(match group {
+        SupportedGroup::SECP224R1 => SslCurve::SECP224R1,
+        SupportedGroup::SECP256R1 => SslCurve::SECP256R1,
+        SupportedGroup::SECP384R1 => SslCurve::SECP384R1,
+        SupportedGroup::SECP521R1 => SslCurve::SECP521R1,
+        SupportedGroup::X25519 => SslCurve::X25519,
+        SupportedGroup::X25519KYBER768DRAFT00 => SslCurve::X25519_KYBER768_DRAFT00,
+        _ => return None,
+    })
+}
+
+fn ssl_signature_algorithm(scheme: SignatureScheme) -> Option<SslSignatureAlgorithm> {
+    Some(match scheme {
+        SignatureScheme::RSA_PKCS1_SHA1 => SslSignatureAlgorithm::RSA_PKCS1_SHA1,
+        SignatureScheme::ECDSA_SHA1_Legacy => SslSignatureAlgorithm::ECDSA_SHA1,
+        SignatureScheme::RSA_PKCS1_SHA256 => SslSignatureAlgorithm::RSA_PKCS1_SHA256,
+        SignatureScheme::RSA_PKCS1_SHA384 => SslSignatureAlgorithm::RSA_PKCS1_SHA384,
+        SignatureScheme::RSA_PKCS1_SHA512 => SslSignatureAlgorithm::RSA_PKCS1_SHA512,
+        SignatureScheme::ECDSA_NISTP256_SHA256 => SslSignatureAlgorithm::ECDSA_SECP256R1_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384 => SslSignatureAlgorithm::ECDSA_SECP384R1_SHA384,
+        SignatureScheme::ECDSA_NISTP521_SHA512 => SslSignatureAlgorithm::ECDSA_SECP521R1_SHA512,
+        SignatureScheme::RSA_PSS_SHA256 => SslSignatureAlgorithm::RSA_PSS_RSAE_SHA256,
+        SignatureScheme::RSA_PSS_SHA384 => SslSignatureAlgorithm::RSA_PSS_RSAE_SHA384,
+        SignatureScheme::RSA_PSS_SHA512 => SslSignatureAlgorithm::RSA_PSS_RSAE_SHA512,
+        SignatureScheme::ED25519 => SslSignatureAlgorithm::ED25519,
+        _ => return None,
+    })
+}
+
+/// Parse the data of a `compress_certificate` extension
+/// ([RFC 8879](https://datatracker.ietf.org/doc/html/rfc8879#section-3)),
+/// which is a length-prefixed list of `u16` algorithm identifiers.
+fn cert_compression_algorithms(data: &[u8]) -> Vec<CertificateCompressionAlgorithm> {
+    let Some((len, list)) = data.split_first() else {
+        return Vec::new();
+    };
+    let list = &list[..(*len as usize).min(list.len())];
+    list.chunks_exact(2)
+        .filter_map(|id| match u16::from_be_bytes([id[0], id[1]]) {
+            1 => Some(CertificateCompressionAlgorithm::Zlib),
+            2 => Some(CertificateCompressionAlgorithm::Brotli),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_grease() {
+        for value in [0x0a0a, 0x1a1a, 0xfafa] {
+            assert!(is_grease(value), "{value:#06x}");
+        }
+        for value in [0x0a1a, 0x1301, 0x001d] {
+            assert!(!is_grease(value), "{value:#06x}");
+        }
+    }
+
+    #[test]
+    fn test_cert_compression_algorithms() {
+        assert_eq!(
+            cert_compression_algorithms(&[6, 0, 2, 0, 3, 0, 1]),
+            vec![
+                CertificateCompressionAlgorithm::Brotli,
+                CertificateCompressionAlgorithm::Zlib,
+            ]
+        );
+        assert!(cert_compression_algorithms(&[]).is_empty());
+        assert!(cert_compression_algorithms(&[4, 0]).is_empty());
+    }
+
+    #[test]
+    fn test_cipher_suite_name() {
+        assert_eq!(
+            cipher_suite_name(CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256).as_deref(),
+            Some("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256")
+        );
+        assert!(cipher_suite_name(CipherSuite::TLS13_AES_128_GCM_SHA256).is_none());
+        assert!(cipher_suite_name(CipherSuite::Unknown(0x0a0a)).is_none());
+    }
+}
@@ -1,13 +1,22 @@
-use crate::types::HttpsTunnel;
-use boring::ssl::SslVerifyMode;
+use super::{verify::set_verify_policy, ClientConfig};
+use crate::boring::dep::boring::ssl::{SslRef, SslVersion};
+use crate::keylog::KeyLogIntent;
+use crate::types::{
+    client::{ClientHello, NegotiatedApplicationProtocol, NegotiatedTlsParameters},
+    HttpsTunnel, ProtocolVersion, SecureTransport,
+};
+use crate::verify::ServerVerifyPolicy;
+use boring_sys as ffi;
+use foreign_types::ForeignTypeRef;
 use pin_project_lite::pin_project;
 use private::{ConnectorKindAuto, ConnectorKindSecure, ConnectorKindTunnel};
 use rama_core::error::{BoxError, ErrorContext, ErrorExt, OpaqueError};
 use rama_core::{Context, Layer, Service};
+use rama_net::address::Host;
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
 use rama_net::transport::TryRefIntoTransportContext;
-use std::fmt;
+use std::{borrow::Cow, fmt, sync::Arc};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_boring::SslStream;
 
@@ -16,12 +25,126 @@ use tokio_boring::SslStream;
 /// See [`HttpsConnector`] for more information.
 #[derive(Clone)]
 pub struct HttpsConnectorLayer<K = ConnectorKindAuto> {
+    config: Option<Arc<ClientConfig>>,
+    emulate_client_hello: bool,
+    verify_policy: Option<ServerVerifyPolicy>,
+    key_log_intent: KeyLogIntent,
+    store_server_info: bool,
     _kind: std::marker::PhantomData<K>,
 }
 
 impl<K> std::fmt::Debug for HttpsConnectorLayer<K> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("HttpsConnectorLayer").finish()
+        f.debug_struct("HttpsConnectorLayer")
+            .field("config", &self.config)
+            .field("emulate_client_hello", &self.emulate_client_hello)
+            .field("verify_policy", &self.verify_policy)
+            .field("key_log_intent", &self.key_log_intent)
+            .field("store_server_info", &self.store_server_info)
+            .finish()
+    }
+}
+
+impl<K> HttpsConnectorLayer<K> {
+    /// Attach a client config to this [`HttpsConnectorLayer`],
+    /// to be used instead of a globally shared default client config.
+    pub fn with_config(mut self, config: Arc<ClientConfig>) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Maybe attach a client config to this [`HttpsConnectorLayer`],
+    /// to be used instead of a globally shared default client config.
+    pub fn maybe_with_config(mut self, config: Option<Arc<ClientConfig>>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Attach a client config to this [`HttpsConnectorLayer`],
+    /// to be used instead of a globally shared default client config.
+    pub fn set_config(&mut self, config: Arc<ClientConfig>) -> &mut Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Define whether or not the [`HttpsConnector`] emulates the [`ClientHello`]
+    /// found in the [`SecureTransport`] of the [`Context`], if any.
+    ///
+    /// See [`HttpsConnector::with_client_hello_emulation`] for more information.
+    pub fn with_client_hello_emulation(mut self, emulate: bool) -> Self {
+        self.emulate_client_hello = emulate;
+        self
+    }
+
+    /// Define whether or not the [`HttpsConnector`] emulates the [`ClientHello`]
+    /// found in the [`SecureTransport`] of the [`Context`], if any.
+    ///
+    /// See [`HttpsConnector::with_client_hello_emulation`] for more information.
+    pub fn set_client_hello_emulation(&mut self, emulate: bool) -> &mut Self {
+        self.emulate_client_hello = emulate;
+        self
+    }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// used to verify the server certificate instead of the verification of the client config.
+    ///
+    /// A [`ServerVerifyPolicy`] found in the [`Context`] takes precedence,
+    /// except for tunnel connectors as these connect to the proxy instead.
+    pub fn with_verify_policy(mut self, policy: ServerVerifyPolicy) -> Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Maybe set the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// used to verify the server certificate instead of the verification of the client config.
+    pub fn maybe_with_verify_policy(mut self, policy: Option<ServerVerifyPolicy>) -> Self {
+        self.verify_policy = policy;
+        self
+    }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// used to verify the server certificate instead of the verification of the client config.
+    pub fn set_verify_policy(&mut self, policy: ServerVerifyPolicy) -> &mut Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnectorLayer`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    ///
+    /// Overwrites the key log intent of the [`ClientConfig`] in case it's not disabled.
+    pub fn with_key_log_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnectorLayer`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    ///
+    /// Overwrites the key log intent of the [`ClientConfig`] in case it's not disabled.
+    pub fn set_key_log_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnectorLayer`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn with_store_server_info(mut self, store: bool) -> Self {
+        self.store_server_info = store;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnectorLayer`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn set_store_server_info(&mut self, store: bool) -> &mut Self {
+        self.store_server_info = store;
+        self
     }
 }
 
@@ -31,6 +154,11 @@ impl HttpsConnectorLayer<ConnectorKindAuto> {
     /// otherwise it will forward the pre-established inner connection.
     pub fn auto() -> Self {
         Self {
+            config: None,
+            emulate_client_hello: false,
+            verify_policy: None,
+            key_log_intent: KeyLogIntent::Disabled,
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
@@ -41,6 +169,11 @@ impl HttpsConnectorLayer<ConnectorKindSecure> {
     /// establish a secure connection regardless of the request it is for.
     pub fn secure_only() -> Self {
         Self {
+            config: None,
+            emulate_client_hello: false,
+            verify_policy: None,
+            key_log_intent: KeyLogIntent::Disabled,
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
@@ -51,6 +184,11 @@ impl HttpsConnectorLayer<ConnectorKindTunnel> {
     /// a secure connection if the request is to be tunneled.
     pub fn tunnel() -> Self {
         Self {
+            config: None,
+            emulate_client_hello: false,
+            verify_policy: None,
+            key_log_intent: KeyLogIntent::Disabled,
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
@@ -61,6 +199,11 @@ impl<K, S> Layer<S> for HttpsConnectorLayer<K> {
 
     fn layer(&self, inner: S) -> Self::Service {
         HttpsConnector::new(inner)
+            .maybe_with_config(self.config.clone())
+            .with_client_hello_emulation(self.emulate_client_hello)
+            .maybe_with_verify_policy(self.verify_policy.clone())
+            .with_key_log_intent(self.key_log_intent.clone())
+            .with_store_server_info(self.store_server_info)
     }
 }
 
@@ -79,6 +222,11 @@ impl Default for HttpsConnectorLayer<ConnectorKindAuto> {
 /// establish a secure connection.
 pub struct HttpsConnector<S, K = ConnectorKindAuto> {
     inner: S,
+    config: Option<Arc<ClientConfig>>,
+    emulate_client_hello: bool,
+    verify_policy: Option<ServerVerifyPolicy>,
+    key_log_intent: KeyLogIntent,
+    store_server_info: bool,
     _kind: std::marker::PhantomData<K>,
 }
 
@@ -86,6 +234,11 @@ impl<S: fmt::Debug, K> fmt::Debug for HttpsConnector<S, K> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("HttpsConnector")
             .field("inner", &self.inner)
+            .field("config", &self.config)
+            .field("emulate_client_hello", &self.emulate_client_hello)
+            .field("verify_policy", &self.verify_policy)
+            .field("key_log_intent", &self.key_log_intent)
+            .field("store_server_info", &self.store_server_info)
             .finish()
     }
 }
@@ -94,6 +247,11 @@ impl<S: Clone, K> Clone for HttpsConnector<S, K> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            config: self.config.clone(),
+            emulate_client_hello: self.emulate_client_hello,
+            verify_policy: self.verify_policy.clone(),
+            key_log_intent: self.key_log_intent.clone(),
+            store_server_info: self.store_server_info,
             _kind: std::marker::PhantomData,
         }
     }
@@ -104,9 +262,116 @@ impl<S, K> HttpsConnector<S, K> {
     pub const fn new(inner: S) -> Self {
         Self {
             inner,
+            config: None,
+            emulate_client_hello: false,
+            verify_policy: None,
+            key_log_intent: KeyLogIntent::Disabled,
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
+
+    /// Attach a client config to this [`HttpsConnector`],
+    pub fn with_config(mut self, config: Arc<ClientConfig>) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Maybe attach a client config to this [`HttpsConnector`],
+    pub fn maybe_with_config(mut self, config: Option<Arc<ClientConfig>>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set a client config to this [`HttpsConnector`],
+    pub fn set_config(&mut self, config: Arc<ClientConfig>) -> &mut Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnector`] emulates the [`ClientHello`]
+    /// found in the [`SecureTransport`] of the [`Context`], if any.
+    ///
+    /// This is useful for a MITM proxy, which can store the [`ClientHello`]
+    /// of the incoming connection, such that the outgoing handshake reproduces it,
+    /// see [`ClientConfig::with_emulated_client_hello`] for more information.
+    ///
+    /// Disabled by default.
+    pub fn with_client_hello_emulation(mut self, emulate: bool) -> Self {
+        self.emulate_client_hello = emulate;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnector`] emulates the [`ClientHello`]
+    /// found in the [`SecureTransport`] of the [`Context`], if any.
+    ///
+    /// See [`HttpsConnector::with_client_hello_emulation`] for more information.
+    pub fn set_client_hello_emulation(&mut self, emulate: bool) -> &mut Self {
+        self.emulate_client_hello = emulate;
+        self
+    }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// used to verify the server certificate instead of the verification of the client config.
+    ///
+    /// A [`ServerVerifyPolicy`] found in the [`Context`] takes precedence,
+    /// except for tunnel connectors as these connect to the proxy instead.
+    pub fn with_verify_policy(mut self, policy: ServerVerifyPolicy) -> Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Maybe set the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// used to verify the server certificate instead of the verification of the client config.
+    pub fn maybe_with_verify_policy(mut self, policy: Option<ServerVerifyPolicy>) -> Self {
+        self.verify_policy = policy;
+        self
+    }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// used to verify the server certificate instead of the verification of the client config.
+    pub fn set_verify_policy(&mut self, policy: ServerVerifyPolicy) -> &mut Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnector`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    ///
+    /// Overwrites the key log intent of the [`ClientConfig`] in case it's not disabled.
+    pub fn with_key_log_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnector`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    ///
+    /// Overwrites the key log intent of the [`ClientConfig`] in case it's not disabled.
+    pub fn set_key_log_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnector`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn with_store_server_info(mut self, store: bool) -> Self {
+        self.store_server_info = store;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnector`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn set_store_server_info(&mut self, store: bool) -> &mut Self {
+        self.store_server_info = store;
+        self
+    }
 }
 
 impl<S> HttpsConnector<S, ConnectorKindAuto> {
@@ -158,6 +423,8 @@ where
             conn,
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
+        let client_hello = self.client_hello_to_emulate(&ctx);
+        let verify_policy = ctx.get::<ServerVerifyPolicy>().cloned();
 
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
@@ -188,12 +455,16 @@ where
 
         let host = transport_ctx.authority.host().to_string();
 
-        let stream = self.handshake(host, conn).await?;
+        let stream = self
+            .handshake(client_hello, verify_policy.as_ref(), host, conn)
+            .await?;
 
         tracing::trace!(
             authority = %transport_ctx.authority,
             "HttpsConnector(auto): protocol secure, established tls connection",
         );
+
+        self.store_server_info(&mut ctx, &stream)?;
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -227,6 +498,8 @@ where
             conn,
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
+        let client_hello = self.client_hello_to_emulate(&ctx);
+        let verify_policy = ctx.get::<ServerVerifyPolicy>().cloned();
 
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
@@ -241,8 +514,11 @@ where
 
         let host = transport_ctx.authority.host().to_string();
 
-        let conn = self.handshake(host, conn).await?;
+        let conn = self
+            .handshake(client_hello, verify_policy.as_ref(), host, conn)
+            .await?;
 
+        self.store_server_info(&mut ctx, &conn)?;
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -267,11 +543,12 @@ where
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
         let EstablishedClientConnection {
-            ctx,
+            mut ctx,
             req,
             conn,
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
+        let client_hello = self.client_hello_to_emulate(&ctx);
 
         let host = match ctx.get::<HttpsTunnel>() {
             Some(tunnel) => tunnel.server_name.clone(),
@@ -290,9 +567,11 @@ where
             }
         };
 
-        let stream = self.handshake(host, conn).await?;
+        // the verify policy of the context is meant for the target server, not the proxy
+        let stream = self.handshake(client_hello, None, host, conn).await?;
 
         tracing::trace!("HttpsConnector(tunnel): connection secured");
+        self.store_server_info(&mut ctx, &stream)?;
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -305,36 +584,113 @@ where
 }
 
 impl<S, K> HttpsConnector<S, K> {
-    async fn handshake<T>(&self, target_host: String, stream: T) -> Result<SslStream<T>, BoxError>
+    fn client_hello_to_emulate<State>(&self, ctx: &Context<State>) -> Option<ClientHello> {
+        if !self.emulate_client_hello {
+            return None;
+        }
+        ctx.get::<SecureTransport>()
+            .and_then(|transport| transport.client_hello())
+            .cloned()
+    }
+
+    async fn handshake<T>(
+        &self,
+        client_hello: Option<ClientHello>,
+        verify_policy: Option<&ServerVerifyPolicy>,
+        target_host: String,
+        stream: T,
+    ) -> Result<SslStream<T>, BoxError>
     where
         T: Stream + Unpin,
     {
-        // TODO: make this configurable from "outside""...
-        // - for emulation
-        // - for obvious stuff like ALPN
-        // - for client preferences/options
-        // TODO: make configurable via ClientConfig
-        let mut cfg_builder =
-            boring::ssl::SslConnector::builder(boring::ssl::SslMethod::tls_client())
-                .context("create ssl connector builder")?;
-        cfg_builder.set_custom_verify_callback(SslVerifyMode::NONE, |_| Ok(()));
-        cfg_builder.set_verify(SslVerifyMode::NONE);
-
-        let cfg = cfg_builder
-            .build()
-            .configure()
-            .context("create ssl connector configuration")?
-            .use_server_name_indication(true)
-            .verify_hostname(false);
+        let mut config = match (self.config.as_deref(), client_hello) {
+            (Some(config), Some(hello)) => {
+                Cow::Owned(config.clone().with_emulated_client_hello(&hello))
+            }
+            (Some(config), None) => Cow::Borrowed(config),
+            (None, Some(hello)) => Cow::Owned(ClientConfig::from(&hello)),
+            (None, None) => Cow::Owned(ClientConfig::default()),
+        };
+        if self.key_log_intent != KeyLogIntent::Disabled {
+            config.to_mut().keylog_intent = self.key_log_intent.clone();
+        }
+        let mut cfg = config.connect_configuration()?;
+        let verify_error = match verify_policy.or(self.verify_policy.as_ref()) {
+            Some(policy) => {
+                let host = Host::try_from(target_host.as_str())
+                    .context("HttpsConnector: parse target host")?;
+                Some(set_verify_policy(&mut cfg, policy.clone(), host)?)
+            }
+            None => None,
+        };
         tokio_boring::connect(cfg, target_host.as_str(), stream)
             .await
-            .map_err(|err| match err.as_io_error() {
-                Some(err) => OpaqueError::from_display(err.to_string())
-                    .context("boring ssl acceptor: accept")
-                    .into_boxed(),
-                None => OpaqueError::from_display("boring ssl acceptor: accept").into_boxed(),
+            .map_err(|err| {
+                if let Some(err) = verify_error.as_ref().and_then(|slot| slot.lock().take()) {
+                    return err.into_handshake_error();
+                }
+                match err.as_io_error() {
+                    Some(err) => OpaqueError::from_display(err.to_string())
+                        .context("boring ssl acceptor: accept")
+                        .into_boxed(),
+                    None => OpaqueError::from_display("boring ssl acceptor: accept").into_boxed(),
+                }
             })
     }
+
+    fn store_server_info<State, T>(
+        &self,
+        ctx: &mut Context<State>,
+        stream: &SslStream<T>,
+    ) -> Result<(), OpaqueError> {
+        if let Some(protocol) = stream.ssl().selected_alpn_protocol() {
+            ctx.insert(NegotiatedApplicationProtocol(protocol.into()));
+        }
+        if self.store_server_info {
+            ctx.insert(negotiated_tls_parameters(stream.ssl())?);
+        }
+        Ok(())
+    }
+}
+
+fn negotiated_tls_parameters(ssl: &SslRef) -> Result<NegotiatedTlsParameters, OpaqueError> {
+    let protocol_version = match ssl.version2() {
+        Some(SslVersion::SSL3) => ProtocolVersion::SSLv3,
+        Some(SslVersion::TLS1) => ProtocolVersion::TLSv1_0,
+        Some(SslVersion::TLS1_1) => ProtocolVersion::TLSv1_1,
+        Some(SslVersion::TLS1_2) => ProtocolVersion::TLSv1_2,
+        Some(SslVersion::TLS1_3) => ProtocolVersion::TLSv1_3,
+        _ => {
+            return Err(OpaqueError::from_display(
+                "HttpsConnector: missing negotiated protocol version",
+            ))
+        }
+    };
+    let cipher = ssl.current_cipher().ok_or_else(|| {
+        OpaqueError::from_display("HttpsConnector: missing negotiated cipher suite")
+    })?;
+    // SAFETY: the cipher pointer is valid for as long as the borrowed ssl connection,
+    // the `boring` crate does however not (yet) expose this getter itself
+    #[allow(unsafe_code)]
+    let cipher_suite = unsafe { ffi::SSL_CIPHER_get_protocol_id(cipher.as_ptr()) };
+
+    // on the client side the peer chain starts with the leaf certificate
+    let server_certificate_chain = match ssl.peer_cert_chain() {
+        Some(chain) => chain
+            .iter()
+            .map(|cert| cert.to_der())
+            .collect::<Result<_, _>>()
+            .context("HttpsConnector: encode server certificate chain")?,
+        None => Vec::new(),
+    };
+
+    Ok(NegotiatedTlsParameters {
+        protocol_version,
+        cipher_suite: cipher_suite.into(),
+        application_layer_protocol: ssl.selected_alpn_protocol().map(Into::into),
+        server_certificate_chain,
+        session_resumed: ssl.session_reused(),
+    })
 }
 
 pin_project! {
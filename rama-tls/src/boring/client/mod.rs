@@ -3,3 +3,18 @@
 mod http;
 #[doc(inline)]
 pub use http::{AutoTlsStream, HttpsConnector, HttpsConnectorLayer};
+
+mod config;
+#[doc(inline)]
+pub use config::{ClientAuth, ClientConfig};
+
+#[doc(inline)]
+pub use crate::verify::ServerVerifyMode;
+
+mod verify;
+
+mod emulate;
+
+mod cert_compression;
+#[doc(inline)]
+pub use cert_compression::CertificateCompressionAlgorithm;
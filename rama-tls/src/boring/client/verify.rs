@@ -0,0 +1,240 @@
+use crate::boring::dep::boring::{
+    ssl::{ConnectConfiguration, SslAlert, SslRef, SslVerifyError, SslVerifyMode},
+    stack::{Stack, StackRef},
+    x509::{
+        store::{X509Store, X509StoreBuilder, X509StoreRef},
+        X509Ref, X509StoreContext, X509,
+    },
+};
+use crate::verify::{
+    ServerVerifyError, ServerVerifyErrorKind, ServerVerifyMode, ServerVerifyPolicy, SpkiPin,
+};
+use boring_sys as ffi;
+use foreign_types::ForeignTypeRef;
+use parking_lot::Mutex;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::{
+    address::Host,
+    tls::server::{PeerCertificate, SubjectAltName},
+};
+use std::sync::Arc;
+
+/// Slot in which the verify callback stores why the server certificate got rejected,
+/// as boring itself only reports the alert it sent.
+pub(super) type ServerVerifyErrorSlot = Arc<Mutex<Option<ServerVerifyError>>>;
+
+/// Verify the server certificate of the given connection according to the given policy,
+/// replacing the verification defined by the [`ClientConfig`].
+///
+/// [`ClientConfig`]: super::ClientConfig
+pub(super) fn set_verify_policy(
+    cfg: &mut ConnectConfiguration,
+    policy: ServerVerifyPolicy,
+    host: Host,
+) -> Result<ServerVerifyErrorSlot, OpaqueError> {
+    let extra_roots = match policy.mode() {
+        ServerVerifyMode::Auto if !policy.root_certs().is_empty() => {
+            let mut builder = X509StoreBuilder::new().context("create boring x509 store")?;
+            for der in policy.root_certs() {
+                let cert = X509::from_der(der).context("boring: parse root cert")?;
+                builder
+                    .add_cert(cert)
+                    .context("boring: add root cert to x509 store")?;
+            }
+            Some(builder.build())
+        }
+        _ => None,
+    };
+
+    let slot = ServerVerifyErrorSlot::default();
+    let callback_slot = slot.clone();
+    cfg.set_custom_verify_callback(SslVerifyMode::PEER, move |ssl| {
+        verify_server(ssl, &policy, &host, extra_roots.as_ref()).map_err(|err| {
+            tracing::debug!(%err, "boring: server certificate rejected by verify policy");
+            *callback_slot.lock() = Some(err);
+            SslVerifyError::Invalid(SslAlert::BAD_CERTIFICATE)
+        })
+    });
+    Ok(slot)
+}
+
+fn verify_server(
+    ssl: &mut SslRef,
+    policy: &ServerVerifyPolicy,
+    host: &Host,
+    extra_roots: Option<&X509Store>,
+) -> Result<(), ServerVerifyError> {
+    let invalid_certificate = |reason: String| {
+        ServerVerifyError::new(
+            host.clone(),
+            ServerVerifyErrorKind::InvalidCertificate(reason),
+        )
+    };
+
+    // on the client side the peer chain starts with the leaf certificate
+    let chain: Vec<X509> = ssl
+        .peer_cert_chain()
+        .map(|chain| chain.iter().map(ToOwned::to_owned).collect())
+        .unwrap_or_default();
+    let Some((leaf, intermediates)) = chain.split_first() else {
+        return Err(invalid_certificate("no certificate presented".to_owned()));
+    };
+
+    // the presented intermediates are only trusted (and pinned) as part of a verified path
+    let verified_path = match policy.mode() {
+        ServerVerifyMode::Auto => {
+            let mut stack = Stack::new().map_err(|err| invalid_certificate(err.to_string()))?;
+            for cert in intermediates {
+                stack
+                    .push(cert.clone())
+                    .map_err(|err| invalid_certificate(err.to_string()))?;
+            }
+
+            let path = verify_chain(ssl.ssl_context().cert_store(), leaf, &stack)
+                .or_else(|reason| match extra_roots {
+                    Some(store) => verify_chain(store, leaf, &stack).map_err(|_| reason),
+                    None => Err(reason),
+                })
+                .map_err(|reason| {
+                    ServerVerifyError::new(host.clone(), ServerVerifyErrorKind::Untrusted(reason))
+                })?;
+
+            if !verify_hostname(leaf, host).map_err(invalid_certificate)? {
+                return Err(ServerVerifyError::new(
+                    host.clone(),
+                    ServerVerifyErrorKind::HostnameMismatch,
+                ));
+            }
+
+            path.iter()
+                .map(|der| SpkiPin::from_certificate_der(der.as_slice()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| invalid_certificate(err.to_string()))?
+        }
+        ServerVerifyMode::Disable => Vec::new(),
+    };
+
+    let leaf = leaf
+        .to_der()
+        .map_err(|err| invalid_certificate(err.to_string()))?;
+    policy.verify_pins(host, &leaf, &verified_path)
+}
+
+/// Verify the given certificate chain against the given trust store,
+/// returning the DER encoded certificates of the verified path (leaf excluded),
+/// or the reason of failure in case it is not trusted.
+///
+/// The chain is verified for the TLS server purpose, as is done by boring itself,
+/// such that e.g. a leaf certificate only valid for client authentication is rejected.
+fn verify_chain(
+    trust: &X509StoreRef,
+    leaf: &X509Ref,
+    chain: &StackRef<X509>,
+) -> Result<Vec<Vec<u8>>, String> {
+    let mut ctx = X509StoreContext::new().map_err(|err| err.to_string())?;
+    ctx.init(trust, leaf, chain, |ctx| {
+        // SAFETY: the context is initialized for the duration of this closure,
+        // the `boring` crate does however not (yet) expose this setter itself
+        #[allow(unsafe_code)]
+        let purpose_set =
+            unsafe { ffi::X509_STORE_CTX_set_default(ctx.as_ptr(), c"ssl_server".as_ptr()) };
+        if purpose_set != 1 {
+            return Ok(Err("set tls server verify purpose".to_owned()));
+        }
+
+        Ok(if ctx.verify_cert()? {
+            // the verified chain starts with the leaf certificate and ends with the trust anchor
+            Ok(ctx
+                .chain()
+                .map(|chain| {
+                    chain
+                        .iter()
+                        .skip(1)
+                        .map(|cert| cert.to_der())
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()?
+                .unwrap_or_default())
+        } else {
+            Err(ctx.verify_result().err().map_or_else(
+                || "certificate verification failed".to_owned(),
+                |err| err.to_string(),
+            ))
+        })
+    })
+    .map_err(|err| err.to_string())?
+}
+
+fn verify_hostname(leaf: &X509Ref, host: &Host) -> Result<bool, String> {
+    match host {
+        Host::Name(domain) => leaf
+            .check_host(domain.as_str())
+            .map_err(|err| err.to_string()),
+        Host::Address(ip) => {
+            let der = leaf.to_der().map_err(|err| err.to_string())?;
+            let cert = PeerCertificate::from_der(der).map_err(|err| err.to_string())?;
+            Ok(cert
+                .subject_alt_names()
+                .iter()
+                .any(|name| matches!(name, SubjectAltName::Ip(addr) if addr == ip)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::rcgen;
+
+    fn x509(cert: &rcgen::Certificate) -> X509 {
+        X509::from_der(cert.der()).unwrap()
+    }
+
+    fn leaf_cert(
+        purposes: Vec<rcgen::ExtendedKeyUsagePurpose>,
+        issuer: &rcgen::Certificate,
+        issuer_key: &rcgen::KeyPair,
+    ) -> rcgen::Certificate {
+        let mut params = rcgen::CertificateParams::new(vec!["example.com".to_owned()]).unwrap();
+        params.extended_key_usages = purposes;
+        params
+            .signed_by(&rcgen::KeyPair::generate().unwrap(), issuer, issuer_key)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_chain_server_purpose() {
+        let root_key = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec![]).unwrap();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let root = params.self_signed(&root_key).unwrap();
+
+        let mut builder = X509StoreBuilder::new().unwrap();
+        builder.add_cert(x509(&root)).unwrap();
+        let trust = builder.build();
+        let chain = Stack::new().unwrap();
+
+        for purposes in [
+            vec![],
+            vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth],
+            vec![
+                rcgen::ExtendedKeyUsagePurpose::ServerAuth,
+                rcgen::ExtendedKeyUsagePurpose::ClientAuth,
+            ],
+        ] {
+            let leaf = leaf_cert(purposes.clone(), &root, &root_key);
+            assert_eq!(
+                verify_chain(&trust, &x509(&leaf), &chain).unwrap(),
+                vec![root.der().to_vec()],
+                "purposes: {purposes:?}",
+            );
+        }
+
+        let leaf = leaf_cert(
+            vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth],
+            &root,
+            &root_key,
+        );
+        assert!(verify_chain(&trust, &x509(&leaf), &chain).is_err());
+    }
+}
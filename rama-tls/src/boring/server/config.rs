@@ -2,6 +2,9 @@ use crate::boring::dep::boring::{
     pkey::{PKey, Private},
     x509::X509,
 };
+use crate::cert_store::LiveCertStore;
+use crate::issuer::CertIssuer;
+use crate::keylog::KeyLogIntent;
 use crate::types::ApplicationProtocol;
 
 #[derive(Clone, Debug)]
@@ -14,10 +17,21 @@ pub struct ServerConfig {
     /// Set the ALPN protocols supported by the service's inner application service.
     pub alpn_protocols: Vec<ApplicationProtocol>,
     /// Write logging information to facilitate tls interception.
+    ///
+    /// Only used in case the `keylog_intent` is [`KeyLogIntent::Disabled`].
+    #[deprecated(
+        note = "use `ServerConfig::with_keylog_intent` with `KeyLogIntent::File` instead"
+    )]
     pub keylog_filename: Option<String>,
+    pub(crate) keylog_intent: KeyLogIntent,
+    pub(crate) cert_issuer: Option<CertIssuer>,
+    pub(crate) cert_store: Option<LiveCertStore>,
+    pub(crate) client_ca_certs: Option<Vec<X509>>,
+    pub(crate) client_cert_required: bool,
 }
 
 impl ServerConfig {
+    #[allow(deprecated)]
     /// Create a new [`ServerConfig`].
     pub const fn new(private_key: PKey<Private>, ca_cert_chain: Vec<X509>) -> ServerConfig {
         ServerConfig {
@@ -25,6 +39,103 @@ impl ServerConfig {
             ca_cert_chain,
             alpn_protocols: vec![],
             keylog_filename: None,
+            keylog_intent: KeyLogIntent::Disabled,
+            cert_issuer: None,
+            cert_store: None,
+            client_ca_certs: None,
+            client_cert_required: false,
+        }
+    }
+
+    /// Write logging information to facilitate tls interception.
+    pub fn with_keylog_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.keylog_intent = intent;
+        self
+    }
+
+    /// Write logging information to facilitate tls interception.
+    pub fn set_keylog_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.keylog_intent = intent;
+        self
+    }
+
+    /// Issue a certificate on the fly for the server name (SNI) requested by the client,
+    /// instead of using the static certificate (chain) of this config.
+    ///
+    /// The static certificate (chain) is still used for clients
+    /// which do not request a server name. The subject alternative names
+    /// of the [`UpstreamCert`] found in the [`Context`], if any, are mirrored.
+    ///
+    /// [`UpstreamCert`]: crate::issuer::UpstreamCert
+    /// [`Context`]: rama_core::Context
+    pub fn with_cert_issuer(mut self, issuer: CertIssuer) -> Self {
+        self.cert_issuer = Some(issuer);
+        self
+    }
+
+    /// Issue a certificate on the fly for the server name (SNI) requested by the client,
+    /// instead of using the static certificate (chain) of this config.
+    pub fn set_cert_issuer(&mut self, issuer: CertIssuer) -> &mut Self {
+        self.cert_issuer = Some(issuer);
+        self
+    }
+
+    /// Use the current certificates of the [`LiveCertStore`] for each new handshake,
+    /// such that these can be replaced without restarting the server.
+    ///
+    /// Server names (SNI) for which the [`LiveCertStore`] has no certificate,
+    /// not even a default one, are handled by the [`CertIssuer`] if set,
+    /// or otherwise with the static certificate (chain) of this config.
+    pub fn with_cert_store(mut self, store: LiveCertStore) -> Self {
+        self.cert_store = Some(store);
+        self
+    }
+
+    /// Use the current certificates of the [`LiveCertStore`] for each new handshake,
+    /// such that these can be replaced without restarting the server.
+    pub fn set_cert_store(&mut self, store: LiveCertStore) -> &mut Self {
+        self.cert_store = Some(store);
+        self
+    }
+
+    /// Set the CA certificates used to verify the certificates presented by clients (mTLS).
+    ///
+    /// Clients are requested to present a certificate in case these are set,
+    /// which is made available as a [`ClientIdentity`] in the [`Context`].
+    ///
+    /// [`ClientIdentity`]: crate::types::server::ClientIdentity
+    /// [`Context`]: rama_core::Context
+    pub fn with_client_ca_certs(mut self, certs: Vec<X509>) -> Self {
+        self.client_ca_certs = Some(certs);
+        self
+    }
+
+    /// Set the CA certificates used to verify the certificates presented by clients (mTLS).
+    pub fn set_client_ca_certs(&mut self, certs: Vec<X509>) -> &mut Self {
+        self.client_ca_certs = Some(certs);
+        self
+    }
+
+    /// Reject clients which do not present a (valid) certificate,
+    /// only used in case the client CA certificates are set.
+    pub const fn with_client_cert_required(mut self, required: bool) -> Self {
+        self.client_cert_required = required;
+        self
+    }
+
+    /// Reject clients which do not present a (valid) certificate,
+    /// only used in case the client CA certificates are set.
+    pub fn set_client_cert_required(&mut self, required: bool) -> &mut Self {
+        self.client_cert_required = required;
+        self
+    }
+
+    #[allow(deprecated)]
+    /// The [`KeyLogIntent`] of this config, falling back to the (deprecated) `keylog_filename`.
+    pub(crate) fn effective_keylog_intent(&self) -> KeyLogIntent {
+        match (&self.keylog_intent, &self.keylog_filename) {
+            (KeyLogIntent::Disabled, Some(filename)) => KeyLogIntent::File(filename.into()),
+            (intent, _) => intent.clone(),
         }
     }
 }
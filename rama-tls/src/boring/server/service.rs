@@ -1,10 +1,19 @@
 use super::ServerConfig;
 use crate::{
     boring::dep::{
-        boring::ssl::{SslAcceptor, SslMethod},
+        boring::{
+            pkey::{PKey, Private},
+            ssl::{
+                ClientHello as BoringClientHello, NameType, SelectCertError, SslAcceptor,
+                SslMethod, SslRef, SslVerifyMode,
+            },
+            x509::{store::X509StoreBuilder, X509},
+        },
         tokio_boring::SslStream,
     },
+    issuer::UpstreamCert,
     types::client::ClientHello,
+    types::server::ClientIdentity,
     types::SecureTransport,
 };
 use parking_lot::Mutex;
@@ -12,7 +21,7 @@ use rama_core::{
     error::{ErrorContext, ErrorExt, OpaqueError},
     Context, Service,
 };
-use rama_net::stream::Stream;
+use rama_net::{address::Domain, stream::Stream};
 use rama_utils::macros::define_inner_service_accessors;
 use std::{fmt, sync::Arc};
 
@@ -82,46 +91,85 @@ where
             .context("build boring ssl acceptor: set default verify paths")
             .map_err(TlsAcceptorError::Accept)?;
 
-        for (i, ca_cert) in self.config.ca_cert_chain.iter().enumerate() {
-            if i == 0 {
-                acceptor_builder
-                    .set_certificate(ca_cert.as_ref())
-                    .context("build boring ssl acceptor: set Leaf CA certificate (x509)")
+        let dynamic_cert = self.config.cert_issuer.is_some() || self.config.cert_store.is_some();
+        if !dynamic_cert {
+            for (i, ca_cert) in self.config.ca_cert_chain.iter().enumerate() {
+                if i == 0 {
+                    acceptor_builder
+                        .set_certificate(ca_cert.as_ref())
+                        .context("build boring ssl acceptor: set Leaf CA certificate (x509)")
+                        .map_err(TlsAcceptorError::Accept)?;
+                } else {
+                    acceptor_builder
+                        .add_extra_chain_cert(ca_cert.clone())
+                        .context("build boring ssl acceptor: add extra chain certificate (x509)")
+                        .map_err(TlsAcceptorError::Accept)?;
+                }
+            }
+            acceptor_builder
+                .set_private_key(self.config.private_key.as_ref())
+                .context("build boring ssl acceptor: set private key")
+                .map_err(TlsAcceptorError::Accept)?;
+            acceptor_builder
+                .check_private_key()
+                .context("build boring ssl acceptor: check private key")
+                .map_err(TlsAcceptorError::Accept)?;
+        }
+
+        if let Some(client_ca_certs) = &self.config.client_ca_certs {
+            let mut store_builder = X509StoreBuilder::new()
+                .context("build boring ssl acceptor: create client ca store")
+                .map_err(TlsAcceptorError::Accept)?;
+            for ca_cert in client_ca_certs {
+                store_builder
+                    .add_cert(ca_cert.clone())
+                    .context("build boring ssl acceptor: add client ca certificate to store")
                     .map_err(TlsAcceptorError::Accept)?;
-            } else {
                 acceptor_builder
-                    .add_extra_chain_cert(ca_cert.clone())
-                    .context("build boring ssl acceptor: add extra chain certificate (x509)")
+                    .add_client_ca(ca_cert)
+                    .context("build boring ssl acceptor: add client ca certificate")
                     .map_err(TlsAcceptorError::Accept)?;
             }
+            acceptor_builder
+                .set_verify_cert_store(store_builder.build())
+                .context("build boring ssl acceptor: set client ca store")
+                .map_err(TlsAcceptorError::Accept)?;
+            acceptor_builder.set_verify(if self.config.client_cert_required {
+                SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT
+            } else {
+                SslVerifyMode::PEER
+            });
         }
-        acceptor_builder
-            .set_private_key(self.config.private_key.as_ref())
-            .context("build boring ssl acceptor: set private key")
-            .map_err(TlsAcceptorError::Accept)?;
-        acceptor_builder
-            .check_private_key()
-            .context("build boring ssl acceptor: check private key")
-            .map_err(TlsAcceptorError::Accept)?;
 
-        let mut maybe_client_hello = if self.store_client_hello {
-            let maybe_client_hello = Arc::new(Mutex::new(None));
+        let mut maybe_client_hello = self.store_client_hello.then(|| Arc::new(Mutex::new(None)));
+        if maybe_client_hello.is_some() || dynamic_cert {
             let cb_maybe_client_hello = maybe_client_hello.clone();
-            acceptor_builder.set_select_certificate_callback(move |boring_client_hello| {
-                let maybe_client_hello = match ClientHello::try_from(boring_client_hello) {
-                    Ok(ch) => Some(ch),
-                    Err(err) => {
-                        tracing::warn!(err = %err, "failed to extract boringssl client hello");
-                        None
+            let config = self.config.clone();
+            let upstream_cert = ctx.get::<UpstreamCert>().cloned();
+            acceptor_builder.set_select_certificate_callback(move |mut boring_client_hello| {
+                if dynamic_cert {
+                    // the certificate (chain) is set per connection,
+                    // as the context cannot be modified at this point
+                    if let Err(err) =
+                        set_certificate(&config, upstream_cert.as_ref(), &mut boring_client_hello)
+                    {
+                        tracing::warn!(err = %err, "failed to set boringssl certificate");
+                        return Err(SelectCertError::ERROR);
                     }
-                };
-                *cb_maybe_client_hello.lock() = maybe_client_hello;
+                }
+                if let Some(cb_maybe_client_hello) = &cb_maybe_client_hello {
+                    let maybe_client_hello = match ClientHello::try_from(boring_client_hello) {
+                        Ok(ch) => Some(ch),
+                        Err(err) => {
+                            tracing::warn!(err = %err, "failed to extract boringssl client hello");
+                            None
+                        }
+                    };
+                    *cb_maybe_client_hello.lock() = maybe_client_hello;
+                }
                 Ok(())
             });
-            Some(maybe_client_hello)
-        } else {
-            None
-        };
+        }
 
         if !self.config.alpn_protocols.is_empty() {
             let mut buf = vec![];
@@ -136,20 +184,14 @@ where
                 .map_err(TlsAcceptorError::Accept)?;
         }
 
-        if let Some(keylog_filename) = &self.config.keylog_filename {
-            // open file in append mode and write keylog to it with callback
-            let file = std::fs::OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(keylog_filename)
-                .context("build boring ssl acceptor: set keylog: open file")
-                .map_err(TlsAcceptorError::Accept)?;
-            acceptor_builder.set_keylog_callback(move |_, line| {
-                use std::io::Write;
-                let line = format!("{}\n", line);
-                let mut file = &file;
-                let _ = file.write_all(line.as_bytes());
-            });
+        if let Some(writer) = self
+            .config
+            .effective_keylog_intent()
+            .writer()
+            .context("build boring ssl acceptor: set keylog")
+            .map_err(TlsAcceptorError::Accept)?
+        {
+            acceptor_builder.set_keylog_callback(move |_, line| writer.write_line(line));
         }
 
         let acceptor = acceptor_builder.build();
@@ -168,7 +210,14 @@ where
             .and_then(|maybe_client_hello| maybe_client_hello.lock().take())
             .map(SecureTransport::with_client_hello)
             .unwrap_or_default();
+        if let Some(hello) = secure_transport.client_hello() {
+            ctx.insert(hello.ja3());
+            ctx.insert(hello.ja4());
+        }
         ctx.insert(secure_transport);
+        if let Some(identity) = client_identity(stream.ssl()) {
+            ctx.insert(identity);
+        }
 
         self.inner
             .serve(ctx, stream)
@@ -177,6 +226,99 @@ where
     }
 }
 
+/// Returns the [`ClientIdentity`] of the peer of the given (accepted) connection,
+/// in case it presented a certificate.
+fn client_identity(ssl: &SslRef) -> Option<ClientIdentity> {
+    let cert = ssl.peer_certificate()?.to_der().ok()?;
+    // the peer chain does not contain the leaf certificate at the server side,
+    // but filter it out anyway in case this changes
+    let chain: Vec<_> = ssl
+        .peer_cert_chain()
+        .into_iter()
+        .flatten()
+        .filter_map(|cert| cert.to_der().ok())
+        .filter(|der| der != &cert)
+        .collect();
+    match ClientIdentity::from_der_chain(std::iter::once(cert).chain(chain)) {
+        Ok(identity) => Some(identity),
+        Err(err) => {
+            tracing::warn!(err = %err, "failed to parse boringssl peer certificate");
+            None
+        }
+    }
+}
+
+/// Set the certificate (chain) and private key for the connection of the given [`BoringClientHello`],
+/// found in the [`LiveCertStore`] or issued by the [`CertIssuer`] for the requested server name,
+/// falling back to the static ones of the [`ServerConfig`].
+///
+/// The issued certificate mirrors the subject alternative names of the [`UpstreamCert`], if any.
+///
+/// [`LiveCertStore`]: crate::cert_store::LiveCertStore
+/// [`CertIssuer`]: crate::issuer::CertIssuer
+fn set_certificate(
+    config: &ServerConfig,
+    upstream_cert: Option<&UpstreamCert>,
+    client_hello: &mut BoringClientHello<'_>,
+) -> Result<(), OpaqueError> {
+    let server_name = client_hello
+        .servername(NameType::HOST_NAME)
+        .and_then(|name| name.parse::<Domain>().ok());
+    let ssl = client_hello.ssl_mut();
+
+    if let Some(cert) = config
+        .cert_store
+        .as_ref()
+        .and_then(|store| store.get(server_name.as_ref()))
+    {
+        let private_key = PKey::private_key_from_der(cert.private_key_der())
+            .context("parse cert store private key")?;
+        return set_der_certificate(ssl, cert.cert_chain_der(), &private_key);
+    }
+
+    if let (Some(cert_issuer), Some(server_name)) = (&config.cert_issuer, &server_name) {
+        let issued = cert_issuer
+            .issue(server_name, upstream_cert.map(AsRef::as_ref))
+            .context("issue certificate")?;
+        let private_key = PKey::private_key_from_pkcs8(issued.private_key_der())
+            .context("parse issued private key")?;
+        return set_der_certificate(ssl, issued.cert_chain_der(), &private_key);
+    }
+
+    for (i, cert) in config.ca_cert_chain.iter().enumerate() {
+        if i == 0 {
+            ssl.set_certificate(cert.as_ref())
+                .context("set Leaf CA certificate (x509)")?;
+        } else {
+            ssl.add_chain_cert(cert.as_ref())
+                .context("add chain certificate (x509)")?;
+        }
+    }
+    ssl.set_private_key(config.private_key.as_ref())
+        .context("set private key")?;
+    Ok(())
+}
+
+fn set_der_certificate(
+    ssl: &mut SslRef,
+    cert_chain_der: &[Vec<u8>],
+    private_key: &PKey<Private>,
+) -> Result<(), OpaqueError> {
+    for (i, der) in cert_chain_der.iter().enumerate() {
+        let cert = X509::from_der(der).context("parse certificate (x509)")?;
+        if i == 0 {
+            ssl.set_certificate(&cert)
+                .context("set Leaf certificate (x509)")?;
+        } else {
+            ssl.add_chain_cert(&cert)
+                .context("add chain certificate (x509)")?;
+        }
+    }
+    ssl.set_private_key(private_key)
+        .context("set private key")?;
+    Ok(())
+}
+
 /// Errors that can happen when using [`TlsAcceptorService`].
 pub enum TlsAcceptorError<E> {
     /// An error occurred while accepting a TLS connection.
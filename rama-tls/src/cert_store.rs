@@ -0,0 +1,730 @@
+//! Server certificates which can be reloaded without restarting the server.
+//!
+//! A [`CertStore`] holds the certificates of a server: one per server name (SNI),
+//! as well as a default certificate used for all other server names,
+//! or in case the client does not request a server name.
+//!
+//! Use [`cert_store_updater`] to create a [`LiveCertStore`], used by the acceptors
+//! to look up the certificate for each new handshake, and the _only_ linked
+//! [`LiveCertStoreSetter`] to replace its [`CertStore`]. The latter can be done
+//! by pushing a new [`CertStore`] or by watching the PEM files of a [`CertStoreFiles`].
+//! Handshakes that are already in progress are not affected by such updates.
+//!
+//! The [`LiveCertStore`] is TLS implementation agnostic, and can be used
+//! with both the rustls and boring acceptors:
+//!
+//! - rustls: `rama_tls::rustls::server::CertStoreProvider`, a `ServerConfigProvider`
+//!   to be used with the `TlsClientConfigHandler`;
+//! - boring: `rama_tls::boring::server::ServerConfig::with_cert_store`.
+
+use crate::dep::rcgen::KeyPair;
+use arc_swap::ArcSwap;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::address::Domain;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use x509_parser::{
+    der_parser::{
+        ber::{BerObject, BerObjectContent},
+        der::{parse_der_bitstring, parse_der_sequence},
+    },
+    prelude::{FromDer, X509Certificate},
+    public_key::PublicKey,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The format of the DER encoded private key of a [`ServerCert`].
+pub(crate) enum PrivateKeyFormat {
+    Pkcs8,
+    Pkcs1,
+    Sec1,
+}
+
+#[derive(Clone)]
+/// A server certificate (chain) and its private key.
+pub struct ServerCert {
+    cert_chain: Vec<Vec<u8>>,
+    private_key: Vec<u8>,
+    private_key_format: PrivateKeyFormat,
+}
+
+impl fmt::Debug for ServerCert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerCert")
+            .field("cert_chain", &self.cert_chain.len())
+            .field("private_key_format", &self.private_key_format)
+            .finish()
+    }
+}
+
+impl ServerCert {
+    /// Create a new [`ServerCert`] from the given DER encoded certificate chain,
+    /// starting with the leaf certificate, and the DER encoded (PKCS#8) private key.
+    ///
+    /// An error is returned in case the private key does not belong to the leaf certificate.
+    pub fn new(
+        cert_chain_der: Vec<Vec<u8>>,
+        private_key_der: Vec<u8>,
+    ) -> Result<Self, OpaqueError> {
+        Self::try_new(cert_chain_der, private_key_der, PrivateKeyFormat::Pkcs8)
+    }
+
+    fn try_new(
+        cert_chain: Vec<Vec<u8>>,
+        private_key: Vec<u8>,
+        private_key_format: PrivateKeyFormat,
+    ) -> Result<Self, OpaqueError> {
+        let leaf = cert_chain.first().ok_or_else(|| {
+            OpaqueError::from_display("server certificate: empty certificate chain")
+        })?;
+        check_private_key(leaf, &private_key, private_key_format)
+            .context("server certificate: check private key")?;
+        Ok(Self {
+            cert_chain,
+            private_key,
+            private_key_format,
+        })
+    }
+
+    /// Create a new [`ServerCert`] from the given PEM encoded certificate chain,
+    /// starting with the leaf certificate, and the PEM encoded private key.
+    ///
+    /// The private key can be a PKCS#8, PKCS#1 (RSA) or SEC1 (EC) private key,
+    /// where the latter has to include its public key.
+    /// An error is returned in case it does not belong to the leaf certificate.
+    pub fn from_pem(cert_chain_pem: &str, private_key_pem: &str) -> Result<Self, OpaqueError> {
+        let cert_chain: Vec<_> = pem::parse_many(cert_chain_pem)
+            .context("parse certificate chain (pem)")?
+            .into_iter()
+            .filter(|pem| pem.tag() == "CERTIFICATE")
+            .map(pem::Pem::into_contents)
+            .collect();
+        if cert_chain.is_empty() {
+            return Err(OpaqueError::from_display(
+                "parse certificate chain (pem): no certificate found",
+            ));
+        }
+
+        let (private_key, private_key_format) = pem::parse_many(private_key_pem)
+            .context("parse private key (pem)")?
+            .into_iter()
+            .find_map(|pem| {
+                let format = match pem.tag() {
+                    "PRIVATE KEY" => PrivateKeyFormat::Pkcs8,
+                    "RSA PRIVATE KEY" => PrivateKeyFormat::Pkcs1,
+                    "EC PRIVATE KEY" => PrivateKeyFormat::Sec1,
+                    _ => return None,
+                };
+                Some((pem.into_contents(), format))
+            })
+            .ok_or_else(|| OpaqueError::from_display("parse private key (pem): no key found"))?;
+
+        Self::try_new(cert_chain, private_key, private_key_format)
+    }
+
+    /// The DER encoded certificate chain, starting with the leaf certificate.
+    pub fn cert_chain_der(&self) -> &[Vec<u8>] {
+        &self.cert_chain
+    }
+
+    /// The DER encoded private key of the (leaf) certificate.
+    pub fn private_key_der(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    pub(crate) fn private_key_format(&self) -> PrivateKeyFormat {
+        self.private_key_format
+    }
+}
+
+/// Check that the given private key belongs to the given (leaf) certificate,
+/// by comparing the public key derived from the private key with the one of the certificate.
+fn check_private_key(
+    cert_der: &[u8],
+    private_key_der: &[u8],
+    private_key_format: PrivateKeyFormat,
+) -> Result<(), OpaqueError> {
+    let (_, cert) = X509Certificate::from_der(cert_der)
+        .map_err(|err| OpaqueError::from_display(err.to_string()))
+        .context("parse leaf certificate")?;
+    let cert_public_key = cert.public_key();
+
+    let matches = match private_key_format {
+        PrivateKeyFormat::Pkcs8 => {
+            let key_pair =
+                KeyPair::try_from(private_key_der).context("parse private key (pkcs8)")?;
+            key_pair.public_key_raw() == cert_public_key.subject_public_key.data.as_ref()
+        }
+        PrivateKeyFormat::Pkcs1 => {
+            // RSAPrivateKey ::= SEQUENCE { version, modulus, publicExponent, ... }
+            let (modulus, exponent) = der_sequence(private_key_der)
+                .and_then(|items| {
+                    Some((
+                        items.get(1)?.as_slice().ok()?,
+                        items.get(2)?.as_slice().ok()?,
+                    ))
+                })
+                .ok_or_else(|| OpaqueError::from_display("parse private key (pkcs1)"))?;
+            match cert_public_key.parsed() {
+                Ok(PublicKey::RSA(key)) => {
+                    trim_integer(key.modulus) == trim_integer(modulus)
+                        && trim_integer(key.exponent) == trim_integer(exponent)
+                }
+                _ => false,
+            }
+        }
+        PrivateKeyFormat::Sec1 => {
+            // ECPrivateKey ::= SEQUENCE { version, privateKey, [0] parameters OPTIONAL, [1] publicKey OPTIONAL }
+            let items = der_sequence(private_key_der)
+                .ok_or_else(|| OpaqueError::from_display("parse private key (sec1)"))?;
+            // the public key is required, as it cannot be derived without an EC implementation
+            let public_key = items
+                .iter()
+                .skip(2)
+                .find_map(sec1_public_key)
+                .ok_or_else(|| {
+                    OpaqueError::from_display(
+                        "sec1 private key without public key is not supported",
+                    )
+                })?;
+            public_key == cert_public_key.subject_public_key.data.as_ref()
+        }
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(OpaqueError::from_display(
+            "private key does not match the certificate",
+        ))
+    }
+}
+
+fn der_sequence(der: &[u8]) -> Option<Vec<BerObject<'_>>> {
+    let (_, obj) = parse_der_sequence(der).ok()?;
+    obj.as_sequence().ok().cloned()
+}
+
+/// Returns the content of the (explicitly tagged) `[1] publicKey` bit string of a SEC1 private key.
+fn sec1_public_key<'a>(obj: &BerObject<'a>) -> Option<&'a [u8]> {
+    if obj.header.tag().0 != 1 {
+        return None;
+    }
+    match &obj.content {
+        BerObjectContent::Tagged(_, _, inner) => match &inner.content {
+            BerObjectContent::BitString(_, bits) => Some(bits.data),
+            _ => None,
+        },
+        BerObjectContent::Unknown(any) => {
+            let (_, inner) = parse_der_bitstring(any.data).ok()?;
+            match inner.content {
+                BerObjectContent::BitString(_, bits) => Some(bits.data),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Strip the leading zero bytes of a DER encoded (unsigned) integer.
+fn trim_integer(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+#[derive(Debug, Clone, Default)]
+/// The certificates of a server, one per server name (SNI),
+/// with an optional default certificate for all other server names.
+pub struct CertStore {
+    default: Option<Arc<ServerCert>>,
+    server_names: HashMap<Domain, Arc<ServerCert>>,
+}
+
+impl CertStore {
+    /// Create a new (empty) [`CertStore`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the default [`ServerCert`], used for server names without their own certificate,
+    /// as well as for clients which do not request a server name.
+    pub fn with_default(mut self, cert: ServerCert) -> Self {
+        self.default = Some(Arc::new(cert));
+        self
+    }
+
+    /// Set the default [`ServerCert`], used for server names without their own certificate,
+    /// as well as for clients which do not request a server name.
+    pub fn set_default(&mut self, cert: ServerCert) -> &mut Self {
+        self.default = Some(Arc::new(cert));
+        self
+    }
+
+    /// Set the [`ServerCert`] for the given server name (SNI).
+    pub fn with_server_name(mut self, server_name: Domain, cert: ServerCert) -> Self {
+        self.server_names.insert(server_name, Arc::new(cert));
+        self
+    }
+
+    /// Set the [`ServerCert`] for the given server name (SNI).
+    pub fn set_server_name(&mut self, server_name: Domain, cert: ServerCert) -> &mut Self {
+        self.server_names.insert(server_name, Arc::new(cert));
+        self
+    }
+
+    /// Returns the [`ServerCert`] for the given server name,
+    /// falling back to the default [`ServerCert`], if any.
+    pub fn get(&self, server_name: Option<&Domain>) -> Option<&Arc<ServerCert>> {
+        server_name
+            .and_then(|server_name| self.server_names.get(server_name))
+            .or(self.default.as_ref())
+    }
+}
+
+/// Create a new [`CertStore`] updater which allows you to replace
+/// the certificates of a running server, without having to restart it.
+///
+/// This construct returns a pair of:
+///
+/// - [`LiveCertStore`]: to be used by the acceptors to look up the certificates, dubbed the "reader";
+/// - [`LiveCertStoreSetter`]: to be used as the _only_ way to set the [`CertStore`] as many times as you wish, dubbed the "writer".
+///
+/// Note that the reader starts with an empty [`CertStore`], meaning that
+/// no certificate can be found until you called [`LiveCertStoreSetter::set`].
+/// It is therefore recommended to immediately set the initial [`CertStore`]
+/// upon receiving the reader/writer pair, prior to accepting connections.
+pub fn cert_store_updater() -> (LiveCertStore, LiveCertStoreSetter) {
+    let data = Arc::new(ArcSwap::from_pointee(CertStore::new()));
+    let reader = LiveCertStore(data.clone());
+    let writer = LiveCertStoreSetter(data);
+    (reader, writer)
+}
+
+#[derive(Clone)]
+/// A [`CertStore`] which can be updated through
+/// the _only_ linked writer [`LiveCertStoreSetter`].
+///
+/// See [`cert_store_updater`] for more details.
+pub struct LiveCertStore(Arc<ArcSwap<CertStore>>);
+
+impl fmt::Debug for LiveCertStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LiveCertStore").field(&self.0).finish()
+    }
+}
+
+impl LiveCertStore {
+    /// Returns the [`ServerCert`] for the given server name from the current [`CertStore`],
+    /// falling back to its default [`ServerCert`], if any.
+    pub fn get(&self, server_name: Option<&Domain>) -> Option<Arc<ServerCert>> {
+        self.0.load().get(server_name).cloned()
+    }
+
+    /// Returns the current [`CertStore`], which is replaced
+    /// (as a whole) by every [`LiveCertStoreSetter::set`].
+    pub(crate) fn current(&self) -> Arc<CertStore> {
+        self.0.load_full()
+    }
+}
+
+/// Writer to set a new [`CertStore`] in the linked [`LiveCertStore`].
+///
+/// There can only be one writer [`LiveCertStoreSetter`] for each
+/// collection of [`LiveCertStore`] linked to the same internal [`CertStore`].
+///
+/// See [`cert_store_updater`] for more details.
+pub struct LiveCertStoreSetter(Arc<ArcSwap<CertStore>>);
+
+impl fmt::Debug for LiveCertStoreSetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LiveCertStoreSetter").field(&self.0).finish()
+    }
+}
+
+impl LiveCertStoreSetter {
+    /// Set the new [`CertStore`] to be used for future handshakes
+    /// by the linked [`LiveCertStore`] instances.
+    pub fn set(&self, store: CertStore) {
+        self.0.store(Arc::new(store))
+    }
+
+    /// Set the default [`ServerCert`] of the current [`CertStore`], keeping
+    /// the certificates of its server names, to be used for future handshakes
+    /// by the linked [`LiveCertStore`] instances.
+    pub fn set_default(&self, cert: ServerCert) {
+        let cert = Arc::new(cert);
+        self.0.rcu(|store| CertStore {
+            default: Some(cert.clone()),
+            server_names: store.server_names.clone(),
+        });
+    }
+
+    /// Watch the PEM files of the given [`CertStoreFiles`], checking for modifications
+    /// every `interval`, and set the [`CertStore`] (re)loaded from these files.
+    ///
+    /// The files are loaded immediately, and after that only when one
+    /// of them is modified. In case (re)loading fails, the current [`CertStore`]
+    /// remains in use and loading is retried on the next check.
+    ///
+    /// The returned future runs forever, and is meant to be spawned as a (graceful) task.
+    pub async fn watch(self, files: CertStoreFiles, interval: Duration) {
+        let mut last_modified = None;
+        loop {
+            let modified = files.modified().await;
+            if last_modified.as_ref() != Some(&modified) {
+                match files.load().await {
+                    Ok(store) => {
+                        tracing::debug!("cert store: (re)loaded certificates from pem files");
+                        self.set(store);
+                        last_modified = Some(modified);
+                    }
+                    Err(err) => {
+                        tracing::warn!(err = %err, "cert store: failed to (re)load pem files");
+                    }
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PemFiles {
+    cert_chain: PathBuf,
+    private_key: PathBuf,
+}
+
+impl PemFiles {
+    async fn load(&self) -> Result<ServerCert, OpaqueError> {
+        let cert_chain = tokio::fs::read_to_string(&self.cert_chain)
+            .await
+            .with_context(|| format!("read certificate chain: {}", self.cert_chain.display()))?;
+        let private_key = tokio::fs::read_to_string(&self.private_key)
+            .await
+            .with_context(|| format!("read private key: {}", self.private_key.display()))?;
+        ServerCert::from_pem(&cert_chain, &private_key)
+            .with_context(|| format!("load pem files: {}", self.cert_chain.display()))
+    }
+
+    async fn modified(&self) -> [Option<SystemTime>; 2] {
+        let mut modified = [None; 2];
+        for (modified, path) in modified
+            .iter_mut()
+            .zip([&self.cert_chain, &self.private_key])
+        {
+            *modified = tokio::fs::metadata(path)
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok();
+        }
+        modified
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The PEM files from which a [`CertStore`] can be loaded,
+/// either once or continuously using [`LiveCertStoreSetter::watch`].
+pub struct CertStoreFiles {
+    default: Option<PemFiles>,
+    server_names: Vec<(Domain, PemFiles)>,
+}
+
+impl CertStoreFiles {
+    /// Create a new (empty) [`CertStoreFiles`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the PEM files of the default [`ServerCert`].
+    ///
+    /// See [`CertStore::with_default`] for more information.
+    pub fn with_default(
+        mut self,
+        cert_chain_path: impl Into<PathBuf>,
+        private_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.set_default(cert_chain_path, private_key_path);
+        self
+    }
+
+    /// Set the PEM files of the default [`ServerCert`].
+    ///
+    /// See [`CertStore::set_default`] for more information.
+    pub fn set_default(
+        &mut self,
+        cert_chain_path: impl Into<PathBuf>,
+        private_key_path: impl Into<PathBuf>,
+    ) -> &mut Self {
+        self.default = Some(PemFiles {
+            cert_chain: cert_chain_path.into(),
+            private_key: private_key_path.into(),
+        });
+        self
+    }
+
+    /// Set the PEM files of the [`ServerCert`] for the given server name (SNI).
+    pub fn with_server_name(
+        mut self,
+        server_name: Domain,
+        cert_chain_path: impl Into<PathBuf>,
+        private_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.set_server_name(server_name, cert_chain_path, private_key_path);
+        self
+    }
+
+    /// Set the PEM files of the [`ServerCert`] for the given server name (SNI).
+    pub fn set_server_name(
+        &mut self,
+        server_name: Domain,
+        cert_chain_path: impl Into<PathBuf>,
+        private_key_path: impl Into<PathBuf>,
+    ) -> &mut Self {
+        self.server_names.push((
+            server_name,
+            PemFiles {
+                cert_chain: cert_chain_path.into(),
+                private_key: private_key_path.into(),
+            },
+        ));
+        self
+    }
+
+    /// Load the [`CertStore`] from the PEM files.
+    pub async fn load(&self) -> Result<CertStore, OpaqueError> {
+        let mut store = CertStore::new();
+        if let Some(files) = &self.default {
+            store.set_default(files.load().await?);
+        }
+        for (server_name, files) in &self.server_names {
+            store.set_server_name(server_name.clone(), files.load().await?);
+        }
+        Ok(store)
+    }
+
+    async fn modified(&self) -> Vec<[Option<SystemTime>; 2]> {
+        let mut modified = Vec::with_capacity(self.server_names.len() + 1);
+        for files in self
+            .default
+            .iter()
+            .chain(self.server_names.iter().map(|(_, files)| files))
+        {
+            modified.push(files.modified().await);
+        }
+        modified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::rcgen::{self, KeyPair};
+
+    fn self_signed_pem(name: &str) -> (String, String) {
+        let key_pair = KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec![name.to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        (cert.pem(), key_pair.serialize_pem())
+    }
+
+    fn server_cert(name: &str) -> ServerCert {
+        let (cert, key) = self_signed_pem(name);
+        ServerCert::from_pem(&cert, &key).unwrap()
+    }
+
+    #[test]
+    fn test_server_cert_from_pem() {
+        let (cert, key) = self_signed_pem("example.com");
+        let server_cert = ServerCert::from_pem(&cert, &key).unwrap();
+        assert_eq!(server_cert.cert_chain_der().len(), 1);
+        assert_eq!(server_cert.private_key_format(), PrivateKeyFormat::Pkcs8);
+
+        assert!(ServerCert::from_pem("", &key).is_err());
+        assert!(ServerCert::from_pem(&cert, &cert).is_err());
+
+        // the private key has to belong to the (leaf) certificate
+        let (_, other_key) = self_signed_pem("example.com");
+        assert!(ServerCert::from_pem(&cert, &other_key).is_err());
+    }
+
+    #[test]
+    fn test_server_cert_check_private_key() {
+        let key_pair = KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        let cert_der = cert.der().to_vec();
+
+        assert!(ServerCert::new(vec![cert_der.clone()], key_pair.serialize_der()).is_ok());
+        assert!(ServerCert::new(vec![], key_pair.serialize_der()).is_err());
+        assert!(ServerCert::new(
+            vec![cert_der.clone()],
+            KeyPair::generate().unwrap().serialize_der()
+        )
+        .is_err());
+
+        // PrivateKeyInfo ::= SEQUENCE { version, algorithm, privateKey OCTET STRING, ... },
+        // where the private key of an EC key pair is a SEC1 ECPrivateKey
+        let sec1_der = |key_pair: &KeyPair| {
+            let pkcs8_der = key_pair.serialize_der();
+            let items = der_sequence(&pkcs8_der).unwrap();
+            items[2].as_slice().unwrap().to_vec()
+        };
+        assert!(check_private_key(&cert_der, &sec1_der(&key_pair), PrivateKeyFormat::Sec1).is_ok());
+        assert!(check_private_key(
+            &cert_der,
+            &sec1_der(&KeyPair::generate().unwrap()),
+            PrivateKeyFormat::Sec1
+        )
+        .is_err());
+
+        // ECPrivateKey ::= SEQUENCE { version 1, privateKey, [0] prime256v1 }, without publicKey
+        let scalar = der_sequence(&sec1_der(&key_pair)).unwrap()[1]
+            .as_slice()
+            .unwrap()
+            .to_vec();
+        let mut sec1_without_public_key = vec![0x30, 0x31, 0x02, 0x01, 0x01, 0x04, 0x20];
+        sec1_without_public_key.extend_from_slice(&scalar);
+        sec1_without_public_key.extend_from_slice(&[
+            0xa0, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07,
+        ]);
+        assert!(der_sequence(&sec1_without_public_key).is_some());
+        assert!(
+            check_private_key(&cert_der, &sec1_without_public_key, PrivateKeyFormat::Sec1).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cert_store_get() {
+        let example = server_cert("example.com");
+        let default = server_cert("localhost");
+
+        let store = CertStore::new().with_server_name(Domain::example(), example.clone());
+        assert!(store.get(None).is_none());
+        assert!(store
+            .get(Some(&Domain::from_static("ramaproxy.org")))
+            .is_none());
+        assert_eq!(
+            store
+                .get(Some(&Domain::from_static("EXAMPLE.com")))
+                .unwrap()
+                .cert_chain_der(),
+            example.cert_chain_der()
+        );
+
+        let store = store.with_default(default.clone());
+        for server_name in [None, Some(Domain::from_static("ramaproxy.org"))] {
+            assert_eq!(
+                store.get(server_name.as_ref()).unwrap().cert_chain_der(),
+                default.cert_chain_der()
+            );
+        }
+        assert_eq!(
+            store
+                .get(Some(&Domain::example()))
+                .unwrap()
+                .cert_chain_der(),
+            example.cert_chain_der()
+        );
+    }
+
+    #[test]
+    fn test_live_cert_store_set() {
+        let (reader, writer) = cert_store_updater();
+        assert!(reader.get(None).is_none());
+
+        let first = server_cert("example.com");
+        writer.set(CertStore::new().with_default(first.clone()));
+        assert_eq!(
+            reader.get(None).unwrap().cert_chain_der(),
+            first.cert_chain_der()
+        );
+
+        let second = server_cert("example.com");
+        writer.set(CertStore::new().with_default(second.clone()));
+        assert_eq!(
+            reader.clone().get(None).unwrap().cert_chain_der(),
+            second.cert_chain_der()
+        );
+
+        // only the default certificate is replaced, the server name certificates are kept
+        let sni = server_cert("ramaproxy.org");
+        writer.set(
+            CertStore::new()
+                .with_default(first.clone())
+                .with_server_name(Domain::from_static("ramaproxy.org"), sni.clone()),
+        );
+        writer.set_default(second.clone());
+        assert_eq!(
+            reader.get(None).unwrap().cert_chain_der(),
+            second.cert_chain_der()
+        );
+        assert_eq!(
+            reader
+                .get(Some(&Domain::from_static("ramaproxy.org")))
+                .unwrap()
+                .cert_chain_der(),
+            sni.cert_chain_der()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_live_cert_store_watch() {
+        let dir = std::env::temp_dir().join(format!("rama-tls-cert-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+
+        let (cert, key) = self_signed_pem("example.com");
+        std::fs::write(&cert_path, &cert).unwrap();
+        std::fs::write(&key_path, &key).unwrap();
+
+        let (reader, writer) = cert_store_updater();
+        let files = CertStoreFiles::new().with_server_name(
+            Domain::example(),
+            cert_path.clone(),
+            key_path.clone(),
+        );
+        let first = files
+            .load()
+            .await
+            .unwrap()
+            .get(Some(&Domain::example()))
+            .unwrap()
+            .clone();
+        tokio::spawn(writer.watch(files, Duration::from_millis(10)));
+
+        let wait_for_cert = |expected: Arc<ServerCert>| {
+            let reader = reader.clone();
+            async move {
+                for _ in 0..500 {
+                    if let Some(cert) = reader.get(Some(&Domain::example())) {
+                        if cert.cert_chain_der() == expected.cert_chain_der() {
+                            return;
+                        }
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                panic!("cert store was not (re)loaded");
+            }
+        };
+        wait_for_cert(first).await;
+
+        // some file systems only have a modification time with a precision of a second
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let (cert, key) = self_signed_pem("example.com");
+        std::fs::write(&cert_path, &cert).unwrap();
+        std::fs::write(&key_path, &key).unwrap();
+        wait_for_cert(Arc::new(ServerCert::from_pem(&cert, &key).unwrap())).await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,452 @@
+//! Issue (leaf) server certificates on the fly, signed by a CA.
+//!
+//! This is typically used by MITM proxies, which need to present a certificate
+//! for the server name (SNI) requested by the client, signed by a CA
+//! trusted by that client.
+//!
+//! The [`CertIssuer`] is TLS implementation agnostic, and can be used
+//! with both the rustls and boring acceptors:
+//!
+//! - rustls: `rama_tls::rustls::server::CertIssuerProvider`, a `ServerConfigProvider`
+//!   to be used with the `TlsClientConfigHandler`;
+//! - boring: `rama_tls::boring::server::ServerConfig::with_cert_issuer`.
+//!
+//! Both acceptors mirror the subject alternative names (SAN) of the
+//! certificate of the upstream server in the issued certificate,
+//! in case that [`UpstreamCert`] is found in the [`Context`] of the incoming connection.
+//!
+//! [`Context`]: rama_core::Context
+
+use crate::dep::rcgen::{
+    self, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
+    SanType,
+};
+#[cfg(feature = "rustls")]
+use crate::rustls::dep::rustls::ServerConfig as RustlsServerConfig;
+use lru::LruCache;
+use parking_lot::Mutex;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::address::Domain;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// Default validity of the certificates issued by the [`CertIssuer`].
+pub const DEFAULT_CERT_VALIDITY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Default capacity of the cache of certificates issued by the [`CertIssuer`].
+pub const DEFAULT_CERT_CACHE_CAPACITY: NonZeroUsize = match NonZeroUsize::new(1024) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
+
+/// The certificates issued are valid starting one day in the past,
+/// to be tolerant of clients with a clock which is a bit behind.
+const NOT_BEFORE_OFFSET: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The type of the key pair generated for the certificates issued by the [`CertIssuer`].
+pub enum CertKeyType {
+    #[default]
+    /// ECDSA using the P-256 curve and SHA-256.
+    EcdsaP256,
+    /// ECDSA using the P-384 curve and SHA-384.
+    EcdsaP384,
+    /// EdDSA using Ed25519.
+    Ed25519,
+}
+
+impl CertKeyType {
+    pub(crate) fn signature_algorithm(self) -> &'static rcgen::SignatureAlgorithm {
+        match self {
+            Self::EcdsaP256 => &rcgen::PKCS_ECDSA_P256_SHA256,
+            Self::EcdsaP384 => &rcgen::PKCS_ECDSA_P384_SHA384,
+            Self::Ed25519 => &rcgen::PKCS_ED25519,
+        }
+    }
+}
+
+/// A (leaf) server certificate issued by the [`CertIssuer`].
+pub struct IssuedCert {
+    cert_chain: Vec<Vec<u8>>,
+    private_key: Vec<u8>,
+    not_after: SystemTime,
+    renew_after: SystemTime,
+    #[cfg(feature = "rustls")]
+    /// The rustls server config using this certificate,
+    /// together with the base config it was derived from.
+    rustls_config: Mutex<Option<(Arc<RustlsServerConfig>, Arc<RustlsServerConfig>)>>,
+}
+
+impl fmt::Debug for IssuedCert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IssuedCert")
+            .field("cert_chain", &self.cert_chain.len())
+            .field("not_after", &self.not_after)
+            .finish()
+    }
+}
+
+impl IssuedCert {
+    /// The DER encoded certificate chain, starting with the issued (leaf)
+    /// certificate, followed by the CA certificate.
+    pub fn cert_chain_der(&self) -> &[Vec<u8>] {
+        &self.cert_chain
+    }
+
+    /// The DER encoded (PKCS#8) private key of the issued certificate.
+    pub fn private_key_der(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    /// The moment after which the issued certificate is no longer valid.
+    pub fn not_after(&self) -> SystemTime {
+        self.not_after
+    }
+
+    #[cfg(feature = "rustls")]
+    /// Returns the rustls server config derived from the given base config for this certificate,
+    /// building it in case it is not yet cached for that base config.
+    ///
+    /// The config is cached as part of the issued certificate,
+    /// such that it is shared by all handshakes as long as the certificate is cached.
+    pub(crate) fn rustls_server_config<E>(
+        &self,
+        base: &Arc<RustlsServerConfig>,
+        build: impl FnOnce(&Self) -> Result<Arc<RustlsServerConfig>, E>,
+    ) -> Result<Arc<RustlsServerConfig>, E> {
+        let mut cached = self.rustls_config.lock();
+        if let Some((cached_base, config)) = cached.as_ref() {
+            if Arc::ptr_eq(cached_base, base) {
+                return Ok(config.clone());
+            }
+        }
+        let config = build(self)?;
+        *cached = Some((base.clone(), config.clone()));
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The DER encoded (leaf) certificate of the upstream server,
+/// whose subject alternative names (SAN) are mirrored by the [`CertIssuer`].
+///
+/// Both the boring and rustls acceptors read it from the [`Context`] of the incoming connection,
+/// such that it can be looked up per connection, e.g. by connecting to the upstream server first.
+///
+/// [`Context`]: rama_core::Context
+pub struct UpstreamCert(pub Vec<u8>);
+
+impl AsRef<[u8]> for UpstreamCert {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+struct CertificateAuthority {
+    cert: rcgen::Certificate,
+    cert_der: Vec<u8>,
+    key_pair: KeyPair,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    server_name: Domain,
+    /// SHA-256 digest of the (DER encoded) upstream certificate, if any.
+    upstream_cert_hash: Option<[u8; 32]>,
+}
+
+#[derive(Clone)]
+/// Issues (leaf) server certificates on the fly, signed by the given CA.
+///
+/// The issued certificates are kept in a bounded LRU cache,
+/// shared between all clones of the [`CertIssuer`], and renewed
+/// once half of their validity has passed.
+///
+/// See [the module docs](self) for more information.
+pub struct CertIssuer {
+    ca: Arc<CertificateAuthority>,
+    validity: Duration,
+    key_type: CertKeyType,
+    cache: Arc<Mutex<LruCache<CacheKey, Arc<IssuedCert>>>>,
+}
+
+impl fmt::Debug for CertIssuer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertIssuer")
+            .field("validity", &self.validity)
+            .field("key_type", &self.key_type)
+            .field("cache_capacity", &self.cache.lock().cap())
+            .finish()
+    }
+}
+
+impl CertIssuer {
+    /// Create a new [`CertIssuer`] for the given DER encoded CA certificate
+    /// and the [`KeyPair`] of that CA certificate.
+    pub fn new(ca_cert_der: impl Into<Vec<u8>>, ca_key_pair: KeyPair) -> Result<Self, OpaqueError> {
+        let cert_der = ca_cert_der.into();
+        let params = CertificateParams::from_ca_cert_der(&cert_der.as_slice().into())
+            .context("parse CA certificate")?;
+        // re-create the CA certificate as rcgen requires it to sign the issued certificates,
+        // only its subject and key matter for that purpose
+        let cert = params
+            .self_signed(&ca_key_pair)
+            .context("create CA certificate")?;
+        Ok(Self {
+            ca: Arc::new(CertificateAuthority {
+                cert,
+                cert_der,
+                key_pair: ca_key_pair,
+            }),
+            validity: DEFAULT_CERT_VALIDITY,
+            key_type: CertKeyType::default(),
+            cache: Arc::new(Mutex::new(LruCache::new(DEFAULT_CERT_CACHE_CAPACITY))),
+        })
+    }
+
+    /// Create a new [`CertIssuer`] for the given PEM encoded CA certificate
+    /// and (PKCS#8) private key of that CA certificate.
+    pub fn from_pem(ca_cert_pem: &str, ca_key_pem: &str) -> Result<Self, OpaqueError> {
+        let ca_key_pair = KeyPair::from_pem(ca_key_pem).context("parse CA private key (pem)")?;
+        let ca_cert = pem::parse(ca_cert_pem).context("parse CA certificate (pem)")?;
+        Self::new(ca_cert.into_contents(), ca_key_pair)
+    }
+
+    /// Set the validity of the issued certificates,
+    /// defaulting to [`DEFAULT_CERT_VALIDITY`].
+    pub fn with_validity(mut self, validity: Duration) -> Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Set the validity of the issued certificates,
+    /// defaulting to [`DEFAULT_CERT_VALIDITY`].
+    pub fn set_validity(&mut self, validity: Duration) -> &mut Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Set the [`CertKeyType`] of the issued certificates.
+    pub fn with_key_type(mut self, key_type: CertKeyType) -> Self {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Set the [`CertKeyType`] of the issued certificates.
+    pub fn set_key_type(&mut self, key_type: CertKeyType) -> &mut Self {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Set the capacity of the cache of issued certificates,
+    /// defaulting to [`DEFAULT_CERT_CACHE_CAPACITY`].
+    pub fn with_cache_capacity(mut self, capacity: NonZeroUsize) -> Self {
+        self.set_cache_capacity(capacity);
+        self
+    }
+
+    /// Set the capacity of the cache of issued certificates,
+    /// defaulting to [`DEFAULT_CERT_CACHE_CAPACITY`].
+    pub fn set_cache_capacity(&mut self, capacity: NonZeroUsize) -> &mut Self {
+        self.cache.lock().resize(capacity);
+        self
+    }
+
+    /// Issue a certificate for the given server name,
+    /// or return the cached one if it was already issued before.
+    ///
+    /// In case the (DER encoded) certificate of the upstream server is given,
+    /// the subject alternative names (SAN) of the issued certificate mirror
+    /// the ones of that upstream certificate, as to make the issued certificate
+    /// valid for the same names as the certificate it replaces.
+    pub fn issue(
+        &self,
+        server_name: &Domain,
+        upstream_cert_der: Option<&[u8]>,
+    ) -> Result<Arc<IssuedCert>, OpaqueError> {
+        let key = CacheKey {
+            server_name: server_name.clone(),
+            upstream_cert_hash: upstream_cert_der.map(|der| Sha256::digest(der).into()),
+        };
+
+        let now = SystemTime::now();
+        if let Some(cert) = self.cache.lock().get(&key) {
+            if now < cert.renew_after {
+                return Ok(cert.clone());
+            }
+        }
+
+        let cert = Arc::new(self.issue_cert(server_name, upstream_cert_der, now)?);
+        self.cache.lock().put(key, cert.clone());
+        Ok(cert)
+    }
+
+    fn issue_cert(
+        &self,
+        server_name: &Domain,
+        upstream_cert_der: Option<&[u8]>,
+        now: SystemTime,
+    ) -> Result<IssuedCert, OpaqueError> {
+        let server_name = server_name.as_str().trim_end_matches('.');
+
+        let mut params = CertificateParams::default();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, server_name);
+        params.subject_alt_names = upstream_cert_der
+            .and_then(|der| match upstream_subject_alt_names(der) {
+                Ok(names) => Some(names),
+                Err(err) => {
+                    tracing::debug!(
+                        err = %err,
+                        "failed to mirror upstream certificate SAN, fallback to server name only",
+                    );
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let server_name_san = SanType::DnsName(
+            server_name
+                .to_owned()
+                .try_into()
+                .context("create server name SAN")?,
+        );
+        if !params.subject_alt_names.contains(&server_name_san) {
+            params.subject_alt_names.push(server_name_san);
+        }
+
+        params.is_ca = IsCa::NoCa;
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.use_authority_key_identifier_extension = true;
+
+        let not_after = now + self.validity;
+        params.not_before = (now - NOT_BEFORE_OFFSET).into();
+        params.not_after = not_after.into();
+
+        let key_pair = KeyPair::generate_for(self.key_type.signature_algorithm())
+            .context("generate key pair")?;
+        let cert = params
+            .signed_by(&key_pair, &self.ca.cert, &self.ca.key_pair)
+            .context("sign issued certificate")?;
+
+        Ok(IssuedCert {
+            cert_chain: vec![cert.der().to_vec(), self.ca.cert_der.clone()],
+            private_key: key_pair.serialize_der(),
+            not_after,
+            renew_after: now + self.validity / 2,
+            #[cfg(feature = "rustls")]
+            rustls_config: Mutex::new(None),
+        })
+    }
+}
+
+fn upstream_subject_alt_names(der: &[u8]) -> Result<Vec<SanType>, OpaqueError> {
+    let params =
+        CertificateParams::from_ca_cert_der(&der.into()).context("parse upstream certificate")?;
+    Ok(params.subject_alt_names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ca_issuer() -> CertIssuer {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::default();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Rama Test CA");
+        params.is_ca = IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        let cert = params.self_signed(&key_pair).unwrap();
+        CertIssuer::from_pem(&cert.pem(), &key_pair.serialize_pem()).unwrap()
+    }
+
+    fn subject_alt_names(cert: &IssuedCert) -> Vec<SanType> {
+        upstream_subject_alt_names(&cert.cert_chain_der()[0]).unwrap()
+    }
+
+    fn dns_name(name: &str) -> SanType {
+        SanType::DnsName(name.to_owned().try_into().unwrap())
+    }
+
+    #[test]
+    fn test_issue_cert() {
+        let issuer = ca_issuer();
+        let cert = issuer
+            .issue(&Domain::from_static("example.com"), None)
+            .unwrap();
+
+        assert_eq!(cert.cert_chain_der().len(), 2);
+        assert_eq!(cert.cert_chain_der()[1], issuer.ca.cert_der);
+        assert_eq!(subject_alt_names(&cert), vec![dns_name("example.com")]);
+        assert!(cert.not_after() > SystemTime::now() + DEFAULT_CERT_VALIDITY / 2);
+        assert!(KeyPair::try_from(cert.private_key_der()).is_ok());
+    }
+
+    #[test]
+    fn test_issue_cert_cached() {
+        let issuer = ca_issuer().with_cache_capacity(NonZeroUsize::new(1).unwrap());
+
+        let a = issuer.issue(&Domain::from_static("a.com"), None).unwrap();
+        let a_cached = issuer
+            .clone()
+            .issue(&Domain::from_static("a.com"), None)
+            .unwrap();
+        assert!(Arc::ptr_eq(&a, &a_cached));
+
+        let b = issuer.issue(&Domain::from_static("b.com"), None).unwrap();
+        assert!(!Arc::ptr_eq(&a, &b));
+
+        // a.com got evicted, given the capacity of 1
+        let a_reissued = issuer.issue(&Domain::from_static("a.com"), None).unwrap();
+        assert!(!Arc::ptr_eq(&a, &a_reissued));
+    }
+
+    #[test]
+    fn test_issue_cert_renewed() {
+        let issuer = ca_issuer().with_validity(Duration::ZERO);
+        let a = issuer.issue(&Domain::from_static("a.com"), None).unwrap();
+        let b = issuer.issue(&Domain::from_static("a.com"), None).unwrap();
+        assert!(!Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn test_issue_cert_mirror_upstream_san() {
+        let issuer = ca_issuer().with_key_type(CertKeyType::EcdsaP384);
+
+        let upstream_key_pair = KeyPair::generate().unwrap();
+        let upstream_cert =
+            CertificateParams::new(vec!["example.com".to_owned(), "*.example.com".to_owned()])
+                .unwrap()
+                .self_signed(&upstream_key_pair)
+                .unwrap();
+
+        let cert = issuer
+            .issue(
+                &Domain::from_static("www.example.com"),
+                Some(upstream_cert.der()),
+            )
+            .unwrap();
+        assert_eq!(
+            subject_alt_names(&cert),
+            vec![
+                dns_name("example.com"),
+                dns_name("*.example.com"),
+                dns_name("www.example.com"),
+            ]
+        );
+
+        // invalid upstream certificates are ignored
+        let cert = issuer
+            .issue(&Domain::from_static("www.example.com"), Some(b"foo"))
+            .unwrap();
+        assert_eq!(subject_alt_names(&cert), vec![dns_name("www.example.com")]);
+    }
+}
@@ -0,0 +1,179 @@
+//! Key logging support for the tls backends of rama.
+//!
+//! Key log files use the [NSS key log format], and can be used by tools
+//! such as Wireshark to decrypt the captured TLS traffic, e.g. when debugging
+//! a MITM proxy.
+//!
+//! All (server and client) handshakes logging to the same file share a single
+//! [`KeyLogWriter`], regardless of the tls backend used, such that concurrent
+//! handshakes never interleave their lines.
+//!
+//! [NSS key log format]: https://developer.mozilla.org/en-US/docs/Mozilla/Projects/NSS/Key_Log_Format
+
+use parking_lot::Mutex;
+use rama_core::error::{ErrorContext, OpaqueError};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+/// The environment variable used by [`KeyLogIntent::Environment`].
+pub const SSLKEYLOGFILE_ENV: &str = "SSLKEYLOGFILE";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The intent of where to write the key log (if anywhere).
+pub enum KeyLogIntent {
+    #[default]
+    /// Do not write a key log.
+    Disabled,
+    /// Write the key log to the file defined by the `SSLKEYLOGFILE`
+    /// environment variable, if set.
+    Environment,
+    /// Write the key log to the given file.
+    File(PathBuf),
+}
+
+impl KeyLogIntent {
+    /// Returns the path of the file to write the key log to, if any.
+    pub fn file_path(&self) -> Option<PathBuf> {
+        match self {
+            Self::Disabled => None,
+            Self::Environment => std::env::var_os(SSLKEYLOGFILE_ENV)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            Self::File(path) => Some(path.clone()),
+        }
+    }
+
+    /// Returns the (shared) [`KeyLogWriter`] for this intent, if any.
+    pub fn writer(&self) -> Result<Option<KeyLogWriter>, OpaqueError> {
+        self.file_path().map(KeyLogWriter::open).transpose()
+    }
+}
+
+#[derive(Clone)]
+/// A writer of key log lines to a file, safe to share across concurrent handshakes.
+///
+/// Writers are cached per path, such that all handshakes logging to the same file,
+/// whether by a server or client, share the same underlying file handle.
+pub struct KeyLogWriter {
+    path: Arc<Path>,
+    file: Arc<Mutex<File>>,
+}
+
+impl fmt::Debug for KeyLogWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyLogWriter")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl KeyLogWriter {
+    /// Returns the (shared) [`KeyLogWriter`] for the file at the given path,
+    /// opening it in append mode (creating it if needed) in case it's not yet open.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, OpaqueError> {
+        static WRITERS: OnceLock<Mutex<HashMap<PathBuf, KeyLogWriter>>> = OnceLock::new();
+
+        let path = path.as_ref();
+        let mut writers = WRITERS.get_or_init(Default::default).lock();
+        if let Some(writer) = writers.get(path) {
+            return Ok(writer.clone());
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!("open key log file: {}", path.display()))?;
+        let writer = Self {
+            path: path.into(),
+            file: Arc::new(Mutex::new(file)),
+        };
+        writers.insert(path.to_owned(), writer.clone());
+        Ok(writer)
+    }
+
+    /// The path of the file this writer writes to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write a single key log line (without trailing newline) to the file.
+    pub fn write_line(&self, line: &str) {
+        let mut buf = String::with_capacity(line.len() + 1);
+        buf.push_str(line);
+        buf.push('\n');
+        if let Err(err) = self.file.lock().write_all(buf.as_bytes()) {
+            tracing::debug!(
+                err = %err,
+                path = %self.path.display(),
+                "key log writer: failed to write line",
+            );
+        }
+    }
+
+    /// Write a key log entry for the given label, client random and secret to the file.
+    pub fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        self.write_line(&format!(
+            "{label} {} {}",
+            hex::encode(client_random),
+            hex::encode(secret)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_log_intent_file_path() {
+        assert_eq!(KeyLogIntent::Disabled.file_path(), None);
+        assert_eq!(
+            KeyLogIntent::File("/tmp/keys.log".into()).file_path(),
+            Some(PathBuf::from("/tmp/keys.log"))
+        );
+        assert!(KeyLogIntent::Disabled.writer().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_key_log_writer_shared_across_threads() {
+        let path =
+            std::env::temp_dir().join(format!("rama-tls-keylog-test-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let handles: Vec<_> = (0..8u8)
+            .map(|i| {
+                let writer = KeyLogIntent::File(path.clone()).writer().unwrap().unwrap();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        writer.log("CLIENT_RANDOM", &[i; 32], &[0xab; 48]);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let writer = KeyLogWriter::open(&path).unwrap();
+        assert_eq!(writer.path(), path);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 800);
+        for line in lines {
+            let parts: Vec<_> = line.split(' ').collect();
+            assert_eq!(parts.len(), 3, "line: {line}");
+            assert_eq!(parts[0], "CLIENT_RANDOM");
+            assert_eq!(parts[1].len(), 64);
+            assert_eq!(parts[2], "ab".repeat(48));
+        }
+    }
+}
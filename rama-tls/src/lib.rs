@@ -49,11 +49,22 @@
 )]
 #![deny(unreachable_pub)]
 #![allow(elided_lifetimes_in_paths, clippy::type_complexity)]
-#![forbid(unsafe_code)]
+// unsafe code is denied rather than forbidden, as the boring backend needs a few
+// BoringSSL FFI calls not (yet) exposed by the `boring` crate: these are
+// allowed one by one, each with a `SAFETY` comment, and never module-wide.
+#![deny(unsafe_code)]
 #![cfg_attr(docsrs, feature(doc_auto_cfg, doc_cfg))]
 #![cfg_attr(test, allow(clippy::float_cmp))]
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
+pub mod cert_store;
+pub mod issuer;
+pub mod keylog;
+pub mod verify;
+
+#[cfg(feature = "acme")]
+pub mod acme;
+
 #[cfg(feature = "rustls")]
 pub mod rustls;
 
@@ -70,8 +81,9 @@ pub mod types {
     //! common tls types
     #[doc(inline)]
     pub use ::rama_net::tls::{
-        client, ApplicationProtocol, CipherSuite, CompressionAlgorithm, ECPointFormat, ExtensionId,
-        HttpsTunnel, ProtocolVersion, SecureTransport, SignatureScheme, SupportedGroup,
+        client, server, ApplicationProtocol, CipherSuite, CompressionAlgorithm, ECPointFormat,
+        ExtensionId, HttpsTunnel, ProtocolVersion, SecureTransport, SignatureScheme,
+        SupportedGroup,
     };
 }
 
@@ -1,9 +1,17 @@
+use crate::keylog::{KeyLogIntent, KeyLogWriter};
 use crate::rustls::dep::pki_types::ServerName;
-use crate::rustls::dep::rustls::ClientConfig;
-use crate::rustls::dep::rustls::RootCertStore;
+use crate::rustls::dep::rustls::{ClientConfig, ClientConnection, HandshakeKind, RootCertStore};
 use crate::rustls::dep::tokio_rustls::{client::TlsStream, TlsConnector};
-use crate::rustls::verify::NoServerCertVerifier;
-use crate::types::HttpsTunnel;
+use crate::rustls::key_log::{client_config_with_key_log, key_log_writer};
+use crate::rustls::verify::{
+    client_config_with_verify_policy, server_verify_error, webpki_root_certs, NoServerCertVerifier,
+};
+use crate::types::{
+    client::{NegotiatedApplicationProtocol, NegotiatedTlsParameters},
+    HttpsTunnel,
+};
+use crate::verify::ServerVerifyPolicy;
+use parking_lot::Mutex;
 use pin_project_lite::pin_project;
 use private::{ConnectorKindAuto, ConnectorKindSecure, ConnectorKindTunnel};
 use rama_core::error::{BoxError, ErrorExt, OpaqueError};
@@ -12,8 +20,11 @@ use rama_http_types::Version;
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
 use rama_net::transport::TryRefIntoTransportContext;
-use std::sync::OnceLock;
-use std::{fmt, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, OnceLock},
+};
 use tokio::io::{AsyncRead, AsyncWrite};
 
 /// A [`Layer`] which wraps the given service with a [`HttpsConnector`].
@@ -22,6 +33,10 @@ use tokio::io::{AsyncRead, AsyncWrite};
 #[derive(Clone)]
 pub struct HttpsConnectorLayer<K = ConnectorKindAuto> {
     config: Option<Arc<ClientConfig>>,
+    verify_policy: Option<ServerVerifyPolicy>,
+    root_certs: Option<Arc<RootCertStore>>,
+    key_log_intent: KeyLogIntent,
+    store_server_info: bool,
     _kind: std::marker::PhantomData<K>,
 }
 
@@ -29,6 +44,10 @@ impl<K> std::fmt::Debug for HttpsConnectorLayer<K> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("HttpsConnectorLayer")
             .field("config", &self.config)
+            .field("verify_policy", &self.verify_policy)
+            .field("root_certs", &self.root_certs)
+            .field("key_log_intent", &self.key_log_intent)
+            .field("store_server_info", &self.store_server_info)
             .finish()
     }
 }
@@ -54,6 +73,84 @@ impl<K> HttpsConnectorLayer<K> {
         self.config = Some(config);
         self
     }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// used to verify the server certificate instead of the verifier of the client config.
+    ///
+    /// A [`ServerVerifyPolicy`] found in the [`Context`] takes precedence,
+    /// except for tunnel connectors as these connect to the proxy instead.
+    pub fn with_verify_policy(mut self, policy: ServerVerifyPolicy) -> Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Maybe set the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// used to verify the server certificate instead of the verifier of the client config.
+    pub fn maybe_with_verify_policy(mut self, policy: Option<ServerVerifyPolicy>) -> Self {
+        self.verify_policy = policy;
+        self
+    }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// used to verify the server certificate instead of the verifier of the client config.
+    pub fn set_verify_policy(&mut self, policy: ServerVerifyPolicy) -> &mut Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Set the root certificates trusted by the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// instead of the Mozilla root certificates.
+    ///
+    /// The root certificates of a client config cannot be read back from it,
+    /// and are thus to be set here as well in order to be trusted when verifying a policy.
+    pub fn with_root_certs(mut self, roots: Arc<RootCertStore>) -> Self {
+        self.root_certs = Some(roots);
+        self
+    }
+
+    /// Set the root certificates trusted by the [`ServerVerifyPolicy`] of this [`HttpsConnectorLayer`],
+    /// instead of the Mozilla root certificates.
+    ///
+    /// The root certificates of a client config cannot be read back from it,
+    /// and are thus to be set here as well in order to be trusted when verifying a policy.
+    pub fn set_root_certs(&mut self, roots: Arc<RootCertStore>) -> &mut Self {
+        self.root_certs = Some(roots);
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnectorLayer`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    pub fn with_key_log_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnectorLayer`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    pub fn set_key_log_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnectorLayer`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn with_store_server_info(mut self, store: bool) -> Self {
+        self.store_server_info = store;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnectorLayer`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn set_store_server_info(&mut self, store: bool) -> &mut Self {
+        self.store_server_info = store;
+        self
+    }
 }
 
 impl HttpsConnectorLayer<ConnectorKindAuto> {
@@ -63,6 +160,10 @@ impl HttpsConnectorLayer<ConnectorKindAuto> {
     pub fn auto() -> Self {
         Self {
             config: None,
+            verify_policy: None,
+            root_certs: None,
+            key_log_intent: KeyLogIntent::Disabled,
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
@@ -74,6 +175,10 @@ impl HttpsConnectorLayer<ConnectorKindSecure> {
     pub fn secure_only() -> Self {
         Self {
             config: None,
+            verify_policy: None,
+            root_certs: None,
+            key_log_intent: KeyLogIntent::Disabled,
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
@@ -85,6 +190,10 @@ impl HttpsConnectorLayer<ConnectorKindTunnel> {
     pub fn tunnel() -> Self {
         Self {
             config: None,
+            verify_policy: None,
+            root_certs: None,
+            key_log_intent: KeyLogIntent::Disabled,
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
@@ -94,7 +203,11 @@ impl<K, S> Layer<S> for HttpsConnectorLayer<K> {
     type Service = HttpsConnector<S, K>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        let connector = HttpsConnector::new(inner);
+        let connector = HttpsConnector::new(inner)
+            .maybe_with_verify_policy(self.verify_policy.clone())
+            .maybe_with_root_certs(self.root_certs.clone())
+            .with_key_log_intent(self.key_log_intent.clone())
+            .with_store_server_info(self.store_server_info);
         match self.config.clone() {
             Some(config) => connector.with_config(config),
             None => connector,
@@ -118,14 +231,38 @@ impl Default for HttpsConnectorLayer<ConnectorKindAuto> {
 pub struct HttpsConnector<S, K = ConnectorKindAuto> {
     inner: S,
     config: Option<Arc<ClientConfig>>,
+    verify_policy: Option<ServerVerifyPolicy>,
+    root_certs: Option<Arc<RootCertStore>>,
+    key_log: Option<Arc<KeyLogWriter>>,
+    configs: OnceLock<Arc<ClientConfigCache>>,
+    store_server_info: bool,
     _kind: std::marker::PhantomData<K>,
 }
 
+#[derive(Debug, Default)]
+/// The [`ClientConfig`]s used by a [`HttpsConnector`] (per http version and [`ServerVerifyPolicy`]),
+/// built on first use, such that these are not rebuilt for each handshake.
+///
+/// The cache itself is only created on first use as well,
+/// such that [`HttpsConnector::new`] can remain a `const fn`.
+struct ClientConfigCache(
+    Mutex<HashMap<(Option<Version>, Option<ServerVerifyPolicy>), Arc<ClientConfig>>>,
+);
+
+impl ClientConfigCache {
+    /// Maximum amount of cached configs, as policies can be defined per request.
+    const MAX_CONFIGS: usize = 64;
+}
+
 impl<S: fmt::Debug, K> fmt::Debug for HttpsConnector<S, K> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("HttpsConnector")
             .field("inner", &self.inner)
             .field("config", &self.config)
+            .field("verify_policy", &self.verify_policy)
+            .field("root_certs", &self.root_certs)
+            .field("key_log", &self.key_log)
+            .field("store_server_info", &self.store_server_info)
             .finish()
     }
 }
@@ -135,6 +272,11 @@ impl<S: Clone, K> Clone for HttpsConnector<S, K> {
         Self {
             inner: self.inner.clone(),
             config: self.config.clone(),
+            verify_policy: self.verify_policy.clone(),
+            root_certs: self.root_certs.clone(),
+            key_log: self.key_log.clone(),
+            configs: self.configs.clone(),
+            store_server_info: self.store_server_info,
             _kind: std::marker::PhantomData,
         }
     }
@@ -146,25 +288,122 @@ impl<S, K> HttpsConnector<S, K> {
         Self {
             inner,
             config: None,
+            verify_policy: None,
+            root_certs: None,
+            key_log: None,
+            configs: OnceLock::new(),
+            store_server_info: false,
             _kind: std::marker::PhantomData,
         }
     }
 
     /// Attach a client config to this [`HttpsConnector`],
     pub fn with_config(mut self, config: Arc<ClientConfig>) -> Self {
-        self.config = Some(config);
+        self.set_config(config);
         self
     }
 
     /// Maybe attach a client config to this [`HttpsConnector`],
     pub fn maybe_with_config(mut self, config: Option<Arc<ClientConfig>>) -> Self {
         self.config = config;
+        self.configs = OnceLock::new();
         self
     }
 
     /// Set a client config to this [`HttpsConnector`],
     pub fn set_config(&mut self, config: Arc<ClientConfig>) -> &mut Self {
         self.config = Some(config);
+        self.configs = OnceLock::new();
+        self
+    }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// used to verify the server certificate instead of the verifier of the client config.
+    ///
+    /// A [`ServerVerifyPolicy`] found in the [`Context`] takes precedence,
+    /// except for tunnel connectors as these connect to the proxy instead.
+    pub fn with_verify_policy(mut self, policy: ServerVerifyPolicy) -> Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Maybe set the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// used to verify the server certificate instead of the verifier of the client config.
+    pub fn maybe_with_verify_policy(mut self, policy: Option<ServerVerifyPolicy>) -> Self {
+        self.verify_policy = policy;
+        self
+    }
+
+    /// Set the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// used to verify the server certificate instead of the verifier of the client config.
+    pub fn set_verify_policy(&mut self, policy: ServerVerifyPolicy) -> &mut Self {
+        self.verify_policy = Some(policy);
+        self
+    }
+
+    /// Set the root certificates trusted by the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// instead of the Mozilla root certificates.
+    ///
+    /// The root certificates of a client config cannot be read back from it,
+    /// and are thus to be set here as well in order to be trusted when verifying a policy.
+    pub fn with_root_certs(mut self, roots: Arc<RootCertStore>) -> Self {
+        self.set_root_certs(roots);
+        self
+    }
+
+    /// Maybe set the root certificates trusted by the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// instead of the Mozilla root certificates.
+    pub fn maybe_with_root_certs(mut self, roots: Option<Arc<RootCertStore>>) -> Self {
+        self.root_certs = roots;
+        self.configs = OnceLock::new();
+        self
+    }
+
+    /// Set the root certificates trusted by the [`ServerVerifyPolicy`] of this [`HttpsConnector`],
+    /// instead of the Mozilla root certificates.
+    ///
+    /// The root certificates of a client config cannot be read back from it,
+    /// and are thus to be set here as well in order to be trusted when verifying a policy.
+    pub fn set_root_certs(&mut self, roots: Arc<RootCertStore>) -> &mut Self {
+        self.root_certs = Some(roots);
+        self.configs = OnceLock::new();
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnector`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    pub fn with_key_log_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.set_key_log_intent(intent);
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`HttpsConnector`],
+    /// used to write the key log of its handshakes, e.g. to decrypt the traffic using Wireshark.
+    ///
+    /// The key log file is opened once, when setting the intent.
+    pub fn set_key_log_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.key_log = key_log_writer(&intent);
+        self.configs = OnceLock::new();
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnector`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn with_store_server_info(mut self, store: bool) -> Self {
+        self.store_server_info = store;
+        self
+    }
+
+    /// Define whether or not this [`HttpsConnector`] stores the [`NegotiatedTlsParameters`]
+    /// of the established tls connections in the [`Context`],
+    /// including the certificate chain presented by the server.
+    ///
+    /// Disabled by default.
+    pub fn set_store_server_info(&mut self, store: bool) -> &mut Self {
+        self.store_server_info = store;
         self
     }
 }
@@ -219,6 +458,8 @@ where
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        let verify_policy = ctx.get::<ServerVerifyPolicy>().cloned();
+
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
             .map_err(|err| {
@@ -259,7 +500,12 @@ where
         );
 
         let stream = self
-            .handshake(domain, transport_ctx.http_version, conn)
+            .handshake(
+                verify_policy.as_ref(),
+                domain,
+                transport_ctx.http_version,
+                conn,
+            )
             .await?;
 
         tracing::trace!(
@@ -268,6 +514,8 @@ where
             http_version = ?transport_ctx.http_version,
             "HttpsConnector(auto): protocol secure, established tls connection",
         );
+
+        self.store_server_info(&mut ctx, &stream)?;
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -302,6 +550,8 @@ where
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        let verify_policy = ctx.get::<ServerVerifyPolicy>().cloned();
+
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
             .map_err(|err| {
@@ -321,9 +571,15 @@ where
             .to_owned();
 
         let conn = self
-            .handshake(domain, transport_ctx.http_version, conn)
+            .handshake(
+                verify_policy.as_ref(),
+                domain,
+                transport_ctx.http_version,
+                conn,
+            )
             .await?;
 
+        self.store_server_info(&mut ctx, &conn)?;
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -348,7 +604,7 @@ where
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
         let EstablishedClientConnection {
-            ctx,
+            mut ctx,
             req,
             conn,
             addr,
@@ -373,9 +629,11 @@ where
             }
         };
 
-        let conn = self.handshake(domain, None, conn).await?;
+        // the verify policy of the context is meant for the target server, not the proxy
+        let conn = self.handshake(None, domain, None, conn).await?;
 
         tracing::trace!("HttpsConnector(tunnel): connection secured");
+        self.store_server_info(&mut ctx, &conn)?;
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -390,6 +648,7 @@ where
 impl<S, K> HttpsConnector<S, K> {
     async fn handshake<T>(
         &self,
+        verify_policy: Option<&ServerVerifyPolicy>,
         server_name: ServerName<'static>,
         http_version: Option<Version>,
         stream: T,
@@ -397,19 +656,92 @@ impl<S, K> HttpsConnector<S, K> {
     where
         T: Stream + Unpin,
     {
+        let config =
+            self.client_config(http_version, verify_policy.or(self.verify_policy.as_ref()))?;
+        let connector = TlsConnector::from(config);
+
+        connector.connect(server_name, stream).await.map_err(|err| {
+            match server_verify_error(&err) {
+                Some(err) => err.into_handshake_error(),
+                None => err.into(),
+            }
+        })
+    }
+
+    /// Returns the [`ClientConfig`] to use for the given http version and [`ServerVerifyPolicy`],
+    /// building it in case it is not yet cached.
+    fn client_config(
+        &self,
+        http_version: Option<Version>,
+        verify_policy: Option<&ServerVerifyPolicy>,
+    ) -> Result<Arc<ClientConfig>, OpaqueError> {
+        // the configured client config is used regardless of the http version
+        let http_version = match (&self.config, http_version) {
+            (None, Some(Version::HTTP_11 | Version::HTTP_2 | Version::HTTP_3)) => http_version,
+            _ => None,
+        };
+        let key = (http_version, verify_policy.cloned());
+
+        let mut configs = self.configs.get_or_init(Default::default).0.lock();
+        if let Some(config) = configs.get(&key) {
+            return Ok(config.clone());
+        }
+
         let config = self
             .config
             .clone()
             .unwrap_or_else(|| new_tls_client_config(http_version));
-        let connector = TlsConnector::from(config);
+        let config = match verify_policy {
+            Some(policy) => {
+                client_config_with_verify_policy(config, policy, self.root_certs.clone())?
+            }
+            None => config,
+        };
+        let config = client_config_with_key_log(config, self.key_log.as_ref());
 
-        connector
-            .connect(server_name, stream)
-            .await
-            .map_err(Into::into)
+        if configs.len() >= ClientConfigCache::MAX_CONFIGS {
+            configs.clear();
+        }
+        configs.insert(key, config.clone());
+        Ok(config)
+    }
+
+    fn store_server_info<State, T>(
+        &self,
+        ctx: &mut Context<State>,
+        stream: &TlsStream<T>,
+    ) -> Result<(), OpaqueError> {
+        if let Some(protocol) = stream.get_ref().1.alpn_protocol() {
+            ctx.insert(NegotiatedApplicationProtocol(protocol.into()));
+        }
+        if self.store_server_info {
+            ctx.insert(negotiated_tls_parameters(stream.get_ref().1)?);
+        }
+        Ok(())
     }
 }
 
+fn negotiated_tls_parameters(
+    conn: &ClientConnection,
+) -> Result<NegotiatedTlsParameters, OpaqueError> {
+    let protocol_version = conn.protocol_version().ok_or_else(|| {
+        OpaqueError::from_display("HttpsConnector: missing negotiated protocol version")
+    })?;
+    let cipher_suite = conn.negotiated_cipher_suite().ok_or_else(|| {
+        OpaqueError::from_display("HttpsConnector: missing negotiated cipher suite")
+    })?;
+    Ok(NegotiatedTlsParameters {
+        protocol_version: protocol_version.into(),
+        cipher_suite: cipher_suite.suite().into(),
+        application_layer_protocol: conn.alpn_protocol().map(Into::into),
+        server_certificate_chain: conn
+            .peer_certificates()
+            .map(|chain| chain.iter().map(|cert| cert.to_vec()).collect())
+            .unwrap_or_default(),
+        session_resumed: conn.handshake_kind() == Some(HandshakeKind::Resumed),
+    })
+}
+
 pin_project! {
     /// A stream which can be either a secure or a plain stream.
     pub struct AutoTlsStream<S> {
@@ -499,14 +831,7 @@ where
 }
 
 fn new_tls_client_config(http_version: Option<Version>) -> Arc<ClientConfig> {
-    static ROOT_CERTS: OnceLock<Arc<RootCertStore>> = OnceLock::new();
-    let root_certs = ROOT_CERTS
-        .get_or_init(|| {
-            let mut root_storage = RootCertStore::empty();
-            root_storage.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-            Arc::new(root_storage)
-        })
-        .clone();
+    let root_certs = webpki_root_certs();
 
     let mut config = ClientConfig::builder()
         .with_root_certificates(root_certs)
@@ -567,4 +892,124 @@ mod tests {
 
         assert_sync::<HttpsConnectorLayer>();
     }
+
+    #[test]
+    fn test_https_connector_client_config_cached() {
+        let path = std::env::temp_dir().join(format!(
+            "rama-tls-rustls-keylog-test-{}.log",
+            std::process::id()
+        ));
+        let connector =
+            HttpsConnector::auto(()).with_key_log_intent(KeyLogIntent::File(path.clone()));
+
+        let config = connector
+            .client_config(Some(Version::HTTP_2), None)
+            .unwrap();
+        assert!(Arc::ptr_eq(
+            &config,
+            &connector
+                .clone()
+                .client_config(Some(Version::HTTP_2), None)
+                .unwrap()
+        ));
+        assert_eq!(config.alpn_protocols, vec![b"h2".to_vec()]);
+        assert!(config.key_log.will_log("CLIENT_RANDOM"));
+        assert!(!Arc::ptr_eq(
+            &config,
+            &connector.client_config(None, None).unwrap()
+        ));
+
+        let policy = ServerVerifyPolicy::verified();
+        let config = connector
+            .client_config(Some(Version::HTTP_2), Some(&policy))
+            .unwrap();
+        assert!(Arc::ptr_eq(
+            &config,
+            &connector
+                .client_config(Some(Version::HTTP_2), Some(&policy))
+                .unwrap()
+        ));
+        assert!(config.key_log.will_log("CLIENT_RANDOM"));
+
+        let connector = connector.with_key_log_intent(KeyLogIntent::Disabled);
+        assert!(!connector
+            .client_config(Some(Version::HTTP_2), None)
+            .unwrap()
+            .key_log
+            .will_log("CLIENT_RANDOM"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_https_connector_store_server_info() {
+        use crate::dep::rcgen;
+        use crate::rustls::dep::{
+            pki_types::{CertificateDer, PrivatePkcs8KeyDer},
+            rustls::ServerConfig,
+            tokio_rustls::TlsAcceptor,
+        };
+        use crate::types::{ApplicationProtocol, ProtocolVersion};
+        use rama_core::service::service_fn;
+        use rama_http_types::{Body, Request};
+        use std::convert::Infallible;
+
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        let mut server_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+            )
+            .unwrap();
+        server_config.alpn_protocols = vec![b"h2".to_vec()];
+
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+        tokio::spawn(async move {
+            let acceptor = TlsAcceptor::from(Arc::new(server_config));
+            let _stream = acceptor.accept(server_stream).await.unwrap();
+        });
+
+        let client_stream = std::sync::Mutex::new(Some(client_stream));
+        let connector =
+            HttpsConnector::secure_only(service_fn(move |ctx: Context<()>, req: Request| {
+                let conn = client_stream.lock().unwrap().take().unwrap();
+                async move {
+                    Ok::<_, Infallible>(EstablishedClientConnection {
+                        ctx,
+                        req,
+                        conn,
+                        addr: ([127, 0, 0, 1], 443).into(),
+                    })
+                }
+            }))
+            .with_store_server_info(true);
+
+        let req = Request::builder()
+            .uri("https://example.com")
+            .version(Version::HTTP_2)
+            .body(Body::empty())
+            .unwrap();
+        let EstablishedClientConnection { ctx, .. } =
+            connector.connect(Context::default(), req).await.unwrap();
+
+        let params = ctx.get::<NegotiatedTlsParameters>().unwrap();
+        assert_eq!(params.protocol_version, ProtocolVersion::TLSv1_3);
+        assert_eq!(
+            params.application_layer_protocol,
+            Some(ApplicationProtocol::HTTP_2)
+        );
+        assert_eq!(
+            params.server_certificate_chain,
+            vec![CertificateDer::as_ref(cert.der()).to_vec()]
+        );
+        assert!(!params.session_resumed);
+
+        let chain = params.parse_server_certificate_chain().unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].der(), cert.der().as_ref());
+    }
 }
@@ -0,0 +1,54 @@
+use crate::keylog::{KeyLogIntent, KeyLogWriter};
+use crate::rustls::dep::rustls::{ClientConfig, KeyLog, ServerConfig};
+use std::sync::Arc;
+
+impl KeyLog for KeyLogWriter {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        KeyLogWriter::log(self, label, client_random, secret)
+    }
+}
+
+/// Returns the [`KeyLogWriter`] for the given intent, if any,
+/// logging (instead of failing) in case it cannot be opened.
+///
+/// Meant to be resolved once, when building a connector or acceptor,
+/// instead of for each handshake.
+pub(super) fn key_log_writer(intent: &KeyLogIntent) -> Option<Arc<KeyLogWriter>> {
+    match intent.writer() {
+        Ok(writer) => writer.map(Arc::new),
+        Err(err) => {
+            tracing::warn!(err = %err, "rustls: failed to open key log file, key logging disabled");
+            None
+        }
+    }
+}
+
+/// Returns the given [`ClientConfig`], with its key log set to the given [`KeyLogWriter`], if any.
+pub(super) fn client_config_with_key_log(
+    config: Arc<ClientConfig>,
+    writer: Option<&Arc<KeyLogWriter>>,
+) -> Arc<ClientConfig> {
+    match writer {
+        Some(writer) => {
+            let mut config = (*config).clone();
+            config.key_log = writer.clone();
+            Arc::new(config)
+        }
+        None => config,
+    }
+}
+
+/// Returns the given [`ServerConfig`], with its key log set to the given [`KeyLogWriter`], if any.
+pub(super) fn server_config_with_key_log(
+    config: Arc<ServerConfig>,
+    writer: Option<&Arc<KeyLogWriter>>,
+) -> Arc<ServerConfig> {
+    match writer {
+        Some(writer) => {
+            let mut config = (*config).clone();
+            config.key_log = writer.clone();
+            Arc::new(config)
+        }
+        None => config,
+    }
+}
@@ -0,0 +1,74 @@
+use super::{issuer::server_config_with_cert, ServerConfigProvider};
+use crate::{
+    acme::TlsAlpn01ChallengeResponder,
+    rustls::dep::{
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        rustls::ServerConfig,
+    },
+    types::{client::ClientHello, ApplicationProtocol},
+};
+use std::{io, sync::Arc};
+
+#[derive(Debug, Clone)]
+/// A [`ServerConfigProvider`] which responds to ACME TLS-ALPN-01 challenges,
+/// prepared by the given [`TlsAlpn01ChallengeResponder`].
+///
+/// For a [`ClientHello`] offering the `acme-tls/1` ALPN protocol for a server name
+/// with a prepared challenge, a copy of the given [`ServerConfig`] is returned,
+/// presenting the challenge certificate and only negotiating `acme-tls/1`.
+/// All other handshakes are delegated to the inner [`ServerConfigProvider`],
+/// e.g. a [`CertStoreProvider`] serving the certificates obtained over ACME.
+///
+/// [`CertStoreProvider`]: super::CertStoreProvider
+pub struct TlsAlpn01Provider<P> {
+    responder: TlsAlpn01ChallengeResponder,
+    config: Arc<ServerConfig>,
+    inner: P,
+}
+
+impl<P> TlsAlpn01Provider<P> {
+    /// Create a new [`TlsAlpn01Provider`], using the given [`ServerConfig`]
+    /// as the base config for the challenge certificates,
+    /// and delegating all other handshakes to the given [`ServerConfigProvider`].
+    pub const fn new(
+        responder: TlsAlpn01ChallengeResponder,
+        config: Arc<ServerConfig>,
+        inner: P,
+    ) -> Self {
+        Self {
+            responder,
+            config,
+            inner,
+        }
+    }
+
+    fn challenge_server_config(
+        &self,
+        client_hello: &ClientHello,
+    ) -> Option<io::Result<Arc<ServerConfig>>> {
+        if !client_hello
+            .ext_alpn()?
+            .contains(&ApplicationProtocol::ACME_TLS)
+        {
+            return None;
+        }
+        let cert = self.responder.get(client_hello.ext_server_name()?)?;
+
+        let mut config = ServerConfig::clone(&self.config);
+        config.alpn_protocols = vec![ApplicationProtocol::ACME_TLS.as_bytes().to_vec()];
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.private_key_der().to_vec()));
+        Some(server_config_with_cert(&config, cert.cert_chain_der(), key))
+    }
+}
+
+impl<P: ServerConfigProvider> ServerConfigProvider for TlsAlpn01Provider<P> {
+    async fn get_server_config(
+        &self,
+        client_hello: ClientHello,
+    ) -> Result<Option<Arc<ServerConfig>>, io::Error> {
+        match self.challenge_server_config(&client_hello) {
+            Some(result) => result.map(Some),
+            None => self.inner.get_server_config(client_hello).await,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::{issuer::server_config_with_cert, ServerConfigProvider};
+use crate::{
+    cert_store::{CertStore, LiveCertStore, PrivateKeyFormat},
+    rustls::dep::{
+        pki_types::{PrivateKeyDer, PrivatePkcs1KeyDer, PrivatePkcs8KeyDer, PrivateSec1KeyDer},
+        rustls::ServerConfig,
+    },
+    types::client::ClientHello,
+};
+use parking_lot::Mutex;
+use std::{collections::HashMap, fmt, future::Future, io, sync::Arc};
+
+#[derive(Clone)]
+/// A [`ServerConfigProvider`] which uses the current certificates of a [`LiveCertStore`]
+/// for each new handshake, such that these can be replaced without restarting the server.
+///
+/// The returned [`ServerConfig`] is a copy of the given [`ServerConfig`],
+/// with only the certificate replaced by the one found in the [`LiveCertStore`]
+/// for the server name (SNI) of the [`ClientHello`]. In case no certificate is found,
+/// not even a default one, the default [`ServerConfig`] of the acceptor is used.
+///
+/// These copies are created once per certificate of the current [`CertStore`],
+/// and shared between all clones of the [`CertStoreProvider`].
+pub struct CertStoreProvider {
+    store: LiveCertStore,
+    config: Arc<ServerConfig>,
+    cache: Arc<Mutex<Option<ServerConfigCache>>>,
+}
+
+/// The [`ServerConfig`]s created for the certificates of a [`CertStore`],
+/// keyed by the address of the (shared) certificate, which remains valid
+/// for as long as the [`CertStore`] is kept alive by this cache.
+struct ServerConfigCache {
+    store: Arc<CertStore>,
+    configs: HashMap<usize, Arc<ServerConfig>>,
+}
+
+impl fmt::Debug for CertStoreProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertStoreProvider")
+            .field("store", &self.store)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl CertStoreProvider {
+    /// Create a new [`CertStoreProvider`], using the given [`ServerConfig`]
+    /// as the base config for the certificates of the [`LiveCertStore`].
+    pub fn new(store: LiveCertStore, config: Arc<ServerConfig>) -> Self {
+        Self {
+            store,
+            config,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn server_config(&self, client_hello: &ClientHello) -> io::Result<Option<Arc<ServerConfig>>> {
+        let store = self.store.current();
+        let Some(cert) = store.get(client_hello.ext_server_name()) else {
+            return Ok(None);
+        };
+        let cert_key = Arc::as_ptr(cert) as usize;
+
+        let mut cache = self.cache.lock();
+        let cache = match cache.as_mut() {
+            Some(cache) if Arc::ptr_eq(&cache.store, &store) => cache,
+            _ => cache.insert(ServerConfigCache {
+                store: store.clone(),
+                configs: HashMap::new(),
+            }),
+        };
+        if let Some(config) = cache.configs.get(&cert_key) {
+            return Ok(Some(config.clone()));
+        }
+
+        let key_der = cert.private_key_der().to_vec();
+        let key = match cert.private_key_format() {
+            PrivateKeyFormat::Pkcs8 => PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_der)),
+            PrivateKeyFormat::Pkcs1 => PrivateKeyDer::Pkcs1(PrivatePkcs1KeyDer::from(key_der)),
+            PrivateKeyFormat::Sec1 => PrivateKeyDer::Sec1(PrivateSec1KeyDer::from(key_der)),
+        };
+        let config = server_config_with_cert(&self.config, cert.cert_chain_der(), key)?;
+        cache.configs.insert(cert_key, config.clone());
+        Ok(Some(config))
+    }
+}
+
+impl ServerConfigProvider for CertStoreProvider {
+    fn get_server_config(
+        &self,
+        client_hello: ClientHello,
+    ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, io::Error>> + Send + '_ {
+        std::future::ready(self.server_config(&client_hello))
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{rustls::dep::rustls::ServerConfig, types::client::ClientHello};
+use crate::{issuer::UpstreamCert, rustls::dep::rustls::ServerConfig, types::client::ClientHello};
 use std::{fmt, future::Future, sync::Arc};
 
 /// A handler that allows you to define what to do with the client config,
@@ -55,6 +55,21 @@ pub trait ServerConfigProvider: Send + Sync + 'static {
         &self,
         client_hello: ClientHello,
     ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, std::io::Error>> + Send + '_;
+
+    /// Same as [`Self::get_server_config`], but also given the [`UpstreamCert`]
+    /// found in the [`Context`] of the incoming connection, if any.
+    ///
+    /// Defaults to [`Self::get_server_config`], ignoring the [`UpstreamCert`].
+    ///
+    /// [`Context`]: rama_core::Context
+    fn get_server_config_with_upstream_cert(
+        &self,
+        client_hello: ClientHello,
+        upstream_cert: Option<UpstreamCert>,
+    ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, std::io::Error>> + Send + '_ {
+        let _ = upstream_cert;
+        self.get_server_config(client_hello)
+    }
 }
 
 impl<F, Fut> ServerConfigProvider for F
@@ -0,0 +1,208 @@
+use super::ServerConfigProvider;
+use crate::{
+    issuer::{CertIssuer, UpstreamCert},
+    rustls::dep::{
+        pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+        rustls::{
+            server::{ClientHello as RustlsClientHello, ResolvesServerCert},
+            sign::CertifiedKey,
+            ServerConfig,
+        },
+    },
+    types::client::ClientHello,
+};
+use std::{future::Future, io, sync::Arc};
+
+#[derive(Debug, Clone)]
+/// A [`ServerConfigProvider`] which uses a [`CertIssuer`] to issue
+/// a certificate for the server name (SNI) of the [`ClientHello`].
+///
+/// The returned [`ServerConfig`] is a copy of the given [`ServerConfig`],
+/// with only the certificate replaced by the issued one. It is cached together
+/// with the issued certificate, such that it is not rebuilt for each handshake.
+/// In case the [`ClientHello`] contains no server name,
+/// the default [`ServerConfig`] of the acceptor is used.
+///
+/// The subject alternative names of the [`UpstreamCert`] found in the [`Context`]
+/// of the incoming connection, if any, are mirrored in the issued certificate.
+///
+/// [`Context`]: rama_core::Context
+pub struct CertIssuerProvider {
+    issuer: CertIssuer,
+    config: Arc<ServerConfig>,
+}
+
+impl CertIssuerProvider {
+    /// Create a new [`CertIssuerProvider`], using the given [`ServerConfig`]
+    /// as the base config for the issued certificates.
+    pub const fn new(issuer: CertIssuer, config: Arc<ServerConfig>) -> Self {
+        Self { issuer, config }
+    }
+
+    fn server_config(
+        &self,
+        client_hello: &ClientHello,
+        upstream_cert: Option<&UpstreamCert>,
+    ) -> io::Result<Option<Arc<ServerConfig>>> {
+        let Some(server_name) = client_hello.ext_server_name() else {
+            return Ok(None);
+        };
+
+        let issued = self
+            .issuer
+            .issue(server_name, upstream_cert.map(AsRef::as_ref))
+            .map_err(io::Error::other)?;
+        issued
+            .rustls_server_config(&self.config, |issued| {
+                let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+                    issued.private_key_der().to_vec(),
+                ));
+                server_config_with_cert(&self.config, issued.cert_chain_der(), key)
+            })
+            .map(Some)
+    }
+}
+
+impl ServerConfigProvider for CertIssuerProvider {
+    fn get_server_config(
+        &self,
+        client_hello: ClientHello,
+    ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, io::Error>> + Send + '_ {
+        std::future::ready(self.server_config(&client_hello, None))
+    }
+
+    fn get_server_config_with_upstream_cert(
+        &self,
+        client_hello: ClientHello,
+        upstream_cert: Option<UpstreamCert>,
+    ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, io::Error>> + Send + '_ {
+        std::future::ready(self.server_config(&client_hello, upstream_cert.as_ref()))
+    }
+}
+
+/// Returns a copy of the given [`ServerConfig`],
+/// using the given certificate (chain) and private key for all handshakes.
+pub(super) fn server_config_with_cert(
+    config: &ServerConfig,
+    cert_chain_der: &[Vec<u8>],
+    key: PrivateKeyDer<'static>,
+) -> io::Result<Arc<ServerConfig>> {
+    let key = config
+        .crypto_provider()
+        .key_provider
+        .load_private_key(key)
+        .map_err(io::Error::other)?;
+    let cert_chain = cert_chain_der
+        .iter()
+        .map(|der| CertificateDer::from(der.clone()))
+        .collect();
+
+    let mut config = config.clone();
+    config.cert_resolver = Arc::new(SingleCertResolver(Arc::new(CertifiedKey::new(
+        cert_chain, key,
+    ))));
+    Ok(Arc::new(config))
+}
+
+#[derive(Debug)]
+struct SingleCertResolver(Arc<CertifiedKey>);
+
+impl ResolvesServerCert for SingleCertResolver {
+    fn resolve(&self, _client_hello: RustlsClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dep::rcgen::{self, KeyPair},
+        types::client::parse_client_hello,
+    };
+
+    fn provider() -> CertIssuerProvider {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::default();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&key_pair).unwrap();
+        let issuer = CertIssuer::new(ca.der().to_vec(), key_pair).unwrap();
+
+        let key_pair = KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["localhost".to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+            )
+            .unwrap();
+        CertIssuerProvider::new(issuer, Arc::new(config))
+    }
+
+    /// Create a TLS 1.2 [`ClientHello`] with only a server name extension, if given.
+    fn client_hello(server_name: Option<&str>) -> ClientHello {
+        let mut extensions = Vec::new();
+        if let Some(name) = server_name {
+            let name = name.as_bytes();
+            let list_len = name.len() as u16 + 3;
+            extensions.extend_from_slice(&[0x00, 0x00]); // server name
+            extensions.extend_from_slice(&(list_len + 2).to_be_bytes());
+            extensions.extend_from_slice(&list_len.to_be_bytes());
+            extensions.push(0x00); // host name
+            extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            extensions.extend_from_slice(name);
+        }
+
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0x42; 32]);
+        body.extend_from_slice(&[
+            0x00, // session id length
+            0x00, 0x02, 0x13, 0x01, // cipher suites
+            0x01, 0x00, // compression algorithms
+        ]);
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend(extensions);
+        parse_client_hello(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cert_issuer_provider_config_cached() {
+        let provider = provider();
+
+        let config = provider
+            .get_server_config(client_hello(Some("example.com")))
+            .await
+            .unwrap()
+            .unwrap();
+        let cached = provider
+            .get_server_config(client_hello(Some("example.com")))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&config, &cached));
+
+        let upstream_cert = rcgen::CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .self_signed(&KeyPair::generate().unwrap())
+            .unwrap();
+        let upstream_config = provider
+            .get_server_config_with_upstream_cert(
+                client_hello(Some("example.com")),
+                Some(UpstreamCert(upstream_cert.der().to_vec())),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&config, &upstream_config));
+
+        assert!(provider
+            .get_server_config(client_hello(None))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use super::{TlsAcceptorService, TlsClientConfigHandler};
+use crate::keylog::KeyLogIntent;
 use crate::rustls::dep::rustls::ServerConfig;
 use rama_core::Layer;
 use std::sync::Arc;
@@ -8,6 +9,7 @@ use std::sync::Arc;
 pub struct TlsAcceptorLayer<H> {
     config: Arc<ServerConfig>,
     client_config_handler: H,
+    key_log_intent: KeyLogIntent,
 }
 
 impl<H> std::fmt::Debug for TlsAcceptorLayer<H> {
@@ -25,6 +27,7 @@ impl TlsAcceptorLayer<()> {
         Self {
             config,
             client_config_handler: (),
+            key_log_intent: KeyLogIntent::Disabled,
         }
     }
 }
@@ -40,10 +43,27 @@ impl<F> TlsAcceptorLayer<TlsClientConfigHandler<F>> {
         Self {
             config,
             client_config_handler,
+            key_log_intent: KeyLogIntent::Disabled,
         }
     }
 }
 
+impl<H> TlsAcceptorLayer<H> {
+    /// Set the [`KeyLogIntent`] of this [`TlsAcceptorLayer`],
+    /// used to write the key log of the accepted handshakes, e.g. to decrypt the traffic using Wireshark.
+    pub fn with_key_log_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.key_log_intent = intent;
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`TlsAcceptorLayer`],
+    /// used to write the key log of the accepted handshakes, e.g. to decrypt the traffic using Wireshark.
+    pub fn set_key_log_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.key_log_intent = intent;
+        self
+    }
+}
+
 impl<H: Clone, S> Layer<S> for TlsAcceptorLayer<H> {
     type Service = TlsAcceptorService<S, H>;
 
@@ -53,6 +73,7 @@ impl<H: Clone, S> Layer<S> for TlsAcceptorLayer<H> {
             inner,
             self.client_config_handler.clone(),
         )
+        .with_key_log_intent(self.key_log_intent.clone())
     }
 }
 
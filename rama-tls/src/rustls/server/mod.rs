@@ -21,6 +21,20 @@ mod client_config;
 #[doc(inline)]
 pub use client_config::{ServerConfigProvider, TlsClientConfigHandler};
 
+mod issuer;
+#[doc(inline)]
+pub use issuer::CertIssuerProvider;
+
+mod cert_store;
+#[doc(inline)]
+pub use cert_store::CertStoreProvider;
+
+#[cfg(feature = "acme")]
+mod acme;
+#[cfg(feature = "acme")]
+#[doc(inline)]
+pub use acme::TlsAlpn01Provider;
+
 mod layer;
 #[doc(inline)]
 pub use layer::TlsAcceptorLayer;
@@ -1,9 +1,16 @@
 use crate::{
+    issuer::UpstreamCert,
+    keylog::{KeyLogIntent, KeyLogWriter},
     rustls::dep::{
-        rustls::{server::Acceptor, ServerConfig},
+        rustls::{
+            server::{Acceptor, ClientHello as RustlsClientHello},
+            ServerConfig,
+        },
         tokio_rustls::{server::TlsStream, LazyConfigAcceptor, TlsAcceptor},
     },
+    rustls::key_log::{key_log_writer, server_config_with_key_log},
     types::client::ClientHello,
+    types::server::ClientIdentity,
     types::SecureTransport,
 };
 use rama_core::{Context, Service};
@@ -18,6 +25,7 @@ use super::{ServerConfigProvider, TlsClientConfigHandler};
 pub struct TlsAcceptorService<S, H> {
     config: Arc<ServerConfig>,
     client_config_handler: H,
+    key_log: Option<Arc<KeyLogWriter>>,
     inner: S,
 }
 
@@ -27,10 +35,30 @@ impl<S, H> TlsAcceptorService<S, H> {
         Self {
             config,
             client_config_handler,
+            key_log: None,
             inner,
         }
     }
 
+    /// Set the [`KeyLogIntent`] of this [`TlsAcceptorService`],
+    /// used to write the key log of the accepted handshakes, e.g. to decrypt the traffic using Wireshark.
+    pub fn with_key_log_intent(mut self, intent: KeyLogIntent) -> Self {
+        self.set_key_log_intent(intent);
+        self
+    }
+
+    /// Set the [`KeyLogIntent`] of this [`TlsAcceptorService`],
+    /// used to write the key log of the accepted handshakes, e.g. to decrypt the traffic using Wireshark.
+    ///
+    /// The key log file is opened once, and set on the static [`ServerConfig`] right away.
+    /// The configs returned by a [`ServerConfigProvider`] get it set per handshake,
+    /// only in case key logging is enabled.
+    pub fn set_key_log_intent(&mut self, intent: KeyLogIntent) -> &mut Self {
+        self.key_log = key_log_writer(&intent);
+        self.config = server_config_with_key_log(self.config.clone(), self.key_log.as_ref());
+        self
+    }
+
     define_inner_service_accessors!();
 }
 
@@ -39,6 +67,7 @@ impl<S: std::fmt::Debug, H: std::fmt::Debug> std::fmt::Debug for TlsAcceptorServ
         f.debug_struct("TlsAcceptorService")
             .field("config", &self.config)
             .field("client_config_handler", &self.client_config_handler)
+            .field("key_log", &self.key_log)
             .field("inner", &self.inner)
             .finish()
     }
@@ -53,6 +82,7 @@ where
         Self {
             config: self.config.clone(),
             client_config_handler: self.client_config_handler.clone(),
+            key_log: self.key_log.clone(),
             inner: self.inner.clone(),
         }
     }
@@ -76,6 +106,7 @@ where
             .map_err(TlsAcceptorError::Accept)?;
 
         ctx.insert(SecureTransport::default());
+        insert_client_identity(&mut ctx, &stream);
         self.inner
             .serve(ctx, stream)
             .await
@@ -98,7 +129,10 @@ where
         let start = acceptor.await.map_err(TlsAcceptorError::Accept)?;
 
         let secure_transport = if self.client_config_handler.store_client_hello {
-            SecureTransport::with_client_hello(start.client_hello().into())
+            SecureTransport::with_client_hello(accepted_client_hello(
+                &mut ctx,
+                start.client_hello(),
+            ))
         } else {
             SecureTransport::default()
         };
@@ -108,7 +142,12 @@ where
             .await
             .map_err(TlsAcceptorError::Accept)?;
 
+        if let Some(hello) = secure_transport.client_hello() {
+            ctx.insert(hello.ja3());
+            ctx.insert(hello.ja4());
+        }
         ctx.insert(secure_transport);
+        insert_client_identity(&mut ctx, &stream);
         self.inner
             .serve(ctx, stream)
             .await
@@ -131,7 +170,7 @@ where
 
         let start = acceptor.await.map_err(TlsAcceptorError::Accept)?;
 
-        let accepted_client_hello = ClientHello::from(start.client_hello());
+        let accepted_client_hello = accepted_client_hello(&mut ctx, start.client_hello());
 
         let secure_transport = if self.client_config_handler.store_client_hello {
             SecureTransport::with_client_hello(accepted_client_hello.clone())
@@ -142,9 +181,13 @@ where
         let config = self
             .client_config_handler
             .server_config_provider
-            .get_server_config(accepted_client_hello)
+            .get_server_config_with_upstream_cert(
+                accepted_client_hello,
+                ctx.get::<UpstreamCert>().cloned(),
+            )
             .await
             .map_err(TlsAcceptorError::Accept)?
+            .map(|config| server_config_with_key_log(config, self.key_log.as_ref()))
             .unwrap_or_else(|| self.config.clone());
 
         let stream = start
@@ -152,7 +195,12 @@ where
             .await
             .map_err(TlsAcceptorError::Accept)?;
 
+        if let Some(hello) = secure_transport.client_hello() {
+            ctx.insert(hello.ja3());
+            ctx.insert(hello.ja4());
+        }
         ctx.insert(secure_transport);
+        insert_client_identity(&mut ctx, &stream);
         self.inner
             .serve(ctx, stream)
             .await
@@ -160,6 +208,47 @@ where
     }
 }
 
+/// Returns the [`ClientHello`] received for this connection.
+///
+/// The [`ClientHello`] peeked from the raw stream (e.g. by the `ClientHelloPeekLayer`)
+/// is preferred as it preserves all extensions and their order. It is taken out of the
+/// [`Context`], such that it cannot be reused for another (e.g. nested) handshake,
+/// and only used in case it matches the [`ClientHello`] received by rustls,
+/// as a [`ClientHello`] found in the [`Context`] might be stale.
+fn accepted_client_hello<T>(ctx: &mut Context<T>, received: RustlsClientHello<'_>) -> ClientHello {
+    let received = ClientHello::from(received);
+    match ctx.remove::<ClientHello>() {
+        Some(peeked)
+            if peeked.cipher_suites() == received.cipher_suites()
+                && peeked.ext_server_name() == received.ext_server_name()
+                && peeked.ext_alpn() == received.ext_alpn() =>
+        {
+            peeked
+        }
+        Some(_) => {
+            tracing::debug!(
+                "ignore client hello found in context: it does not match the received one"
+            );
+            received
+        }
+        None => received,
+    }
+}
+
+/// Insert the [`ClientIdentity`] of the peer of the given (accepted) stream
+/// in the [`Context`], in case it presented a certificate.
+fn insert_client_identity<T, IO>(ctx: &mut Context<T>, stream: &TlsStream<IO>) {
+    let Some(certs) = stream.get_ref().1.peer_certificates() else {
+        return;
+    };
+    match ClientIdentity::from_der_chain(certs.iter().map(|cert| cert.as_ref())) {
+        Ok(identity) => {
+            ctx.insert(identity);
+        }
+        Err(err) => tracing::warn!(err = %err, "failed to parse rustls peer certificate"),
+    }
+}
+
 /// Errors that can happen when using [`TlsAcceptorService`].
 pub enum TlsAcceptorError<E> {
     /// An error occurred while accepting a TLS connection.
@@ -1,14 +1,26 @@
 //! TLS Verify support for Rustls usage in Rama.
 //!
-//! ... or rather the lack of verification where it is not needed.
+//! ... or rather the lack of verification where it is not needed,
+//! and verification according to a [`ServerVerifyPolicy`] where it is.
 
 use crate::rustls::dep::{
-    pki_types::{CertificateDer, ServerName, UnixTime},
+    pki_types::{CertificateDer, ServerName, TrustAnchor, UnixTime},
     rustls::{
         client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
-        DigitallySignedStruct, SignatureScheme,
+        crypto::{
+            verify_tls12_signature, verify_tls13_signature, CryptoProvider,
+            WebPkiSupportedAlgorithms,
+        },
+        CertificateError, ClientConfig, DigitallySignedStruct, OtherError, RootCertStore,
+        SignatureScheme,
     },
 };
+use crate::verify::{
+    ServerVerifyError, ServerVerifyErrorKind, ServerVerifyMode, ServerVerifyPolicy, SpkiPin,
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::address::{Domain, Host};
+use std::sync::{Arc, OnceLock};
 
 /// Cert verifier that does not verify the server certificate.
 #[derive(Debug)]
@@ -76,3 +88,380 @@ impl ServerCertVerifier for NoServerCertVerifier {
         ]
     }
 }
+
+/// Cert verifier that verifies the server certificate according to a [`ServerVerifyPolicy`].
+///
+/// In [`ServerVerifyMode::Auto`] the certificate chain is verified using the
+/// root certificates of the verifier (the Mozilla root certificates by default)
+/// and the root certificates of the policy, after which the pins are matched
+/// against the end entity certificate and the verified path.
+/// In [`ServerVerifyMode::Disable`] only the end entity certificate is pinned.
+///
+/// The handshake signatures are verified in all modes,
+/// such that a pinned certificate cannot be presented without its private key.
+///
+/// Verification failures are returned as [`rustls::Error::InvalidCertificate`]
+/// with a [`CertificateError::Other`] containing the [`ServerVerifyError`].
+#[derive(Debug)]
+pub struct PolicyServerCertVerifier {
+    policy: ServerVerifyPolicy,
+    roots: Arc<RootCertStore>,
+    supported_algs: WebPkiSupportedAlgorithms,
+}
+
+impl PolicyServerCertVerifier {
+    /// Create a new [`PolicyServerCertVerifier`] for the given [`ServerVerifyPolicy`],
+    /// trusting the Mozilla root certificates and using the default [`CryptoProvider`].
+    pub fn new(policy: ServerVerifyPolicy) -> Result<Self, OpaqueError> {
+        let provider = match CryptoProvider::get_default() {
+            Some(provider) => provider.clone(),
+            None => ClientConfig::builder()
+                .with_root_certificates(RootCertStore::empty())
+                .with_no_client_auth()
+                .crypto_provider()
+                .clone(),
+        };
+        Self::new_with_roots(policy, webpki_root_certs(), &provider)
+    }
+
+    /// Create a new [`PolicyServerCertVerifier`] for the given [`ServerVerifyPolicy`],
+    /// trusting the given root certificates and using the given [`CryptoProvider`],
+    /// e.g. the ones of the [`ClientConfig`] that it is used for.
+    pub fn new_with_roots(
+        policy: ServerVerifyPolicy,
+        mut roots: Arc<RootCertStore>,
+        provider: &CryptoProvider,
+    ) -> Result<Self, OpaqueError> {
+        if !policy.root_certs().is_empty() {
+            let roots = Arc::make_mut(&mut roots);
+            for der in policy.root_certs() {
+                roots
+                    .add(CertificateDer::from(der.clone()))
+                    .context("rustls verifier: add root certificate")?;
+            }
+        }
+        Ok(Self {
+            policy,
+            roots,
+            supported_algs: provider.signature_verification_algorithms,
+        })
+    }
+
+    /// The [`ServerVerifyPolicy`] of this verifier.
+    pub fn policy(&self) -> &ServerVerifyPolicy {
+        &self.policy
+    }
+
+    /// Verify the chain of the given end entity certificate,
+    /// returning the [`SpkiPin`]s of the other certificates of the verified path.
+    fn verify_path(
+        &self,
+        host: &Host,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        now: UnixTime,
+    ) -> Result<Vec<SpkiPin>, ServerVerifyError> {
+        let error = |kind| ServerVerifyError::new(host.clone(), kind);
+
+        let cert = webpki::EndEntityCert::try_from(end_entity)
+            .map_err(|err| error(ServerVerifyErrorKind::InvalidCertificate(err.to_string())))?;
+        let path = cert
+            .verify_for_usage(
+                self.supported_algs.all,
+                &self.roots.roots,
+                intermediates,
+                now,
+                webpki::KeyUsage::server_auth(),
+                None,
+                None,
+            )
+            .map_err(|err| error(ServerVerifyErrorKind::Untrusted(err.to_string())))?;
+        cert.verify_is_valid_for_subject_name(server_name)
+            .map_err(|err| match err {
+                webpki::Error::CertNotValidForName => {
+                    error(ServerVerifyErrorKind::HostnameMismatch)
+                }
+                err => error(ServerVerifyErrorKind::InvalidCertificate(err.to_string())),
+            })?;
+
+        let mut pins = path
+            .intermediate_certificates()
+            .map(|cert| SpkiPin::from_certificate_der(&cert.der()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| error(ServerVerifyErrorKind::InvalidCertificate(err.to_string())))?;
+        pins.push(trust_anchor_pin(path.anchor()));
+        Ok(pins)
+    }
+}
+
+impl ServerCertVerifier for PolicyServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let host = server_name_to_host(server_name);
+
+        // the presented intermediates are only trusted (and pinned) as part of a verified path
+        let verified_path = match self.policy.mode() {
+            ServerVerifyMode::Auto => self
+                .verify_path(&host, end_entity, intermediates, server_name, now)
+                .map_err(into_rustls_error)?,
+            ServerVerifyMode::Disable => Vec::new(),
+        };
+
+        self.policy
+            .verify_pins(&host, end_entity, &verified_path)
+            .map_err(into_rustls_error)?;
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported_algs.supported_schemes()
+    }
+}
+
+/// The [`SpkiPin`] of the given trust anchor,
+/// of which the subject public key info is stored without its outer (sequence) header.
+fn trust_anchor_pin(anchor: &TrustAnchor<'_>) -> SpkiPin {
+    let spki = anchor.subject_public_key_info.as_ref();
+    let mut der = Vec::with_capacity(spki.len() + 4);
+    der.push(0x30);
+    match spki.len() {
+        len @ 0..=0x7f => der.push(len as u8),
+        len @ 0x80..=0xff => der.extend([0x81, len as u8]),
+        len => der.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    der.extend_from_slice(spki);
+    SpkiPin::from_spki_der(&der)
+}
+
+/// The Mozilla root certificates, as provided by the `webpki-roots` crate.
+pub(super) fn webpki_root_certs() -> Arc<RootCertStore> {
+    static ROOT_CERTS: OnceLock<Arc<RootCertStore>> = OnceLock::new();
+    ROOT_CERTS
+        .get_or_init(|| {
+            let mut root_storage = RootCertStore::empty();
+            root_storage.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            Arc::new(root_storage)
+        })
+        .clone()
+}
+
+/// Returns the given [`ClientConfig`], with its server certificate verifier
+/// replaced by a [`PolicyServerCertVerifier`] for the given policy,
+/// trusting the given root certificates (the Mozilla root certificates if none are given).
+pub(super) fn client_config_with_verify_policy(
+    config: Arc<ClientConfig>,
+    policy: &ServerVerifyPolicy,
+    roots: Option<Arc<RootCertStore>>,
+) -> Result<Arc<ClientConfig>, OpaqueError> {
+    let verifier = PolicyServerCertVerifier::new_with_roots(
+        policy.clone(),
+        roots.unwrap_or_else(webpki_root_certs),
+        config.crypto_provider(),
+    )?;
+    let mut config = (*config).clone();
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(verifier));
+    Ok(Arc::new(config))
+}
+
+fn server_name_to_host(server_name: &ServerName<'_>) -> Host {
+    // server names are validated by rustls, this fallback is only here to be on the safe side
+    Host::try_from(server_name.to_str().as_ref())
+        .unwrap_or(Host::Name(Domain::from_static("invalid")))
+}
+
+fn into_rustls_error(err: ServerVerifyError) -> rustls::Error {
+    rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(Arc::new(err))))
+}
+
+/// Returns the [`ServerVerifyError`] of the given (handshake) error, if any.
+pub(super) fn server_verify_error(err: &std::io::Error) -> Option<ServerVerifyError> {
+    match err.get_ref()?.downcast_ref::<rustls::Error>()? {
+        rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(err))) => {
+            err.downcast_ref::<ServerVerifyError>().cloned()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::rcgen;
+
+    fn verify(
+        verifier: &PolicyServerCertVerifier,
+        cert: &rcgen::Certificate,
+        server_name: &'static str,
+    ) -> Result<(), ServerVerifyErrorKind> {
+        verify_chain(verifier, cert, &[], server_name)
+    }
+
+    fn verify_chain(
+        verifier: &PolicyServerCertVerifier,
+        cert: &rcgen::Certificate,
+        intermediates: &[&rcgen::Certificate],
+        server_name: &'static str,
+    ) -> Result<(), ServerVerifyErrorKind> {
+        let server_name = ServerName::try_from(server_name).unwrap();
+        let intermediates: Vec<_> = intermediates
+            .iter()
+            .map(|cert| cert.der().clone())
+            .collect();
+        match verifier.verify_server_cert(
+            cert.der(),
+            &intermediates,
+            &server_name,
+            &[],
+            UnixTime::now(),
+        ) {
+            Ok(_) => Ok(()),
+            Err(rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(err)))) => {
+                Err(err
+                    .downcast_ref::<ServerVerifyError>()
+                    .unwrap()
+                    .kind()
+                    .clone())
+            }
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn test_policy_server_cert_verifier() {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+
+        let verifier = PolicyServerCertVerifier::new(ServerVerifyPolicy::verified()).unwrap();
+        assert!(matches!(
+            verify(&verifier, &cert, "example.com"),
+            Err(ServerVerifyErrorKind::Untrusted(_))
+        ));
+
+        let policy = ServerVerifyPolicy::verified().with_root_cert_der(cert.der().to_vec());
+        let verifier = PolicyServerCertVerifier::new(policy.clone()).unwrap();
+        verify(&verifier, &cert, "example.com").unwrap();
+        assert_eq!(
+            verify(&verifier, &cert, "example.org"),
+            Err(ServerVerifyErrorKind::HostnameMismatch)
+        );
+
+        let other_pin = SpkiPin::new([0; 32]);
+        let verifier = PolicyServerCertVerifier::new(
+            policy.with_pin(Domain::from_static("example.com"), other_pin),
+        )
+        .unwrap();
+        assert_eq!(
+            verify(&verifier, &cert, "example.com"),
+            Err(ServerVerifyErrorKind::PinMismatch)
+        );
+
+        let pin = SpkiPin::from_certificate_der(cert.der()).unwrap();
+        let verifier = PolicyServerCertVerifier::new(
+            ServerVerifyPolicy::new().with_pin(Domain::from_static("example.com"), pin),
+        )
+        .unwrap();
+        verify(&verifier, &cert, "example.com").unwrap();
+    }
+
+    fn ca_cert(
+        name: &str,
+        issuer: Option<(&rcgen::Certificate, &rcgen::KeyPair)>,
+    ) -> (rcgen::Certificate, rcgen::KeyPair) {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec![]).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, name);
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let cert = match issuer {
+            Some((cert, key)) => params.signed_by(&key_pair, cert, key),
+            None => params.self_signed(&key_pair),
+        }
+        .unwrap();
+        (cert, key_pair)
+    }
+
+    #[test]
+    fn test_policy_server_cert_verifier_pins_verified_path_only() {
+        let (root, root_key) = ca_cert("root", None);
+        let (intermediate, intermediate_key) = ca_cert("intermediate", Some((&root, &root_key)));
+        let leaf = rcgen::CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .signed_by(
+                &rcgen::KeyPair::generate().unwrap(),
+                &intermediate,
+                &intermediate_key,
+            )
+            .unwrap();
+        // a certificate the server can present without it being part of the verified path
+        let attacker = rcgen::CertificateParams::new(vec!["attacker.example".to_owned()])
+            .unwrap()
+            .self_signed(&rcgen::KeyPair::generate().unwrap())
+            .unwrap();
+
+        let pin = |cert: &rcgen::Certificate| SpkiPin::from_certificate_der(cert.der()).unwrap();
+        let policy = |mode, pinned: &rcgen::Certificate| {
+            ServerVerifyPolicy::new()
+                .with_mode(mode)
+                .with_root_cert_der(root.der().to_vec())
+                .with_pin(Domain::from_static("example.com"), pin(pinned))
+        };
+
+        for pinned in [&leaf, &intermediate, &root] {
+            let verifier =
+                PolicyServerCertVerifier::new(policy(ServerVerifyMode::Auto, pinned)).unwrap();
+            verify_chain(&verifier, &leaf, &[&intermediate, &attacker], "example.com").unwrap();
+        }
+
+        let verifier =
+            PolicyServerCertVerifier::new(policy(ServerVerifyMode::Auto, &attacker)).unwrap();
+        assert_eq!(
+            verify_chain(&verifier, &leaf, &[&intermediate, &attacker], "example.com"),
+            Err(ServerVerifyErrorKind::PinMismatch)
+        );
+
+        // nothing is verified, and thus only the end entity certificate is pinned
+        let verifier =
+            PolicyServerCertVerifier::new(policy(ServerVerifyMode::Disable, &leaf)).unwrap();
+        verify_chain(&verifier, &leaf, &[&attacker], "example.com").unwrap();
+        for pinned in [&intermediate, &attacker] {
+            let verifier =
+                PolicyServerCertVerifier::new(policy(ServerVerifyMode::Disable, pinned)).unwrap();
+            assert_eq!(
+                verify_chain(&verifier, &leaf, &[&intermediate, &attacker], "example.com"),
+                Err(ServerVerifyErrorKind::PinMismatch)
+            );
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! Verification of the server certificate by the tls clients of rama.
+//!
+//! A [`ServerVerifyPolicy`] defines how the `HttpsConnector` of either tls backend
+//! verifies the certificate (chain) presented by the server:
+//!
+//! - the [`ServerVerifyMode`], defining whether or not the chain and hostname are verified;
+//! - (extra) trusted root certificates, e.g. loaded from a PEM bundle of a private CA;
+//! - [`SpkiPin`]s per host, of which at least one has to match the end entity certificate,
+//!   or (in [`ServerVerifyMode::Auto`]) a certificate of the verified path.
+//!
+//! The policy can be set on the connector, or overwritten per request
+//! by inserting a [`ServerVerifyPolicy`] in the [`Context`].
+//!
+//! In case the verification fails the handshake is aborted, and the returned error
+//! contains a [`ServerVerifyError`], which can be found using [`ServerVerifyError::find`].
+//!
+//! [`Context`]: rama_core::Context
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rama_core::error::{BoxError, ErrorContext, OpaqueError};
+use rama_net::{address::Host, tls::server::PeerCertificate};
+use sha2::{Digest, Sha256};
+use std::{fmt, str::FromStr, sync::Arc};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+/// Mode used to verify the server certificate.
+pub enum ServerVerifyMode {
+    /// Verify the server certificate and hostname, using the root certificates
+    /// configured for the tls backend (or the default ones of the system),
+    /// as well as the root certificates of the [`ServerVerifyPolicy`] (if any).
+    Auto,
+    #[default]
+    /// Do not verify the server certificate.
+    ///
+    /// The [`SpkiPin`]s of the [`ServerVerifyPolicy`] are still verified,
+    /// but only against the end entity certificate.
+    Disable,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+/// The SHA-256 digest of the subject public key info (SPKI) of a certificate,
+/// used to pin the (public key of the) certificate of a host.
+///
+/// It uses the same format as the `pin-sha256` directive of
+/// [RFC 7469](https://datatracker.ietf.org/doc/html/rfc7469), being the base64 encoded digest.
+pub struct SpkiPin([u8; 32]);
+
+impl SpkiPin {
+    /// Create a new [`SpkiPin`] from the given SHA-256 digest.
+    pub const fn new(sha256: [u8; 32]) -> Self {
+        Self(sha256)
+    }
+
+    /// Create a new [`SpkiPin`] from the given base64 encoded SHA-256 digest.
+    pub fn from_base64(s: &str) -> Result<Self, OpaqueError> {
+        let digest = STANDARD.decode(s).context("decode spki pin (base64)")?;
+        let digest = <[u8; 32]>::try_from(digest)
+            .map_err(|_| OpaqueError::from_display("spki pin: expected a SHA-256 digest"))?;
+        Ok(Self(digest))
+    }
+
+    /// Create a new [`SpkiPin`] for the given DER encoded subject public key info.
+    pub fn from_spki_der(spki: &[u8]) -> Self {
+        Self(Sha256::digest(spki).into())
+    }
+
+    /// Create a new [`SpkiPin`] for the given DER encoded certificate.
+    pub fn from_certificate_der(der: &[u8]) -> Result<Self, OpaqueError> {
+        let cert = PeerCertificate::from_der(der)?;
+        Ok(Self(*cert.spki_sha256()))
+    }
+
+    /// The SHA-256 digest of the subject public key info.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for SpkiPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&STANDARD.encode(self.0))
+    }
+}
+
+impl fmt::Debug for SpkiPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SpkiPin").field(&self.to_string()).finish()
+    }
+}
+
+impl FromStr for SpkiPin {
+    type Err = OpaqueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_base64(s)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+/// Policy used by the tls clients to verify the server certificate.
+///
+/// See the [module docs](self) for more information.
+///
+/// The default policy does not verify the server certificate,
+/// matching the default behaviour of the `HttpsConnector`s.
+pub struct ServerVerifyPolicy {
+    inner: Arc<ServerVerifyPolicyInner>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct ServerVerifyPolicyInner {
+    mode: ServerVerifyMode,
+    root_certs: Vec<Vec<u8>>,
+    pins: Vec<(Host, Vec<SpkiPin>)>,
+}
+
+impl ServerVerifyPolicy {
+    /// Create a new [`ServerVerifyPolicy`] which does not verify the server certificate.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new [`ServerVerifyPolicy`] which verifies the server certificate
+    /// and hostname, using [`ServerVerifyMode::Auto`].
+    pub fn verified() -> Self {
+        Self::new().with_mode(ServerVerifyMode::Auto)
+    }
+
+    /// Set the [`ServerVerifyMode`] of this [`ServerVerifyPolicy`].
+    pub fn with_mode(mut self, mode: ServerVerifyMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    /// Set the [`ServerVerifyMode`] of this [`ServerVerifyPolicy`].
+    pub fn set_mode(&mut self, mode: ServerVerifyMode) -> &mut Self {
+        Arc::make_mut(&mut self.inner).mode = mode;
+        self
+    }
+
+    /// Trust the given DER encoded root certificate,
+    /// in addition to the default root certificates of the tls backend.
+    pub fn with_root_cert_der(mut self, der: Vec<u8>) -> Self {
+        self.set_root_cert_der(der);
+        self
+    }
+
+    /// Trust the given DER encoded root certificate,
+    /// in addition to the default root certificates of the tls backend.
+    pub fn set_root_cert_der(&mut self, der: Vec<u8>) -> &mut Self {
+        Arc::make_mut(&mut self.inner).root_certs.push(der);
+        self
+    }
+
+    /// Trust all root certificates of the given PEM bundle,
+    /// in addition to the default root certificates of the tls backend.
+    pub fn with_root_certs_pem(mut self, pem: &str) -> Result<Self, OpaqueError> {
+        self.set_root_certs_pem(pem)?;
+        Ok(self)
+    }
+
+    /// Trust all root certificates of the given PEM bundle,
+    /// in addition to the default root certificates of the tls backend.
+    pub fn set_root_certs_pem(&mut self, pem: &str) -> Result<&mut Self, OpaqueError> {
+        let certs: Vec<_> = pem::parse_many(pem)
+            .context("parse root certificates (pem)")?
+            .into_iter()
+            .filter(|pem| pem.tag() == "CERTIFICATE")
+            .map(pem::Pem::into_contents)
+            .collect();
+        if certs.is_empty() {
+            return Err(OpaqueError::from_display(
+                "parse root certificates (pem): no certificate found",
+            ));
+        }
+        Arc::make_mut(&mut self.inner).root_certs.extend(certs);
+        Ok(self)
+    }
+
+    /// Pin the given [`SpkiPin`] for the given host,
+    /// such that (one of) its pins has to match the certificate presented by that host,
+    /// or (in [`ServerVerifyMode::Auto`]) one of the certificates of the verified path.
+    pub fn with_pin(mut self, host: impl Into<Host>, pin: SpkiPin) -> Self {
+        self.set_pin(host, pin);
+        self
+    }
+
+    /// Pin the given [`SpkiPin`] for the given host,
+    /// such that (one of) its pins has to match the certificate presented by that host,
+    /// or (in [`ServerVerifyMode::Auto`]) one of the certificates of the verified path.
+    pub fn set_pin(&mut self, host: impl Into<Host>, pin: SpkiPin) -> &mut Self {
+        let host = host.into();
+        let pins = &mut Arc::make_mut(&mut self.inner).pins;
+        match pins.iter_mut().find(|(h, _)| *h == host) {
+            Some((_, host_pins)) => host_pins.push(pin),
+            None => pins.push((host, vec![pin])),
+        }
+        self
+    }
+
+    /// The [`ServerVerifyMode`] of this [`ServerVerifyPolicy`].
+    pub fn mode(&self) -> ServerVerifyMode {
+        self.inner.mode
+    }
+
+    /// The (extra) trusted DER encoded root certificates.
+    pub fn root_certs(&self) -> &[Vec<u8>] {
+        &self.inner.root_certs
+    }
+
+    /// The [`SpkiPin`]s of the given host, empty in case it has no pins.
+    pub fn pins(&self, host: &Host) -> &[SpkiPin] {
+        self.inner
+            .pins
+            .iter()
+            .find_map(|(h, pins)| (h == host).then_some(pins.as_slice()))
+            .unwrap_or_default()
+    }
+
+    /// Verify that one of the pins of the given host matches the given DER encoded
+    /// end entity certificate, as presented by that host, or (only in [`ServerVerifyMode::Auto`])
+    /// one of the [`SpkiPin`]s of the other certificates of the verified path
+    /// (intermediates and trust anchor).
+    ///
+    /// The other certificates presented by the host are not to be passed as the verified path,
+    /// as these can be chosen freely by the host, unless they are part of the path verified
+    /// against the trusted root certificates. In [`ServerVerifyMode::Disable`]
+    /// nothing is verified, and thus only the end entity certificate is pinned.
+    ///
+    /// Always succeeds for hosts without pins.
+    pub fn verify_pins(
+        &self,
+        host: &Host,
+        end_entity: &[u8],
+        verified_path: &[SpkiPin],
+    ) -> Result<(), ServerVerifyError> {
+        let pins = self.pins(host);
+        if pins.is_empty() {
+            return Ok(());
+        }
+        let pin = SpkiPin::from_certificate_der(end_entity).map_err(|err| {
+            ServerVerifyError::new(
+                host.clone(),
+                ServerVerifyErrorKind::InvalidCertificate(err.to_string()),
+            )
+        })?;
+        let verified_path = match self.mode() {
+            ServerVerifyMode::Auto => verified_path,
+            ServerVerifyMode::Disable => &[],
+        };
+        if std::iter::once(&pin)
+            .chain(verified_path)
+            .any(|pin| pins.contains(pin))
+        {
+            return Ok(());
+        }
+        Err(ServerVerifyError::new(
+            host.clone(),
+            ServerVerifyErrorKind::PinMismatch,
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Error returned in case the server certificate failed
+/// to verify according to the [`ServerVerifyPolicy`].
+pub struct ServerVerifyError {
+    host: Host,
+    kind: ServerVerifyErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// The kind of [`ServerVerifyError`].
+pub enum ServerVerifyErrorKind {
+    /// The certificate (chain) is not trusted, e.g. because it expired
+    /// or is not issued by a trusted root certificate.
+    Untrusted(String),
+    /// The certificate is not valid for the host.
+    HostnameMismatch,
+    /// None of the (verified) certificates match the pins of the host.
+    PinMismatch,
+    /// A certificate could not be parsed.
+    InvalidCertificate(String),
+}
+
+impl ServerVerifyError {
+    /// Create a new [`ServerVerifyError`].
+    pub const fn new(host: Host, kind: ServerVerifyErrorKind) -> Self {
+        Self { host, kind }
+    }
+
+    /// The host of which the certificate failed to verify.
+    pub fn host(&self) -> &Host {
+        &self.host
+    }
+
+    /// The [`ServerVerifyErrorKind`] of this error.
+    pub fn kind(&self) -> &ServerVerifyErrorKind {
+        &self.kind
+    }
+
+    /// Find the [`ServerVerifyError`] in the given error or its sources,
+    /// e.g. to handle verification failures of a connector or http client.
+    pub fn find<'a>(err: &'a (dyn std::error::Error + 'static)) -> Option<&'a Self> {
+        let mut err = Some(err);
+        while let Some(current) = err {
+            if let Some(verify_err) = current.downcast_ref::<Self>() {
+                return Some(verify_err);
+            }
+            err = current.source();
+        }
+        None
+    }
+
+    /// Convert this error into the error returned by the connectors,
+    /// which keeps it available as its source, as context wrappers are transparent.
+    #[cfg_attr(not(any(feature = "rustls", feature = "boring")), allow(dead_code))]
+    pub(crate) fn into_handshake_error(self) -> BoxError {
+        Box::new(HandshakeVerifyError(self))
+    }
+}
+
+impl fmt::Display for ServerVerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "server certificate verification failed for {}: ",
+            self.host
+        )?;
+        match &self.kind {
+            ServerVerifyErrorKind::Untrusted(reason) => write!(f, "untrusted: {reason}"),
+            ServerVerifyErrorKind::HostnameMismatch => f.write_str("not valid for host"),
+            ServerVerifyErrorKind::PinMismatch => f.write_str("no matching pin"),
+            ServerVerifyErrorKind::InvalidCertificate(reason) => {
+                write!(f, "invalid certificate: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ServerVerifyError {}
+
+#[derive(Debug)]
+#[cfg_attr(not(any(feature = "rustls", feature = "boring")), allow(dead_code))]
+struct HandshakeVerifyError(ServerVerifyError);
+
+impl fmt::Display for HandshakeVerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tls handshake: {}", self.0)
+    }
+}
+
+impl std::error::Error for HandshakeVerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dep::rcgen;
+    use rama_core::error::ErrorExt;
+    use rama_net::address::Domain;
+
+    fn self_signed_cert() -> rcgen::Certificate {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        rcgen::CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_spki_pin_base64() {
+        let pin: SpkiPin = "2CpixS4ckpr5t5Rk9B8D6PuSAw7DrI6JFiqI02Dw+Lc="
+            .parse()
+            .unwrap();
+        assert_eq!(
+            pin.to_string(),
+            "2CpixS4ckpr5t5Rk9B8D6PuSAw7DrI6JFiqI02Dw+Lc="
+        );
+        assert!(SpkiPin::from_base64("AAAA").is_err());
+        assert!(SpkiPin::from_base64("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_server_verify_policy_root_certs_pem() {
+        let pem = format!("{}{}", self_signed_cert().pem(), self_signed_cert().pem());
+        let policy = ServerVerifyPolicy::verified()
+            .with_root_certs_pem(&pem)
+            .unwrap();
+        assert_eq!(policy.mode(), ServerVerifyMode::Auto);
+        assert_eq!(policy.root_certs().len(), 2);
+
+        assert!(ServerVerifyPolicy::new().with_root_certs_pem("").is_err());
+    }
+
+    #[test]
+    fn test_server_verify_policy_verify_pins() {
+        let cert = self_signed_cert();
+        let other_cert = self_signed_cert();
+        let pin = SpkiPin::from_certificate_der(cert.der()).unwrap();
+
+        let host = Host::Name(Domain::from_static("example.com"));
+        let other_host = Host::Name(Domain::from_static("example.org"));
+        let policy = ServerVerifyPolicy::new().with_pin(host.clone(), pin);
+        assert_eq!(policy.pins(&host), [pin]);
+        assert!(policy.pins(&other_host).is_empty());
+
+        policy.verify_pins(&host, cert.der(), &[]).unwrap();
+        policy
+            .verify_pins(&other_host, other_cert.der(), &[])
+            .unwrap();
+
+        let err = policy
+            .verify_pins(&host, other_cert.der(), &[])
+            .unwrap_err();
+        assert_eq!(err.host(), &host);
+        assert_eq!(err.kind(), &ServerVerifyErrorKind::PinMismatch);
+
+        // only the end entity certificate is pinned in case the chain is not verified
+        let err = policy
+            .verify_pins(&host, other_cert.der(), &[pin])
+            .unwrap_err();
+        assert_eq!(err.kind(), &ServerVerifyErrorKind::PinMismatch);
+        policy
+            .clone()
+            .with_mode(ServerVerifyMode::Auto)
+            .verify_pins(&host, other_cert.der(), &[pin])
+            .unwrap();
+
+        let err = policy.verify_pins(&host, b"garbage", &[]).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ServerVerifyErrorKind::InvalidCertificate(_)
+        ));
+    }
+
+    #[test]
+    fn test_server_verify_error_find() {
+        let host = Host::Name(Domain::from_static("example.com"));
+        let err = ServerVerifyError::new(host.clone(), ServerVerifyErrorKind::HostnameMismatch)
+            .into_handshake_error();
+        let err = OpaqueError::from_boxed(err).context("connect");
+        let found = ServerVerifyError::find(&err).unwrap();
+        assert_eq!(found.host(), &host);
+        assert_eq!(found.kind(), &ServerVerifyErrorKind::HostnameMismatch);
+
+        assert!(ServerVerifyError::find(&OpaqueError::from_display("other")).is_none());
+    }
+}
@@ -0,0 +1,137 @@
+use rama_core::{
+    error::{ErrorContext, OpaqueError},
+    Context,
+};
+use rama_net::address::{Authority, Host};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::UdpSocket;
+
+/// Create a UDP socket "connected" to the given authority.
+///
+/// In the case where the authority is already an IP address, we can directly connect to it.
+/// Otherwise, the domain is resolved using the [`Dns`] resolver found in the [`Context`],
+/// preferring IPv4 over IPv6 addresses.
+///
+/// The returned socket is bound to an OS-assigned port on the unspecified address
+/// of the same family as the target, and only sends to and receives from the target.
+///
+/// [`Dns`]: rama_core::dns::Dns
+pub async fn connect<State: Send + Sync + 'static>(
+    ctx: &Context<State>,
+    authority: Authority,
+) -> Result<(UdpSocket, SocketAddr), OpaqueError> {
+    connect_inner(ctx, authority, false).await
+}
+
+/// Create a UDP socket "connected" to the given authority.
+///
+/// Same as [`connect`] but without allowing DNS overwrites.
+pub async fn connect_trusted<State: Send + Sync + 'static>(
+    ctx: &Context<State>,
+    authority: Authority,
+) -> Result<(UdpSocket, SocketAddr), OpaqueError> {
+    connect_inner(ctx, authority, true).await
+}
+
+async fn connect_inner<State>(
+    ctx: &Context<State>,
+    authority: Authority,
+    trusted_only: bool,
+) -> Result<(UdpSocket, SocketAddr), OpaqueError>
+where
+    State: Send + Sync + 'static,
+{
+    let (host, port) = authority.into_parts();
+    let ip = match host {
+        Host::Address(ip) => ip,
+        Host::Name(domain) => {
+            let dns = ctx.dns();
+
+            let ipv4 = if trusted_only {
+                dns.ipv4_lookup_trusted(domain.clone())
+                    .await
+                    .map(|mut it| it.next())
+            } else {
+                dns.ipv4_lookup(domain.clone())
+                    .await
+                    .map(|mut it| it.next())
+            };
+            let ipv4 = match ipv4 {
+                Ok(ip) => ip,
+                Err(err) => {
+                    tracing::trace!(err = %err, "failed to resolve domain to IPv4 addresses");
+                    None
+                }
+            };
+
+            match ipv4 {
+                Some(ip) => IpAddr::V4(ip),
+                None => {
+                    let ipv6 = if trusted_only {
+                        dns.ipv6_lookup_trusted(domain.clone())
+                            .await
+                            .map(|mut it| it.next())
+                    } else {
+                        dns.ipv6_lookup(domain.clone())
+                            .await
+                            .map(|mut it| it.next())
+                    };
+                    ipv6.context("resolve domain to IPv6 addresses")?
+                        .map(IpAddr::V6)
+                        .ok_or_else(|| {
+                            OpaqueError::from_display(format!(
+                                "failed to resolve any IP address for {domain} (port {port})"
+                            ))
+                        })?
+                }
+            }
+        }
+    };
+
+    let addr = SocketAddr::new(ip, port);
+    let bind_ip: IpAddr = match ip {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+
+    let socket = UdpSocket::bind((bind_ip, 0))
+        .await
+        .context("bind udp client socket")?;
+    socket
+        .connect(addr)
+        .await
+        .context("connect udp client socket")?;
+
+    Ok((socket, addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::dns::Dns;
+
+    #[tokio::test]
+    async fn test_connect_dns_overwrite() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let mut ctx = Context::default();
+        let mut dns = Dns::default();
+        dns.insert_overwrite("example.com", vec!["127.0.0.1".parse().unwrap()])
+            .unwrap();
+        *ctx.dns_mut() = dns;
+
+        let authority = Authority::new(Host::try_from("example.com").unwrap(), server_addr.port());
+        let (socket, addr) = connect(&ctx, authority).await.unwrap();
+        assert_eq!(addr, server_addr);
+
+        socket.send(b"ping").await.unwrap();
+        let mut buf = [0u8; 16];
+        let (n, src) = server.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+
+        server.send_to(b"pong", src).await.unwrap();
+        let n = socket.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"pong");
+    }
+}
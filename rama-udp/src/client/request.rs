@@ -0,0 +1,86 @@
+use rama_core::Context;
+use rama_net::{
+    address::Authority,
+    transport::{TransportContext, TransportProtocol, TryRefIntoTransportContext},
+    Protocol,
+};
+use std::convert::Infallible;
+
+#[derive(Debug, Clone)]
+/// A request to establish a Udp "Connection".
+///
+/// This can be used in case you operate on a layer below
+/// an application layer such as Dns.
+pub struct Request {
+    authority: Authority,
+    protocol: Option<Protocol>,
+}
+
+impl Request {
+    /// Create a new Udp [`Request`].
+    pub const fn new(authority: Authority) -> Self {
+        Self {
+            authority,
+            protocol: None,
+        }
+    }
+
+    /// Attach an application protocol to this [`Request`]
+    /// on which the established connection will operate.
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Set an application protocol to this [`Request`]
+    /// on which the established connection will operate.
+    pub fn set_protocol(&mut self, protocol: Protocol) -> &mut Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Return the application protocol on which the established
+    /// connection will operate, if known.
+    pub fn protocol(&self) -> Option<Protocol> {
+        self.protocol.clone()
+    }
+
+    /// View a reference to the target [`Authority`] of
+    /// this Udp [`Request`].
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+}
+
+impl From<&Request> for TransportContext {
+    fn from(value: &Request) -> Self {
+        TransportContext {
+            protocol: TransportProtocol::Udp,
+            app_protocol: value.protocol.clone(),
+            http_version: None,
+            authority: value.authority.clone(),
+        }
+    }
+}
+
+impl From<Request> for TransportContext {
+    fn from(value: Request) -> Self {
+        TransportContext {
+            protocol: TransportProtocol::Udp,
+            app_protocol: value.protocol,
+            http_version: None,
+            authority: value.authority,
+        }
+    }
+}
+
+impl<State> TryRefIntoTransportContext<State> for Request {
+    type Error = Infallible;
+
+    fn try_ref_into_transport_ctx(
+        &self,
+        _ctx: &Context<State>,
+    ) -> Result<TransportContext, Self::Error> {
+        Ok(self.into())
+    }
+}
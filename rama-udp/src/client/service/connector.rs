@@ -0,0 +1,87 @@
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Service,
+};
+use rama_net::{
+    address::ProxyAddress,
+    client::EstablishedClientConnection,
+    transport::{TransportProtocol, TryRefIntoTransportContext},
+};
+use tokio::net::UdpSocket;
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+/// A connector which can be used to establish a UDP "connection" to a server,
+/// meaning a [`UdpSocket`] which only sends to and receives from that server.
+pub struct UdpConnector;
+
+impl UdpConnector {
+    /// Create a new [`UdpConnector`], which is used to establish a connection to a server.
+    ///
+    /// You can use middleware around the [`UdpConnector`]
+    /// or add retry logic and more.
+    pub const fn new() -> Self {
+        UdpConnector
+    }
+}
+
+impl Default for UdpConnector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<State, Request> Service<State, Request> for UdpConnector
+where
+    State: Send + Sync + 'static,
+    Request: TryRefIntoTransportContext<State> + Send + 'static,
+    Request::Error: Into<BoxError> + Send + Sync + 'static,
+{
+    type Response = EstablishedClientConnection<UdpSocket, State, Request>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        if ctx.contains::<ProxyAddress>() {
+            // proxying datagrams requires a proxy protocol aware connector (e.g. socks5),
+            // sending them directly to the proxy address would be wrong
+            return Err(OpaqueError::from_display(
+                "udp connector: proxy address is not supported by Udp Connector Service",
+            )
+            .into());
+        }
+
+        let transport_ctx = ctx
+            .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
+            .map_err(|err| {
+                OpaqueError::from_boxed(err.into())
+                    .context("udp connector: compute transport context to get authority")
+            })?;
+
+        match transport_ctx.protocol {
+            TransportProtocol::Udp => (), // a-ok :)
+            TransportProtocol::Tcp => {
+                // sanity check, shouldn't happen, but in case someone makes a weird stack, it can
+                return Err(OpaqueError::from_display(
+                    "Udp Connector Service cannot establish a TCP transport",
+                )
+                .into());
+            }
+        }
+
+        let authority = transport_ctx.authority.clone();
+        let (conn, addr) = crate::client::connect(&ctx, authority)
+            .await
+            .context("udp connector: connect to server")?;
+
+        Ok(EstablishedClientConnection {
+            ctx,
+            req,
+            conn,
+            addr,
+        })
+    }
+}
@@ -0,0 +1,474 @@
+use super::UdpConnector;
+use crate::{client::Request as UdpRequest, server::Datagram};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    graceful::ShutdownGuard,
+    Context, Service,
+};
+use rama_net::{
+    address::{Authority, ForwardAuthority},
+    client::{ConnectorService, EstablishedClientConnection},
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{net::UdpSocket, sync::OnceCell, time::Instant};
+
+/// The default duration after which an idle session is expired.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The default maximum number of concurrent sessions.
+const DEFAULT_MAX_SESSIONS: usize = 4096;
+
+/// The maximum size of a datagram received from the target.
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+#[derive(Debug, Clone)]
+enum ForwarderKind {
+    Static(Authority),
+    Dynamic,
+}
+
+/// A UDP forwarder, serving the [`Datagram`]s received by a [`UdpListener`].
+///
+/// For each client peer (and target) a session is created, NAT-style,
+/// with its own socket connected to the target. Datagrams received from the client
+/// are sent to the target via that socket, and the datagrams received
+/// from the target on that socket are relayed back to the client,
+/// via the socket on which the client datagrams were received.
+///
+/// Sessions are expired once no datagram was exchanged in either direction
+/// for the idle timeout, which can be configured using [`Forwarder::with_idle_timeout`].
+/// Datagrams which would create a new session are dropped while the maximum number
+/// of sessions is reached, which can be configured using [`Forwarder::with_max_sessions`].
+///
+/// [`UdpListener`]: crate::server::UdpListener
+pub struct Forwarder<C> {
+    kind: ForwarderKind,
+    connector: C,
+    idle_timeout: Duration,
+    max_sessions: usize,
+    sessions: Arc<Mutex<HashMap<SessionKey, Arc<Session>>>>,
+}
+
+impl<C> fmt::Debug for Forwarder<C>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Forwarder")
+            .field("kind", &self.kind)
+            .field("connector", &self.connector)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_sessions", &self.max_sessions)
+            .finish()
+    }
+}
+
+impl<C> Clone for Forwarder<C>
+where
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            kind: self.kind.clone(),
+            connector: self.connector.clone(),
+            idle_timeout: self.idle_timeout,
+            max_sessions: self.max_sessions,
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+impl Forwarder<UdpConnector> {
+    /// Create a new static forwarder for the given target [`Authority`]
+    pub fn new(target: impl Into<Authority>) -> Self {
+        Self {
+            kind: ForwarderKind::Static(target.into()),
+            connector: UdpConnector::new(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            sessions: Default::default(),
+        }
+    }
+
+    /// Create a new dynamic forwarder, which will fetch the target from the [`Context`]
+    pub fn ctx() -> Self {
+        Self {
+            kind: ForwarderKind::Dynamic,
+            connector: UdpConnector::new(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            sessions: Default::default(),
+        }
+    }
+
+    /// Set a custom "connector" for this forwarder, overwriting
+    /// the default udp connector which simply creates a connected UDP socket.
+    ///
+    /// The connector is used to create the socket of each new session.
+    pub fn connector<T>(self, connector: T) -> Forwarder<T> {
+        Forwarder {
+            kind: self.kind,
+            connector,
+            idle_timeout: self.idle_timeout,
+            max_sessions: self.max_sessions,
+            sessions: self.sessions,
+        }
+    }
+}
+
+impl<C> Forwarder<C> {
+    /// Set the duration after which a session is expired
+    /// in case no datagram was exchanged in either direction.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set the duration after which a session is expired
+    /// in case no datagram was exchanged in either direction.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of concurrent sessions,
+    /// datagrams which would create a new session are dropped once reached.
+    ///
+    /// Defaults to 4096.
+    pub fn with_max_sessions(mut self, max: usize) -> Self {
+        self.max_sessions = max;
+        self
+    }
+
+    /// Set the maximum number of concurrent sessions,
+    /// datagrams which would create a new session are dropped once reached.
+    ///
+    /// Defaults to 4096.
+    pub fn set_max_sessions(&mut self, max: usize) -> &mut Self {
+        self.max_sessions = max;
+        self
+    }
+
+    /// Returns the number of active sessions.
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+}
+
+impl<S, C> Service<S, Datagram> for Forwarder<C>
+where
+    S: Send + Sync + 'static,
+    C: ConnectorService<S, UdpRequest, Connection = UdpSocket, Error: Into<BoxError>>,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<S>,
+        datagram: Datagram,
+    ) -> Result<Self::Response, Self::Error> {
+        let authority = match &self.kind {
+            ForwarderKind::Static(target) => target.clone(),
+            ForwarderKind::Dynamic => ctx
+                .get::<ForwardAuthority>()
+                .map(|f| f.as_ref().clone())
+                .ok_or_else(|| {
+                    OpaqueError::from_display("missing forward authority").into_boxed()
+                })?,
+        };
+
+        let key = SessionKey {
+            local_addr: datagram.local_addr().ok(),
+            peer_addr: datagram.peer_addr(),
+            target: authority.clone(),
+        };
+        let session = {
+            let mut sessions = self.sessions.lock().unwrap();
+            if !sessions.contains_key(&key) && sessions.len() >= self.max_sessions {
+                tracing::debug!(
+                    peer_addr = %key.peer_addr,
+                    target = %key.target,
+                    "udp forwarder: drop datagram: max number of sessions reached",
+                );
+                return Err(OpaqueError::from_display(
+                    "udp forwarder: max number of sessions reached",
+                )
+                .into_boxed());
+            }
+            sessions
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(Session::new()))
+                .clone()
+        };
+
+        let target = session
+            .socket
+            .get_or_try_init(|| async {
+                let guard = ctx.guard().cloned();
+                let executor = ctx.executor().clone();
+
+                let EstablishedClientConnection { conn, addr, .. } = self
+                    .connector
+                    .connect(ctx, UdpRequest::new(authority.clone()))
+                    .await
+                    .map_err(|err| {
+                        OpaqueError::from_boxed(err.into())
+                            .with_context(|| format!("create udp session to {authority}"))
+                    })?;
+                tracing::trace!(
+                    peer_addr = %key.peer_addr,
+                    target_addr = %addr,
+                    "udp forwarder: session created",
+                );
+
+                let conn = Arc::new(conn);
+                executor.spawn_task(relay_to_client(
+                    self.sessions.clone(),
+                    key.clone(),
+                    session.clone(),
+                    conn.clone(),
+                    datagram.shared_socket(),
+                    self.idle_timeout,
+                    guard,
+                ));
+                Ok::<_, OpaqueError>(conn)
+            })
+            .await;
+
+        let target = match target {
+            Ok(target) => target,
+            Err(err) => {
+                // remove the failed session, unless it was already replaced
+                let mut sessions = self.sessions.lock().unwrap();
+                if sessions
+                    .get(&key)
+                    .map(|s| Arc::ptr_eq(s, &session))
+                    .unwrap_or_default()
+                {
+                    sessions.remove(&key);
+                }
+                return Err(err.into_boxed());
+            }
+        };
+
+        session.touch();
+        target
+            .send(datagram.payload())
+            .await
+            .context("udp forwarder: send datagram to target")?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SessionKey {
+    local_addr: Option<SocketAddr>,
+    peer_addr: SocketAddr,
+    target: Authority,
+}
+
+#[derive(Debug)]
+struct Session {
+    socket: OnceCell<Arc<UdpSocket>>,
+    last_active: Mutex<Instant>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            socket: OnceCell::new(),
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    fn last_active(&self) -> Instant {
+        *self.last_active.lock().unwrap()
+    }
+}
+
+/// Relay the datagrams received from the target back to the client,
+/// until the session expires or a graceful shutdown is initiated.
+async fn relay_to_client(
+    sessions: Arc<Mutex<HashMap<SessionKey, Arc<Session>>>>,
+    key: SessionKey,
+    session: Arc<Session>,
+    target: Arc<UdpSocket>,
+    source: Arc<UdpSocket>,
+    idle_timeout: Duration,
+    guard: Option<ShutdownGuard>,
+) {
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    let cancelled = async {
+        match guard {
+            Some(guard) => guard.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+    let mut cancelled = std::pin::pin!(cancelled);
+
+    loop {
+        let deadline = session.last_active() + idle_timeout;
+        tokio::select! {
+            _ = cancelled.as_mut() => {
+                tracing::trace!(peer_addr = %key.peer_addr, "udp forwarder: session closed: shutdown");
+                break;
+            }
+            _ = tokio::time::sleep_until(deadline) => {
+                if session.last_active() + idle_timeout <= Instant::now() {
+                    tracing::trace!(peer_addr = %key.peer_addr, "udp forwarder: session expired");
+                    break;
+                }
+            }
+            result = target.recv(&mut buf) => match result {
+                Ok(n) => {
+                    session.touch();
+                    if let Err(err) = source.send_to(&buf[..n], key.peer_addr).await {
+                        tracing::debug!(err = %err, peer_addr = %key.peer_addr, "udp forwarder: failed to relay datagram to client");
+                    }
+                }
+                Err(err) => {
+                    // e.g. an ICMP port unreachable message,
+                    // which shouldn't end the session by itself
+                    tracing::trace!(err = %err, target = %key.target, "udp forwarder: failed to receive datagram from target");
+                }
+            },
+        }
+    }
+
+    let mut sessions = sessions.lock().unwrap();
+    if sessions
+        .get(&key)
+        .map(|s| Arc::ptr_eq(s, &session))
+        .unwrap_or_default()
+    {
+        sessions.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::UdpListener;
+
+    async fn spawn_echo_target() -> SocketAddr {
+        let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = target.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (n, src) = target.recv_from(&mut buf).await.unwrap();
+                // echo the payload, followed by the port the datagram was received from
+                let mut reply = buf[..n].to_vec();
+                reply.extend_from_slice(&src.port().to_be_bytes());
+                target.send_to(&reply, src).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    async fn exchange(client: &UdpSocket, payload: &[u8]) -> u16 {
+        client.send(payload).await.unwrap();
+        let mut buf = [0u8; 1024];
+        let n = client.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n - 2], payload);
+        u16::from_be_bytes([buf[n - 2], buf[n - 1]])
+    }
+
+    #[tokio::test]
+    async fn test_forward_session_reuse_and_expiry() {
+        let target_addr = spawn_echo_target().await;
+
+        let forwarder = Forwarder::new(target_addr).with_idle_timeout(Duration::from_millis(100));
+        let listener = UdpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(forwarder.clone()));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(listener_addr).await.unwrap();
+
+        let first_port = exchange(&client, b"hello").await;
+        let second_port = exchange(&client, b"world").await;
+        assert_eq!(first_port, second_port);
+        assert_eq!(forwarder.session_count(), 1);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(forwarder.session_count(), 0);
+
+        let third_port = exchange(&client, b"again").await;
+        assert_ne!(first_port, third_port);
+        assert_eq!(forwarder.session_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_forward_max_sessions() {
+        let target_addr = spawn_echo_target().await;
+
+        let forwarder = Forwarder::new(target_addr)
+            .with_idle_timeout(Duration::from_millis(100))
+            .with_max_sessions(1);
+        let listener = UdpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(forwarder.clone()));
+
+        let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        first.connect(listener_addr).await.unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        second.connect(listener_addr).await.unwrap();
+
+        exchange(&first, b"hello").await;
+        assert_eq!(forwarder.session_count(), 1);
+
+        // the datagram of the second client is dropped while the first session is active
+        second.send(b"dropped").await.unwrap();
+        let mut buf = [0u8; 1024];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), second.recv(&mut buf))
+                .await
+                .is_err()
+        );
+        assert_eq!(forwarder.session_count(), 1);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(forwarder.session_count(), 0);
+
+        exchange(&second, b"world").await;
+        assert_eq!(forwarder.session_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_forward_dynamic_authority() {
+        let target_addr = spawn_echo_target().await;
+
+        let forwarder = Forwarder::ctx();
+        let listener = UdpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(rama_core::service::service_fn(
+            move |mut ctx: Context<()>, datagram: Datagram| {
+                let forwarder = forwarder.clone();
+                async move {
+                    ctx.insert(ForwardAuthority::new(target_addr));
+                    forwarder.serve(ctx, datagram).await
+                }
+            },
+        )));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(listener_addr).await.unwrap();
+        exchange(&client, b"ping").await;
+    }
+}
@@ -0,0 +1,12 @@
+//! UDP services for Rama.
+
+mod forward;
+#[doc(inline)]
+pub use forward::Forwarder;
+
+#[doc(inline)]
+pub use rama_net::address::ForwardAuthority;
+
+mod connector;
+#[doc(inline)]
+pub use connector::UdpConnector;
@@ -1,4 +1,4 @@
-//! UDP module for Rama.
+//! UDP support for Rama.
 //!
 //! # Rama
 //!
@@ -54,5 +54,12 @@
 #![cfg_attr(test, allow(clippy::float_cmp))]
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
-/// TODO
-pub const PLACEHOLDER: bool = true;
+#[doc(inline)]
+pub use tokio::net::UdpSocket;
+
+mod tracker;
+#[doc(inline)]
+pub use tracker::{UdpSocketTracker, UdpSocketTrackerHandle};
+
+pub mod client;
+pub mod server;
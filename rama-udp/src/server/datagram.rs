@@ -0,0 +1,71 @@
+use bytes::Bytes;
+use std::{io, net::SocketAddr, sync::Arc};
+use tokio::net::UdpSocket;
+
+#[derive(Debug, Clone)]
+/// A datagram received by a [`UdpListener`],
+/// which is the request served by the service of that listener.
+///
+/// It gives access to the received payload and the address of the peer,
+/// as well as the possibility to reply to that peer via
+/// the socket on which the datagram was received.
+///
+/// [`UdpListener`]: super::UdpListener
+pub struct Datagram {
+    payload: Bytes,
+    peer_addr: SocketAddr,
+    socket: Arc<UdpSocket>,
+}
+
+impl Datagram {
+    pub(super) fn new(payload: Bytes, peer_addr: SocketAddr, socket: Arc<UdpSocket>) -> Self {
+        Self {
+            payload,
+            peer_addr,
+            socket,
+        }
+    }
+
+    /// Returns the payload of this [`Datagram`].
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Consumes this [`Datagram`], returning its payload.
+    pub fn into_payload(self) -> Bytes {
+        self.payload
+    }
+
+    /// Returns the address of the peer which sent this [`Datagram`].
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Returns the local address of the socket on which this [`Datagram`] was received.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Returns a reference to the socket on which this [`Datagram`] was received,
+    /// which is shared with the [`UdpListener`] and all other datagrams received by it.
+    ///
+    /// [`UdpListener`]: super::UdpListener
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns a shared handle to the socket on which this [`Datagram`] was received,
+    /// such that it can be used to reply to the peer beyond the lifetime of this [`Datagram`].
+    #[cfg(feature = "http")]
+    pub(crate) fn shared_socket(&self) -> Arc<UdpSocket> {
+        self.socket.clone()
+    }
+
+    /// Send the given payload back to the peer which sent this [`Datagram`],
+    /// using the socket on which it was received.
+    ///
+    /// On success, the number of bytes sent is returned.
+    pub async fn reply(&self, payload: &[u8]) -> io::Result<usize> {
+        self.socket.send_to(payload, self.peer_addr).await
+    }
+}
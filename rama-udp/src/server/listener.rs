@@ -0,0 +1,391 @@
+use super::Datagram;
+use bytes::Bytes;
+use rama_core::graceful::ShutdownGuard;
+use rama_core::rt::Executor;
+use rama_core::service::handler::{Factory, FromContextRequest};
+use rama_core::Context;
+use rama_core::Service;
+use rama_net::stream::SocketInfo;
+use std::fmt;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::{io, net::SocketAddr};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+/// The default maximum size of a datagram received by a [`UdpListener`],
+/// which is the maximum size of a UDP payload.
+const DEFAULT_MAX_DATAGRAM_SIZE: usize = 65_535;
+
+/// Builder for `UdpListener`.
+pub struct UdpListenerBuilder<S> {
+    ttl: Option<u32>,
+    max_datagram_size: usize,
+    state: Arc<S>,
+}
+
+impl<S> fmt::Debug for UdpListenerBuilder<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpListenerBuilder")
+            .field("ttl", &self.ttl)
+            .field("max_datagram_size", &self.max_datagram_size)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl UdpListenerBuilder<()> {
+    /// Create a new `UdpListenerBuilder` without a state.
+    pub fn new() -> Self {
+        Self {
+            ttl: None,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+            state: Arc::new(()),
+        }
+    }
+}
+
+impl Default for UdpListenerBuilder<()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Clone for UdpListenerBuilder<S> {
+    fn clone(&self) -> Self {
+        Self {
+            ttl: self.ttl,
+            max_datagram_size: self.max_datagram_size,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S> UdpListenerBuilder<S> {
+    /// Sets the value for the `IP_TTL` option on this socket.
+    ///
+    /// This value sets the time-to-live field that is used in every packet sent
+    /// from this socket.
+    pub fn ttl(&mut self, ttl: u32) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the maximum size of a received datagram,
+    /// any bytes beyond this size are discarded by the OS.
+    ///
+    /// Defaults to `65_535`, the maximum size of a UDP payload.
+    pub fn max_datagram_size(&mut self, size: usize) -> &mut Self {
+        self.max_datagram_size = size;
+        self
+    }
+}
+
+impl<S> UdpListenerBuilder<S>
+where
+    S: Send + Sync + 'static,
+{
+    /// Create a new `UdpListenerBuilder` with the given state.
+    pub fn with_state(state: S) -> Self {
+        Self {
+            ttl: None,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+            state: Arc::new(state),
+        }
+    }
+
+    /// Creates a new UdpListener, which will be bound to the specified address.
+    ///
+    /// The returned listener is ready for receiving datagrams.
+    ///
+    /// Binding with a port number of 0 will request that the OS assigns a port
+    /// to this listener. The port allocated can be queried via the `local_addr`
+    /// method.
+    pub async fn bind<A: ToSocketAddrs>(&self, addr: A) -> io::Result<UdpListener<S>> {
+        let inner = UdpSocket::bind(addr).await?;
+
+        if let Some(ttl) = self.ttl {
+            inner.set_ttl(ttl)?;
+        }
+
+        Ok(UdpListener {
+            inner: Arc::new(inner),
+            max_datagram_size: self.max_datagram_size,
+            state: self.state.clone(),
+        })
+    }
+}
+
+/// A UDP socket server, receiving datagrams once served
+/// using one of the `serve` methods such as [`UdpListener::serve`].
+///
+/// Each received datagram is served as a [`Datagram`],
+/// with its own [`Context`] containing the [`SocketInfo`] of the peer.
+pub struct UdpListener<S> {
+    inner: Arc<UdpSocket>,
+    max_datagram_size: usize,
+    state: Arc<S>,
+}
+
+impl<S> fmt::Debug for UdpListener<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpListener")
+            .field("inner", &self.inner)
+            .field("max_datagram_size", &self.max_datagram_size)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl UdpListener<()> {
+    /// Create a new `UdpListenerBuilder` without a state,
+    /// which can be used to configure a `UdpListener`.
+    pub fn build() -> UdpListenerBuilder<()> {
+        UdpListenerBuilder::new()
+    }
+
+    /// Create a new `UdpListenerBuilder` with the given state,
+    /// which can be used to configure a `UdpListener`.
+    pub fn build_with_state<S>(state: S) -> UdpListenerBuilder<S>
+    where
+        S: Send + Sync + 'static,
+    {
+        UdpListenerBuilder::with_state(state)
+    }
+
+    /// Creates a new UdpListener, which will be bound to the specified address.
+    ///
+    /// The returned listener is ready for receiving datagrams.
+    ///
+    /// Binding with a port number of 0 will request that the OS assigns a port
+    /// to this listener. The port allocated can be queried via the `local_addr`
+    /// method.
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        UdpListenerBuilder::default().bind(addr).await
+    }
+}
+
+impl<S> UdpListener<S> {
+    /// Returns the local address that this listener is bound to.
+    ///
+    /// This can be useful, for example, when binding to port 0 to figure out
+    /// which port was actually bound.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Gets the value of the `IP_TTL` option for this socket.
+    ///
+    /// For more information about this option, see [`set_ttl`].
+    ///
+    /// [`set_ttl`]: UdpListenerBuilder::ttl
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.inner.ttl()
+    }
+
+    /// Gets a reference to the listener's state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+}
+
+impl<State> UdpListener<State>
+where
+    State: Send + Sync + 'static,
+{
+    /// Serve datagrams received by this listener with the given service.
+    ///
+    /// Each datagram is served within its own spawned task,
+    /// replies can be sent using [`Datagram::reply`].
+    pub async fn serve<S>(self, service: S)
+    where
+        S: Service<State, Datagram>,
+    {
+        let ctx = Context::new(self.state, Executor::new());
+        let service = Arc::new(service);
+        let local_addr = self.inner.local_addr().ok();
+        let mut buf = vec![0u8; self.max_datagram_size];
+
+        loop {
+            let (n, peer_addr) = match self.inner.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(err) => {
+                    handle_recv_err(err).await;
+                    continue;
+                }
+            };
+
+            let datagram = Datagram::new(
+                Bytes::copy_from_slice(&buf[..n]),
+                peer_addr,
+                self.inner.clone(),
+            );
+            let service = service.clone();
+            let mut ctx = ctx.clone();
+
+            tokio::spawn(async move {
+                ctx.insert(SocketInfo::new(local_addr, peer_addr));
+
+                let _ = service.serve(ctx, datagram).await;
+            });
+        }
+    }
+
+    /// Serve datagrams received by this listener with the given service function.
+    ///
+    /// See [`Self::serve`] for more details.
+    pub async fn serve_fn<F, T, R, O, E>(self, f: F)
+    where
+        F: Factory<T, R, O, E>,
+        R: Future<Output = Result<O, E>> + Send + Sync + 'static,
+        O: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+        T: FromContextRequest<State, Datagram>,
+    {
+        let service = rama_core::service::service_fn(f);
+        self.serve(service).await
+    }
+
+    /// Serve gracefully datagrams received by this listener with the given service.
+    ///
+    /// This method does the same as [`Self::serve`] but it
+    /// will respect the given [`rama_core::graceful::ShutdownGuard`], and also pass
+    /// it to the service.
+    pub async fn serve_graceful<S>(self, guard: ShutdownGuard, service: S)
+    where
+        S: Service<State, Datagram>,
+    {
+        let ctx: Context<State> = Context::new(self.state, Executor::graceful(guard.clone()));
+        let service = Arc::new(service);
+        let local_addr = self.inner.local_addr().ok();
+        let mut buf = vec![0u8; self.max_datagram_size];
+        let mut cancelled_fut = pin!(guard.cancelled());
+
+        loop {
+            tokio::select! {
+                _ = cancelled_fut.as_mut() => {
+                    tracing::trace!("signal received: initiate graceful shutdown");
+                    break;
+                }
+                result = self.inner.recv_from(&mut buf) => {
+                    match result {
+                        Ok((n, peer_addr)) => {
+                            let datagram = Datagram::new(
+                                Bytes::copy_from_slice(&buf[..n]),
+                                peer_addr,
+                                self.inner.clone(),
+                            );
+                            let service = service.clone();
+                            let mut ctx = ctx.clone();
+
+                            guard.spawn_task(async move {
+                                ctx.insert(SocketInfo::new(local_addr, peer_addr));
+
+                                let _ = service.serve(ctx, datagram).await;
+                            });
+                        }
+                        Err(err) => {
+                            handle_recv_err(err).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Serve gracefully datagrams received by this listener with the given service function.
+    ///
+    /// See [`Self::serve_graceful`] for more details.
+    pub async fn serve_fn_graceful<F, T, R, O, E>(self, guard: ShutdownGuard, service: F)
+    where
+        F: Factory<T, R, O, E>,
+        R: Future<Output = Result<O, E>> + Send + Sync + 'static,
+        O: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+        T: FromContextRequest<State, Datagram>,
+    {
+        let service = rama_core::service::service_fn(service);
+        self.serve_graceful(guard, service).await
+    }
+}
+
+async fn handle_recv_err(err: io::Error) {
+    if matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    ) {
+        // on some platforms (e.g. windows) an ICMP "port unreachable" message,
+        // received in response to a datagram previously sent, is surfaced
+        // as an error on the next receive, which does not affect the listener itself
+        tracing::trace!(
+            error = &err as &dyn std::error::Error,
+            "UDP recv error: connection error"
+        );
+    } else {
+        // same reasoning as for the TCP accept errors,
+        // e.g. the process might have hit a resource limit,
+        // so back off for a bit before receiving again
+        tracing::error!(error = &err as &dyn std::error::Error, "UDP recv error");
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::graceful::Shutdown;
+    use std::{convert::Infallible, time::Duration};
+
+    #[tokio::test]
+    async fn test_serve_graceful_echo() {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let shutdown = Shutdown::new(async move {
+            let _ = rx.await;
+        });
+
+        let listener = UdpListener::build_with_state(42u8)
+            .bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+
+        shutdown.spawn_task_fn(|guard| {
+            listener.serve_graceful(
+                guard,
+                rama_core::service::service_fn(|ctx: Context<u8>, datagram: Datagram| async move {
+                    assert_eq!(*ctx.state(), 42);
+                    let info = ctx.get::<SocketInfo>().unwrap();
+                    assert_eq!(*info.peer_addr(), datagram.peer_addr());
+                    assert_eq!(info.local_addr(), datagram.local_addr().ok().as_ref());
+
+                    let mut reply = b"echo: ".to_vec();
+                    reply.extend_from_slice(datagram.payload());
+                    datagram.reply(&reply).await.unwrap();
+                    Ok::<_, Infallible>(())
+                }),
+            )
+        });
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"hello", listener_addr).await.unwrap();
+
+        let mut buf = [0u8; 64];
+        let (n, src) = client.recv_from(&mut buf).await.unwrap();
+        assert_eq!(src, listener_addr);
+        assert_eq!(&buf[..n], b"echo: hello");
+
+        tx.send(()).unwrap();
+        shutdown
+            .shutdown_with_limit(Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,37 @@
+//! UDP server module for Rama.
+//!
+//! The UDP server is used to create a [`UdpListener`] and serve incoming datagrams,
+//! each of which is served as a [`Datagram`] with its own [`Context`].
+//!
+//! [`Context`]: rama_core::Context
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rama_udp::server::{Datagram, UdpListener};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     UdpListener::bind("127.0.0.1:9000")
+//!         .await
+//!         .expect("bind UDP Listener")
+//!         .serve_fn(|datagram: Datagram| async move {
+//!             // echo the payload back to the peer
+//!             datagram
+//!                 .reply(datagram.payload())
+//!                 .await
+//!                 .expect("reply to peer");
+//!
+//!             Ok::<_, std::convert::Infallible>(())
+//!         })
+//!         .await;
+//! }
+//! ```
+
+mod datagram;
+#[doc(inline)]
+pub use datagram::Datagram;
+
+mod listener;
+#[doc(inline)]
+pub use listener::{UdpListener, UdpListenerBuilder};
@@ -0,0 +1,152 @@
+//! Provides [`UdpSocketTracker`] which wraps a [`UdpSocket`]
+//! in order to track the number of bytes received and/or sent.
+//!
+//! It is the datagram counterpart of the `BytesRWTracker` found in `rama-net`,
+//! and similarly offers a [`UdpSocketTrackerHandle`] to get the number of bytes
+//! received and/or sent while the tracker itself is owned by a relay or other consumer.
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+#[derive(Debug)]
+/// A wrapper around a [`UdpSocket`] that tracks the number
+/// of bytes read (received) and/or written (sent).
+///
+/// Use [`UdpSocketTracker::handle`] to get a [`UdpSocketTrackerHandle`] in order
+/// to get the number of bytes read and/or written even though the [`UdpSocketTracker`]
+/// is consumed by a protocol consumer.
+pub struct UdpSocketTracker {
+    read: Arc<AtomicUsize>,
+    written: Arc<AtomicUsize>,
+    socket: UdpSocket,
+}
+
+impl UdpSocketTracker {
+    /// Create a new [`UdpSocketTracker`] that wraps the given [`UdpSocket`].
+    pub fn new(socket: UdpSocket) -> Self {
+        Self {
+            read: Arc::new(AtomicUsize::new(0)),
+            written: Arc::new(AtomicUsize::new(0)),
+            socket,
+        }
+    }
+
+    /// Get the number of bytes read (so far).
+    pub fn read(&self) -> usize {
+        self.read.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of bytes written (so far).
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::SeqCst)
+    }
+
+    /// Get a [`UdpSocketTrackerHandle`] that can be used to get the number of bytes
+    /// read and/or written even though the tracker is consumed by a protocol
+    /// consumer in a later stage.
+    pub fn handle(&self) -> UdpSocketTrackerHandle {
+        UdpSocketTrackerHandle {
+            read: self.read.clone(),
+            written: self.written.clone(),
+        }
+    }
+
+    /// Get a reference to the inner [`UdpSocket`].
+    ///
+    /// Bytes sent or received directly via this reference are not tracked.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Get the inner [`UdpSocket`].
+    /// Dropping the tracking info and capabilities for this socket.
+    ///
+    /// Any previously obtained [`UdpSocketTrackerHandle`] will no longer
+    /// be updated but will still report the number of bytes read and/or
+    /// written up to the point where this method was called.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the local address that this socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Receives a single datagram message on the socket,
+    /// returning the number of bytes read and the origin address.
+    ///
+    /// See [`UdpSocket::recv_from`] for more information.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (n, addr) = self.socket.recv_from(buf).await?;
+        self.read.fetch_add(n, Ordering::SeqCst);
+        Ok((n, addr))
+    }
+
+    /// Sends data on the socket to the given address,
+    /// returning the number of bytes written.
+    ///
+    /// See [`UdpSocket::send_to`] for more information.
+    pub async fn send_to(&self, buf: &[u8], target: impl ToSocketAddrs) -> io::Result<usize> {
+        let n = self.socket.send_to(buf, target).await?;
+        self.written.fetch_add(n, Ordering::SeqCst);
+        Ok(n)
+    }
+}
+
+/// A handle to a tracker that can be used to get the number of bytes
+/// read and/or written even though the tracker is consumed by a protocol
+/// consumer.
+#[derive(Debug, Clone)]
+pub struct UdpSocketTrackerHandle {
+    read: Arc<AtomicUsize>,
+    written: Arc<AtomicUsize>,
+}
+
+impl UdpSocketTrackerHandle {
+    /// Get the number of bytes read (so far).
+    pub fn read(&self) -> usize {
+        self.read.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of bytes written (so far).
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_udp_socket_tracker() {
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let tracker = UdpSocketTracker::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let handle = tracker.handle();
+
+        tracker
+            .send_to(b"hello", peer.local_addr().unwrap())
+            .await
+            .unwrap();
+        let mut buf = [0u8; 16];
+        let (n, addr) = peer.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"hello");
+
+        peer.send_to(b"hi", addr).await.unwrap();
+        let (n, _) = tracker.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"hi");
+
+        assert_eq!(handle.read(), 2);
+        assert_eq!(handle.written(), 5);
+        assert_eq!(tracker.read(), 2);
+        assert_eq!(tracker.written(), 5);
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     error::{ErrorContext, OpaqueError},
     http::{
         header::{Entry, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+        service::client::{MultipartForm, Part},
         Body, Method, Request, Uri,
     },
 };
@@ -49,6 +50,18 @@ impl RequestArgsBuilder {
         }
     }
 
+    /// Create a new [`RequestArgsBuilder`], which expects Multipart form data.
+    ///
+    /// Multipart form data is also used when files are defined using `field@path`,
+    /// regardless of the content type that the builder expects.
+    pub const fn new_multipart() -> RequestArgsBuilder {
+        RequestArgsBuilder {
+            state: BuilderState::MethodOrUrl {
+                content_type: Some(ContentType::Multipart),
+            },
+        }
+    }
+
     /// parse a command line argument, the possible meaning
     /// depend on the current state of the builder, driven by the position of the argument.
     pub fn parse_arg(&mut self, arg: String) {
@@ -67,6 +80,7 @@ impl RequestArgsBuilder {
                         query: HashMap::new(),
                         headers: HashMap::new(),
                         body: HashMap::new(),
+                        files: Vec::new(),
                     })
                 }
             }
@@ -80,13 +94,15 @@ impl RequestArgsBuilder {
                 query: HashMap::new(),
                 headers: HashMap::new(),
                 body: HashMap::new(),
+                files: Vec::new(),
             }),
             BuilderState::Data {
                 ref mut query,
                 ref mut headers,
                 ref mut body,
+                ref mut files,
                 ..
-            } => match parse_arg_as_data(arg, query, headers, body) {
+            } => match parse_arg_as_data(arg, query, headers, body, files) {
                 Ok(_) => None,
                 Err(msg) => Some(BuilderState::Error {
                     message: msg,
@@ -128,6 +144,7 @@ impl RequestArgsBuilder {
                 query,
                 headers,
                 body,
+                files,
             } => {
                 let mut req = Request::builder();
 
@@ -184,7 +201,7 @@ impl RequestArgsBuilder {
                 match method {
                     Some(method) => req = req.method(method),
                     None => {
-                        if body.is_empty() {
+                        if body.is_empty() && files.is_empty() {
                             req = req.method(Method::GET);
                         } else {
                             req = req.method(Method::POST);
@@ -195,14 +212,19 @@ impl RequestArgsBuilder {
                     req = req.header(name, value);
                 }
 
-                if body.is_empty() {
+                if body.is_empty() && files.is_empty() {
                     return req
                         .body(Body::empty())
                         .map_err(OpaqueError::from_std)
                         .context("create request without body");
                 }
 
-                let ct = content_type.unwrap_or_else(|| {
+                let ct = if files.is_empty() {
+                    content_type
+                } else {
+                    Some(ContentType::Multipart)
+                };
+                let ct = ct.unwrap_or_else(|| {
                     match req
                         .headers_ref()
                         .and_then(|h| h.get(CONTENT_TYPE))
@@ -211,10 +233,36 @@ impl RequestArgsBuilder {
                         Some(cv) if cv.contains("application/x-www-form-urlencoded") => {
                             ContentType::Form
                         }
+                        Some(cv) if cv.contains("multipart/form-data") => ContentType::Multipart,
                         _ => ContentType::Json,
                     }
                 });
 
+                if ct == ContentType::Multipart {
+                    let mut form = MultipartForm::new();
+                    for (name, value) in body {
+                        let value = match value {
+                            Value::String(value) => value,
+                            value => value.to_string(),
+                        };
+                        form.add_text(name, value);
+                    }
+                    for (name, path) in files {
+                        let part = Part::file(&path)?;
+                        form.add_part(name, part);
+                    }
+                    // the boundary has to match the one of the form,
+                    // so the content type is always overwritten
+                    if let Some(headers) = req.headers_mut() {
+                        headers.remove(CONTENT_TYPE);
+                    }
+                    return req
+                        .header(CONTENT_TYPE, form.content_type())
+                        .body(form.into_body())
+                        .map_err(OpaqueError::from_std)
+                        .context("create request with multipart body");
+                }
+
                 let req = if req.headers_ref().is_none() {
                     let req = req.header(CONTENT_TYPE, ct.header_value());
                     if ct == ContentType::Json {
@@ -253,6 +301,7 @@ impl RequestArgsBuilder {
                         req.header(CONTENT_LENGTH, body.len().to_string())
                             .body(Body::from(body))
                     }
+                    ContentType::Multipart => unreachable!("multipart body is created above"),
                 }
                 .map_err(OpaqueError::from_std)
                 .context("create request with body")
@@ -266,14 +315,21 @@ fn parse_arg_as_data(
     query: &mut HashMap<String, Vec<String>>,
     headers: &mut HashMap<String, String>,
     body: &mut HashMap<String, Value>,
+    files: &mut Vec<(String, String)>,
 ) -> Result<(), String> {
     let mut state = DataParseArgState::None;
-    for (i, c) in arg.chars().enumerate() {
+    for (i, c) in arg.char_indices() {
         match state {
             DataParseArgState::None => match c {
                 '\\' => state = DataParseArgState::Escaped,
                 '=' => state = DataParseArgState::Equal,
                 ':' => state = DataParseArgState::Colon,
+                '@' => {
+                    // @
+                    let (name, path) = arg.split_at(i);
+                    files.push((name.to_owned(), path[1..].to_owned()));
+                    break;
+                }
                 _ => (),
             },
             DataParseArgState::Escaped => {
@@ -370,6 +426,7 @@ enum DataParseArgState {
 enum ContentType {
     Json,
     Form,
+    Multipart,
 }
 
 impl ContentType {
@@ -377,6 +434,7 @@ impl ContentType {
         HeaderValue::from_static(match self {
             ContentType::Json => "application/json",
             ContentType::Form => "application/x-www-form-urlencoded",
+            ContentType::Multipart => "multipart/form-data",
         })
     }
 }
@@ -397,6 +455,7 @@ enum BuilderState {
         query: HashMap<String, Vec<String>>,
         headers: HashMap<String, String>,
         body: HashMap<String, Value>,
+        files: Vec<(String, String)>,
     },
     Error {
         message: String,
@@ -407,7 +466,7 @@ enum BuilderState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::http::io::write_http_request;
+    use crate::http::{dep::http_body_util::BodyExt, io::write_http_request};
 
     #[test]
     fn test_parse_arg_as_method() {
@@ -581,6 +640,59 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_request_args_builder_multipart_happy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.txt");
+        std::fs::write(&path, "hello").unwrap();
+
+        for (mut builder, args, expected_parts) in [
+            (
+                RequestArgsBuilder::new(),
+                vec![
+                    "example.com/foo".to_owned(),
+                    "x-a:1".to_owned(),
+                    format!("file@{}", path.display()),
+                ],
+                vec![
+                    "Content-Disposition: form-data; name=\"file\"; filename=\"hello.txt\"\r\ncontent-type: text/plain\r\n\r\nhello\r\n",
+                ],
+            ),
+            (
+                RequestArgsBuilder::new_multipart(),
+                vec![
+                    "example.com/foo".to_owned(),
+                    "a=b".to_owned(),
+                    "n:=42".to_owned(),
+                ],
+                vec![
+                    "Content-Disposition: form-data; name=\"a\"\r\n\r\nb\r\n",
+                    "Content-Disposition: form-data; name=\"n\"\r\n\r\n42\r\n",
+                ],
+            ),
+        ] {
+            for arg in args {
+                builder.parse_arg(arg);
+            }
+            let request = builder.build().unwrap();
+            assert_eq!(request.method(), Method::POST);
+            let content_type = request.headers()[CONTENT_TYPE].to_str().unwrap().to_owned();
+            let boundary = content_type
+                .strip_prefix("multipart/form-data; boundary=")
+                .unwrap();
+
+            let body = request.into_body().collect().await.unwrap().to_bytes();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            assert!(body.ends_with(&format!("--{boundary}--\r\n")));
+            for part in expected_parts {
+                assert!(
+                    body.contains(&format!("--{boundary}\r\n{part}")),
+                    "part {part:?} not found in body {body:?}"
+                );
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_request_args_builder_error() {
         for test in [
@@ -588,6 +700,7 @@ mod tests {
             vec!["invalid url"],
             vec!["get"],
             vec!["get", "invalid url"],
+            vec![":8080", "file@/this/file/does/not/exist"],
         ] {
             let mut builder = RequestArgsBuilder::new();
             for arg in test {
@@ -5,6 +5,7 @@ use crate::http::Version;
 use crate::tls::boring::dep::boring::pkey::PKey;
 use crate::tls::boring::dep::boring::x509::X509;
 use crate::tls::boring::server::ServerConfig;
+use crate::tls::keylog::KeyLogIntent;
 use crate::tls::types::ApplicationProtocol;
 use base64::Engine;
 
@@ -70,9 +71,7 @@ impl TlsServerCertKeyPair {
         let mut server_config = ServerConfig::new(key, ca_cert_chain);
 
         // support key logging
-        if let Ok(keylog_file) = std::env::var("SSLKEYLOGFILE") {
-            server_config.keylog_filename = Some(keylog_file);
-        }
+        server_config.set_keylog_intent(KeyLogIntent::Environment);
 
         // set ALPN protocols
         server_config.alpn_protocols = match self.http_version {
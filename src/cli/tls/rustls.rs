@@ -2,8 +2,9 @@
 
 use crate::error::BoxError;
 use crate::http::Version;
+use crate::tls::keylog::KeyLogIntent;
 use crate::tls::rustls::dep::pemfile;
-use crate::tls::rustls::dep::rustls::{KeyLogFile, ServerConfig};
+use crate::tls::rustls::dep::rustls::ServerConfig;
 use base64::Engine;
 use std::io::BufReader;
 use std::sync::Arc;
@@ -74,8 +75,8 @@ impl TlsServerCertKeyPair {
             .with_single_cert(certs, key)?;
 
         // support key logging
-        if std::env::var("SSLKEYLOGFILE").is_ok() {
-            server_config.key_log = Arc::new(KeyLogFile::new());
+        if let Some(writer) = KeyLogIntent::Environment.writer()? {
+            server_config.key_log = Arc::new(writer);
         }
 
         // set ALPN protocols
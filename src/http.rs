@@ -27,4 +27,4 @@ pub mod layer {
 
 #[cfg(feature = "http-full")]
 #[doc(inline)]
-pub use ::rama_http_backend::{client, server};
+pub use ::rama_http_backend::{client, server, ws};
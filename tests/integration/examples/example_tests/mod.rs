@@ -14,6 +14,7 @@ mod http_service_hello;
 mod http_service_match;
 mod http_user_agent_classifier;
 mod http_web_service_dir_and_api;
+mod socks5_connect_proxy;
 mod tcp_listener_hello;
 mod tcp_listener_layers;
 